@@ -0,0 +1,121 @@
+//! Pipeline benchmarks: parse, layout (including the constraint solver),
+//! connection routing and SVG rendering, each measured on its own
+//!
+//! Run with `cargo bench`; `agent-illustrator --profile FILE` gives the same
+//! breakdown for a single document.
+
+use agent_illustrator::layout::{self, LayoutConfig, LayoutResult};
+use agent_illustrator::{
+    parse, render_svg, resolve_templates, Document, SvgConfig, TemplateRegistry,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+/// Corpora: example diagrams plus a generated document heavy on constraints
+fn corpora() -> Vec<(&'static str, String)> {
+    vec![
+        (
+            "feedback-loops",
+            include_str!("../examples/feedback-loops.ail").to_string(),
+        ),
+        (
+            "agentic-loop",
+            include_str!("../examples/agentic-loop.ail").to_string(),
+        ),
+        (
+            "railway-templated",
+            include_str!("../examples/railway-topology-templated.ail").to_string(),
+        ),
+        ("constraint-chain", constraint_chain(100)),
+    ]
+}
+
+/// `n` rectangles, each constrained right of the previous one and connected
+/// to it, plus template instances with constraints of their own
+fn constraint_chain(n: usize) -> String {
+    let mut source = String::from(
+        "template \"card\" {\n    rect body [width: 60, height: 30]\n    rect tag [width: 20, height: 10]\n    constrain tag.left = body.left + 4\n    constrain tag.top = body.bottom + 2\n}\n",
+    );
+    for i in 0..n {
+        source.push_str(&format!("rect r{} [width: 40, height: 20]\n", i));
+    }
+    for i in 0..n / 4 {
+        source.push_str(&format!("card c{}\n", i));
+    }
+    for i in 1..n {
+        source.push_str(&format!("constrain r{}.left = r{}.right + 10\n", i, i - 1));
+        source.push_str(&format!("r{} -> r{}\n", i - 1, i));
+    }
+    source
+}
+
+fn resolved(source: &str) -> Document {
+    let doc = parse(source).expect("corpus parses");
+    resolve_templates(doc, &mut TemplateRegistry::new()).expect("corpus resolves")
+}
+
+/// Everything before routing, as the render pipeline does it
+fn laid_out(doc: &Document, config: &LayoutConfig) -> LayoutResult {
+    let mut result = layout::compute(doc, config).expect("corpus lays out");
+    layout::resolve_constrain_statements(&mut result, doc, config).expect("constraints solve");
+    layout::resolve_constraints(&mut result, doc, None).expect("constraints resolve");
+    layout::place_notes(&mut result, doc, config).expect("notes place");
+    layout::place_titles_and_legends(&mut result, doc, config).expect("titles place");
+    result
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for (name, source) in corpora() {
+        group.bench_function(name, |b| b.iter(|| parse(&source)));
+    }
+    group.finish();
+}
+
+fn bench_layout(c: &mut Criterion) {
+    let config = LayoutConfig::default();
+    let mut group = c.benchmark_group("layout");
+    for (name, source) in corpora() {
+        let doc = resolved(&source);
+        group.bench_function(name, |b| b.iter(|| laid_out(&doc, &config)));
+    }
+    group.finish();
+}
+
+fn bench_routing(c: &mut Criterion) {
+    let config = LayoutConfig::default();
+    let mut group = c.benchmark_group("routing");
+    for (name, source) in corpora() {
+        let doc = resolved(&source);
+        let result = laid_out(&doc, &config);
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || result.clone(),
+                |mut result| layout::route_connections(&mut result, &doc),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_render(c: &mut Criterion) {
+    let config = LayoutConfig::default();
+    let svg_config = SvgConfig::default();
+    let mut group = c.benchmark_group("render");
+    for (name, source) in corpora() {
+        let doc = resolved(&source);
+        let mut result = laid_out(&doc, &config);
+        layout::route_connections(&mut result, &doc).expect("connections route");
+        group.bench_function(name, |b| b.iter(|| render_svg(&result, &svg_config)));
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_parse,
+    bench_layout,
+    bench_routing,
+    bench_render
+);
+criterion_main!(benches);
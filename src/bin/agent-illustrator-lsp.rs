@@ -0,0 +1,274 @@
+//! Language server for Agent Illustrator documents
+//!
+//! Speaks LSP over stdio: diagnostics on open/change, go-to-definition for
+//! element and template names, hover with computed bounds, and completion of
+//! modifier keys. The queries themselves live in `agent_illustrator::ide`;
+//! this binary only translates between byte offsets and LSP positions.
+//!
+//! Build with `cargo build --features lsp`.
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use agent_illustrator::{ide, RenderConfig, Severity};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as _};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents,
+    HoverParams, HoverProviderCapability, Location, MarkupContent, MarkupKind, NumberOrString,
+    OneOf, Position, PublishDiagnosticsParams, Range, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+fn main() -> Result<(), BoxError> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["[".to_string(), ",".to_string()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+    // The server owns the connection so the writer thread can finish
+    Server::default().run(connection)?;
+    io_threads.join()?;
+    Ok(())
+}
+
+/// Open documents by URI
+#[derive(Default)]
+struct Server {
+    documents: HashMap<Url, String>,
+}
+
+impl Server {
+    fn run(&mut self, connection: Connection) -> Result<(), BoxError> {
+        for message in &connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(request);
+                    connection.sender.send(Message::Response(response))?;
+                }
+                Message::Notification(notification) => {
+                    if let Some(uri) = self.handle_notification(notification)? {
+                        let params = PublishDiagnosticsParams {
+                            diagnostics: self.diagnostics(&uri),
+                            uri,
+                            version: None,
+                        };
+                        connection
+                            .sender
+                            .send(Message::Notification(Notification::new(
+                                PublishDiagnostics::METHOD.to_string(),
+                                params,
+                            )))?;
+                    }
+                }
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Update the open documents; returns the document whose diagnostics changed
+    fn handle_notification(&mut self, notification: Notification) -> Result<Option<Url>, BoxError> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                self.documents
+                    .insert(uri.clone(), params.text_document.text);
+                Ok(Some(uri))
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                // Full sync: the last change holds the whole document
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.documents.insert(uri.clone(), change.text);
+                }
+                Ok(Some(uri))
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                self.documents.remove(&params.text_document.uri);
+                Ok(None)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn handle_request(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            GotoDefinition::METHOD => self.definition(request.params).and_then(to_json),
+            HoverRequest::METHOD => self.hover(request.params).and_then(to_json),
+            Completion::METHOD => self.completion(request.params).and_then(to_json),
+            method => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unsupported request {}", method),
+                )
+            }
+        };
+        match result {
+            Ok(value) => Response::new_ok(id, value),
+            Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+        }
+    }
+
+    fn definition(
+        &self,
+        params: serde_json::Value,
+    ) -> Result<Option<GotoDefinitionResponse>, BoxError> {
+        let params: GotoDefinitionParams = serde_json::from_value(params)?;
+        let position = params.text_document_position_params;
+        let uri = position.text_document.uri;
+        let Some(source) = self.documents.get(&uri) else {
+            return Ok(None);
+        };
+        let offset = position_to_offset(source, position.position);
+        Ok(ide::definition(source, offset).map(|span| {
+            GotoDefinitionResponse::Scalar(Location::new(uri.clone(), span_to_range(source, span)))
+        }))
+    }
+
+    fn hover(&self, params: serde_json::Value) -> Result<Option<Hover>, BoxError> {
+        let params: HoverParams = serde_json::from_value(params)?;
+        let position = params.text_document_position_params;
+        let uri = position.text_document.uri;
+        let Some(source) = self.documents.get(&uri) else {
+            return Ok(None);
+        };
+        let offset = position_to_offset(source, position.position);
+        let Some(text) = ide::hover(source, offset, &render_config(&uri)) else {
+            return Ok(None);
+        };
+        let range = ide::word_at(source, offset).map(|(span, _)| span_to_range(source, span));
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::PlainText,
+                value: text,
+            }),
+            range,
+        }))
+    }
+
+    fn completion(
+        &self,
+        params: serde_json::Value,
+    ) -> Result<Option<CompletionResponse>, BoxError> {
+        let params: CompletionParams = serde_json::from_value(params)?;
+        let position = params.text_document_position;
+        let Some(source) = self.documents.get(&position.text_document.uri) else {
+            return Ok(None);
+        };
+        let offset = position_to_offset(source, position.position);
+        let items = ide::complete_modifier_keys(source, offset)
+            .into_iter()
+            .map(|key| CompletionItem {
+                label: key.to_string(),
+                kind: Some(CompletionItemKind::PROPERTY),
+                insert_text: Some(format!("{}: ", key)),
+                ..Default::default()
+            })
+            .collect();
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    fn diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+        let Some(source) = self.documents.get(uri) else {
+            return vec![];
+        };
+        ide::diagnostics(source, &render_config(uri))
+            .into_iter()
+            .map(|d| Diagnostic {
+                range: span_to_range(source, d.span),
+                severity: Some(match d.severity {
+                    Severity::Error => DiagnosticSeverity::ERROR,
+                    Severity::Warning => DiagnosticSeverity::WARNING,
+                    Severity::Info => DiagnosticSeverity::INFORMATION,
+                }),
+                code: Some(NumberOrString::String(d.code.to_string())),
+                source: Some("agent-illustrator".to_string()),
+                message: match d.suggestion {
+                    Some(suggestion) => format!("{} ({})", d.message, suggestion),
+                    None => d.message,
+                },
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+/// Resolve template files relative to the document, like the CLI does
+fn render_config(uri: &Url) -> RenderConfig {
+    let config = RenderConfig::new();
+    match uri
+        .to_file_path()
+        .ok()
+        .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+    {
+        Some(dir) => config.with_template_base_path(dir),
+        None => config,
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: T) -> Result<serde_json::Value, BoxError> {
+    Ok(serde_json::to_value(value)?)
+}
+
+/// Byte offset of an LSP position (line, UTF-16 column)
+fn position_to_offset(source: &str, position: Position) -> usize {
+    let mut offset = 0;
+    for (i, line) in source.split_inclusive('\n').enumerate() {
+        if i == position.line as usize {
+            let mut units = 0;
+            for (byte, c) in line.char_indices() {
+                if units >= position.character as usize || c == '\n' {
+                    return offset + byte;
+                }
+                units += c.len_utf16();
+            }
+            return offset + line.len();
+        }
+        offset += line.len();
+    }
+    source.len()
+}
+
+/// LSP position (line, UTF-16 column) of a byte offset
+fn offset_to_position(source: &str, offset: usize) -> Position {
+    let before = source.get(..offset).unwrap_or(source);
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+fn span_to_range(source: &str, span: std::ops::Range<usize>) -> Range {
+    Range::new(
+        offset_to_position(source, span.start),
+        offset_to_position(source, span.end),
+    )
+}
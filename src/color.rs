@@ -0,0 +1,273 @@
+//! Color functions: `darken(accent, 20%)`, `lighten(...)`, `alpha(accent, 0.5)`
+//!
+//! The functions are evaluated before layout against the stylesheet palette,
+//! so their results are concrete colors rather than CSS variable references:
+//! a later CSS override of the palette does not reach them.
+
+use crate::parser::ast::{ColorValue, StyleValue};
+use crate::parser::Spanned;
+use crate::stylesheet::{validate_color_token, Stylesheet};
+
+/// Names of the color functions evaluated here
+pub(crate) const COLOR_FUNCTIONS: &[&str] = &["darken", "lighten", "alpha"];
+
+/// Named colors the functions accept (the CSS 2.1 basic colors)
+const NAMED_COLORS: &[(&str, &str)] = &[
+    ("aqua", "#00ffff"),
+    ("black", "#000000"),
+    ("blue", "#0000ff"),
+    ("fuchsia", "#ff00ff"),
+    ("gray", "#808080"),
+    ("grey", "#808080"),
+    ("green", "#008000"),
+    ("lime", "#00ff00"),
+    ("maroon", "#800000"),
+    ("navy", "#000080"),
+    ("olive", "#808000"),
+    ("orange", "#ffa500"),
+    ("purple", "#800080"),
+    ("red", "#ff0000"),
+    ("silver", "#c0c0c0"),
+    ("teal", "#008080"),
+    ("white", "#ffffff"),
+    ("yellow", "#ffff00"),
+];
+
+/// An sRGB color with alpha, channels in 0..=1
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rgba {
+    r: f64,
+    g: f64,
+    b: f64,
+    a: f64,
+}
+
+impl Rgba {
+    /// Parse `#rgb`, `#rrggbb`, `#rrggbbaa` or a basic named color
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if let Some((_, hex)) = NAMED_COLORS.iter().find(|(name, _)| *name == s) {
+            return Self::parse(hex);
+        }
+        let hex = s.strip_prefix('#')?;
+        let digits: Vec<u8> = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()?;
+        let channel = |hi: u8, lo: u8| f64::from(hi * 16 + lo) / 255.0;
+        match digits.as_slice() {
+            [r, g, b] => Some(Self {
+                r: channel(*r, *r),
+                g: channel(*g, *g),
+                b: channel(*b, *b),
+                a: 1.0,
+            }),
+            [r1, r2, g1, g2, b1, b2, rest @ ..] if rest.is_empty() || rest.len() == 2 => {
+                Some(Self {
+                    r: channel(*r1, *r2),
+                    g: channel(*g1, *g2),
+                    b: channel(*b1, *b2),
+                    a: match rest {
+                        [a1, a2] => channel(*a1, *a2),
+                        _ => 1.0,
+                    },
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// `#rrggbb`, or `#rrggbbaa` when translucent
+    fn to_hex(self) -> String {
+        let byte = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let mut hex = format!(
+            "#{:02x}{:02x}{:02x}",
+            byte(self.r),
+            byte(self.g),
+            byte(self.b)
+        );
+        if self.a < 1.0 {
+            hex.push_str(&format!("{:02x}", byte(self.a)));
+        }
+        hex
+    }
+
+    /// Hue (0..1), saturation and lightness
+    fn to_hsl(self) -> (f64, f64, f64) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let l = (max + min) / 2.0;
+        let d = max - min;
+        if d == 0.0 {
+            return (0.0, 0.0, l);
+        }
+        let s = d / (1.0 - (2.0 * l - 1.0).abs());
+        let h = if max == self.r {
+            ((self.g - self.b) / d).rem_euclid(6.0)
+        } else if max == self.g {
+            (self.b - self.r) / d + 2.0
+        } else {
+            (self.r - self.g) / d + 4.0
+        };
+        (h / 6.0, s, l)
+    }
+
+    fn from_hsl(h: f64, s: f64, l: f64, a: f64) -> Self {
+        let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        let h6 = h * 6.0;
+        let x = c * (1.0 - (h6.rem_euclid(2.0) - 1.0).abs());
+        let (r, g, b) = match h6 as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        let m = l - c / 2.0;
+        Self {
+            r: r + m,
+            g: g + m,
+            b: b + m,
+            a,
+        }
+    }
+
+    /// Shift the HSL lightness by `amount` (-1..1)
+    fn adjust_lightness(self, amount: f64) -> Self {
+        let (h, s, l) = self.to_hsl();
+        Self::from_hsl(h, s, (l + amount).clamp(0.0, 1.0), self.a)
+    }
+}
+
+/// Evaluate a color function call to a hex color
+///
+/// Returns `Ok(None)` for functions that are not color functions. Arguments
+/// that are themselves color functions must already be evaluated.
+pub(crate) fn evaluate(
+    name: &str,
+    args: &[Spanned<StyleValue>],
+    stylesheet: &Stylesheet,
+) -> Result<Option<String>, String> {
+    if !COLOR_FUNCTIONS.contains(&name) {
+        return Ok(None);
+    }
+    let [color, amount] = args else {
+        return Err(format!(
+            "{}() takes a color and an amount: {}(accent, {})",
+            name,
+            name,
+            if name == "alpha" { "0.5" } else { "20%" }
+        ));
+    };
+    let color = color_arg(&color.node, stylesheet)?;
+    let amount = match &amount.node {
+        StyleValue::Number {
+            value,
+            unit: Some(unit),
+        } if unit == "%" => value / 100.0,
+        StyleValue::Number { value, unit: None } => *value,
+        _ => {
+            return Err(format!(
+                "{}() amount must be a number or percentage, e.g. 20% or 0.2",
+                name
+            ))
+        }
+    };
+    let result = match name {
+        "darken" => color.adjust_lightness(-amount),
+        "lighten" => color.adjust_lightness(amount),
+        _ => Rgba {
+            a: amount.clamp(0.0, 1.0),
+            ..color
+        },
+    };
+    Ok(Some(result.to_hex()))
+}
+
+/// The concrete color of a function argument, resolving palette tokens
+fn color_arg(value: &StyleValue, stylesheet: &Stylesheet) -> Result<Rgba, String> {
+    let css = match value {
+        StyleValue::Color(color @ ColorValue::Symbolic { .. }) => {
+            let token = color.token_string().unwrap_or_default();
+            validate_color_token(&token, stylesheet)?;
+            stylesheet.resolve_or_default(&token)
+        }
+        StyleValue::Color(ColorValue::Hex(s) | ColorValue::Named(s))
+        | StyleValue::Keyword(s)
+        | StyleValue::String(s) => s.clone(),
+        StyleValue::Identifier(id) => id.0.clone(),
+        _ => return Err("Color functions take a color as their first argument".to_string()),
+    };
+    Rgba::parse(&css).ok_or_else(|| {
+        format!(
+            "Cannot compute with color '{}'; use a hex color, a palette color or a basic named color",
+            css
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(name: &str, color: StyleValue, amount: StyleValue) -> Result<Option<String>, String> {
+        let args = vec![Spanned::new(color, 0..0), Spanned::new(amount, 0..0)];
+        evaluate(name, &args, &Stylesheet::empty())
+    }
+
+    fn hex(s: &str) -> StyleValue {
+        StyleValue::Color(ColorValue::Hex(s.to_string()))
+    }
+
+    fn percent(value: f64) -> StyleValue {
+        StyleValue::Number {
+            value,
+            unit: Some("%".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_parse_and_format() {
+        assert_eq!(Rgba::parse("#f00").unwrap().to_hex(), "#ff0000");
+        assert_eq!(Rgba::parse("teal").unwrap().to_hex(), "#008080");
+        assert_eq!(Rgba::parse("#11223380").unwrap().to_hex(), "#11223380");
+        assert!(Rgba::parse("steelblue").is_none());
+        assert!(Rgba::parse("#12345").is_none());
+    }
+
+    #[test]
+    fn test_hsl_round_trip() {
+        for color in ["#3366cc", "#ff8800", "#808080", "#00ff7f"] {
+            let rgba = Rgba::parse(color).unwrap();
+            let (h, s, l) = rgba.to_hsl();
+            assert_eq!(Rgba::from_hsl(h, s, l, 1.0).to_hex(), color);
+        }
+    }
+
+    #[test]
+    fn test_color_functions() {
+        assert_eq!(
+            eval("darken", hex("#808080"), percent(20.0)),
+            Ok(Some("#4d4d4d".to_string()))
+        );
+        let plain = StyleValue::Number {
+            value: 0.2,
+            unit: None,
+        };
+        assert_eq!(
+            eval("lighten", hex("#808080"), plain),
+            Ok(Some("#b3b3b3".to_string()))
+        );
+        assert_eq!(
+            eval("alpha", hex("#ff0000"), percent(50.0)),
+            Ok(Some("#ff000080".to_string()))
+        );
+        assert_eq!(
+            eval("darken", hex("#000"), percent(10.0)),
+            Ok(Some("#000000".to_string()))
+        );
+        assert_eq!(eval("fade", hex("#000"), percent(10.0)), Ok(None));
+        assert!(eval("darken", hex("#000"), hex("#fff")).is_err());
+    }
+}
@@ -0,0 +1,270 @@
+//! Machine-readable diagnostics
+//!
+//! [`parse_with_diagnostics`] reports problems as [`Diagnostic`] values with
+//! a stable code, byte span, line/column and (when one can be guessed) a
+//! suggested fix. They serialize to JSON, so an agent can act on them
+//! without scraping [`ParseError::format`] output.
+
+use serde::Serialize;
+
+use crate::error::{ParseError, Span};
+use crate::parser::{parse_recovering, Document};
+use crate::{error_span, RenderError};
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+/// A problem found in the source, with enough context to fix it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable kind of problem, e.g. `unexpected-token`
+    pub code: &'static str,
+    pub message: String,
+    /// Byte range in the source
+    pub span: Span,
+    /// 1-based line of the span start
+    pub line: usize,
+    /// 1-based column (in characters) of the span start
+    pub column: usize,
+    /// What the parser would have accepted at this point
+    pub expected: Vec<String>,
+    /// Likely fix, when one can be guessed
+    pub suggestion: Option<String>,
+}
+
+/// Statement keywords a misspelled word is compared against
+const KEYWORDS: &[&str] = &[
+    "rect",
+    "circle",
+    "ellipse",
+    "polygon",
+    "line",
+    "icon",
+    "image",
+    "text",
+    "path",
+    "row",
+    "col",
+    "grid",
+    "stack",
+    "flow",
+    "group",
+    "layer",
+    "template",
+    "export",
+    "embed",
+    "import",
+    "anchor",
+    "keyframe",
+    "constrain",
+    "note",
+    "repeat",
+    "if",
+    "else",
+    "let",
+    "slot",
+];
+
+/// Parse DSL source, returning the document and all problems as structured
+/// diagnostics
+///
+/// The parser recovers at statement boundaries, so the document holds every
+/// statement that parsed even when some did not.
+///
+/// ```rust
+/// use agent_illustrator::{parse_with_diagnostics, Severity};
+///
+/// let (doc, diagnostics) = parse_with_diagnostics("row {\n  rectt\n}");
+/// assert!(doc.is_some());
+/// assert_eq!(diagnostics[0].severity, Severity::Error);
+/// assert_eq!(diagnostics[0].line, 3);
+/// assert_eq!(diagnostics[0].suggestion.as_deref(), Some("did you mean 'rect'?"));
+/// ```
+pub fn parse_with_diagnostics(source: &str) -> (Option<Document>, Vec<Diagnostic>) {
+    let (doc, errors) = parse_recovering(source);
+    let diagnostics = errors
+        .iter()
+        .map(|e| Diagnostic::from_parse_error(e, source))
+        .collect();
+    (doc, diagnostics)
+}
+
+impl Diagnostic {
+    /// A diagnostic without expected tokens or suggestion
+    pub fn new(
+        severity: Severity,
+        code: &'static str,
+        message: impl Into<String>,
+        span: Span,
+        source: &str,
+    ) -> Self {
+        let (line, column) = line_col(source, span.start);
+        Diagnostic {
+            severity,
+            code,
+            message: message.into(),
+            span,
+            line,
+            column,
+            expected: vec![],
+            suggestion: None,
+        }
+    }
+
+    /// Diagnostics for a failed render: one per syntax error, otherwise one
+    /// `render-error` at the failing statement (or the start of the source)
+    pub fn from_render_error(err: &RenderError, source: &str) -> Vec<Self> {
+        match err {
+            RenderError::Parse(errors) => errors
+                .iter()
+                .map(|e| Diagnostic::from_parse_error(e, source))
+                .collect(),
+            _ => vec![Diagnostic::new(
+                Severity::Error,
+                "render-error",
+                err.to_string(),
+                error_span(err).unwrap_or(0..0),
+                source,
+            )],
+        }
+    }
+
+    pub fn from_parse_error(err: &ParseError, source: &str) -> Self {
+        let ParseError::Syntax {
+            span,
+            message,
+            expected,
+            code,
+        } = err;
+        let (line, column) = line_col(source, span.start);
+        let found = source.get(span.clone()).unwrap_or_default();
+        Diagnostic {
+            severity: Severity::Error,
+            code,
+            message: message.clone(),
+            span: span.clone(),
+            line,
+            column,
+            expected: expected.clone(),
+            suggestion: suggest(
+                code,
+                found,
+                &source[..span.start.min(source.len())],
+                expected,
+            ),
+        }
+    }
+}
+
+fn suggest(code: &str, found: &str, before: &str, expected: &[String]) -> Option<String> {
+    match code {
+        "reserved-keyword" => Some(format!("rename it, e.g. '{}_box'", found)),
+        "unexpected-token" => {
+            // A misspelled keyword is read as a name, so the error usually
+            // lands on the token after it
+            let previous = before
+                .trim_end()
+                .rsplit(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .next()
+                .unwrap_or_default();
+            let keyword = [found, previous].into_iter().find_map(similar_keyword);
+            match (keyword, expected) {
+                (Some(kw), _) => Some(format!("did you mean '{}'?", kw)),
+                (None, [only]) => Some(format!("insert {} before '{}'", only, found)),
+                _ => None,
+            }
+        }
+        "unexpected-end" => {
+            let unclosed = |open: char, close: char| {
+                before.matches(open).count() > before.matches(close).count()
+            };
+            if unclosed('{', '}') {
+                Some("add the missing '}'".to_string())
+            } else if unclosed('[', ']') {
+                Some("add the missing ']'".to_string())
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The statement keyword `word` is a likely misspelling of
+fn similar_keyword(word: &str) -> Option<&'static str> {
+    if word.is_empty() || KEYWORDS.contains(&word) {
+        return None;
+    }
+    // One typo in a short word, two in a longer one
+    let max_distance = if word.chars().count() <= 4 { 1 } else { 2 };
+    KEYWORDS
+        .iter()
+        .map(|kw| (crate::layout::levenshtein_distance(kw, word), *kw))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, kw)| kw)
+}
+
+/// 1-based line and column (in characters) of a byte offset
+pub fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = source.get(..offset).unwrap_or(source);
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostics_for_valid_source() {
+        let (doc, diagnostics) = parse_with_diagnostics("rect a\nrect b");
+        assert!(doc.is_some());
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_diagnostic_fields() {
+        let (_, diagnostics) = parse_with_diagnostics("row {\n  rect left\n}");
+        let d = &diagnostics[0];
+        assert_eq!(d.code, "reserved-keyword");
+        assert_eq!((d.line, d.column), (2, 8));
+        assert_eq!(d.suggestion.as_deref(), Some("rename it, e.g. 'left_box'"));
+
+        let (_, diagnostics) = parse_with_diagnostics("group g {\n  rect a [fill: red]\n");
+        let d = &diagnostics[0];
+        assert_eq!(d.code, "unexpected-end");
+        assert_eq!(d.suggestion.as_deref(), Some("add the missing '}'"));
+
+        let (_, diagnostics) = parse_with_diagnostics("rect a [fill red]");
+        assert_eq!(
+            diagnostics[0].suggestion.as_deref(),
+            Some("insert ':' before 'red'")
+        );
+    }
+
+    #[test]
+    fn test_diagnostic_json() {
+        let (_, diagnostics) = parse_with_diagnostics("col { circl }");
+        let json = serde_json::to_value(&diagnostics).unwrap();
+        assert_eq!(json[0]["severity"], "error");
+        assert_eq!(json[0]["code"], "unexpected-token");
+        assert_eq!(json[0]["span"]["start"], 12);
+        assert_eq!(json[0]["line"], 1);
+        assert_eq!(json[0]["suggestion"], "did you mean 'circle'?");
+    }
+
+    #[test]
+    fn test_line_col_counts_characters() {
+        assert_eq!(line_col("a\né b", 4), (2, 2));
+        assert_eq!(line_col("abc", 0), (1, 1));
+    }
+}
@@ -13,6 +13,9 @@ pub enum ParseError {
         span: Span,
         message: String,
         expected: Vec<String>,
+        /// Kind of problem: `reserved-keyword`, `unexpected-token`,
+        /// `unexpected-end` or `invalid-syntax`
+        code: &'static str,
     },
 }
 
@@ -25,6 +28,7 @@ impl ParseError {
                 span,
                 message,
                 expected,
+                ..
             } => {
                 let expected_str = if expected.is_empty() {
                     String::new()
@@ -68,6 +72,13 @@ impl<'a> From<chumsky::error::Rich<'a, crate::parser::lexer::Token>> for ParseEr
                 | Some(Token::VerticalCenter)
         );
 
+        let code = match err.reason() {
+            RichReason::ExpectedFound { .. } if is_reserved_keyword => "reserved-keyword",
+            RichReason::ExpectedFound { found: None, .. } => "unexpected-end",
+            RichReason::ExpectedFound { .. } => "unexpected-token",
+            RichReason::Custom(_) => "invalid-syntax",
+        };
+
         // Format the message based on the reason
         let message = match err.reason() {
             RichReason::ExpectedFound { found, .. } => {
@@ -120,6 +131,7 @@ impl<'a> From<chumsky::error::Rich<'a, crate::parser::lexer::Token>> for ParseEr
             span: err.span().into_range(),
             message,
             expected,
+            code,
         }
     }
 }
@@ -0,0 +1,217 @@
+//! Source formatter
+//!
+//! [`format`] rewrites AIL source in one canonical layout: one statement per
+//! line, four-space indentation, single spaces around operators and a fixed
+//! modifier order. Comments and single blank lines between statements are
+//! kept, so agent-generated diagrams can be committed with clean diffs.
+
+use crate::parser::ast::{Spanned, Statement, StyleModifier};
+use crate::parser::{key_text, parse, unparse_with_source};
+
+/// Modifier keys in the order the formatter writes them: geometry, layout,
+/// paint, text, connection routing, then everything else. Keys not listed
+/// keep their relative order after the listed ones.
+const MODIFIER_ORDER: &[&str] = &[
+    // Geometry
+    "x",
+    "y",
+    "width",
+    "height",
+    "size",
+    "radius",
+    "rotation",
+    "scale",
+    "flip",
+    "pinned",
+    "start",
+    "end",
+    // Layout
+    "gap",
+    "padding",
+    "padding_top",
+    "padding_right",
+    "padding_bottom",
+    "padding_left",
+    "align",
+    "self_align",
+    "max_width",
+    "unit_width",
+    "tick",
+    // Paint
+    "fill",
+    "stroke",
+    "stroke_width",
+    "stroke_dasharray",
+    "line_cap",
+    "line_join",
+    "opacity",
+    "corner_radius",
+    "depth",
+    // Text
+    "label",
+    "on",
+    "font_size",
+    "font_family",
+    "label_position",
+    "text_align",
+    "vertical_align",
+    "label_at",
+    "label_offset",
+    // Connections
+    "routing",
+    "via",
+    "crossings",
+    "marker_mid",
+    "seq",
+    "style",
+];
+
+/// Format AIL source
+///
+/// Source that does not parse is returned unchanged.
+///
+/// ```rust
+/// use agent_illustrator::format;
+///
+/// let source = "row   main{\n// the API\nrect api[label:\"API\",width:80]\n}";
+/// assert_eq!(
+///     format(source),
+///     "row main {\n    // the API\n    rect api [width: 80, label: \"API\"]\n}\n"
+/// );
+/// ```
+pub fn format(source: &str) -> String {
+    let Ok(mut doc) = parse(source) else {
+        return source.to_string();
+    };
+    sort_statements(&mut doc.statements);
+    unparse_with_source(&doc, source)
+}
+
+fn sort_statements(stmts: &mut [Spanned<Statement>]) {
+    for stmt in stmts {
+        sort_statement(&mut stmt.node);
+    }
+}
+
+fn sort_statement(stmt: &mut Statement) {
+    match stmt {
+        Statement::Shape(s) => sort_modifiers(&mut s.modifiers),
+        Statement::Connection(conns) => {
+            for conn in conns {
+                sort_modifiers(&mut conn.modifiers);
+            }
+        }
+        Statement::Layout(l) => {
+            sort_modifiers(&mut l.modifiers);
+            sort_statements(&mut l.children);
+        }
+        Statement::Group(g) => {
+            sort_modifiers(&mut g.modifiers);
+            sort_statements(&mut g.children);
+        }
+        Statement::Constraint(c) => sort_modifiers(&mut c.modifiers),
+        Statement::Canvas(c) => sort_modifiers(&mut c.modifiers),
+        Statement::Title(t) => sort_modifiers(&mut t.modifiers),
+        Statement::Animate(a) => sort_modifiers(&mut a.modifiers),
+        Statement::Legend(l) => {
+            sort_modifiers(&mut l.modifiers);
+            for entry in &mut l.entries {
+                sort_modifiers(&mut entry.node.modifiers);
+            }
+        }
+        Statement::Label(inner) => sort_statement(inner),
+        Statement::TemplateDecl(t) => {
+            if let Some(body) = &mut t.body {
+                sort_statements(body);
+            }
+        }
+        Statement::TemplateInstance(t) => sort_statements(&mut t.children),
+        Statement::Embed(e) => sort_modifiers(&mut e.modifiers),
+        Statement::Note(n) => {
+            sort_modifiers(&mut n.modifiers);
+            sort_statements(&mut n.children);
+        }
+        Statement::Repeat(r) => sort_statements(&mut r.body),
+        Statement::Step(s) => sort_statements(&mut s.children),
+        Statement::If(i) => {
+            sort_statements(&mut i.then_body);
+            sort_statements(&mut i.else_body);
+        }
+        Statement::Constrain(_)
+        | Statement::Import(_)
+        | Statement::Export(_)
+        | Statement::AnchorDecl(_)
+        | Statement::Keyframe(_)
+        | Statement::Let(_)
+        | Statement::Slot => {}
+    }
+}
+
+/// Stable sort, so repeated keys keep their order (the last one still wins)
+fn sort_modifiers(modifiers: &mut [Spanned<StyleModifier>]) {
+    modifiers.sort_by_key(|m| {
+        let key = key_text(&m.node.key.node);
+        MODIFIER_ORDER
+            .iter()
+            .position(|k| *k == key)
+            .unwrap_or(MODIFIER_ORDER.len())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_is_canonical_and_idempotent() {
+        let source = r#"// Architecture overview
+template "svc" {rect body[label:"Svc",fill:blue,width:100]}
+
+
+col main[gap:20]{
+  /* front end */
+  rect web [label: "Web", fill: red, x: 10]   // entry point
+        rect api
+  svc backend
+}
+web->api[label:"calls",routing:curved]
+"#;
+        let formatted = format(source);
+        assert_eq!(
+            formatted,
+            r#"// Architecture overview
+template "svc" {
+    rect body [width: 100, fill: blue, label: "Svc"]
+}
+
+col main [gap: 20] {
+    /* front end */
+    rect web [x: 10, fill: red, label: "Web"] // entry point
+    rect api
+    svc backend
+}
+web -> api [label: "calls", routing: curved]
+"#
+        );
+        assert_eq!(format(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_format_keeps_comments_in_place() {
+        let source = "row {\n  rect a\n  // last\n}\n// trailing\n";
+        assert_eq!(
+            format(source),
+            "row {\n    rect a\n    // last\n}\n// trailing\n"
+        );
+
+        // Comment markers inside strings are text
+        let source = "rect a [label: \"http://x /* y */\"]\n";
+        assert_eq!(format(source), source);
+    }
+
+    #[test]
+    fn test_format_leaves_invalid_source_alone() {
+        let source = "rect a [fill: ]\n  rect   b";
+        assert_eq!(format(source), source);
+    }
+}
@@ -0,0 +1,36 @@
+//! Render pipeline hooks
+//!
+//! A [`PipelineHooks`] implementation added with
+//! [`RenderConfig::with_hooks`](crate::RenderConfig::with_hooks) is called
+//! between the pipeline phases, so embedders can inspect or rewrite the
+//! document and its layout (inject a watermark, enforce naming policies)
+//! without forking the pipeline. Every callback defaults to doing nothing;
+//! returning an error aborts the render with it.
+
+use crate::layout::LayoutResult;
+use crate::parser::Document;
+use crate::RenderError;
+
+/// Callbacks run between the phases of the render pipeline, in this order
+pub trait PipelineHooks: std::fmt::Debug + Send + Sync {
+    /// The parsed document, before templates are resolved
+    fn after_parse(&self, _doc: &mut Document) -> Result<(), RenderError> {
+        Ok(())
+    }
+
+    /// The document with templates expanded, before layout
+    fn after_resolve(&self, _doc: &mut Document) -> Result<(), RenderError> {
+        Ok(())
+    }
+
+    /// The finished layout: constraints solved and connections routed.
+    /// Elements moved here keep their connections where they were.
+    fn after_layout(&self, _result: &mut LayoutResult) -> Result<(), RenderError> {
+        Ok(())
+    }
+
+    /// The layout about to be written as SVG (not called by `compute_layout`)
+    fn before_render(&self, _result: &mut LayoutResult) -> Result<(), RenderError> {
+        Ok(())
+    }
+}
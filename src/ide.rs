@@ -0,0 +1,294 @@
+//! Editor queries
+//!
+//! Position-based questions an editor asks about a document: where is this
+//! element declared, how big did it end up, which modifier keys fit here.
+//! Offsets and spans are byte ranges into the source; the
+//! `agent-illustrator-lsp` binary (feature `lsp`) translates them to the
+//! Language Server Protocol.
+
+use std::collections::HashMap;
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::layout::lint::LintSeverity;
+use crate::parser::ast::{Span, Spanned, Statement};
+use crate::{compute_layout, parse_with_diagnostics, render_with_lint, RenderConfig};
+
+/// Modifier keys offered for completion inside `[...]`
+pub const MODIFIER_KEYS: &[&str] = &[
+    "fill",
+    "stroke",
+    "stroke_width",
+    "stroke_dasharray",
+    "line_cap",
+    "line_join",
+    "marker_mid",
+    "opacity",
+    "label",
+    "on",
+    "initial",
+    "label_position",
+    "label_at",
+    "label_offset",
+    "font_size",
+    "font_family",
+    "text_align",
+    "vertical_align",
+    "class",
+    "role",
+    "size",
+    "width",
+    "height",
+    "x",
+    "y",
+    "gap",
+    "padding",
+    "padding_top",
+    "padding_right",
+    "padding_bottom",
+    "padding_left",
+    "align",
+    "self_align",
+    "corner_radius",
+    "depth",
+    "rotation",
+    "scale",
+    "flip",
+    "pinned",
+    "z",
+    "tooltip",
+    "description",
+    "detail",
+    "raw_svg",
+    "routing",
+    "via",
+    "from_anchor",
+    "from_offset",
+    "to_anchor",
+    "to_offset",
+    "crossings",
+    "seq",
+    "style",
+    "opacity_start",
+    "opacity_end",
+    "max_width",
+    "radius",
+    "start",
+    "end",
+    "unit_width",
+    "tick",
+    "orientation",
+    "scale",
+    "fit",
+    "property",
+    "from",
+    "to",
+    "duration",
+    "delay",
+    "repeat",
+];
+
+/// All problems in the source: syntax errors, then (if it parses) the first
+/// layout or template error and the lint warnings
+pub fn diagnostics(source: &str, config: &RenderConfig) -> Vec<Diagnostic> {
+    let (_, mut diagnostics) = parse_with_diagnostics(source);
+    if !diagnostics.is_empty() {
+        return diagnostics;
+    }
+    match render_with_lint(source, config.clone().with_lint(true)) {
+        Ok((_, warnings)) => {
+            for warning in warnings {
+                let severity = match warning.severity() {
+                    LintSeverity::Warning => Severity::Warning,
+                    LintSeverity::Info => Severity::Info,
+                };
+                diagnostics.push(Diagnostic::new(
+                    severity,
+                    "lint",
+                    format!("[{}] {}", warning.category, warning.message),
+                    warning.span.unwrap_or(0..0),
+                    source,
+                ));
+            }
+        }
+        Err(err) => diagnostics.extend(Diagnostic::from_render_error(&err, source)),
+    }
+    diagnostics
+}
+
+/// Identifier under (or just before) `offset`, with its span
+pub fn word_at(source: &str, offset: usize) -> Option<(Span, &str)> {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '$';
+    let offset = offset.min(source.len());
+    if !source.is_char_boundary(offset) {
+        return None;
+    }
+    let start = source[..offset]
+        .rfind(|c: char| !is_word(c))
+        .map_or(0, |i| i + 1);
+    let end = source[offset..]
+        .find(|c: char| !is_word(c))
+        .map_or(source.len(), |i| offset + i);
+    let word = &source[start..end];
+    let starts_ok = word
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$');
+    starts_ok.then_some((start..end, word))
+}
+
+/// Span of the name that declares the identifier at `offset`
+///
+/// Elements are searched first, then templates, so `card` in `card c1`
+/// jumps to `template "card"`.
+pub fn definition(source: &str, offset: usize) -> Option<Span> {
+    let (_, word) = word_at(source, offset)?;
+    let (doc, _) = crate::parser::parse_recovering(source);
+    let mut elements = HashMap::new();
+    let mut templates = HashMap::new();
+    collect_declarations(&doc?.statements, &mut elements, &mut templates);
+    elements.remove(word).or_else(|| templates.remove(word))
+}
+
+/// Hover text for the element at `offset`: its kind and computed bounds
+pub fn hover(source: &str, offset: usize, config: &RenderConfig) -> Option<String> {
+    let (_, word) = word_at(source, offset)?;
+    let decl = definition(source, offset)?;
+    let kind = statement_keyword(source, decl.start);
+    let result = compute_layout(source, config).ok();
+    let Some(element) = result.as_ref().and_then(|r| r.elements.get(word)) else {
+        return Some(format!("{} {}", kind, word));
+    };
+    let b = element.bounds;
+    Some(format!(
+        "{} {}\nx: {}, y: {}, width: {}, height: {}",
+        kind,
+        word,
+        round(b.x),
+        round(b.y),
+        round(b.width),
+        round(b.height)
+    ))
+}
+
+/// Modifier keys that complete the partial key before `offset`, or nothing
+/// when the cursor is not at a key position inside `[...]`
+pub fn complete_modifier_keys(source: &str, offset: usize) -> Vec<&'static str> {
+    let before = source.get(..offset).unwrap_or(source);
+    let Some(open) = before.rfind('[') else {
+        return vec![];
+    };
+    if before[open..].contains(']') {
+        return vec![];
+    }
+    // Only the text after the last separator matters, and it must still be a key
+    let current = before[open + 1..]
+        .rsplit(',')
+        .next()
+        .unwrap_or_default()
+        .trim_start();
+    if current.contains(|c: char| !(c.is_ascii_alphanumeric() || c == '_')) {
+        return vec![];
+    }
+    MODIFIER_KEYS
+        .iter()
+        .copied()
+        .filter(|key| key.starts_with(current))
+        .collect()
+}
+
+fn collect_declarations(
+    stmts: &[Spanned<Statement>],
+    elements: &mut HashMap<String, Span>,
+    templates: &mut HashMap<String, Span>,
+) {
+    for stmt in stmts {
+        let (name, children): (_, &[Spanned<Statement>]) = match &stmt.node {
+            Statement::Shape(s) => (s.name.as_ref(), &[]),
+            Statement::Layout(l) => (l.name.as_ref(), &l.children),
+            Statement::Group(g) => (g.name.as_ref(), &g.children),
+            Statement::TemplateInstance(t) => (Some(&t.instance_name), &t.children),
+            Statement::Note(n) => (None, &n.children),
+            Statement::TemplateDecl(t) => {
+                templates
+                    .entry(t.name.node.0.clone())
+                    .or_insert(t.name.span.clone());
+                continue;
+            }
+            _ => continue,
+        };
+        if let Some(name) = name {
+            elements
+                .entry(name.node.0.clone())
+                .or_insert(name.span.clone());
+        }
+        collect_declarations(children, elements, templates);
+    }
+}
+
+/// Word written before the name declared at `offset`, e.g. `rect` for
+/// `rect a [...]` or the template name for an instance
+fn statement_keyword(source: &str, offset: usize) -> &str {
+    source[..offset]
+        .split_whitespace()
+        .next_back()
+        .filter(|word| word.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or("element")
+}
+
+fn round(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str =
+        "template \"card\" { rect body }\nrow r {\n  rect a [fill: red]\n  card c1\n}\na -> c1";
+
+    #[test]
+    fn test_definition() {
+        let use_of_a = SOURCE.rfind("a -> c1").unwrap();
+        let decl = definition(SOURCE, use_of_a).unwrap();
+        assert_eq!(&SOURCE[decl.clone()], "a");
+        assert_eq!(decl.start, SOURCE.find("rect a").unwrap() + 5);
+
+        // Template names jump to the template declaration
+        let use_of_card = SOURCE.find("card c1").unwrap() + 1;
+        let decl = definition(SOURCE, use_of_card).unwrap();
+        assert_eq!(&SOURCE[decl], "\"card\"");
+
+        assert_eq!(definition(SOURCE, SOURCE.find("fill").unwrap()), None);
+    }
+
+    #[test]
+    fn test_hover_shows_bounds() {
+        let offset = SOURCE.rfind("a ->").unwrap();
+        let text = hover(SOURCE, offset, &RenderConfig::new()).unwrap();
+        assert!(text.starts_with("rect a\nx: "), "{}", text);
+        assert!(text.contains("width: 80, height: 30"), "{}", text);
+    }
+
+    #[test]
+    fn test_complete_modifier_keys() {
+        let source = "rect a [fill: red, str";
+        let keys = complete_modifier_keys(source, source.len());
+        assert_eq!(keys, ["stroke", "stroke_width", "stroke_dasharray"]);
+
+        // Not inside brackets, or at a value position
+        assert!(complete_modifier_keys("rect str", 8).is_empty());
+        assert!(complete_modifier_keys("rect a [fill: re", 16).is_empty());
+        assert!(complete_modifier_keys("rect a [fill: red] ", 19).is_empty());
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let d = diagnostics("rect a\na -> missing", &RenderConfig::new());
+        assert_eq!(d.len(), 1);
+        assert_eq!(d[0].code, "render-error");
+        assert_eq!((d[0].line, d[0].column), (2, 6));
+
+        let d = diagnostics("rect a [fill red]", &RenderConfig::new());
+        assert_eq!(d[0].code, "unexpected-token");
+    }
+}
@@ -0,0 +1,290 @@
+//! Element inventory for documentation
+//!
+//! Lists every named element of a (template-resolved) document with its type,
+//! label, enclosing group, tags (`class:` values) and connections, so a table
+//! can be published next to the rendered figure (`--emit inventory`).
+
+use crate::parser::ast::{
+    ConnectionDirection, Document, LayoutType, ShapeType, Spanned, Statement, StyleKey,
+    StyleModifier, StyleValue,
+};
+
+/// One row of the inventory
+#[derive(Debug, Clone, PartialEq)]
+pub struct InventoryEntry {
+    pub id: String,
+    /// Element type as written in the source (`rect`, `row`, `group`, ...)
+    pub kind: String,
+    pub label: Option<String>,
+    /// Nearest named enclosing container
+    pub group: Option<String>,
+    pub tags: Vec<String>,
+    /// Connections touching the element, e.g. `-> db` or `<- client`
+    pub links: Vec<String>,
+}
+
+/// Collect the named elements of a document, in document order
+pub fn collect(doc: &Document) -> Vec<InventoryEntry> {
+    let mut entries = vec![];
+    collect_statements(&doc.statements, None, &mut entries);
+
+    let mut connections = vec![];
+    collect_connections(&doc.statements, &mut connections);
+    for entry in &mut entries {
+        for (from, to, direction) in &connections {
+            let (arrow, other) = if *from == entry.id {
+                (direction_arrow(*direction, true), to)
+            } else if *to == entry.id {
+                (direction_arrow(*direction, false), from)
+            } else {
+                continue;
+            };
+            entry.links.push(format!("{} {}", arrow, other));
+        }
+    }
+    entries
+}
+
+fn collect_statements(
+    stmts: &[Spanned<Statement>],
+    group: Option<&str>,
+    entries: &mut Vec<InventoryEntry>,
+) {
+    for stmt in stmts {
+        collect_statement(&stmt.node, group, entries);
+    }
+}
+
+fn collect_statement(stmt: &Statement, group: Option<&str>, entries: &mut Vec<InventoryEntry>) {
+    let mut push = |name: &str, kind: String, modifiers: &[Spanned<StyleModifier>]| {
+        entries.push(InventoryEntry {
+            id: name.to_string(),
+            kind,
+            label: label_text(modifiers),
+            group: group.map(str::to_string),
+            tags: tags(modifiers),
+            links: vec![],
+        });
+    };
+
+    match stmt {
+        Statement::Shape(s) => {
+            if let Some(name) = &s.name {
+                push(&name.node.0, shape_kind(&s.shape_type.node), &s.modifiers);
+                if let ShapeType::Text { content } = &s.shape_type.node {
+                    if let Some(entry) = entries.last_mut() {
+                        entry.label.get_or_insert_with(|| content.clone());
+                    }
+                }
+            }
+        }
+        Statement::Layout(l) => {
+            let name = l.name.as_ref().map(|n| n.node.0.as_str());
+            if let Some(name) = name {
+                push(
+                    name,
+                    layout_kind(l.layout_type.node).to_string(),
+                    &l.modifiers,
+                );
+            }
+            collect_statements(&l.children, name.or(group), entries);
+        }
+        Statement::Group(g) => {
+            let name = g.name.as_ref().map(|n| n.node.0.as_str());
+            if let Some(name) = name {
+                let kind = if g.is_template_instance {
+                    "template"
+                } else {
+                    "group"
+                };
+                push(name, kind.to_string(), &g.modifiers);
+            }
+            collect_statements(&g.children, name.or(group), entries);
+        }
+        Statement::Label(inner) => collect_statement(inner, group, entries),
+        Statement::Note(note) => collect_statements(&note.children, group, entries),
+        _ => {}
+    }
+}
+
+fn collect_connections(
+    stmts: &[Spanned<Statement>],
+    out: &mut Vec<(String, String, ConnectionDirection)>,
+) {
+    for stmt in stmts {
+        match &stmt.node {
+            Statement::Connection(conns) => {
+                for conn in conns {
+                    out.push((
+                        conn.from.element.node.0.clone(),
+                        conn.to.element.node.0.clone(),
+                        conn.direction,
+                    ));
+                }
+            }
+            Statement::Layout(l) => collect_connections(&l.children, out),
+            Statement::Group(g) => collect_connections(&g.children, out),
+            _ => {}
+        }
+    }
+}
+
+/// Arrow as seen from one end of the connection
+pub(crate) fn direction_arrow(direction: ConnectionDirection, outgoing: bool) -> &'static str {
+    match (direction, outgoing) {
+        (ConnectionDirection::Bidirectional, _) => "<->",
+        (ConnectionDirection::Undirected, _) => "--",
+        (ConnectionDirection::Forward, true) | (ConnectionDirection::Backward, false) => "->",
+        (ConnectionDirection::Forward, false) | (ConnectionDirection::Backward, true) => "<-",
+    }
+}
+
+pub(crate) fn shape_kind(shape: &ShapeType) -> String {
+    match shape {
+        ShapeType::Rectangle => "rect".to_string(),
+        ShapeType::Circle => "circle".to_string(),
+        ShapeType::Ellipse => "ellipse".to_string(),
+        ShapeType::Line => "line".to_string(),
+        ShapeType::Polygon => "polygon".to_string(),
+        ShapeType::Icon { icon_name, .. } => format!("icon {}", icon_name),
+        ShapeType::Text { .. } => "text".to_string(),
+        ShapeType::SvgEmbed { .. } => "svg".to_string(),
+        ShapeType::RasterImage { .. } => "image".to_string(),
+        ShapeType::Path(_) => "path".to_string(),
+        ShapeType::Bus => "bus".to_string(),
+        ShapeType::State => "state".to_string(),
+        ShapeType::Class { .. } => "class".to_string(),
+        ShapeType::Custom { kind, .. } => kind.clone(),
+    }
+}
+
+pub(crate) fn layout_kind(layout: LayoutType) -> &'static str {
+    match layout {
+        LayoutType::Row => "row",
+        LayoutType::Column => "col",
+        LayoutType::Grid => "grid",
+        LayoutType::Stack => "stack",
+        LayoutType::Flow => "flow",
+        LayoutType::Ring => "ring",
+        LayoutType::Timeline => "timeline",
+        LayoutType::Absolute => "absolute",
+    }
+}
+
+pub(crate) fn label_text(modifiers: &[Spanned<StyleModifier>]) -> Option<String> {
+    modifiers
+        .iter()
+        .find_map(|m| match (&m.node.key.node, &m.node.value.node) {
+            (StyleKey::Label, StyleValue::String(s)) => Some(s.clone()),
+            (StyleKey::Label, StyleValue::Identifier(id)) => Some(id.0.clone()),
+            _ => None,
+        })
+}
+
+fn tags(modifiers: &[Spanned<StyleModifier>]) -> Vec<String> {
+    modifiers
+        .iter()
+        .filter(|m| matches!(m.node.key.node, StyleKey::Class))
+        .filter_map(|m| match &m.node.value.node {
+            StyleValue::String(s) | StyleValue::Keyword(s) => Some(s.clone()),
+            StyleValue::Identifier(id) => Some(id.0.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Format entries as a Markdown table
+pub fn to_markdown(entries: &[InventoryEntry]) -> String {
+    let cell = |s: &str| s.replace('|', "\\|").replace('\n', " ");
+    let mut out = String::from("| id | type | label | group | tags | links |\n");
+    out.push_str("|---|---|---|---|---|---|\n");
+    for e in entries {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {} |\n",
+            cell(&e.id),
+            cell(&e.kind),
+            cell(e.label.as_deref().unwrap_or("")),
+            cell(e.group.as_deref().unwrap_or("")),
+            cell(&e.tags.join(", ")),
+            cell(&e.links.join(", ")),
+        ));
+    }
+    out
+}
+
+/// Format entries as CSV (RFC 4180 quoting; lists are `;`-separated)
+pub fn to_csv(entries: &[InventoryEntry]) -> String {
+    let field = |s: &str| {
+        if s.contains([',', '"', '\n']) {
+            format!("\"{}\"", s.replace('"', "\"\""))
+        } else {
+            s.to_string()
+        }
+    };
+    let mut out = String::from("id,type,label,group,tags,links\n");
+    for e in entries {
+        let row = [
+            field(&e.id),
+            field(&e.kind),
+            field(e.label.as_deref().unwrap_or("")),
+            field(e.group.as_deref().unwrap_or("")),
+            field(&e.tags.join(";")),
+            field(&e.links.join(";")),
+        ];
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_collect_inventory() {
+        let doc = parse(
+            r#"group backend [label: "Backend"] {
+                rect api [class: service]
+                circle db [label: "Orders DB", class: storage]
+            }
+            text "Read only" note_text
+            rect client
+            client -> api
+            api <- db"#,
+        )
+        .unwrap();
+        let entries = collect(&doc);
+        let ids: Vec<_> = entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["backend", "api", "db", "note_text", "client"]);
+
+        let api = &entries[1];
+        assert_eq!(api.kind, "rect");
+        assert_eq!(api.group.as_deref(), Some("backend"));
+        assert_eq!(api.tags, ["service"]);
+        assert_eq!(api.links, ["<- client", "<- db"]);
+        assert_eq!(entries[2].label.as_deref(), Some("Orders DB"));
+        assert_eq!(entries[2].links, ["-> api"]);
+        assert_eq!(entries[3].label.as_deref(), Some("Read only"));
+    }
+
+    #[test]
+    fn test_inventory_formats() {
+        let entries = vec![InventoryEntry {
+            id: "a".to_string(),
+            kind: "rect".to_string(),
+            label: Some("Say \"hi\", | bye".to_string()),
+            group: None,
+            tags: vec!["x".to_string(), "y".to_string()],
+            links: vec!["-> b".to_string()],
+        }];
+        let md = to_markdown(&entries);
+        assert!(md.contains("| a | rect | Say \"hi\", \\| bye |  | x, y | -> b |"));
+        let csv = to_csv(&entries);
+        assert_eq!(
+            csv.lines().nth(1),
+            Some("a,rect,\"Say \"\"hi\"\", | bye\",,x;y,-> b")
+        );
+    }
+}
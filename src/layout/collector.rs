@@ -14,12 +14,16 @@ use super::solver::{
 };
 use super::types::LayoutResult;
 
+/// Default clearance for `constrain a right_of b`, matching `place`
+const RELATION_GAP: f64 = 20.0;
+
 /// A deferred anchor constraint expression, stored during collection
 /// and resolved after local constraint solving when anchor positions are known.
 #[derive(Debug, Clone)]
 pub struct DeferredAnchorConstraint {
     pub expr: ConstraintExpr,
     pub span: Span,
+    pub strength: Option<ConstraintStrength>,
 }
 
 /// Collects all constraints from a document
@@ -121,8 +125,13 @@ impl ConstraintCollector {
                         LayoutType::Stack => {
                             self.collect_stack_constraints(&child_ids, l, &stmt.span)
                         }
-                        LayoutType::Grid => {
-                            // Grid is more complex - skip for now
+                        LayoutType::Grid
+                        | LayoutType::Flow
+                        | LayoutType::Ring
+                        | LayoutType::Timeline
+                        | LayoutType::Absolute => {
+                            // Grid, flow, ring and timeline are more complex - skip for now;
+                            // absolute children have no alignment to keep
                         }
                     }
 
@@ -156,6 +165,7 @@ impl ConstraintCollector {
                     origin: ConstraintOrigin::LayoutContainer,
                     template_instance: None,
                     layout_container: None,
+                    strength: None,
                 },
             });
         }
@@ -179,6 +189,7 @@ impl ConstraintCollector {
                     origin: ConstraintOrigin::LayoutContainer,
                     template_instance: None,
                     layout_container: None,
+                    strength: None,
                 },
             });
         }
@@ -208,6 +219,7 @@ impl ConstraintCollector {
                     origin: ConstraintOrigin::LayoutContainer,
                     template_instance: None,
                     layout_container: None,
+                    strength: None,
                 },
             });
         }
@@ -226,6 +238,7 @@ impl ConstraintCollector {
                     origin: ConstraintOrigin::LayoutContainer,
                     template_instance: None,
                     layout_container: None,
+                    strength: None,
                 },
             });
         }
@@ -250,6 +263,7 @@ impl ConstraintCollector {
                     origin: ConstraintOrigin::LayoutContainer,
                     template_instance: None,
                     layout_container: None,
+                    strength: None,
                 },
             });
             // Same y
@@ -263,6 +277,7 @@ impl ConstraintCollector {
                     origin: ConstraintOrigin::LayoutContainer,
                     template_instance: None,
                     layout_container: None,
+                    strength: None,
                 },
             });
         }
@@ -309,7 +324,11 @@ impl ConstraintCollector {
     fn collect_user_constraints(&mut self, stmts: &[Spanned<Statement>]) {
         for stmt in stmts {
             match &stmt.node {
-                Statement::Constrain(c) => self.collect_constrain(&c.expr, &stmt.span),
+                Statement::Constrain(c) => {
+                    let start = self.constraints.len();
+                    self.collect_constrain(&c.expr, &stmt.span);
+                    self.apply_strength(start, c.strength);
+                }
                 Statement::Layout(l) => self.collect_user_constraints(&l.children),
                 Statement::Group(g) => self.collect_user_constraints(&g.children),
                 _ => {}
@@ -317,6 +336,33 @@ impl ConstraintCollector {
         }
     }
 
+    /// Collect a constrain statement, honouring its `[strength: ...]` modifier.
+    /// Anchor-based expressions are deferred together with their strength.
+    pub fn collect_constrain_decl(&mut self, decl: &ConstrainDecl, span: &Span) {
+        let start = self.constraints.len();
+        let deferred_start = self.deferred_anchor_constraints.len();
+        self.collect_constrain_expr(&decl.expr, span);
+        self.apply_strength(start, decl.strength);
+        for deferred in &mut self.deferred_anchor_constraints[deferred_start..] {
+            deferred.strength = decl.strength;
+        }
+    }
+
+    /// Set an explicit strength on every constraint collected since `start`.
+    ///
+    /// Preferences implied by the statement (the alignment of `right_of`)
+    /// keep their own strength, so the modifier only changes what it states.
+    fn apply_strength(&mut self, start: usize, strength: Option<ConstraintStrength>) {
+        if let Some(strength) = strength {
+            for constraint in &mut self.constraints[start..] {
+                let source = constraint.source_mut();
+                if source.origin != ConstraintOrigin::UserPreference {
+                    source.strength = Some(strength);
+                }
+            }
+        }
+    }
+
     /// Public method to collect a single constrain expression.
     /// Used by the engine to collect constraints selectively.
     /// If the expression involves anchor references, it is deferred
@@ -327,19 +373,58 @@ impl ConstraintCollector {
                 .push(DeferredAnchorConstraint {
                     expr: expr.clone(),
                     span: span.clone(),
+                    strength: None,
                 });
         } else {
             self.collect_constrain(expr, span);
         }
     }
 
+    /// Add lower bounds for an equal-size constraint from the current layout result.
+    ///
+    /// Every element must be at least as large as the largest intrinsic size in
+    /// the set, so the shared size the solver picks is the maximum rather than
+    /// a compromise between the members.
+    pub fn collect_equal_size_minimums(
+        &mut self,
+        dimension: SizeDimension,
+        elements: &[Spanned<Identifier>],
+        span: &Span,
+        layout_result: &LayoutResult,
+    ) {
+        let max = elements
+            .iter()
+            .filter_map(|e| layout_result.get_element_by_name(&e.node.0))
+            .map(|e| match dimension {
+                SizeDimension::Width => e.bounds.width,
+                SizeDimension::Height => e.bounds.height,
+            })
+            .reduce(f64::max);
+        let Some(max) = max else {
+            return;
+        };
+        let property = size_property(dimension);
+        for elem in elements {
+            self.constraints.push(LayoutConstraint::GreaterOrEqual {
+                variable: LayoutVariable::new(&elem.node.0, property),
+                value: max,
+                source: ConstraintSource::user(
+                    span.clone(),
+                    format!("{} at least shared size {}", elem.node.0, max),
+                ),
+            });
+        }
+    }
+
     /// Resolve deferred anchor constraints using the current layout result.
     /// Call this AFTER local constraint solving is complete, so that anchor
     /// positions reflect their post-solve coordinates.
     pub fn resolve_deferred_anchors(&mut self, layout_result: &LayoutResult) -> Result<(), String> {
         let deferred = std::mem::take(&mut self.deferred_anchor_constraints);
         for d in &deferred {
+            let start = self.constraints.len();
             self.collect_constrain_expr_with_anchors(&d.expr, &d.span, layout_result)?;
+            self.apply_strength(start, d.strength);
         }
         Ok(())
     }
@@ -705,6 +790,83 @@ impl ConstraintCollector {
                         });
                 }
             }
+
+            ConstraintExpr::Linear {
+                left,
+                terms,
+                constant,
+            } => {
+                self.constraints.push(LayoutConstraint::Linear {
+                    target: self.property_to_variable(left),
+                    terms: terms
+                        .iter()
+                        .map(|t| (t.coefficient, self.property_to_variable(&t.property)))
+                        .collect(),
+                    constant: *constant,
+                    source: ConstraintSource::user(span.clone(), "constrain linear expression"),
+                });
+            }
+
+            ConstraintExpr::EqualSize {
+                dimension,
+                elements,
+            } => {
+                // Chain every element to the first one: a.width = b.width = c.width
+                let property = size_property(*dimension);
+                if let Some((first, rest)) = elements.split_first() {
+                    for elem in rest {
+                        self.constraints.push(LayoutConstraint::Equal {
+                            left: LayoutVariable::new(&elem.node.0, property),
+                            right: LayoutVariable::new(&first.node.0, property),
+                            offset: 0.0,
+                            source: ConstraintSource::user(
+                                span.clone(),
+                                format!("{} equal size to {}", elem.node.0, first.node.0),
+                            ),
+                        });
+                    }
+                }
+            }
+
+            ConstraintExpr::Relative {
+                subject,
+                relation,
+                anchor,
+                gap,
+            } => {
+                use LayoutProperty::{Bottom, Right, X, Y};
+                let gap = gap.unwrap_or(RELATION_GAP);
+                // (subject edge, anchor edge, offset) and the aligned axis
+                let ((edge, anchor_edge, offset), align) = match relation {
+                    PositionRelation::RightOf => ((X, Right, gap), Y),
+                    PositionRelation::LeftOf => ((Right, X, -gap), Y),
+                    PositionRelation::Below => ((Y, Bottom, gap), X),
+                    PositionRelation::Above => ((Bottom, Y, -gap), X),
+                    // The parser only accepts the four directional relations
+                    PositionRelation::Inside => return,
+                };
+                let (s, a) = (&subject.node.0, &anchor.node.0);
+                self.constraints.push(LayoutConstraint::Equal {
+                    left: LayoutVariable::new(s, edge),
+                    right: LayoutVariable::new(a, anchor_edge),
+                    offset,
+                    source: ConstraintSource::user(
+                        span.clone(),
+                        format!("{} {:?} {} (gap {})", s, relation, a, gap),
+                    ),
+                });
+                // Alignment is a preference, so explicit constraints on the
+                // other axis win without a conflict
+                self.constraints.push(LayoutConstraint::Equal {
+                    left: LayoutVariable::new(s, align),
+                    right: LayoutVariable::new(a, align),
+                    offset: 0.0,
+                    source: ConstraintSource::preference(
+                        span.clone(),
+                        format!("{} aligned with {}", s, a),
+                    ),
+                });
+            }
         }
     }
 
@@ -759,7 +921,19 @@ fn expr_has_anchor_ref(expr: &ConstraintExpr) -> bool {
         ConstraintExpr::GreaterOrEqual { left, .. } => prop_is_anchor(&left.property.node),
         ConstraintExpr::LessOrEqual { left, .. } => prop_is_anchor(&left.property.node),
         ConstraintExpr::Midpoint { target, .. } => prop_is_anchor(&target.property.node),
-        ConstraintExpr::Contains { .. } => false,
+        // The parser rejects anchors in arithmetic expressions
+        ConstraintExpr::Linear { .. }
+        | ConstraintExpr::Contains { .. }
+        | ConstraintExpr::EqualSize { .. }
+        | ConstraintExpr::Relative { .. } => false,
+    }
+}
+
+/// Map an equal-size dimension to the solver property it constrains.
+fn size_property(dimension: SizeDimension) -> LayoutProperty {
+    match dimension {
+        SizeDimension::Width => LayoutProperty::Width,
+        SizeDimension::Height => LayoutProperty::Height,
     }
 }
 
@@ -918,6 +1092,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::len_zero)]
     fn test_collect_constrain_constant() {
         let doc = parse("rect a\nconstrain a.width = 150").unwrap();
         let mut collector = ConstraintCollector::new(LayoutConfig::default());
@@ -26,6 +26,17 @@ pub struct LayoutConfig {
 
     /// Trace mode: emit debug output for constraint solving and routing
     pub trace: bool,
+
+    /// Maximum random displacement for an organic look (0 disables); when
+    /// unset, the document's `canvas [organic: N]` applies
+    pub organic: Option<f64>,
+
+    /// Seed for organic jitter; derived from the document source when unset
+    pub organic_seed: Option<u64>,
+
+    /// Glyph width relative to the default sans-serif, applied to the text
+    /// width estimates that size shapes to their text
+    pub text_width_scale: f64,
 }
 
 impl Default for LayoutConfig {
@@ -39,6 +50,9 @@ impl Default for LayoutConfig {
             container_padding: 5.0,
             connection_spacing: 10.0,
             trace: false,
+            organic: None,
+            organic_seed: None,
+            text_width_scale: 1.0,
         }
     }
 }
@@ -72,6 +86,24 @@ impl LayoutConfig {
         self.container_padding = padding;
         self
     }
+
+    /// Enable organic jitter of up to `amount` units, overriding the document
+    pub fn with_organic(mut self, amount: f64) -> Self {
+        self.organic = Some(amount);
+        self
+    }
+
+    /// Use a fixed seed for organic jitter
+    pub fn with_organic_seed(mut self, seed: u64) -> Self {
+        self.organic_seed = Some(seed);
+        self
+    }
+
+    /// Scale text width estimates for a wider or narrower font
+    pub fn with_text_width_scale(mut self, scale: f64) -> Self {
+        self.text_width_scale = scale;
+        self
+    }
 }
 
 #[cfg(test)]
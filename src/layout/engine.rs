@@ -10,7 +10,7 @@
 //! 1. **Constraint Collection**: Gather all constraints from the document
 //! 2. **Constraint Partitioning**: Classify constraints as Local (within one template) or Global
 //! 3. **Local Solving**: Solve each template's internal constraints independently
-//! 4. **Rotation Transformation**: Apply rotation to templates with the `rotation` modifier
+//! 4. **Rotation Transformation**: Apply the `rotation`, `scale` and `flip` modifiers
 //! 5. **Apply Local Results**: Update the layout with locally solved positions
 //! 6. **Global Solving**: Solve cross-template constraints using post-rotation bounds
 //! 7. **Anchor Recomputation**: Update anchor positions after all transformations
@@ -23,6 +23,7 @@
 //! - [`compute`]: Main entry point for layout computation
 //! - [`resolve_constrain_statements`]: Original single-pass constraint solver
 //! - [`resolve_constrain_statements_two_phase`]: Two-phase solver with rotation support
+//! - [`resolve_constrain_statements_with_transforms`]: The same, with scale and flip
 //! - [`classify_constraint`]: Determine if a constraint is local or global
 //! - [`partition_constraints`]: Group constraints by scope
 //! - [`build_element_to_template_map`]: Map elements to their template instances
@@ -33,6 +34,7 @@ use crate::parser::ast::*;
 
 use super::config::LayoutConfig;
 use super::error::LayoutError;
+use super::transform::ElementTransform;
 use super::types::*;
 
 // ============================================
@@ -294,9 +296,21 @@ fn collect_element_template_mapping(
         | Statement::Constrain(_)
         | Statement::TemplateDecl(_)
         | Statement::TemplateInstance(_)
+        | Statement::Embed(_)
+        | Statement::Import(_)
         | Statement::Export(_)
         | Statement::AnchorDecl(_)
-        | Statement::Keyframe(_) => {}
+        | Statement::Keyframe(_)
+        | Statement::Note(_)
+        | Statement::Repeat(_)
+        | Statement::If(_)
+        | Statement::Let(_)
+        | Statement::Slot
+        | Statement::Canvas(_)
+        | Statement::Title(_)
+        | Statement::Legend(_)
+        | Statement::Animate(_)
+        | Statement::Step(_) => {}
     }
 }
 
@@ -309,12 +323,13 @@ fn collect_element_template_mapping(
 /// Solve constraints for a single template instance in isolation (Phase 1).
 ///
 /// This function:
-/// 1. Creates a constraint solver with the template's child elements
+/// 1. Starts a new pass on `solver` with the template's child elements
 /// 2. Adds current bounds as suggestions (MEDIUM strength)
 /// 3. Adds the local constraints (STRONG strength)
 /// 4. Solves and extracts the results
 ///
 /// # Arguments
+/// * `solver` - Solver shared by all instances and the global phase
 /// * `instance` - The template instance name (e.g., "alice")
 /// * `constraints` - The local constraints for this template
 /// * `result` - The current layout result (to get element bounds)
@@ -323,15 +338,18 @@ fn collect_element_template_mapping(
 /// # Returns
 /// A `LocalSolverResult` with solved bounds and anchors, or an error if unsolvable.
 pub fn solve_local(
+    solver: &mut super::solver::ConstraintSolver,
     instance: &str,
     constraints: &[super::solver::LayoutConstraint],
     result: &LayoutResult,
     element_to_template: &HashMap<String, String>,
     group_anchor_decls: &HashMap<String, Vec<AnchorDecl>>,
 ) -> Result<LocalSolverResult, LayoutError> {
-    use super::solver::{ConstraintSolver, LayoutProperty};
+    use super::solver::LayoutProperty;
 
-    let mut solver = ConstraintSolver::new();
+    solver
+        .clear_constraints()
+        .map_err(LayoutError::solver_error)?;
     let mut local_result = LocalSolverResult::new(instance);
 
     // Collect target variables from constraints to distinguish targets from references.
@@ -339,7 +357,7 @@ pub fn solve_local(
     // This prevents nondeterministic pivot choices in the solver.
     let target_vars: std::collections::HashSet<(String, LayoutProperty)> = constraints
         .iter()
-        .filter_map(get_constraint_target_var)
+        .filter_map(|c| get_constraint_target_var(c, result))
         .collect();
 
     // Find all elements belonging to this template instance
@@ -356,7 +374,7 @@ pub fn solve_local(
     for elem_id in &elements {
         if let Some(elem) = result.elements.get(*elem_id) {
             add_element_by_name_with_per_property_strength(
-                &mut solver,
+                solver,
                 result,
                 elem_id,
                 &target_vars,
@@ -414,6 +432,7 @@ pub fn solve_local(
     for (elem_id, anchors) in anchor_updates {
         local_result.add_anchors(elem_id, anchors);
     }
+    local_result.solution = solution;
 
     // Recompute custom anchors for the template group using updated child bounds
     if let Some(anchor_decls) = group_anchor_decls.get(instance) {
@@ -452,20 +471,32 @@ pub fn solve_local(
 
 /// Apply rotation transformation to a local solver result (Phase 2).
 ///
-/// This function:
-/// 1. Computes the rotation center from the combined child bounds
-/// 2. Creates a rotation transform for the given angle
-/// 3. Transforms only the template instance bounds (for global constraints)
-/// 4. Transforms all anchor positions and directions (for external routing)
+/// Shorthand for [`apply_transform_to_local_result`] with a plain rotation.
 ///
 /// # Arguments
 /// * `local_result` - The local solver result to transform in place
 /// * `angle_degrees` - The rotation angle in degrees (clockwise positive)
 pub fn apply_rotation_to_local_result(local_result: &mut LocalSolverResult, angle_degrees: f64) {
-    use super::transform::RotationTransform;
+    apply_transform_to_local_result(local_result, &ElementTransform::rotation(angle_degrees));
+}
 
-    // Skip if no rotation needed
-    if angle_degrees.abs() < f64::EPSILON {
+/// Apply an element transform to a local solver result (Phase 2).
+///
+/// This function:
+/// 1. Computes the transform center from the combined child bounds
+/// 2. Scales, mirrors and rotates around that center
+/// 3. Transforms only the template instance bounds (for global constraints)
+/// 4. Transforms all anchor positions and directions (for external routing)
+///
+/// # Arguments
+/// * `local_result` - The local solver result to transform in place
+/// * `element_transform` - Rotation, scale and flip of the instance
+pub fn apply_transform_to_local_result(
+    local_result: &mut LocalSolverResult,
+    element_transform: &ElementTransform,
+) {
+    // Skip if no transform needed
+    if element_transform.is_identity() {
         return;
     }
 
@@ -476,13 +507,13 @@ pub fn apply_rotation_to_local_result(local_result: &mut LocalSolverResult, angl
         local_result.pre_rotation_anchors = local_result.anchors.clone();
     }
 
-    // Compute rotation center from combined bounds
+    // Compute transform center from combined bounds
     let center = match local_result.combined_bounds() {
         Some(bounds) => bounds.center(),
-        None => return, // No elements to rotate
+        None => return, // No elements to transform
     };
 
-    let transform = RotationTransform::new(angle_degrees, center);
+    let transform = element_transform.around(center);
 
     // Transform only the template instance bounds
     if let Some(bounds) = local_result
@@ -497,8 +528,8 @@ pub fn apply_rotation_to_local_result(local_result: &mut LocalSolverResult, angl
         *anchors = anchors.transform(&transform);
     }
 
-    // Record the rotation
-    local_result.rotation = Some(angle_degrees);
+    // Record the rotation (0 when only scaled or flipped)
+    local_result.rotation = Some(element_transform.rotation);
     local_result.rotation_center = Some(center);
 }
 
@@ -576,39 +607,104 @@ fn update_element_anchors_in_tree(
     }
 }
 
-/// Recursively update rotation style for an element in the tree structure.
-fn update_element_rotation_in_tree(elements: &mut [ElementLayout], elem_id: &str, rotation: f64) {
-    for elem in elements.iter_mut() {
-        if elem.id.as_ref().map(|i| i.0.as_str()) == Some(elem_id) {
-            elem.styles.rotation = Some(rotation);
-            return;
+/// Set the render-time rotation, scale and flip styles of a transformed element.
+fn set_transform_styles(elem: &mut ElementLayout, transform: &ElementTransform) {
+    if transform.rotation.abs() > f64::EPSILON {
+        elem.styles.rotation = Some(transform.rotation);
+    }
+    if (transform.scale - 1.0).abs() > f64::EPSILON {
+        elem.styles.scale = Some(transform.scale);
+    }
+    if transform.flip.is_some() {
+        elem.styles.flip = transform.flip;
+    }
+}
+
+/// Map the elements nested in transformed groups and layouts to the
+/// transformed element, so they are solved and transformed with it like
+/// template children.
+///
+/// Elements already mapped to a template instance keep that mapping.
+fn map_transformed_descendants(
+    elements: &[ElementLayout],
+    transforms: &HashMap<String, ElementTransform>,
+    current: Option<&str>,
+    map: &mut HashMap<String, String>,
+) {
+    for elem in elements {
+        let id = elem.id_str();
+        if let (Some(id), Some(rotated)) = (id, current) {
+            map.entry(id.to_string())
+                .or_insert_with(|| rotated.to_string());
+        }
+        let context = match id {
+            Some(id) if transforms.get(id).is_some_and(|t| !t.is_identity()) => Some(id),
+            _ => current,
+        };
+        map_transformed_descendants(&elem.children, transforms, context, map);
+    }
+}
+
+/// Names of the transformed elements and of everything nested inside them.
+///
+/// Their anchors were transformed by the two-phase solver, so later passes
+/// must not recompute them from the transformed bounds.
+pub fn transformed_element_ids(
+    result: &LayoutResult,
+    transforms: &HashMap<String, ElementTransform>,
+) -> HashSet<String> {
+    fn collect(
+        elements: &[ElementLayout],
+        inside: bool,
+        transforms: &HashMap<String, ElementTransform>,
+        ids: &mut HashSet<String>,
+    ) {
+        for elem in elements {
+            let transformed = elem
+                .id_str()
+                .and_then(|id| transforms.get(id))
+                .is_some_and(|t| !t.is_identity());
+            if inside || transformed {
+                if let Some(id) = elem.id_str() {
+                    ids.insert(id.to_string());
+                }
+            }
+            collect(&elem.children, inside || transformed, transforms, ids);
         }
-        update_element_rotation_in_tree(&mut elem.children, elem_id, rotation);
     }
+
+    let mut ids = HashSet::new();
+    collect(&result.root_elements, false, transforms, &mut ids);
+    ids
 }
 
 /// Solve global (cross-template) constraints using post-rotation bounds (Phase 4).
 ///
 /// This function:
-/// 1. Creates a constraint solver with all elements (now at their rotated positions)
-/// 2. Adds current bounds as suggestions (MEDIUM strength for targets, FIXED for references)
+/// 1. Starts a new pass on `solver` with all elements (now at their rotated positions)
+/// 2. Adds current bounds as suggestions (MEDIUM strength for targets, FIXED for references),
+///    preferring values already solved in the local phase (`warm_start`)
 /// 3. Adds the global constraints (STRONG strength)
 /// 4. Solves and applies the results
 ///
 /// # Arguments
+/// * `solver` - Solver the local phase used, so its variables are reused
 /// * `result` - The main layout result to update
 /// * `constraints` - The global constraints (cross-template or involving top-level elements)
+/// * `warm_start` - Local-phase solutions for unrotated templates
 /// * `config` - Layout configuration (for trace output)
 ///
 /// # Returns
 /// Ok(()) on success, or an error if constraints are unsolvable.
 pub fn solve_global(
+    solver: &mut super::solver::ConstraintSolver,
     result: &mut LayoutResult,
     constraints: &[super::solver::LayoutConstraint],
     element_to_template: &HashMap<String, String>,
+    warm_start: &super::solver::Solution,
     config: &super::config::LayoutConfig,
 ) -> Result<(), LayoutError> {
-    use super::solver::{ConstraintSolver, LayoutProperty};
+    use super::solver::LayoutProperty;
 
     if constraints.is_empty() {
         return Ok(());
@@ -618,7 +714,7 @@ pub fn solve_global(
     // We only want to move the specific property that is targeted
     let target_vars: std::collections::HashSet<(String, LayoutProperty)> = constraints
         .iter()
-        .filter_map(get_constraint_target_var)
+        .filter_map(|c| get_constraint_target_var(c, result))
         .collect();
 
     // Collect all elements referenced in constraints
@@ -627,7 +723,10 @@ pub fn solve_global(
         .flat_map(get_constraint_referenced_elements)
         .collect();
 
-    let mut solver = ConstraintSolver::new();
+    solver
+        .clear_constraints()
+        .map_err(LayoutError::solver_error)?;
+    solver.set_warm_start(warm_start);
 
     // Sort referenced elements for deterministic solver input order
     let mut sorted_refs: Vec<&String> = referenced_elements.iter().collect();
@@ -637,7 +736,7 @@ pub fn solve_global(
     // For each property: if it's targeted → SUGGESTED (can move), else → FIXED (reference)
     for element_name in &sorted_refs {
         add_element_by_name_with_per_property_strength(
-            &mut solver,
+            solver,
             result,
             element_name,
             &target_vars,
@@ -720,6 +819,12 @@ pub fn solve_global(
                             .filter(|template_name| result.elements.contains_key(*template_name))
                             .cloned()
                             .unwrap_or_else(|| var.element_id.clone());
+                        if result
+                            .get_element_by_name(&target_id)
+                            .is_some_and(|e| e.styles.pinned)
+                        {
+                            continue;
+                        }
 
                         let axis_key = if axis == Axis::Horizontal { 0 } else { 1 };
                         if let Some(existing) = applied_deltas.get(&(target_id.clone(), axis_key)) {
@@ -773,9 +878,14 @@ pub fn compute(doc: &Document, config: &LayoutConfig) -> Result<LayoutResult, La
             | Statement::Constraint(_)
             | Statement::Constrain(_)
             | Statement::Label(_)
-            | Statement::Keyframe(_) => continue,
+            | Statement::Keyframe(_)
+            | Statement::Canvas(_)
+            | Statement::Title(_)
+            | Statement::Legend(_)
+            | Statement::Animate(_)
+            | Statement::Note(_) => continue,
             _ => {
-                let element = layout_statement(&stmt.node, position, config);
+                let element = layout_spanned(stmt, position, config);
                 position.y += element.bounds.height + config.element_spacing;
                 result.add_element(element);
             }
@@ -822,6 +932,345 @@ pub fn resolve_constraints(
     Ok(())
 }
 
+/// Place `note at element.anchor { ... }` annotations.
+///
+/// Runs after all constraints are resolved so each note lands on the final
+/// position of its anchor. The note content is laid out like a group and
+/// centered on the anchor point, shifted by the optional `dx`/`dy` modifiers.
+/// Without an explicit anchor the note attaches to the element's `top_right`.
+pub fn place_notes(
+    result: &mut LayoutResult,
+    doc: &Document,
+    config: &LayoutConfig,
+) -> Result<(), LayoutError> {
+    let mut placed = false;
+    for stmt in &doc.statements {
+        let Statement::Note(note) = &stmt.node else {
+            continue;
+        };
+
+        let point = note_anchor_point(result, &note.target)?;
+        let dx = super::collector::extract_number_modifier(&note.modifiers, "dx").unwrap_or(0.0);
+        let dy = super::collector::extract_number_modifier(&note.modifiers, "dy").unwrap_or(0.0);
+
+        let group = GroupDecl {
+            name: None,
+            children: note.children.clone(),
+            modifiers: note
+                .modifiers
+                .iter()
+                .filter(
+                    |m| !matches!(&m.node.key.node, StyleKey::Custom(k) if k == "dx" || k == "dy"),
+                )
+                .cloned()
+                .collect(),
+            anchors: vec![],
+            is_template_instance: false,
+            layer: None,
+        };
+        let mut element = layout_group(&group, Point::new(0.0, 0.0), config);
+        let center = element.bounds.center();
+        offset_element(
+            &mut element,
+            point.x + dx - center.x,
+            point.y + dy - center.y,
+        );
+        result.add_element(element);
+        placed = true;
+    }
+
+    if placed {
+        result.compute_bounds();
+    }
+    Ok(())
+}
+
+/// Resolve the point a note attaches to: a named anchor of the element, or
+/// one of its bounding box corners/center.
+fn note_anchor_point(
+    result: &LayoutResult,
+    target: &AnchorReference,
+) -> Result<Point, LayoutError> {
+    let name = &target.element.node.0;
+    let element = result
+        .get_element_by_name(name)
+        .ok_or_else(|| LayoutError::undefined(name.clone(), target.element.span.clone(), vec![]))?;
+
+    let anchor = target
+        .anchor
+        .as_ref()
+        .map(|a| a.node.as_str())
+        .unwrap_or("top_right");
+    if let Some(found) = element.anchors.get(anchor) {
+        return Ok(found.position);
+    }
+
+    let b = &element.bounds;
+    match anchor {
+        "top_left" => Ok(b.top_left()),
+        "top_right" => Ok(b.top_right()),
+        "bottom_left" => Ok(b.bottom_left()),
+        "bottom_right" => Ok(b.bottom_right()),
+        "center" => Ok(b.center()),
+        _ => {
+            let mut valid: Vec<String> = element.anchors.names().map(String::from).collect();
+            for corner in [
+                "top_left",
+                "top_right",
+                "bottom_left",
+                "bottom_right",
+                "center",
+            ] {
+                if !valid.iter().any(|v| v == corner) {
+                    valid.push(corner.to_string());
+                }
+            }
+            valid.sort();
+            let span = target
+                .anchor
+                .as_ref()
+                .map(|a| a.span.clone())
+                .unwrap_or_else(|| target.element.span.clone());
+            Err(LayoutError::invalid_anchor(
+                name.clone(),
+                anchor,
+                valid,
+                span,
+            ))
+        }
+    }
+}
+
+/// Place legends beside the diagram, then titles above and captions below
+///
+/// Runs last, so the diagram (including notes) is in its final position.
+/// Titles stack upwards in reverse order so they read top to bottom as
+/// written; captions stack downwards.
+pub fn place_titles_and_legends(
+    result: &mut LayoutResult,
+    doc: &Document,
+    config: &LayoutConfig,
+) -> Result<(), LayoutError> {
+    // Clear of the diagram; element_spacing is meant for siblings and too tight
+    let gap = 20.0;
+    let mut titles = vec![];
+    let mut captions = vec![];
+    result.compute_bounds();
+    for stmt in &doc.statements {
+        match &stmt.node {
+            Statement::Legend(legend) => {
+                let (top, right) = legend_corner(legend)?;
+                let mut element = layout_legend(legend, &stmt.span, config);
+                element.span = Some(stmt.span.clone());
+                let (b, diagram) = (element.bounds, result.bounds);
+                let x = if right {
+                    diagram.right() + gap
+                } else {
+                    diagram.x - gap - b.width
+                };
+                let y = if top {
+                    diagram.y
+                } else {
+                    diagram.bottom() - b.height
+                };
+                offset_element(&mut element, x - b.x, y - b.y);
+                result.add_element(element);
+                result.compute_bounds();
+            }
+            Statement::Title(t) if t.kind == TitleKind::Title => titles.push((t, &stmt.span)),
+            Statement::Title(t) => captions.push((t, &stmt.span)),
+            _ => {}
+        }
+    }
+
+    for (title, span) in titles.into_iter().rev() {
+        let mut element = layout_title(title, span, config);
+        let (b, diagram) = (element.bounds, result.bounds);
+        offset_element(
+            &mut element,
+            round_offset(diagram.center().x - b.center().x),
+            diagram.y - gap / 2.0 - b.bottom(),
+        );
+        result.add_element(element);
+        result.compute_bounds();
+    }
+    for (caption, span) in captions {
+        let mut element = layout_title(caption, span, config);
+        let (b, diagram) = (element.bounds, result.bounds);
+        offset_element(
+            &mut element,
+            round_offset(diagram.center().x - b.center().x),
+            diagram.bottom() + gap / 2.0 - b.y,
+        );
+        result.add_element(element);
+        result.compute_bounds();
+    }
+    Ok(())
+}
+
+/// Centering offsets to a tenth of a pixel, so coordinates stay short
+fn round_offset(offset: f64) -> f64 {
+    (offset * 10.0).round() / 10.0
+}
+
+fn modifier(key: StyleKey, value: StyleValue, span: &Span) -> Spanned<StyleModifier> {
+    Spanned::new(
+        StyleModifier {
+            key: Spanned::new(key, span.clone()),
+            value: Spanned::new(value, span.clone()),
+        },
+        span.clone(),
+    )
+}
+
+fn number(value: f64) -> StyleValue {
+    StyleValue::Number { value, unit: None }
+}
+
+/// Modifiers written in the source go last, so they override the defaults
+fn with_defaults(
+    mut defaults: Vec<Spanned<StyleModifier>>,
+    modifiers: &[Spanned<StyleModifier>],
+) -> Vec<Spanned<StyleModifier>> {
+    defaults.retain(|d| !modifiers.iter().any(|m| m.node.key.node == d.node.key.node));
+    defaults.extend(modifiers.iter().cloned());
+    defaults
+}
+
+/// A title or caption as a text element, with a larger default font for titles
+fn layout_title(title: &TitleDecl, span: &Span, config: &LayoutConfig) -> ElementLayout {
+    let defaults = match title.kind {
+        TitleKind::Title => vec![modifier(StyleKey::FontSize, number(20.0), span)],
+        TitleKind::Caption => vec![],
+    };
+    let shape = ShapeDecl {
+        shape_type: Spanned::new(
+            ShapeType::Text {
+                content: title.text.node.clone(),
+            },
+            title.text.span.clone(),
+        ),
+        name: None,
+        modifiers: with_defaults(defaults, &title.modifiers),
+        ports: vec![],
+    };
+    let mut element = layout_shape(&shape, Point::new(0.0, 0.0), config);
+    element
+        .styles
+        .css_classes
+        .push(title.kind.as_str().to_string());
+    element.span = Some(span.clone());
+    element
+}
+
+/// A legend as a column of rows, each a color swatch and its label, on a
+/// light background
+fn layout_legend(legend: &LegendDecl, span: &Span, config: &LayoutConfig) -> ElementLayout {
+    let color = |category, variant| {
+        StyleValue::Color(ColorValue::Symbolic {
+            category,
+            variant: Some(variant),
+            lightness: None,
+        })
+    };
+    let keyword = |k: &str| StyleValue::Keyword(k.to_string());
+    let rows = legend
+        .entries
+        .iter()
+        .map(|entry| {
+            let span = &entry.span;
+            let swatch = ShapeDecl {
+                shape_type: Spanned::new(ShapeType::Rectangle, span.clone()),
+                name: None,
+                modifiers: with_defaults(
+                    vec![
+                        modifier(StyleKey::Width, number(14.0), span),
+                        modifier(StyleKey::Height, number(14.0), span),
+                        modifier(StyleKey::Fill, entry.node.color.node.clone(), span),
+                        modifier(StyleKey::Class, keyword("legend-swatch"), span),
+                    ],
+                    &entry.node.modifiers,
+                ),
+                ports: vec![],
+            };
+            let label = ShapeDecl {
+                shape_type: Spanned::new(
+                    ShapeType::Text {
+                        content: entry.node.label.node.clone(),
+                    },
+                    entry.node.label.span.clone(),
+                ),
+                name: None,
+                modifiers: vec![],
+                ports: vec![],
+            };
+            let row = LayoutDecl {
+                layout_type: Spanned::new(LayoutType::Row, span.clone()),
+                name: None,
+                children: vec![
+                    Spanned::new(Statement::Shape(swatch), span.clone()),
+                    Spanned::new(Statement::Shape(label), span.clone()),
+                ],
+                modifiers: vec![
+                    modifier(StyleKey::Gap, number(6.0), span),
+                    modifier(StyleKey::Custom("align".into()), keyword("center"), span),
+                ],
+            };
+            Spanned::new(Statement::Layout(row), span.clone())
+        })
+        .collect();
+    let modifiers: Vec<_> = legend
+        .modifiers
+        .iter()
+        .filter(|m| !matches!(&m.node.key.node, StyleKey::Custom(k) if k == "position"))
+        .cloned()
+        .collect();
+    let column = LayoutDecl {
+        layout_type: Spanned::new(LayoutType::Column, span.clone()),
+        name: None,
+        children: rows,
+        modifiers: with_defaults(
+            vec![
+                modifier(StyleKey::Gap, number(4.0), span),
+                modifier(StyleKey::Custom("padding".into()), number(8.0), span),
+                modifier(StyleKey::Fill, color(ColorCategory::Background, 1), span),
+                modifier(StyleKey::Stroke, color(ColorCategory::Foreground, 3), span),
+                modifier(StyleKey::Custom("corner_radius".into()), number(4.0), span),
+            ],
+            &modifiers,
+        ),
+    };
+    let mut element = layout_container(&column, Point::new(0.0, 0.0), config);
+    element.styles.css_classes.push("legend".to_string());
+    element
+}
+
+/// `(top, right)` for the legend's `position`, `bottom_right` by default
+fn legend_corner(legend: &LegendDecl) -> Result<(bool, bool), LayoutError> {
+    let position = legend
+        .modifiers
+        .iter()
+        .find_map(|m| match &m.node.key.node {
+            StyleKey::Custom(k) if k == "position" => Some(&m.node.value.node),
+            _ => None,
+        });
+    let name = match position {
+        None => return Ok((false, true)),
+        Some(StyleValue::Identifier(id)) => id.0.as_str(),
+        Some(StyleValue::Keyword(k)) => k.as_str(),
+        Some(_) => "",
+    };
+    match name {
+        "top_left" => Ok((true, false)),
+        "top_right" => Ok((true, true)),
+        "bottom_left" => Ok((false, false)),
+        "bottom_right" => Ok((false, true)),
+        _ => Err(LayoutError::validation_error(
+            "legend position must be top_left, top_right, bottom_left or bottom_right",
+        )),
+    }
+}
+
 /// Apply x/y position offsets from place statements
 fn apply_position_offsets(result: &mut LayoutResult, doc: &Document) -> Result<(), LayoutError> {
     // Collect all place statements with position modifiers
@@ -888,6 +1337,17 @@ fn collect_position_offsets(stmts: &[Spanned<Statement>]) -> Vec<(String, f64, f
     offsets
 }
 
+/// Lay out a statement and record its source range on the element
+fn layout_spanned(
+    stmt: &Spanned<Statement>,
+    position: Point,
+    config: &LayoutConfig,
+) -> ElementLayout {
+    let mut element = layout_statement(&stmt.node, position, config);
+    element.span = Some(stmt.span.clone());
+    element
+}
+
 fn layout_statement(stmt: &Statement, position: Point, config: &LayoutConfig) -> ElementLayout {
     match stmt {
         Statement::Shape(s) => layout_shape(s, position, config),
@@ -901,8 +1361,11 @@ fn layout_statement(stmt: &Statement, position: Point, config: &LayoutConfig) ->
             // These are handled separately
             unreachable!("Connections and constraints should be filtered out")
         }
-        Statement::TemplateDecl(_) | Statement::Export(_) | Statement::AnchorDecl(_) => {
-            // Template declarations, exports, and anchor declarations are metadata, not layout elements
+        Statement::TemplateDecl(_)
+        | Statement::Import(_)
+        | Statement::Export(_)
+        | Statement::AnchorDecl(_) => {
+            // Template declarations, imports, exports, and anchor declarations are metadata, not layout elements
             // They are handled during template resolution, not layout
             unreachable!("Template declarations, exports, and anchor declarations should be filtered out before layout")
         }
@@ -911,43 +1374,114 @@ fn layout_statement(stmt: &Statement, position: Point, config: &LayoutConfig) ->
             // After template resolution, instances are replaced with their expanded content
             unreachable!("Template instances should be expanded before layout")
         }
+        Statement::Embed(_) => {
+            // Embeds are rendered into SVG shapes during template resolution
+            unreachable!("Embedded documents should be resolved before layout")
+        }
         Statement::Keyframe(_) => {
             // Keyframes are handled after layout, not during layout
             unreachable!("Keyframes should be filtered out before layout")
         }
+        Statement::Canvas(_) => {
+            // The canvas sizes the SVG output and takes no part in layout
+            unreachable!("Canvas should be filtered out before layout")
+        }
+        Statement::Title(_) | Statement::Legend(_) => {
+            // Placed around the finished diagram by place_titles_and_legends
+            unreachable!("Titles and legends should be filtered out before layout")
+        }
+        Statement::Animate(_) => {
+            // Animations are emitted into the rendered SVG
+            unreachable!("Animations should be filtered out before layout")
+        }
+        Statement::Step(_) => {
+            // Step bodies are spliced into the document right after parsing
+            unreachable!("Steps should be flattened before layout")
+        }
+        Statement::Repeat(_) => {
+            // Repeats are unrolled by template::expand_document
+            unreachable!("Repeats should be expanded before layout")
+        }
+        Statement::If(_) => {
+            // Conditionals only appear in template bodies and are decided on instantiation
+            unreachable!("Conditionals should be resolved before layout")
+        }
+        Statement::Let(_) => {
+            // Variables are inlined by template::expand_document
+            unreachable!("Variables should be inlined before layout")
+        }
+        Statement::Slot => {
+            // Slots are filled with instance children during template resolution
+            unreachable!("Slots should be filled before layout")
+        }
+        Statement::Note(_) => {
+            // Notes are placed by place_notes once constraints are resolved
+            unreachable!("Notes should be filtered out before layout")
+        }
     }
 }
 
 fn layout_shape(shape: &ShapeDecl, position: Point, config: &LayoutConfig) -> ElementLayout {
     let (width, height) = compute_shape_size(shape, config);
-    let styles = ResolvedStyles::from_modifiers(&shape.modifiers);
+    let mut styles = ResolvedStyles::from_modifiers(&shape.modifiers);
+    if shape.shape_type.node == ShapeType::State && styles.corner_radius.is_none() {
+        styles.corner_radius = Some(STATE_CORNER_RADIUS);
+    }
 
     // For Line shapes, position label above the line with an offset
     // For other shapes, center the label within the shape
-    let label = extract_label(&shape.modifiers).map(|text| {
+    let label = match &shape.shape_type.node {
+        ShapeType::Class { .. } => class_name(shape),
+        _ => extract_label(&shape.modifiers),
+    };
+    let font_size = styles.font_size.unwrap_or(14.0);
+    let label = label.map(|text| {
+        let mut vertical_align = VerticalAlign::Middle;
         let (label_x, label_y, anchor) = match &shape.shape_type.node {
-            ShapeType::Line => {
-                // Center horizontally on the line, position above with offset
-                let label_offset = 12.0; // pixels above the line
+            ShapeType::Class {
+                attributes,
+                operations,
+            } => {
+                // Centered in the name compartment
+                let rows = ClassCompartments::new(attributes.len(), operations.len(), font_size);
                 (
                     position.x + width / 2.0,
-                    position.y + height / 2.0 - label_offset,
+                    position.y + rows.name / 2.0,
                     TextAnchor::Middle,
                 )
             }
-            _ => {
-                // Default: center within the shape bounds
+            ShapeType::Line | ShapeType::Bus => {
+                // Center horizontally on the line, position above with offset
+                let label_offset = 12.0; // pixels above the line
                 (
                     position.x + width / 2.0,
-                    position.y + height / 2.0,
+                    position.y + height / 2.0 - label_offset,
                     TextAnchor::Middle,
                 )
             }
+            _ => {
+                // Default: centered within the shape bounds, or kept
+                // LABEL_INSET from the edges `text_align`/`vertical_align` name
+                let anchor = styles.text_align.unwrap_or(TextAnchor::Middle);
+                vertical_align = styles.vertical_align.unwrap_or_default();
+                let x = match anchor {
+                    TextAnchor::Start => position.x + LABEL_INSET,
+                    TextAnchor::Middle => position.x + width / 2.0,
+                    TextAnchor::End => position.x + width - LABEL_INSET,
+                };
+                let y = match vertical_align {
+                    VerticalAlign::Top => position.y + LABEL_INSET,
+                    VerticalAlign::Middle => position.y + height / 2.0,
+                    VerticalAlign::Bottom => position.y + height - LABEL_INSET,
+                };
+                (x, y, anchor)
+            }
         };
         LabelLayout {
             text,
             position: Point::new(label_x, label_y),
             anchor,
+            vertical_align,
             styles: None,
         }
     });
@@ -963,14 +1497,63 @@ fn layout_shape(shape: &ShapeDecl, position: Point, config: &LayoutConfig) -> El
 
     let bounds = BoundingBox::new(position.x, position.y, width, height);
     // Feature 009: Compute anchors based on shape type
-    let anchors = match &shape.shape_type.node {
-        ShapeType::Path(_) => AnchorSet::path_shape(&bounds),
+    let mut anchors = match &shape.shape_type.node {
+        ShapeType::Path(decl) => {
+            let mut anchors = AnchorSet::path_shape(&bounds);
+            anchors.insert_path_vertices(decl, &bounds);
+            anchors
+        }
         _ => AnchorSet::simple_shape(&bounds),
     };
+    // Each compartment of a class box gets a `<compartment>_left` and
+    // `<compartment>_right` anchor at its middle
+    if let ShapeType::Class {
+        attributes,
+        operations,
+    } = &shape.shape_type.node
+    {
+        let rows = ClassCompartments::new(attributes.len(), operations.len(), font_size);
+        let mut top = 0.0;
+        for (name, h) in [
+            ("name", rows.name),
+            ("attributes", rows.attributes),
+            ("operations", rows.operations),
+        ] {
+            let at = (top + h / 2.0) / rows.height();
+            for (side, direction) in [
+                ("left", AnchorDirection::Left),
+                ("right", AnchorDirection::Right),
+            ] {
+                let port = Port {
+                    name: format!("{}_{}", name, side),
+                    side: direction,
+                    at,
+                    marker: false,
+                };
+                anchors.insert_port(port, &bounds);
+            }
+            top += h;
+        }
+    }
+    for port in &shape.ports {
+        let side = match port.node.side {
+            Edge::Top => AnchorDirection::Up,
+            Edge::Bottom => AnchorDirection::Down,
+            Edge::Left => AnchorDirection::Left,
+            _ => AnchorDirection::Right,
+        };
+        let port = Port {
+            name: port.node.name.node.0.clone(),
+            side,
+            at: port.node.at,
+            marker: port.node.marker,
+        };
+        anchors.insert_port(port, &bounds);
+    }
 
     ElementLayout {
         id,
-        z_order: 0,
+        z_order: extract_z_order(&shape.modifiers),
         element_type: ElementType::Shape(shape.shape_type.node.clone()),
         bounds,
         styles,
@@ -978,9 +1561,42 @@ fn layout_shape(shape: &ShapeDecl, position: Point, config: &LayoutConfig) -> El
         label,
         anchors,
         path_normalize: true,
+        padding: Padding::default(),
+        span: None,
+        pre_transform_size: None,
     }
 }
 
+/// Default length of a `bus` rail
+const BUS_LENGTH: f64 = 200.0;
+/// Default thickness of a `bus` rail
+const BUS_THICKNESS: f64 = 6.0;
+
+/// Space between a shape's edge and a label aligned to it
+const LABEL_INSET: f64 = 8.0;
+
+/// Corner radius of a `state` unless it sets `corner_radius`
+const STATE_CORNER_RADIUS: f64 = 12.0;
+
+/// The heading of a `class` box: its label, or else its name
+fn class_name(shape: &ShapeDecl) -> Option<String> {
+    extract_label(&shape.modifiers).or_else(|| shape.name.as_ref().map(|n| n.node.0.clone()))
+}
+
+/// Whether a bus runs vertically (`orientation: vertical`); horizontal by default
+fn is_vertical_bus(modifiers: &[Spanned<StyleModifier>]) -> bool {
+    modifiers.iter().any(|m| {
+        if !matches!(&m.node.key.node, StyleKey::Custom(k) if k == "orientation") {
+            return false;
+        }
+        match &m.node.value.node {
+            StyleValue::Keyword(k) => k == "vertical",
+            StyleValue::Identifier(id) => id.0 == "vertical",
+            _ => false,
+        }
+    })
+}
+
 fn compute_shape_size(shape: &ShapeDecl, config: &LayoutConfig) -> (f64, f64) {
     // Extract size modifiers from the shape
     let size = extract_size_modifier(&shape.modifiers);
@@ -992,23 +1608,52 @@ fn compute_shape_size(shape: &ShapeDecl, config: &LayoutConfig) -> (f64, f64) {
         return (w, h);
     }
 
+    // Images and SVG templates keep their aspect ratio when only one side is
+    // given; `size` is the longer side
+    if let ShapeType::RasterImage {
+        intrinsic_width: Some(iw),
+        intrinsic_height: Some(ih),
+        ..
+    }
+    | ShapeType::SvgEmbed {
+        intrinsic_width: Some(iw),
+        intrinsic_height: Some(ih),
+        ..
+    } = &shape.shape_type.node
+    {
+        if *iw > 0.0 && *ih > 0.0 {
+            match (width, height, size) {
+                (Some(w), None, _) => return (w, w * ih / iw),
+                (None, Some(h), _) => return (h * iw / ih, h),
+                (None, None, Some(s)) => {
+                    let scale = s / iw.max(*ih);
+                    return (iw * scale, ih * scale);
+                }
+                _ => {}
+            }
+        }
+    }
+
     // If only size is provided, use it for both dimensions (square/circle)
     if let Some(s) = size {
         return (s, s);
     }
 
-    // Calculate minimum width needed to fit label (if present)
-    let label_min_width = extract_label(&shape.modifiers).map(|text| {
-        // Approximate: ~8px per character for 14px font, plus 20px padding
-        let char_width = 8.0;
-        let padding = 20.0;
-        text.len() as f64 * char_width + padding
-    });
+    // Calculate minimum width needed to fit label (if present); a bus keeps
+    // its thickness
+    let label_min_width = extract_label(&shape.modifiers)
+        .filter(|_| !matches!(shape.shape_type.node, ShapeType::Bus))
+        .map(|text| {
+            // Approximate: ~8px per character for 14px font, plus 20px padding
+            let char_width = 8.0 * config.text_width_scale;
+            let padding = 20.0;
+            text.len() as f64 * char_width + padding
+        });
 
     // If only width is provided, use it for width and default for height
     // If only height is provided, use default for width and it for height
     let (default_width, default_height) = match &shape.shape_type.node {
-        ShapeType::Rectangle => config.default_rect_size,
+        ShapeType::Rectangle | ShapeType::State => config.default_rect_size,
         ShapeType::Circle => {
             let d = config.default_circle_radius * 2.0;
             (d, d)
@@ -1017,12 +1662,32 @@ fn compute_shape_size(shape: &ShapeDecl, config: &LayoutConfig) -> (f64, f64) {
         ShapeType::Polygon => config.default_rect_size,
         ShapeType::Icon { .. } => config.default_rect_size,
         ShapeType::Line => (config.default_line_width, 4.0),
+        ShapeType::Bus if is_vertical_bus(&shape.modifiers) => (BUS_THICKNESS, BUS_LENGTH),
+        ShapeType::Bus => (BUS_LENGTH, BUS_THICKNESS),
+        ShapeType::Class {
+            attributes,
+            operations,
+        } => {
+            // Wide enough for the longest line, tall enough for every member
+            let font_size = extract_font_size(&shape.modifiers).unwrap_or(14.0);
+            let longest = attributes
+                .iter()
+                .chain(operations)
+                .map(|member| member.node.len())
+                .chain(class_name(shape).map(|name| name.len()))
+                .max()
+                .unwrap_or(0);
+            let width = longest as f64 * font_size * 0.6 * config.text_width_scale
+                + 2.0 * ClassCompartments::INSET;
+            let rows = ClassCompartments::new(attributes.len(), operations.len(), font_size);
+            (width.max(config.default_rect_size.0), rows.height())
+        }
         ShapeType::Text { content } => {
             // Estimate text size based on content length
             // Use font_size from modifiers if available, otherwise default to 14px
             let font_size = extract_font_size(&shape.modifiers).unwrap_or(14.0);
             // Approximate width: ~0.6 * font_size per character
-            let estimated_width = content.len() as f64 * font_size * 0.6;
+            let estimated_width = content.len() as f64 * font_size * 0.6 * config.text_width_scale;
             // Height is approximately the font size
             (estimated_width.max(20.0), font_size)
         }
@@ -1036,14 +1701,25 @@ fn compute_shape_size(shape: &ShapeDecl, config: &LayoutConfig) -> (f64, f64) {
             let h = intrinsic_height.unwrap_or(config.default_rect_size.1);
             (w, h)
         }
-        ShapeType::RasterImage { .. } => {
-            // Raster images require explicit dimensions; use default rect size as fallback
-            config.default_rect_size
+        ShapeType::RasterImage {
+            intrinsic_width,
+            intrinsic_height,
+            ..
+        } => {
+            // Use the pixel size read from the file, or the default rect size if unknown
+            let w = intrinsic_width.unwrap_or(config.default_rect_size.0);
+            let h = intrinsic_height.unwrap_or(config.default_rect_size.1);
+            (w, h)
         }
         ShapeType::Path(path_decl) => {
             // Compute bounds from path vertices
             compute_path_bounds(path_decl).unwrap_or(config.default_rect_size)
         }
+        ShapeType::Custom {
+            intrinsic_width,
+            intrinsic_height,
+            ..
+        } => (*intrinsic_width, *intrinsic_height),
     };
 
     // Start with specified or default width
@@ -1376,6 +2052,100 @@ fn has_role_label(stmt: &Statement) -> bool {
     })
 }
 
+/// Cross-axis placement of row/col children (`align` on the container,
+/// `self_align` on a child)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum ChildAlign {
+    /// Top of a row, left of a column
+    #[default]
+    Start,
+    Center,
+    /// Bottom of a row, right of a column
+    End,
+    /// Fill the full cross size of the container
+    Stretch,
+}
+
+/// Extract a `start|center|end|stretch` value for the given modifier key
+fn extract_child_align(modifiers: &[Spanned<StyleModifier>], key: &str) -> Option<ChildAlign> {
+    modifiers.iter().find_map(|m| {
+        if !matches!(&m.node.key.node, StyleKey::Custom(k) if k == key) {
+            return None;
+        }
+        let value = match &m.node.value.node {
+            StyleValue::Keyword(k) => k.as_str(),
+            StyleValue::Identifier(id) => id.0.as_str(),
+            _ => return None,
+        };
+        match value {
+            "start" => Some(ChildAlign::Start),
+            "center" => Some(ChildAlign::Center),
+            "end" => Some(ChildAlign::End),
+            "stretch" => Some(ChildAlign::Stretch),
+            _ => None,
+        }
+    })
+}
+
+/// Per-child `self_align` override
+fn extract_self_align(stmt: &Statement) -> Option<ChildAlign> {
+    let modifiers = match stmt {
+        Statement::Shape(s) => &s.modifiers,
+        Statement::Layout(l) => &l.modifiers,
+        Statement::Group(g) => &g.modifiers,
+        _ => return None,
+    };
+    extract_child_align(modifiers, "self_align")
+}
+
+/// Place a child within the cross axis of its row (`horizontal == false`) or
+/// column (`horizontal == true`), given the largest cross size among siblings.
+fn align_child(element: &mut ElementLayout, align: ChildAlign, cross: f64, horizontal: bool) {
+    let size = if horizontal {
+        element.bounds.width
+    } else {
+        element.bounds.height
+    };
+    let delta = match align {
+        ChildAlign::Start => return,
+        ChildAlign::Center => (cross - size) / 2.0,
+        ChildAlign::End => cross - size,
+        ChildAlign::Stretch => {
+            // Only the child's own box grows; a label keeps its place relative
+            // to the edge it is aligned to, or to the center
+            let grow = cross - size;
+            if horizontal {
+                element.bounds.width = cross;
+            } else {
+                element.bounds.height = cross;
+            }
+            if let (Some(label), true) = (&mut element.label, element.children.is_empty()) {
+                if horizontal {
+                    label.position.x += grow
+                        * match label.anchor {
+                            TextAnchor::Start => 0.0,
+                            TextAnchor::Middle => 0.5,
+                            TextAnchor::End => 1.0,
+                        };
+                } else {
+                    label.position.y += grow
+                        * match label.vertical_align {
+                            VerticalAlign::Top => 0.0,
+                            VerticalAlign::Middle => 0.5,
+                            VerticalAlign::Bottom => 1.0,
+                        };
+                }
+            }
+            return;
+        }
+    };
+    if horizontal {
+        offset_element(element, delta, 0.0);
+    } else {
+        offset_element(element, 0.0, delta);
+    }
+}
+
 /// Extract the first child with `role: label` modifier from a list of children.
 /// Returns the statement if found.
 fn extract_role_label_statement(children: &[Spanned<Statement>]) -> Option<&Statement> {
@@ -1402,6 +2172,66 @@ fn extract_gap(modifiers: &[Spanned<StyleModifier>]) -> Option<f64> {
     })
 }
 
+/// Extract the wrap width of a flow layout (`max_width: N`)
+fn extract_max_width(modifiers: &[Spanned<StyleModifier>]) -> Option<f64> {
+    modifiers
+        .iter()
+        .find_map(|m| match (&m.node.key.node, &m.node.value.node) {
+            (StyleKey::Custom(k), StyleValue::Number { value, .. }) if k == "max_width" => {
+                Some(*value)
+            }
+            _ => None,
+        })
+}
+
+/// `z: N` (or `z_order: N`): emission order among siblings, higher on top
+fn extract_z_order(modifiers: &[Spanned<StyleModifier>]) -> i32 {
+    modifiers
+        .iter()
+        .find_map(|m| match (&m.node.key.node, &m.node.value.node) {
+            (StyleKey::ZOrder, StyleValue::Number { value, .. }) => Some(*value as i32),
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
+/// Extract the circle radius of a ring layout (`radius: N`)
+fn extract_radius(modifiers: &[Spanned<StyleModifier>]) -> Option<f64> {
+    modifiers
+        .iter()
+        .find_map(|m| match (&m.node.key.node, &m.node.value.node) {
+            (StyleKey::Custom(k), StyleValue::Number { value, .. }) if k == "radius" => {
+                Some(*value)
+            }
+            _ => None,
+        })
+}
+
+impl Padding {
+    /// `padding: N` overrides `LayoutConfig::container_padding` on all sides;
+    /// `padding_top`, `padding_right`, `padding_bottom` and `padding_left`
+    /// override a single side.
+    fn from_modifiers(modifiers: &[Spanned<StyleModifier>], config: &LayoutConfig) -> Self {
+        let number = |key: &str| {
+            modifiers
+                .iter()
+                .find_map(|m| match (&m.node.key.node, &m.node.value.node) {
+                    (StyleKey::Custom(k), StyleValue::Number { value, .. }) if k == key => {
+                        Some(*value)
+                    }
+                    _ => None,
+                })
+        };
+        let all = number("padding").unwrap_or(config.container_padding);
+        Padding {
+            top: number("padding_top").unwrap_or(all),
+            right: number("padding_right").unwrap_or(all),
+            bottom: number("padding_bottom").unwrap_or(all),
+            left: number("padding_left").unwrap_or(all),
+        }
+    }
+}
+
 fn layout_container(layout: &LayoutDecl, position: Point, config: &LayoutConfig) -> ElementLayout {
     // Check for a child with [role: label] modifier (preferred)
     // Falls back to Label statement (deprecated) if not found
@@ -1410,12 +2240,48 @@ fn layout_container(layout: &LayoutDecl, position: Point, config: &LayoutConfig)
 
     // Extract gap modifier from layout modifiers (can be negative for overlap)
     let gap = extract_gap(&layout.modifiers);
+    let align = extract_child_align(&layout.modifiers, "align").unwrap_or_default();
+    let padding = match layout.layout_type.node {
+        // Coordinates are exact, so nothing is inset
+        LayoutType::Absolute => Padding::default(),
+        _ => Padding::from_modifiers(&layout.modifiers, config),
+    };
 
     let (mut children, bounds) = match layout.layout_type.node {
-        LayoutType::Row => layout_row(&layout.children, position, config, gap),
-        LayoutType::Column => layout_column(&layout.children, position, config, gap),
-        LayoutType::Grid => layout_grid(&layout.children, position, config),
-        LayoutType::Stack => layout_stack(&layout.children, position, config),
+        LayoutType::Row => layout_row(&layout.children, position, config, padding, gap, align),
+        LayoutType::Column => {
+            layout_column(&layout.children, position, config, padding, gap, align)
+        }
+        LayoutType::Grid => layout_grid(&layout.children, position, config, padding),
+        LayoutType::Stack => layout_stack(&layout.children, position, config, padding),
+        LayoutType::Flow => layout_flow(
+            &layout.children,
+            position,
+            config,
+            padding,
+            gap,
+            extract_max_width(&layout.modifiers),
+        ),
+        LayoutType::Ring => layout_ring(
+            &layout.children,
+            position,
+            config,
+            padding,
+            gap,
+            extract_radius(&layout.modifiers),
+        ),
+        LayoutType::Timeline => layout_timeline(
+            &layout.children,
+            &layout.layout_type.span,
+            position,
+            config,
+            padding,
+            gap,
+            &layout.modifiers,
+        ),
+        LayoutType::Absolute => {
+            layout_absolute(&layout.children, position, config, &layout.modifiers)
+        }
     };
 
     let styles = ResolvedStyles::from_modifiers(&layout.modifiers);
@@ -1445,6 +2311,7 @@ fn layout_container(layout: &LayoutDecl, position: Point, config: &LayoutConfig)
             text,
             position: Point::new(bounds.x + bounds.width / 2.0, bounds.y - 5.0),
             anchor: TextAnchor::Middle,
+            vertical_align: VerticalAlign::Middle,
             styles: None,
         })
     };
@@ -1461,11 +2328,18 @@ fn layout_container(layout: &LayoutDecl, position: Point, config: &LayoutConfig)
         label,
         anchors,
         path_normalize: true,
-        z_order: 0,
+        z_order: extract_z_order(&layout.modifiers),
+        padding,
+        span: None,
+        pre_transform_size: None,
     }
 }
 
 fn layout_group(group: &GroupDecl, position: Point, config: &LayoutConfig) -> ElementLayout {
+    if let Some(layer) = &group.layer {
+        return layout_layer(group, &layer.node.0, position, config);
+    }
+
     // Check for a child with [role: label] modifier (preferred)
     // Falls back to Label statement (deprecated) if not found
     let role_label_stmt = extract_role_label_statement(&group.children);
@@ -1473,7 +2347,15 @@ fn layout_group(group: &GroupDecl, position: Point, config: &LayoutConfig) -> El
 
     // Groups default to column layout (no gap override)
     // Filter out Label statements from layout children
-    let (mut children, bounds) = layout_column(&group.children, position, config, None);
+    let padding = Padding::from_modifiers(&group.modifiers, config);
+    let (mut children, bounds) = layout_column(
+        &group.children,
+        position,
+        config,
+        padding,
+        None,
+        ChildAlign::Start,
+    );
 
     let styles = ResolvedStyles::from_modifiers(&group.modifiers);
 
@@ -1503,6 +2385,7 @@ fn layout_group(group: &GroupDecl, position: Point, config: &LayoutConfig) -> El
             text,
             position: Point::new(bounds.x - 10.0, bounds.y + bounds.height / 2.0),
             anchor: TextAnchor::End,
+            vertical_align: VerticalAlign::Middle,
             styles: None,
         })
     };
@@ -1515,19 +2398,6 @@ fn layout_group(group: &GroupDecl, position: Point, config: &LayoutConfig) -> El
         resolve_custom_anchors(&group.anchors, &children, &mut anchors);
     }
 
-    // Extract z_order from group modifiers
-    let z_order = group.modifiers.iter().find_map(|m| {
-        if matches!(m.node.key.node, StyleKey::ZOrder) {
-            if let StyleValue::Number { value, .. } = &m.node.value.node {
-                Some(*value as i32)
-            } else {
-                None
-            }
-        } else {
-            None
-        }
-    }).unwrap_or(0);
-
     ElementLayout {
         id: group.name.as_ref().map(|n| n.node.clone()),
         element_type: ElementType::Group,
@@ -1537,7 +2407,52 @@ fn layout_group(group: &GroupDecl, position: Point, config: &LayoutConfig) -> El
         label,
         anchors,
         path_normalize: true,
-        z_order,
+        z_order: extract_z_order(&group.modifiers),
+        padding,
+        span: None,
+        pre_transform_size: None,
+    }
+}
+
+/// Layers stack their content like a group but have no padding, label or
+/// background. `order` (default -1 for `background`, 1 for `overlay`, else 0)
+/// decides which layer is drawn on top.
+fn layout_layer(
+    group: &GroupDecl,
+    name: &str,
+    position: Point,
+    config: &LayoutConfig,
+) -> ElementLayout {
+    let (children, bounds) = layout_column(
+        &group.children,
+        position,
+        config,
+        Padding::default(),
+        None,
+        ChildAlign::Start,
+    );
+    let default_order = match name {
+        "background" => -1,
+        "overlay" => 1,
+        _ => 0,
+    };
+    let order = super::collector::extract_number_modifier(&group.modifiers, "order")
+        .map(|o| o as i32)
+        .unwrap_or(default_order);
+
+    ElementLayout {
+        id: None,
+        element_type: ElementType::Layer(name.to_string()),
+        bounds,
+        styles: ResolvedStyles::from_modifiers(&group.modifiers),
+        children,
+        label: None,
+        anchors: AnchorSet::simple_shape(&bounds),
+        path_normalize: true,
+        z_order: order,
+        padding: Padding::default(),
+        span: None,
+        pre_transform_size: None,
     }
 }
 
@@ -1653,10 +2568,13 @@ fn layout_row(
     children: &[Spanned<Statement>],
     position: Point,
     config: &LayoutConfig,
+    padding: Padding,
     gap_override: Option<f64>,
+    align: ChildAlign,
 ) -> (Vec<ElementLayout>, BoundingBox) {
     let mut layouts = vec![];
-    let mut x = position.x + config.container_padding;
+    let mut aligns = vec![];
+    let mut x = position.x + padding.left;
     let mut max_height = 0.0f64;
 
     // Use gap override if provided, otherwise use default element spacing
@@ -1676,22 +2594,23 @@ fn layout_row(
             continue;
         }
 
-        let child_layout = layout_statement(
-            &child.node,
-            Point::new(x, position.y + config.container_padding),
-            config,
-        );
+        let child_layout = layout_spanned(child, Point::new(x, position.y + padding.top), config);
         x += child_layout.bounds.width + spacing;
         max_height = max_height.max(child_layout.bounds.height);
         layouts.push(child_layout);
+        aligns.push(extract_self_align(&child.node).unwrap_or(align));
+    }
+
+    for (layout, align) in layouts.iter_mut().zip(aligns) {
+        align_child(layout, align, max_height, false);
     }
 
     let total_width = if layouts.is_empty() {
-        config.container_padding * 2.0
+        padding.left + padding.right
     } else {
-        x - position.x - spacing + config.container_padding
+        x - position.x - spacing + padding.right
     };
-    let total_height = max_height + 2.0 * config.container_padding;
+    let total_height = max_height + padding.top + padding.bottom;
 
     (
         layouts,
@@ -1703,10 +2622,13 @@ fn layout_column(
     children: &[Spanned<Statement>],
     position: Point,
     config: &LayoutConfig,
+    padding: Padding,
     gap_override: Option<f64>,
+    align: ChildAlign,
 ) -> (Vec<ElementLayout>, BoundingBox) {
     let mut layouts = vec![];
-    let mut y = position.y + config.container_padding;
+    let mut aligns = vec![];
+    let mut y = position.y + padding.top;
     let mut max_width = 0.0f64;
 
     // Use gap override if provided, otherwise use default element spacing
@@ -1726,21 +2648,22 @@ fn layout_column(
             continue;
         }
 
-        let child_layout = layout_statement(
-            &child.node,
-            Point::new(position.x + config.container_padding, y),
-            config,
-        );
+        let child_layout = layout_spanned(child, Point::new(position.x + padding.left, y), config);
         y += child_layout.bounds.height + spacing;
         max_width = max_width.max(child_layout.bounds.width);
         layouts.push(child_layout);
+        aligns.push(extract_self_align(&child.node).unwrap_or(align));
+    }
+
+    for (layout, align) in layouts.iter_mut().zip(aligns) {
+        align_child(layout, align, max_width, true);
     }
 
-    let total_width = max_width + 2.0 * config.container_padding;
+    let total_width = max_width + padding.left + padding.right;
     let total_height = if layouts.is_empty() {
-        config.container_padding * 2.0
+        padding.top + padding.bottom
     } else {
-        y - position.y - spacing + config.container_padding
+        y - position.y - spacing + padding.bottom
     };
 
     (
@@ -1753,6 +2676,7 @@ fn layout_grid(
     children: &[Spanned<Statement>],
     position: Point,
     config: &LayoutConfig,
+    padding: Padding,
 ) -> (Vec<ElementLayout>, BoundingBox) {
     // Filter out connections, constraints, and labels (labels are handled separately by parent)
     // Labels include both Statement::Label and elements with [role: label] modifier
@@ -1775,8 +2699,8 @@ fn layout_grid(
             BoundingBox::new(
                 position.x,
                 position.y,
-                config.container_padding * 2.0,
-                config.container_padding * 2.0,
+                padding.left + padding.right,
+                padding.top + padding.bottom,
             ),
         );
     }
@@ -1790,7 +2714,7 @@ fn layout_grid(
     let mut max_cell_height = 0.0f64;
 
     for child in &filtered {
-        let temp = layout_statement(&child.node, Point::new(0.0, 0.0), config);
+        let temp = layout_spanned(child, Point::new(0.0, 0.0), config);
         max_cell_width = max_cell_width.max(temp.bounds.width);
         max_cell_height = max_cell_height.max(temp.bounds.height);
     }
@@ -1800,21 +2724,19 @@ fn layout_grid(
     for (i, child) in filtered.iter().enumerate() {
         let row = i / cols;
         let col = i % cols;
-        let x = position.x
-            + config.container_padding
-            + col as f64 * (max_cell_width + config.element_spacing);
-        let y = position.y
-            + config.container_padding
-            + row as f64 * (max_cell_height + config.element_spacing);
-        layouts.push(layout_statement(&child.node, Point::new(x, y), config));
+        let x = position.x + padding.left + col as f64 * (max_cell_width + config.element_spacing);
+        let y = position.y + padding.top + row as f64 * (max_cell_height + config.element_spacing);
+        layouts.push(layout_spanned(child, Point::new(x, y), config));
     }
 
     let total_width = cols as f64 * (max_cell_width + config.element_spacing)
         - config.element_spacing
-        + 2.0 * config.container_padding;
+        + padding.left
+        + padding.right;
     let total_height = rows as f64 * (max_cell_height + config.element_spacing)
         - config.element_spacing
-        + 2.0 * config.container_padding;
+        + padding.top
+        + padding.bottom;
 
     (
         layouts,
@@ -1822,10 +2744,344 @@ fn layout_grid(
     )
 }
 
+/// Place children left-to-right, starting a new line when the next child would
+/// make the container wider than `max_width`. Without `max_width` this is a row
+/// aligned to the top.
+fn layout_flow(
+    children: &[Spanned<Statement>],
+    position: Point,
+    config: &LayoutConfig,
+    padding: Padding,
+    gap_override: Option<f64>,
+    max_width: Option<f64>,
+) -> (Vec<ElementLayout>, BoundingBox) {
+    let spacing = gap_override.unwrap_or(config.element_spacing);
+    let line_limit = max_width.map_or(f64::INFINITY, |w| w - padding.left - padding.right);
+
+    let mut layouts = vec![];
+    let (mut x, mut y) = (0.0f64, 0.0f64);
+    let mut line_height = 0.0f64;
+    let mut content_width = 0.0f64;
+
+    for child in children {
+        // Skip connections, constraints, and labels (labels are handled separately by parent)
+        if matches!(
+            child.node,
+            Statement::Connection(_)
+                | Statement::Constraint(_)
+                | Statement::Constrain(_)
+                | Statement::Label(_)
+        ) || has_role_label(&child.node)
+        {
+            continue;
+        }
+
+        let size = layout_spanned(child, Point::new(0.0, 0.0), config).bounds;
+        // Wrap unless this is the first child on the line (oversized children get their own line)
+        if x > 0.0 && x + size.width > line_limit {
+            y += line_height + spacing;
+            x = 0.0;
+            line_height = 0.0;
+        }
+
+        let child_layout = layout_spanned(
+            child,
+            Point::new(position.x + padding.left + x, position.y + padding.top + y),
+            config,
+        );
+        content_width = content_width.max(x + size.width);
+        line_height = line_height.max(size.height);
+        x += size.width + spacing;
+        layouts.push(child_layout);
+    }
+
+    let bounds = BoundingBox::new(
+        position.x,
+        position.y,
+        content_width + padding.left + padding.right,
+        y + line_height + padding.top + padding.bottom,
+    );
+    (layouts, bounds)
+}
+
+/// Place children evenly around a circle, clockwise starting at the top.
+///
+/// Each child's center sits on the circle. Without an explicit radius, the
+/// circle is just large enough for neighbours to keep `gap` between them. The
+/// container is centered on the circle, and every child gets `outward` and
+/// `inward` anchors pointing away from and towards the center.
+fn layout_ring(
+    children: &[Spanned<Statement>],
+    position: Point,
+    config: &LayoutConfig,
+    padding: Padding,
+    gap_override: Option<f64>,
+    radius: Option<f64>,
+) -> (Vec<ElementLayout>, BoundingBox) {
+    let spacing = gap_override.unwrap_or(config.element_spacing);
+
+    let mut layouts = vec![];
+    for child in children {
+        // Skip connections, constraints, and labels (labels are handled separately by parent)
+        if matches!(
+            child.node,
+            Statement::Connection(_)
+                | Statement::Constraint(_)
+                | Statement::Constrain(_)
+                | Statement::Label(_)
+        ) || has_role_label(&child.node)
+        {
+            continue;
+        }
+        layouts.push(layout_spanned(child, Point::new(0.0, 0.0), config));
+    }
+
+    let count = layouts.len();
+    let radius = radius.unwrap_or_else(|| {
+        if count < 2 {
+            return 0.0;
+        }
+        let largest = layouts
+            .iter()
+            .map(|l| l.bounds.width.max(l.bounds.height))
+            .fold(0.0, f64::max);
+        (largest + spacing) / (2.0 * (std::f64::consts::PI / count as f64).sin())
+    });
+
+    let angles: Vec<f64> = (0..count)
+        .map(|i| -90.0 + 360.0 * i as f64 / count as f64)
+        .collect();
+
+    // Half extents of the content around the ring center
+    let (mut half_width, mut half_height) = (0.0f64, 0.0f64);
+    for (layout, angle) in layouts.iter().zip(&angles) {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        half_width = half_width.max((radius * cos).abs() + layout.bounds.width / 2.0);
+        half_height = half_height.max((radius * sin).abs() + layout.bounds.height / 2.0);
+    }
+
+    let center = Point::new(
+        position.x + padding.left + half_width,
+        position.y + padding.top + half_height,
+    );
+    for (layout, angle) in layouts.iter_mut().zip(&angles) {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let dx = center.x + radius * cos - layout.bounds.width / 2.0 - layout.bounds.x;
+        let dy = center.y + radius * sin - layout.bounds.height / 2.0 - layout.bounds.y;
+        offset_element(layout, dx, dy);
+        layout.anchors.translate(dx, dy);
+        layout.anchors.insert_radial(&layout.bounds, *angle);
+    }
+
+    let bounds = BoundingBox::new(
+        position.x,
+        position.y,
+        2.0 * half_width + padding.left + padding.right,
+        2.0 * half_height + padding.top + padding.bottom,
+    );
+    (layouts, bounds)
+}
+
+/// Pixels per time unit on a timeline axis unless `unit_width: N` is given
+const TIMELINE_UNIT_WIDTH: f64 = 40.0;
+/// Default bar height of timeline shapes
+const TIMELINE_BAR_HEIGHT: f64 = 24.0;
+/// Height of the tick label row above the bars
+const TIMELINE_AXIS_HEIGHT: f64 = 20.0;
+/// Font size of the tick labels
+const TIMELINE_TICK_FONT_SIZE: f64 = 11.0;
+/// Automatic tick intervals are at least this many pixels apart
+const TIMELINE_MIN_TICK_SPACING: f64 = 40.0;
+
+/// Lay out children as bars on a horizontal time axis, one row each.
+///
+/// A child spans from its `start: N` to its `end: N` value; shapes are
+/// stretched to that width, other children are only placed at `start`.
+/// The axis is scaled by `unit_width: N` pixels per unit and labelled every
+/// `tick: N` units (by default the smallest 1, 2 or 5 times a power of ten
+/// that keeps labels apart). Tick labels and gridlines are generated as
+/// unnamed children drawn beneath the bars.
+fn layout_timeline(
+    children: &[Spanned<Statement>],
+    span: &Span,
+    position: Point,
+    config: &LayoutConfig,
+    padding: Padding,
+    gap_override: Option<f64>,
+    modifiers: &[Spanned<StyleModifier>],
+) -> (Vec<ElementLayout>, BoundingBox) {
+    use super::collector::extract_number_modifier;
+
+    let unit = extract_number_modifier(modifiers, "unit_width")
+        .filter(|u| *u > 0.0)
+        .unwrap_or(TIMELINE_UNIT_WIDTH);
+    let spacing = gap_override.unwrap_or(config.element_spacing / 2.0);
+
+    let bars: Vec<(&Spanned<Statement>, f64, Option<f64>)> = children
+        .iter()
+        .filter(|child| {
+            !matches!(
+                child.node,
+                Statement::Connection(_)
+                    | Statement::Constraint(_)
+                    | Statement::Constrain(_)
+                    | Statement::Label(_)
+            ) && !has_role_label(&child.node)
+        })
+        .map(|child| {
+            let mods: &[Spanned<StyleModifier>] = match &child.node {
+                Statement::Shape(s) => &s.modifiers,
+                Statement::Layout(l) => &l.modifiers,
+                Statement::Group(g) => &g.modifiers,
+                _ => &[],
+            };
+            let start = extract_number_modifier(mods, "start").unwrap_or(0.0);
+            let end = extract_number_modifier(mods, "end").filter(|e| *e > start);
+            (child, start, end)
+        })
+        .collect();
+
+    let t_min = bars.iter().map(|b| b.1).fold(0.0f64, f64::min);
+    let t_max = bars
+        .iter()
+        .map(|b| b.2.unwrap_or(b.1))
+        .fold(t_min, f64::max);
+    let tick = extract_number_modifier(modifiers, "tick")
+        .filter(|t| *t > 0.0)
+        .unwrap_or_else(|| auto_tick(TIMELINE_MIN_TICK_SPACING / unit));
+    let axis_start = (t_min / tick).floor() * tick;
+    let axis_end = ((t_max / tick).ceil() * tick).max(axis_start + tick);
+
+    let origin_x = position.x + padding.left;
+    let bars_top = position.y + padding.top + TIMELINE_AXIS_HEIGHT;
+    let to_x = |t: f64| origin_x + (t - axis_start) * unit;
+
+    let mut rows = vec![];
+    let mut y = bars_top;
+    for (child, start, end) in bars {
+        let stmt = match (&child.node, end) {
+            (Statement::Shape(shape), Some(end)) => {
+                let mut shape = shape.clone();
+                shape.modifiers = with_defaults(
+                    vec![modifier(
+                        StyleKey::Height,
+                        number(TIMELINE_BAR_HEIGHT),
+                        span,
+                    )],
+                    &shape.modifiers,
+                );
+                shape.modifiers.push(modifier(
+                    StyleKey::Width,
+                    number((end - start) * unit),
+                    span,
+                ));
+                Spanned::new(Statement::Shape(shape), child.span.clone())
+            }
+            _ => child.clone(),
+        };
+        let layout = layout_spanned(&stmt, Point::new(to_x(start), y), config);
+        y += layout.bounds.height + spacing;
+        rows.push(layout);
+    }
+    let bars_bottom = if rows.is_empty() { y } else { y - spacing };
+
+    let mut layouts = vec![];
+    let ticks = ((axis_end - axis_start) / tick).round() as usize;
+    for i in 0..=ticks {
+        let t = axis_start + i as f64 * tick;
+        let x = to_x(t);
+        let line = ShapeDecl {
+            shape_type: Spanned::new(ShapeType::Rectangle, span.clone()),
+            name: None,
+            modifiers: vec![
+                modifier(StyleKey::Width, number(1.0), span),
+                modifier(StyleKey::Height, number(bars_bottom - bars_top), span),
+                modifier(
+                    StyleKey::Fill,
+                    StyleValue::Color(ColorValue::Symbolic {
+                        category: ColorCategory::Foreground,
+                        variant: Some(3),
+                        lightness: None,
+                    }),
+                    span,
+                ),
+                modifier(StyleKey::Stroke, StyleValue::Keyword("none".into()), span),
+                modifier(StyleKey::Opacity, number(0.5), span),
+                modifier(
+                    StyleKey::Class,
+                    StyleValue::Keyword("timeline-grid".into()),
+                    span,
+                ),
+            ],
+            ports: vec![],
+        };
+        layouts.push(layout_shape(&line, Point::new(x - 0.5, bars_top), config));
+
+        let content = format!("{}", (t * 1000.0).round() / 1000.0);
+        // Text is drawn from its left edge, and its bounds have a minimum
+        // width, so center the estimated glyph run on the tick instead
+        let text_width =
+            content.len() as f64 * TIMELINE_TICK_FONT_SIZE * 0.6 * config.text_width_scale;
+        let label = ShapeDecl {
+            shape_type: Spanned::new(ShapeType::Text { content }, span.clone()),
+            name: None,
+            modifiers: vec![
+                modifier(StyleKey::FontSize, number(TIMELINE_TICK_FONT_SIZE), span),
+                modifier(
+                    StyleKey::Class,
+                    StyleValue::Keyword("timeline-tick".into()),
+                    span,
+                ),
+            ],
+            ports: vec![],
+        };
+        let mut text = layout_shape(&label, Point::new(0.0, 0.0), config);
+        let dx = x - text_width / 2.0 - text.bounds.x;
+        let dy = bars_top - TIMELINE_AXIS_HEIGHT / 2.0 - text.bounds.height / 2.0 - text.bounds.y;
+        offset_element(&mut text, dx, dy);
+        text.anchors.translate(dx, dy);
+        layouts.push(text);
+    }
+    layouts.extend(rows);
+
+    // Content may stick out past the axis (labels around the outer ticks,
+    // children without an end), so size the box from what was placed
+    let right = layouts
+        .iter()
+        .map(|l| l.bounds.right())
+        .fold(to_x(axis_end), f64::max);
+    let left = layouts.iter().map(|l| l.bounds.x).fold(origin_x, f64::min);
+    if left < origin_x {
+        let dx = origin_x - left;
+        for layout in &mut layouts {
+            offset_element(layout, dx, 0.0);
+            layout.anchors.translate(dx, 0.0);
+        }
+    }
+    let bounds = BoundingBox::new(
+        position.x,
+        position.y,
+        right + (origin_x - left).max(0.0) - position.x + padding.right,
+        bars_bottom - position.y + padding.bottom,
+    );
+    (layouts, bounds)
+}
+
+/// The smallest 1, 2 or 5 times a power of ten that is at least `min`
+fn auto_tick(min: f64) -> f64 {
+    let magnitude = 10f64.powf(min.max(f64::EPSILON).log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|t| *t >= min)
+        .unwrap_or(10.0 * magnitude)
+}
+
 fn layout_stack(
     children: &[Spanned<Statement>],
     position: Point,
     config: &LayoutConfig,
+    padding: Padding,
 ) -> (Vec<ElementLayout>, BoundingBox) {
     // First pass: compute all layouts and find max size
     let mut temp_layouts = vec![];
@@ -1846,12 +3102,9 @@ fn layout_stack(
             continue;
         }
 
-        let child_layout = layout_statement(
-            &child.node,
-            Point::new(
-                position.x + config.container_padding,
-                position.y + config.container_padding,
-            ),
+        let child_layout = layout_spanned(
+            child,
+            Point::new(position.x + padding.left, position.y + padding.top),
             config,
         );
         max_width = max_width.max(child_layout.bounds.width);
@@ -1873,12 +3126,67 @@ fn layout_stack(
         BoundingBox::new(
             position.x,
             position.y,
-            max_width + 2.0 * config.container_padding,
-            max_height + 2.0 * config.container_padding,
+            max_width + padding.left + padding.right,
+            max_height + padding.top + padding.bottom,
         ),
     )
 }
 
+/// Place every child at its `x`/`y` modifiers as exact canvas coordinates,
+/// without stacking or `element_spacing`. Children without coordinates sit at
+/// the container's origin. The container reaches from its origin to the
+/// farthest child edge, or to its own `width`/`height` when those are larger.
+fn layout_absolute(
+    children: &[Spanned<Statement>],
+    position: Point,
+    config: &LayoutConfig,
+    modifiers: &[Spanned<StyleModifier>],
+) -> (Vec<ElementLayout>, BoundingBox) {
+    let coordinate = |mods: &[Spanned<StyleModifier>], axis: &StyleKey| {
+        mods.iter().rev().find_map(|m| match &m.node.value.node {
+            StyleValue::Number { value, .. } if &m.node.key.node == axis => Some(*value),
+            _ => None,
+        })
+    };
+
+    let mut layouts = vec![];
+    let mut bounds = BoundingBox::new(
+        position.x,
+        position.y,
+        extract_width_modifier(modifiers).unwrap_or(0.0),
+        extract_height_modifier(modifiers).unwrap_or(0.0),
+    );
+    for child in children {
+        // Skip connections, constraints, and labels (labels are handled separately by parent)
+        if matches!(
+            child.node,
+            Statement::Connection(_)
+                | Statement::Constraint(_)
+                | Statement::Constrain(_)
+                | Statement::Label(_)
+        ) || has_role_label(&child.node)
+        {
+            continue;
+        }
+
+        let mods: &[Spanned<StyleModifier>] = match &child.node {
+            Statement::Shape(s) => &s.modifiers,
+            Statement::Layout(l) => &l.modifiers,
+            Statement::Group(g) => &g.modifiers,
+            _ => &[],
+        };
+        let at = Point::new(
+            coordinate(mods, &StyleKey::X).unwrap_or(position.x),
+            coordinate(mods, &StyleKey::Y).unwrap_or(position.y),
+        );
+        let layout = layout_spanned(child, at, config);
+        bounds = bounds.union(&layout.bounds);
+        layouts.push(layout);
+    }
+
+    (layouts, bounds)
+}
+
 /// Recursively offset an element and all its children
 fn offset_element(element: &mut ElementLayout, dx: f64, dy: f64) {
     element.bounds.x += dx;
@@ -2300,12 +3608,35 @@ fn shift_element_and_children(elem: &mut ElementLayout, delta: f64, axis: Axis)
 /// * `result` - The layout result to update
 /// * `doc` - The document containing constraints
 /// * `config` - Layout configuration
-/// * `template_rotations` - Map from template instance name to rotation angle in degrees
+/// * `template_rotations` - Map from rotated element name (template instance, shape,
+///   group or layout) to rotation angle in degrees
 pub fn resolve_constrain_statements_two_phase(
     result: &mut LayoutResult,
     doc: &Document,
     config: &LayoutConfig,
     template_rotations: &HashMap<String, f64>,
+) -> Result<(), LayoutError> {
+    let transforms: HashMap<String, ElementTransform> = template_rotations
+        .iter()
+        .map(|(name, angle)| (name.clone(), ElementTransform::rotation(*angle)))
+        .collect();
+    resolve_constrain_statements_with_transforms(result, doc, config, &transforms)
+}
+
+/// Resolve `constrain` statements with the two-phase architecture, applying
+/// each element's rotation, scale and flip between the local and global phases.
+///
+/// # Arguments
+/// * `result` - The layout result to update
+/// * `doc` - The document containing constraints
+/// * `config` - Layout configuration
+/// * `transforms` - Map from transformed element name (template instance, shape,
+///   group or layout) to its transform
+pub fn resolve_constrain_statements_with_transforms(
+    result: &mut LayoutResult,
+    doc: &Document,
+    config: &LayoutConfig,
+    transforms: &HashMap<String, ElementTransform>,
 ) -> Result<(), LayoutError> {
     use super::collector::ConstraintCollector;
 
@@ -2313,23 +3644,33 @@ pub fn resolve_constrain_statements_two_phase(
     let mut collector = ConstraintCollector::new(config.clone());
 
     // Collect row/col alignment constraints (siblings stay aligned)
-    collect_layout_alignment_constraints(&doc.statements, &mut collector);
+    collect_layout_alignment_constraints(&doc.statements, &mut collector, result);
 
     // Collect user constraints (constrain statements)
     // Anchor-based constraints are automatically deferred by the collector (Feature 011)
-    collect_constrain_statements(&doc.statements, &mut collector);
+    collect_constrain_statements(&doc.statements, &mut collector, result);
+
+    // Pinned shapes take their x/y directly; the solver then holds them there
+    apply_pinned_positions(&doc.statements, result)?;
 
     // Also collect x/y modifiers from shapes as position constraints
     collect_position_constraints_from_shapes(&doc.statements, &mut collector);
 
-    // Only return early if there are no constraints AND no rotations AND no deferred anchors
+    // Only return early if there are no constraints AND no transforms AND no deferred anchors
     let has_deferred_anchors = !collector.deferred_anchor_constraints.is_empty();
-    if collector.constraints.is_empty() && template_rotations.is_empty() && !has_deferred_anchors {
+    if collector.constraints.is_empty() && transforms.is_empty() && !has_deferred_anchors {
         return Ok(());
     }
 
-    // Build element-to-template mapping and group anchor declarations
-    let element_to_template = build_element_to_template_map(doc);
+    // Build element-to-template mapping and group anchor declarations. Transformed
+    // groups and layouts own their children the way template instances do.
+    let mut element_to_template = build_element_to_template_map(doc);
+    map_transformed_descendants(
+        &result.root_elements,
+        transforms,
+        None,
+        &mut element_to_template,
+    );
     let group_anchor_decls = build_group_anchor_decl_map(doc);
 
     // Partition constraints into local (per-template) and global
@@ -2354,6 +3695,9 @@ pub fn resolve_constrain_statements_two_phase(
 
     // Phase 1 & 2: Solve local constraints for each template, then apply rotation
     let mut local_results: HashMap<String, LocalSolverResult> = HashMap::new();
+    // Solved values carried into the global phase. Rotated templates are left
+    // out: their local values are in the pre-rotation frame.
+    let mut warm_start = super::solver::Solution::default();
 
     // IMPORTANT: Sort instance names for deterministic constraint solving order.
     // HashMap iteration order is nondeterministic, which can cause the solver
@@ -2361,10 +3705,14 @@ pub fn resolve_constrain_statements_two_phase(
     let mut sorted_instances: Vec<_> = local_by_instance.keys().cloned().collect();
     sorted_instances.sort();
 
+    // One solver for every local pass and the global one, so variables and
+    // suggestions are created once and later passes only edit them
+    let mut solver = super::solver::ConstraintSolver::new();
     for instance in &sorted_instances {
         let local_constraints = local_by_instance.get(instance).unwrap();
         // Phase 1: Solve local constraints
         let mut local_result = solve_local(
+            &mut solver,
             instance,
             local_constraints,
             result,
@@ -2372,33 +3720,33 @@ pub fn resolve_constrain_statements_two_phase(
             &group_anchor_decls,
         )?;
 
-        // Phase 2: Apply rotation if this template has one
-        if let Some(&angle) = template_rotations.get(instance) {
-            if angle.abs() > f64::EPSILON {
+        // Phase 2: Apply the transform if this template has one
+        if let Some(transform) = transforms.get(instance) {
+            if !transform.is_identity() {
                 if config.trace {
-                    eprintln!(
-                        "TRACE: Applying {}° rotation to template '{}'",
-                        angle, instance
-                    );
+                    eprintln!("TRACE: Applying {:?} to template '{}'", transform, instance);
                 }
-                apply_rotation_to_local_result(&mut local_result, angle);
+                apply_transform_to_local_result(&mut local_result, transform);
             }
         }
+        if local_result.rotation.is_none() {
+            warm_start.extend(&local_result.solution);
+        }
 
         local_results.insert(instance.clone(), local_result);
     }
 
-    // Phase 2b: Handle templates with rotation but no constraints
-    // These still need rotation applied to their bounds and anchors
+    // Phase 2b: Handle transformed templates without constraints
+    // These still need the transform applied to their bounds and anchors
     if config.trace {
         eprintln!(
-            "TRACE: Phase 2b - Processing {} templates with rotation",
-            template_rotations.len()
+            "TRACE: Phase 2b - Processing {} transformed templates",
+            transforms.len()
         );
     }
-    for (instance, &angle) in template_rotations {
+    for (instance, transform) in transforms {
         if config.trace {
-            eprintln!("TRACE: Checking template '{}' angle={}", instance, angle);
+            eprintln!("TRACE: Checking template '{}' {:?}", instance, transform);
         }
         if local_results.contains_key(instance) {
             if config.trace {
@@ -2406,22 +3754,23 @@ pub fn resolve_constrain_statements_two_phase(
             }
             continue; // Already processed above
         }
-        if angle.abs() <= f64::EPSILON {
+        if transform.is_identity() {
             if config.trace {
-                eprintln!("TRACE: Skipping '{}' - angle is ~0", instance);
+                eprintln!("TRACE: Skipping '{}' - identity transform", instance);
             }
-            continue; // No rotation to apply
+            continue; // No transform to apply
         }
 
         if config.trace {
             eprintln!(
-                "TRACE: Applying {}° rotation to template '{}' (no constraints)",
-                angle, instance
+                "TRACE: Applying {:?} to template '{}' (no constraints)",
+                transform, instance
             );
         }
 
         // Create a LocalSolverResult with current bounds for all elements in this template
-        let mut local_result = LocalSolverResult::new(instance.clone()).with_rotation(angle);
+        let mut local_result =
+            LocalSolverResult::new(instance.clone()).with_rotation(transform.rotation);
 
         // Find all elements belonging to this template instance
         for (elem_id, template_name) in &element_to_template {
@@ -2439,26 +3788,26 @@ pub fn resolve_constrain_statements_two_phase(
             local_result.add_anchors(instance.clone(), elem.anchors.clone());
         }
 
-        // Apply rotation
-        apply_rotation_to_local_result(&mut local_result, angle);
+        // Apply the transform
+        apply_transform_to_local_result(&mut local_result, transform);
         local_results.insert(instance.clone(), local_result);
     }
 
     // Phase 3: Apply local results back to the layout
     apply_local_results(result, &local_results);
 
-    let rotated_instances: HashSet<String> = template_rotations
+    let rotated_instances: HashSet<String> = transforms
         .iter()
-        .filter_map(|(name, angle)| {
-            if angle.abs() > f64::EPSILON {
-                Some(name.clone())
-            } else {
+        .filter_map(|(name, transform)| {
+            if transform.is_identity() {
                 None
+            } else {
+                Some(name.clone())
             }
         })
         .collect();
 
-    // Recompute group bounds after local constraints, but keep rotated template bounds
+    // Recompute group bounds after local constraints, but keep transformed template bounds
     let skip_groups = if rotated_instances.is_empty() {
         None
     } else {
@@ -2466,15 +3815,23 @@ pub fn resolve_constrain_statements_two_phase(
     };
     recompute_group_bounds(result, skip_groups);
 
-    // Apply render-time rotation to template instance groups
-    for (instance, angle) in template_rotations {
-        if angle.abs() < f64::EPSILON {
+    // Apply the render-time transform, keeping the untransformed size to draw at
+    for (instance, transform) in transforms {
+        if transform.is_identity() {
             continue;
         }
+        let size = local_results
+            .get(instance)
+            .and_then(|r| r.pre_rotation_bounds.get(instance))
+            .map(|b| (b.width, b.height));
         if let Some(elem) = result.elements.get_mut(instance) {
-            elem.styles.rotation = Some(*angle);
+            set_transform_styles(elem, transform);
+            elem.pre_transform_size = size;
+        }
+        if let Some(elem) = result.get_element_mut_by_name(instance) {
+            set_transform_styles(elem, transform);
+            elem.pre_transform_size = size;
         }
-        update_element_rotation_in_tree(&mut result.root_elements, instance, *angle);
     }
 
     // Phase 3b: Resolve deferred anchor constraints (Feature 011)
@@ -2482,18 +3839,8 @@ pub fn resolve_constrain_statements_two_phase(
     // anchor positions are accurate. Resolve anchor refs to Fixed constraints.
     let mut all_global = global_constraints;
     if !collector.deferred_anchor_constraints.is_empty() {
-        // Build skip set for rotated template internals — must NOT overwrite their anchors
-        let mut skip_anchors_3b: HashSet<String> = HashSet::new();
-        for (elem_id, template_name) in &element_to_template {
-            if template_rotations
-                .get(template_name)
-                .map(|angle| angle.abs() > f64::EPSILON)
-                .unwrap_or(false)
-            {
-                skip_anchors_3b.insert(elem_id.clone());
-                skip_anchors_3b.insert(template_name.clone());
-            }
-        }
+        // Build skip set for transformed element internals — must NOT overwrite their anchors
+        let skip_anchors_3b = transformed_element_ids(result, transforms);
         let skip_3b = if skip_anchors_3b.is_empty() {
             None
         } else {
@@ -2501,7 +3848,7 @@ pub fn resolve_constrain_statements_two_phase(
         };
 
         // Recompute anchors so positions reflect post-local-solve state,
-        // but skip rotated template internals (their anchors are already correct)
+        // but skip transformed template internals (their anchors are already correct)
         recompute_builtin_anchors(result, skip_3b);
         recompute_custom_anchors(result, doc, skip_3b);
 
@@ -2516,20 +3863,17 @@ pub fn resolve_constrain_statements_two_phase(
     }
 
     // Phase 4: Solve global constraints (using post-rotation positions)
-    solve_global(result, &all_global, &element_to_template, config)?;
-
-    // Build skip set for rotated template internals
-    let mut skip_anchors: HashSet<String> = HashSet::new();
-    for (elem_id, template_name) in &element_to_template {
-        if template_rotations
-            .get(template_name)
-            .map(|angle| angle.abs() > f64::EPSILON)
-            .unwrap_or(false)
-        {
-            skip_anchors.insert(elem_id.clone());
-            skip_anchors.insert(template_name.clone());
-        }
-    }
+    solve_global(
+        &mut solver,
+        result,
+        &all_global,
+        &element_to_template,
+        &warm_start,
+        config,
+    )?;
+
+    // Build skip set for transformed element internals
+    let skip_anchors = transformed_element_ids(result, transforms);
 
     // Recompute bounds and anchors after applying all constraints
     result.compute_bounds();
@@ -2560,11 +3904,14 @@ pub fn resolve_constrain_statements(
     let mut collector = ConstraintCollector::new(config.clone());
 
     // Collect row/col alignment constraints (siblings stay aligned)
-    collect_layout_alignment_constraints(&doc.statements, &mut collector);
+    collect_layout_alignment_constraints(&doc.statements, &mut collector, result);
 
     // Collect user constraints (constrain statements)
     // Anchor-based constraints are automatically deferred by the collector (Feature 011)
-    collect_constrain_statements(&doc.statements, &mut collector);
+    collect_constrain_statements(&doc.statements, &mut collector, result);
+
+    // Pinned shapes take their x/y directly; the solver then holds them there
+    apply_pinned_positions(&doc.statements, result)?;
 
     // Also collect x/y modifiers from shapes as position constraints
     collect_position_constraints_from_shapes(&doc.statements, &mut collector);
@@ -2594,6 +3941,11 @@ pub fn resolve_constrain_statements(
         .flat_map(|k| local_by_instance.remove(&k).unwrap_or_default())
         .collect();
 
+    // Positions solved in pass 1, carried into pass 2 as its starting point
+    let mut warm_start = super::solver::Solution::default();
+    // Both passes share a solver; pass 2 edits the suggestions of pass 1
+    let mut solver = ConstraintSolver::new();
+
     // PASS 1: Solve internal constraints first
     // These position children relative to each other within their groups
     if !internal_constraints.is_empty() {
@@ -2602,7 +3954,7 @@ pub fn resolve_constrain_statements(
         // elements are fixed (REQUIRED), preventing nondeterministic pivot choices.
         let target_vars: std::collections::HashSet<(String, LayoutProperty)> = internal_constraints
             .iter()
-            .filter_map(get_constraint_target_var)
+            .filter_map(|c| get_constraint_target_var(c, result))
             .collect();
 
         let referenced_elements: std::collections::HashSet<String> = internal_constraints
@@ -2610,8 +3962,6 @@ pub fn resolve_constrain_statements(
             .flat_map(get_constraint_referenced_elements)
             .collect();
 
-        let mut internal_solver = ConstraintSolver::new();
-
         // Sort referenced elements for deterministic solver input order
         let mut sorted_refs: Vec<&String> = referenced_elements.iter().collect();
         sorted_refs.sort();
@@ -2620,7 +3970,7 @@ pub fn resolve_constrain_statements(
         // For each property: if it's targeted → SUGGESTED (can move), else → FIXED (reference)
         for element_name in &sorted_refs {
             add_element_by_name_with_per_property_strength(
-                &mut internal_solver,
+                &mut solver,
                 result,
                 element_name,
                 &target_vars,
@@ -2635,12 +3985,12 @@ pub fn resolve_constrain_statements(
         }
 
         for constraint in &internal_constraints {
-            internal_solver
+            solver
                 .add_constraint(constraint.clone())
                 .map_err(LayoutError::solver_error)?;
         }
 
-        let internal_solution = internal_solver.solve().map_err(LayoutError::solver_error)?;
+        let internal_solution = solver.solve().map_err(LayoutError::solver_error)?;
 
         // Apply internal solution - shift children within their groups
         // Sort for deterministic application order
@@ -2669,6 +4019,13 @@ pub fn resolve_constrain_statements(
             }
         }
 
+        // Only positions are applied above, so only positions carry over
+        warm_start.values = internal_solution
+            .values
+            .into_iter()
+            .filter(|(var, _)| matches!(var.property, LayoutProperty::X | LayoutProperty::Y))
+            .collect();
+
         // Recompute group bounds after internal constraints
         recompute_group_bounds(result, None);
     }
@@ -2697,7 +4054,7 @@ pub fn resolve_constrain_statements(
         // We only want to move the specific property that is targeted
         let target_vars: std::collections::HashSet<(String, LayoutProperty)> = external_constraints
             .iter()
-            .filter_map(get_constraint_target_var)
+            .filter_map(|c| get_constraint_target_var(c, result))
             .collect();
 
         // Collect all elements referenced in external constraints
@@ -2707,7 +4064,10 @@ pub fn resolve_constrain_statements(
             .flat_map(get_constraint_referenced_elements)
             .collect();
 
-        let mut external_solver = ConstraintSolver::new();
+        solver
+            .clear_constraints()
+            .map_err(LayoutError::solver_error)?;
+        solver.set_warm_start(&warm_start);
 
         // Sort referenced elements for deterministic solver input order.
         // HashSet iteration is nondeterministic; the Cassowary solver can produce
@@ -2719,7 +4079,7 @@ pub fn resolve_constrain_statements(
         // For each property: if it's targeted → SUGGESTED (can move), else → FIXED (reference)
         for element_name in &sorted_refs {
             add_element_by_name_with_per_property_strength(
-                &mut external_solver,
+                &mut solver,
                 result,
                 element_name,
                 &target_vars,
@@ -2728,12 +4088,12 @@ pub fn resolve_constrain_statements(
         }
 
         for constraint in &external_constraints {
-            external_solver
+            solver
                 .add_constraint(constraint.clone())
                 .map_err(LayoutError::solver_error)?;
         }
 
-        let external_solution = external_solver.solve().map_err(LayoutError::solver_error)?;
+        let external_solution = solver.solve().map_err(LayoutError::solver_error)?;
 
         // Trace: print all solution values
         if config.trace {
@@ -3046,16 +4406,33 @@ fn update_element_anchors_recursive(elem: &mut ElementLayout, name: &str, anchor
 /// Extract the target (element_id, property) from a constraint
 /// For Equal constraints, we extract the left-hand side variable
 /// For Midpoint constraints, we extract the target variable
+/// When a two-sided constraint's left element is pinned, the right side moves instead
 fn get_constraint_target_var(
     constraint: &super::solver::LayoutConstraint,
+    result: &LayoutResult,
 ) -> Option<(String, super::solver::LayoutProperty)> {
     use super::solver::LayoutConstraint;
 
+    let pinned = |id: &str| {
+        result
+            .get_element_by_name(id)
+            .is_some_and(|e| e.styles.pinned)
+    };
     match constraint {
+        LayoutConstraint::Equal { left, right, .. }
+        | LayoutConstraint::LessOrEqualRelational { left, right, .. }
+        | LayoutConstraint::GreaterOrEqualRelational { left, right, .. }
+            if pinned(&left.element_id) =>
+        {
+            Some((right.element_id.clone(), right.property))
+        }
         LayoutConstraint::Equal { left, .. } => Some((left.element_id.clone(), left.property)),
         LayoutConstraint::Midpoint { target, .. } => {
             Some((target.element_id.clone(), target.property))
         }
+        LayoutConstraint::Linear { target, .. } => {
+            Some((target.element_id.clone(), target.property))
+        }
         LayoutConstraint::GreaterOrEqual { variable, .. } => {
             Some((variable.element_id.clone(), variable.property))
         }
@@ -3093,6 +4470,11 @@ fn get_constraint_referenced_elements(constraint: &super::solver::LayoutConstrai
                 b.element_id.clone(),
             ]
         }
+        LayoutConstraint::Linear { target, terms, .. } => {
+            std::iter::once(target.element_id.clone())
+                .chain(terms.iter().map(|(_, v)| v.element_id.clone()))
+                .collect()
+        }
         LayoutConstraint::GreaterOrEqual { variable, .. } => {
             vec![variable.element_id.clone()]
         }
@@ -3222,7 +4604,13 @@ fn recompute_element_bounds_recursive(elem: &mut ElementLayout, skip: Option<&Ha
         for child in &elem.children[1..] {
             bounds = bounds.union(&child.bounds);
         }
-        elem.bounds = bounds;
+        let p = elem.padding;
+        elem.bounds = BoundingBox::new(
+            bounds.x - p.left,
+            bounds.y - p.top,
+            bounds.width + p.left + p.right,
+            bounds.height + p.top + p.bottom,
+        );
     }
 }
 
@@ -3239,17 +4627,26 @@ fn collect_bounds_updates(elem: &ElementLayout, updates: &mut Vec<(String, Bound
 fn collect_constrain_statements(
     stmts: &[Spanned<Statement>],
     collector: &mut super::collector::ConstraintCollector,
+    result: &LayoutResult,
 ) {
     for stmt in stmts {
         match &stmt.node {
             Statement::Constrain(c) => {
-                collector.collect_constrain_expr(&c.expr, &stmt.span);
+                collector.collect_constrain_decl(c, &stmt.span);
+                // Equal-size sets grow to the largest member's current size
+                if let ConstraintExpr::EqualSize {
+                    dimension,
+                    elements,
+                } = &c.expr
+                {
+                    collector.collect_equal_size_minimums(*dimension, elements, &stmt.span, result);
+                }
             }
             Statement::Layout(l) => {
-                collect_constrain_statements(&l.children, collector);
+                collect_constrain_statements(&l.children, collector, result);
             }
             Statement::Group(g) => {
-                collect_constrain_statements(&g.children, collector);
+                collect_constrain_statements(&g.children, collector, result);
             }
             _ => {}
         }
@@ -3265,26 +4662,62 @@ fn collect_constrain_statements(
 fn collect_layout_alignment_constraints(
     stmts: &[Spanned<Statement>],
     collector: &mut super::collector::ConstraintCollector,
+    result: &LayoutResult,
 ) {
-    use super::solver::{ConstraintSource, LayoutConstraint, LayoutVariable};
+    use super::solver::{ConstraintSource, LayoutConstraint, LayoutProperty, LayoutVariable};
     use crate::parser::ast::LayoutType;
 
     for stmt in stmts {
         match &stmt.node {
             Statement::Layout(l) => {
-                // Collect child IDs
-                let child_ids: Vec<String> = l
+                // Collect child IDs with their effective cross-axis alignment
+                let align = extract_child_align(&l.modifiers, "align").unwrap_or_default();
+                let (child_ids, child_aligns): (Vec<String>, Vec<ChildAlign>) = l
                     .children
                     .iter()
-                    .filter_map(|child| match &child.node {
-                        Statement::Shape(s) => s.name.as_ref().map(|n| n.node.0.clone()),
-                        Statement::Layout(inner_l) => {
-                            inner_l.name.as_ref().map(|n| n.node.0.clone())
+                    .filter_map(|child| {
+                        let id = match &child.node {
+                            Statement::Shape(s) => s.name.as_ref().map(|n| n.node.0.clone()),
+                            Statement::Layout(inner_l) => {
+                                inner_l.name.as_ref().map(|n| n.node.0.clone())
+                            }
+                            Statement::Group(g) => g.name.as_ref().map(|n| n.node.0.clone()),
+                            _ => None,
+                        }?;
+                        Some((id, extract_self_align(&child.node).unwrap_or(align)))
+                    })
+                    .unzip();
+
+                // Children align against the one with the largest cross size, whose
+                // start, center and end coincide with the container's content box
+                let cross_size = |id: &String, horizontal: bool| {
+                    result.get_element_by_name(id).map_or(0.0, |e| {
+                        if horizontal {
+                            e.bounds.width
+                        } else {
+                            e.bounds.height
                         }
-                        Statement::Group(g) => g.name.as_ref().map(|n| n.node.0.clone()),
-                        _ => None,
                     })
-                    .collect();
+                };
+                let reference = |horizontal: bool| {
+                    (0..child_ids.len())
+                        .max_by(|&a, &b| {
+                            cross_size(&child_ids[a], horizontal)
+                                .total_cmp(&cross_size(&child_ids[b], horizontal))
+                                .then(b.cmp(&a))
+                        })
+                        .unwrap_or(0)
+                };
+                // Stretched children already span the cross axis, so they pin their start
+                let cross_property = |align: ChildAlign, horizontal: bool| match (align, horizontal)
+                {
+                    (ChildAlign::Start | ChildAlign::Stretch, false) => LayoutProperty::Y,
+                    (ChildAlign::Center, false) => LayoutProperty::CenterY,
+                    (ChildAlign::End, false) => LayoutProperty::Bottom,
+                    (ChildAlign::Start | ChildAlign::Stretch, true) => LayoutProperty::X,
+                    (ChildAlign::Center, true) => LayoutProperty::CenterX,
+                    (ChildAlign::End, true) => LayoutProperty::Right,
+                };
 
                 // Extract gap from modifiers
                 let gap = crate::layout::collector::extract_number_modifier(&l.modifiers, "gap")
@@ -3305,17 +4738,20 @@ fn collect_layout_alignment_constraints(
                 if child_ids.len() > 1 {
                     match l.layout_type.node {
                         LayoutType::Row => {
-                            for i in 1..child_ids.len() {
+                            let r = reference(false);
+                            for i in (0..child_ids.len()).filter(|&i| i != r) {
+                                let property = cross_property(child_aligns[i], false);
                                 collector.constraints.push(LayoutConstraint::Equal {
-                                    left: LayoutVariable::y(&child_ids[i]),
-                                    right: LayoutVariable::y(&child_ids[0]),
+                                    left: LayoutVariable::new(&child_ids[i], property),
+                                    right: LayoutVariable::new(&child_ids[r], property),
                                     offset: 0.0,
                                     source: make_source(format!(
-                                        "row alignment: {}.y = {}.y",
-                                        child_ids[i], child_ids[0]
+                                        "row alignment: {} with {} ({:?})",
+                                        child_ids[i], child_ids[r], child_aligns[i]
                                     )),
                                 });
-
+                            }
+                            for i in 1..child_ids.len() {
                                 collector.constraints.push(LayoutConstraint::Equal {
                                     left: LayoutVariable::x(&child_ids[i]),
                                     right: LayoutVariable::new(
@@ -3333,17 +4769,20 @@ fn collect_layout_alignment_constraints(
                             }
                         }
                         LayoutType::Column => {
-                            for i in 1..child_ids.len() {
+                            let r = reference(true);
+                            for i in (0..child_ids.len()).filter(|&i| i != r) {
+                                let property = cross_property(child_aligns[i], true);
                                 collector.constraints.push(LayoutConstraint::Equal {
-                                    left: LayoutVariable::x(&child_ids[i]),
-                                    right: LayoutVariable::x(&child_ids[0]),
+                                    left: LayoutVariable::new(&child_ids[i], property),
+                                    right: LayoutVariable::new(&child_ids[r], property),
                                     offset: 0.0,
                                     source: make_source(format!(
-                                        "col alignment: {}.x = {}.x",
-                                        child_ids[i], child_ids[0]
+                                        "col alignment: {} with {} ({:?})",
+                                        child_ids[i], child_ids[r], child_aligns[i]
                                     )),
                                 });
-
+                            }
+                            for i in 1..child_ids.len() {
                                 collector.constraints.push(LayoutConstraint::Equal {
                                     left: LayoutVariable::y(&child_ids[i]),
                                     right: LayoutVariable::new(
@@ -3382,17 +4821,21 @@ fn collect_layout_alignment_constraints(
                                 });
                             }
                         }
-                        LayoutType::Grid => {
-                            // Grid alignment is more complex - skip for now
+                        LayoutType::Grid
+                        | LayoutType::Flow
+                        | LayoutType::Ring
+                        | LayoutType::Timeline
+                        | LayoutType::Absolute => {
+                            // Grid, flow, ring and timeline alignment are not axis-based - skip for now
                         }
                     }
                 }
 
                 // Recurse into children
-                collect_layout_alignment_constraints(&l.children, collector);
+                collect_layout_alignment_constraints(&l.children, collector, result);
             }
             Statement::Group(g) => {
-                collect_layout_alignment_constraints(&g.children, collector);
+                collect_layout_alignment_constraints(&g.children, collector, result);
             }
             _ => {}
         }
@@ -3413,7 +4856,7 @@ fn collect_position_constraints_from_shapes(
 
     for stmt in stmts {
         match &stmt.node {
-            Statement::Shape(s) => {
+            Statement::Shape(s) if !is_pinned(&s.modifiers) => {
                 if let Some(name) = &s.name {
                     let id = &name.node.0;
 
@@ -3430,6 +4873,7 @@ fn collect_position_constraints_from_shapes(
                                         origin: ConstraintOrigin::UserDefined,
                                         template_instance: None,
                                         layout_container: None,
+                                        strength: None,
                                     },
                                 });
                             }
@@ -3445,6 +4889,7 @@ fn collect_position_constraints_from_shapes(
                                         origin: ConstraintOrigin::UserDefined,
                                         template_instance: None,
                                         layout_container: None,
+                                        strength: None,
                                     },
                                 });
                             }
@@ -3463,9 +4908,70 @@ fn collect_position_constraints_from_shapes(
     }
 }
 
+/// Whether the modifiers include `pinned: true`
+fn is_pinned(modifiers: &[Spanned<StyleModifier>]) -> bool {
+    use crate::parser::ast::{StyleKey, StyleValue};
+
+    modifiers.iter().any(|m| {
+        matches!(&m.node.key.node, StyleKey::Custom(key) if key == "pinned")
+            && match &m.node.value.node {
+                StyleValue::Keyword(k) => k == "true",
+                StyleValue::Identifier(id) => id.0 == "true",
+                _ => false,
+            }
+    })
+}
+
+/// Move pinned shapes to their x/y modifiers before solving
+///
+/// A pinned element is held at its current bounds by every solver pass, so its
+/// x/y cannot go through [`collect_position_constraints_from_shapes`] (the two
+/// REQUIRED constraints would conflict). They are applied up front instead.
+fn apply_pinned_positions(
+    stmts: &[Spanned<Statement>],
+    result: &mut LayoutResult,
+) -> Result<(), LayoutError> {
+    use crate::parser::ast::{StyleKey, StyleValue};
+
+    for stmt in stmts {
+        match &stmt.node {
+            Statement::Shape(s) if is_pinned(&s.modifiers) => {
+                let Some(name) = &s.name else { continue };
+                let id = &name.node.0;
+                for modifier in &s.modifiers {
+                    let StyleValue::Number { value, .. } = &modifier.node.value.node else {
+                        continue;
+                    };
+                    let (axis, current) = match modifier.node.key.node {
+                        StyleKey::X => (
+                            Axis::Horizontal,
+                            result.get_element_by_name(id).map(|e| e.bounds.x),
+                        ),
+                        StyleKey::Y => (
+                            Axis::Vertical,
+                            result.get_element_by_name(id).map(|e| e.bounds.y),
+                        ),
+                        _ => continue,
+                    };
+                    if let Some(current) = current {
+                        let delta = value - current;
+                        if delta.abs() > 0.001 {
+                            shift_element_by_name(result, id, delta, axis)?;
+                        }
+                    }
+                }
+            }
+            Statement::Layout(l) => apply_pinned_positions(&l.children, result)?,
+            Statement::Group(g) => apply_pinned_positions(&g.children, result)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
 /// Add position and size for a specific element by name, with per-property targeting
 /// For each axis (X/Y), if any property on that axis is targeted → SUGGESTED (can move)
-/// Otherwise → FIXED (used as reference value)
+/// Otherwise → FIXED (used as reference value). Pinned elements are always FIXED.
 fn add_element_by_name_with_per_property_strength(
     solver: &mut super::solver::ConstraintSolver,
     result: &LayoutResult,
@@ -3476,6 +4982,14 @@ fn add_element_by_name_with_per_property_strength(
     use super::solver::{ConstraintSource, LayoutConstraint, LayoutProperty, LayoutVariable};
 
     if let Some(elem) = result.get_element_by_name(element_name) {
+        // A pinned element is only ever a reference
+        let no_targets = HashSet::new();
+        let target_vars = if elem.styles.pinned {
+            &no_targets
+        } else {
+            target_vars
+        };
+
         // Check if X axis is targeted (X, CenterX, Right, or Left all map to X)
         let x_is_targeted = target_vars.contains(&(element_name.to_string(), LayoutProperty::X))
             || target_vars.contains(&(element_name.to_string(), LayoutProperty::CenterX))
@@ -3657,6 +5171,96 @@ mod tests {
         assert!(b_bounds.x > a_bounds.right());
     }
 
+    #[test]
+    fn test_layout_row_align() {
+        let doc = parse(
+            "row [align: center] {
+                rect tall [height: 100]
+                rect mid [height: 40]
+                rect low [height: 40, self_align: end]
+                rect full [height: 40, self_align: stretch]
+            }",
+        )
+        .unwrap();
+        let config = LayoutConfig::default();
+        let mut result = compute(&doc, &config).unwrap();
+        resolve_constrain_statements(&mut result, &doc, &config).unwrap();
+
+        let bounds = |name: &str| result.get_element_by_name(name).unwrap().bounds;
+        let tall = bounds("tall");
+        assert!((bounds("mid").center().y - tall.center().y).abs() < 0.01);
+        assert!((bounds("low").bottom() - tall.bottom()).abs() < 0.01);
+        assert!((bounds("full").y - tall.y).abs() < 0.01);
+        assert_eq!(bounds("full").height, 100.0);
+    }
+
+    #[test]
+    fn test_layout_column_align_defaults_to_start() {
+        let doc = parse("col { rect wide [width: 200] rect narrow [width: 50] }").unwrap();
+        let result = compute(&doc, &LayoutConfig::default()).unwrap();
+        let children = &result.root_elements[0].children;
+        assert_eq!(children[0].bounds.x, children[1].bounds.x);
+
+        let doc =
+            parse("col [align: end] { rect wide [width: 200] rect narrow [width: 50] }").unwrap();
+        let result = compute(&doc, &LayoutConfig::default()).unwrap();
+        let children = &result.root_elements[0].children;
+        assert_eq!(children[0].bounds.right(), children[1].bounds.right());
+    }
+
+    #[test]
+    fn test_layout_padding_modifiers() {
+        let doc = parse("row box [padding: 20] { rect a [width: 50, height: 30] }").unwrap();
+        let result = compute(&doc, &LayoutConfig::default()).unwrap();
+        let container = &result.root_elements[0];
+        let child = container.children[0].bounds;
+        assert_eq!(child.x - container.bounds.x, 20.0);
+        assert_eq!(child.y - container.bounds.y, 20.0);
+        assert_eq!(container.bounds.width, 90.0);
+        assert_eq!(container.bounds.height, 70.0);
+
+        // Per-side values override `padding` and the global default
+        let doc = parse(
+            "col [padding: 0, padding_top: 4, padding_left: 12] { rect a [width: 50, height: 30] }",
+        )
+        .unwrap();
+        let result = compute(&doc, &LayoutConfig::default()).unwrap();
+        let container = &result.root_elements[0];
+        let child = container.children[0].bounds;
+        assert_eq!(child.x - container.bounds.x, 12.0);
+        assert_eq!(child.y - container.bounds.y, 4.0);
+        assert_eq!(container.bounds.width, 62.0);
+        assert_eq!(container.bounds.height, 34.0);
+    }
+
+    #[test]
+    fn test_place_notes_follows_anchor() {
+        let doc = parse(
+            r#"rect api [width: 100, height: 50]
+            rect other [width: 40, height: 40]
+            constrain api.x = 300
+            note at api.top_right [dx: 10, dy: -10] { rect tag [width: 20, height: 10] }"#,
+        )
+        .unwrap();
+        let config = LayoutConfig::default();
+        let mut result = compute(&doc, &config).unwrap();
+        resolve_constrain_statements(&mut result, &doc, &config).unwrap();
+        resolve_constraints(&mut result, &doc, None).unwrap();
+        place_notes(&mut result, &doc, &config).unwrap();
+
+        // The note is centered on the solved top-right corner plus the offset
+        let api = result.get_element_by_name("api").unwrap().bounds;
+        assert_eq!(api.x, 300.0);
+        let tag = result.get_element_by_name("tag").unwrap().bounds.center();
+        assert_eq!(tag.x, api.right() + 10.0);
+        assert_eq!(tag.y, api.y - 10.0);
+
+        let doc = parse("rect api\nnote at api.nowhere { text \"x\" }").unwrap();
+        let mut result = compute(&doc, &config).unwrap();
+        let err = place_notes(&mut result, &doc, &config).unwrap_err();
+        assert!(matches!(err, LayoutError::InvalidAnchor { .. }));
+    }
+
     #[test]
     fn test_layout_column() {
         let doc = parse("col { rect a rect b }").unwrap();
@@ -3673,6 +5277,144 @@ mod tests {
         assert!(b_bounds.y > a_bounds.bottom());
     }
 
+    #[test]
+    fn test_layout_flow_wraps() {
+        // Default rects are 80 wide; 200 fits two per line with padding and spacing
+        let doc = parse("flow [max_width: 200] { rect a rect b rect c rect d rect e }").unwrap();
+        let config = LayoutConfig::default();
+        let result = compute(&doc, &config).unwrap();
+
+        let container = &result.root_elements[0];
+        let bounds: Vec<_> = container.children.iter().map(|c| c.bounds).collect();
+        assert_eq!(bounds.len(), 5);
+        assert!(bounds[1].x > bounds[0].right());
+        assert_eq!(bounds[1].y, bounds[0].y);
+        // Third child starts a new line at the left edge
+        assert_eq!(bounds[2].x, bounds[0].x);
+        assert!(bounds[2].y > bounds[0].bottom());
+        assert!(bounds[4].y > bounds[2].bottom());
+        assert!(container.bounds.width <= 200.0);
+        assert!(container.bounds.bottom() >= bounds[4].bottom());
+    }
+
+    #[test]
+    fn test_layout_flow_without_max_width_is_one_line() {
+        let doc = parse("flow { rect a rect b rect c }").unwrap();
+        let result = compute(&doc, &LayoutConfig::default()).unwrap();
+        let children = &result.root_elements[0].children;
+        assert!(children.iter().all(|c| c.bounds.y == children[0].bounds.y));
+    }
+
+    #[test]
+    fn test_layout_ring() {
+        let doc = parse(
+            "ring spokes [radius: 100] { rect n rect e rect s rect w }\nrect hub\nhub.right -> e.inward",
+        )
+        .unwrap();
+        let result = compute(&doc, &LayoutConfig::default()).unwrap();
+
+        let ring = result.get_element_by_name("spokes").unwrap();
+        let center = ring.bounds.center();
+        let at = |name: &str| result.get_element_by_name(name).unwrap().bounds.center();
+        // Clockwise from the top
+        assert!((at("n").x - center.x).abs() < 0.01);
+        assert!((at("n").y - (center.y - 100.0)).abs() < 0.01);
+        assert!((at("e").x - (center.x + 100.0)).abs() < 0.01);
+        assert!((at("s").y - (center.y + 100.0)).abs() < 0.01);
+        assert!((at("w").x - (center.x - 100.0)).abs() < 0.01);
+
+        let e = result.get_element_by_name("e").unwrap();
+        let outward = e.anchors.get("outward").unwrap();
+        assert_eq!(outward.direction, AnchorDirection::Right);
+        assert!((outward.position.x - e.bounds.right()).abs() < 0.01);
+        let inward = e.anchors.get("inward").unwrap();
+        assert_eq!(inward.direction, AnchorDirection::Left);
+        assert!((inward.position.x - e.bounds.x).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_layout_ring_default_radius_separates_children() {
+        let doc = parse("ring { rect a rect b rect c rect d rect e rect f }").unwrap();
+        let result = compute(&doc, &LayoutConfig::default()).unwrap();
+        let children = &result.root_elements[0].children;
+        for (i, a) in children.iter().enumerate() {
+            for b in &children[i + 1..] {
+                assert!(!a.bounds.intersects(&b.bounds));
+            }
+        }
+    }
+
+    #[test]
+    fn test_layout_absolute() {
+        let doc = parse(
+            "absolute [width: 300] {\n    rect a [x: 40, y: 200]\n    col b [x: 120, y: 10] { rect c }\n    rect d\n}\nrect after",
+        )
+        .unwrap();
+        let result = compute(&doc, &LayoutConfig::default()).unwrap();
+
+        let a = &result.get_element_by_name("a").unwrap().bounds;
+        assert_eq!((a.x, a.y), (40.0, 200.0));
+        let b = &result.get_element_by_name("b").unwrap().bounds;
+        assert_eq!((b.x, b.y), (120.0, 10.0));
+        // No coordinates: the container origin, overlapping rather than stacking
+        let d = &result.get_element_by_name("d").unwrap().bounds;
+        assert_eq!((d.x, d.y), (0.0, 0.0));
+
+        // The canvas is as wide as declared and reaches the lowest child
+        let canvas = &result.root_elements[0].bounds;
+        assert_eq!(canvas.width, 300.0);
+        assert_eq!(canvas.height, a.bottom());
+        let after = &result.get_element_by_name("after").unwrap().bounds;
+        assert!(after.y > a.bottom());
+    }
+
+    #[test]
+    fn test_layout_timeline() {
+        let doc = parse(
+            "timeline plan [unit_width: 30] {\n    rect design [start: 0, end: 3]\n    rect build [start: 2, end: 9]\n}",
+        )
+        .unwrap();
+        let result = compute(&doc, &LayoutConfig::default()).unwrap();
+
+        let design = &result.get_element_by_name("design").unwrap().bounds;
+        let build = &result.get_element_by_name("build").unwrap().bounds;
+        assert!((design.width - 90.0).abs() < 0.01);
+        assert!((build.width - 210.0).abs() < 0.01);
+        assert!((build.x - design.x - 60.0).abs() < 0.01);
+        // One row per child
+        assert!(build.y >= design.bottom());
+
+        // Ticks every 2 units (30px per unit, at least 40px apart) up to 10
+        let plan = result.get_element_by_name("plan").unwrap();
+        let ticks: Vec<_> = plan
+            .children
+            .iter()
+            .filter_map(|c| match &c.element_type {
+                ElementType::Shape(ShapeType::Text { content }) => Some(content.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ticks, ["0", "2", "4", "6", "8", "10"]);
+        let grid = plan
+            .children
+            .iter()
+            .filter(|c| c.styles.css_classes.iter().any(|k| k == "timeline-grid"))
+            .count();
+        assert_eq!(grid, 6);
+        assert!(plan
+            .bounds
+            .contains_bbox(&BoundingBox::new(design.x, design.y, 300.0, 1.0)));
+    }
+
+    #[test]
+    fn test_auto_tick() {
+        assert_eq!(auto_tick(1.0), 1.0);
+        assert_eq!(auto_tick(1.3), 2.0);
+        assert_eq!(auto_tick(3.0), 5.0);
+        assert_eq!(auto_tick(0.4), 0.5);
+        assert_eq!(auto_tick(12.0), 20.0);
+    }
+
     #[test]
     fn test_layout_nested() {
         let doc = parse(
@@ -3823,6 +5565,7 @@ mod tests {
     // ============================================
 
     #[test]
+    #[allow(clippy::unnecessary_get_then_check)]
     fn test_build_element_to_template_map() {
         use crate::template::{resolve_templates, TemplateRegistry};
 
@@ -4002,7 +5745,7 @@ mod tests {
 
         // Create a simple local constraint
         use super::super::solver::{
-            ConstraintSource, LayoutConstraint, LayoutProperty, LayoutVariable,
+            ConstraintSolver, ConstraintSource, LayoutConstraint, LayoutProperty, LayoutVariable,
         };
         let constraints = vec![LayoutConstraint::Equal {
             left: LayoutVariable::new("alice_body", LayoutProperty::Y),
@@ -4013,6 +5756,7 @@ mod tests {
 
         // Solve locally
         let local_result = solve_local(
+            &mut ConstraintSolver::new(),
             "alice",
             &constraints,
             &result,
@@ -4252,4 +5996,193 @@ mod tests {
             group.bounds.height
         );
     }
+
+    #[test]
+    fn test_equal_width_picks_largest() {
+        let doc = parse(
+            r#"
+            col {
+                rect a [width: 50, height: 30]
+                rect b [width: 120, height: 30]
+                rect c [width: 80, height: 40]
+            }
+            constrain equal_width a, b, c
+        "#,
+        )
+        .unwrap();
+        let config = LayoutConfig::default();
+        let mut result = compute(&doc, &config).unwrap();
+        resolve_constrain_statements(&mut result, &doc, &config).unwrap();
+
+        for id in ["a", "b", "c"] {
+            let width = result.elements.get(id).unwrap().bounds.width;
+            assert!((width - 120.0).abs() < 0.01, "{} width was {}", id, width);
+        }
+        // Heights are untouched
+        assert!((result.elements.get("c").unwrap().bounds.height - 40.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_equal_height_picks_largest() {
+        let doc = parse(
+            r#"
+            row {
+                rect a [width: 50, height: 30]
+                rect b [width: 50, height: 70]
+            }
+            constrain equal_height a, b
+        "#,
+        )
+        .unwrap();
+        let config = LayoutConfig::default();
+        let mut result = compute(&doc, &config).unwrap();
+        resolve_constrain_statements(&mut result, &doc, &config).unwrap();
+
+        assert!((result.elements.get("a").unwrap().bounds.height - 70.0).abs() < 0.01);
+        assert!((result.elements.get("b").unwrap().bounds.height - 70.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_relative_constraint_with_gap() {
+        let doc = parse(
+            r#"
+            rect a [width: 50, height: 30]
+            rect b [width: 60, height: 40]
+            constrain a.x = 10
+            constrain a.y = 5
+            constrain b right_of a [gap: 40]
+        "#,
+        )
+        .unwrap();
+        let config = LayoutConfig::default();
+        let mut result = compute(&doc, &config).unwrap();
+        resolve_constrain_statements(&mut result, &doc, &config).unwrap();
+
+        let b = result.elements.get("b").unwrap().bounds;
+        assert!((b.x - 100.0).abs() < 0.01, "b.x was {}", b.x);
+        assert!((b.y - 5.0).abs() < 0.01, "b.y was {}", b.y);
+    }
+
+    #[test]
+    fn test_relative_alignment_yields_to_explicit_constraint() {
+        let doc = parse(
+            r#"
+            rect a [width: 50, height: 30]
+            rect b [width: 60, height: 40]
+            constrain a.x = 0
+            constrain a.y = 0
+            constrain b above a
+            constrain b.x = 25
+        "#,
+        )
+        .unwrap();
+        let config = LayoutConfig::default();
+        let mut result = compute(&doc, &config).unwrap();
+        resolve_constrain_statements(&mut result, &doc, &config).unwrap();
+
+        let b = result.elements.get("b").unwrap().bounds;
+        assert!(
+            (b.bottom() - -20.0).abs() < 0.01,
+            "b.bottom was {}",
+            b.bottom()
+        );
+        assert!((b.x - 25.0).abs() < 0.01, "b.x was {}", b.x);
+    }
+
+    #[test]
+    fn test_relative_strength_leaves_alignment_a_preference() {
+        let doc = parse(
+            r#"
+            rect a [width: 50, height: 30]
+            rect b [width: 60, height: 40]
+            constrain a.x = 0
+            constrain a.y = 0
+            constrain b right_of a [strength: required]
+            constrain b.y = 50
+        "#,
+        )
+        .unwrap();
+        let config = LayoutConfig::default();
+        let mut result = compute(&doc, &config).unwrap();
+        resolve_constrain_statements(&mut result, &doc, &config).unwrap();
+
+        let b = result.elements.get("b").unwrap().bounds;
+        assert!((b.x - 70.0).abs() < 0.01, "b.x was {}", b.x);
+        assert!((b.y - 50.0).abs() < 0.01, "b.y was {}", b.y);
+    }
+
+    #[test]
+    fn test_weak_constraint_yields_to_conflict() {
+        let config = LayoutConfig::default();
+
+        let doc = parse("rect a\nrect b\nconstrain a.x = 10\nconstrain a.x = 20").unwrap();
+        let mut result = compute(&doc, &config).unwrap();
+        assert!(resolve_constrain_statements(&mut result, &doc, &config).is_err());
+
+        let doc = parse("rect a\nrect b\nconstrain a.x = 10\nconstrain a.x = 20 [strength: weak]")
+            .unwrap();
+        let mut result = compute(&doc, &config).unwrap();
+        resolve_constrain_statements(&mut result, &doc, &config).unwrap();
+        assert!((result.elements.get("a").unwrap().bounds.x - 10.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_pinned_element_is_never_moved() {
+        let doc = parse(
+            r#"
+            rect a [width: 50, height: 30, pinned: true]
+            rect b [width: 60, height: 40]
+            rect c [x: 40, y: 300, pinned: true]
+            constrain a right_of b [gap: 10]
+        "#,
+        )
+        .unwrap();
+        let config = LayoutConfig::default();
+        let mut result = compute(&doc, &config).unwrap();
+        let a_before = result.elements.get("a").unwrap().bounds;
+        resolve_constrain_statements(&mut result, &doc, &config).unwrap();
+
+        let a = result.elements.get("a").unwrap().bounds;
+        assert_eq!((a.x, a.y), (a_before.x, a_before.y));
+        // The relation is satisfied by moving the unpinned side
+        let b = result.elements.get("b").unwrap().bounds;
+        assert!(
+            (a.x - b.right() - 10.0).abs() < 0.01,
+            "b.right was {}",
+            b.right()
+        );
+        // A pinned element's own coordinates still apply
+        let c = result.elements.get("c").unwrap().bounds;
+        assert_eq!((c.x, c.y), (40.0, 300.0));
+
+        // Constraining the pinned element itself is a reported conflict
+        let doc = parse("rect a [pinned: true]\nconstrain a.x = 200").unwrap();
+        let mut result = compute(&doc, &config).unwrap();
+        assert!(resolve_constrain_statements(&mut result, &doc, &config).is_err());
+    }
+
+    #[test]
+    fn test_arithmetic_constraint() {
+        let doc = parse(
+            r#"
+            rect b [x: 0, y: 0, width: 200, height: 40]
+            rect c [x: 300, y: 0, width: 100, height: 40]
+            rect a [y: 100, width: 50, height: 40]
+            constrain a.width = b.width * 0.5 + 10
+            constrain a.center_x = (b.left + c.right) / 2
+        "#,
+        )
+        .unwrap();
+        let config = LayoutConfig::default();
+        let mut result = compute(&doc, &config).unwrap();
+        resolve_constrain_statements(&mut result, &doc, &config).unwrap();
+
+        let a = result.elements.get("a").unwrap().bounds;
+        assert!((a.width - 110.0).abs() < 0.01, "width was {}", a.width);
+        assert!(
+            (a.x + a.width / 2.0 - 200.0).abs() < 0.01,
+            "center was {}",
+            a.x + a.width / 2.0
+        );
+    }
 }
@@ -40,6 +40,14 @@ pub enum LayoutError {
         suggestions: Vec<String>,
     },
 
+    /// Element name that matches elements in more than one scope
+    #[error("ambiguous reference '{name}' (could be {}); qualify it with its group", candidates.join(", "))]
+    AmbiguousReference {
+        name: String,
+        span: Span,
+        candidates: Vec<String>,
+    },
+
     /// Constraint solver error
     #[error("constraint solver error: {0}")]
     SolverError(#[from] SolverError),
@@ -56,6 +64,10 @@ pub enum LayoutError {
     /// Validation error (e.g., invalid color reference)
     #[error("{0}")]
     ValidationError(String),
+
+    /// Modifier with a value that is not allowed where it is used
+    #[error("{message}")]
+    InvalidModifier { message: String, span: Span },
 }
 
 impl LayoutError {
@@ -94,7 +106,9 @@ impl LayoutError {
         match self {
             Self::UndefinedIdentifier { span, .. } => Some(span),
             Self::PathNotFound { span, .. } => Some(span),
+            Self::AmbiguousReference { span, .. } => Some(span),
             Self::InvalidAnchor { span, .. } => Some(span),
+            Self::InvalidModifier { span, .. } => Some(span),
             _ => None,
         }
     }
@@ -117,6 +131,15 @@ impl LayoutError {
         }
     }
 
+    /// Create an ambiguous reference error
+    pub fn ambiguous(name: impl Into<String>, span: Span, candidates: Vec<String>) -> Self {
+        Self::AmbiguousReference {
+            name: name.into(),
+            span,
+            candidates,
+        }
+    }
+
     /// Create a solver error from a SolverError
     pub fn solver_error(e: SolverError) -> Self {
         Self::SolverError(e)
@@ -141,6 +164,14 @@ impl LayoutError {
     pub fn validation_error(message: impl Into<String>) -> Self {
         Self::ValidationError(message.into())
     }
+
+    /// Create an invalid modifier error pointing at the modifier
+    pub fn invalid_modifier(message: impl Into<String>, span: Span) -> Self {
+        Self::InvalidModifier {
+            message: message.into(),
+            span,
+        }
+    }
 }
 
 #[cfg(test)]
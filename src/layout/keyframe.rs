@@ -6,9 +6,12 @@
 
 use std::collections::{BTreeMap, HashMap, HashSet};
 
-use crate::parser::ast::{Document, KeyframeDecl, KeyframeOp, Statement, StyleKey, StyleValue};
+use crate::parser::ast::{
+    Document, KeyframeDecl, KeyframeOp, Span, Statement, StyleKey, StyleValue,
+};
 use super::config::LayoutConfig;
-use super::types::{ConnectionLayout, ElementLayout, LayoutResult, ResolvedStyles};
+use super::error::LayoutError;
+use super::types::{ConnectionLayout, ElementLayout, LayoutResult, Point, ResolvedStyles};
 
 /// Visibility and transform state for a single frame
 #[derive(Debug, Clone)]
@@ -20,7 +23,8 @@ pub struct FrameState {
     /// Connections hidden in this frame (by name)
     pub hidden_connections: HashSet<String>,
     /// Per-element transform overrides (element_id -> style modifiers)
-    pub transforms: HashMap<String, Vec<crate::parser::ast::Spanned<crate::parser::ast::StyleModifier>>>,
+    pub transforms:
+        BTreeMap<String, Vec<crate::parser::ast::Spanned<crate::parser::ast::StyleModifier>>>,
     /// If true, skip constraint re-solving for this frame
     pub no_resolve: bool,
 }
@@ -117,7 +121,7 @@ pub fn compute_frame_states(keyframes: &[&KeyframeDecl]) -> Vec<FrameState> {
         }
 
         // Collect transforms for this frame
-        let mut transforms = HashMap::new();
+        let mut transforms = BTreeMap::new();
         for op in &kf.operations {
             if let KeyframeOp::Transform { target, modifiers } = &op.node {
                 transforms.insert(target.node.0.clone(), modifiers.clone());
@@ -136,6 +140,226 @@ pub fn compute_frame_states(keyframes: &[&KeyframeDecl]) -> Vec<FrameState> {
     frames
 }
 
+/// One property animation from an `animate` statement, ready to render
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animation {
+    /// Id of the animated element
+    pub target: String,
+    /// SVG attribute being animated (`opacity`, `fill`, `stroke`,
+    /// `stroke-width`), or `transform` for `rotation`
+    pub attribute: String,
+    pub from: String,
+    pub to: String,
+    /// Duration in seconds
+    pub duration: f64,
+    /// Start delay in seconds
+    pub delay: f64,
+    /// Repeat count, or `indefinite`; plays once when `None`
+    pub repeat: Option<String>,
+    /// Center of the target, the pivot for `rotation`
+    pub center: Point,
+}
+
+/// Collect the document's `animate` statements, checked against the layout
+pub fn extract_animations(
+    doc: &Document,
+    result: &LayoutResult,
+) -> Result<Vec<Animation>, LayoutError> {
+    let mut animations = vec![];
+    for stmt in &doc.statements {
+        let Statement::Animate(decl) = &stmt.node else {
+            continue;
+        };
+        let target = &decl.target.node.0;
+        let element = result.get_element_by_name(target).ok_or_else(|| {
+            LayoutError::undefined(target.clone(), decl.target.span.clone(), vec![])
+        })?;
+        let invalid = |message: String| {
+            LayoutError::validation_error(format!("animate {}: {}", target, message))
+        };
+
+        let mut property = None;
+        let (mut from, mut to, mut repeat) = (None, None, None);
+        let (mut duration, mut delay) = (1.0, 0.0);
+        for modifier in &decl.modifiers {
+            let key = crate::parser::key_text(&modifier.node.key.node);
+            let value = &modifier.node.value.node;
+            match key {
+                "property" => property = animation_word(value),
+                "from" => from = Some(value),
+                "to" => to = Some(value),
+                "duration" | "delay" => {
+                    let seconds = animation_seconds(value)
+                        .ok_or_else(|| invalid(format!("{} must be a time like 2s", key)))?;
+                    if key == "duration" {
+                        duration = seconds;
+                    } else {
+                        delay = seconds;
+                    }
+                }
+                "repeat" => {
+                    repeat = match value {
+                        StyleValue::Number { value, .. } if *value > 0.0 => Some(value.to_string()),
+                        _ if animation_word(value).as_deref() == Some("indefinite") => {
+                            Some("indefinite".to_string())
+                        }
+                        _ => {
+                            return Err(invalid(
+                                "repeat must be a count or 'indefinite'".to_string(),
+                            ))
+                        }
+                    };
+                }
+                _ => {
+                    return Err(invalid(format!(
+                        "'{}' is not one of property, from, to, duration, delay, repeat",
+                        key
+                    )))
+                }
+            }
+        }
+
+        let property = property.ok_or_else(|| invalid("missing property".to_string()))?;
+        let (Some(from), Some(to)) = (from, to) else {
+            return Err(invalid("both from and to are required".to_string()));
+        };
+        let (attribute, from, to) = match property.as_str() {
+            "opacity" | "stroke_width" | "rotation" => {
+                let number = |value: &StyleValue| match value {
+                    StyleValue::Number { value, .. } => Some(value.to_string()),
+                    _ => None,
+                };
+                let (Some(from), Some(to)) = (number(from), number(to)) else {
+                    return Err(invalid(format!("{} animates between numbers", property)));
+                };
+                let attribute = match property.as_str() {
+                    "stroke_width" => "stroke-width",
+                    "rotation" => "transform",
+                    _ => "opacity",
+                };
+                (attribute, from, to)
+            }
+            "fill" | "stroke" => {
+                let (Some(from), Some(to)) = (
+                    ResolvedStyles::color_to_css(from),
+                    ResolvedStyles::color_to_css(to),
+                ) else {
+                    return Err(invalid(format!("{} animates between colors", property)));
+                };
+                (property.as_str(), from, to)
+            }
+            other => {
+                return Err(invalid(format!(
+                    "cannot animate '{}' (expected opacity, fill, stroke, stroke_width, rotation)",
+                    other
+                )))
+            }
+        };
+
+        animations.push(Animation {
+            target: target.clone(),
+            attribute: attribute.to_string(),
+            from,
+            to,
+            duration,
+            delay,
+            repeat,
+            center: element.bounds.center(),
+        });
+    }
+    Ok(animations)
+}
+
+fn animation_word(value: &StyleValue) -> Option<String> {
+    match value {
+        StyleValue::Keyword(s) | StyleValue::String(s) => Some(s.clone()),
+        StyleValue::Identifier(id) => Some(id.0.clone()),
+        _ => None,
+    }
+}
+
+/// A time in seconds: `2s`, `500ms`, or a bare number of seconds
+fn animation_seconds(value: &StyleValue) -> Option<f64> {
+    match value {
+        StyleValue::Number { value, unit } if *value >= 0.0 => match unit.as_deref() {
+            None | Some("s") => Some(*value),
+            Some("ms") => Some(*value / 1000.0),
+            Some(_) => None,
+        },
+        _ => None,
+    }
+}
+
+/// Step number and source range of each `step N { ... }` block, in document order
+pub fn step_ranges(doc: &Document) -> Vec<(u32, Span)> {
+    doc.statements
+        .iter()
+        .filter_map(|stmt| match &stmt.node {
+            Statement::Step(step) => Some((step.number.node, stmt.span.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Splice the bodies of `step` blocks into the document root, so every step
+/// is laid out together and slides keep the same geometry
+pub fn flatten_steps(doc: &mut Document) -> Result<(), LayoutError> {
+    for stmt in std::mem::take(&mut doc.statements) {
+        let Statement::Step(step) = stmt.node else {
+            doc.statements.push(stmt);
+            continue;
+        };
+        if step
+            .children
+            .iter()
+            .any(|child| matches!(child.node, Statement::Step(_)))
+        {
+            return Err(LayoutError::validation_error(format!(
+                "step {} contains another step; steps cannot be nested",
+                step.number.node
+            )));
+        }
+        doc.statements.extend(step.children);
+    }
+    Ok(())
+}
+
+/// Drop what later steps reveal: elements declared in the body of a step
+/// numbered above `step`, and connections declared there or attached to
+/// such an element
+pub fn hide_later_steps(result: &mut LayoutResult, ranges: &[(u32, Span)], step: u32) {
+    let later: Vec<&Span> = ranges
+        .iter()
+        .filter(|(number, _)| *number > step)
+        .map(|(_, span)| span)
+        .collect();
+    let revealed_later = |span: &Option<Span>| {
+        span.as_ref()
+            .is_some_and(|s| later.iter().any(|r| r.start <= s.start && s.end <= r.end))
+    };
+
+    fn collect_ids(element: &ElementLayout, ids: &mut HashSet<String>) {
+        if let Some(id) = &element.id {
+            ids.insert(id.0.clone());
+        }
+        for child in &element.children {
+            collect_ids(child, ids);
+        }
+    }
+    let mut hidden = HashSet::new();
+    result.root_elements.retain(|element| {
+        let keep = !revealed_later(&element.span);
+        if !keep {
+            collect_ids(element, &mut hidden);
+        }
+        keep
+    });
+    result.elements.retain(|id, _| !hidden.contains(id));
+    result.connections.retain(|c| {
+        !revealed_later(&c.span) && !hidden.contains(&c.from_id.0) && !hidden.contains(&c.to_id.0)
+    });
+}
+
 /// Compute layout diffs for all frames against frame 0 (the base layout).
 /// For frames with transforms, re-solves constraints and re-routes connections.
 pub fn compute_frame_diffs(
@@ -220,7 +444,7 @@ pub fn compute_frame_diffs(
         }
 
         // Compute connection visibility diffs
-        for (name, _conn) in &base_connections {
+        for name in base_connections.keys() {
             let hidden_in_frame0 = frame_states[0].hidden_connections.contains(*name);
             let hidden_in_this_frame = state.hidden_connections.contains(*name);
 
@@ -351,7 +575,8 @@ fn get_constraint_lhs_element(expr: &crate::parser::ast::ConstraintExpr) -> Opti
         | ConstraintExpr::EqualWithOffset { left, .. }
         | ConstraintExpr::Constant { left, .. }
         | ConstraintExpr::GreaterOrEqual { left, .. }
-        | ConstraintExpr::LessOrEqual { left, .. } => {
+        | ConstraintExpr::LessOrEqual { left, .. }
+        | ConstraintExpr::Linear { left, .. } => {
             Some(left.element.node.leaf().0.clone())
         }
         ConstraintExpr::Midpoint { target, .. } => {
@@ -360,6 +585,10 @@ fn get_constraint_lhs_element(expr: &crate::parser::ast::ConstraintExpr) -> Opti
         ConstraintExpr::Contains { container, .. } => {
             Some(container.node.0.clone())
         }
+        ConstraintExpr::EqualSize { elements, .. } => {
+            elements.first().map(|e| e.node.0.clone())
+        }
+        ConstraintExpr::Relative { subject, .. } => Some(subject.node.0.clone()),
     }
 }
 
@@ -370,7 +599,7 @@ fn apply_transform_to_element(
     modifiers: &[crate::parser::ast::Spanned<crate::parser::ast::StyleModifier>],
 ) {
     for elem in elements.iter_mut() {
-        if elem.id.as_ref().map_or(false, |id| id.0 == target_id) {
+        if elem.id.as_ref().is_some_and(|id| id.0 == target_id) {
             // Apply style modifiers
             for modifier in modifiers {
                 match &modifier.node.key.node {
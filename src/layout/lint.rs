@@ -8,21 +8,52 @@ use std::collections::{HashMap, HashSet};
 use std::fmt;
 
 use crate::parser::ast::{
-    ConstraintExpr, ConstraintProperty, Document, LayoutType, ShapeType, Statement,
+    ConstraintExpr, ConstraintProperty, Document, LayoutType, ShapeType, Span, Spanned, Statement,
 };
 
-use super::routing::{RoutingMode, MIN_FINAL_SEGMENT_LENGTH};
+use super::routing::{endpoint_with_modifiers, RoutingMode, MIN_FINAL_SEGMENT_LENGTH};
 use super::types::{
     BoundingBox, ElementLayout, ElementType, LabelLayout, LayoutResult, Point, TextAnchor,
+    VerticalAlign,
 };
 
 /// A lint warning about a layout defect
 #[derive(Debug)]
 pub struct LintWarning {
     pub category: LintCategory,
+    /// ID of the element the warning is about, if it has one
+    pub element: Option<String>,
+    /// The other element involved, e.g. the second element of an overlap
+    pub related: Option<String>,
+    /// Source location of that element (or of the offending statement)
+    pub span: Option<Span>,
     pub message: String,
 }
 
+impl LintWarning {
+    pub fn severity(&self) -> LintSeverity {
+        self.category.severity()
+    }
+}
+
+/// How serious a lint finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LintSeverity {
+    /// A suggestion that would make the diagram cleaner
+    Info,
+    /// A visible defect in the rendered diagram
+    Warning,
+}
+
+impl fmt::Display for LintSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintSeverity::Info => write!(f, "info"),
+            LintSeverity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
 /// Category of lint defect
 #[derive(Debug)]
 pub enum LintCategory {
@@ -61,6 +92,28 @@ impl fmt::Display for LintCategory {
     }
 }
 
+impl LintCategory {
+    /// Defects that show in the output are warnings; routing and
+    /// constraint style suggestions are informational
+    pub fn severity(&self) -> LintSeverity {
+        match self {
+            LintCategory::Overlap
+            | LintCategory::Containment
+            | LintCategory::Label
+            | LintCategory::Connection
+            | LintCategory::Contrast
+            | LintCategory::OverConstrained
+            | LintCategory::LabelOverflow => LintSeverity::Warning,
+            LintCategory::Alignment
+            | LintCategory::RedundantConstant
+            | LintCategory::ReducibleBend
+            | LintCategory::MissingAnchor
+            | LintCategory::SteepDirect
+            | LintCategory::CrowdedLayout => LintSeverity::Info,
+        }
+    }
+}
+
 /// Run all lint checks on a completed layout.
 /// If the document contains keyframes, overlap checks run per-frame
 /// with hidden elements excluded.
@@ -105,9 +158,49 @@ pub fn check(result: &LayoutResult, doc: &Document) -> Vec<LintWarning> {
     check_crowded_layouts(doc, &mut warnings);
     check_over_constrained(result, doc, &mut warnings);
     check_label_overflow(result, &mut warnings);
+
+    let mut spans = HashMap::new();
+    collect_id_spans(&doc.statements, &mut spans);
+    for w in &mut warnings {
+        if w.span.is_none() {
+            w.span = w
+                .element
+                .as_ref()
+                .and_then(|id| spans.get(id.as_str()).cloned());
+        }
+    }
     warnings
 }
 
+/// Where each named element is declared, for pointing warnings at the source
+fn collect_id_spans<'a>(stmts: &'a [Spanned<Statement>], spans: &mut HashMap<&'a str, Span>) {
+    for stmt in stmts {
+        let (name, children): (_, &[Spanned<Statement>]) = match &stmt.node {
+            Statement::Shape(s) => (s.name.as_ref(), &[]),
+            Statement::Layout(l) => (l.name.as_ref(), &l.children),
+            Statement::Group(g) => (g.name.as_ref(), &g.children),
+            Statement::TemplateInstance(i) => (Some(&i.instance_name), &i.children),
+            Statement::Label(inner) => {
+                if let Statement::Shape(s) = inner.as_ref() {
+                    if let Some(name) = &s.name {
+                        spans
+                            .entry(name.node.0.as_str())
+                            .or_insert(stmt.span.clone());
+                    }
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        if let Some(name) = name {
+            spans
+                .entry(name.node.0.as_str())
+                .or_insert(stmt.span.clone());
+        }
+        collect_id_spans(children, spans);
+    }
+}
+
 /// Display name for an element: its ID if named, or positional path if anonymous.
 fn element_display_name(
     elem: &ElementLayout,
@@ -131,6 +224,11 @@ fn is_text_shape(elem: &ElementLayout) -> bool {
     )
 }
 
+/// A `bus` rail: its label sits beside it and connections tap in anywhere
+fn is_bus_shape(elem: &ElementLayout) -> bool {
+    matches!(elem.element_type, ElementType::Shape(ShapeType::Bus))
+}
+
 fn is_opaque(elem: &ElementLayout) -> bool {
     elem.styles.opacity.is_none() || elem.styles.opacity == Some(1.0)
 }
@@ -233,7 +331,7 @@ fn check_overlaps(
         .iter()
         .filter(|e| {
             e.id.as_ref()
-                .map_or(true, |id| !hidden_ids.contains(&id.0))
+                .is_none_or(|id| !hidden_ids.contains(&id.0))
         })
         .collect();
 
@@ -245,6 +343,113 @@ fn check_overlaps(
     for elem in &visible_roots {
         check_overlaps_recursive(elem, None, contains_ids, warnings);
     }
+
+    check_nested_overlaps(&visible_roots, contains_ids, hidden_ids, warnings);
+}
+
+/// A shape together with the containers above it
+struct PlacedShape<'a> {
+    /// Ancestors from the root down, ending with the shape itself
+    chain: Vec<&'a ElementLayout>,
+    /// Child index at each level of `chain`
+    path: Vec<usize>,
+    /// Nearest enclosing template instance, whose internals may overlap by design
+    template: Option<&'a str>,
+}
+
+fn collect_placed_shapes<'a>(
+    elem: &'a ElementLayout,
+    mut placed: PlacedShape<'a>,
+    hidden_ids: &HashSet<String>,
+    out: &mut Vec<PlacedShape<'a>>,
+) {
+    if elem.id_str().is_some_and(|id| hidden_ids.contains(id)) {
+        return;
+    }
+    placed.chain.push(elem);
+    if is_template_instance_group(elem) {
+        placed.template = elem.id_str();
+    }
+    if elem.children.is_empty() {
+        if is_visual_shape(elem) {
+            out.push(placed);
+        }
+        return;
+    }
+    for (i, child) in elem.children.iter().enumerate() {
+        let mut path = placed.path.clone();
+        path.push(i);
+        let next = PlacedShape {
+            chain: placed.chain.clone(),
+            path,
+            template: placed.template,
+        };
+        collect_placed_shapes(child, next, hidden_ids, out);
+    }
+}
+
+/// Overlaps between shapes in different containers, which the sibling
+/// checks miss when the shapes stick out of containers that themselves
+/// don't overlap
+fn check_nested_overlaps(
+    roots: &[&ElementLayout],
+    contains_ids: &HashSet<String>,
+    hidden_ids: &HashSet<String>,
+    warnings: &mut Vec<LintWarning>,
+) {
+    let mut shapes = Vec::new();
+    for (i, root) in roots.iter().enumerate() {
+        let placed = PlacedShape {
+            chain: vec![],
+            path: vec![i],
+            template: None,
+        };
+        collect_placed_shapes(root, placed, hidden_ids, &mut shapes);
+    }
+
+    for (i, a) in shapes.iter().enumerate() {
+        for b in &shapes[i + 1..] {
+            let common = a
+                .path
+                .iter()
+                .zip(&b.path)
+                .take_while(|(x, y)| x == y)
+                .count();
+            // Siblings are covered by the sibling checks
+            if a.path.len() == common + 1 && b.path.len() == common + 1 {
+                continue;
+            }
+            // Containers that overlap are already reported as a pair
+            if a.chain[common].bounds.intersects(&b.chain[common].bounds) {
+                continue;
+            }
+            // Stacks overlap by design, and so do template internals
+            if common > 0
+                && matches!(
+                    a.chain[common - 1].element_type,
+                    ElementType::Layout(LayoutType::Stack)
+                )
+            {
+                continue;
+            }
+            if a.template.is_some() && a.template == b.template {
+                continue;
+            }
+            let (ea, eb) = (a.chain[a.chain.len() - 1], b.chain[b.chain.len() - 1]);
+            if !overlap_candidates(ea, eb, contains_ids) {
+                continue;
+            }
+            let name_a = placed_display_name(a);
+            let name_b = placed_display_name(b);
+            warnings.extend(overlap_warning(ea, eb, &name_a, &name_b));
+        }
+    }
+}
+
+fn placed_display_name(placed: &PlacedShape) -> String {
+    let n = placed.chain.len();
+    let parent = n.checked_sub(2).and_then(|i| placed.chain[i].id_str());
+    element_display_name(placed.chain[n - 1], parent, placed.path[n - 1])
 }
 
 /// Check pairwise overlaps among sibling elements
@@ -256,54 +461,84 @@ fn check_overlap_siblings(
 ) {
     for i in 0..siblings.len() {
         for j in (i + 1)..siblings.len() {
-            let a = &siblings[i];
-            let b = &siblings[j];
-
-            // Skip if both are non-opaque (two transparent zones)
-            if !is_opaque(a) && !is_opaque(b) {
+            let (a, b) = (&siblings[i], &siblings[j]);
+            if !overlap_candidates(a, b, contains_ids) {
                 continue;
             }
+            let name_a = element_display_name(a, parent_name, i);
+            let name_b = element_display_name(b, parent_name, j);
+            warnings.extend(overlap_warning(a, b, &name_a, &name_b));
+        }
+    }
+}
 
-            // For two non-text shapes, skip if either is non-opaque (zone background)
-            if !is_text_shape(a) && !is_text_shape(b) && (!is_opaque(a) || !is_opaque(b)) {
-                continue;
-            }
+/// Overlaps thinner than this (in either direction) are solver rounding,
+/// not a visible collision
+const MIN_OVERLAP_DEPTH: f64 = 1.0;
 
-            if let Some(id) = a.id_str() {
-                if contains_ids.contains(id) {
-                    continue;
-                }
-            }
-            if let Some(id) = b.id_str() {
-                if contains_ids.contains(id) {
-                    continue;
-                }
-            }
+/// Whether two elements are expected not to overlap: transparent zones,
+/// `contains` participants and text fully inside/outside a shape may
+fn overlap_candidates(
+    a: &ElementLayout,
+    b: &ElementLayout,
+    contains_ids: &HashSet<String>,
+) -> bool {
+    // Skip if both are non-opaque (two transparent zones)
+    if !is_opaque(a) && !is_opaque(b) {
+        return false;
+    }
 
-            // Text-on-shape: only flag if the text straddles the edge
-            if is_text_shape(a) != is_text_shape(b) {
-                let (text, shape) = if is_text_shape(a) { (a, b) } else { (b, a) };
-                if !is_text_shape_straddle(text, shape) {
-                    continue;
-                }
-            }
+    // For two non-text shapes, skip if either is non-opaque (zone background)
+    if !is_text_shape(a) && !is_text_shape(b) && (!is_opaque(a) || !is_opaque(b)) {
+        return false;
+    }
 
-            if a.bounds.intersects(&b.bounds) {
-                let overlap_w = a.bounds.right().min(b.bounds.right()) - a.bounds.x.max(b.bounds.x);
-                let overlap_h =
-                    a.bounds.bottom().min(b.bounds.bottom()) - a.bounds.y.max(b.bounds.y);
-                let name_a = element_display_name(a, parent_name, i);
-                let name_b = element_display_name(b, parent_name, j);
-                warnings.push(LintWarning {
-                    category: LintCategory::Overlap,
-                    message: format!(
-                        "elements {} and {} overlap by {:.0}x{:.0}px",
-                        name_a, name_b, overlap_w, overlap_h
-                    ),
-                });
-            }
-        }
+    // Skip if either is a contains target/container
+    if [a, b]
+        .iter()
+        .any(|e| e.id_str().is_some_and(|id| contains_ids.contains(id)))
+    {
+        return false;
+    }
+
+    // Text-on-shape: only flag if the text straddles the edge
+    if is_text_shape(a) != is_text_shape(b) {
+        let (text, shape) = if is_text_shape(a) { (a, b) } else { (b, a) };
+        return is_text_shape_straddle(text, shape);
+    }
+    true
+}
+
+/// Overlap warning for two elements whose bounds intersect by more than
+/// [`MIN_OVERLAP_DEPTH`] in both directions
+fn overlap_warning(
+    a: &ElementLayout,
+    b: &ElementLayout,
+    name_a: &str,
+    name_b: &str,
+) -> Option<LintWarning> {
+    if !a.bounds.intersects(&b.bounds) {
+        return None;
+    }
+    let overlap_w = a.bounds.right().min(b.bounds.right()) - a.bounds.x.max(b.bounds.x);
+    let overlap_h = a.bounds.bottom().min(b.bounds.bottom()) - a.bounds.y.max(b.bounds.y);
+    if overlap_w < MIN_OVERLAP_DEPTH || overlap_h < MIN_OVERLAP_DEPTH {
+        return None;
     }
+    Some(LintWarning {
+        category: LintCategory::Overlap,
+        element: a.id.as_ref().map(|id| id.0.clone()),
+        related: b.id.as_ref().map(|id| id.0.clone()),
+        span: None,
+        message: format!(
+            "elements {} and {} overlap by {:.0}x{:.0}px ({:.0}px\u{00b2})",
+            name_a,
+            name_b,
+            overlap_w,
+            overlap_h,
+            overlap_w * overlap_h
+        ),
+    })
 }
 
 /// Check if a group looks like a resolved template instance.
@@ -379,54 +614,13 @@ fn check_overlaps_recursive(
     if !skip_sibling_checks {
         for i in 0..children.len() {
             for j in (i + 1)..children.len() {
-                let a = &children[i];
-                let b = &children[j];
-
-                // Skip if both are non-opaque (two transparent zones)
-                if !is_opaque(a) && !is_opaque(b) {
-                    continue;
-                }
-
-                // For two non-text shapes, skip if either is non-opaque (zone background)
-                if !is_text_shape(a) && !is_text_shape(b) && (!is_opaque(a) || !is_opaque(b)) {
+                let (a, b) = (&children[i], &children[j]);
+                if !overlap_candidates(a, b, contains_ids) {
                     continue;
                 }
-
-                // Skip if either is a contains target/container
-                if let Some(id) = a.id_str() {
-                    if contains_ids.contains(id) {
-                        continue;
-                    }
-                }
-                if let Some(id) = b.id_str() {
-                    if contains_ids.contains(id) {
-                        continue;
-                    }
-                }
-
-                // Text-on-shape: only flag if the text straddles the edge
-                if is_text_shape(a) != is_text_shape(b) {
-                    let (text, shape) = if is_text_shape(a) { (a, b) } else { (b, a) };
-                    if !is_text_shape_straddle(text, shape) {
-                        continue;
-                    }
-                }
-
-                if a.bounds.intersects(&b.bounds) {
-                    let overlap_w =
-                        a.bounds.right().min(b.bounds.right()) - a.bounds.x.max(b.bounds.x);
-                    let overlap_h =
-                        a.bounds.bottom().min(b.bounds.bottom()) - a.bounds.y.max(b.bounds.y);
-                    let name_a = element_display_name(a, parent_name, i);
-                    let name_b = element_display_name(b, parent_name, j);
-                    warnings.push(LintWarning {
-                        category: LintCategory::Overlap,
-                        message: format!(
-                            "elements {} and {} overlap by {:.0}x{:.0}px",
-                            name_a, name_b, overlap_w, overlap_h
-                        ),
-                    });
-                }
+                let name_a = element_display_name(a, parent_name, i);
+                let name_b = element_display_name(b, parent_name, j);
+                warnings.extend(overlap_warning(a, b, &name_a, &name_b));
             }
         }
     } // end skip_sibling_checks
@@ -470,6 +664,9 @@ fn check_contains_in_stmts(
                                     let overflow = cb.x - (eb.x - pad);
                                     warnings.push(LintWarning {
                                         category: LintCategory::Containment,
+                                        element: Some(elem_id.node.0.clone()),
+                                        related: None,
+                                        span: None,
                                         message: format!(
                                             "element \"{}\" extends {:.0}px past left edge of container \"{}\"",
                                             elem_id.node.0, overflow, container.node.0
@@ -481,6 +678,9 @@ fn check_contains_in_stmts(
                                     let overflow = (eb.right() + pad) - cb.right();
                                     warnings.push(LintWarning {
                                         category: LintCategory::Containment,
+                                        element: Some(elem_id.node.0.clone()),
+                                        related: None,
+                                        span: None,
                                         message: format!(
                                             "element \"{}\" extends {:.0}px past right edge of container \"{}\"",
                                             elem_id.node.0, overflow, container.node.0
@@ -492,6 +692,9 @@ fn check_contains_in_stmts(
                                     let overflow = cb.y - (eb.y - pad);
                                     warnings.push(LintWarning {
                                         category: LintCategory::Containment,
+                                        element: Some(elem_id.node.0.clone()),
+                                        related: None,
+                                        span: None,
                                         message: format!(
                                             "element \"{}\" extends {:.0}px past top edge of container \"{}\"",
                                             elem_id.node.0, overflow, container.node.0
@@ -503,6 +706,9 @@ fn check_contains_in_stmts(
                                     let overflow = (eb.bottom() + pad) - cb.bottom();
                                     warnings.push(LintWarning {
                                         category: LintCategory::Containment,
+                                        element: Some(elem_id.node.0.clone()),
+                                        related: None,
+                                        span: None,
                                         message: format!(
                                             "element \"{}\" extends {:.0}px past bottom edge of container \"{}\"",
                                             elem_id.node.0, overflow, container.node.0
@@ -539,7 +745,7 @@ fn estimate_label_bbox(label: &LabelLayout) -> BoundingBox {
         .as_ref()
         .and_then(|s| s.font_size)
         .unwrap_or(14.0);
-    let width = label.text.len() as f64 * (font_size * 0.6);
+    let width = label.text.chars().count() as f64 * (font_size * 0.6);
     let height = font_size;
 
     let x = match label.anchor {
@@ -547,7 +753,11 @@ fn estimate_label_bbox(label: &LabelLayout) -> BoundingBox {
         TextAnchor::Middle => label.position.x - width / 2.0,
         TextAnchor::End => label.position.x - width,
     };
-    let y = label.position.y - height / 2.0;
+    let y = match label.vertical_align {
+        VerticalAlign::Top => label.position.y,
+        VerticalAlign::Middle => label.position.y - height / 2.0,
+        VerticalAlign::Bottom => label.position.y - height,
+    };
 
     BoundingBox::new(x, y, width, height)
 }
@@ -629,6 +839,9 @@ fn check_labels(result: &LayoutResult, warnings: &mut Vec<LintWarning>) {
             if a.bbox.intersects(&b.bbox) {
                 warnings.push(LintWarning {
                     category: LintCategory::Label,
+                    element: Some(a.owner.clone()),
+                    related: None,
+                    span: None,
                     message: format!("labels on \"{}\" and \"{}\" overlap", a.owner, b.owner),
                 });
             }
@@ -687,6 +900,9 @@ fn check_label_element_overlaps(result: &LayoutResult, warnings: &mut Vec<LintWa
                     - label.bbox.y.max(shape.bounds.y);
                 warnings.push(LintWarning {
                     category: LintCategory::Label,
+                    element: Some(label.owner.clone()),
+                    related: None,
+                    span: None,
                     message: format!(
                         "label on \"{}\" straddles the edge of element \"{}\"; \
                          overlaps by {:.0}x{:.0}px",
@@ -834,6 +1050,34 @@ fn collect_visible_elements(
     }
 }
 
+/// How far a route may run into an element's edge before it counts as
+/// passing through it (routes hugging a border are fine)
+const CROSSING_TOLERANCE: f64 = 2.0;
+
+/// `fetch (api→db)` for named connections, `api→db` otherwise
+fn connection_display_name(conn: &super::types::ConnectionLayout) -> String {
+    let ends = format!("{}→{}", conn.from_id.0, conn.to_id.0);
+    match &conn.name {
+        Some(name) => format!("{} ({})", name.0, ends),
+        None => ends,
+    }
+}
+
+/// An element's bounds shrunk by [`CROSSING_TOLERANCE`], or `None` if
+/// nothing is left (thin rails and separators)
+fn crossing_interior(bounds: &BoundingBox) -> Option<BoundingBox> {
+    let t = CROSSING_TOLERANCE;
+    if bounds.width <= 2.0 * t || bounds.height <= 2.0 * t {
+        return None;
+    }
+    Some(BoundingBox::new(
+        bounds.x + t,
+        bounds.y + t,
+        bounds.width - 2.0 * t,
+        bounds.height - 2.0 * t,
+    ))
+}
+
 fn check_connections(
     result: &LayoutResult,
     hidden_elements: &HashSet<String>,
@@ -857,6 +1101,7 @@ fn check_connections(
 
         let from_id = &conn.from_id.0;
         let to_id = &conn.to_id.0;
+        let conn_name = connection_display_name(conn);
 
         let path_start = match conn.path.first() {
             Some(p) => p,
@@ -867,68 +1112,74 @@ fn check_connections(
             None => continue,
         };
 
-        // Track which elements this connection crosses (deduplicate)
-        let mut crossed: HashSet<String> = HashSet::new();
+        // The endpoints and the parts of endpoint template instances
+        // (`server_body` for `server`) are related to the connection
+        let is_endpoint = |id: &str| {
+            [from_id, to_id].iter().any(|end| {
+                id == end.as_str()
+                    || id
+                        .strip_prefix(end.as_str())
+                        .is_some_and(|rest| rest.starts_with('_'))
+            })
+        };
 
-        if conn.routing_mode == RoutingMode::Curved {
-            // For curved connections, sample points along the Bézier curve and
-            // check if any sample falls inside an element's bounds.
-            // The path has 4 points for a cubic Bézier: start, cp1, cp2, end.
-            let samples = if conn.path.len() == 4 {
-                sample_cubic_bezier(&conn.path[0], &conn.path[1], &conn.path[2], &conn.path[3], 20)
-            } else {
-                // Fallback: treat as polyline
-                conn.path.clone()
+        // For curved connections, sample points along the Bézier curve and
+        // check if any sample falls inside an element's bounds.
+        // The path has 4 points for a cubic Bézier: start, cp1, cp2, end.
+        let samples = if conn.routing_mode != RoutingMode::Curved {
+            vec![]
+        } else if conn.path.len() == 4 {
+            sample_cubic_bezier(
+                &conn.path[0],
+                &conn.path[1],
+                &conn.path[2],
+                &conn.path[3],
+                20,
+            )
+        } else {
+            // Fallback: treat as polyline
+            conn.path.clone()
+        };
+
+        for oe in &opaque_elements {
+            if is_endpoint(&oe.id) {
+                continue;
+            }
+            // Skip if the connection starts or ends inside this element —
+            // the connection originates/terminates there, so crossing is expected
+            if oe.bounds.contains(*path_start) || oe.bounds.contains(*path_end) {
+                continue;
+            }
+            let Some(interior) = crossing_interior(&oe.bounds) else {
+                continue;
             };
 
-            for oe in &opaque_elements {
-                if crossed.contains(&oe.id) {
+            let verb = if conn.routing_mode == RoutingMode::Curved {
+                if !samples.iter().any(|p| interior.contains(*p)) {
                     continue;
                 }
-                if oe.bounds.contains(*path_start) || oe.bounds.contains(*path_end) {
+                "overlaps"
+            } else {
+                // For straight/orthogonal connections, check each path segment
+                let crosses = conn
+                    .path
+                    .windows(2)
+                    .any(|seg| line_segment_intersects_bbox(&seg[0], &seg[1], &interior));
+                if !crosses {
                     continue;
                 }
-                if samples.iter().any(|p| oe.bounds.contains(*p)) {
-                    crossed.insert(oe.id.clone());
-                    warnings.push(LintWarning {
-                        category: LintCategory::Connection,
-                        message: format!(
-                            "connection {}→{} overlaps element \"{}\"",
-                            from_id, to_id, oe.id
-                        ),
-                    });
-                }
-            }
-        } else {
-            // For straight/orthogonal connections, check each path segment
-            for seg in conn.path.windows(2) {
-                let p1 = &seg[0];
-                let p2 = &seg[1];
-
-                for oe in &opaque_elements {
-                    // Skip if already reported for this connection
-                    if crossed.contains(&oe.id) {
-                        continue;
-                    }
-
-                    if line_segment_intersects_bbox(p1, p2, &oe.bounds) {
-                        // Skip if the connection starts or ends inside this element —
-                        // the connection originates/terminates there, so crossing is expected
-                        if oe.bounds.contains(*path_start) || oe.bounds.contains(*path_end) {
-                            continue;
-                        }
-
-                        crossed.insert(oe.id.clone());
-                        warnings.push(LintWarning {
-                            category: LintCategory::Connection,
-                            message: format!(
-                                "connection {}→{} crosses element \"{}\"",
-                                from_id, to_id, oe.id
-                            ),
-                        });
-                    }
-                }
-            }
+                "crosses"
+            };
+            warnings.push(LintWarning {
+                category: LintCategory::Connection,
+                element: Some(oe.id.clone()),
+                related: Some(conn_name.clone()),
+                span: None,
+                message: format!(
+                    "connection {} {} element \"{}\"; route it around or move the element",
+                    conn_name, verb, oe.id
+                ),
+            });
         }
     }
 }
@@ -1006,6 +1257,9 @@ fn check_label_connection_overlaps(result: &LayoutResult, warnings: &mut Vec<Lin
                 if line_segment_intersects_bbox(p1, p2, &label.bbox) {
                     warnings.push(LintWarning {
                         category: LintCategory::Connection,
+                        element: Some(label.owner.clone()),
+                        related: None,
+                        span: None,
                         message: format!(
                             "label on \"{}\" overlaps connection {}",
                             label.owner, conn_name
@@ -1036,6 +1290,11 @@ fn check_alignment(result: &LayoutResult, warnings: &mut Vec<LintWarning>) {
             None => continue,
         };
 
+        // Stubs to a bus meet the rail head-on wherever the element sits
+        if is_bus_shape(from) || is_bus_shape(to) {
+            continue;
+        }
+
         let from_center = from.bounds.center();
         let to_center = to.bounds.center();
         let dx = (from_center.x - to_center.x).abs();
@@ -1050,6 +1309,9 @@ fn check_alignment(result: &LayoutResult, warnings: &mut Vec<LintWarning>) {
             // Nearly horizontal — small Y offset
             warnings.push(LintWarning {
                 category: LintCategory::Alignment,
+                element: Some(conn.from_id.0.clone()),
+                related: None,
+                span: None,
                 message: format!(
                     "connection {}→{} is nearly horizontal (off by {:.0}px); aligning Y positions would straighten it",
                     conn.from_id.0, conn.to_id.0, dy
@@ -1059,6 +1321,9 @@ fn check_alignment(result: &LayoutResult, warnings: &mut Vec<LintWarning>) {
             // Nearly vertical — small X offset
             warnings.push(LintWarning {
                 category: LintCategory::Alignment,
+                element: Some(conn.from_id.0.clone()),
+                related: None,
+                span: None,
                 message: format!(
                     "connection {}→{} is nearly vertical (off by {:.0}px); aligning X positions would straighten it",
                     conn.from_id.0, conn.to_id.0, dx
@@ -1166,6 +1431,9 @@ fn check_redundant_constants(doc: &Document, warnings: &mut Vec<LintWarning>) {
 
         warnings.push(LintWarning {
             category: LintCategory::RedundantConstant,
+            element: None,
+            related: None,
+            span: None,
             message,
         });
     }
@@ -1215,6 +1483,9 @@ fn check_reducible_bends(result: &LayoutResult, warnings: &mut Vec<LintWarning>)
         if shortest_len < f64::MAX {
             warnings.push(LintWarning {
                 category: LintCategory::ReducibleBend,
+                element: Some(conn.from_id.0.clone()),
+                related: None,
+                span: None,
                 message: format!(
                     "connection {}→{}: path jogs {:.0}px {} between bends; \
                      moving elements at least {:.0}px further apart {} would eliminate 2 corners",
@@ -1279,6 +1550,11 @@ fn check_missing_anchors_in_stmts(
                         continue;
                     }
 
+                    // Self-transitions loop over the element's top on their own
+                    if from_name == to_name {
+                        continue;
+                    }
+
                     // Check the solved connection to decide if anchors would help
                     if let Some(solved) = find_connection_layout(result, from_name, to_name) {
                         // Skip direct routing — straight lines don't need anchor guidance
@@ -1293,9 +1569,14 @@ fn check_missing_anchors_in_stmts(
                         }
                     }
 
-                    if conn.from.anchor.is_none() {
+                    let from = endpoint_with_modifiers(&conn.from, &conn.modifiers, "from");
+                    let to = endpoint_with_modifiers(&conn.to, &conn.modifiers, "to");
+                    if from.anchor.is_none() {
                         warnings.push(LintWarning {
                             category: LintCategory::MissingAnchor,
+                            element: None,
+                            related: None,
+                            span: Some(conn.from.element.span.clone()),
                             message: format!(
                                 "connection {}\u{2192}{}: no explicit anchor on source; \
                                  use e.g. {}.bottom -> {}.top for better routing",
@@ -1303,9 +1584,12 @@ fn check_missing_anchors_in_stmts(
                             ),
                         });
                     }
-                    if conn.to.anchor.is_none() {
+                    if to.anchor.is_none() {
                         warnings.push(LintWarning {
                             category: LintCategory::MissingAnchor,
+                            element: None,
+                            related: None,
+                            span: Some(conn.to.element.span.clone()),
                             message: format!(
                                 "connection {}\u{2192}{}: no explicit anchor on target; \
                                  use e.g. {}.bottom -> {}.top for better routing",
@@ -1422,6 +1706,9 @@ fn check_contrast_recursive(elem: &ElementLayout, warnings: &mut Vec<LintWarning
                         .unwrap_or_else(|| "<anon>".to_string());
                     warnings.push(LintWarning {
                         category: LintCategory::Contrast,
+                        element: elem.id.as_ref().map(|id| id.0.clone()),
+                        related: None,
+                        span: None,
                         message: format!(
                             "element {} has dark fill ({}) with a label; \
                              label text may be unreadable without CSS overrides for light text",
@@ -1472,6 +1759,9 @@ fn check_steep_direct(result: &LayoutResult, warnings: &mut Vec<LintWarning>) {
             let angle_deg = angle.to_degrees().round() as i32;
             warnings.push(LintWarning {
                 category: LintCategory::SteepDirect,
+                element: Some(conn.from_id.0.clone()),
+                related: None,
+                span: None,
                 message: format!(
                     "connection {}\u{2192}{} uses direct routing at {}\u{00b0} angle; \
                      steep diagonals look poor mixed with orthogonal routes \u{2014} \
@@ -1523,6 +1813,9 @@ fn check_crowded_layouts_in_stmts(
                         };
                         warnings.push(LintWarning {
                             category: LintCategory::CrowdedLayout,
+                            element: l.name.as_ref().map(|n| n.node.0.clone()),
+                            related: None,
+                            span: None,
                             message: format!(
                                 "{} {} has {} children; for >8 elements, consider using group with constraints instead",
                                 layout_kind, layout_name, child_count
@@ -1652,6 +1945,9 @@ fn check_over_constrained_in_stmts(
                                     let desc = format_constraint_expr(&c.expr);
                                     warnings.push(LintWarning {
                                         category: LintCategory::OverConstrained,
+                                        element: None,
+                                        related: None,
+                                        span: Some(stmt.span.clone()),
                                         message: format!(
                                             "constraint \"{}\" is violated by {:.0}px; the system may be over-constrained",
                                             desc, residual
@@ -1678,6 +1974,9 @@ fn check_over_constrained_in_stmts(
                                     let desc = format_constraint_expr(&c.expr);
                                     warnings.push(LintWarning {
                                         category: LintCategory::OverConstrained,
+                                        element: None,
+                                        related: None,
+                                        span: Some(stmt.span.clone()),
                                         message: format!(
                                             "constraint \"{}\" is violated by {:.0}px; the system may be over-constrained",
                                             desc, residual
@@ -1698,6 +1997,9 @@ fn check_over_constrained_in_stmts(
                                     let desc = format_constraint_expr(&c.expr);
                                     warnings.push(LintWarning {
                                         category: LintCategory::OverConstrained,
+                                        element: None,
+                                        related: None,
+                                        span: Some(stmt.span.clone()),
                                         message: format!(
                                             "constraint \"{}\" is violated by {:.0}px; the system may be over-constrained",
                                             desc, residual
@@ -1718,6 +2020,9 @@ fn check_over_constrained_in_stmts(
                                     let violation = value - solved;
                                     warnings.push(LintWarning {
                                         category: LintCategory::OverConstrained,
+                                        element: None,
+                                        related: None,
+                                        span: Some(stmt.span.clone()),
                                         message: format!(
                                             "constraint \"{}\" is violated by {:.0}px; the system may be over-constrained",
                                             desc, violation
@@ -1738,6 +2043,9 @@ fn check_over_constrained_in_stmts(
                                     let violation = solved - value;
                                     warnings.push(LintWarning {
                                         category: LintCategory::OverConstrained,
+                                        element: None,
+                                        related: None,
+                                        span: Some(stmt.span.clone()),
                                         message: format!(
                                             "constraint \"{}\" is violated by {:.0}px; the system may be over-constrained",
                                             desc, violation
@@ -1768,20 +2076,30 @@ fn check_over_constrained_in_stmts(
 /// where the text visibly overflows the element.
 fn check_label_overflow(result: &LayoutResult, warnings: &mut Vec<LintWarning>) {
     for elem in &result.root_elements {
-        check_label_overflow_recursive(elem, warnings);
+        check_label_overflow_recursive(elem, None, warnings);
     }
 }
 
-fn check_label_overflow_recursive(elem: &ElementLayout, warnings: &mut Vec<LintWarning>) {
+/// Room to leave on each side of a label when suggesting a shape size
+const LABEL_MARGIN: f64 = 8.0;
+
+fn check_label_overflow_recursive(
+    elem: &ElementLayout,
+    parent: Option<&ElementLayout>,
+    warnings: &mut Vec<LintWarning>,
+) {
     if let Some(label) = &elem.label {
-        // Skip text elements — they don't have a "container" to overflow
-        if !is_text_shape(elem) {
+        // Skip text elements — they don't have a "container" to overflow —
+        // and buses, whose label sits above the rail
+        if !is_text_shape(elem) && !is_bus_shape(elem) {
             let label_bbox = estimate_label_bbox(label);
             let shape_bounds = &elem.bounds;
 
             // Check if label is wider or taller than the shape
             let width_overflow = label_bbox.width > shape_bounds.width + 2.0;
             let height_overflow = label_bbox.height > shape_bounds.height + 2.0;
+            let min_width = (label_bbox.width + 2.0 * LABEL_MARGIN).ceil();
+            let min_height = (label_bbox.height + 2.0 * LABEL_MARGIN).ceil();
 
             if width_overflow || height_overflow {
                 let name = elem
@@ -1793,40 +2111,140 @@ fn check_label_overflow_recursive(elem: &ElementLayout, warnings: &mut Vec<LintW
 
                 let detail = if width_overflow && height_overflow {
                     format!(
-                        "label \"{}\" on {} overflows both width ({:.0}px label vs {:.0}px shape) and height ({:.0}px vs {:.0}px); consider using a separate text element positioned nearby",
+                        "label \"{}\" on {} overflows both width ({:.0}px label vs {:.0}px shape) and height ({:.0}px vs {:.0}px); make the shape at least {:.0}x{:.0}px or use a separate text element positioned nearby",
                         label_text, name,
                         label_bbox.width, shape_bounds.width,
                         label_bbox.height, shape_bounds.height,
+                        min_width, min_height,
                     )
                 } else if width_overflow {
                     format!(
-                        "label \"{}\" on {} overflows width ({:.0}px label vs {:.0}px shape); consider making the shape wider or using a separate text element",
+                        "label \"{}\" on {} overflows width ({:.0}px label vs {:.0}px shape); make the shape at least {:.0}px wide or use a separate text element",
                         label_text, name,
                         label_bbox.width, shape_bounds.width,
+                        min_width,
                     )
                 } else {
                     format!(
-                        "label \"{}\" on {} overflows height ({:.0}px label vs {:.0}px shape); consider making the shape taller or using a separate text element",
+                        "label \"{}\" on {} overflows height ({:.0}px label vs {:.0}px shape); make the shape at least {:.0}px tall or use a separate text element",
                         label_text, name,
                         label_bbox.height, shape_bounds.height,
+                        min_height,
                     )
                 };
 
                 warnings.push(LintWarning {
                     category: LintCategory::LabelOverflow,
+                    element: elem.id.as_ref().map(|id| id.0.clone()),
+                    related: None,
+                    span: None,
                     message: detail,
                 });
             }
         }
     }
+    if let Some(container) = parent {
+        check_text_clipping(elem, container, warnings);
+    }
     for child in &elem.children {
-        check_label_overflow_recursive(child, warnings);
+        check_label_overflow_recursive(child, Some(elem), warnings);
     }
 }
 
+/// Estimated extent of a text element: its laid-out bounds, widened to the
+/// estimated width of its content if that is larger
+fn estimate_text_bbox(elem: &ElementLayout) -> Option<BoundingBox> {
+    let ElementType::Shape(ShapeType::Text { content }) = &elem.element_type else {
+        return None;
+    };
+    let font_size = elem.styles.font_size.unwrap_or(14.0);
+    let width = content.chars().count() as f64 * (font_size * 0.6);
+    if width <= elem.bounds.width {
+        return Some(elem.bounds);
+    }
+    let center = elem.bounds.center();
+    Some(BoundingBox::new(
+        center.x - width / 2.0,
+        elem.bounds.y,
+        width,
+        elem.bounds.height,
+    ))
+}
+
+/// Whether a group or layout draws a background or border, so text
+/// sticking out of it is visible as such
+fn draws_container_box(elem: &ElementLayout) -> bool {
+    let painted = |paint: &Option<String>| {
+        paint
+            .as_deref()
+            .is_some_and(|p| !p.eq_ignore_ascii_case("none"))
+    };
+    painted(&elem.styles.fill) || painted(&elem.styles.stroke)
+}
+
+/// Text elements sticking out of the group or layout box they belong to
+fn check_text_clipping(
+    elem: &ElementLayout,
+    container: &ElementLayout,
+    warnings: &mut Vec<LintWarning>,
+) {
+    if !draws_container_box(container) {
+        return;
+    }
+    let Some(text) = estimate_text_bbox(elem) else {
+        return;
+    };
+    let cb = &container.bounds;
+    let left = (cb.x - text.x).max(0.0);
+    let right = (text.right() - cb.right()).max(0.0);
+    let top = (cb.y - text.y).max(0.0);
+    let bottom = (text.bottom() - cb.bottom()).max(0.0);
+    if left + right <= 2.0 && top + bottom <= 2.0 {
+        return;
+    }
+
+    let name = elem
+        .id
+        .as_ref()
+        .map(|id| format!("\"{}\"", id.0))
+        .unwrap_or_else(|| "<anon>".to_string());
+    let container_name = container
+        .id
+        .as_ref()
+        .map(|id| format!("\"{}\"", id.0))
+        .unwrap_or_else(|| "<anon>".to_string());
+    let mut fixes = Vec::new();
+    if left + right > 2.0 {
+        fixes.push(format!(
+            "at least {:.0}px wide",
+            (cb.width + left + right).ceil()
+        ));
+    }
+    if top + bottom > 2.0 {
+        fixes.push(format!(
+            "at least {:.0}px tall",
+            (cb.height + top + bottom).ceil()
+        ));
+    }
+    warnings.push(LintWarning {
+        category: LintCategory::LabelOverflow,
+        element: elem.id.as_ref().map(|id| id.0.clone()),
+        related: container.id.as_ref().map(|id| id.0.clone()),
+        span: None,
+        message: format!(
+            "text {} extends {:.0}px outside container {}; make the container {}",
+            name,
+            left.max(right).max(top).max(bottom),
+            container_name,
+            fixes.join(" and ")
+        ),
+    });
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::layout::Padding;
     use crate::parser::ast::Identifier;
 
     fn make_rect(id: Option<&str>, x: f64, y: f64, w: f64, h: f64) -> ElementLayout {
@@ -1840,6 +2258,9 @@ mod tests {
             anchors: super::super::types::AnchorSet::default(),
             path_normalize: false,
             z_order: 0,
+            padding: Padding::default(),
+            span: None,
+            pre_transform_size: None,
         }
     }
 
@@ -1869,6 +2290,9 @@ mod tests {
             anchors: super::super::types::AnchorSet::default(),
             path_normalize: false,
             z_order: 0,
+            padding: Padding::default(),
+            span: None,
+            pre_transform_size: None,
         }
     }
 
@@ -1888,6 +2312,9 @@ mod tests {
             anchors: super::super::types::AnchorSet::default(),
             path_normalize: false,
             z_order: 0,
+            padding: Padding::default(),
+            span: None,
+            pre_transform_size: None,
         }
     }
 
@@ -1924,6 +2351,43 @@ mod tests {
         assert_eq!(warnings.len(), 0);
     }
 
+    #[test]
+    fn test_sliver_overlap_ignored() {
+        let group = make_group(
+            Some("g"),
+            vec![
+                make_rect(Some("a"), 0.0, 0.0, 50.0, 50.0),
+                make_rect(Some("b"), 49.5, 0.0, 50.0, 50.0),
+            ],
+        );
+        let mut warnings = Vec::new();
+        check_overlaps_recursive(&group, None, &HashSet::new(), &mut warnings);
+        assert_eq!(warnings.len(), 0);
+    }
+
+    #[test]
+    fn test_overlap_across_containers() {
+        // Groups keep their own bounds, but "b" was moved out of its group onto "a"
+        let mut right = make_group(
+            Some("right"),
+            vec![make_rect(Some("b"), 200.0, 0.0, 50.0, 50.0)],
+        );
+        right.bounds = BoundingBox::new(200.0, 0.0, 50.0, 50.0);
+        right.children[0].bounds = BoundingBox::new(30.0, 10.0, 50.0, 50.0);
+        let left = make_group(
+            Some("left"),
+            vec![make_rect(Some("a"), 0.0, 0.0, 50.0, 50.0)],
+        );
+        let roots = [&left, &right];
+
+        let mut warnings = Vec::new();
+        check_nested_overlaps(&roots, &HashSet::new(), &HashSet::new(), &mut warnings);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].element.as_deref(), Some("a"));
+        assert_eq!(warnings[0].related.as_deref(), Some("b"));
+        assert!(warnings[0].message.contains("20x40px (800px"));
+    }
+
     #[test]
     fn test_overlap_skipped_for_contains_target() {
         let group = make_group(
@@ -2042,13 +2506,17 @@ mod tests {
                     },
                     value,
                 },
+                strength: None,
             }),
             span,
         )
     }
 
     fn make_doc(stmts: Vec<crate::parser::ast::Spanned<Statement>>) -> Document {
-        Document { statements: stmts }
+        Document {
+            statements: stmts,
+            comments: vec![],
+        }
     }
 
     #[test]
@@ -2127,6 +2595,7 @@ mod tests {
                 modifiers: vec![],
                 anchors: vec![],
                 is_template_instance: false,
+                layer: None,
             }),
             span,
         )]);
@@ -2155,7 +2624,10 @@ mod tests {
             styles: super::super::types::ResolvedStyles::default(),
             label: None,
             routing_mode,
+            crossings: None,
             name: None,
+            seq: None,
+            span: None,
         }
     }
 
@@ -2269,6 +2741,33 @@ mod tests {
         assert!(warnings.is_empty());
     }
 
+    // ── Connection-through-shape tests ──
+
+    #[test]
+    fn test_connection_through_shape() {
+        let mut conn = make_connection(
+            "a",
+            "b",
+            vec![Point::new(0.0, 50.0), Point::new(300.0, 50.0)],
+            RoutingMode::Orthogonal,
+        );
+        conn.name = Some(Identifier("fetch".to_string()));
+        let mut result = make_layout_with_connections(vec![conn]);
+        result.root_elements = vec![
+            make_rect(Some("wall"), 100.0, 0.0, 50.0, 100.0),
+            // The route runs along this one's top edge
+            make_rect(Some("shelf"), 200.0, 50.0, 50.0, 50.0),
+            // Part of the target template instance
+            make_rect(Some("b_icon"), 260.0, 25.0, 20.0, 50.0),
+        ];
+        let mut warnings = Vec::new();
+        check_connections(&result, &HashSet::new(), &HashSet::new(), &mut warnings);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].element.as_deref(), Some("wall"));
+        assert_eq!(warnings[0].related.as_deref(), Some("fetch (a→b)"));
+        assert!(warnings[0].message.contains("crosses element \"wall\""));
+    }
+
     // ── Label overflow tests ─────────────────────────────────────
 
     fn make_rect_with_label(
@@ -2284,6 +2783,7 @@ mod tests {
             text: label_text.to_string(),
             position: Point::new(x + w / 2.0, y + h / 2.0),
             anchor: TextAnchor::Middle,
+            vertical_align: VerticalAlign::Middle,
             styles: None,
         });
         elem
@@ -2320,6 +2820,7 @@ mod tests {
         check_label_overflow(&result, &mut warnings);
         assert_eq!(warnings.len(), 1);
         assert!(warnings[0].message.contains("overflows width"));
+        assert!(warnings[0].message.contains("at least 184px wide"));
     }
 
     #[test]
@@ -2336,4 +2837,35 @@ mod tests {
         check_label_overflow(&result, &mut warnings);
         assert!(warnings.is_empty());
     }
+
+    #[test]
+    fn test_text_clipped_by_container() {
+        // 33 characters at 14px ≈ 277px of text in a 100px-wide box
+        let mut text = make_text(Some("caption"), 30.0, 10.0, 40.0, 20.0);
+        text.element_type = ElementType::Shape(ShapeType::Text {
+            content: "A caption much wider than its box".to_string(),
+        });
+        let mut boxed = make_group(Some("card"), vec![text]);
+        boxed.bounds = BoundingBox::new(0.0, 0.0, 100.0, 40.0);
+        let result = LayoutResult {
+            root_elements: vec![boxed],
+            connections: vec![],
+            elements: HashMap::new(),
+            bounds: BoundingBox::new(0.0, 0.0, 100.0, 100.0),
+        };
+
+        // An unpainted group is only a logical grouping
+        let mut warnings = Vec::new();
+        check_label_overflow(&result, &mut warnings);
+        assert!(warnings.is_empty());
+
+        let mut result = result;
+        result.root_elements[0].styles.fill = Some("#eef".to_string());
+        check_label_overflow(&result, &mut warnings);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].element.as_deref(), Some("caption"));
+        assert_eq!(warnings[0].related.as_deref(), Some("card"));
+        assert!(warnings[0].message.contains("outside container \"card\""));
+        assert!(warnings[0].message.contains("at least 278px wide"));
+    }
 }
@@ -9,6 +9,8 @@ pub mod engine;
 pub mod error;
 pub mod keyframe;
 pub mod lint;
+pub mod organic;
+pub mod paginate;
 pub mod routing;
 pub mod solver;
 pub mod transform;
@@ -18,15 +20,19 @@ pub use collector::ConstraintCollector;
 pub use solver::{
     ConstraintSolver, LayoutConstraint, LayoutProperty, LayoutVariable, Solution, SolverError,
 };
-pub use transform::RotationTransform;
+pub use transform::{ElementTransform, RotationTransform};
 
 #[cfg(test)]
 mod solver_spike;
 
 pub use config::LayoutConfig;
-pub use engine::{compute, resolve_constrain_statements, resolve_constraints};
+pub use engine::{
+    compute, place_notes, place_titles_and_legends, resolve_constrain_statements,
+    resolve_constraints,
+};
 pub use error::LayoutError;
-pub use routing::{route_connections, RoutingMode};
+pub use paginate::{paginate, Continuation, Page, PageSpec};
+pub use routing::{route_connections, CrossingStyle, RoutingMode};
 pub use types::*;
 
 use std::collections::HashSet;
@@ -102,11 +108,48 @@ fn collect_ids_from_statement(stmt: &Statement, ids: &mut HashSet<String>) {
             ids.insert(t.name.node.0.clone());
         }
         Statement::TemplateInstance(inst) => {
-            // Template instances define new element identifiers
+            // Template instances define new element identifiers, as do the
+            // children they pass to the template's slot
             ids.insert(inst.instance_name.node.0.clone());
+            for child in &inst.children {
+                collect_ids_from_statement(&child.node, ids);
+            }
+        }
+        Statement::Embed(e) => {
+            ids.insert(e.name.node.0.clone());
+        }
+        Statement::Note(n) => {
+            for child in &n.children {
+                collect_ids_from_statement(&child.node, ids);
+            }
+        }
+        Statement::Repeat(r) => {
+            // Names in a repeat body are only known once it is expanded
+            for child in &r.body {
+                collect_ids_from_statement(&child.node, ids);
+            }
         }
-        Statement::Export(_) | Statement::AnchorDecl(_) | Statement::Keyframe(_) => {
-            // Exports, anchor declarations, and keyframes don't define new element identifiers
+        Statement::If(i) => {
+            for child in i.then_body.iter().chain(&i.else_body) {
+                collect_ids_from_statement(&child.node, ids);
+            }
+        }
+        Statement::Step(s) => {
+            for child in &s.children {
+                collect_ids_from_statement(&child.node, ids);
+            }
+        }
+        Statement::Import(_)
+        | Statement::Export(_)
+        | Statement::AnchorDecl(_)
+        | Statement::Keyframe(_)
+        | Statement::Let(_)
+        | Statement::Slot
+        | Statement::Canvas(_)
+        | Statement::Title(_)
+        | Statement::Legend(_)
+        | Statement::Animate(_) => {
+            // Imports, exports, anchor declarations, keyframes, variables, slots, the canvas, titles, legends and animations don't define new element identifiers
         }
     }
 }
@@ -156,11 +199,31 @@ fn validate_refs_in_statement(
         }
         Statement::Layout(l) => {
             for child in &l.children {
+                reject_nested_top_level(&child.node)?;
                 validate_refs_in_statement(&child.node, defined, &child.span)?;
             }
         }
         Statement::Group(g) => {
             for child in &g.children {
+                reject_nested_top_level(&child.node)?;
+                validate_refs_in_statement(&child.node, defined, &child.span)?;
+            }
+        }
+        Statement::Step(s) => {
+            for child in &s.children {
+                validate_refs_in_statement(&child.node, defined, &child.span)?;
+            }
+        }
+        Statement::Note(n) => {
+            if !defined.contains(&n.target.element.node.0) {
+                return Err(LayoutError::UndefinedIdentifier {
+                    name: n.target.element.node.0.clone(),
+                    span: n.target.element.span.clone(),
+                    suggestions: find_similar(defined, &n.target.element.node.0, 2),
+                });
+            }
+            for child in &n.children {
+                reject_nested_top_level(&child.node)?;
                 validate_refs_in_statement(&child.node, defined, &child.span)?;
             }
         }
@@ -172,13 +235,32 @@ fn validate_refs_in_statement(
             // Validate element references in constrain expressions
             validate_constraint_expr_refs(&c.expr, defined, _span)?;
         }
-        Statement::Shape(_) => {}
-        Statement::TemplateDecl(_) => {
-            // Template declarations are validated separately during template resolution
+        Statement::Animate(a) => {
+            if !defined.contains(&a.target.node.0) {
+                return Err(LayoutError::UndefinedIdentifier {
+                    name: a.target.node.0.clone(),
+                    span: a.target.span.clone(),
+                    suggestions: find_similar(defined, &a.target.node.0, 2),
+                });
+            }
+        }
+        Statement::Shape(_)
+        | Statement::Embed(_)
+        | Statement::Canvas(_)
+        | Statement::Title(_)
+        | Statement::Legend(_) => {}
+        Statement::TemplateDecl(_)
+        | Statement::Import(_)
+        | Statement::Repeat(_)
+        | Statement::If(_)
+        | Statement::Let(_)
+        | Statement::Slot => {
+            // Template declarations, imports, repeats, conditionals, variables and slots are validated separately during template resolution
         }
         Statement::TemplateInstance(inst) => {
-            // Validate that the template name is defined
-            if !defined.contains(&inst.template_name.node.0) {
+            // Validate that the template name is defined (imported names are checked on import)
+            let name = &inst.template_name.node.0;
+            if !name.contains('.') && !defined.contains(name) {
                 return Err(LayoutError::UndefinedIdentifier {
                     name: inst.template_name.node.0.clone(),
                     span: inst.template_name.span.clone(),
@@ -221,6 +303,27 @@ fn validate_refs_in_statement(
     Ok(())
 }
 
+/// Notes, titles and legends are placed after layout, layers split the
+/// rendered document, the canvas sizes all of it, animations are emitted
+/// alongside it and steps slice it into slides, so these can only appear at
+/// the document root
+fn reject_nested_top_level(stmt: &Statement) -> Result<(), LayoutError> {
+    let kind = match stmt {
+        Statement::Note(_) => "note",
+        Statement::Canvas(_) => "canvas",
+        Statement::Title(t) => t.kind.as_str(),
+        Statement::Legend(_) => "legend",
+        Statement::Animate(_) => "animate",
+        Statement::Step(_) => "step",
+        Statement::Group(g) if g.layer.is_some() => "layer",
+        _ => return Ok(()),
+    };
+    Err(LayoutError::validation_error(format!(
+        "{} must be declared at the top level of the document",
+        kind
+    )))
+}
+
 /// Validate element references within a constraint expression
 fn validate_constraint_expr_refs(
     expr: &crate::parser::ast::ConstraintExpr,
@@ -274,6 +377,12 @@ fn validate_constraint_expr_refs(
         ConstraintExpr::LessOrEqual { left, .. } => {
             validate_prop_ref(left)?;
         }
+        ConstraintExpr::Linear { left, terms, .. } => {
+            validate_prop_ref(left)?;
+            for term in terms {
+                validate_prop_ref(&term.property)?;
+            }
+        }
         ConstraintExpr::Midpoint { target, a, b, .. } => {
             validate_prop_ref(target)?;
             validate_ident(a)?;
@@ -289,12 +398,23 @@ fn validate_constraint_expr_refs(
                 validate_ident(elem)?;
             }
         }
+        ConstraintExpr::EqualSize { elements, .. } => {
+            for elem in elements {
+                validate_ident(elem)?;
+            }
+        }
+        ConstraintExpr::Relative {
+            subject, anchor, ..
+        } => {
+            validate_ident(subject)?;
+            validate_ident(anchor)?;
+        }
     }
     Ok(())
 }
 
 /// Compute Levenshtein edit distance between two strings
-fn levenshtein_distance(a: &str, b: &str) -> usize {
+pub(crate) fn levenshtein_distance(a: &str, b: &str) -> usize {
     let a_chars: Vec<char> = a.chars().collect();
     let b_chars: Vec<char> = b.chars().collect();
     let m = a_chars.len();
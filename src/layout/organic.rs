@@ -0,0 +1,197 @@
+//! Seeded jitter for a hand-drawn, organic look
+//!
+//! Concept maps and mind maps look mechanical when every box sits exactly on
+//! the grid. With `organic` enabled, leaf elements are displaced by a small
+//! bounded random offset before constraint solving, so explicit constraints
+//! still win, and curved connections get slightly uneven control arms.
+//!
+//! The jitter is fully deterministic: the same seed and document always
+//! produce the same output.
+
+use super::types::{BoundingBox, ConnectionLayout, ElementLayout, LayoutResult, Point};
+use super::RoutingMode;
+
+/// Small deterministic PRNG (SplitMix64), so output is stable across platforms
+/// without pulling in a random number crate.
+struct Jitter {
+    state: u64,
+    amount: f64,
+}
+
+impl Jitter {
+    fn new(seed: u64, amount: f64) -> Self {
+        Self {
+            state: seed,
+            amount,
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform offset in `[-amount, amount]`
+    fn offset(&mut self) -> f64 {
+        let unit = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        (unit * 2.0 - 1.0) * self.amount
+    }
+}
+
+/// Derive a per-document seed from its source text (FNV-1a).
+pub fn seed_from_source(source: &str) -> u64 {
+    source.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Displace every leaf element by up to `amount` on each axis.
+///
+/// Containers keep their position; only their children move, and never past
+/// the area their children already span, so container padding is kept. Run
+/// this before constraint solving so user constraints are still satisfied
+/// exactly.
+pub fn jitter_elements(result: &mut LayoutResult, amount: f64, seed: u64) {
+    if amount <= 0.0 {
+        return;
+    }
+    let mut jitter = Jitter::new(seed, amount);
+    for element in &mut result.root_elements {
+        jitter_element(element, None, &mut jitter);
+    }
+    result.rebuild_index();
+    result.compute_bounds();
+}
+
+fn jitter_element(element: &mut ElementLayout, area: Option<BoundingBox>, jitter: &mut Jitter) {
+    if !element.children.is_empty() {
+        let content = element
+            .children
+            .iter()
+            .map(|c| c.bounds)
+            .reduce(|a, b| a.union(&b));
+        for child in &mut element.children {
+            jitter_element(child, content, jitter);
+        }
+        return;
+    }
+    let (mut dx, mut dy) = (jitter.offset(), jitter.offset());
+    if let Some(area) = area {
+        let b = element.bounds;
+        dx = dx.clamp((area.x - b.x).min(0.0), (area.right() - b.right()).max(0.0));
+        dy = dy.clamp(
+            (area.y - b.y).min(0.0),
+            (area.bottom() - b.bottom()).max(0.0),
+        );
+    }
+    element.bounds.x += dx;
+    element.bounds.y += dy;
+    if let Some(label) = &mut element.label {
+        label.position.x += dx;
+        label.position.y += dy;
+    }
+    element.anchors.translate(dx, dy);
+}
+
+/// Vary the control points of curved connections by up to `amount`.
+///
+/// Endpoints stay attached to their elements; only the curve tension changes.
+pub fn jitter_curves(connections: &mut [ConnectionLayout], amount: f64, seed: u64) {
+    if amount <= 0.0 {
+        return;
+    }
+    // Use a different stream than the element jitter
+    let mut jitter = Jitter::new(!seed, amount);
+    for conn in connections
+        .iter_mut()
+        .filter(|c| c.routing_mode == RoutingMode::Curved)
+    {
+        let len = conn.path.len();
+        if len < 3 {
+            continue;
+        }
+        for point in &mut conn.path[1..len - 1] {
+            *point = Point::new(point.x + jitter.offset(), point.y + jitter.offset());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jitter_is_bounded_and_deterministic() {
+        let mut a = Jitter::new(42, 3.0);
+        let mut b = Jitter::new(42, 3.0);
+        for _ in 0..1000 {
+            let (x, y) = (a.offset(), b.offset());
+            assert_eq!(x, y);
+            assert!((-3.0..=3.0).contains(&x));
+        }
+    }
+
+    #[test]
+    fn test_constraints_hold_after_jitter() {
+        use crate::layout::{compute, resolve_constrain_statements, LayoutConfig};
+
+        let doc = crate::parse("rect a\nrect b\nrect c\nconstrain b.left = a.right + 10").unwrap();
+        let config = LayoutConfig::default();
+        let mut result = compute(&doc, &config).unwrap();
+        let before = result.elements.get("c").unwrap().bounds;
+
+        jitter_elements(&mut result, 5.0, 7);
+        resolve_constrain_statements(&mut result, &doc, &config).unwrap();
+
+        let (a, b) = (
+            result.elements.get("a").unwrap().bounds,
+            result.elements.get("b").unwrap().bounds,
+        );
+        assert!((b.x - (a.right() + 10.0)).abs() < 0.01);
+        let after = result.elements.get("c").unwrap().bounds;
+        assert_ne!((before.x, before.y), (after.x, after.y));
+        assert!((after.x - before.x).abs() <= 5.0 && (after.y - before.y).abs() <= 5.0);
+    }
+
+    #[test]
+    fn test_jitter_stays_inside_containers() {
+        use crate::layout::{compute, LayoutConfig};
+
+        let doc =
+            crate::parse("row r [padding: 10] { rect a rect b col c { rect d rect e } }").unwrap();
+        let mut result = compute(&doc, &LayoutConfig::default()).unwrap();
+        let row = result.elements.get("r").unwrap().bounds;
+        let col = result.elements.get("c").unwrap().bounds;
+
+        jitter_elements(&mut result, 50.0, 3);
+        // Children keep the row's padding and stay inside the nested column
+        for (id, parent, padding) in [
+            ("a", row, 10.0),
+            ("b", row, 10.0),
+            ("d", col, 0.0),
+            ("e", col, 0.0),
+        ] {
+            let b = result.elements.get(id).unwrap().bounds;
+            let eps = 1e-9;
+            assert!(
+                b.x >= parent.x + padding - eps
+                    && b.y >= parent.y + padding - eps
+                    && b.right() <= parent.right() - padding + eps
+                    && b.bottom() <= parent.bottom() - padding + eps,
+                "{} at {:?} left {:?}",
+                id,
+                b,
+                parent
+            );
+        }
+    }
+
+    #[test]
+    fn test_seed_from_source_differs_per_document() {
+        assert_eq!(seed_from_source("rect a"), seed_from_source("rect a"));
+        assert_ne!(seed_from_source("rect a"), seed_from_source("rect b"));
+    }
+}
@@ -0,0 +1,291 @@
+//! Splitting a layout into printable pages
+//!
+//! Very large diagrams (generated system maps, wiring plans) do not fit on a
+//! single sheet. This module tiles the layout bounds into fixed-size pages with
+//! a configurable overlap, and records where connections leave a page so the
+//! renderer can draw continuation markers ("continues on page 3").
+
+use super::types::{BoundingBox, LayoutResult, Point};
+
+/// Page size and overlap used for tiling
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageSpec {
+    /// Page width in layout units
+    pub width: f64,
+    /// Page height in layout units
+    pub height: f64,
+    /// Distance shared by neighbouring pages, so content cut at an edge
+    /// appears in full on at least one of them
+    pub overlap: f64,
+}
+
+impl PageSpec {
+    /// Create a page spec without overlap
+    pub fn new(width: f64, height: f64) -> Self {
+        Self {
+            width,
+            height,
+            overlap: 0.0,
+        }
+    }
+
+    /// Set the overlap between neighbouring pages
+    pub fn with_overlap(mut self, overlap: f64) -> Self {
+        self.overlap = overlap;
+        self
+    }
+}
+
+/// A point where a connection crosses the edge of a page
+#[derive(Debug, Clone, PartialEq)]
+pub struct Continuation {
+    /// Index into `LayoutResult::connections`
+    pub connection: usize,
+    /// Where the connection crosses the page edge
+    pub point: Point,
+    /// Page holding the far end of the connection, if it lies on the canvas
+    pub remote_page: Option<usize>,
+}
+
+/// One tile of a paginated layout
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    /// Page number in row-major order, starting at 0
+    pub index: usize,
+    pub row: usize,
+    pub column: usize,
+    /// Region of the layout shown on this page
+    pub viewport: BoundingBox,
+    /// Named elements that are at least partially visible, sorted
+    pub element_ids: Vec<String>,
+    /// Connections leaving or entering this page
+    pub continuations: Vec<Continuation>,
+}
+
+/// Split a layout into pages of the given size.
+///
+/// Pages are laid out row-major over the layout bounds. A layout smaller than
+/// one page yields a single page. The overlap is clamped so every page still
+/// advances by at least one unit.
+pub fn paginate(result: &LayoutResult, spec: PageSpec) -> Vec<Page> {
+    let bounds = result.bounds;
+    let step_x = (spec.width - spec.overlap).max(1.0);
+    let step_y = (spec.height - spec.overlap).max(1.0);
+    let columns = tile_count(bounds.width, spec.width, step_x);
+    let rows = tile_count(bounds.height, spec.height, step_y);
+
+    let viewports: Vec<BoundingBox> = (0..rows)
+        .flat_map(|row| {
+            (0..columns).map(move |column| {
+                BoundingBox::new(
+                    bounds.x + column as f64 * step_x,
+                    bounds.y + row as f64 * step_y,
+                    spec.width,
+                    spec.height,
+                )
+            })
+        })
+        .collect();
+
+    viewports
+        .iter()
+        .enumerate()
+        .map(|(index, viewport)| {
+            let mut element_ids: Vec<String> = result
+                .elements
+                .iter()
+                .filter(|(_, e)| e.bounds.intersects(viewport))
+                .map(|(id, _)| id.clone())
+                .collect();
+            element_ids.sort();
+
+            Page {
+                index,
+                row: index / columns,
+                column: index % columns,
+                viewport: *viewport,
+                element_ids,
+                continuations: find_continuations(result, viewport, &viewports),
+            }
+        })
+        .collect()
+}
+
+/// Number of tiles of `size`, advancing by `step`, needed to cover `extent`
+fn tile_count(extent: f64, size: f64, step: f64) -> usize {
+    if extent <= size {
+        1
+    } else {
+        1 + ((extent - size) / step).ceil() as usize
+    }
+}
+
+/// Page containing a point, preferring the first in row-major order
+fn page_of(point: Point, viewports: &[BoundingBox]) -> Option<usize> {
+    viewports.iter().position(|v| v.contains(point))
+}
+
+fn find_continuations(
+    result: &LayoutResult,
+    viewport: &BoundingBox,
+    viewports: &[BoundingBox],
+) -> Vec<Continuation> {
+    let mut continuations = vec![];
+    for (index, conn) in result.connections.iter().enumerate() {
+        let (Some(&start), Some(&end)) = (conn.path.first(), conn.path.last()) else {
+            continue;
+        };
+        for seg in conn.path.windows(2) {
+            let (a, b) = (seg[0], seg[1]);
+            let Some((t_in, t_out)) = clip_segment(viewport, a, b) else {
+                continue;
+            };
+            // A segment that only grazes a corner neither enters nor leaves
+            if t_in > 0.0 && t_out < 1.0 && t_in == t_out {
+                continue;
+            }
+            let at = |t: f64| Point::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t);
+            // Entering from the start points back at the source's page,
+            // leaving towards the end of the path points at the target's page.
+            if t_in > 0.0 {
+                continuations.push(Continuation {
+                    connection: index,
+                    point: at(t_in),
+                    remote_page: page_of(start, viewports),
+                });
+            }
+            if t_out < 1.0 {
+                continuations.push(Continuation {
+                    connection: index,
+                    point: at(t_out),
+                    remote_page: page_of(end, viewports),
+                });
+            }
+        }
+    }
+    continuations
+}
+
+/// Clip the segment from `a` to `b` to the viewport (Liang-Barsky)
+///
+/// Returns the parameters, from 0 at `a` to 1 at `b`, where the segment
+/// enters and leaves the viewport, or `None` when it misses it.
+fn clip_segment(viewport: &BoundingBox, a: Point, b: Point) -> Option<(f64, f64)> {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let edges = [
+        (-dx, a.x - viewport.x),
+        (dx, viewport.right() - a.x),
+        (-dy, a.y - viewport.y),
+        (dy, viewport.bottom() - a.y),
+    ];
+    let (mut t_in, mut t_out) = (0.0_f64, 1.0_f64);
+    for (p, q) in edges {
+        if p == 0.0 {
+            // Parallel to this edge: entirely outside or no constraint
+            if q < 0.0 {
+                return None;
+            }
+        } else if p < 0.0 {
+            t_in = t_in.max(q / p);
+        } else {
+            t_out = t_out.min(q / p);
+        }
+    }
+    (t_in <= t_out).then_some((t_in, t_out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{ConnectionLayout, ResolvedStyles, RoutingMode};
+    use crate::parser::ast::{ConnectionDirection, Identifier};
+
+    fn result_with_bounds(bounds: BoundingBox) -> LayoutResult {
+        let mut result = LayoutResult::new();
+        result.bounds = bounds;
+        result
+    }
+
+    #[test]
+    fn test_small_layout_is_one_page() {
+        let result = result_with_bounds(BoundingBox::new(0.0, 0.0, 100.0, 50.0));
+        let pages = paginate(&result, PageSpec::new(200.0, 200.0));
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0].viewport, BoundingBox::new(0.0, 0.0, 200.0, 200.0));
+    }
+
+    #[test]
+    fn test_tiles_with_overlap() {
+        let result = result_with_bounds(BoundingBox::new(0.0, 0.0, 500.0, 150.0));
+        let pages = paginate(&result, PageSpec::new(200.0, 200.0).with_overlap(20.0));
+        // Columns start at 0, 180, 360 (the last one reaches 560 >= 500)
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[1].viewport.x, 180.0);
+        assert_eq!(pages[2].viewport.x, 360.0);
+        assert_eq!((pages[2].row, pages[2].column), (0, 2));
+    }
+
+    #[test]
+    fn test_connection_continuation() {
+        let mut result = result_with_bounds(BoundingBox::new(0.0, 0.0, 400.0, 100.0));
+        result.connections.push(ConnectionLayout {
+            from_id: Identifier::new("a"),
+            to_id: Identifier::new("b"),
+            direction: ConnectionDirection::Forward,
+            path: vec![Point::new(50.0, 50.0), Point::new(350.0, 50.0)],
+            styles: ResolvedStyles::default(),
+            label: None,
+            routing_mode: RoutingMode::Orthogonal,
+            crossings: None,
+            name: None,
+            seq: None,
+            span: None,
+        });
+
+        let pages = paginate(&result, PageSpec::new(200.0, 100.0));
+        assert_eq!(pages.len(), 2);
+
+        let out = &pages[0].continuations;
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].point, Point::new(200.0, 50.0));
+        assert_eq!(out[0].remote_page, Some(1));
+
+        let back = &pages[1].continuations;
+        assert_eq!(back.len(), 1);
+        assert_eq!(back[0].point, Point::new(200.0, 50.0));
+        assert_eq!(back[0].remote_page, Some(0));
+    }
+
+    #[test]
+    fn test_connection_passing_through_a_page() {
+        let mut result = result_with_bounds(BoundingBox::new(0.0, 0.0, 600.0, 100.0));
+        result.connections.push(ConnectionLayout {
+            from_id: Identifier::new("a"),
+            to_id: Identifier::new("b"),
+            direction: ConnectionDirection::Forward,
+            path: vec![Point::new(50.0, 50.0), Point::new(550.0, 50.0)],
+            styles: ResolvedStyles::default(),
+            label: None,
+            routing_mode: RoutingMode::Orthogonal,
+            crossings: None,
+            name: None,
+            seq: None,
+            span: None,
+        });
+
+        let pages = paginate(&result, PageSpec::new(200.0, 100.0));
+        assert_eq!(pages.len(), 3);
+
+        // Neither end is on the middle page, yet the segment enters and leaves it
+        let middle = &pages[1].continuations;
+        assert_eq!(middle.len(), 2);
+        assert_eq!(middle[0].point, Point::new(200.0, 50.0));
+        assert_eq!(middle[0].remote_page, Some(0));
+        assert_eq!(middle[1].point, Point::new(400.0, 50.0));
+        assert_eq!(middle[1].remote_page, Some(2));
+
+        assert_eq!(pages[0].continuations.len(), 1);
+        assert_eq!(pages[2].continuations.len(), 1);
+        assert_eq!(pages[2].continuations[0].remote_page, Some(0));
+    }
+}
@@ -32,6 +32,19 @@ pub enum RoutingMode {
     Orthogonal,
     /// Curved routing using quadratic Bezier (Feature 008)
     Curved,
+    /// Orthogonal routing that shares one trunk with the other bundled
+    /// connections into (or out of) the same element
+    Bundled,
+}
+
+/// How a connection is drawn where it crosses another connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrossingStyle {
+    /// Draw straight through the crossing
+    #[default]
+    Plain,
+    /// Insert a small hop arc over the connection being crossed
+    Jump,
 }
 
 /// Edge of a bounding box for connection attachment
@@ -112,6 +125,44 @@ pub fn boundary_point_toward_shape(bounds: &BoundingBox, target: Point, is_ellip
     }
 }
 
+/// Whether an element is a `bus` rail
+fn is_bus(element: &ElementLayout) -> bool {
+    matches!(element.element_type, ElementType::Shape(ShapeType::Bus))
+}
+
+/// Where a connection toward `other` taps into a bus: the point on the rail's
+/// center line closest to `other`'s center, as a zero-size box
+///
+/// A bus runs along its longer side.
+fn bus_tap(bus: &BoundingBox, other: &BoundingBox) -> BoundingBox {
+    let center = other.center();
+    if bus.width >= bus.height {
+        let x = center.x.clamp(bus.x, bus.right());
+        BoundingBox::new(x, bus.center().y, 0.0, 0.0)
+    } else {
+        let y = center.y.clamp(bus.y, bus.bottom());
+        BoundingBox::new(bus.center().x, y, 0.0, 0.0)
+    }
+}
+
+/// Anchor on the side of a bus facing `other`, at its tap point, so the
+/// connection reaches the rail as a short perpendicular stub
+fn bus_anchor(bus: &BoundingBox, other: &BoundingBox) -> ResolvedAnchor {
+    let tap = bus_tap(bus, other);
+    let center = other.center();
+    if bus.width >= bus.height {
+        if center.y < bus.center().y {
+            ResolvedAnchor::new(Point::new(tap.x, bus.y), AnchorDirection::Up)
+        } else {
+            ResolvedAnchor::new(Point::new(tap.x, bus.bottom()), AnchorDirection::Down)
+        }
+    } else if center.x < bus.center().x {
+        ResolvedAnchor::new(Point::new(bus.x, tap.y), AnchorDirection::Left)
+    } else {
+        ResolvedAnchor::new(Point::new(bus.right(), tap.y), AnchorDirection::Right)
+    }
+}
+
 /// Check if an ElementType represents an ellipse or circle shape
 fn is_ellipse_type(element_type: &ElementType) -> bool {
     matches!(
@@ -129,10 +180,28 @@ fn is_ellipse_type(element_type: &ElementType) -> bool {
 /// When anchor is None, returns the center of the element with auto-computed direction
 /// toward the target element.
 /// When anchor is Some, looks up the anchor in the element's AnchorSet.
+/// An offset then shifts the position along the anchor's edge.
 pub fn resolve_anchor(
     anchor_ref: &AnchorReference,
     elements: &std::collections::HashMap<String, ElementLayout>,
     target_bounds: Option<&BoundingBox>,
+) -> Result<ResolvedAnchor, LayoutError> {
+    let mut resolved = resolve_anchor_position(anchor_ref, elements, target_bounds)?;
+    if let Some(offset) = &anchor_ref.offset {
+        let normal = cardinal_direction_for_anchor(resolved.direction);
+        if normal.x != 0.0 {
+            resolved.position.y += offset.node;
+        } else {
+            resolved.position.x += offset.node;
+        }
+    }
+    Ok(resolved)
+}
+
+fn resolve_anchor_position(
+    anchor_ref: &AnchorReference,
+    elements: &std::collections::HashMap<String, ElementLayout>,
+    target_bounds: Option<&BoundingBox>,
 ) -> Result<ResolvedAnchor, LayoutError> {
     let element_name = &anchor_ref.element.node.0;
     let element = elements.get(element_name).ok_or_else(|| {
@@ -159,6 +228,10 @@ pub fn resolve_anchor(
             Ok(ResolvedAnchor::from_anchor(anchor))
         }
         None => {
+            // A bus is tapped where it passes closest to the target
+            if let (true, Some(target)) = (is_bus(element), target_bounds) {
+                return Ok(bus_anchor(&element.bounds, target));
+            }
             // Auto-detect: compute direction toward target, then find the
             // boundary intersection point so the connection attaches at the
             // element's edge rather than its center.
@@ -654,22 +727,18 @@ pub fn route_connection_with_anchors_and_types(
             end.y + to_dir.y * control_distance,
         );
 
-        // Handle via points with bisector tangents and explicit C commands.
-        //
-        // Each segment is an explicit cubic Bezier (C command). At each via
-        // point, the tangent direction is the bisector of the incoming and
-        // outgoing directions (Catmull-Rom style). This gives G1 continuity
-        // (smooth transitions) while using independent distance scaling per
-        // segment to prevent overshooting.
+        // Via points: one Catmull-Rom style spline through all waypoints,
+        // emitted as explicit cubic Bezier segments (C commands).
         //
-        // Path: M start C ctrl1_a ctrl2_a via C ctrl1_b ctrl2_b end
+        // Each waypoint gets a single tangent vector T. The segment arriving
+        // at it ends with control point `via - T/3` and the segment leaving it
+        // starts with `via + T/3`, so the first derivative matches on both
+        // sides (C1 continuity): the curve cannot kink at a via.
         //
-        // The bisector tangent solves both problems:
-        // - Symmetric arcs: bisector is horizontal at apex → smooth arc
-        // - Asymmetric turns: bisector follows the turn naturally → no loops
-        //
-        // Using explicit C (not S) allows different distances for ctrl2_a and
-        // ctrl1_b, scaled to their respective segment lengths.
+        // Plain Catmull-Rom uses T = (next - prev) / 2, which overshoots when
+        // the neighbouring segments differ a lot in length. The tangent here
+        // points along a weighted bisector and is as long as the shorter
+        // neighbouring segment, which keeps the curve between its waypoints.
         if !via_points.is_empty() {
             // Helper: distance between two points
             let dist = |a: Point, b: Point| -> f64 {
@@ -726,52 +795,39 @@ pub fn route_connection_with_anchors_and_types(
             waypoints.push(start);
             waypoints.extend_from_slice(via_points);
             waypoints.push(end);
-
             let n = waypoints.len(); // at least 3
 
-            // Segment 1: start → waypoints[1]
-            let seg1_d = dist(start, waypoints[1]);
-            let ctrl1 = Point::new(
-                start.x + from_dir.x * seg1_d / 3.0,
-                start.y + from_dir.y * seg1_d / 3.0,
-            );
-            // ctrl2 of seg1: arrive at waypoints[1] along bisector (incoming side)
-            let (bx, by) = bisector_dir(waypoints[0], waypoints[1], waypoints[2]);
-            let ctrl2 = Point::new(
-                waypoints[1].x - bx * seg1_d / 3.0,
-                waypoints[1].y - by * seg1_d / 3.0,
-            );
-            let mut path = vec![start, ctrl1, ctrl2, waypoints[1]];
-
-            // Intermediate + last segments: waypoints[i] → waypoints[i+1]
-            for i in 1..(n - 1) {
-                let prev = waypoints[i - 1];
-                let curr = waypoints[i];
-                let next = waypoints[i + 1];
-                let seg_d = dist(curr, next);
-
-                // ctrl1: depart curr along bisector (outgoing side)
-                let (bx_c, by_c) = bisector_dir(prev, curr, next);
-                let c1 = Point::new(curr.x + bx_c * seg_d / 3.0, curr.y + by_c * seg_d / 3.0);
-
-                if i + 1 < n - 1 {
-                    // Next point is another via: use bisector for ctrl2
-                    let next_next = waypoints[i + 2];
-                    let (bx_n, by_n) = bisector_dir(curr, next, next_next);
-                    let c2 = Point::new(next.x - bx_n * seg_d / 3.0, next.y - by_n * seg_d / 3.0);
-                    path.push(c1);
-                    path.push(c2);
-                    path.push(next);
-                } else {
-                    // Next point is end: use to_dir for perpendicular entry
-                    let c2 = Point::new(
-                        end.x + to_dir.x * seg_d / 3.0,
-                        end.y + to_dir.y * seg_d / 3.0,
-                    );
-                    path.push(c1);
-                    path.push(c2);
-                    path.push(end);
-                }
+            // Tangent at every waypoint; the ends leave and enter along the
+            // anchor directions, scaled to their segment
+            let tangents: Vec<Point> = (0..n)
+                .map(|i| {
+                    if i == 0 {
+                        let d = dist(waypoints[0], waypoints[1]);
+                        Point::new(from_dir.x * d, from_dir.y * d)
+                    } else if i == n - 1 {
+                        let d = dist(waypoints[n - 2], waypoints[n - 1]);
+                        Point::new(-to_dir.x * d, -to_dir.y * d)
+                    } else {
+                        let (prev, via, next) = (waypoints[i - 1], waypoints[i], waypoints[i + 1]);
+                        let (bx, by) = bisector_dir(prev, via, next);
+                        let len = dist(prev, via).min(dist(via, next));
+                        Point::new(bx * len, by * len)
+                    }
+                })
+                .collect();
+
+            let mut path = vec![start];
+            for i in 0..(n - 1) {
+                let (curr, next) = (waypoints[i], waypoints[i + 1]);
+                path.push(Point::new(
+                    curr.x + tangents[i].x / 3.0,
+                    curr.y + tangents[i].y / 3.0,
+                ));
+                path.push(Point::new(
+                    next.x - tangents[i + 1].x / 3.0,
+                    next.y - tangents[i + 1].y / 3.0,
+                ));
+                path.push(next);
             }
 
             return path;
@@ -907,7 +963,8 @@ fn extract_routing_mode(modifiers: &[Spanned<StyleModifier>]) -> RoutingMode {
                     "direct" => return RoutingMode::Direct,
                     "orthogonal" => return RoutingMode::Orthogonal,
                     "curved" => return RoutingMode::Curved, // Feature 008
-                    _ => {}                                 // Unknown value, use default
+                    "bundled" => return RoutingMode::Bundled,
+                    _ => {} // Unknown value, use default
                 }
             }
         }
@@ -915,6 +972,63 @@ fn extract_routing_mode(modifiers: &[Spanned<StyleModifier>]) -> RoutingMode {
     RoutingMode::default() // Orthogonal
 }
 
+/// A connection endpoint with its `{end}_anchor` and `{end}_offset` modifiers
+/// applied, `end` being `from` or `to`
+///
+/// `[from_anchor: right, from_offset: 10]` is the modifier form of
+/// `a.right+10`; the modifiers win over the endpoint syntax.
+pub(crate) fn endpoint_with_modifiers(
+    endpoint: &AnchorReference,
+    modifiers: &[Spanned<StyleModifier>],
+    end: &str,
+) -> AnchorReference {
+    let mut endpoint = endpoint.clone();
+    for modifier in modifiers {
+        let StyleKey::Custom(key) = &modifier.node.key.node else {
+            continue;
+        };
+        let Some(setting) = key.strip_prefix(end).and_then(|k| k.strip_prefix('_')) else {
+            continue;
+        };
+        let value = &modifier.node.value;
+        match (setting, &value.node) {
+            ("anchor", StyleValue::Keyword(name)) => {
+                endpoint.anchor = Some(Spanned::new(name.clone(), value.span.clone()));
+            }
+            ("anchor", StyleValue::Identifier(id)) => {
+                endpoint.anchor = Some(Spanned::new(id.0.clone(), value.span.clone()));
+            }
+            ("offset", StyleValue::Number { value: offset, .. }) => {
+                endpoint.offset = Some(Spanned::new(*offset, value.span.clone()));
+            }
+            _ => {}
+        }
+    }
+    endpoint
+}
+
+/// Extract the crossing style from connection modifiers (`crossings: jump|plain`)
+///
+/// Returns `None` when the connection does not specify one, so the renderer
+/// can fall back to the global `SvgConfig` setting.
+fn extract_crossing_style(modifiers: &[Spanned<StyleModifier>]) -> Option<CrossingStyle> {
+    for modifier in modifiers {
+        if matches!(modifier.node.key.node, StyleKey::Custom(ref k) if k == "crossings") {
+            let value = match &modifier.node.value.node {
+                StyleValue::Keyword(k) => k.as_str(),
+                StyleValue::Identifier(id) => id.0.as_str(),
+                _ => continue,
+            };
+            match value {
+                "jump" => return Some(CrossingStyle::Jump),
+                "plain" | "none" => return Some(CrossingStyle::Plain),
+                _ => {}
+            }
+        }
+    }
+    None
+}
+
 /// Extract via references from connection modifiers (Feature 008)
 /// Returns a list of identifier names for steering vertices
 fn extract_via_references(modifiers: &[Spanned<StyleModifier>]) -> Vec<String> {
@@ -970,10 +1084,12 @@ pub fn route_connections(result: &mut LayoutResult, doc: &Document) -> Result<()
     // Track element IDs that are used as connection labels (to remove them from rendering)
     let mut label_element_ids: std::collections::HashSet<String> = std::collections::HashSet::new();
 
-    fn process_statements(
-        stmts: &[Spanned<Statement>],
-        result: &mut LayoutResult,
-        label_element_ids: &mut std::collections::HashSet<String>,
+    // Connections are routed first and labelled after channel nudging, so labels
+    // follow the final path. Each entry keeps the modifiers for label extraction.
+    fn process_statements<'a>(
+        stmts: &'a [Spanned<Statement>],
+        result: &LayoutResult,
+        routed: &mut Vec<(ConnectionLayout, &'a [Spanned<StyleModifier>])>,
     ) -> Result<(), LayoutError> {
         for stmt in stmts {
             match &stmt.node {
@@ -1004,10 +1120,23 @@ pub fn route_connections(result: &mut LayoutResult, doc: &Document) -> Result<()
                         let to_bounds = to_element.bounds;
 
                         // Feature 009: Resolve anchors for connection endpoints
+                        let from = endpoint_with_modifiers(&conn.from, &conn.modifiers, "from");
+                        let to = endpoint_with_modifiers(&conn.to, &conn.modifiers, "to");
+                        // An element connected to a bus aims at its tap point
+                        // on the rail rather than at the rail's center
+                        let from_target = if is_bus(to_element) && to.anchor.is_none() {
+                            bus_tap(&to_bounds, &from_bounds)
+                        } else {
+                            to_bounds
+                        };
+                        let to_target = if is_bus(from_element) && from.anchor.is_none() {
+                            bus_tap(&from_bounds, &to_bounds)
+                        } else {
+                            from_bounds
+                        };
                         let from_anchor =
-                            resolve_anchor(&conn.from, &result.elements, Some(&to_bounds))?;
-                        let to_anchor =
-                            resolve_anchor(&conn.to, &result.elements, Some(&from_bounds))?;
+                            resolve_anchor(&from, &result.elements, Some(&from_target))?;
+                        let to_anchor = resolve_anchor(&to, &result.elements, Some(&to_target))?;
 
                         // Always pass resolved anchors (auto-picked or explicit) so
                         // the router can use their direction for routing.
@@ -1016,41 +1145,51 @@ pub fn route_connections(result: &mut LayoutResult, doc: &Document) -> Result<()
 
                         let via_refs = extract_via_references(&conn.modifiers);
                         let via_points = resolve_via_points(&via_refs, result)?;
-                        let path = route_connection_with_anchors_and_types(
-                            &from_bounds,
-                            &to_bounds,
-                            routing_mode,
-                            &via_points,
-                            from_anchor_opt,
-                            to_anchor_opt,
-                            Some(&from_element.element_type),
-                            Some(&to_element.element_type),
-                        );
+                        // A transition from an element back to itself loops over its top
+                        let self_loop = conn.from.element.node == conn.to.element.node
+                            && from.anchor.is_none()
+                            && to.anchor.is_none()
+                            && via_points.is_empty();
+                        let (path, routing_mode) = if self_loop {
+                            (self_loop_path(&from_bounds), RoutingMode::Curved)
+                        } else {
+                            let path = route_connection_with_anchors_and_types(
+                                &from_bounds,
+                                &to_bounds,
+                                routing_mode,
+                                &via_points,
+                                from_anchor_opt,
+                                to_anchor_opt,
+                                Some(&from_element.element_type),
+                                Some(&to_element.element_type),
+                            );
+                            (path, routing_mode)
+                        };
                         let styles = ResolvedStyles::from_modifiers(&conn.modifiers);
-                        let (label, label_ref_id) =
-                            extract_connection_label_with_ref(&conn.modifiers, &path, result);
 
-                        if let Some(id) = label_ref_id {
-                            label_element_ids.insert(id);
-                        }
-
-                        result.connections.push(ConnectionLayout {
-                            from_id: conn.from.element.node.clone(),
-                            to_id: conn.to.element.node.clone(),
-                            direction: conn.direction,
-                            path,
-                            styles,
-                            label,
-                            routing_mode,
-                            name: conn.name.as_ref().map(|n| n.node.clone()),
-                        });
+                        routed.push((
+                            ConnectionLayout {
+                                from_id: conn.from.element.node.clone(),
+                                to_id: conn.to.element.node.clone(),
+                                direction: conn.direction,
+                                path,
+                                styles,
+                                label: None,
+                                routing_mode,
+                                crossings: extract_crossing_style(&conn.modifiers),
+                                name: conn.name.as_ref().map(|n| n.node.clone()),
+                                seq: None,
+                                span: Some(stmt.span.clone()),
+                            },
+                            &conn.modifiers,
+                        ));
                     }
                 }
                 Statement::Layout(l) => {
-                    process_statements(&l.children, result, label_element_ids)?;
+                    process_statements(&l.children, result, routed)?;
                 }
                 Statement::Group(g) => {
-                    process_statements(&g.children, result, label_element_ids)?;
+                    process_statements(&g.children, result, routed)?;
                 }
                 _ => {}
             }
@@ -1058,7 +1197,29 @@ pub fn route_connections(result: &mut LayoutResult, doc: &Document) -> Result<()
         Ok(())
     }
 
-    process_statements(&doc.statements, result, &mut label_element_ids)?;
+    let mut routed = vec![];
+    process_statements(&doc.statements, result, &mut routed)?;
+
+    // Spread parallel segments sharing a channel so no edge hides another
+    let (mut connections, modifiers): (Vec<_>, Vec<_>) = routed.into_iter().unzip();
+    bundle_connections(&mut connections, result);
+    nudge_shared_channels(&mut connections, CHANNEL_SPACING);
+
+    let mut steps = vec![];
+    for (index, (conn, modifiers)) in connections.iter_mut().zip(modifiers).enumerate() {
+        let (label, label_ref_id) =
+            extract_connection_label_with_ref(modifiers, &conn.path, result);
+        if let Some(id) = label_ref_id {
+            label_element_ids.insert(id);
+        }
+        conn.label = label;
+        if let Some((number, span)) = extract_seq(modifiers)? {
+            conn.seq = Some(SeqMarker { number });
+            steps.push((number, span, index));
+        }
+    }
+    validate_seq_numbers(&connections, steps)?;
+    result.connections.extend(connections);
 
     // Resolve overlapping connection labels
     resolve_label_overlaps(&mut result.connections);
@@ -1072,6 +1233,241 @@ pub fn route_connections(result: &mut LayoutResult, doc: &Document) -> Result<()
     Ok(())
 }
 
+/// Distance a bundle's trunk splits before the nearest of its branch ends
+const BUNDLE_SPLIT_MARGIN: f64 = 20.0;
+
+/// Merge `routing: bundled` connections that share an endpoint into one trunk
+///
+/// Connections into the same element (fan-in) are grouped first, then the
+/// remaining ones out of the same element (fan-out). A group's trunk leaves
+/// the shared element from the side facing the other ends, runs straight and
+/// splits [`BUNDLE_SPLIT_MARGIN`] before the nearest of them; each branch then
+/// turns once toward its own end. A connection without partners, or a group
+/// whose other ends are not all in front of the shared side, keeps its
+/// orthogonal route.
+fn bundle_connections(connections: &mut [ConnectionLayout], result: &LayoutResult) {
+    use std::collections::BTreeMap;
+
+    let bundled: Vec<usize> = (0..connections.len())
+        .filter(|&i| connections[i].routing_mode == RoutingMode::Bundled)
+        .collect();
+    let mut fan_in: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for &i in &bundled {
+        fan_in
+            .entry(connections[i].to_id.as_str())
+            .or_default()
+            .push(i);
+    }
+    let mut fan_out: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for members in fan_in.values().filter(|m| m.len() < 2) {
+        for &i in members {
+            fan_out
+                .entry(connections[i].from_id.as_str())
+                .or_default()
+                .push(i);
+        }
+    }
+
+    let mut groups: Vec<(String, Vec<usize>, bool)> = vec![];
+    for (shared, members) in fan_in.into_iter().filter(|(_, m)| m.len() >= 2) {
+        groups.push((shared.to_string(), members, true));
+    }
+    for (shared, members) in fan_out.into_iter().filter(|(_, m)| m.len() >= 2) {
+        groups.push((shared.to_string(), members, false));
+    }
+
+    for (shared, members, is_fan_in) in groups {
+        let Some(shared_bounds) = result.get_element_by_name(&shared).map(|e| e.bounds) else {
+            continue;
+        };
+        let others: Vec<BoundingBox> = members
+            .iter()
+            .filter_map(|&i| {
+                let conn = &connections[i];
+                let other = if is_fan_in {
+                    &conn.from_id
+                } else {
+                    &conn.to_id
+                };
+                result.get_element_by_name(other.as_str()).map(|e| e.bounds)
+            })
+            .collect();
+        if others.len() != members.len() {
+            continue;
+        }
+
+        // The trunk leaves the side with the most room in front of it; every
+        // other end must lie beyond that side
+        let depth = |edge: Edge, p: Point| {
+            let root = attachment_point(&shared_bounds, edge);
+            match edge {
+                Edge::Top => root.y - p.y,
+                Edge::Bottom => p.y - root.y,
+                Edge::Left => root.x - p.x,
+                Edge::Right => p.x - root.x,
+            }
+        };
+        let facing = |edge: Edge| match edge {
+            Edge::Top => Edge::Bottom,
+            Edge::Bottom => Edge::Top,
+            Edge::Left => Edge::Right,
+            Edge::Right => Edge::Left,
+        };
+        let nearest = |edge: Edge| {
+            others
+                .iter()
+                .map(|b| depth(edge, attachment_point(b, facing(edge))))
+                .fold(f64::INFINITY, f64::min)
+        };
+        let (edge, nearest) = [Edge::Top, Edge::Bottom, Edge::Left, Edge::Right]
+            .into_iter()
+            .map(|edge| (edge, nearest(edge)))
+            .fold((Edge::Top, f64::NEG_INFINITY), |best, candidate| {
+                if candidate.1 > best.1 {
+                    candidate
+                } else {
+                    best
+                }
+            });
+        if nearest <= 0.0 {
+            continue;
+        }
+        let root = attachment_point(&shared_bounds, edge);
+        let horizontal = matches!(edge, Edge::Left | Edge::Right);
+        let sign = if matches!(edge, Edge::Right | Edge::Bottom) {
+            1.0
+        } else {
+            -1.0
+        };
+        let ends: Vec<Point> = others
+            .iter()
+            .map(|b| attachment_point(b, facing(edge)))
+            .collect();
+        let split = if nearest > 2.0 * BUNDLE_SPLIT_MARGIN {
+            nearest - BUNDLE_SPLIT_MARGIN
+        } else {
+            nearest / 2.0
+        };
+        let fork = if horizontal {
+            Point::new(root.x + sign * split, root.y)
+        } else {
+            Point::new(root.x, root.y + sign * split)
+        };
+
+        for (&i, end) in members.iter().zip(ends) {
+            let turn = if horizontal {
+                Point::new(fork.x, end.y)
+            } else {
+                Point::new(end.x, fork.y)
+            };
+            let mut path = vec![end, turn, fork, root];
+            if !is_fan_in {
+                path.reverse();
+            }
+            connections[i].path = simplify_path(path);
+        }
+    }
+}
+
+/// Distance between parallel connections nudged apart within a shared channel
+pub(crate) const CHANNEL_SPACING: f64 = 8.0;
+
+/// An interior orthogonal segment considered for channel assignment
+struct ChannelSegment {
+    conn: usize,
+    /// Index of the segment's first point in the connection path
+    index: usize,
+    horizontal: bool,
+    /// Fixed coordinate: y for horizontal segments, x for vertical ones
+    coord: f64,
+    lo: f64,
+    hi: f64,
+}
+
+/// Assign distinct offsets to collinear, overlapping segments of different connections.
+///
+/// Like tracks in a circuit-board channel, segments that would be drawn exactly
+/// on top of each other are spread symmetrically around the original line,
+/// `spacing` apart, in document order. Only interior segments of orthogonal
+/// routes move: both their endpoints are bends, so shifting them keeps the path
+/// orthogonal and leaves the attachment points untouched.
+fn nudge_shared_channels(connections: &mut [ConnectionLayout], spacing: f64) {
+    const EPSILON: f64 = 0.001;
+
+    let mut segments: Vec<ChannelSegment> = vec![];
+    for (conn_idx, conn) in connections.iter().enumerate() {
+        if conn.routing_mode != RoutingMode::Orthogonal || conn.path.len() < 4 {
+            continue;
+        }
+        for index in 1..conn.path.len() - 2 {
+            let (a, b) = (conn.path[index], conn.path[index + 1]);
+            let horizontal = (a.y - b.y).abs() < EPSILON && (a.x - b.x).abs() > EPSILON;
+            let vertical = (a.x - b.x).abs() < EPSILON && (a.y - b.y).abs() > EPSILON;
+            if !horizontal && !vertical {
+                continue;
+            }
+            let (coord, p, q) = if horizontal {
+                (a.y, a.x, b.x)
+            } else {
+                (a.x, a.y, b.y)
+            };
+            segments.push(ChannelSegment {
+                conn: conn_idx,
+                index,
+                horizontal,
+                coord,
+                lo: p.min(q),
+                hi: p.max(q),
+            });
+        }
+    }
+
+    // Group segments into channels: same line, overlapping extent
+    let mut channel: Vec<usize> = (0..segments.len()).collect();
+    fn root(channel: &mut [usize], mut i: usize) -> usize {
+        while channel[i] != i {
+            channel[i] = channel[channel[i]];
+            i = channel[i];
+        }
+        i
+    }
+    for i in 0..segments.len() {
+        for j in (i + 1)..segments.len() {
+            let (a, b) = (&segments[i], &segments[j]);
+            if a.conn != b.conn
+                && a.horizontal == b.horizontal
+                && (a.coord - b.coord).abs() < EPSILON
+                && a.lo.max(b.lo) < a.hi.min(b.hi) - EPSILON
+            {
+                let (ri, rj) = (root(&mut channel, i), root(&mut channel, j));
+                channel[ri.max(rj)] = ri.min(rj);
+            }
+        }
+    }
+
+    let mut members: std::collections::BTreeMap<usize, Vec<usize>> = Default::default();
+    for i in 0..segments.len() {
+        let r = root(&mut channel, i);
+        members.entry(r).or_default().push(i);
+    }
+
+    for tracks in members.values().filter(|m| m.len() > 1) {
+        let center = (tracks.len() - 1) as f64 / 2.0;
+        for (track, &i) in tracks.iter().enumerate() {
+            let seg = &segments[i];
+            let offset = (track as f64 - center) * spacing;
+            let path = &mut connections[seg.conn].path;
+            for point in &mut path[seg.index..=seg.index + 1] {
+                if seg.horizontal {
+                    point.y += offset;
+                } else {
+                    point.x += offset;
+                }
+            }
+        }
+    }
+}
+
 /// Resolve overlapping connection labels by nudging them apart
 fn resolve_label_overlaps(connections: &mut [ConnectionLayout]) {
     // Approximate character width and line height for label bounds estimation
@@ -1193,6 +1589,23 @@ fn extract_connection_label(
 }
 
 /// Extract connection label and return both the label and the referenced element ID (if any)
+/// How far a self-transition loops above its element
+const SELF_LOOP_HEIGHT: f64 = 40.0;
+
+/// Bezier loop (start, two controls, end) leaving and re-entering the top
+/// edge of an element, for a connection from the element to itself
+fn self_loop_path(bounds: &BoundingBox) -> Vec<Point> {
+    let center = bounds.center();
+    let spread = (bounds.width / 4.0).min(20.0);
+    let rise = bounds.y - SELF_LOOP_HEIGHT;
+    vec![
+        Point::new(center.x + spread, bounds.y),
+        Point::new(center.x + spread * 2.5, rise),
+        Point::new(center.x - spread * 2.5, rise),
+        Point::new(center.x - spread, bounds.y),
+    ]
+}
+
 fn extract_connection_label_with_ref(
     modifiers: &[Spanned<StyleModifier>],
     path: &[Point],
@@ -1201,8 +1614,11 @@ fn extract_connection_label_with_ref(
     let mut label_ref_id: Option<String> = None;
     let mut label_styles: Option<ResolvedStyles> = None;
 
+    // `on: "event"` labels a state machine transition
     let text = modifiers.iter().find_map(|m| {
-        if matches!(m.node.key.node, StyleKey::Label) {
+        if matches!(&m.node.key.node, StyleKey::Label)
+            || matches!(&m.node.key.node, StyleKey::Custom(key) if key == "on")
+        {
             match &m.node.value.node {
                 StyleValue::String(s) => Some(s.clone()),
                 // Support identifier references: [label: my_shape]
@@ -1282,34 +1698,12 @@ fn extract_connection_label_with_ref(
         })
         .unwrap_or(0.5);
 
-    // Calculate label position - for curves, use the actual curve point at label_at
-    let (mid_x, mid_y, anchor) = if path.len() == 4 {
-        // Cubic Bezier: calculate point at t=label_at
-        let t = label_at;
-        let mt = 1.0 - t;
-        let p0 = path[0];
-        let p1 = path[1];
-        let p2 = path[2];
-        let p3 = path[3];
-        let base_mid_x = mt * mt * mt * p0.x
-            + 3.0 * mt * mt * t * p1.x
-            + 3.0 * mt * t * t * p2.x
-            + t * t * t * p3.x;
-        let base_mid_y = mt * mt * mt * p0.y
-            + 3.0 * mt * mt * t * p1.y
-            + 3.0 * mt * t * t * p2.y
-            + t * t * t * p3.y;
-
-        // Compute tangent via B'(t) derivative
-        let dt_x = 3.0 * mt * mt * (p1.x - p0.x)
-            + 6.0 * mt * t * (p2.x - p1.x)
-            + 3.0 * t * t * (p3.x - p2.x);
-        let dt_y = 3.0 * mt * mt * (p1.y - p0.y)
-            + 6.0 * mt * t * (p2.y - p1.y)
-            + 3.0 * t * t * (p3.y - p2.y);
-        let tangent_len = (dt_x * dt_x + dt_y * dt_y).sqrt();
+    // Position the label beside the path point at label_at
+    let (mid_x, mid_y, anchor) = if path.len() >= 2 {
+        let (base, (dt_x, dt_y)) = point_along_path(path, label_at);
 
         // Right-side perpendicular (clockwise 90°): (ty, -tx) normalized
+        let tangent_len = (dt_x * dt_x + dt_y * dt_y).sqrt();
         let (perp_x, perp_y) = if tangent_len > 0.001 {
             (dt_y / tangent_len, -dt_x / tangent_len)
         } else {
@@ -1317,71 +1711,12 @@ fn extract_connection_label_with_ref(
         };
 
         match label_position {
-            Some(LabelPosition::Right) => {
-                perpendicular_label_position(base_mid_x, base_mid_y, perp_x, perp_y, label_offset)
-            }
-            Some(LabelPosition::Left) => {
-                perpendicular_label_position(base_mid_x, base_mid_y, perp_x, perp_y, -label_offset)
-            }
-            Some(LabelPosition::Center) => (base_mid_x, base_mid_y, TextAnchor::Middle),
-            None => {
-                perpendicular_label_position(base_mid_x, base_mid_y, perp_x, perp_y, label_offset)
-            }
-        }
-    } else if path.len() >= 2 {
-        // Other paths: interpolate along polyline at label_at fraction
-
-        // Compute total polyline length and find point at label_at fraction
-        let mut segment_lengths: Vec<f64> = Vec::with_capacity(path.len() - 1);
-        let mut total_length = 0.0;
-        for i in 0..path.len() - 1 {
-            let dx = path[i + 1].x - path[i].x;
-            let dy = path[i + 1].y - path[i].y;
-            let len = (dx * dx + dy * dy).sqrt();
-            segment_lengths.push(len);
-            total_length += len;
-        }
-
-        let target_dist = label_at * total_length;
-        let mut accumulated = 0.0;
-        let mut base_mid_x = path[0].x;
-        let mut base_mid_y = path[0].y;
-        let mut seg_dx = 0.0;
-        let mut seg_dy = 0.0;
-        for (i, &seg_len) in segment_lengths.iter().enumerate() {
-            if accumulated + seg_len >= target_dist {
-                let frac = if seg_len > 0.0 {
-                    (target_dist - accumulated) / seg_len
-                } else {
-                    0.0
-                };
-                base_mid_x = path[i].x + frac * (path[i + 1].x - path[i].x);
-                base_mid_y = path[i].y + frac * (path[i + 1].y - path[i].y);
-                seg_dx = path[i + 1].x - path[i].x;
-                seg_dy = path[i + 1].y - path[i].y;
-                break;
-            }
-            accumulated += seg_len;
-        }
-
-        // Compute right-side perpendicular from segment tangent
-        let tangent_len = (seg_dx * seg_dx + seg_dy * seg_dy).sqrt();
-        let (perp_x, perp_y) = if tangent_len > 0.001 {
-            (seg_dy / tangent_len, -seg_dx / tangent_len)
-        } else {
-            (1.0, 0.0) // degenerate fallback
-        };
-
-        match label_position {
-            Some(LabelPosition::Right) => {
-                perpendicular_label_position(base_mid_x, base_mid_y, perp_x, perp_y, label_offset)
-            }
             Some(LabelPosition::Left) => {
-                perpendicular_label_position(base_mid_x, base_mid_y, perp_x, perp_y, -label_offset)
+                perpendicular_label_position(base.x, base.y, perp_x, perp_y, -label_offset)
             }
-            Some(LabelPosition::Center) => (base_mid_x, base_mid_y, TextAnchor::Middle),
-            None => {
-                perpendicular_label_position(base_mid_x, base_mid_y, perp_x, perp_y, label_offset)
+            Some(LabelPosition::Center) => (base.x, base.y, TextAnchor::Middle),
+            Some(LabelPosition::Right) | None => {
+                perpendicular_label_position(base.x, base.y, perp_x, perp_y, label_offset)
             }
         }
     } else if !path.is_empty() {
@@ -1395,12 +1730,121 @@ fn extract_connection_label_with_ref(
             text,
             position: Point::new(mid_x, mid_y),
             anchor,
+            vertical_align: VerticalAlign::Middle,
             styles: label_styles,
         }),
         label_ref_id,
     )
 }
 
+/// Point at fraction `t` along a connection path, with the travel direction
+/// (unnormalized tangent) there.
+///
+/// Four-point paths are cubic Béziers (curved routing); other paths are
+/// interpolated along the polyline by length. `path` must have two or more points.
+pub(super) fn point_along_path(path: &[Point], t: f64) -> (Point, (f64, f64)) {
+    if path.len() == 4 {
+        let mt = 1.0 - t;
+        let (p0, p1, p2, p3) = (path[0], path[1], path[2], path[3]);
+        let x = mt * mt * mt * p0.x
+            + 3.0 * mt * mt * t * p1.x
+            + 3.0 * mt * t * t * p2.x
+            + t * t * t * p3.x;
+        let y = mt * mt * mt * p0.y
+            + 3.0 * mt * mt * t * p1.y
+            + 3.0 * mt * t * t * p2.y
+            + t * t * t * p3.y;
+
+        // Tangent via the B'(t) derivative
+        let dt_x = 3.0 * mt * mt * (p1.x - p0.x)
+            + 6.0 * mt * t * (p2.x - p1.x)
+            + 3.0 * t * t * (p3.x - p2.x);
+        let dt_y = 3.0 * mt * mt * (p1.y - p0.y)
+            + 6.0 * mt * t * (p2.y - p1.y)
+            + 3.0 * t * t * (p3.y - p2.y);
+        return (Point::new(x, y), (dt_x, dt_y));
+    }
+
+    let segment_lengths: Vec<f64> = path
+        .windows(2)
+        .map(|w| ((w[1].x - w[0].x).powi(2) + (w[1].y - w[0].y).powi(2)).sqrt())
+        .collect();
+    let target_dist = t * segment_lengths.iter().sum::<f64>();
+
+    let mut accumulated = 0.0;
+    for (i, &seg_len) in segment_lengths.iter().enumerate() {
+        if accumulated + seg_len >= target_dist {
+            let frac = if seg_len > 0.0 {
+                (target_dist - accumulated) / seg_len
+            } else {
+                0.0
+            };
+            let (dx, dy) = (path[i + 1].x - path[i].x, path[i + 1].y - path[i].y);
+            return (
+                Point::new(path[i].x + frac * dx, path[i].y + frac * dy),
+                (dx, dy),
+            );
+        }
+        accumulated += seg_len;
+    }
+    (path[0], (0.0, 0.0))
+}
+
+/// Extract the step number of a connection (`seq: 1`) and the modifier's span
+fn extract_seq(modifiers: &[Spanned<StyleModifier>]) -> Result<Option<(u32, Span)>, LayoutError> {
+    for modifier in modifiers {
+        if matches!(modifier.node.key.node, StyleKey::Custom(ref k) if k == "seq") {
+            return match modifier.node.value.node {
+                StyleValue::Number { value, .. } if value >= 1.0 && value.fract() == 0.0 => {
+                    Ok(Some((value as u32, modifier.span.clone())))
+                }
+                _ => Err(LayoutError::invalid_modifier(
+                    "seq must be a positive whole number (seq: 1)",
+                    modifier.span.clone(),
+                )),
+            };
+        }
+    }
+    Ok(None)
+}
+
+/// Check that step numbers are unique and run from 1 without gaps
+///
+/// `steps` holds each number with the span of its `seq` modifier and the
+/// index of its connection.
+fn validate_seq_numbers(
+    connections: &[ConnectionLayout],
+    mut steps: Vec<(u32, Span, usize)>,
+) -> Result<(), LayoutError> {
+    // Stable, so a duplicate points at the later statement
+    steps.sort_by_key(|(n, _, _)| *n);
+
+    for (i, (number, span, index)) in steps.iter().enumerate() {
+        let expected = i as u32 + 1;
+        if *number < expected {
+            let (other, conn) = (&connections[steps[i - 1].2], &connections[*index]);
+            return Err(LayoutError::invalid_modifier(
+                format!(
+                    "Step {} is used by both {} -> {} and {} -> {}",
+                    number, other.from_id, other.to_id, conn.from_id, conn.to_id
+                ),
+                span.clone(),
+            ));
+        }
+        if *number > expected {
+            return Err(LayoutError::invalid_modifier(
+                format!(
+                    "Steps must run 1..{} without gaps; step {} is missing",
+                    steps.len(),
+                    expected
+                ),
+                span.clone(),
+            ));
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1799,6 +2243,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_curved_multi_via_is_c1_continuous() {
+        let from_bounds = BoundingBox::new(0.0, 0.0, 40.0, 40.0);
+        let to_bounds = BoundingBox::new(400.0, 0.0, 40.0, 40.0);
+        let vias = [Point::new(100.0, 150.0), Point::new(300.0, -60.0)];
+        let path = route_connection(&from_bounds, &to_bounds, RoutingMode::Curved, &vias);
+
+        // start + 3 points per segment, one segment per gap between waypoints
+        assert_eq!(path.len(), 1 + 3 * 3);
+        for (k, via) in vias.iter().enumerate() {
+            let at = 3 * (k + 1);
+            assert_eq!(path[at], *via);
+            // The controls around a via mirror each other through it
+            let before = path[at - 1];
+            let after = path[at + 1];
+            assert!((via.x - before.x - (after.x - via.x)).abs() < 1e-9);
+            assert!((via.y - before.y - (after.y - via.y)).abs() < 1e-9);
+        }
+    }
+
     #[test]
     fn test_routing_mode_curved_exists() {
         // Verify the Curved variant exists and is distinct
@@ -1806,6 +2270,116 @@ mod tests {
         assert_ne!(RoutingMode::Curved, RoutingMode::Orthogonal);
     }
 
+    fn orthogonal_connection(path: Vec<Point>) -> ConnectionLayout {
+        ConnectionLayout {
+            from_id: Identifier::new("a"),
+            to_id: Identifier::new("b"),
+            direction: ConnectionDirection::Forward,
+            path,
+            styles: ResolvedStyles::default(),
+            label: None,
+            routing_mode: RoutingMode::Orthogonal,
+            crossings: None,
+            name: None,
+            seq: None,
+            span: None,
+        }
+    }
+
+    #[test]
+    fn test_seq_position_follows_path() {
+        let mut conn = orthogonal_connection(vec![Point::new(0.0, 0.0), Point::new(100.0, 0.0)]);
+        assert_eq!(conn.seq_position(), None);
+        conn.seq = Some(SeqMarker { number: 1 });
+        assert_eq!(conn.seq_position(), Some(Point::new(50.0, 0.0)));
+        // Moving the path after routing (bundling, jitter) moves the marker
+        conn.path = vec![
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 40.0),
+            Point::new(100.0, 40.0),
+        ];
+        assert_eq!(conn.seq_position(), Some(Point::new(30.0, 40.0)));
+    }
+
+    #[test]
+    fn test_nudge_shared_channel() {
+        // Two S-shaped routes whose vertical middle segments overlap at x=50
+        let mut connections = vec![
+            orthogonal_connection(vec![
+                Point::new(0.0, 0.0),
+                Point::new(50.0, 0.0),
+                Point::new(50.0, 100.0),
+                Point::new(100.0, 100.0),
+            ]),
+            orthogonal_connection(vec![
+                Point::new(0.0, 20.0),
+                Point::new(50.0, 20.0),
+                Point::new(50.0, 120.0),
+                Point::new(100.0, 120.0),
+            ]),
+        ];
+        nudge_shared_channels(&mut connections, 8.0);
+
+        assert_eq!(connections[0].path[1], Point::new(46.0, 0.0));
+        assert_eq!(connections[0].path[2], Point::new(46.0, 100.0));
+        assert_eq!(connections[1].path[1], Point::new(54.0, 20.0));
+        assert_eq!(connections[1].path[2], Point::new(54.0, 120.0));
+        // Endpoints stay attached
+        assert_eq!(connections[0].path[0], Point::new(0.0, 0.0));
+        assert_eq!(connections[1].path[3], Point::new(100.0, 120.0));
+    }
+
+    #[test]
+    fn test_nudge_ignores_disjoint_segments() {
+        let mut connections = vec![
+            orthogonal_connection(vec![
+                Point::new(0.0, 0.0),
+                Point::new(50.0, 0.0),
+                Point::new(50.0, 40.0),
+                Point::new(100.0, 40.0),
+            ]),
+            orthogonal_connection(vec![
+                Point::new(0.0, 60.0),
+                Point::new(50.0, 60.0),
+                Point::new(50.0, 100.0),
+                Point::new(100.0, 100.0),
+            ]),
+        ];
+        let before: Vec<_> = connections.iter().map(|c| c.path.clone()).collect();
+        nudge_shared_channels(&mut connections, 8.0);
+        let after: Vec<_> = connections.iter().map(|c| c.path.clone()).collect();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn test_bundled_fan_in_shares_trunk() {
+        let source = "col [gap: 60] {\n  row { rect a\n rect b\n rect c }\n  rect hub [width: 200]\n}\n\
+                      a -> hub [routing: bundled]\nb -> hub [routing: bundled]\nc -> hub [routing: bundled]";
+        let result = crate::compute_layout(source, &crate::RenderConfig::new()).unwrap();
+        let hub = result.get_element_by_name("hub").unwrap().bounds;
+        let paths: Vec<_> = result.connections.iter().map(|c| &c.path).collect();
+        assert_eq!(paths.len(), 3);
+
+        // All branches end on one trunk into the middle of the hub's top edge
+        let root = Point::new(hub.center().x, hub.y);
+        for path in &paths {
+            assert_eq!(path.last(), Some(&root));
+            assert_eq!(path[path.len() - 2], paths[0][paths[0].len() - 2]);
+        }
+        let fork = paths[0][paths[0].len() - 2];
+        assert_eq!(fork.x, root.x);
+        assert!(fork.y < root.y);
+    }
+
+    #[test]
+    fn test_bundled_single_connection_stays_orthogonal() {
+        let source = "rect a\nrect b\na -> b [routing: bundled]";
+        let bundled = crate::compute_layout(source, &crate::RenderConfig::new()).unwrap();
+        let plain =
+            crate::compute_layout("rect a\nrect b\na -> b", &crate::RenderConfig::new()).unwrap();
+        assert_eq!(bundled.connections[0].path, plain.connections[0].path);
+    }
+
     fn make_label_modifiers_with_offset(label: &str, offset: f64) -> Vec<Spanned<StyleModifier>> {
         let mut modifiers = make_label_modifiers(label, None);
         modifiers.push(Spanned::new(
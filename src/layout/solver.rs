@@ -3,14 +3,15 @@
 //! This module provides a wrapper around the kasuari Cassowary constraint solver,
 //! translating our layout constraints into the solver's format and extracting solutions.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use kasuari::{
-    Solver as KasuariSolver, Strength, Variable as KasuariVariable, WeightedRelation::*,
+    Constraint as KasuariConstraint, Solver as KasuariSolver, Strength,
+    Variable as KasuariVariable, WeightedRelation::*,
 };
 use thiserror::Error;
 
-use crate::parser::ast::Span;
+use crate::parser::ast::{ConstraintStrength, Span};
 
 // ============================================================================
 // T004: LayoutProperty enum
@@ -105,6 +106,9 @@ pub enum ConstraintOrigin {
     LayoutContainer,
     /// Generated from intrinsic properties (text size, etc.)
     Intrinsic,
+    /// Implied by a user constraint but yielding to explicit ones
+    /// (e.g. the alignment of `constrain a right_of b`)
+    UserPreference,
 }
 
 /// Tracks where a constraint came from
@@ -123,6 +127,8 @@ pub struct ConstraintSource {
     /// Parent layout container name (for LayoutContainer origin).
     /// Used to group row/col/stack constraints together in PASS 1.
     pub layout_container: Option<String>,
+    /// Explicit strength from `[strength: ...]`, overriding the default for the constraint kind
+    pub strength: Option<ConstraintStrength>,
 }
 
 impl ConstraintSource {
@@ -133,6 +139,7 @@ impl ConstraintSource {
             origin: ConstraintOrigin::UserDefined,
             template_instance: None,
             layout_container: None,
+            strength: None,
         }
     }
 
@@ -143,6 +150,18 @@ impl ConstraintSource {
             origin: ConstraintOrigin::LayoutContainer,
             template_instance: None,
             layout_container: None,
+            strength: None,
+        }
+    }
+
+    pub fn preference(span: Span, description: impl Into<String>) -> Self {
+        Self {
+            span,
+            description: description.into(),
+            origin: ConstraintOrigin::UserPreference,
+            template_instance: None,
+            layout_container: None,
+            strength: None,
         }
     }
 
@@ -153,6 +172,7 @@ impl ConstraintSource {
             origin: ConstraintOrigin::Intrinsic,
             template_instance: None,
             layout_container: None,
+            strength: None,
         }
     }
 
@@ -167,6 +187,23 @@ impl ConstraintSource {
         self.layout_container = Some(container.into());
         self
     }
+
+    /// Set an explicit solver strength.
+    pub fn with_strength(mut self, strength: ConstraintStrength) -> Self {
+        self.strength = Some(strength);
+        self
+    }
+
+    /// Solver strength for this constraint, falling back to `default`.
+    fn strength_or(&self, default: Strength) -> Strength {
+        match self.strength {
+            Some(ConstraintStrength::Weak) => Strength::WEAK,
+            Some(ConstraintStrength::Medium) => Strength::MEDIUM,
+            Some(ConstraintStrength::Strong) => Strength::STRONG,
+            Some(ConstraintStrength::Required) => Strength::REQUIRED,
+            None => default,
+        }
+    }
 }
 
 // ============================================================================
@@ -242,6 +279,14 @@ pub enum LayoutConstraint {
         offset: f64,
         source: ConstraintSource,
     },
+
+    /// target = sum(coefficient * variable) + constant (STRONG - overrides layout)
+    Linear {
+        target: LayoutVariable,
+        terms: Vec<(f64, LayoutVariable)>,
+        constant: f64,
+        source: ConstraintSource,
+    },
 }
 
 impl LayoutConstraint {
@@ -257,6 +302,22 @@ impl LayoutConstraint {
             LayoutConstraint::LessOrEqualRelational { source, .. } => source,
             LayoutConstraint::GreaterOrEqualRelational { source, .. } => source,
             LayoutConstraint::Midpoint { source, .. } => source,
+            LayoutConstraint::Linear { source, .. } => source,
+        }
+    }
+
+    /// Get a mutable reference to the source of this constraint
+    pub fn source_mut(&mut self) -> &mut ConstraintSource {
+        match self {
+            LayoutConstraint::Fixed { source, .. } => source,
+            LayoutConstraint::Suggested { source, .. } => source,
+            LayoutConstraint::Equal { source, .. } => source,
+            LayoutConstraint::GreaterOrEqual { source, .. } => source,
+            LayoutConstraint::LessOrEqual { source, .. } => source,
+            LayoutConstraint::LessOrEqualRelational { source, .. } => source,
+            LayoutConstraint::GreaterOrEqualRelational { source, .. } => source,
+            LayoutConstraint::Midpoint { source, .. } => source,
+            LayoutConstraint::Linear { source, .. } => source,
         }
     }
 
@@ -282,6 +343,11 @@ impl LayoutConstraint {
             LayoutConstraint::Midpoint { target, a, b, .. } => {
                 vec![&target.element_id, &a.element_id, &b.element_id]
             }
+            LayoutConstraint::Linear { target, terms, .. } => {
+                std::iter::once(target.element_id.as_str())
+                    .chain(terms.iter().map(|(_, v)| v.element_id.as_str()))
+                    .collect()
+            }
         }
     }
 }
@@ -318,10 +384,29 @@ pub enum SolverError {
 // ============================================================================
 
 /// Wrapper around kasuari solver
+///
+/// One solver can serve several passes: [`ConstraintSolver::clear_constraints`]
+/// drops the constraints of the previous pass but keeps its variables and the
+/// edit variables behind `Suggested` constraints, so a later pass on the same
+/// elements only moves the suggested values instead of rebuilding the tableau.
 pub struct ConstraintSolver {
     solver: KasuariSolver,
     /// Maps our variables to kasuari variables
     variables: HashMap<LayoutVariable, KasuariVariable>,
+    /// Reverse of `variables`, for reading back solutions
+    layout_vars: HashMap<KasuariVariable, LayoutVariable>,
+    /// Values solved in an earlier pass, preferred over fresh suggestions
+    warm_start: HashMap<LayoutVariable, f64>,
+    /// Edit variables and their strength; they outlive `clear_constraints`
+    edits: HashMap<KasuariVariable, Strength>,
+    /// `Suggested` constraints of the current pass, applied when solving
+    suggestions: Vec<(LayoutVariable, f64, ConstraintSource)>,
+    /// Constraints of the current pass, removed by `clear_constraints`
+    constraints: Vec<KasuariConstraint>,
+    /// Variables the current pass uses; these are what `solve` reports
+    pass_vars: HashSet<KasuariVariable>,
+    /// Edit variables the current pass suggested a value for
+    pass_edits: HashSet<KasuariVariable>,
     /// Tracks constraint sources for error reporting
     #[allow(dead_code)]
     sources: Vec<ConstraintSource>,
@@ -332,35 +417,77 @@ impl ConstraintSolver {
         Self {
             solver: KasuariSolver::new(),
             variables: HashMap::new(),
+            layout_vars: HashMap::new(),
+            warm_start: HashMap::new(),
+            edits: HashMap::new(),
+            suggestions: Vec::new(),
+            constraints: Vec::new(),
+            pass_vars: HashSet::new(),
+            pass_edits: HashSet::new(),
             sources: Vec::new(),
         }
     }
 
+    /// Seed suggestions with the values of an earlier pass
+    ///
+    /// A `Suggested` constraint on a variable that `previous` solved uses the
+    /// solved value instead of the one it was given, so a later pass starts
+    /// from where the earlier one ended rather than from stale bounds.
+    pub fn with_warm_start(mut self, previous: &Solution) -> Self {
+        self.set_warm_start(previous);
+        self
+    }
+
+    /// Replace the warm start values; see [`ConstraintSolver::with_warm_start`]
+    pub fn set_warm_start(&mut self, previous: &Solution) {
+        self.warm_start = previous
+            .values
+            .iter()
+            .map(|(var, value)| (var.clone(), *value))
+            .collect();
+    }
+
+    /// Start a new pass: remove the constraints and warm start values added
+    /// since the last clear
+    ///
+    /// Variables and edit variables stay, so suggestions for elements seen in
+    /// an earlier pass become cheap edits of an existing value.
+    pub fn clear_constraints(&mut self) -> Result<(), SolverError> {
+        for constraint in self.constraints.drain(..) {
+            self.solver.remove_constraint(&constraint).map_err(|e| {
+                SolverError::Internal(format!("Failed to remove constraint: {:?}", e))
+            })?;
+        }
+        self.warm_start.clear();
+        self.suggestions.clear();
+        self.pass_vars.clear();
+        self.pass_edits.clear();
+        self.sources.clear();
+        Ok(())
+    }
+
     /// Get or create a kasuari variable for a base property (X, Y, Width, Height)
     fn get_or_create_base_var(
         &mut self,
         element_id: &str,
         property: LayoutProperty,
     ) -> KasuariVariable {
-        let var = LayoutVariable::new(element_id, property);
-        if let Some(&kvar) = self.variables.get(&var) {
-            kvar
-        } else {
-            let kvar = KasuariVariable::new();
-            self.variables.insert(var, kvar);
-            kvar
-        }
+        self.get_or_create_var(&LayoutVariable::new(element_id, property))
     }
 
     /// Get or create a kasuari variable for our layout variable (for base properties only)
     fn get_or_create_var(&mut self, var: &LayoutVariable) -> KasuariVariable {
-        if let Some(&kvar) = self.variables.get(var) {
-            kvar
-        } else {
-            let kvar = KasuariVariable::new();
-            self.variables.insert(var.clone(), kvar);
-            kvar
-        }
+        let kvar = match self.variables.get(var) {
+            Some(&kvar) => kvar,
+            None => {
+                let kvar = KasuariVariable::new();
+                self.variables.insert(var.clone(), kvar);
+                self.layout_vars.insert(kvar, var.clone());
+                kvar
+            }
+        };
+        self.pass_vars.insert(kvar);
+        kvar
     }
 
     /// Create a kasuari expression for a layout variable
@@ -443,6 +570,21 @@ impl ConstraintSolver {
         }
     }
 
+    /// Add a kasuari constraint for the current pass
+    fn add(
+        &mut self,
+        constraint: KasuariConstraint,
+        source: &ConstraintSource,
+        desc: &str,
+    ) -> Result<(), SolverError> {
+        self.solver
+            .add_constraint(constraint.clone())
+            .map_err(|e| self.convert_kasuari_error(e, source, desc))?;
+        self.constraints.push(constraint);
+        self.sources.push(source.clone());
+        Ok(())
+    }
+
     /// Add a constraint to the solver
     pub fn add_constraint(&mut self, constraint: LayoutConstraint) -> Result<(), SolverError> {
         match &constraint {
@@ -457,10 +599,11 @@ impl ConstraintSolver {
                     "{}.{:?} = {}",
                     variable.element_id, variable.property, value
                 );
-                self.solver
-                    .add_constraint(expr | EQ(Strength::REQUIRED) | *value)
-                    .map_err(|e| self.convert_kasuari_error(e, source, &desc))?;
-                self.sources.push(source.clone());
+                self.add(
+                    expr | EQ(source.strength_or(Strength::REQUIRED)) | *value,
+                    source,
+                    &desc,
+                )?;
             }
 
             LayoutConstraint::Suggested {
@@ -468,16 +611,12 @@ impl ConstraintSolver {
                 value,
                 source,
             } => {
-                // MEDIUM strength - can be overridden by user constraints (STRONG)
-                let expr = self.get_expression(variable);
-                let desc = format!(
-                    "{}.{:?} ~= {} (suggested)",
-                    variable.element_id, variable.property, value
-                );
-                self.solver
-                    .add_constraint(expr | EQ(Strength::MEDIUM) | *value)
-                    .map_err(|e| self.convert_kasuari_error(e, source, &desc))?;
-                self.sources.push(source.clone());
+                // MEDIUM strength - can be overridden by user constraints (STRONG).
+                // Applied when solving, after the constraints it yields to.
+                let value = self.warm_start.get(variable).copied().unwrap_or(*value);
+                self.get_expression(variable);
+                self.suggestions
+                    .push((variable.clone(), value, source.clone()));
             }
 
             LayoutConstraint::Equal {
@@ -488,12 +627,16 @@ impl ConstraintSolver {
             } => {
                 // Use different strength based on origin:
                 // - UserDefined: STRONG (1,000,000) - user constraints take priority
-                // - LayoutContainer: STRONG * 0.1 (100,000) - can be broken by user constraints
+                // - LayoutContainer / UserPreference: STRONG * 0.1 (100,000) - can be
+                //   broken by user constraints, but still beat layout suggestions (MEDIUM)
                 let strength = match source.origin {
                     ConstraintOrigin::UserDefined => Strength::STRONG,
-                    ConstraintOrigin::LayoutContainer => Strength::STRONG * 0.1,
+                    ConstraintOrigin::LayoutContainer | ConstraintOrigin::UserPreference => {
+                        Strength::STRONG * 0.1
+                    }
                     ConstraintOrigin::Intrinsic => Strength::STRONG,
                 };
+                let strength = source.strength_or(strength);
                 let left_expr = self.get_expression(left);
                 let right_expr = self.get_expression(right);
                 let desc = if *offset == 0.0 {
@@ -507,10 +650,11 @@ impl ConstraintSolver {
                         left.element_id, left.property, right.element_id, right.property, offset
                     )
                 };
-                self.solver
-                    .add_constraint(left_expr | EQ(strength) | (right_expr + *offset))
-                    .map_err(|e| self.convert_kasuari_error(e, source, &desc))?;
-                self.sources.push(source.clone());
+                self.add(
+                    left_expr | EQ(strength) | (right_expr + *offset),
+                    source,
+                    &desc,
+                )?;
             }
 
             LayoutConstraint::GreaterOrEqual {
@@ -524,10 +668,11 @@ impl ConstraintSolver {
                     "{}.{:?} >= {}",
                     variable.element_id, variable.property, value
                 );
-                self.solver
-                    .add_constraint(expr | GE(Strength::STRONG) | *value)
-                    .map_err(|e| self.convert_kasuari_error(e, source, &desc))?;
-                self.sources.push(source.clone());
+                self.add(
+                    expr | GE(source.strength_or(Strength::STRONG)) | *value,
+                    source,
+                    &desc,
+                )?;
             }
 
             LayoutConstraint::LessOrEqual {
@@ -541,10 +686,11 @@ impl ConstraintSolver {
                     "{}.{:?} <= {}",
                     variable.element_id, variable.property, value
                 );
-                self.solver
-                    .add_constraint(expr | LE(Strength::STRONG) | *value)
-                    .map_err(|e| self.convert_kasuari_error(e, source, &desc))?;
-                self.sources.push(source.clone());
+                self.add(
+                    expr | LE(source.strength_or(Strength::STRONG)) | *value,
+                    source,
+                    &desc,
+                )?;
             }
 
             LayoutConstraint::LessOrEqualRelational {
@@ -559,10 +705,11 @@ impl ConstraintSolver {
                     "{}.{:?} <= {}.{:?} + {}",
                     left.element_id, left.property, right.element_id, right.property, offset
                 );
-                self.solver
-                    .add_constraint(left_expr | LE(Strength::STRONG) | (right_expr + *offset))
-                    .map_err(|e| self.convert_kasuari_error(e, source, &desc))?;
-                self.sources.push(source.clone());
+                self.add(
+                    left_expr | LE(source.strength_or(Strength::STRONG)) | (right_expr + *offset),
+                    source,
+                    &desc,
+                )?;
             }
 
             LayoutConstraint::GreaterOrEqualRelational {
@@ -577,10 +724,11 @@ impl ConstraintSolver {
                     "{}.{:?} >= {}.{:?} + {}",
                     left.element_id, left.property, right.element_id, right.property, offset
                 );
-                self.solver
-                    .add_constraint(left_expr | GE(Strength::STRONG) | (right_expr + *offset))
-                    .map_err(|e| self.convert_kasuari_error(e, source, &desc))?;
-                self.sources.push(source.clone());
+                self.add(
+                    left_expr | GE(source.strength_or(Strength::STRONG)) | (right_expr + *offset),
+                    source,
+                    &desc,
+                )?;
             }
 
             LayoutConstraint::Midpoint {
@@ -618,14 +766,38 @@ impl ConstraintSolver {
                 };
                 // Express midpoint + offset as: 2*target = a + b + 2*offset
                 // Which is equivalent to: target = (a + b) / 2 + offset
-                self.solver
-                    .add_constraint(
-                        (2.0 * target_expr)
-                            | EQ(Strength::STRONG)
-                            | (a_expr + b_expr + 2.0 * offset),
-                    )
-                    .map_err(|e| self.convert_kasuari_error(e, source, &desc))?;
-                self.sources.push(source.clone());
+                self.add(
+                    (2.0 * target_expr)
+                        | EQ(source.strength_or(Strength::STRONG))
+                        | (a_expr + b_expr + 2.0 * offset),
+                    source,
+                    &desc,
+                )?;
+            }
+
+            LayoutConstraint::Linear {
+                target,
+                terms,
+                constant,
+                source,
+            } => {
+                // STRONG strength - overrides layout suggestions (MEDIUM) but not fixed (REQUIRED)
+                let target_expr = self.get_expression(target);
+                let mut rhs = kasuari::Expression::from_constant(*constant);
+                let mut desc = format!("{}.{:?} =", target.element_id, target.property);
+                for (coefficient, var) in terms {
+                    rhs += *coefficient * self.get_expression(var);
+                    desc.push_str(&format!(
+                        " {}*{}.{:?}",
+                        coefficient, var.element_id, var.property
+                    ));
+                }
+                desc.push_str(&format!(" + {}", constant));
+                self.add(
+                    target_expr | EQ(source.strength_or(Strength::STRONG)) | rhs,
+                    source,
+                    &desc,
+                )?;
             }
         }
         Ok(())
@@ -637,6 +809,7 @@ impl ConstraintSolver {
 // ============================================================================
 
 /// Solution from the constraint solver
+#[derive(Debug, Clone, Default)]
 pub struct Solution {
     pub values: HashMap<LayoutVariable, f64>,
 }
@@ -654,25 +827,37 @@ impl Solution {
             .get(&LayoutVariable::new(element_id, property))
             .copied()
     }
+
+    /// Add the values of another solution, overwriting shared variables
+    pub fn extend(&mut self, other: &Solution) {
+        self.values.extend(
+            other
+                .values
+                .iter()
+                .map(|(var, value)| (var.clone(), *value)),
+        );
+    }
 }
 
 impl ConstraintSolver {
     /// Solve the constraint system
+    ///
+    /// The solution holds every variable used since the last
+    /// [`ConstraintSolver::clear_constraints`].
     pub fn solve(&mut self) -> Result<Solution, SolverError> {
-        // Fetch changes from kasuari
-        let changes = self.solver.fetch_changes();
-
-        // Build solution map
-        let mut values = HashMap::new();
-        for (kvar, value) in changes {
-            // Find our variable for this kasuari variable
-            for (our_var, &k) in &self.variables {
-                if k == *kvar {
-                    values.insert(our_var.clone(), *value);
-                    break;
-                }
-            }
-        }
+        self.apply_suggestions()?;
+
+        // kasuari's hash maps are seeded per process, so the pivot order and
+        // with it the last bits of a value change between runs. Rounding to a
+        // millionth of a pixel keeps the output byte-identical.
+        let values = self
+            .pass_vars
+            .iter()
+            .map(|kvar| {
+                let value = (self.solver.get_value(*kvar) * 1e6).round() / 1e6;
+                (self.layout_vars[kvar].clone(), value)
+            })
+            .collect();
 
         Ok(Solution { values })
     }
@@ -680,14 +865,81 @@ impl ConstraintSolver {
     /// Add an edit variable with suggested value (for anchoring the system)
     pub fn suggest_value(&mut self, var: &LayoutVariable, value: f64) -> Result<(), SolverError> {
         let kvar = self.get_or_create_var(var);
-        self.solver
-            .add_edit_variable(kvar, Strength::STRONG)
-            .map_err(|e| SolverError::Internal(format!("Failed to add edit variable: {}", e)))?;
+        self.edit(kvar, value, Strength::STRONG)
+    }
+
+    /// Apply the pass's `Suggested` constraints
+    ///
+    /// Cassowary re-optimizes after every addition, and an equality that
+    /// contradicts suggestions already in the tableau costs far more than one
+    /// added before them, so suggestions go in last. A suggestion on a base
+    /// property becomes an edit variable that later passes reuse.
+    fn apply_suggestions(&mut self) -> Result<(), SolverError> {
+        for (variable, value, source) in std::mem::take(&mut self.suggestions) {
+            let strength = source.strength_or(Strength::MEDIUM);
+            let kvar = self.variables.get(&variable).copied();
+            match kvar {
+                Some(kvar)
+                    if strength != Strength::REQUIRED && !self.pass_edits.contains(&kvar) =>
+                {
+                    self.edit(kvar, value, strength)?;
+                }
+                // Derived properties, required suggestions and repeated
+                // suggestions for one variable stay plain constraints
+                _ => {
+                    let expr = self.get_expression(&variable);
+                    let desc = format!(
+                        "{}.{:?} ~= {} (suggested)",
+                        variable.element_id, variable.property, value
+                    );
+                    self.add(expr | EQ(strength) | value, &source, &desc)?;
+                }
+            }
+        }
+
+        // An edit variable left over from an earlier pass would still pull
+        // on a variable this pass uses
+        let stale: Vec<_> = self
+            .edits
+            .keys()
+            .filter(|kvar| self.pass_vars.contains(kvar) && !self.pass_edits.contains(kvar))
+            .copied()
+            .collect();
+        for kvar in stale {
+            self.remove_edit(kvar)?;
+        }
+        Ok(())
+    }
+
+    /// Suggest `value` for `kvar`, adding its edit variable if needed
+    fn edit(
+        &mut self,
+        kvar: KasuariVariable,
+        value: f64,
+        strength: Strength,
+    ) -> Result<(), SolverError> {
+        if self.edits.get(&kvar).is_some_and(|s| *s != strength) {
+            self.remove_edit(kvar)?;
+        }
+        if !self.edits.contains_key(&kvar) {
+            self.solver.add_edit_variable(kvar, strength).map_err(|e| {
+                SolverError::Internal(format!("Failed to add edit variable: {}", e))
+            })?;
+            self.edits.insert(kvar, strength);
+        }
         self.solver
             .suggest_value(kvar, value)
             .map_err(|e| SolverError::Internal(format!("Failed to suggest value: {}", e)))?;
+        self.pass_edits.insert(kvar);
         Ok(())
     }
+
+    fn remove_edit(&mut self, kvar: KasuariVariable) -> Result<(), SolverError> {
+        self.edits.remove(&kvar);
+        self.solver
+            .remove_edit_variable(kvar)
+            .map_err(|e| SolverError::Internal(format!("Failed to remove edit variable: {}", e)))
+    }
 }
 
 // ============================================================================
@@ -740,6 +992,145 @@ mod tests {
         assert!((solution.get(&b).unwrap() - 50.0).abs() < 0.001);
     }
 
+    #[test]
+    fn test_weak_fixed_constraint_does_not_conflict() {
+        let mut solver = ConstraintSolver::new();
+        let var = LayoutVariable::x("box");
+
+        solver
+            .add_constraint(LayoutConstraint::Fixed {
+                variable: var.clone(),
+                value: 10.0,
+                source: ConstraintSource::user(0..0, "required"),
+            })
+            .unwrap();
+        solver
+            .add_constraint(LayoutConstraint::Fixed {
+                variable: var.clone(),
+                value: 20.0,
+                source: ConstraintSource::user(0..0, "weak")
+                    .with_strength(ConstraintStrength::Weak),
+            })
+            .unwrap();
+
+        let solution = solver.solve().unwrap();
+        assert!((solution.get(&var).unwrap() - 10.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_warm_start_replaces_suggestion() {
+        let var = LayoutVariable::x("box");
+        let mut previous = Solution::default();
+        previous.values.insert(var.clone(), 42.0);
+
+        let mut solver = ConstraintSolver::new().with_warm_start(&previous);
+        solver
+            .add_constraint(LayoutConstraint::Suggested {
+                variable: var.clone(),
+                value: 10.0,
+                source: ConstraintSource::layout(0..0, "stale bounds"),
+            })
+            .unwrap();
+        let other = LayoutVariable::y("box");
+        solver
+            .add_constraint(LayoutConstraint::Suggested {
+                variable: other.clone(),
+                value: 7.0,
+                source: ConstraintSource::layout(0..0, "no earlier value"),
+            })
+            .unwrap();
+
+        let solution = solver.solve().unwrap();
+        assert!((solution.get(&var).unwrap() - 42.0).abs() < 0.001);
+        assert!((solution.get(&other).unwrap() - 7.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_solver_reused_across_passes() {
+        let a = LayoutVariable::x("a");
+        let b = LayoutVariable::x("b");
+        let mut solver = ConstraintSolver::new();
+
+        // Pass 1: b follows a
+        solver
+            .add_constraint(LayoutConstraint::Suggested {
+                variable: a.clone(),
+                value: 10.0,
+                source: ConstraintSource::layout(0..0, "a"),
+            })
+            .unwrap();
+        solver
+            .add_constraint(LayoutConstraint::Equal {
+                left: b.clone(),
+                right: a.clone(),
+                offset: 20.0,
+                source: ConstraintSource::user(0..0, "b = a + 20"),
+            })
+            .unwrap();
+        let solution = solver.solve().unwrap();
+        assert!((solution.get(&b).unwrap() - 30.0).abs() < 0.001);
+
+        // Pass 2: a follows a weak suggestion on b; the (stronger) suggestion
+        // on a from pass 1 must not pull
+        solver.clear_constraints().unwrap();
+        solver
+            .add_constraint(LayoutConstraint::Suggested {
+                variable: b.clone(),
+                value: 50.0,
+                source: ConstraintSource::layout(0..0, "b").with_strength(ConstraintStrength::Weak),
+            })
+            .unwrap();
+        solver
+            .add_constraint(LayoutConstraint::Equal {
+                left: a.clone(),
+                right: b.clone(),
+                offset: 0.0,
+                source: ConstraintSource::user(0..0, "a = b"),
+            })
+            .unwrap();
+        let solution = solver.solve().unwrap();
+        assert!((solution.get(&a).unwrap() - 50.0).abs() < 0.001);
+        assert!((solution.get(&b).unwrap() - 50.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_suggest_value_edits_existing_variable() {
+        let mut solver = ConstraintSolver::new();
+        let var = LayoutVariable::x("box");
+
+        solver.suggest_value(&var, 10.0).unwrap();
+        solver.suggest_value(&var, 20.0).unwrap();
+        let solution = solver.solve().unwrap();
+        assert!((solution.get(&var).unwrap() - 20.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_linear_constraint() {
+        let mut solver = ConstraintSolver::new();
+        let a = LayoutVariable::width("a");
+        let b = LayoutVariable::width("b");
+
+        // a.width = b.width * 0.5 + 10
+        solver
+            .add_constraint(LayoutConstraint::Linear {
+                target: a.clone(),
+                terms: vec![(0.5, b.clone())],
+                constant: 10.0,
+                source: ConstraintSource::user(0..0, "test"),
+            })
+            .unwrap();
+        solver
+            .add_constraint(LayoutConstraint::Fixed {
+                variable: b.clone(),
+                value: 200.0,
+                source: ConstraintSource::intrinsic("test"),
+            })
+            .unwrap();
+
+        let solution = solver.solve().unwrap();
+        assert!((solution.get(&a).unwrap() - 110.0).abs() < 0.001);
+    }
+
     #[test]
     fn test_midpoint_constraint() {
         let mut solver = ConstraintSolver::new();
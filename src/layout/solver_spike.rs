@@ -3,6 +3,7 @@
 //! Run with: cargo test spike_kasuari_fitness --lib
 
 #[cfg(test)]
+#[allow(clippy::precedence)]
 mod tests {
     use kasuari::{Solver, Strength, Variable, WeightedRelation::*};
 
@@ -29,8 +29,81 @@
 //! - 90° = rotated clockwise (right becomes down)
 //! - 180° = upside down
 //! - 270° = rotated counter-clockwise (right becomes up)
+//!
+//! ## Scale and Flip
+//!
+//! `scale` and `flip` go through the same pipeline: the transform scales (and
+//! mirrors, for a negative factor) around the center first, then rotates, which
+//! matches the SVG `rotate(..) scale(..)` order.
+
+use crate::layout::types::{Anchor, AnchorDirection, BoundingBox, Flip, Point, ResolvedStyles};
+
+/// Rotation, scale and mirroring declared on an element with the `rotation`,
+/// `scale` and `flip` modifiers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ElementTransform {
+    /// Rotation angle in degrees (clockwise positive)
+    pub rotation: f64,
+    /// Uniform scale factor (1 = unchanged)
+    pub scale: f64,
+    /// Mirroring applied before the rotation
+    pub flip: Option<Flip>,
+}
 
-use crate::layout::types::{Anchor, AnchorDirection, BoundingBox, Point};
+impl Default for ElementTransform {
+    fn default() -> Self {
+        Self {
+            rotation: 0.0,
+            scale: 1.0,
+            flip: None,
+        }
+    }
+}
+
+impl ElementTransform {
+    /// A plain rotation by `angle_degrees`
+    pub fn rotation(angle_degrees: f64) -> Self {
+        Self {
+            rotation: angle_degrees,
+            ..Self::default()
+        }
+    }
+
+    /// The transform declared by an element's resolved styles
+    pub fn from_styles(styles: &ResolvedStyles) -> Self {
+        Self {
+            rotation: styles.rotation.unwrap_or(0.0),
+            scale: styles.scale.unwrap_or(1.0),
+            flip: styles.flip,
+        }
+    }
+
+    /// Horizontal and vertical scale factors, negative when mirrored
+    pub fn factors(&self) -> (f64, f64) {
+        let (mut x, mut y) = (self.scale, self.scale);
+        match self.flip {
+            Some(Flip::Horizontal) => x = -x,
+            Some(Flip::Vertical) => y = -y,
+            Some(Flip::Both) => (x, y) = (-x, -y),
+            None => {}
+        }
+        (x, y)
+    }
+
+    /// Check if this transform leaves elements unchanged
+    pub fn is_identity(&self) -> bool {
+        let (x, y) = self.factors();
+        self.rotation.abs() < f64::EPSILON
+            && (x - 1.0).abs() < f64::EPSILON
+            && (y - 1.0).abs() < f64::EPSILON
+    }
+
+    /// This transform applied around `center`
+    pub fn around(&self, center: Point) -> RotationTransform {
+        let (x, y) = self.factors();
+        RotationTransform::new(self.rotation, center).with_scale(x, y)
+    }
+}
 
 /// Represents a 2D rotation transformation around a center point.
 ///
@@ -42,6 +115,10 @@ pub struct RotationTransform {
     pub angle_degrees: f64,
     /// Center point of rotation (typically the geometric center of the template)
     pub center: Point,
+    /// Horizontal scale applied before rotating (negative mirrors)
+    pub scale_x: f64,
+    /// Vertical scale applied before rotating (negative mirrors)
+    pub scale_y: f64,
 }
 
 impl RotationTransform {
@@ -54,15 +131,26 @@ impl RotationTransform {
         Self {
             angle_degrees,
             center,
+            scale_x: 1.0,
+            scale_y: 1.0,
         }
     }
 
-    /// Check if this is effectively a no-op (0° rotation).
+    /// Scale (and mirror, for negative factors) around the center before rotating
+    pub fn with_scale(mut self, scale_x: f64, scale_y: f64) -> Self {
+        self.scale_x = scale_x;
+        self.scale_y = scale_y;
+        self
+    }
+
+    /// Check if this is effectively a no-op (0° rotation, unit scale).
     ///
     /// Returns true if the rotation angle is close enough to zero that it
     /// would not produce any visible change.
     pub fn is_identity(&self) -> bool {
         self.angle_degrees.abs() < f64::EPSILON
+            && (self.scale_x - 1.0).abs() < f64::EPSILON
+            && (self.scale_y - 1.0).abs() < f64::EPSILON
     }
 
     /// Rotate a point around the center using standard 2D rotation matrix.
@@ -90,8 +178,8 @@ impl RotationTransform {
         let cos_a = radians.cos();
         let sin_a = radians.sin();
 
-        let dx = point.x - self.center.x;
-        let dy = point.y - self.center.y;
+        let dx = (point.x - self.center.x) * self.scale_x;
+        let dy = (point.y - self.center.y) * self.scale_y;
 
         // In SVG's coordinate system (Y-down), clockwise rotation uses:
         // [cos  -sin] [dx]
@@ -191,7 +279,14 @@ impl RotationTransform {
     /// # Returns
     /// The rotated direction
     pub fn transform_direction(&self, dir: AnchorDirection) -> AnchorDirection {
-        let original_angle = dir.to_degrees();
+        let mut original_angle = dir.to_degrees();
+        // Mirroring reflects the direction before it is rotated
+        if self.scale_x < 0.0 {
+            original_angle = 180.0 - original_angle;
+        }
+        if self.scale_y < 0.0 {
+            original_angle = -original_angle;
+        }
         // Add angle for clockwise rotation in SVG coordinates
         // When shape rotates 90° CW, direction vectors rotate 90° CW too:
         // Right (0°) -> Down (90°), Down (90°) -> Left (180°), etc.
@@ -536,4 +631,53 @@ mod tests {
             top.direction
         );
     }
+
+    #[test]
+    fn test_scale_and_flip() {
+        let center = Point { x: 50.0, y: 25.0 };
+        let bounds = BoundingBox::new(0.0, 0.0, 100.0, 50.0);
+
+        let half = ElementTransform {
+            scale: 0.5,
+            ..ElementTransform::default()
+        }
+        .around(center);
+        let result = half.transform_bounds(&bounds);
+        assert!(approx_eq(result.x, 25.0) && approx_eq(result.y, 12.5));
+        assert!(approx_eq(result.width, 50.0) && approx_eq(result.height, 25.0));
+
+        // Mirroring keeps the box but swaps the anchors across it
+        let flip = ElementTransform {
+            flip: Some(Flip::Horizontal),
+            ..ElementTransform::default()
+        }
+        .around(center);
+        assert!(approx_eq(flip.transform_bounds(&bounds).x, 0.0));
+        let anchors = AnchorSet::simple_shape(&bounds);
+        let left = anchors.transform(&flip).get("left").unwrap().clone();
+        assert!(approx_eq(left.position.x, 100.0));
+        assert_eq!(left.direction, AnchorDirection::Right);
+        let top = anchors.transform(&flip).get("top").unwrap().clone();
+        assert_eq!(top.direction, AnchorDirection::Up);
+
+        let vertical = ElementTransform {
+            flip: Some(Flip::Vertical),
+            ..ElementTransform::default()
+        }
+        .around(center);
+        let top = anchors.transform(&vertical).get("top").unwrap().clone();
+        assert!(approx_eq(top.position.y, 50.0));
+        assert_eq!(top.direction, AnchorDirection::Down);
+
+        assert!(ElementTransform::default().is_identity());
+        assert_eq!(
+            ElementTransform {
+                flip: Some(Flip::Both),
+                scale: 2.0,
+                ..ElementTransform::default()
+            }
+            .factors(),
+            (-2.0, -2.0)
+        );
+    }
 }
@@ -3,11 +3,12 @@
 use std::collections::HashMap;
 
 use crate::parser::ast::{
-    ColorValue, ConnectionDirection, ConstraintProperty, Identifier, LayoutType, ShapeType, Span,
-    Spanned, StyleKey, StyleModifier, StyleValue,
+    ColorValue, ConnectionDirection, ConstraintProperty, Identifier, LayoutType, PathDecl,
+    ShapeType, Span, Spanned, StyleKey, StyleModifier, StyleValue,
 };
 
-use super::routing::RoutingMode;
+use super::routing::{CrossingStyle, RoutingMode};
+use super::solver::Solution;
 
 // ============================================
 // Anchor Types (Feature 009)
@@ -117,18 +118,78 @@ impl Anchor {
     }
 }
 
+/// A named port: an anchor at a fraction along one side of its element
+#[derive(Debug, Clone, PartialEq)]
+pub struct Port {
+    pub name: String,
+    /// Outward direction of the side the port sits on
+    pub side: AnchorDirection,
+    /// Position along the side, from 0 (top or left end) to 1
+    pub at: f64,
+    /// Whether the renderer draws a dot at the port
+    pub marker: bool,
+}
+
+impl Port {
+    /// The port's anchor on an element with these bounds
+    pub fn anchor(&self, bounds: &BoundingBox) -> Anchor {
+        let along_x = bounds.x + bounds.width * self.at;
+        let along_y = bounds.y + bounds.height * self.at;
+        let position = match self.side {
+            AnchorDirection::Up => Point::new(along_x, bounds.y),
+            AnchorDirection::Down => Point::new(along_x, bounds.bottom()),
+            AnchorDirection::Left => Point::new(bounds.x, along_y),
+            _ => Point::new(bounds.right(), along_y),
+        };
+        Anchor::new(self.name.clone(), position, self.side)
+    }
+}
+
+/// Heights of the name, attribute and operation compartments of a UML
+/// `class` box, top to bottom
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClassCompartments {
+    /// Height of one attribute or operation line
+    pub line_height: f64,
+    pub name: f64,
+    pub attributes: f64,
+    pub operations: f64,
+}
+
+impl ClassCompartments {
+    /// Space above and below the lines of a compartment
+    pub const PADDING: f64 = 6.0;
+    /// Space between the box's left edge and the member text
+    pub const INSET: f64 = 10.0;
+
+    pub fn new(attributes: usize, operations: usize, font_size: f64) -> Self {
+        let line_height = font_size * 1.4;
+        let height = |lines: usize| lines as f64 * line_height + 2.0 * Self::PADDING;
+        Self {
+            line_height,
+            name: height(1),
+            attributes: height(attributes),
+            operations: height(operations),
+        }
+    }
+
+    pub fn height(&self) -> f64 {
+        self.name + self.attributes + self.operations
+    }
+}
+
 /// Collection of anchors for an element (T002)
 #[derive(Debug, Clone, Default)]
 pub struct AnchorSet {
     anchors: HashMap<String, Anchor>,
+    /// Declared ports, whose anchors are rebuilt when the bounds change
+    ports: Vec<Port>,
 }
 
 impl AnchorSet {
     /// Create an empty anchor set
     pub fn new() -> Self {
-        Self {
-            anchors: HashMap::new(),
-        }
+        Self::default()
     }
 
     /// Get an anchor by name
@@ -197,13 +258,75 @@ impl AnchorSet {
         set
     }
 
+    /// Add an anchor for each named vertex of a path shape.
+    ///
+    /// The anchor points away from the center of the bounds. Vertices named
+    /// like a built-in anchor keep the built-in one.
+    pub fn insert_path_vertices(&mut self, decl: &PathDecl, bounds: &BoundingBox) {
+        const BUILTIN: &[&str] = &[
+            "top",
+            "bottom",
+            "left",
+            "right",
+            "top_left",
+            "top_right",
+            "bottom_left",
+            "bottom_right",
+        ];
+        let origin = Point::new(bounds.x, bounds.y);
+        let center = bounds.center();
+        for (name, position) in crate::renderer::path::vertex_positions(decl, origin) {
+            if BUILTIN.contains(&name.as_str()) {
+                continue;
+            }
+            let (dx, dy) = (position.x - center.x, position.y - center.y);
+            let direction = if dx.abs() < f64::EPSILON && dy.abs() < f64::EPSILON {
+                AnchorDirection::Up
+            } else {
+                AnchorDirection::from_degrees(dy.atan2(dx).to_degrees())
+            };
+            self.insert(Anchor::new(name, position, direction));
+        }
+    }
+
+    /// Add `outward` and `inward` anchors for an element placed on a ring.
+    ///
+    /// `degrees` is the direction from the ring center to the element. The anchors
+    /// sit where that ray (and its opposite) leaves the element's bounds.
+    pub fn insert_radial(&mut self, bounds: &BoundingBox, degrees: f64) {
+        for (name, angle) in [("outward", degrees), ("inward", degrees + 180.0)] {
+            let direction = AnchorDirection::from_degrees(angle);
+            let position = match direction {
+                AnchorDirection::Up => bounds.top_center(),
+                AnchorDirection::Down => bounds.bottom_center(),
+                AnchorDirection::Left => bounds.left_center(),
+                AnchorDirection::Right => bounds.right_center(),
+                AnchorDirection::Angle(_) => {
+                    let v = direction.to_vector();
+                    let center = bounds.center();
+                    // Distance from the center to the bounds edge along v
+                    let t = f64::min(
+                        bounds.width / 2.0 / v.x.abs(),
+                        bounds.height / 2.0 / v.y.abs(),
+                    );
+                    Point::new(center.x + v.x * t, center.y + v.y * t)
+                }
+            };
+            self.insert(Anchor::new(name, position, direction));
+        }
+    }
+
     /// Create anchors for an element type with the given bounds.
     /// This determines the appropriate anchor set based on element type:
     /// - Path shapes get 8 anchors (4 sides + 4 corners)
     /// - All other shapes, layouts, and groups get 4 anchors (top, bottom, left, right)
     pub fn for_element_type(element_type: &ElementType, bounds: &BoundingBox) -> Self {
         match element_type {
-            ElementType::Shape(ShapeType::Path(_)) => Self::path_shape(bounds),
+            ElementType::Shape(ShapeType::Path(decl)) => {
+                let mut set = Self::path_shape(bounds);
+                set.insert_path_vertices(decl, bounds);
+                set
+            }
             _ => Self::simple_shape(bounds),
         }
     }
@@ -230,8 +353,9 @@ impl AnchorSet {
             AnchorDirection::Right,
         ));
 
-        // For path shapes, also update corner anchors
-        if matches!(element_type, ElementType::Shape(ShapeType::Path(_))) {
+        // For path shapes, also update corner and vertex anchors
+        if let ElementType::Shape(ShapeType::Path(decl)) = element_type {
+            self.insert_path_vertices(decl, bounds);
             self.insert(Anchor::new(
                 "top_left",
                 bounds.top_left(),
@@ -253,6 +377,28 @@ impl AnchorSet {
                 AnchorDirection::Angle(45.0),
             ));
         }
+
+        // Radial anchors (children of a ring) keep their angle and follow the bounds
+        if let Some(degrees) = self.get("outward").map(|a| a.direction.to_degrees()) {
+            self.insert_radial(bounds, degrees);
+        }
+
+        let ports: Vec<Anchor> = self.ports.iter().map(|p| p.anchor(bounds)).collect();
+        for anchor in ports {
+            self.insert(anchor);
+        }
+    }
+
+    /// Add a port and its anchor; the anchor follows the bounds from then on
+    pub fn insert_port(&mut self, port: Port, bounds: &BoundingBox) {
+        self.insert(port.anchor(bounds));
+        self.ports.retain(|p| p.name != port.name);
+        self.ports.push(port);
+    }
+
+    /// Declared ports, in declaration order
+    pub fn ports(&self) -> &[Port] {
+        &self.ports
     }
 
     /// Create anchors from a list of custom anchor definitions
@@ -298,6 +444,7 @@ impl AnchorSet {
                 .iter()
                 .map(|(name, anchor)| (name.clone(), rotation.transform_anchor(anchor)))
                 .collect(),
+            ports: self.ports.clone(),
         }
     }
 
@@ -505,11 +652,53 @@ pub struct ResolvedStyles {
     pub stroke: Option<String>,
     pub stroke_width: Option<f64>,
     pub stroke_dasharray: Option<String>,
+    /// Stroke end shape (`line_cap: butt|round|square`)
+    pub line_cap: Option<String>,
+    /// Stroke corner shape (`line_join: miter|round|bevel`)
+    pub line_join: Option<String>,
+    /// Marker drawn at each interior vertex of a connection (`marker_mid: dot|arrow`)
+    pub marker_mid: Option<String>,
     pub opacity: Option<f64>,
     pub font_size: Option<f64>,
+    /// Font family of the element's text (`font_family: "Inter, sans-serif"`)
+    pub font_family: Option<String>,
     pub css_classes: Vec<String>,
     /// Rotation angle in degrees (clockwise positive, 0 = no rotation)
     pub rotation: Option<f64>,
+    /// Uniform scale factor around the element's center (`scale: 0.5`)
+    pub scale: Option<f64>,
+    /// Mirroring around the element's center (`flip: horizontal|vertical|both`)
+    pub flip: Option<Flip>,
+    /// Trusted SVG markup emitted verbatim (`raw_svg` modifier)
+    pub raw_svg: Option<String>,
+    /// Level of detail (`detail: high`), used to hide elements at small sizes
+    pub detail: Option<String>,
+    /// Rounded corner radius (`corner_radius: 8`) for rects and container backgrounds
+    pub corner_radius: Option<f64>,
+    /// Stroke gradient from the start to the end of a connection (`stroke: fade(a, b)`)
+    pub stroke_fade: Option<(String, String)>,
+    /// Stroke opacity at the start of a connection (`opacity_start: 0.2`)
+    pub opacity_start: Option<f64>,
+    /// Stroke opacity at the end of a connection (`opacity_end: 0.2`)
+    pub opacity_end: Option<f64>,
+    /// Extrusion depth of a rect (`depth: 8`), drawn as shaded top and side faces
+    pub depth: Option<f64>,
+    /// Gradient fill (`fill: gradient(...)`); `fill` holds the first color as fallback
+    pub fill_gradient: Option<FillGradient>,
+    /// Accessible name / hover tooltip (`tooltip: "..."`), emitted as `<title>`
+    pub tooltip: Option<String>,
+    /// Longer accessible description (`description: "..."`), emitted as `<desc>`
+    pub description: Option<String>,
+    /// Mark a state machine's initial state (`initial: true`) with a dot and
+    /// an arrow into its left side
+    pub initial: bool,
+    /// Keep the element where layout put it (`pinned: true`); constraint
+    /// solving never moves it
+    pub pinned: bool,
+    /// Horizontal text placement (`text_align: left|center|right`)
+    pub text_align: Option<TextAnchor>,
+    /// Vertical text placement (`vertical_align: top|middle|bottom`)
+    pub vertical_align: Option<VerticalAlign>,
 }
 
 impl ResolvedStyles {
@@ -520,10 +709,30 @@ impl ResolvedStyles {
             stroke: Some("#333333".to_string()),
             stroke_width: Some(2.0),
             stroke_dasharray: None,
+            line_cap: None,
+            line_join: None,
+            marker_mid: None,
             opacity: Some(1.0),
             font_size: Some(14.0),
+            font_family: None,
             css_classes: vec![],
             rotation: None,
+            scale: None,
+            flip: None,
+            raw_svg: None,
+            detail: None,
+            corner_radius: None,
+            stroke_fade: None,
+            opacity_start: None,
+            opacity_end: None,
+            depth: None,
+            fill_gradient: None,
+            tooltip: None,
+            description: None,
+            initial: false,
+            pinned: false,
+            text_align: None,
+            vertical_align: None,
         }
     }
 
@@ -536,12 +745,50 @@ impl ResolvedStyles {
 
         for modifier in modifiers {
             match &modifier.node.key.node {
-                StyleKey::Fill => {
-                    styles.fill = Self::color_to_css(&modifier.node.value.node);
-                }
-                StyleKey::Stroke => {
-                    styles.stroke = Self::color_to_css(&modifier.node.value.node);
-                }
+                StyleKey::Fill => match &modifier.node.value.node {
+                    StyleValue::Function { name, args } if name == "gradient" => {
+                        let word = |value: &StyleValue| match value {
+                            StyleValue::Keyword(s) | StyleValue::String(s) => Some(s.clone()),
+                            StyleValue::Identifier(id) => Some(id.0.clone()),
+                            _ => None,
+                        };
+                        match args.as_slice() {
+                            [named] => {
+                                styles.fill_gradient = word(&named.node).map(FillGradient::Named);
+                            }
+                            [from, to, rest @ ..] if rest.len() <= 1 => {
+                                let from = Self::color_to_css(&from.node);
+                                let to = Self::color_to_css(&to.node);
+                                if let (Some(from), Some(to)) = (from, to) {
+                                    let direction = rest
+                                        .first()
+                                        .and_then(|d| word(&d.node))
+                                        .unwrap_or_else(|| "horizontal".to_string());
+                                    styles.fill = Some(from.clone());
+                                    styles.fill_gradient = Some(FillGradient::Linear {
+                                        from,
+                                        to,
+                                        direction,
+                                    });
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    value => styles.fill = Self::color_to_css(value),
+                },
+                StyleKey::Stroke => match &modifier.node.value.node {
+                    StyleValue::Function { name, args } if name == "fade" && args.len() == 2 => {
+                        let from = Self::color_to_css(&args[0].node);
+                        let to = Self::color_to_css(&args[1].node);
+                        if let (Some(from), Some(to)) = (from, to) {
+                            // Plain stroke fallback for anything that can't draw gradients
+                            styles.stroke = Some(from.clone());
+                            styles.stroke_fade = Some((from, to));
+                        }
+                    }
+                    value => styles.stroke = Self::color_to_css(value),
+                },
                 StyleKey::StrokeWidth => {
                     if let StyleValue::Number { value, .. } = &modifier.node.value.node {
                         styles.stroke_width = Some(*value);
@@ -585,6 +832,134 @@ impl ResolvedStyles {
                         styles.rotation = Some(*value);
                     }
                 }
+                StyleKey::Custom(key) if key == "scale" => {
+                    if let StyleValue::Number { value, .. } = &modifier.node.value.node {
+                        styles.scale = Some(*value);
+                    }
+                }
+                StyleKey::Custom(key) if key == "flip" => {
+                    let value = match &modifier.node.value.node {
+                        StyleValue::Keyword(k) | StyleValue::String(k) => k.as_str(),
+                        StyleValue::Identifier(id) => id.0.as_str(),
+                        _ => continue,
+                    };
+                    styles.flip = match value {
+                        "horizontal" => Some(Flip::Horizontal),
+                        "vertical" => Some(Flip::Vertical),
+                        "both" => Some(Flip::Both),
+                        _ => styles.flip,
+                    };
+                }
+                StyleKey::Custom(key) if key == "font_family" => match &modifier.node.value.node {
+                    StyleValue::Keyword(k) | StyleValue::String(k) => {
+                        styles.font_family = Some(k.clone());
+                    }
+                    StyleValue::Identifier(id) => styles.font_family = Some(id.0.clone()),
+                    _ => {}
+                },
+                StyleKey::Custom(key) if key == "detail" => match &modifier.node.value.node {
+                    StyleValue::Keyword(k) | StyleValue::String(k) => {
+                        styles.detail = Some(k.clone());
+                    }
+                    StyleValue::Identifier(id) => styles.detail = Some(id.0.clone()),
+                    _ => {}
+                },
+                StyleKey::Custom(key) if key == "opacity_start" || key == "opacity_end" => {
+                    if let StyleValue::Number { value, .. } = &modifier.node.value.node {
+                        if key == "opacity_start" {
+                            styles.opacity_start = Some(*value);
+                        } else {
+                            styles.opacity_end = Some(*value);
+                        }
+                    }
+                }
+                StyleKey::Custom(key) if key == "tooltip" || key == "description" => {
+                    if let StyleValue::String(text) = &modifier.node.value.node {
+                        if key == "tooltip" {
+                            styles.tooltip = Some(text.clone());
+                        } else {
+                            styles.description = Some(text.clone());
+                        }
+                    }
+                }
+                StyleKey::Custom(key) if key == "depth" => {
+                    if let StyleValue::Number { value, .. } = &modifier.node.value.node {
+                        styles.depth = Some(*value);
+                    }
+                }
+                StyleKey::Custom(key) if key == "corner_radius" => {
+                    if let StyleValue::Number { value, .. } = &modifier.node.value.node {
+                        styles.corner_radius = Some(*value);
+                    }
+                }
+                StyleKey::Custom(key)
+                    if key == "line_cap" || key == "line_join" || key == "marker_mid" =>
+                {
+                    let value = match &modifier.node.value.node {
+                        StyleValue::Keyword(k) | StyleValue::String(k) => k.as_str(),
+                        StyleValue::Identifier(id) => id.0.as_str(),
+                        _ => continue,
+                    };
+                    match (key.as_str(), value) {
+                        ("line_cap", "butt" | "round" | "square") => {
+                            styles.line_cap = Some(value.to_string());
+                        }
+                        ("line_join", "miter" | "round" | "bevel") => {
+                            styles.line_join = Some(value.to_string());
+                        }
+                        ("marker_mid", "dot" | "arrow" | "none") => {
+                            styles.marker_mid = Some(value.to_string());
+                        }
+                        _ => {}
+                    }
+                }
+                StyleKey::Custom(key) if key == "initial" => {
+                    styles.initial = match &modifier.node.value.node {
+                        StyleValue::Keyword(k) => k == "true",
+                        StyleValue::Identifier(id) => id.0 == "true",
+                        _ => false,
+                    };
+                }
+                StyleKey::Custom(key) if key == "pinned" => {
+                    styles.pinned = match &modifier.node.value.node {
+                        StyleValue::Keyword(k) => k == "true",
+                        StyleValue::Identifier(id) => id.0 == "true",
+                        _ => false,
+                    };
+                }
+                StyleKey::Custom(key) if key == "text_align" || key == "vertical_align" => {
+                    let value = match &modifier.node.value.node {
+                        StyleValue::Keyword(k) | StyleValue::String(k) => k.as_str(),
+                        StyleValue::Identifier(id) => id.0.as_str(),
+                        _ => continue,
+                    };
+                    match (key.as_str(), value) {
+                        ("text_align", "left" | "start") => {
+                            styles.text_align = Some(TextAnchor::Start);
+                        }
+                        ("text_align", "center" | "middle") => {
+                            styles.text_align = Some(TextAnchor::Middle);
+                        }
+                        ("text_align", "right" | "end") => {
+                            styles.text_align = Some(TextAnchor::End);
+                        }
+                        ("vertical_align", "top") => {
+                            styles.vertical_align = Some(VerticalAlign::Top);
+                        }
+                        ("vertical_align", "middle" | "center") => {
+                            styles.vertical_align = Some(VerticalAlign::Middle);
+                        }
+                        ("vertical_align", "bottom") => {
+                            styles.vertical_align = Some(VerticalAlign::Bottom);
+                        }
+                        _ => {}
+                    }
+                }
+                StyleKey::Custom(key) if key == "raw_svg" => {
+                    if let StyleValue::String(raw) = &modifier.node.value.node {
+                        styles.raw_svg = Some(raw.clone());
+                    }
+                }
                 StyleKey::Label
                 | StyleKey::LabelPosition
                 | StyleKey::Gap
@@ -642,24 +1017,75 @@ impl ResolvedStyles {
                 .stroke_dasharray
                 .clone()
                 .or_else(|| self.stroke_dasharray.clone()),
+            line_cap: other.line_cap.clone().or_else(|| self.line_cap.clone()),
+            line_join: other.line_join.clone().or_else(|| self.line_join.clone()),
+            marker_mid: other.marker_mid.clone().or_else(|| self.marker_mid.clone()),
             opacity: other.opacity.or(self.opacity),
             font_size: other.font_size.or(self.font_size),
+            font_family: other
+                .font_family
+                .clone()
+                .or_else(|| self.font_family.clone()),
             css_classes: {
                 let mut classes = self.css_classes.clone();
                 classes.extend(other.css_classes.clone());
                 classes
             },
             rotation: other.rotation.or(self.rotation),
+            scale: other.scale.or(self.scale),
+            flip: other.flip.or(self.flip),
+            raw_svg: other.raw_svg.clone().or_else(|| self.raw_svg.clone()),
+            detail: other.detail.clone().or_else(|| self.detail.clone()),
+            corner_radius: other.corner_radius.or(self.corner_radius),
+            stroke_fade: other
+                .stroke_fade
+                .clone()
+                .or_else(|| self.stroke_fade.clone()),
+            opacity_start: other.opacity_start.or(self.opacity_start),
+            opacity_end: other.opacity_end.or(self.opacity_end),
+            depth: other.depth.or(self.depth),
+            // A plain fill in `other` replaces an inherited gradient
+            fill_gradient: other.fill_gradient.clone().or_else(|| {
+                other
+                    .fill
+                    .is_none()
+                    .then(|| self.fill_gradient.clone())
+                    .flatten()
+            }),
+            tooltip: other.tooltip.clone().or_else(|| self.tooltip.clone()),
+            description: other
+                .description
+                .clone()
+                .or_else(|| self.description.clone()),
+            initial: other.initial || self.initial,
+            pinned: other.pinned || self.pinned,
+            text_align: other.text_align.or(self.text_align),
+            vertical_align: other.vertical_align.or(self.vertical_align),
         }
     }
 }
 
+/// Linear gradient fill of a shape or container background
+#[derive(Debug, Clone, PartialEq)]
+pub enum FillGradient {
+    /// Inline colors and direction (`horizontal`, `vertical` or `diagonal`)
+    Linear {
+        from: String,
+        to: String,
+        direction: String,
+    },
+    /// Gradient defined in the stylesheet's `[gradients]` table
+    Named(String),
+}
+
 /// Type of element in the layout
 #[derive(Debug, Clone, PartialEq)]
 pub enum ElementType {
     Shape(ShapeType),
     Layout(LayoutType),
     Group,
+    /// Named render layer (`layer name { ... }`)
+    Layer(String),
 }
 
 /// Text anchor position for labels
@@ -670,12 +1096,46 @@ pub enum TextAnchor {
     End,
 }
 
+/// Axis an element is mirrored across (`flip` modifier)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flip {
+    /// Left and right swap
+    Horizontal,
+    /// Top and bottom swap
+    Vertical,
+    /// Both, the same as a 180° rotation
+    Both,
+}
+
+/// Which side of its position point a line of text sits on vertically
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VerticalAlign {
+    /// Text hangs below the point
+    Top,
+    #[default]
+    Middle,
+    /// Text sits above the point
+    Bottom,
+}
+
+impl VerticalAlign {
+    /// SVG `dominant-baseline` value for this alignment
+    pub fn dominant_baseline(self) -> &'static str {
+        match self {
+            VerticalAlign::Top => "hanging",
+            VerticalAlign::Middle => "middle",
+            VerticalAlign::Bottom => "text-after-edge",
+        }
+    }
+}
+
 /// Layout information for a label
 #[derive(Debug, Clone)]
 pub struct LabelLayout {
     pub text: String,
     pub position: Point,
     pub anchor: TextAnchor,
+    pub vertical_align: VerticalAlign,
     /// Optional styles for the label (used when referencing a styled element)
     pub styles: Option<ResolvedStyles>,
 }
@@ -694,9 +1154,28 @@ pub struct ElementLayout {
     /// Whether to normalize path geometry to the element origin when rendering.
     /// Paths that have already been rotated in layout should skip normalization.
     pub path_normalize: bool,
-    /// Z-order for controlling render order (higher values render on top).
-    /// Only meaningful on root-level groups. Default is 0.
+    /// Z-order among siblings (`z: N`); higher values render on top.
+    /// Default is 0, and equal values keep declaration order.
     pub z_order: i32,
+    /// Space kept between a container's edge and its children when its
+    /// bounds are recomputed after constraint solving (zero for shapes)
+    pub padding: Padding,
+    /// Source range of the statement this element was laid out from
+    /// (`None` for elements synthesized during layout)
+    pub span: Option<Span>,
+    /// Width and height before `rotation`, `scale` and `flip` were applied.
+    /// When set, `bounds` is the transformed bounding box and the element is
+    /// drawn at this size around the center of `bounds`.
+    pub pre_transform_size: Option<(f64, f64)>,
+}
+
+/// Space between a container's edge and its children, per side
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Padding {
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+    pub left: f64,
 }
 
 impl ElementLayout {
@@ -704,6 +1183,18 @@ impl ElementLayout {
     pub fn id_str(&self) -> Option<&str> {
         self.id.as_ref().map(|id| id.0.as_str())
     }
+
+    /// The box the element is drawn in before its transform is applied
+    pub fn pre_transform_bounds(&self) -> Option<BoundingBox> {
+        let (width, height) = self.pre_transform_size?;
+        let center = self.bounds.center();
+        Some(BoundingBox::new(
+            center.x - width / 2.0,
+            center.y - height / 2.0,
+            width,
+            height,
+        ))
+    }
 }
 
 /// Layout information for a connection between elements
@@ -716,8 +1207,33 @@ pub struct ConnectionLayout {
     pub styles: ResolvedStyles,
     pub label: Option<LabelLayout>,
     pub routing_mode: RoutingMode, // Feature 008: track routing mode for rendering
+    /// Per-connection crossing style; `None` defers to `SvgConfig::crossings`
+    pub crossings: Option<CrossingStyle>,
     /// Optional name for referencing in keyframes (Feature 011)
     pub name: Option<Identifier>,
+    /// Numbered step marker (`seq: 1`), drawn at [`ConnectionLayout::seq_position`]
+    pub seq: Option<SeqMarker>,
+    /// Source range of the connection statement
+    pub span: Option<Span>,
+}
+
+/// Numbered circle marking a connection's place in a call flow
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeqMarker {
+    pub number: u32,
+}
+
+impl ConnectionLayout {
+    /// Where the step marker goes: halfway along the path
+    ///
+    /// Computed from the path as it is now, so markers follow bundling,
+    /// organic jitter and any hook that moves the connection.
+    pub fn seq_position(&self) -> Option<Point> {
+        if self.seq.is_none() || self.path.len() < 2 {
+            return None;
+        }
+        Some(super::routing::point_along_path(&self.path, 0.5).0)
+    }
 }
 
 /// The complete result of layout computation
@@ -787,6 +1303,21 @@ impl LayoutResult {
         self.elements.get(name)
     }
 
+    /// Computed bounds of a named element
+    pub fn bounds_of(&self, name: &str) -> Option<BoundingBox> {
+        self.elements.get(name).map(|e| e.bounds)
+    }
+
+    /// Anchor of a named element (`"top"`, `"right"`, a custom anchor, ...)
+    pub fn anchor_of(&self, name: &str, anchor: &str) -> Option<&Anchor> {
+        self.elements.get(name)?.anchors.get(anchor)
+    }
+
+    /// Routed path of the connection at `index`, in document order
+    pub fn route_of(&self, index: usize) -> Option<&[Point]> {
+        self.connections.get(index).map(|c| c.path.as_slice())
+    }
+
     /// Get mutable reference to element by name (for constraint resolution)
     pub fn get_element_mut_by_name(&mut self, name: &str) -> Option<&mut ElementLayout> {
         // First check root elements
@@ -973,6 +1504,8 @@ pub struct LocalSolverResult {
     pub pre_rotation_anchors: HashMap<String, AnchorSet>,
     /// Rotation center for this template instance (if rotated)
     pub rotation_center: Option<Point>,
+    /// Raw solver values, used to warm-start the global phase
+    pub solution: Solution,
 }
 
 impl LocalSolverResult {
@@ -986,6 +1519,7 @@ impl LocalSolverResult {
             pre_rotation_bounds: HashMap::new(),
             pre_rotation_anchors: HashMap::new(),
             rotation_center: None,
+            solution: Solution::default(),
         }
     }
 
@@ -1108,6 +1642,9 @@ mod tests {
             anchors: AnchorSet::default(),
             path_normalize: true,
             z_order: 0,
+            padding: Padding::default(),
+            span: None,
+            pre_transform_size: None,
         };
 
         result.add_element(element);
@@ -1245,6 +1782,57 @@ mod tests {
         assert_eq!(right.direction, AnchorDirection::Right);
     }
 
+    #[test]
+    fn test_radial_anchors() {
+        let bounds = BoundingBox::new(0.0, 0.0, 100.0, 50.0);
+        let mut anchors = AnchorSet::simple_shape(&bounds);
+        anchors.insert_radial(&bounds, 0.0);
+
+        let outward = anchors.get("outward").unwrap();
+        assert_eq!(outward.position, Point::new(100.0, 25.0));
+        assert_eq!(outward.direction, AnchorDirection::Right);
+        let inward = anchors.get("inward").unwrap();
+        assert_eq!(inward.position, Point::new(0.0, 25.0));
+        assert_eq!(inward.direction, AnchorDirection::Left);
+
+        // Moving the element keeps the angle and re-projects onto the new bounds
+        let moved = BoundingBox::new(200.0, 0.0, 100.0, 50.0);
+        anchors.update_builtin_from_bounds(&ElementType::Layout(LayoutType::Row), &moved);
+        assert_eq!(
+            anchors.get("outward").unwrap().position,
+            Point::new(300.0, 25.0)
+        );
+    }
+
+    #[test]
+    fn test_path_vertex_anchors() {
+        let doc = crate::parse(
+            "path plug { vertex a [x: 10, y: 5] line_to pin3 [x: 50, y: 5] line_to c [x: 50, y: 35] }",
+        )
+        .unwrap();
+        let decl = match &doc.statements[0].node {
+            crate::parser::ast::Statement::Shape(s) => match &s.shape_type.node {
+                ShapeType::Path(decl) => decl.clone(),
+                _ => panic!("Expected path"),
+            },
+            _ => panic!("Expected shape"),
+        };
+        // Normalized: the path's top-left vertex sits at the bounds origin
+        let bounds = BoundingBox::new(100.0, 100.0, 40.0, 30.0);
+        let element_type = ElementType::Shape(ShapeType::Path(decl));
+        let mut anchors = AnchorSet::for_element_type(&element_type, &bounds);
+        assert_eq!(anchors.len(), 11);
+        assert_eq!(anchors.get("a").unwrap().position, Point::new(100.0, 100.0));
+        let pin3 = anchors.get("pin3").unwrap();
+        assert_eq!(pin3.position, Point::new(140.0, 100.0));
+        assert!(matches!(pin3.direction, AnchorDirection::Angle(a) if a > 270.0));
+
+        // Vertex anchors follow the element when it moves
+        let moved = BoundingBox::new(0.0, 0.0, 40.0, 30.0);
+        anchors.update_builtin_from_bounds(&element_type, &moved);
+        assert_eq!(anchors.get("c").unwrap().position, Point::new(40.0, 30.0));
+    }
+
     #[test]
     fn test_path_shape_anchors() {
         let bounds = BoundingBox::new(0.0, 0.0, 100.0, 50.0);
@@ -1345,6 +1933,27 @@ mod tests {
         assert!(anchors.get("bottom_right").is_some());
     }
 
+    #[test]
+    fn test_ports_follow_bounds() {
+        let bounds = BoundingBox::new(0.0, 0.0, 100.0, 40.0);
+        let element_type = ElementType::Shape(ShapeType::Rectangle);
+        let mut anchors = AnchorSet::for_element_type(&element_type, &bounds);
+        let port = Port {
+            name: "in1".to_string(),
+            side: AnchorDirection::Up,
+            at: 0.25,
+            marker: false,
+        };
+        anchors.insert_port(port, &bounds);
+        assert_eq!(anchors.get("in1").unwrap().position, Point::new(25.0, 0.0));
+
+        let moved = BoundingBox::new(10.0, 20.0, 200.0, 40.0);
+        anchors.update_builtin_from_bounds(&element_type, &moved);
+        let in1 = anchors.get("in1").unwrap();
+        assert_eq!(in1.position, Point::new(60.0, 20.0));
+        assert_eq!(in1.direction, AnchorDirection::Up);
+    }
+
     #[test]
     fn test_update_builtin_from_bounds() {
         // Create anchors at initial position
@@ -1432,6 +2041,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::unnecessary_get_then_check)]
     fn test_local_solver_result_add_anchors() {
         let mut result = LocalSolverResult::new("alice");
         let bounds = BoundingBox::new(0.0, 0.0, 100.0, 50.0);
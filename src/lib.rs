@@ -11,19 +11,42 @@
 //! assert!(svg.contains("<svg"));
 //! ```
 
+mod color;
+pub mod diagnostic;
 pub mod error;
+mod format;
+pub mod hooks;
+pub mod ide;
+pub mod inventory;
 pub mod layout;
+mod minimal;
+mod outline;
 pub mod parser;
+pub mod patch;
+mod profile;
 pub mod renderer;
+mod scope;
 pub mod stylesheet;
 pub mod template;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
+pub use diagnostic::{parse_with_diagnostics, Diagnostic, Severity};
 pub use error::ParseError;
-pub use layout::{LayoutConfig, LayoutError, LayoutResult};
+pub use format::format;
+pub use hooks::PipelineHooks;
+pub use layout::{paginate, LayoutConfig, LayoutError, LayoutResult, Page, PageSpec};
 pub use parser::{parse, Document};
-pub use renderer::{render_svg, render_svg_with_keyframes, render_svg_with_stylesheet, SvgConfig};
-pub use template::{resolve_templates, TemplateError, TemplateRegistry};
-
+pub use patch::{apply_patch, PatchError, PatchOp};
+pub use renderer::{
+    render_svg, render_svg_page, render_svg_with_keyframes, render_svg_with_stylesheet, Fit,
+    ShapeRenderer, StyleMode, SvgConfig, TraversalOrder,
+};
+pub use template::{
+    resolve_templates, IconDirectory, IconResolver, TemplateError, TemplateRegistry,
+};
+
+use profile::PhaseTimer;
 use thiserror::Error;
 
 // Re-export Stylesheet for public API
@@ -43,6 +66,18 @@ pub enum ImageHrefMode {
     Base64,
 }
 
+/// How markup from embedded SVG templates is sanitized before inlining
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SvgEmbedPolicy {
+    /// Remove scripts, event handlers, and references to external resources
+    #[default]
+    Strict,
+    /// Remove scripts and event handlers, but keep external references
+    AllowExternal,
+    /// Embed the file unchanged (only for template libraries you control)
+    Trusted,
+}
+
 /// Errors that can occur during the render pipeline
 #[derive(Debug, Error)]
 pub enum RenderError {
@@ -88,6 +123,8 @@ pub struct RenderConfig {
     pub debug: bool,
     /// Trace mode: show internal constraint solver and routing debug output
     pub trace: bool,
+    /// Profile mode: print the time spent in each pipeline phase to stderr
+    pub profile: bool,
     /// Lint mode: check for layout defects
     pub lint: bool,
     /// Whether to resolve templates (default: true)
@@ -96,12 +133,28 @@ pub struct RenderConfig {
     pub template_base_path: Option<std::path::PathBuf>,
     /// How image href paths are emitted in SVG output
     pub image_href_mode: ImageHrefMode,
+    /// How embedded SVG template content is sanitized
+    pub svg_embed_policy: SvgEmbedPolicy,
+    /// How deep template instances may nest, which bounds recursive templates
+    pub max_template_depth: usize,
     /// Render a single keyframe as static SVG (by index or name)
     pub frame: Option<String>,
     /// Embed minimal JS for animated playback
     pub animate: bool,
     /// Use pure CSS animation (no JS, works in GitLab/GitHub READMEs)
     pub animate_css: bool,
+    /// Render the slide for this presentation step: everything outside
+    /// `step` blocks plus the steps numbered up to this one
+    pub step: Option<u32>,
+    /// Allow the `raw_svg` modifier to inject unescaped markup (default: false).
+    /// Only enable this for trusted input.
+    pub allow_raw_svg: bool,
+    /// Where `icon "name"` markup is looked up, in order
+    pub icon_resolvers: Vec<std::sync::Arc<dyn IconResolver>>,
+    /// Callbacks run between pipeline phases, in order
+    pub hooks: Vec<std::sync::Arc<dyn PipelineHooks>>,
+    /// Documents embedding this one (set when rendering `embed` targets)
+    pub(crate) embed_chain: Vec<std::path::PathBuf>,
 }
 
 impl Default for RenderConfig {
@@ -113,13 +166,21 @@ impl Default for RenderConfig {
             custom_css: None,
             debug: false,
             trace: false,
+            profile: false,
             lint: false,
             resolve_templates: true, // Templates are resolved by default
             template_base_path: None,
             image_href_mode: ImageHrefMode::default(),
+            svg_embed_policy: SvgEmbedPolicy::default(),
+            max_template_depth: template::DEFAULT_MAX_TEMPLATE_DEPTH,
             frame: None,
             animate: false,
             animate_css: false,
+            step: None,
+            allow_raw_svg: false,
+            icon_resolvers: vec![],
+            hooks: vec![],
+            embed_chain: vec![],
         }
     }
 }
@@ -136,6 +197,17 @@ impl RenderConfig {
         self
     }
 
+    /// The layout configuration with the trace flag and the stylesheet's
+    /// font metrics applied
+    fn effective_layout(&self) -> LayoutConfig {
+        let mut layout = self.layout.clone();
+        layout.trace = self.trace;
+        if let Some(scale) = self.stylesheet.fonts.width_scale {
+            layout.text_width_scale = scale;
+        }
+        layout
+    }
+
     /// Set the SVG configuration
     pub fn with_svg(mut self, config: SvgConfig) -> Self {
         self.svg = config;
@@ -166,6 +238,12 @@ impl RenderConfig {
         self
     }
 
+    /// Enable or disable profile mode (per-phase timings)
+    pub fn with_profile(mut self, profile: bool) -> Self {
+        self.profile = profile;
+        self
+    }
+
     /// Enable or disable lint mode
     pub fn with_lint(mut self, lint: bool) -> Self {
         self.lint = lint;
@@ -189,6 +267,59 @@ impl RenderConfig {
         self.image_href_mode = mode;
         self
     }
+
+    /// Set the sanitization policy for embedded SVG templates
+    pub fn with_svg_embed_policy(mut self, policy: SvgEmbedPolicy) -> Self {
+        self.svg_embed_policy = policy;
+        self
+    }
+
+    /// Set how deep template instances may nest
+    pub fn with_max_template_depth(mut self, depth: usize) -> Self {
+        self.max_template_depth = depth;
+        self
+    }
+
+    /// Allow or reject the `raw_svg` modifier
+    pub fn with_allow_raw_svg(mut self, allow: bool) -> Self {
+        self.allow_raw_svg = allow;
+        self
+    }
+
+    /// Look up icons in these directories (`icon "aws/ec2"` reads `<dir>/aws/ec2.svg`)
+    pub fn with_icon_paths(mut self, paths: Vec<std::path::PathBuf>) -> Self {
+        for path in paths {
+            self.icon_resolvers
+                .push(std::sync::Arc::new(IconDirectory::new(path)));
+        }
+        self
+    }
+
+    /// Add a custom icon source, consulted after those added before it
+    pub fn with_icon_resolver(mut self, resolver: impl IconResolver + 'static) -> Self {
+        self.icon_resolvers.push(std::sync::Arc::new(resolver));
+        self
+    }
+
+    /// Draw `name instance [...]` with `renderer`, an application-defined shape
+    ///
+    /// A template of the same name takes precedence.
+    pub fn register_shape(
+        mut self,
+        name: impl Into<String>,
+        renderer: impl ShapeRenderer + 'static,
+    ) -> Self {
+        self.svg
+            .shape_renderers
+            .insert(name.into(), std::sync::Arc::new(renderer));
+        self
+    }
+
+    /// Add pipeline hooks, run after those added before them
+    pub fn with_hooks(mut self, hooks: impl PipelineHooks + 'static) -> Self {
+        self.hooks.push(std::sync::Arc::new(hooks));
+        self
+    }
 }
 
 /// Render DSL source to SVG with default configuration
@@ -222,112 +353,454 @@ pub fn render(source: &str) -> Result<String, RenderError> {
 /// Returns an error if any symbolic color (like `foreground`, `accent-1`) is not
 /// defined in the stylesheet or default palette.
 fn validate_colors(doc: &Document, stylesheet: &Stylesheet) -> Result<(), RenderError> {
-    use parser::ast::{Statement, StyleValue};
+    use parser::ast::StyleValue;
+    use parser::{Spanned, Visitor};
 
     fn check_color(value: &StyleValue, stylesheet: &Stylesheet) -> Result<(), String> {
-        if let StyleValue::Color(color_value) = value {
-            if let Some(token) = color_value.token_string() {
-                stylesheet::validate_color_token(&token, stylesheet)?;
+        match value {
+            StyleValue::Color(color_value) => {
+                if let Some(token) = color_value.token_string() {
+                    stylesheet::validate_color_token(&token, stylesheet)?;
+                }
+            }
+            StyleValue::Function { name, args } => {
+                let word = |value: &StyleValue| match value {
+                    StyleValue::Keyword(s) | StyleValue::String(s) => Some(s.clone()),
+                    StyleValue::Identifier(id) => Some(id.0.clone()),
+                    _ => None,
+                };
+                match (name.as_str(), args.as_slice()) {
+                    ("fade", [_, _]) => {}
+                    ("fade", _) => {
+                        return Err("fade() takes two colors: fade(from, to)".to_string())
+                    }
+                    ("gradient", [named]) => {
+                        let name = word(&named.node).unwrap_or_default();
+                        if stylesheet.gradient(&name).is_none() {
+                            let mut known: Vec<_> = stylesheet.gradients.keys().cloned().collect();
+                            known.sort();
+                            return Err(format!(
+                                "Unknown gradient '{}'. Defined gradients: {}",
+                                name,
+                                if known.is_empty() {
+                                    "(none)".to_string()
+                                } else {
+                                    known.join(", ")
+                                }
+                            ));
+                        }
+                        return Ok(());
+                    }
+                    ("gradient", [_, _]) => {}
+                    ("gradient", [_, _, direction]) => {
+                        let direction = word(&direction.node).unwrap_or_default();
+                        if !matches!(direction.as_str(), "horizontal" | "vertical" | "diagonal") {
+                            return Err(format!(
+                                "Unknown gradient direction '{}'. Expected horizontal, vertical or diagonal",
+                                direction
+                            ));
+                        }
+                        check_color(&args[0].node, stylesheet)?;
+                        return check_color(&args[1].node, stylesheet);
+                    }
+                    ("gradient", _) => {
+                        return Err(
+                            "gradient() takes gradient(from, to[, direction]) or gradient(name)"
+                                .to_string(),
+                        )
+                    }
+                    _ => {
+                        return Err(format!(
+                            "Unknown style function '{}'. Supported: fade, gradient, darken, lighten, alpha",
+                            name
+                        ))
+                    }
+                }
+                for arg in args {
+                    check_color(&arg.node, stylesheet)?;
+                }
             }
+            _ => {}
         }
         Ok(())
     }
 
-    fn validate_modifiers(
-        modifiers: &[parser::Spanned<parser::ast::StyleModifier>],
-        stylesheet: &Stylesheet,
-    ) -> Result<(), String> {
-        for modifier in modifiers {
-            check_color(&modifier.node.value.node, stylesheet)?;
+    struct Validator<'a> {
+        stylesheet: &'a Stylesheet,
+        error: Option<String>,
+    }
+
+    impl Visitor for Validator<'_> {
+        fn visit_value(&mut self, value: &Spanned<StyleValue>) {
+            // check_color descends into function arguments itself
+            if self.error.is_none() {
+                self.error = check_color(&value.node, self.stylesheet).err();
+            }
         }
-        Ok(())
     }
 
-    fn validate_statement(stmt: &Statement, stylesheet: &Stylesheet) -> Result<(), String> {
-        match stmt {
-            Statement::Shape(s) => validate_modifiers(&s.modifiers, stylesheet)?,
-            Statement::Layout(l) => {
-                validate_modifiers(&l.modifiers, stylesheet)?;
-                for child in &l.children {
-                    validate_statement(&child.node, stylesheet)?;
-                }
+    let mut validator = Validator {
+        stylesheet,
+        error: None,
+    };
+    validator.visit_document(doc);
+    match validator.error {
+        Some(e) => Err(RenderError::Layout(layout::LayoutError::validation_error(
+            e,
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Replace color function calls (`darken(accent, 20%)`, `lighten`, `alpha`)
+/// with the concrete colors they compute from the stylesheet palette
+fn resolve_color_functions(doc: &mut Document, stylesheet: &Stylesheet) -> Result<(), RenderError> {
+    use parser::ast::{ColorValue, StyleValue};
+    use parser::visit::{walk_value_mut, VisitorMut};
+    use parser::Spanned;
+
+    struct Resolver<'a> {
+        stylesheet: &'a Stylesheet,
+        error: Option<String>,
+    }
+
+    impl VisitorMut for Resolver<'_> {
+        fn visit_value(&mut self, value: &mut Spanned<StyleValue>) {
+            // Arguments first, so nested calls are concrete colors
+            walk_value_mut(self, value);
+            if self.error.is_some() {
+                return;
             }
-            Statement::Group(g) => {
-                validate_modifiers(&g.modifiers, stylesheet)?;
-                for child in &g.children {
-                    validate_statement(&child.node, stylesheet)?;
+            if let StyleValue::Function { name, args } = &value.node {
+                match color::evaluate(name, args, self.stylesheet) {
+                    Ok(Some(hex)) => value.node = StyleValue::Color(ColorValue::Hex(hex)),
+                    Ok(None) => {}
+                    Err(e) => self.error = Some(e),
                 }
             }
-            Statement::Connection(connections) => {
-                for conn in connections {
-                    validate_modifiers(&conn.modifiers, stylesheet)?;
+        }
+    }
+
+    let mut resolver = Resolver {
+        stylesheet,
+        error: None,
+    };
+    resolver.visit_document(doc);
+    match resolver.error {
+        Some(e) => Err(RenderError::Layout(layout::LayoutError::validation_error(
+            e,
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Fill in the output size from the document's `canvas [...]` statement
+///
+/// Settings already on the SVG config (e.g. from the command line) win: a
+/// configured width, height or scale replaces the canvas size as a whole.
+/// A canvas measured in physical units (`width: 210mm`) is written in them.
+fn apply_canvas(doc: &Document, svg: &mut SvgConfig) -> Result<(), RenderError> {
+    use parser::ast::{Statement, StyleKey, StyleValue, Unit};
+
+    let invalid =
+        |message: String| RenderError::Layout(layout::LayoutError::validation_error(message));
+    let mut canvas = SvgConfig::default();
+    let mut size_unit = None;
+    for stmt in &doc.statements {
+        let Statement::Canvas(decl) = &stmt.node else {
+            continue;
+        };
+        for modifier in &decl.modifiers {
+            let key = &modifier.node.key.node;
+            let value = &modifier.node.value.node;
+            let (number, unit) = match value {
+                StyleValue::Number { value, unit } if *value > 0.0 => {
+                    (Some(*value), unit.as_deref().and_then(Unit::parse))
                 }
-            }
-            Statement::TemplateDecl(t) => {
-                if let Some(body) = &t.body {
-                    for child in body {
-                        validate_statement(&child.node, stylesheet)?;
+                _ => (None, None),
+            };
+            let size = || {
+                number
+                    .map(|n| n * unit.map_or(1.0, Unit::px))
+                    .ok_or_else(|| {
+                        invalid(format!(
+                            "canvas {} must be a positive number",
+                            parser::key_text(key)
+                        ))
+                    })
+            };
+            let name = match value {
+                StyleValue::Identifier(id) => id.0.as_str(),
+                StyleValue::Keyword(s) | StyleValue::String(s) => s.as_str(),
+                _ => "",
+            };
+            match key {
+                StyleKey::Width | StyleKey::Height => {
+                    let px = Some(size()?);
+                    if key == &StyleKey::Width {
+                        canvas.width = px;
+                    } else {
+                        canvas.height = px;
+                    }
+                    size_unit = size_unit.or(unit.filter(|u| *u != Unit::Px));
+                }
+                StyleKey::Custom(k) if k == "scale" => canvas.scale = Some(size()?),
+                StyleKey::Custom(k) if k == "fit" => {
+                    let fit = Fit::parse(name).ok_or_else(|| {
+                        invalid("canvas fit must be 'contain' or 'cover'".to_string())
+                    })?;
+                    canvas.fit = Some(fit);
+                }
+                StyleKey::Custom(k) if k == "unit" => {
+                    let unit = Unit::parse(name).ok_or_else(|| {
+                        invalid("canvas unit must be one of px, mm, cm, in, pt".to_string())
+                    })?;
+                    canvas.unit = Some(unit);
+                }
+                StyleKey::Custom(k) if k == "background" => {
+                    let color = layout::ResolvedStyles::color_to_css(value).ok_or_else(|| {
+                        invalid("canvas background must be a color".to_string())
+                    })?;
+                    canvas.background = Some(color);
+                }
+                // Applies to layout, so it is read before layout by `canvas_organic`
+                StyleKey::Custom(k) if k == "organic" => {}
+                StyleKey::Custom(k) if k.starts_with("margin") => {
+                    let px = match value {
+                        StyleValue::Number { value, unit } if *value >= 0.0 => {
+                            value * unit.as_deref().and_then(Unit::parse).map_or(1.0, Unit::px)
+                        }
+                        _ => {
+                            return Err(invalid(format!(
+                                "canvas {} must be a non-negative number",
+                                k
+                            )))
+                        }
+                    };
+                    let margin = canvas.margin.get_or_insert(svg.margins());
+                    match k.as_str() {
+                        "margin" => {
+                            *margin = layout::Padding {
+                                top: px,
+                                right: px,
+                                bottom: px,
+                                left: px,
+                            }
+                        }
+                        "margin_top" => margin.top = px,
+                        "margin_right" => margin.right = px,
+                        "margin_bottom" => margin.bottom = px,
+                        "margin_left" => margin.left = px,
+                        _ => {
+                            return Err(invalid(format!(
+                                "unknown canvas setting '{}' (expected margin or margin_top|right|bottom|left)",
+                                k
+                            )))
+                        }
                     }
                 }
+                _ => {
+                    return Err(invalid(format!(
+                        "unknown canvas setting '{}' (expected width, height, scale, fit, unit, background, margin or organic)",
+                        parser::key_text(key)
+                    )))
+                }
             }
-            _ => {}
         }
-        Ok(())
     }
 
+    if svg.width.is_none() && svg.height.is_none() && svg.scale.is_none() {
+        svg.width = canvas.width;
+        svg.height = canvas.height;
+        svg.scale = canvas.scale;
+        svg.unit = svg.unit.or(size_unit);
+    }
+    svg.fit = svg.fit.or(canvas.fit);
+    svg.unit = svg.unit.or(canvas.unit);
+    svg.margin = svg.margin.or(canvas.margin);
+    svg.background = svg.background.take().or(canvas.background);
+    Ok(())
+}
+
+/// Organic jitter amount from the document's `canvas [organic: N]` (0 when unset)
+fn canvas_organic(doc: &Document) -> Result<f64, RenderError> {
+    use parser::ast::{Statement, StyleKey, StyleValue};
+
+    let mut amount = 0.0;
     for stmt in &doc.statements {
-        validate_statement(&stmt.node, stylesheet)
-            .map_err(|e| RenderError::Layout(layout::LayoutError::validation_error(e)))?;
+        let Statement::Canvas(decl) = &stmt.node else {
+            continue;
+        };
+        for modifier in &decl.modifiers {
+            if !matches!(&modifier.node.key.node, StyleKey::Custom(k) if k == "organic") {
+                continue;
+            }
+            amount = match &modifier.node.value.node {
+                StyleValue::Number { value, unit: None } if *value >= 0.0 => *value,
+                _ => {
+                    return Err(RenderError::Layout(layout::LayoutError::validation_error(
+                        "canvas organic must be a non-negative number",
+                    )))
+                }
+            };
+        }
+    }
+    Ok(amount)
+}
+
+/// Convert numbers with a unit (`20mm`) to layout pixels, so layout and
+/// rendering only ever see plain numbers
+///
+/// The canvas is left alone: its units choose the SVG output unit.
+fn convert_units(doc: &mut Document) {
+    use parser::ast::{Statement, StyleModifier, StyleValue, Unit};
+    use parser::visit::{walk_statement_mut, VisitorMut};
+    use parser::Spanned;
+
+    struct Units;
+
+    impl VisitorMut for Units {
+        fn visit_statement(&mut self, stmt: &mut Statement) {
+            // Animation durations carry time units (`2s`)
+            if !matches!(stmt, Statement::Canvas(_) | Statement::Animate(_)) {
+                walk_statement_mut(self, stmt);
+            }
+        }
+
+        fn visit_modifier(&mut self, modifier: &mut Spanned<StyleModifier>) {
+            if let StyleValue::Number { value, unit } = &mut modifier.node.value.node {
+                if let Some(u) = unit.take() {
+                    *value *= Unit::parse(&u).map_or(1.0, Unit::px);
+                }
+            }
+        }
+    }
+
+    Units.visit_document(doc);
+}
+
+/// Reject `raw_svg` modifiers unless the caller opted in.
+///
+/// All other user text is escaped by the renderer; `raw_svg` is the one way to
+/// emit markup verbatim, so it must never be reachable from untrusted input by
+/// default.
+fn validate_raw_svg(doc: &Document, allow: bool) -> Result<(), RenderError> {
+    use parser::ast::{StyleKey, StyleModifier};
+    use parser::{Spanned, Visitor};
+
+    if allow {
+        return Ok(());
+    }
+
+    struct RawSvg(bool);
+
+    impl Visitor for RawSvg {
+        fn visit_modifier(&mut self, modifier: &Spanned<StyleModifier>) {
+            self.0 |= matches!(&modifier.node.key.node, StyleKey::Custom(k) if k == "raw_svg");
+        }
+    }
+
+    let mut raw_svg = RawSvg(false);
+    raw_svg.visit_document(doc);
+    if raw_svg.0 {
+        return Err(RenderError::Layout(layout::LayoutError::validation_error(
+            "raw_svg is disabled; enable RenderConfig::allow_raw_svg (--allow-raw-svg) for trusted input",
+        )));
     }
 
     Ok(())
 }
 
-/// Extract rotation modifiers from template instances in a document.
+/// Extract transform modifiers (`rotation`, `scale`, `flip`) from a document.
 ///
-/// Scans all statements (including nested ones) for template instances with
-/// a `rotation` modifier and builds a map from instance name to rotation angle.
-fn extract_template_rotations(doc: &Document) -> std::collections::HashMap<String, f64> {
-    use parser::ast::{Statement, StyleValue};
-    let mut rotations = std::collections::HashMap::new();
-
-    fn visit_statements(
-        stmts: &[parser::ast::Spanned<Statement>],
-        rotations: &mut std::collections::HashMap<String, f64>,
-    ) {
-        for stmt in stmts {
-            match &stmt.node {
+/// Scans all statements (including nested ones) for template instances and
+/// named shapes, groups and layouts that rotate, scale or flip, and builds a
+/// map from element name to its transform.
+fn extract_transforms(
+    doc: &Document,
+) -> std::collections::HashMap<String, layout::ElementTransform> {
+    use layout::{ElementTransform, Flip, ResolvedStyles};
+    use parser::ast::{Identifier, ShapeType, Statement, StyleModifier, StyleValue};
+    use parser::visit::walk_statement;
+    use parser::{Spanned, Visitor};
+
+    #[derive(Default)]
+    struct Transforms(std::collections::HashMap<String, ElementTransform>);
+
+    impl Transforms {
+        fn insert(
+            &mut self,
+            name: Option<&Spanned<Identifier>>,
+            modifiers: &[Spanned<StyleModifier>],
+        ) {
+            let transform =
+                ElementTransform::from_styles(&ResolvedStyles::from_modifiers(modifiers));
+            if let Some(name) = name.filter(|_| !transform.is_identity()) {
+                self.0.insert(name.node.0.clone(), transform);
+            }
+        }
+    }
+
+    impl Visitor for Transforms {
+        fn visit_statement(&mut self, stmt: &Statement) {
+            match stmt {
                 Statement::TemplateInstance(inst) => {
-                    // Check for rotation modifier
+                    // Check for transform arguments
+                    let mut transform = ElementTransform::default();
                     for (key, value) in &inst.arguments {
-                        if key.node.0 == "rotation" {
-                            if let StyleValue::Number { value: angle, .. } = &value.node {
-                                rotations.insert(inst.instance_name.node.0.clone(), *angle);
+                        match (key.node.0.as_str(), &value.node) {
+                            ("rotation", StyleValue::Number { value, .. }) => {
+                                transform.rotation = *value;
                             }
+                            ("scale", StyleValue::Number { value, .. }) => {
+                                transform.scale = *value;
+                            }
+                            ("flip", StyleValue::Keyword(k) | StyleValue::String(k))
+                            | ("flip", StyleValue::Identifier(Identifier(k))) => {
+                                transform.flip = match k.as_str() {
+                                    "horizontal" => Some(Flip::Horizontal),
+                                    "vertical" => Some(Flip::Vertical),
+                                    "both" => Some(Flip::Both),
+                                    _ => transform.flip,
+                                };
+                            }
+                            _ => {}
                         }
                     }
+                    if inst
+                        .arguments
+                        .iter()
+                        .any(|(key, _)| key.node.0 == "rotation")
+                        || !transform.is_identity()
+                    {
+                        self.0.insert(inst.instance_name.node.0.clone(), transform);
+                    }
+                }
+                Statement::Shape(s) => {
+                    let name = s.name.as_ref().or(match &s.shape_type.node {
+                        ShapeType::Path(path) => path.name.as_ref(),
+                        _ => None,
+                    });
+                    self.insert(name, &s.modifiers);
                 }
                 Statement::Layout(l) => {
-                    visit_statements(&l.children, rotations);
+                    self.insert(l.name.as_ref(), &l.modifiers);
+                    walk_statement(self, stmt);
                 }
                 Statement::Group(g) => {
-                    visit_statements(&g.children, rotations);
+                    self.insert(g.name.as_ref(), &g.modifiers);
+                    walk_statement(self, stmt);
                 }
-                Statement::Label(inner) => {
-                    // Labels contain a single inner statement
-                    let inner_spanned = parser::ast::Spanned {
-                        node: (**inner).clone(),
-                        span: stmt.span.clone(),
-                    };
-                    visit_statements(&[inner_spanned], rotations);
-                }
-                _ => {}
+                // Template bodies are transformed through their instances
+                Statement::TemplateDecl(_) => {}
+                _ => walk_statement(self, stmt),
             }
         }
     }
 
-    visit_statements(&doc.statements, &mut rotations);
-    rotations
+    let mut transforms = Transforms::default();
+    transforms.visit_document(doc);
+    transforms.0
 }
 
 /// Render DSL source to SVG with custom configuration
@@ -359,72 +832,377 @@ pub fn render_with_lint(
     render_pipeline(source, config)
 }
 
-/// Internal shared render pipeline.
-fn render_pipeline(
-    source: &str,
-    config: RenderConfig,
-) -> Result<(String, Vec<layout::lint::LintWarning>), RenderError> {
-    // Parse the source
-    let doc = parse(source)?;
+/// Render each presentation step as its own SVG, in step order
+///
+/// The steps share one layout, so elements keep their place from slide to
+/// slide while later `step N { ... }` blocks are revealed.
+///
+/// ```rust
+/// use agent_illustrator::{render_steps, RenderConfig};
+///
+/// let source = "rect a\nstep 1 { rect b }\nstep 2 { a -> b }";
+/// let slides = render_steps(source, RenderConfig::default()).unwrap();
+/// assert_eq!(slides.len(), 2);
+/// assert!(slides[0].contains(r#"id="b""#) && !slides[0].contains("ai-connection"));
+/// assert!(slides[1].contains("ai-connection"));
+/// ```
+pub fn render_steps(source: &str, config: RenderConfig) -> Result<Vec<String>, RenderError> {
+    let mut numbers: Vec<u32> = layout::keyframe::step_ranges(&parse(source)?)
+        .into_iter()
+        .map(|(number, _)| number)
+        .collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+    if numbers.is_empty() {
+        return Err(RenderError::Layout(layout::LayoutError::validation_error(
+            "--steps requires step blocks in the input",
+        )));
+    }
+    numbers
+        .into_iter()
+        .map(|number| {
+            let mut config = config.clone();
+            config.step = Some(number);
+            render_with_config(source, config)
+        })
+        .collect()
+}
 
-    // Extract rotation modifiers from template instances BEFORE resolution
-    // (template instances are converted to groups during resolution, losing their modifiers)
-    let template_rotations = extract_template_rotations(&doc);
+/// How many failing statements [`render_graceful`] replaces before giving up
+const MAX_RECOVERED_ERRORS: usize = 16;
 
-    // Resolve templates if enabled
-    let doc = if config.resolve_templates {
-        let mut registry = if let Some(base) = &config.template_base_path {
-            TemplateRegistry::with_base_path(base.clone())
-        } else {
-            TemplateRegistry::new()
+/// Render DSL source to SVG, replacing failing statements with error placeholders.
+///
+/// Each statement the parser had to skip is swapped for a red box carrying
+/// the error text (keeping the element's name, so connections to it still
+/// resolve), and so is each statement that later fails to resolve. A
+/// statement whose box does not make its error go away is dropped, with the
+/// box added at the end instead, so the rest of the document still renders.
+/// Errors without a location are shown as boxes on their own. Always returns
+/// an SVG; the errors are returned alongside it.
+///
+/// ```rust
+/// use agent_illustrator::{render_graceful, RenderConfig};
+///
+/// let (svg, errors) = render_graceful("rect a\na -> missing", RenderConfig::default());
+/// assert_eq!(errors.len(), 1);
+/// assert!(svg.contains("undefined identifier"));
+/// ```
+pub fn render_graceful(source: &str, config: RenderConfig) -> (String, Vec<RenderError>) {
+    let (mut patched, mut errors) = patch_syntax_errors(source);
+    // Boxes for statements that were dropped rather than replaced
+    let mut dropped = vec![];
+    // The last error boxed, and the line it was boxed on
+    let mut previous: Option<(String, usize)> = None;
+    let mut dropped_last = false;
+
+    while errors.len() < MAX_RECOVERED_ERRORS {
+        let document = [patched.as_str()]
+            .into_iter()
+            .chain(dropped.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let err = match render_pipeline(&document, config.clone()) {
+            Ok((svg, _)) => return (svg, errors),
+            Err(err) => err,
+        };
+        let Some(span) = error_span(&err).filter(|s| s.start <= patched.len()) else {
+            errors.push(err);
+            break;
         };
-        registry.set_image_href_mode(config.image_href_mode);
-        resolve_templates(doc, &mut registry)?
+        let (start, end) = (
+            line_start(&patched, span.start),
+            line_end(&patched, span.start),
+        );
+        let message = err.to_string();
+        if previous.as_ref() == Some(&(message.clone(), start)) {
+            if dropped_last {
+                break;
+            }
+            // The box that kept the element's name brought the error back:
+            // drop the statement and show its box at the end instead
+            let index = errors.len() - 1;
+            dropped.push(error_placeholder("", index, &errors[index]));
+            let braces = balancing_braces(&patched[start..end]);
+            patched.replace_range(start..end, braces.trim_start());
+            dropped_last = true;
+            continue;
+        }
+        dropped_last = false;
+        let placeholder = error_placeholder(&patched[start..end], errors.len(), &err);
+        patched.replace_range(start..end, &placeholder);
+        previous = Some((message, start));
+        errors.push(err);
+    }
+
+    // Nothing usable is left: show the errors by themselves
+    let boxes: Vec<String> = errors
+        .iter()
+        .enumerate()
+        .map(|(i, err)| error_placeholder("", i, err))
+        .collect();
+    let fallback = format!("col {{\n{}\n}}", boxes.join("\n"));
+    let svg = render_with_config(&fallback, config).unwrap_or_default();
+    (svg, errors)
+}
+
+/// Replace every statement the parser skipped with an error box
+///
+/// The parser recovers at statement boundaries and reports every syntax
+/// error at once. An error is often reported on the line after the broken
+/// statement (an unclosed `[` is only noticed at the next statement), so
+/// the boxes go where the parser skipped source, each carrying the first
+/// error at or after it.
+fn patch_syntax_errors(source: &str) -> (String, Vec<RenderError>) {
+    let (_, parse_errors, skipped) = parser::parse_skipping(source);
+    let span_of = |e: &ParseError| match e {
+        ParseError::Syntax { span, .. } => span.clone(),
+    };
+    // Without a document to recover into (an unmatched `}`), the error lines
+    let regions: Vec<parser::ast::Span> = if skipped.is_empty() {
+        parse_errors.iter().map(span_of).collect()
+    } else {
+        skipped
+    };
+
+    let mut patches: Vec<(usize, usize, usize)> = vec![];
+    for region in regions.iter().filter(|r| r.start <= source.len()) {
+        let start = line_start(source, region.start);
+        let end = line_end(source, region.end.max(region.start));
+        if patches
+            .iter()
+            .any(|&(s, e, _)| start < e && s < end.max(start + 1))
+        {
+            continue;
+        }
+        let index = parse_errors
+            .iter()
+            .position(|e| span_of(e).start >= region.start)
+            .unwrap_or(parse_errors.len().saturating_sub(1));
+        patches.push((start, end, index));
+    }
+
+    let errors: Vec<RenderError> = parse_errors
+        .into_iter()
+        .map(|e| RenderError::Parse(vec![e]))
+        .collect();
+    let mut patched = source.to_string();
+    patches.sort_by_key(|&(start, _, _)| std::cmp::Reverse(start));
+    for (start, end, index) in patches {
+        if let Some(err) = errors.get(index) {
+            let placeholder = error_placeholder(&patched[start..end], index, err);
+            patched.replace_range(start..end, &placeholder);
+        }
+    }
+    (patched, errors)
+}
+
+/// Offset of the start of the line holding `pos`
+fn line_start(source: &str, pos: usize) -> usize {
+    source[..pos].rfind('\n').map_or(0, |i| i + 1)
+}
+
+/// Offset of the end of the line holding `pos`, before its newline
+fn line_end(source: &str, pos: usize) -> usize {
+    source[pos..].find('\n').map_or(source.len(), |i| pos + i)
+}
+
+/// Source location an error points at, if any
+pub(crate) fn error_span(err: &RenderError) -> Option<parser::ast::Span> {
+    match err {
+        RenderError::Parse(errors) => errors.first().map(|e| match e {
+            ParseError::Syntax { span, .. } => span.clone(),
+        }),
+        RenderError::Layout(e) => e.span().cloned(),
+        RenderError::Template(e) => e.span().cloned(),
+    }
+}
+
+/// A red box statement standing in for a failing source line
+fn error_placeholder(line: &str, index: usize, err: &RenderError) -> String {
+    const NAMED: &[&str] = &[
+        "rect", "circle", "ellipse", "line", "polygon", "row", "col", "grid", "stack", "flow",
+        "ring", "group", "layer",
+    ];
+    let mut words = line.split_whitespace();
+    let name = match (words.next(), words.next()) {
+        (Some(kw), Some(name))
+            if NAMED.contains(&kw)
+                && name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') =>
+        {
+            name.to_string()
+        }
+        _ => format!("render_error_{}", index + 1),
+    };
+
+    let message = match err {
+        RenderError::Parse(errors) => errors
+            .iter()
+            .map(|ParseError::Syntax { message, .. }| message.clone())
+            .collect::<Vec<_>>()
+            .join("; "),
+        RenderError::Layout(e) => e.to_string(),
+        RenderError::Template(e) => e.to_string(),
+    };
+    let mut message: String = message
+        .chars()
+        .map(|c| if c == '"' || c == '\\' { '\'' } else { c })
+        .collect();
+    if message.chars().count() > 60 {
+        message = message.chars().take(57).collect::<String>() + "...";
+    }
+
+    format!(
+        "rect {} [fill: #fdecea, stroke: #d32f2f, stroke_width: 2, label: \"{}\"]{}",
+        name,
+        message,
+        balancing_braces(line)
+    )
+}
+
+/// What keeps a replaced line's braces balanced, so the surrounding block
+/// still parses
+fn balancing_braces(line: &str) -> String {
+    let depth = line.matches('{').count() as i64 - line.matches('}').count() as i64;
+    if depth > 0 {
+        " group {".repeat(depth as usize)
+    } else {
+        " }".repeat(depth.unsigned_abs() as usize)
+    }
+}
+
+/// List the named elements of a document as an inventory table
+///
+/// Templates are resolved first, so instances show up with their expanded
+/// contents. Format the entries with [`inventory::to_markdown`] or
+/// [`inventory::to_csv`].
+pub fn inventory(
+    source: &str,
+    config: &RenderConfig,
+) -> Result<Vec<inventory::InventoryEntry>, RenderError> {
+    let mut doc = parse(source)?;
+    layout::keyframe::flatten_steps(&mut doc)?;
+    scope::resolve_scopes(&mut doc)?;
+    let doc = resolve_document(doc, config)?;
+    Ok(inventory::collect(&doc))
+}
+
+/// Resolve templates if enabled
+///
+/// Variables, repeats and conditionals are expanded either way, since layout
+/// cannot place them.
+fn resolve_document(doc: Document, config: &RenderConfig) -> Result<Document, RenderError> {
+    if !config.resolve_templates {
+        return Ok(template::expand_document(doc)?);
+    }
+    let mut registry = if let Some(base) = &config.template_base_path {
+        TemplateRegistry::with_base_path(base.clone())
     } else {
-        doc
+        TemplateRegistry::new()
     };
+    registry.set_image_href_mode(config.image_href_mode);
+    registry.set_svg_embed_policy(config.svg_embed_policy);
+    registry.set_max_depth(config.max_template_depth);
+    registry.set_embed_chain(config.embed_chain.clone());
+    registry.set_stylesheet(config.stylesheet.clone());
+    registry.set_icon_resolvers(config.icon_resolvers.clone());
+    registry.set_shape_renderers(config.svg.shape_renderers.clone());
+    Ok(resolve_templates(doc, &mut registry)?)
+}
+
+/// Parse, resolve and lay out DSL source without rendering it
+///
+/// The result holds the final bounds of every element and the routed path of
+/// every connection, for tools that need geometry rather than SVG.
+///
+/// ```rust
+/// use agent_illustrator::{compute_layout, RenderConfig};
+///
+/// let result = compute_layout("row { rect a rect b }", &RenderConfig::default()).unwrap();
+/// assert!(result.elements["b"].bounds.x > result.elements["a"].bounds.x);
+/// ```
+pub fn compute_layout(source: &str, config: &RenderConfig) -> Result<LayoutResult, RenderError> {
+    Ok(layout_pipeline(source, config, &mut PhaseTimer::new(false))?.1)
+}
+
+/// Shared front half of the pipeline: everything up to routed connections
+fn layout_pipeline(
+    source: &str,
+    config: &RenderConfig,
+    timer: &mut PhaseTimer,
+) -> Result<(Document, LayoutResult), RenderError> {
+    // Parse the source
+    let mut doc = parse(source)?;
+    layout::keyframe::flatten_steps(&mut doc)?;
+    for hooks in &config.hooks {
+        hooks.after_parse(&mut doc)?;
+    }
+    // Give elements declared in several groups unique ids
+    scope::resolve_scopes(&mut doc)?;
+    timer.lap("parse");
+
+    // Extract transform modifiers BEFORE resolution
+    // (template instances are converted to groups during resolution, losing their modifiers)
+    let transforms = extract_transforms(&doc);
+
+    let mut doc = resolve_document(doc, config)?;
+    for hooks in &config.hooks {
+        hooks.after_resolve(&mut doc)?;
+    }
+
+    // Expand named connection styles (`style: "async-call"`) from the stylesheet
+    stylesheet::apply_connection_styles(&mut doc, &config.stylesheet)
+        .map_err(|e| RenderError::Layout(layout::LayoutError::validation_error(e)))?;
+
+    // Add the stylesheet's per-class style rules and per-type defaults
+    stylesheet::apply_style_rules(&mut doc, &config.stylesheet)
+        .map_err(|e| RenderError::Layout(layout::LayoutError::validation_error(e)))?;
+
+    // Lengths such as `20mm` become layout pixels
+    convert_units(&mut doc);
 
     // Validate color references against stylesheet
+    resolve_color_functions(&mut doc, &config.stylesheet)?;
     validate_colors(&doc, &config.stylesheet)?;
 
-    // Create layout config with trace flag propagated
-    let mut layout_config = config.layout.clone();
-    layout_config.trace = config.trace;
+    // Reject unescaped markup unless explicitly allowed
+    validate_raw_svg(&doc, config.allow_raw_svg)?;
+    timer.lap("resolve");
+
+    let layout_config = config.effective_layout();
 
     // Compute layout
     let mut result = layout::compute(&doc, &layout_config)?;
 
+    // Organic jitter goes in before solving so constraints still hold exactly
+    let organic = match layout_config.organic {
+        Some(amount) => amount,
+        None => canvas_organic(&doc)?,
+    };
+    let organic_seed = layout_config
+        .organic_seed
+        .unwrap_or_else(|| layout::organic::seed_from_source(source));
+    layout::organic::jitter_elements(&mut result, organic, organic_seed);
+    timer.lap("layout");
+
     // Resolve constrain statements first (constraint-solver based positioning)
     // This must run before place statements so that offsets are applied after alignment
-    // Use two-phase solver when there are rotations, otherwise use single-phase
-    if template_rotations.is_empty() {
+    // Use two-phase solver when elements are rotated, scaled or flipped, otherwise use single-phase
+    if transforms.is_empty() {
         layout::resolve_constrain_statements(&mut result, &doc, &layout_config)?;
     } else {
-        layout::engine::resolve_constrain_statements_two_phase(
+        layout::engine::resolve_constrain_statements_with_transforms(
             &mut result,
             &doc,
             &layout_config,
-            &template_rotations,
+            &transforms,
         )?;
     }
 
-    // Build skip set for rotated template instances — their anchors were already
+    // Build skip set for transformed elements — their anchors were already
     // correctly transformed by the two-phase solver and must not be overwritten.
-    let rotated_skip: std::collections::HashSet<String> = template_rotations
-        .iter()
-        .filter(|(_, angle)| angle.abs() > f64::EPSILON)
-        .flat_map(|(name, _)| {
-            // Include the template instance name and all its prefixed children
-            let prefix = format!("{}_", name);
-            let mut names = vec![name.clone()];
-            for key in result.elements.keys() {
-                if key.starts_with(&prefix) {
-                    names.push(key.clone());
-                }
-            }
-            names
-        })
-        .collect();
+    let rotated_skip = layout::engine::transformed_element_ids(&result, &transforms);
     let skip_ref = if rotated_skip.is_empty() {
         None
     } else {
@@ -434,8 +1212,39 @@ fn render_pipeline(
     // Resolve constraints (relational positioning and offsets from `place` statements)
     layout::resolve_constraints(&mut result, &doc, skip_ref)?;
 
+    // Attach notes to their anchors now that every element is in place
+    layout::place_notes(&mut result, &doc, &layout_config)?;
+    layout::place_titles_and_legends(&mut result, &doc, &layout_config)?;
+    timer.lap("constraints");
+
     // Route connections
     layout::route_connections(&mut result, &doc)?;
+    layout::organic::jitter_curves(&mut result.connections, organic, organic_seed);
+    for hooks in &config.hooks {
+        hooks.after_layout(&mut result)?;
+    }
+    timer.lap("routing");
+
+    Ok((doc, result))
+}
+
+/// Internal shared render pipeline.
+fn render_pipeline(
+    source: &str,
+    mut config: RenderConfig,
+) -> Result<(String, Vec<layout::lint::LintWarning>), RenderError> {
+    let mut timer = PhaseTimer::new(config.profile);
+    let (doc, mut result) = layout_pipeline(source, &config, &mut timer)?;
+    apply_canvas(&doc, &mut config.svg)?;
+
+    // A presentation step leaves out what later steps reveal
+    if let Some(step) = config.step {
+        let ranges = layout::keyframe::step_ranges(&parse(source)?);
+        layout::keyframe::hide_later_steps(&mut result, &ranges, step);
+    }
+    for hooks in &config.hooks {
+        hooks.before_render(&mut result)?;
+    }
 
     // Debug output
     if config.debug {
@@ -460,11 +1269,17 @@ fn render_pipeline(
     // Keyframe processing (Feature 011)
     let keyframes = layout::keyframe::extract_keyframes(&doc);
     let frame_states = layout::keyframe::compute_frame_states(&keyframes);
-    let frame_diffs = layout::keyframe::compute_frame_diffs(&result, &frame_states, &doc, &config.layout);
+    let layout_config = config.effective_layout();
+    let frame_diffs =
+        layout::keyframe::compute_frame_diffs(&result, &frame_states, &doc, &layout_config);
+    let animations = layout::keyframe::extract_animations(&doc, &result)?;
+    timer.lap("keyframes");
 
     // Lint pass
     let lint_warnings = if config.lint {
-        layout::lint::check(&result, &doc)
+        let warnings = layout::lint::check(&result, &doc);
+        timer.lap("lint");
+        warnings
     } else {
         Vec::new()
     };
@@ -489,15 +1304,14 @@ fn render_pipeline(
 
         // Apply transforms if present, then remove hidden elements
         let mut frame_result = if !state.transforms.is_empty() {
-            layout::keyframe::resolve_frame_for_static(
-                &result, state, &doc, &config.layout,
-            ).unwrap_or_else(|| result.clone())
+            layout::keyframe::resolve_frame_for_static(&result, state, &doc, &layout_config)
+                .unwrap_or_else(|| result.clone())
         } else {
             result.clone()
         };
         frame_result.root_elements = filter_visible_elements(&frame_result.root_elements, &state.hidden_elements);
         frame_result.connections.retain(|c| {
-            c.name.as_ref().map_or(true, |n| !state.hidden_connections.contains(&n.0))
+            c.name.as_ref().is_none_or(|n| !state.hidden_connections.contains(&n.0))
         });
 
         render_svg_with_stylesheet(
@@ -541,6 +1355,23 @@ fn render_pipeline(
             config.debug,
         )
     };
+    // `animate` statements play in every rendering mode
+    let svg = if animations.is_empty() {
+        svg
+    } else {
+        let mut svg = svg;
+        if let Some(pos) = svg.rfind("</svg>") {
+            svg.insert_str(
+                pos,
+                &renderer::render_animations(&animations, &config.stylesheet),
+            );
+        }
+        svg
+    };
+    timer.lap("render");
+    if let Some(report) = timer.report() {
+        eprintln!("{}", report);
+    }
 
     Ok((svg, lint_warnings))
 }
@@ -556,7 +1387,7 @@ fn resolve_frame_index(
             return Ok(idx);
         }
         return Err(RenderError::Layout(layout::LayoutError::validation_error(
-            &format!("frame index {} out of range (0-{})", idx, frame_states.len() - 1),
+            format!("frame index {} out of range (0-{})", idx, frame_states.len() - 1),
         )));
     }
     // Try as name
@@ -566,7 +1397,7 @@ fn resolve_frame_index(
         }
     }
     Err(RenderError::Layout(layout::LayoutError::validation_error(
-        &format!("unknown frame '{}'. Available: {}", selector,
+        format!("unknown frame '{}'. Available: {}", selector,
             frame_states.iter().map(|s| s.name.as_str()).collect::<Vec<_>>().join(", ")),
     )))
 }
@@ -580,7 +1411,7 @@ fn filter_visible_elements(
     elements
         .iter()
         .filter(|e| {
-            e.id.as_ref().map_or(true, |id| !hidden.contains(&id.0))
+            e.id.as_ref().is_none_or(|id| !hidden.contains(&id.0))
         })
         .cloned()
         .map(|mut e| {
@@ -771,16 +1602,1056 @@ mod tests {
     }
 
     #[test]
-    fn test_render_row_layout() {
-        let svg = render("row { rect a rect b }").unwrap();
-        assert!(svg.contains("<g"));
-        assert!(svg.contains("</g>"));
+    fn test_render_escapes_untrusted_label() {
+        let svg =
+            render(r#"rect a [label: "<script>alert('x')</script>", class: "c\" onclick=\"x"]"#)
+                .unwrap();
+        assert!(!svg.contains("<script>"));
+        assert!(svg.contains("&lt;script&gt;"));
+        assert!(!svg.contains(r#"" onclick=""#));
     }
 
     #[test]
-    fn test_render_with_label() {
-        let svg = render(r#"rect server [label: "Server"]"#).unwrap();
-        assert!(svg.contains("<text"));
+    fn test_raw_svg_rejected_by_default() {
+        let source = r#"rect a [raw_svg: "<circle r='3'/>"]"#;
+        let err = render(source).unwrap_err();
+        assert!(err.to_string().contains("raw_svg"));
+
+        let svg = render_with_config(source, RenderConfig::new().with_allow_raw_svg(true)).unwrap();
+        assert!(svg.contains("<circle r='3'/>"));
+        assert!(svg.contains("ai-raw-svg"));
+    }
+
+    #[test]
+    fn test_render_connection_crossing_jump() {
+        let source = r#"
+            rect a [x: 0, y: 100]
+            rect b [x: 300, y: 100]
+            rect c [x: 150, y: 0]
+            rect d [x: 150, y: 250]
+            c -> d
+            a -> b [crossings: jump]
+        "#;
+        let svg = render(source).unwrap();
+        assert!(svg.contains(" A5 5 0 0 1 "), "expected a hop arc: {svg}");
+
+        // Plain crossings by default
+        let plain = source.replace(" [crossings: jump]", "");
+        let svg = render(&plain).unwrap();
+        assert!(!svg.contains(" A5 5 "));
+
+        // Global setting via SvgConfig
+        let config = RenderConfig::new()
+            .with_svg(SvgConfig::default().with_crossings(layout::CrossingStyle::Jump));
+        let svg = render_with_config(&plain, config).unwrap();
+        assert!(svg.contains(" A5 5 0 0 1 "));
+    }
+
+    #[test]
+    fn test_render_detail_breakpoint() {
+        let source = r#"
+            rect overview [label: "Overview"]
+            rect internals [detail: high, label: "Internals"]
+        "#;
+        let svg = render(source).unwrap();
+        assert!(svg.contains(r#"<g class="ai-detail-high">"#));
+        assert!(!svg.contains("@media"));
+
+        let config =
+            RenderConfig::new().with_svg(SvgConfig::default().with_detail_breakpoint(300.0));
+        let svg = render_with_config(source, config).unwrap();
+        assert!(svg.contains("@media (max-width: 300px)"));
+        assert!(svg.contains(".ai-detail-high { display: none; }"));
+    }
+
+    #[test]
+    fn test_render_semantic_groups() {
+        let source = r#"
+            group pipeline [label: "Build & test"] {
+                col {
+                    rect build
+                    rect test
+                }
+            }
+        "#;
+        let svg = render(source).unwrap();
+        assert!(!svg.contains("role=\"group\""));
+
+        let config = RenderConfig::new().with_svg(
+            SvgConfig::default()
+                .with_semantic_groups(true)
+                .with_traversal_order(TraversalOrder::Reading),
+        );
+        let svg = render_with_config(source, config).unwrap();
+        assert!(svg.contains(
+            r#"id="pipeline" tabindex="1" class="ai-container" role="group" aria-label="Build &amp; test""#
+        ));
+        // Anonymous containers are still groups, just without a label
+        assert!(svg.contains(r#"<g class="ai-container" role="group">"#));
+    }
+
+    #[test]
+    fn test_render_reading_traversal_order() {
+        let source = r#"
+            rect footer [x: 0, y: 300]
+            rect header [x: 0, y: 0]
+        "#;
+        let svg = render(source).unwrap();
+        assert!(svg.find(r#"id="footer""#).unwrap() < svg.find(r#"id="header""#).unwrap());
+
+        let config = RenderConfig::new()
+            .with_svg(SvgConfig::default().with_traversal_order(TraversalOrder::Reading));
+        let svg = render_with_config(source, config).unwrap();
+        // Paint order stays with the document; navigation follows reading order
+        assert!(svg.find(r#"id="footer""#).unwrap() < svg.find(r#"id="header""#).unwrap());
+        assert!(svg.contains(r#"id="header" tabindex="1""#));
+        assert!(svg.contains(r#"id="footer" tabindex="2""#));
+    }
+
+    #[test]
+    fn test_render_organic_is_seeded() {
+        let source = "rect a\nrect b\na -> b [routing: curved]";
+        // Compare the drawing only; the CSS variable block is not ordered
+        let body = |svg: String| svg[svg.find("</style>").unwrap()..].to_string();
+        let organic = |seed| {
+            let config = RenderConfig::new().with_layout(
+                LayoutConfig::default()
+                    .with_organic(4.0)
+                    .with_organic_seed(seed),
+            );
+            body(render_with_config(source, config).unwrap())
+        };
+        assert_eq!(organic(1), organic(1));
+        assert_ne!(organic(1), organic(2));
+        assert_ne!(organic(1), body(render(source).unwrap()));
+    }
+
+    #[test]
+    fn test_render_canvas_organic() {
+        let source = "canvas [organic: 4]\nrect a\nrect b\na -> b [routing: curved]";
+        let plain = "canvas [organic: 0]\nrect a\nrect b\na -> b [routing: curved]";
+        let body = |svg: String| svg[svg.find("</style>").unwrap()..].to_string();
+        let seeded = |organic: Option<f64>| {
+            let mut layout = LayoutConfig::default().with_organic_seed(1);
+            layout.organic = organic;
+            RenderConfig::new().with_layout(layout)
+        };
+        let jittered = body(render_with_config(source, seeded(None)).unwrap());
+        assert_ne!(
+            jittered,
+            body(render_with_config(plain, seeded(None)).unwrap())
+        );
+        // The configuration (--organic) overrides the document
+        assert_eq!(
+            jittered,
+            body(render_with_config(plain, seeded(Some(4.0))).unwrap())
+        );
+        assert_eq!(
+            body(render_with_config(source, seeded(Some(0.0))).unwrap()),
+            body(render_with_config(plain, seeded(None)).unwrap())
+        );
+
+        let err = render("canvas [organic: wobbly]\nrect a").unwrap_err();
+        assert!(err.to_string().contains("canvas organic"), "{}", err);
+    }
+
+    #[test]
+    fn test_render_embed_document() {
+        let dir = std::env::temp_dir().join(format!("ail-embed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("sub.ail"),
+            "rect inner [width: 200, height: 100]\nrect other [width: 200, height: 100]",
+        )
+        .unwrap();
+        std::fs::write(dir.join("loop.ail"), r#"embed "loop.ail" as again"#).unwrap();
+
+        let config = RenderConfig::new().with_template_base_path(dir.clone());
+        let svg = render_with_config(
+            r#"embed "sub.ail" as subsystem [scale: 0.5]
+            rect host
+            host -> subsystem"#,
+            config.clone(),
+        )
+        .unwrap();
+        assert!(svg.contains(r#"id="subsystem""#));
+        assert!(svg.contains(r#"id="subsystem_inner""#));
+        assert!(!svg.contains(r#"id="inner""#));
+        // Intrinsic 200-wide drawing rendered at half size
+        assert!(svg.contains("scale(0.5, 0.5)"));
+
+        let err = render_with_config(r#"embed "loop.ail" as a"#, config.clone()).unwrap_err();
+        assert!(err.to_string().contains("ircular"), "{}", err);
+
+        // The embedded document uses the outer palette, and only the outer
+        // document declares it
+        std::fs::write(dir.join("themed.ail"), "rect inner [fill: accent-1]").unwrap();
+        let stylesheet = Stylesheet::from_str("[colors]\naccent-1 = \"#ff0000\"").unwrap();
+        let themed = config
+            .clone()
+            .with_stylesheet(Stylesheet::merge(Stylesheet::default(), stylesheet));
+        let svg = render_with_config(
+            r#"rect host [fill: accent-1]
+            embed "themed.ail" as sub"#,
+            themed,
+        )
+        .unwrap();
+        assert_eq!(svg.matches("<style>").count(), 1, "{}", svg);
+        assert!(svg.contains("--accent-1: #ff0000;"), "{}", svg);
+
+        // Errors inside the embedded document keep their kind
+        std::fs::write(dir.join("broken.ail"), "rect a [").unwrap();
+        let err = render_with_config(r#"embed "broken.ail" as b"#, config).unwrap_err();
+        match &err {
+            RenderError::Template(TemplateError::Embedded { source, span, .. }) => {
+                assert!(matches!(**source, RenderError::Parse(_)), "{}", err);
+                assert_eq!(*span, 0..23);
+            }
+            other => panic!("expected an embedded error, got {}", other),
+        }
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_render_image_shape() {
+        let dir = std::env::temp_dir().join(format!("ail-image-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&[0, 0, 0, 200, 0, 0, 0, 50]);
+        std::fs::write(dir.join("logo.png"), &png).unwrap();
+
+        let config = RenderConfig::new().with_template_base_path(dir.clone());
+        let svg =
+            render_with_config(r#"image "logo.png" mylogo [width: 64]"#, config.clone()).unwrap();
+        // Height follows the 4:1 aspect ratio of the file
+        assert!(svg.contains(r#"href="logo.png" x="0" y="0" width="64" height="16""#));
+
+        let svg = render_with_config(
+            r#"image "logo.png" mylogo"#,
+            config.clone().with_image_href_mode(ImageHrefMode::Base64),
+        )
+        .unwrap();
+        assert!(svg.contains(r#"href="data:image/png;base64,"#));
+        assert!(svg.contains(r#"width="200" height="50""#));
+
+        // A missing file is an error at the image, like a missing template file
+        let source = "rect a\nimage \"missing.png\" b";
+        let err = render_with_config(source, config).unwrap_err();
+        match err {
+            RenderError::Template(TemplateError::ImageNotFound { path, span }) => {
+                assert!(path.ends_with("missing.png"));
+                assert_eq!(span.start, source.find("image").unwrap());
+            }
+            other => panic!("expected ImageNotFound, got {:?}", other),
+        }
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_render_fonts() {
+        let dir = std::env::temp_dir().join(format!("ail-fonts-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("fonts")).unwrap();
+        std::fs::write(dir.join("fonts/Inter.woff2"), b"wOF2").unwrap();
+        std::fs::write(
+            dir.join("brand.toml"),
+            r#"
+[colors]
+
+[fonts]
+family = "Inter, sans-serif"
+width_scale = 1.5
+
+[[fonts.face]]
+family = "Inter"
+file = "fonts/Inter.woff2"
+weight = 400
+
+[[fonts.face]]
+family = "Mono"
+src = "https://example.com/mono.woff2"
+"#,
+        )
+        .unwrap();
+        let stylesheet = Stylesheet::from_file(&dir.join("brand.toml")).unwrap();
+        let config = RenderConfig::new().with_stylesheet(stylesheet);
+
+        let svg = render_with_config(
+            r#"text "Hello" greeting [font_family: "Mono, monospace"]"#,
+            config.clone(),
+        )
+        .unwrap();
+        assert!(
+            svg.contains(r#"font-family="Inter, sans-serif""#),
+            "{}",
+            svg
+        );
+        assert!(svg.contains(
+            r#"@font-face { font-family: "Inter"; src: url("data:font/woff2;base64,d09GMg=="); font-weight: 400; }"#
+        ));
+        assert!(svg.contains(r#"src: url("https://example.com/mono.woff2");"#));
+        assert!(svg.contains(r#"font-family="Mono, monospace""#));
+
+        // Text is measured with the font's width scale
+        let result = compute_layout(r#"text "Hello" greeting"#, &config).unwrap();
+        assert_eq!(
+            result.bounds_of("greeting").unwrap().width,
+            5.0 * 14.0 * 0.6 * 1.5
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_render_resolved_icons() {
+        let icons: std::collections::HashMap<String, String> = [(
+            "aws/ec2".to_string(),
+            r#"<svg viewBox="0 0 20 20"><rect width="20" height="20" onclick="x()"/></svg>"#
+                .to_string(),
+        )]
+        .into();
+        let config = RenderConfig::new().with_icon_resolver(icons);
+        let svg = render_with_config(
+            r#"icon "aws/ec2" vm [width: 80, height: 40]
+            icon "aws/s3" bucket"#,
+            config,
+        )
+        .unwrap();
+        // 20x20 icon scaled into 80x40, centered horizontally
+        assert!(svg.contains(r#"id="vm" class="ai-icon" transform="translate(20, 0) scale(2)""#));
+        assert!(svg.contains(r#"<rect width="20" height="20"/>"#));
+        // Unknown icons fall back to the labelled placeholder
+        assert!(svg.contains(r#"id="bucket""#));
+        assert!(svg.contains(">aws/s3</text>"));
+    }
+
+    #[test]
+    fn test_render_color_functions() {
+        let stylesheet = Stylesheet::from_str(
+            r##"
+[colors]
+accent-1 = "#3366cc"
+
+[styles.muted]
+fill = "alpha(accent-1, 0.25)"
+"##,
+        )
+        .unwrap();
+        let config = RenderConfig::new()
+            .with_stylesheet(Stylesheet::merge(Stylesheet::default(), stylesheet));
+        let source = r#"rect a [fill: darken(accent-1, 20%), stroke: lighten(#000, 50%)]
+            rect b [class: "muted"]
+            rect c [fill: alpha(darken(red, 10%), 50%)]"#;
+        let result = compute_layout(source, &config).unwrap();
+        let styles = |name: &str| &result.elements[name].styles;
+        assert_eq!(styles("a").fill.as_deref(), Some("#1f3d7a"));
+        assert_eq!(styles("a").stroke.as_deref(), Some("#808080"));
+        assert_eq!(styles("b").fill.as_deref(), Some("#3366cc40"));
+        assert_eq!(styles("c").fill.as_deref(), Some("#cc000080"));
+
+        let err = compute_layout("rect a [fill: darken(accent-1)]", &config).unwrap_err();
+        assert!(
+            err.to_string().contains("takes a color and an amount"),
+            "{}",
+            err
+        );
+        let err = compute_layout("rect a [fill: darken(steelblue, 20%)]", &config).unwrap_err();
+        assert!(err.to_string().contains("steelblue"), "{}", err);
+    }
+
+    #[test]
+    fn test_render_faded_connection() {
+        let svg =
+            render("rect a\nrect b\na -> b [stroke: fade(#ff0000, transparent), opacity_end: 0.5]")
+                .unwrap();
+        assert!(svg.contains(r#"<linearGradient id="ai-fade-0" gradientUnits="userSpaceOnUse""#));
+        assert!(svg.contains(r##"<stop offset="0" stop-color="#ff0000" stop-opacity="1"/>"##));
+        assert!(svg.contains(r#"<stop offset="1" stop-color="transparent" stop-opacity="0.5"/>"#));
+        let path = svg.lines().find(|l| l.contains("ai-connection")).unwrap();
+        assert!(path.contains(r#"stroke="url(#ai-fade-0)""#), "{}", path);
+
+        let err = render("rect a\nrect b\na -> b [stroke: glow(red)]").unwrap_err();
+        assert!(err.to_string().contains("Unknown style function 'glow'"));
+    }
+
+    #[test]
+    fn test_render_style_mode_classes() {
+        let config =
+            RenderConfig::new().with_svg(SvgConfig::new().with_style_mode(StyleMode::Classes));
+        let source = r#"rect a [corner_radius: 4]
+            rect b [corner_radius: 4]
+            rect c [fill: #ff0000, label: "C"]
+            a -> b"#;
+        let svg = render_with_config(source, config).unwrap();
+
+        assert!(
+            svg.contains(".ai-style-0 { fill: #f0f0f0; stroke: #333333; stroke-width: 1.5px; }")
+        );
+        // Identical paint shares one class; geometry stays inline
+        let a = svg.lines().find(|l| l.contains(r#"id="a""#)).unwrap();
+        let b = svg.lines().find(|l| l.contains(r#"id="b""#)).unwrap();
+        assert!(
+            a.contains(r#"class="ai-shape ai-rect ai-style-0""#),
+            "{}",
+            a
+        );
+        assert!(b.contains("ai-style-0") && b.contains(r#"rx="4""#), "{}", b);
+        assert!(svg.contains("fill: #ff0000;"));
+
+        // Elements reference classes only
+        let body = &svg[svg.find("</style>").unwrap()..];
+        assert!(!body.contains(" fill=\"#"), "{}", body);
+        assert!(!body.contains(" stroke-width="), "{}", body);
+        assert!(body.contains("marker-end="));
+    }
+
+    #[test]
+    fn test_inventory_resolves_templates() {
+        let source = r#"template "card" { rect body [label: "Card"] rect footer }
+            card c1
+            rect other
+            c1 -> other"#;
+        let entries = inventory(source, &RenderConfig::new()).unwrap();
+        let c1 = entries.iter().find(|e| e.id == "c1").unwrap();
+        assert_eq!(c1.kind, "template");
+        assert_eq!(c1.links, ["-> other"]);
+        // Template contents are listed under the instance
+        assert!(entries
+            .iter()
+            .any(|e| e.group.as_deref() == Some("c1") && e.label.as_deref() == Some("Card")));
+    }
+
+    #[test]
+    fn test_inventory_lists_step_contents() {
+        let entries = inventory("rect a\nstep 2 { rect b  a -> b }", &RenderConfig::new()).unwrap();
+        let ids: Vec<_> = entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["a", "b"]);
+        assert_eq!(entries[0].links, ["-> b"]);
+    }
+
+    #[test]
+    fn test_render_source_spans() {
+        let source = "row r {\n  rect a\n  circle\n}\na -> r";
+        let svg = render(source).unwrap();
+        assert!(!svg.contains("data-ai-span"));
+
+        let config = RenderConfig::new().with_svg(SvgConfig::new().with_source_spans(true));
+        let svg = render_with_config(source, config).unwrap();
+        let span_of = |needle: &str| {
+            let line = svg.lines().find(|l| l.contains(needle)).unwrap();
+            let start = line.find("data-ai-span=\"").unwrap() + 14;
+            let (a, b) = line[start..line[start..].find('"').unwrap() + start]
+                .split_once("..")
+                .unwrap();
+            &source[a.parse::<usize>().unwrap()..b.parse::<usize>().unwrap()]
+        };
+        assert_eq!(span_of(r#"id="r""#), "row r {\n  rect a\n  circle\n}");
+        assert_eq!(span_of(r#"id="a""#), "rect a");
+        // Unnamed elements are mapped too
+        assert_eq!(span_of("<circle"), "circle");
+        assert_eq!(span_of("ai-connection"), "a -> r");
+    }
+
+    #[test]
+    fn test_custom_shape_renderer() {
+        #[derive(Debug)]
+        struct Pod;
+        impl ShapeRenderer for Pod {
+            fn intrinsic_size(&self, styles: &layout::ResolvedStyles) -> (f64, f64) {
+                // Filled pods are drawn larger
+                let side = if styles.fill.is_some() { 70.0 } else { 50.0 };
+                (side, side)
+            }
+
+            fn render(&self, bounds: &layout::BoundingBox, _: &layout::ResolvedStyles) -> String {
+                let c = bounds.center();
+                format!(
+                    r#"<circle cx="{}" cy="{}" r="{}"/>"#,
+                    c.x,
+                    c.y,
+                    bounds.width / 2.0
+                )
+            }
+        }
+
+        let source =
+            "row { kubernetes_pod web [label: \"Web\", fill: blue] rect db }\nweb.right -> db.left";
+        let config = RenderConfig::new().register_shape("kubernetes_pod", Pod);
+        let result = compute_layout(source, &config).unwrap();
+        let web = result.bounds_of("web").unwrap();
+        assert_eq!((web.width, web.height), (70.0, 70.0));
+        assert_eq!(
+            result.route_of(0).unwrap()[0],
+            result.anchor_of("web", "right").unwrap().position
+        );
+
+        let svg = render_with_config(source, config.clone()).unwrap();
+        assert!(
+            svg.contains(r#"<g id="web" class="ai-custom ai-kubernetes_pod">"#),
+            "{}",
+            svg
+        );
+        assert!(svg.contains(r#"r="35"/>"#), "{}", svg);
+
+        // Explicit sizes win; unregistered names are still unknown templates
+        let result = compute_layout("kubernetes_pod p [width: 20]", &config).unwrap();
+        assert_eq!(result.bounds_of("p").unwrap().width, 20.0);
+        assert!(render("kubernetes_pod p").is_err());
+    }
+
+    #[test]
+    fn test_pipeline_hooks() {
+        #[derive(Debug)]
+        struct Policy;
+        impl PipelineHooks for Policy {
+            fn after_resolve(&self, doc: &mut Document) -> Result<(), RenderError> {
+                if inventory::collect(doc)
+                    .iter()
+                    .any(|e| e.id.starts_with('_'))
+                {
+                    return Err(RenderError::Layout(LayoutError::validation_error(
+                        "names must not start with '_'",
+                    )));
+                }
+                Ok(())
+            }
+
+            fn before_render(&self, result: &mut LayoutResult) -> Result<(), RenderError> {
+                let mut mark = result.elements["a"].clone();
+                mark.id = Some(parser::ast::Identifier::new("watermark"));
+                mark.bounds.x = result.bounds.right();
+                result.add_element(mark);
+                Ok(())
+            }
+        }
+
+        let config = RenderConfig::new().with_hooks(Policy);
+        let svg = render_with_config("rect a", config.clone()).unwrap();
+        assert!(svg.contains(r#"id="watermark""#), "{}", svg);
+        // compute_layout stops before rendering
+        assert!(compute_layout("rect a", &config)
+            .unwrap()
+            .bounds_of("watermark")
+            .is_none());
+
+        let err = render_with_config("rect _a", config).unwrap_err();
+        assert!(err.to_string().contains("must not start"), "{}", err);
+    }
+
+    #[test]
+    fn test_layout_queries() {
+        let source = "row { rect a [width: 40, height: 20] rect b }\na.right -> b.left";
+        let result = compute_layout(source, &RenderConfig::new()).unwrap();
+
+        let a = result.bounds_of("a").unwrap();
+        assert_eq!((a.width, a.height), (40.0, 20.0));
+        let right = result.anchor_of("a", "right").unwrap();
+        assert_eq!(right.position, layout::Point::new(a.right(), a.center().y));
+        assert_eq!(right.direction, layout::AnchorDirection::Right);
+
+        let route = result.route_of(0).unwrap();
+        assert_eq!(route.first(), Some(&right.position));
+        assert_eq!(
+            route.last().map(|p| p.x),
+            Some(result.bounds_of("b").unwrap().x)
+        );
+
+        assert!(result.bounds_of("missing").is_none());
+        assert!(result.anchor_of("a", "missing").is_none());
+        assert!(result.route_of(1).is_none());
+    }
+
+    #[test]
+    fn test_render_geometry_attrs() {
+        let source = "row r {\n  rect a [width: 40, height: 20]\n  circle\n}";
+        assert!(!render(source).unwrap().contains("data-ai-x"));
+
+        let config = RenderConfig::new().with_svg(SvgConfig::new().with_geometry_attrs(true));
+        let result = compute_layout(source, &config).unwrap();
+        let a = result.elements["a"].bounds;
+        let svg = render_with_config(source, config).unwrap();
+        let line = svg.lines().find(|l| l.contains(r#"id="a""#)).unwrap();
+        assert!(
+            line.contains(&format!(
+                r#"data-ai-id="a" data-ai-x="{}" data-ai-y="{}" data-ai-width="40" data-ai-height="20""#,
+                a.x, a.y
+            )),
+            "{}",
+            line
+        );
+        // Unnamed elements carry their bounds without an id
+        let line = svg.lines().find(|l| l.contains("<circle")).unwrap();
+        assert!(
+            line.contains("data-ai-x=") && !line.contains("data-ai-id"),
+            "{}",
+            line
+        );
+    }
+
+    #[test]
+    fn test_render_optimized() {
+        for fixture in ["architecture", "feedback-loops", "person-rotation"] {
+            let path = std::path::Path::new("examples").join(format!("{}.ail", fixture));
+            let source = std::fs::read_to_string(&path).unwrap();
+            let config = RenderConfig::new().with_template_base_path("examples".into());
+            let plain = render_with_config(&source, config.clone()).unwrap();
+            let config = config.with_svg(SvgConfig::new().with_optimize(true));
+            let optimized = render_with_config(&source, config).unwrap();
+            assert!(
+                optimized.len() < plain.len(),
+                "{}: {} >= {}",
+                fixture,
+                optimized.len(),
+                plain.len()
+            );
+            assert_eq!(
+                optimized.matches("<path").count(),
+                plain.matches("<path").count()
+            );
+            let svgz = renderer::gzip(&optimized);
+            assert!(svgz.len() * 2 < optimized.len(), "{}", fixture);
+        }
+    }
+
+    #[test]
+    fn test_render_css_variables() {
+        let source = "rect a [fill: accent-1, stroke: #000]";
+        let svg = render(source).unwrap();
+        assert!(svg.contains(r#"fill="var(--accent-1)""#));
+
+        let config = RenderConfig::new().with_svg(SvgConfig::new().with_css_variables(true));
+        let svg = render_with_config(source, config).unwrap();
+        assert!(svg.contains(":where(:root) {"), "{}", svg);
+        assert!(svg.contains("--ai-accent-1: #2196f3;"));
+        assert!(
+            svg.contains(r#"fill="var(--ai-accent-1, #2196f3)""#),
+            "{}",
+            svg
+        );
+        assert!(svg.contains(r##"stroke="#000""##));
+        assert!(!svg.contains("var(--accent-1)"));
+    }
+
+    #[test]
+    fn test_render_graceful() {
+        // A bad reference becomes a box in place of the connection
+        let source = "row {\n  rect a [label: \"A\"]\n  rect b\n}\na -> nowhere\na -> b";
+        let (svg, errors) = render_graceful(source, RenderConfig::new());
+        assert_eq!(errors.len(), 1);
+        assert!(svg.contains(">A</text>"));
+        assert!(svg.contains(">undefined identifier &apos;nowhere&apos;</text>"));
+        assert!(svg.contains(r##"stroke="#d32f2f""##));
+
+        // A syntax error inside a block keeps the element name and the siblings
+        let source = "row {\n  rect a\n  rect b [fill: ]\n}\na -> b";
+        let (svg, errors) = render_graceful(source, RenderConfig::new());
+        assert!(matches!(errors[0], RenderError::Parse(_)));
+        assert!(svg.contains(r#"id="b""#));
+        assert!(svg.contains(r#"id="a""#));
+
+        // Several syntax errors are all reported and patched in one pass
+        let source = "rect a [fill: ]\nrect b\nrect c [stroke ]\nrect d";
+        let (svg, errors) = render_graceful(source, RenderConfig::new());
+        assert_eq!(errors.len(), 2);
+        assert!(svg.contains(r#"id="b""#) && svg.contains(r#"id="d""#));
+        assert_eq!(svg.matches(r##"stroke="#d32f2f""##).count(), 2);
+
+        // An unclosed bracket is reported on the next line; the box goes on
+        // the broken line and the valid statements around it still render
+        let source = "row {\n  rect a [fill: red\n  rect b\n}\nrect c\nb -> c";
+        let (svg, errors) = render_graceful(source, RenderConfig::new());
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(svg.matches(r##"stroke="#d32f2f""##).count(), 1);
+        for id in ["a", "b", "c"] {
+            assert!(svg.contains(&format!(r#"id="{}""#, id)), "{}", svg);
+        }
+
+        // Errors without a location still produce a picture
+        let (svg, errors) = render_graceful("rect a [fill: accent]", RenderConfig::new());
+        assert_eq!(errors.len(), 1);
+        assert!(svg.contains("<svg"));
+        assert!(svg.contains("accent"));
+    }
+
+    #[test]
+    fn test_render_seq_markers() {
+        let svg = render(
+            "row { rect a rect b rect c }\na -> b [seq: 1]\nb -> c [seq: 2, stroke: #cc0000]",
+        )
+        .unwrap();
+        assert_eq!(svg.matches(r#"<g class="ai-seq">"#).count(), 2);
+        assert!(svg.contains(r##"fill="#cc0000"/><text"##));
+        assert!(svg.contains(">2</text></g>"));
+
+        // Errors point at the offending `seq` modifier
+        let source = "rect a\nrect b\na -> b [seq: 1]\nb -> a [seq: 1]";
+        let err = render(source).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Step 1 is used by both a -> b and b -> a"),
+            "{}",
+            err
+        );
+        assert_eq!(&source[error_span(&err).unwrap()], "seq: 1");
+        assert_eq!(
+            error_span(&err).unwrap().start,
+            source.rfind("seq").unwrap()
+        );
+        let source = "rect a\nrect b\na -> b [seq: 1]\nb -> a [seq: 3]";
+        let err = render(source).unwrap_err();
+        assert!(err.to_string().contains("step 2 is missing"), "{}", err);
+        assert_eq!(&source[error_span(&err).unwrap()], "seq: 3");
+        let source = "rect a\nrect b\na -> b [seq: 1.5]";
+        let err = render(source).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("seq must be a positive whole number"));
+        assert_eq!(&source[error_span(&err).unwrap()], "seq: 1.5");
+    }
+
+    #[test]
+    fn test_render_gradient_fill() {
+        let svg = render(
+            "rect a [fill: gradient(accent-1, accent-dark, vertical)]\nrect b [fill: gradient(accent-1, accent-dark, vertical)]\ngroup g [fill: gradient(#fff, #000)] { rect c }",
+        )
+        .unwrap();
+        assert!(svg.contains(r#"<linearGradient id="ai-gradient-0" x1="0" y1="0" x2="0" y2="1">"#));
+        assert!(svg.contains(r#"<stop offset="0" stop-color="var(--accent-1)"/>"#));
+        // Identical gradients share one definition
+        assert_eq!(svg.matches("<linearGradient").count(), 2);
+        assert_eq!(svg.matches(r##"fill="url(#ai-gradient-0)""##).count(), 2);
+        // Container backgrounds take gradients too (horizontal by default)
+        assert!(svg.contains(r#"<linearGradient id="ai-gradient-1" x1="0" y1="0" x2="1" y2="0">"#));
+        assert!(svg.contains(r##"class="ai-container-bg""##));
+
+        let stylesheet = Stylesheet::from_str(
+            r##"
+[colors]
+brand = "#123456"
+
+[gradients.hero]
+from = "brand"
+to = "#ffffff"
+direction = "diagonal"
+"##,
+        )
+        .unwrap();
+        let config = RenderConfig::new().with_stylesheet(stylesheet);
+        let svg = render_with_config("rect a [fill: gradient(hero)]", config.clone()).unwrap();
+        assert!(svg.contains(r#"x2="1" y2="1""#));
+        assert!(svg.contains(r#"stop-color="var(--brand)""#));
+        assert!(svg.contains(r##"fill="url(#ai-gradient-0)""##));
+
+        let err = render_with_config("rect a [fill: gradient(sky)]", config).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Unknown gradient 'sky'. Defined gradients: hero"));
+        let err = render("rect a [fill: gradient(#fff, #000, sideways)]").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Unknown gradient direction 'sideways'"));
+    }
+
+    #[test]
+    fn test_render_tooltips() {
+        let svg = render(
+            r#"rect a [tooltip: "Primary <db>", description: "Stores orders"]
+group g [tooltip: "Backend"] { rect b [label: "Cache"] }"#,
+        )
+        .unwrap();
+        assert!(svg.contains(r#"<g role="img">"#));
+        assert!(svg.contains("<title>Primary &lt;db&gt;</title>"));
+        assert!(svg.contains("<desc>Stores orders</desc>"));
+        assert!(svg.find("<title>Primary").unwrap() < svg.find(r#"id="a""#).unwrap());
+        // Containers keep their own group and get the title as first child
+        assert!(svg.contains("<title>Backend</title>"));
+        assert_eq!(svg.matches(r#"<g role="img">"#).count(), 1);
+        // Labels are not tooltips unless requested
+        assert!(!svg.contains("<title>Cache</title>"));
+
+        let config = RenderConfig::new().with_svg(SvgConfig::new().with_label_tooltips(true));
+        let svg = render_with_config(r#"rect b [label: "Cache"]"#, config).unwrap();
+        assert!(svg.contains("<title>Cache</title>"));
+    }
+
+    #[test]
+    fn test_render_depth_extrusion() {
+        let svg = render("rect a [fill: #4080c0, depth: 8]").unwrap();
+        let faces = svg
+            .matches(r#"class="ai-shape ai-polygon ai-extrusion""#)
+            .count();
+        assert_eq!(faces, 2);
+        assert!(svg.contains(r##"fill="#366da3""##));
+        assert!(svg.contains(r##"fill="#2d5a86""##));
+        // Faces are drawn behind the rect itself
+        assert!(svg.find("ai-extrusion").unwrap() < svg.find(r#"id="a""#).unwrap());
+
+        // Non-hex fills are darkened with an overlay
+        let svg = render("rect a [fill: accent-1, depth: 8]").unwrap();
+        assert_eq!(svg.matches("ai-extrusion").count(), 4);
+        assert!(svg.contains(r#"fill-opacity="0.30""#));
+
+        let svg = render("rect a").unwrap();
+        assert!(!svg.contains("ai-extrusion"));
+    }
+
+    #[test]
+    fn test_render_layers() {
+        let svg = render(
+            "rect main\nlayer overlay [opacity: 0.5] { rect hint }\nlayer background { rect bg }",
+        )
+        .unwrap();
+        let pos = |needle: &str| svg.find(needle).unwrap();
+        assert!(pos(r#"<g class="ai-layer-background">"#) < pos(r#"id="bg""#));
+        assert!(pos(r#"id="bg""#) < pos(r#"id="main""#));
+        assert!(pos(r#"id="main""#) < pos(r#"<g class="ai-layer-overlay" opacity="0.5">"#));
+        assert!(pos(r#"<g class="ai-layer-overlay""#) < pos(r#"id="hint""#));
+
+        // Explicit order wins over the name-based default
+        let svg = render("layer overlay [order: -2] { rect hint }\nrect main").unwrap();
+        assert!(svg.find(r#"id="hint""#).unwrap() < svg.find(r#"id="main""#).unwrap());
+
+        let err = render("group { layer inner { rect a } }").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("layer must be declared at the top level"));
+    }
+
+    #[test]
+    fn test_render_z_order() {
+        let svg = render("stack { rect front [z: 1] rect back rect middle [z_order: 0] }").unwrap();
+        let pos = |id: &str| svg.find(&format!(r#"id="{}""#, id)).unwrap();
+        // Ties keep declaration order; higher z is emitted last (on top)
+        assert!(pos("back") < pos("middle"));
+        assert!(pos("middle") < pos("front"));
+
+        let svg = render("rect a [z: 1]\nrect b").unwrap();
+        assert!(svg.find(r#"id="b""#).unwrap() < svg.find(r#"id="a""#).unwrap());
+    }
+
+    #[test]
+    fn test_render_container_background() {
+        let svg = render(
+            "row [fill: #eef, stroke: #88a, corner_radius: 6, padding: 10] { rect a rect b [corner_radius: 4] }",
+        )
+        .unwrap();
+        let bg = svg.lines().find(|l| l.contains("ai-container-bg")).unwrap();
+        // The background keeps the padding around the children after solving
+        assert!(
+            bg.contains(r#"x="0" y="0" width="200" height="50""#),
+            "{}",
+            bg
+        );
+        assert!(bg.contains(r##"fill="#eef" stroke="#88a""##), "{}", bg);
+        assert!(bg.contains(r#"rx="6" ry="6""#), "{}", bg);
+        let b = svg.lines().find(|l| l.contains(r#"id="b""#)).unwrap();
+        assert!(b.contains(r#"rx="4""#));
+
+        // A stroke-only group is not filled
+        let svg = render("group [stroke: #88a] { rect a }").unwrap();
+        let bg = svg.lines().find(|l| l.contains("ai-container-bg")).unwrap();
+        assert!(bg.contains(r#"fill="none""#), "{}", bg);
+
+        // Unstyled containers stay invisible
+        let svg = render("row { rect a rect b }").unwrap();
+        assert!(!svg.contains("ai-container-bg"));
+    }
+
+    #[test]
+    fn test_render_named_connection_style() {
+        let stylesheet = Stylesheet::from_str(
+            r##"
+[colors]
+brand = "#123456"
+
+[connection_styles.async-call]
+stroke = "#123456"
+stroke_dasharray = "6,3"
+stroke_width = 3
+"##,
+        )
+        .unwrap();
+        let config = RenderConfig::new().with_stylesheet(stylesheet);
+        let source = r#"rect a
+            rect b
+            a -> b [style: "async-call", stroke_width: 1]"#;
+        let svg = render_with_config(source, config.clone()).unwrap();
+        let path = svg.lines().find(|l| l.contains("ai-connection")).unwrap();
+        assert!(path.contains(r##"stroke="#123456""##), "{}", path);
+        assert!(path.contains(r#"stroke-dasharray="6,3""#), "{}", path);
+        // Modifiers on the connection win over the style
+        assert!(path.contains(r#"stroke-width="1""#), "{}", path);
+
+        let err = render_with_config("rect a\nrect b\na -> b [style: sync]", config).unwrap_err();
+        assert!(err.to_string().contains("Unknown connection style 'sync'"));
+    }
+
+    #[test]
+    fn test_render_class_style_rules() {
+        let stylesheet = Stylesheet::from_str(
+            r##"
+[colors]
+
+[styles.database]
+fill = "accent-2"
+stroke_width = 3
+width = 150
+
+[styles.primary]
+fill = "#ff0000"
+"##,
+        )
+        .unwrap();
+        let config = RenderConfig::new()
+            .with_stylesheet(Stylesheet::merge(Stylesheet::default(), stylesheet));
+        let source = r#"rect users [class: "database"]
+            rect orders [class: "database", fill: green]
+            rect both [class: "database", class: "primary"]"#;
+        let result = compute_layout(source, &config).unwrap();
+        assert_eq!(result.bounds_of("users").unwrap().width, 150.0);
+        let users = &result.elements["users"].styles;
+        assert_eq!(users.fill.as_deref(), Some("var(--accent-2)"));
+        assert_eq!(users.stroke_width, Some(3.0));
+        assert_eq!(users.css_classes, vec!["database".to_string()]);
+        // The element's own modifiers win, and the last class wins
+        assert_eq!(
+            result.elements["orders"].styles.fill.as_deref(),
+            Some("green")
+        );
+        assert_eq!(
+            result.elements["both"].styles.fill.as_deref(),
+            Some("#ff0000")
+        );
+    }
+
+    #[test]
+    fn test_render_type_defaults() {
+        let stylesheet = Stylesheet::from_str(
+            r##"
+[colors]
+
+[styles.highlight]
+fill = "#ff0000"
+
+[defaults.circle]
+fill = "accent-1"
+
+[defaults.group]
+stroke_width = 4
+"##,
+        )
+        .unwrap();
+        let config = RenderConfig::new()
+            .with_stylesheet(Stylesheet::merge(Stylesheet::default(), stylesheet));
+        let source = r#"group g {
+                circle a
+                circle b [fill: green]
+                circle c [class: "highlight"]
+                rect d
+            }"#;
+        let result = compute_layout(source, &config).unwrap();
+        let fill = |name: &str| result.elements[name].styles.fill.clone();
+        assert_eq!(fill("a").as_deref(), Some("var(--accent-1)"));
+        // The element's own modifiers and its class rules win over defaults
+        assert_eq!(fill("b").as_deref(), Some("green"));
+        assert_eq!(fill("c").as_deref(), Some("#ff0000"));
+        assert_ne!(fill("d").as_deref(), Some("var(--accent-1)"));
+        assert_eq!(result.elements["g"].styles.stroke_width, Some(4.0));
+    }
+
+    #[test]
+    fn test_render_canvas() {
+        let source = "canvas [width: 800, height: 600, fit: cover]\nrect a";
+        let svg = render(source).unwrap();
+        let root = svg.lines().find(|l| l.starts_with("<svg")).unwrap();
+        assert!(root.contains(r#"width="800" height="600""#), "{}", root);
+        assert!(
+            root.contains(r#"preserveAspectRatio="xMidYMid slice""#),
+            "{}",
+            root
+        );
+
+        // A configured size replaces the canvas size; an unset fit is filled in
+        let config = RenderConfig::new().with_svg(SvgConfig::new().with_scale(2.0));
+        let svg = render_with_config(source, config).unwrap();
+        let root = svg.lines().find(|l| l.starts_with("<svg")).unwrap();
+        assert!(!root.contains(r#"width="800""#), "{}", root);
+
+        // Background and margins
+        let svg = render("canvas [background: white, margin: 10, margin_left: 0]\nrect a").unwrap();
+        let root = svg.lines().find(|l| l.starts_with("<svg")).unwrap();
+        assert!(root.contains(r#"viewBox="0 -10 90 50""#), "{}", root);
+        assert!(
+            svg.contains(
+                r#"<rect class="ai-background" x="0" y="-10" width="90" height="50" fill="white"/>"#
+            ),
+            "{}",
+            svg
+        );
+        let config = RenderConfig::new().with_svg(SvgConfig::new().with_background("#222"));
+        let svg = render_with_config("canvas [background: white]\nrect a", config).unwrap();
+        assert!(svg.contains(r##"fill="#222"/>"##), "{}", svg);
+        assert!(render("canvas [margin: -5]\nrect a").is_err());
+
+        assert!(render("canvas [fit: stretch]\nrect a").is_err());
+        assert!(render("canvas [width: 0]\nrect a").is_err());
+        assert!(render("row { canvas [width: 800] }").is_err());
+    }
+
+    #[test]
+    fn test_render_title_caption_and_legend() {
+        let source = r#"title "Overview"
+            caption "Figure 1"
+            legend [position: top_right] { entry accent-1 "API" entry secondary-1 "DB" }
+            row main { rect api rect db }"#;
+        let result = compute_layout(source, &RenderConfig::new()).unwrap();
+        let main = result.elements["main"].bounds;
+        let [legend, title, caption] = &result.root_elements[1..] else {
+            panic!("Expected legend, title and caption after main");
+        };
+        assert!(legend.bounds.x > main.right(), "{:?}", legend.bounds);
+        assert_eq!(legend.bounds.y, result.elements["api"].bounds.y);
+        assert_eq!(legend.children.len(), 2);
+        assert!(title.bounds.bottom() < main.y, "{:?}", title.bounds);
+        assert!(caption.bounds.y > main.bottom(), "{:?}", caption.bounds);
+
+        let svg = render(source).unwrap();
+        assert!(svg.contains("ai-text title"), "{}", svg);
+        assert!(svg.contains("legend-swatch"), "{}", svg);
+
+        assert!(render("legend [position: middle] { entry red \"x\" }").is_err());
+        assert!(render("row { title \"nested\" }").is_err());
+    }
+
+    #[test]
+    fn test_render_units() {
+        let config = RenderConfig::new();
+        let result = compute_layout("rect a [width: 1in, height: 12pt]", &config).unwrap();
+        let bounds = result.elements["a"].bounds;
+        assert_eq!((bounds.width, bounds.height), (96.0, 16.0));
+
+        // A physical canvas writes the root size in its unit
+        let svg = render("canvas [width: 210mm, height: 297mm]\nrect a [width: 20mm]").unwrap();
+        let root = svg.lines().find(|l| l.starts_with("<svg")).unwrap();
+        assert!(root.contains(r#"width="210mm" height="297mm""#), "{}", root);
+
+        let svg = render("canvas [unit: cm]\nrect a").unwrap();
+        let root = svg.lines().find(|l| l.starts_with("<svg")).unwrap();
+        assert!(root.contains(r#"cm" height=""#), "{}", root);
+    }
+
+    #[test]
+    fn test_render_row_layout() {
+        let svg = render("row { rect a rect b }").unwrap();
+        assert!(svg.contains("<g"));
+        assert!(svg.contains("</g>"));
+    }
+
+    #[test]
+    fn test_render_with_label() {
+        let svg = render(r#"rect server [label: "Server"]"#).unwrap();
+        assert!(svg.contains("<text"));
         assert!(svg.contains("Server"));
     }
 
@@ -943,6 +2814,365 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_render_connection_anchor_offsets() {
+        let source = "row [gap: 80] {\n  rect a [height: 80]\n  rect b [height: 80]\n}\n";
+        let svg = render(&format!("{}a.right+10 -> b.left-5", source)).unwrap();
+        assert!(svg.contains(r#"d="M85 55 L125 55 L125 40 "#), "{}", svg);
+
+        // Modifier form, which also forces a side the router would not pick
+        let svg = render(&format!(
+            "{}a -> b [from_anchor: bottom, to_anchor: bottom, to_offset: 15]",
+            source
+        ))
+        .unwrap();
+        assert!(svg.contains(r#"d="M45 85 L45 100 L220 100 "#), "{}", svg);
+    }
+
+    #[test]
+    fn test_render_shape_ports() {
+        let svg = render(
+            r#"
+            rect chip [x: 0, y: 0, width: 80, height: 80] {
+                port in1 [side: left, at: 0.25, marker: true]
+                port out [side: right, at: 0.75]
+            }
+            rect a [x: -150, y: 5]
+            rect b [width: 20, height: 20]
+            constrain b.left = chip.right + 40
+            constrain b.center_y = chip.out_y
+            a.right -> chip.in1
+        "#,
+        )
+        .unwrap();
+        // The connection ends on the port a quarter down the left side
+        assert!(svg.contains(r#"d="M-70 20 L"#), "{}", svg);
+        assert!(
+            svg.contains(r#"class="ai-shape ai-circle ai-port" cx="0" cy="20""#),
+            "{}",
+            svg
+        );
+        // Constraints see the port too
+        assert!(
+            svg.contains(r#"id="b" class="ai-shape ai-rect" x="120" y="50""#),
+            "{}",
+            svg
+        );
+    }
+
+    #[test]
+    fn test_render_line_caps_and_mid_markers() {
+        let svg = render(
+            r#"
+            rect a [x: 0, y: 0]
+            rect b [x: 200, y: 100]
+            a.right -> b.top [marker_mid: dot, line_cap: round, line_join: bevel]
+            line l [line_cap: square]
+        "#,
+        )
+        .unwrap();
+        assert!(
+            svg.contains(
+                r#"stroke-linecap="round" stroke-linejoin="bevel" marker-mid="url(#ai-mid-dot-8)""#
+            ),
+            "{}",
+            svg
+        );
+        assert!(svg.contains(r#"<marker id="ai-mid-dot-8""#), "{}", svg);
+        assert!(svg.contains(r#"stroke-linecap="square"/>"#), "{}", svg);
+    }
+
+    #[test]
+    fn test_render_steps() {
+        let source = r#"
+            rect a
+            step 2 {
+                rect b
+                a -> b
+            }
+            step 1 { rect c }
+            c -> b
+        "#;
+        let slides = render_steps(source, RenderConfig::default()).unwrap();
+        assert_eq!(slides.len(), 2);
+        assert!(slides[0].contains(r#"id="c""#) && !slides[0].contains(r#"id="b""#));
+        // `c -> b` waits for b even though it is outside any step
+        assert!(!slides[0].contains("ai-connection"), "{}", slides[0]);
+        assert_eq!(slides[1].matches(r#"class="ai-connection"#).count(), 2);
+
+        // Slides share one layout
+        let position = |svg: &str| svg.split(r#"id="a""#).nth(1).unwrap()[..40].to_string();
+        assert_eq!(position(&slides[0]), position(&slides[1]));
+
+        assert!(render_steps("rect a", RenderConfig::default()).is_err());
+        assert!(render_steps("row { step 1 { rect a } }", RenderConfig::default()).is_err());
+        assert!(render("step 1 { step 2 { rect a } }").is_err());
+    }
+
+    #[test]
+    fn test_render_animate() {
+        let svg = render(
+            r#"
+            rect box
+            animate box [property: opacity, from: 0, to: 1, duration: 2s]
+            animate box [property: rotation, from: 0, to: 90, duration: 500ms, repeat: indefinite]
+        "#,
+        )
+        .unwrap();
+        assert!(
+            svg.contains(
+                r##"<animate href="#box" attributeName="opacity" from="0" to="1" dur="2s" fill="freeze"/>"##
+            ),
+            "{}",
+            svg
+        );
+        assert!(
+            svg.contains(r#"type="rotate" from="0 "#)
+                && svg.contains(r#"dur="0.5s" repeatCount="indefinite""#),
+            "{}",
+            svg
+        );
+
+        let err = render("rect box\nanimate box [property: height, from: 0, to: 1]");
+        assert!(err.is_err());
+        let err = render("animate ghost [property: opacity, from: 0, to: 1]");
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_render_state_machine() {
+        let svg = render(
+            r#"
+            row [gap: 80] {
+                state idle [initial: true]
+                state running
+            }
+            idle -> running [on: "start"]
+            running -> running [on: "tick"]
+        "#,
+        )
+        .unwrap();
+        assert!(
+            svg.contains(r#"class="ai-shape ai-rect ai-state""#),
+            "{}",
+            svg
+        );
+        assert!(svg.contains(r#"rx="12""#), "{}", svg);
+        assert!(
+            svg.contains(r#"<circle class="ai-shape ai-circle ai-initial""#),
+            "{}",
+            svg
+        );
+        assert!(
+            svg.contains(">start</text>") && svg.contains(">tick</text>"),
+            "{}",
+            svg
+        );
+
+        // The self-transition is a loop over the top of `running`
+        let result =
+            compute_layout("state s\ns -> s [on: \"tick\"]", &RenderConfig::default()).unwrap();
+        let bounds = result.elements["s"].bounds;
+        let path = &result.connections[0].path;
+        assert_eq!(path.len(), 4);
+        assert_eq!(path[0].y, bounds.y);
+        assert_eq!(path[3].y, bounds.y);
+        assert!(path[1].y < bounds.y && path[0].x > path[3].x);
+    }
+
+    #[test]
+    fn test_render_text_alignment() {
+        let svg = render(
+            r#"
+            col {
+                rect a [width: 200, height: 80, label: "A", text_align: left, vertical_align: top]
+                rect b [width: 200, height: 80, label: "B", text_align: right, vertical_align: bottom]
+                text "C" c [width: 200, text_align: center]
+            }
+        "#,
+        )
+        .unwrap();
+        let result = compute_layout(
+            r#"rect a [width: 200, height: 80, label: "A", text_align: left, vertical_align: top]"#,
+            &RenderConfig::default(),
+        )
+        .unwrap();
+        let a = &result.elements["a"];
+        let label = a.label.as_ref().unwrap();
+        assert_eq!(label.position.x, a.bounds.x + 8.0);
+        assert_eq!(label.position.y, a.bounds.y + 8.0);
+
+        assert!(
+            svg.contains(r#"text-anchor="start" dominant-baseline="hanging">A</text>"#),
+            "{}",
+            svg
+        );
+        assert!(
+            svg.contains(r#"text-anchor="end" dominant-baseline="text-after-edge">B</text>"#),
+            "{}",
+            svg
+        );
+        assert!(
+            svg.contains(r#"text-anchor="middle" dominant-baseline="middle">C</text>"#),
+            "{}",
+            svg
+        );
+    }
+
+    #[test]
+    fn test_rotation_of_plain_shapes_and_groups() {
+        let source = r#"
+            rect bar [width: 100, height: 20, rotation: 90]
+            group box [rotation: 90] {
+                rect inner [width: 60, height: 20]
+            }
+            constrain box.left = bar.right + 10
+            bar.bottom -> inner.right
+        "#;
+        let result = compute_layout(source, &RenderConfig::default()).unwrap();
+
+        // Layout sees the rotated bounding box, centered where the shape was
+        let bar = &result.elements["bar"];
+        assert!((bar.bounds.width - 20.0).abs() < 1e-6, "{:?}", bar.bounds);
+        assert!((bar.bounds.height - 100.0).abs() < 1e-6, "{:?}", bar.bounds);
+        assert_eq!(bar.pre_transform_size, Some((100.0, 20.0)));
+
+        // Anchors turn with the shape: its bottom now faces left
+        let bottom = bar.anchors.get("bottom").unwrap();
+        assert!((bottom.position.x - bar.bounds.x).abs() < 1e-6);
+        assert_eq!(bottom.direction, layout::AnchorDirection::Left);
+
+        // Constraints apply to the rotated box
+        let group = &result.elements["box"];
+        assert!((group.bounds.x - (bar.bounds.right() + 10.0)).abs() < 1e-6);
+
+        // Children of a rotated group get rotated anchors as well
+        let inner = &result.elements["inner"];
+        let right = inner.anchors.get("right").unwrap();
+        assert_eq!(right.direction, layout::AnchorDirection::Down);
+
+        let svg = render(source).unwrap();
+        let center = bar.bounds.center();
+        assert!(
+            svg.contains(&format!("rotate(90 {} {})", center.x, center.y)),
+            "{}",
+            svg
+        );
+        // The rect is drawn at its own size, the transform turns it
+        assert!(svg.contains(r#"width="100" height="20""#), "{}", svg);
+    }
+
+    #[test]
+    fn test_scale_and_flip_modifiers() {
+        let source = r#"
+            template "tag" {
+                rect body [width: 40, height: 20]
+                anchor tip [position: body.right, direction: right]
+            }
+            row {
+                rect a [width: 100, height: 40, scale: 0.5]
+                tag t [flip: horizontal]
+                path p [flip: vertical] {
+                    vertex a1 [x: 0, y: 0]
+                    line_to a2 [x: 40, y: 20]
+                }
+            }
+            a.right -> t.tip
+        "#;
+        let result = compute_layout(source, &RenderConfig::default()).unwrap();
+
+        // Scaled shapes shrink around their center
+        let a = &result.elements["a"];
+        assert!((a.bounds.width - 50.0).abs() < 1e-6, "{:?}", a.bounds);
+        assert!((a.bounds.height - 20.0).abs() < 1e-6, "{:?}", a.bounds);
+        assert_eq!(a.pre_transform_size, Some((100.0, 40.0)));
+        let right = a.anchors.get("right").unwrap();
+        assert!((right.position.x - a.bounds.right()).abs() < 1e-6);
+
+        // A flipped template's anchors move to the mirrored side
+        let tip = result.elements["t"].anchors.get("tip").unwrap();
+        let body = &result.elements["t_body"];
+        assert!((tip.position.x - body.bounds.x).abs() < 1e-6, "{:?}", tip);
+        assert_eq!(tip.direction, layout::AnchorDirection::Left);
+
+        let svg = render(source).unwrap();
+        let center = a.bounds.center();
+        assert!(
+            svg.contains(&format!(
+                "translate({} {}) scale(0.5 0.5) translate({} {})",
+                center.x, center.y, -center.x, -center.y
+            )),
+            "{}",
+            svg
+        );
+        assert!(svg.contains("scale(-1 1)"), "{}", svg);
+        assert!(svg.contains("scale(1 -1)"), "{}", svg);
+    }
+
+    #[test]
+    fn test_render_class_box() {
+        let source = r#"
+            row [gap: 80] {
+                class User {
+                    attr "name: String"
+                    attr "email: String"
+                    op "login()"
+                }
+                class Session
+            }
+            User.attributes_right -> Session.name_left
+        "#;
+        let svg = render(source).unwrap();
+        assert!(
+            svg.contains(r#"class="ai-shape ai-rect ai-class""#),
+            "{}",
+            svg
+        );
+        assert_eq!(svg.matches("ai-class-divider").count(), 4, "{}", svg);
+        assert!(
+            svg.contains(">email: String</text>") && svg.contains(">login()</text>"),
+            "{}",
+            svg
+        );
+        assert!(svg.contains(">User</text>"), "{}", svg);
+
+        // Sized to fit the members, with anchors at each compartment's middle
+        let result = compute_layout(source, &RenderConfig::default()).unwrap();
+        let user = &result.elements["User"];
+        assert!(user.bounds.width >= "email: String".len() as f64 * 14.0 * 0.6);
+        let name = user.anchors.get("name_left").unwrap().position;
+        let attributes = user.anchors.get("attributes_right").unwrap().position;
+        let operations = user.anchors.get("operations_right").unwrap().position;
+        assert_eq!(name.x, user.bounds.x);
+        assert_eq!(attributes.x, user.bounds.right());
+        assert!(name.y < attributes.y && attributes.y < operations.y);
+        assert_eq!(result.connections[0].path[0], attributes);
+    }
+
+    #[test]
+    fn test_render_bus_stubs() {
+        let svg = render(
+            r#"
+            bus rail [x: 0, y: 100, width: 300]
+            rect a [x: 20, y: 0]
+            rect b [x: 150, y: 200]
+            a -> rail
+            rail -> b
+        "#,
+        )
+        .unwrap();
+        assert!(
+            svg.contains(
+                r##"class="ai-shape ai-rect ai-bus" x="0" y="100" width="300" height="6" fill="#333333""##
+            ),
+            "{}",
+            svg
+        );
+        // Each element gets a straight stub to the rail right below it
+        assert!(svg.contains(r#"d="M60 30 L60 "#), "{}", svg);
+        assert!(svg.contains(r#"d="M190 106 L190 "#), "{}", svg);
+    }
+
     #[test]
     fn test_render_curved_connection_multi_via() {
         // Multi-via with explicit C commands for each segment
@@ -964,4 +3194,97 @@ mod tests {
             "Multi-via should use C commands"
         );
     }
+
+    #[test]
+    fn test_render_scoped_names() {
+        let source = r#"
+            row {
+                group a { rect x [width: 40]  rect y  x -> y }
+                group b { rect x [width: 80] }
+            }
+            a.x.right -> b.x
+            constrain b.x.top = a.x.bottom + 10
+        "#;
+        let result = compute_layout(source, &RenderConfig::default()).unwrap();
+        assert_eq!(result.bounds_of("a_x").unwrap().width, 40.0);
+        assert_eq!(result.bounds_of("b_x").unwrap().width, 80.0);
+        assert!(result.elements.contains_key("y"));
+        let ends: Vec<(&str, &str)> = result
+            .connections
+            .iter()
+            .map(|c| (c.from_id.as_str(), c.to_id.as_str()))
+            .collect();
+        assert_eq!(ends, [("a_x", "y"), ("a_x", "b_x")]);
+        assert_eq!(
+            result.bounds_of("b_x").unwrap().y,
+            result.bounds_of("a_x").unwrap().bottom() + 10.0
+        );
+
+        let err = compute_layout(
+            "group a { rect x }\ngroup b { rect x }\nx -> a",
+            &RenderConfig::default(),
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("ambiguous reference 'x'"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_render_template_instance_overrides() {
+        let source = r#"
+            template "motor" { row { rect body [fill: gray]  circle shaft } }
+            motor m1
+            motor m2 [body.fill: red, shaft.size: 12]
+        "#;
+        let svg = render(source).unwrap();
+        let tag = |id: &str| {
+            let start = svg.find(&format!(r#"id="{}""#, id)).unwrap();
+            svg[start..start + svg[start..].find('>').unwrap()].to_string()
+        };
+        // Only the overridden instance changes
+        assert!(tag("m1_body").contains(r#"fill="gray""#), "{}", svg);
+        assert!(tag("m2_body").contains(r#"fill="red""#), "{}", svg);
+        assert!(tag("m2_shaft").contains(r#"r="6""#), "{}", svg);
+    }
+
+    #[test]
+    fn test_render_paths_into_template_instances() {
+        let source = r#"
+            template "motor" {
+                row { rect body  circle terminal_a  circle terminal_b }
+            }
+            template "rack" {
+                motor m1
+                rect base
+            }
+            col {
+                group bay { motor motor1 }
+                rack r
+                rect battery
+            }
+            bay.motor1.terminal_a -> battery
+            r.m1.terminal_b.left -> battery
+            constrain r.m1.body.left = motor1.body.left + 20
+        "#;
+        let result = compute_layout(source, &RenderConfig::default()).unwrap();
+        let ends: Vec<(&str, &str)> = result
+            .connections
+            .iter()
+            .map(|c| (c.from_id.as_str(), c.to_id.as_str()))
+            .collect();
+        assert_eq!(
+            ends,
+            [
+                ("motor1_terminal_a", "battery"),
+                ("r_m1_terminal_b", "battery")
+            ]
+        );
+        assert_eq!(
+            result.bounds_of("r_m1_body").unwrap().x,
+            result.bounds_of("motor1_body").unwrap().x + 20.0
+        );
+    }
 }
@@ -2,46 +2,65 @@
 //!
 //! Usage:
 //!   agent-illustrator [OPTIONS] [FILE]
+//!   agent-illustrator lint [--deny <LEVEL>] [--format <FORMAT>] [OPTIONS] [FILE]
+//!   agent-illustrator fmt [--write | --check] [FILE]
+//!   agent-illustrator build [OPTIONS] <DIR> -o <OUT>
 //!
 //! Options:
-//!   -s, --stylesheet <FILE>  Stylesheet file for color palette (TOML format)
+//!   -s, --stylesheet <FILE>  Stylesheet file for color palette (TOML format, repeatable)
+//!   --theme <NAME>           Apply a stylesheet theme (e.g. dark)
 //!   -g, --grammar            Show language grammar reference
 //!   -e, --examples           Show annotated examples
 //!   --skill                  Output LLM-optimized skill document
+//!   --emit <svg|inventory|inventory-csv>
+//!                            Write the SVG (default) or a table of all elements
+//!   --lint                   Report layout defects (see also the `lint` subcommand)
+//!   --steps <PREFIX>         Write one SVG per presentation step (PREFIX-1.svg, ...)
+//!   --serve <ADDR>           Live preview over HTTP, pushed over a WebSocket on changes
+//!   --profile                Print per-phase timings to stderr
+//!   --width/--height <PX>    Output size (overrides the document's `canvas`)
+//!   --scale <FACTOR>         Output size as a multiple of the diagram size
+//!   --fit <contain|cover>    Letterbox or crop when width and height are both set
+//!   --unit <mm|cm|in|pt|px>  Write the output size in a physical unit for print
+//!   --background <COLOR>     Paint the canvas behind the diagram
+//!   --margin <PX>            Space around the diagram on every side
+//!   --css-variables          Themable --ai-* palette custom properties
+//!   --optimize               Round coordinates, merge defs, drop empty groups
+//!   --svgz                   Write gzip-compressed SVG to stdout
 //!   -h, --help               Print help
 
+mod serve;
+
 use std::fs;
-use std::io::{self, IsTerminal, Read};
-use std::path::PathBuf;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 
+use agent_illustrator::diagnostic::line_col;
+use agent_illustrator::layout::lint::{LintSeverity, LintWarning};
+use agent_illustrator::layout::Padding;
+use agent_illustrator::parser::Unit;
+use agent_illustrator::template::DEFAULT_MAX_TEMPLATE_DEPTH;
 use agent_illustrator::{
-    render_with_config, render_with_lint, ImageHrefMode, RenderConfig, Stylesheet,
+    format, inventory, parse, render_graceful, render_steps, render_with_config, render_with_lint,
+    Diagnostic, Fit, ImageHrefMode, RenderConfig, RenderError, StyleMode, Stylesheet,
+    SvgEmbedPolicy, TraversalOrder,
 };
 
 #[derive(Parser)]
 #[command(name = "agent-illustrator")]
 #[command(about = "Declarative illustration language for AI agents")]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Input file (reads from stdin if not provided)
     input: Option<PathBuf>,
 
-    /// [Deprecated: use --stylesheet-css] TOML color palette file
-    #[arg(short, long)]
-    stylesheet: Option<PathBuf>,
-
-    /// CSS file to inject into the SVG <style> block
-    #[arg(long)]
-    stylesheet_css: Option<PathBuf>,
-
-    /// Debug mode: show container bounds and element IDs
-    #[arg(short, long)]
-    debug: bool,
-
-    /// Trace mode: show internal constraint solver and routing debug output
-    #[arg(short, long)]
-    trace: bool,
+    #[command(flatten)]
+    render: RenderArgs,
 
     /// Show language grammar reference
     #[arg(short, long)]
@@ -67,10 +86,116 @@ struct Cli {
     #[arg(long)]
     skill_styling: bool,
 
+    /// On errors, still render the valid parts with red boxes at the failing statements
+    #[arg(long)]
+    graceful: bool,
+
     /// Lint mode: check for layout defects (overlaps, containment violations, etc.)
     #[arg(long)]
     lint: bool,
 
+    /// Write one SVG per presentation step (`step N { ... }`): PREFIX-1.svg, PREFIX-2.svg, ...
+    #[arg(
+        long,
+        value_name = "PREFIX",
+        conflicts_with_all = ["frame", "animate", "animate_css", "lint"]
+    )]
+    steps: Option<PathBuf>,
+
+    /// Serve a live preview on ADDR (e.g. 127.0.0.1:8000): the page and any page
+    /// embedding /live-reload.js update over a WebSocket when the input changes
+    #[arg(
+        long,
+        value_name = "ADDR",
+        requires = "input",
+        conflicts_with_all = ["steps", "lint", "graceful"]
+    )]
+    serve: Option<String>,
+
+    /// Write gzip-compressed SVG (.svgz) to stdout
+    #[arg(long)]
+    svgz: bool,
+
+    /// What to write to stdout: the SVG, or a table of all elements for documentation
+    #[arg(long, value_enum, default_value_t = EmitArg::Svg)]
+    emit: EmitArg,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Render the input and report layout defects (overlaps, containment violations, etc.)
+    Lint {
+        /// Input file (reads from stdin if not provided)
+        input: Option<PathBuf>,
+
+        /// Exit with status 1 when lint finds anything at or above this severity
+        #[arg(long, value_enum, value_name = "LEVEL")]
+        deny: Option<DenyArg>,
+
+        /// How lint results are reported: text on stderr, or JSON on stdout (instead of the SVG)
+        #[arg(long, value_enum, default_value_t = LintFormatArg::Text)]
+        format: LintFormatArg,
+
+        #[command(flatten)]
+        render: RenderArgs,
+    },
+
+    /// Print the source in canonical formatting instead of rendering it
+    Fmt {
+        /// Input file (reads from stdin if not provided)
+        input: Option<PathBuf>,
+
+        /// Rewrite the input file in place
+        #[arg(long, requires = "input", conflicts_with = "check")]
+        write: bool,
+
+        /// Print nothing, exit with status 1 if the input is not formatted
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Render every .ail file under a directory into --out-dir
+    Build {
+        /// Directory to search for .ail files
+        dir: PathBuf,
+
+        /// Where the SVG files go, mirroring the input tree
+        #[arg(short, long, value_name = "DIR")]
+        out_dir: PathBuf,
+
+        #[command(flatten)]
+        render: RenderArgs,
+    },
+}
+
+/// Options that control how a document is rendered, shared by all modes
+#[derive(Args)]
+struct RenderArgs {
+    /// [Deprecated: use --stylesheet-css] TOML color palette file.
+    /// Repeat to layer palettes; later files override earlier ones.
+    #[arg(short, long)]
+    stylesheet: Vec<PathBuf>,
+
+    /// Stylesheet theme to apply, e.g. dark (from [themes.NAME] in the TOML)
+    #[arg(long)]
+    theme: Option<String>,
+
+    /// CSS file to inject into the SVG <style> block
+    #[arg(long)]
+    stylesheet_css: Option<PathBuf>,
+
+    /// Debug mode: show container bounds and element IDs
+    #[arg(short, long)]
+    debug: bool,
+
+    /// Trace mode: show internal constraint solver and routing debug output
+    #[arg(short, long)]
+    trace: bool,
+
+    /// Print the time spent in each phase (parse, layout, routing, render) to stderr
+    #[arg(long)]
+    profile: bool,
+
     /// How raster image paths (from "template X from file.png") appear in SVG output.
     /// Use 'base64' to embed images directly in the SVG for fully self-contained output.
     /// Use 'verbatim' (default) to keep paths as written in the AIL source.
@@ -88,6 +213,186 @@ struct Cli {
     /// Use pure CSS animation (no JS, works in GitLab/GitHub READMEs)
     #[arg(long)]
     animate_css: bool,
+
+    /// How embedded SVG templates are sanitized before inlining
+    #[arg(long, value_enum, default_value_t = SvgEmbedArg::Strict)]
+    svg_embed_policy: SvgEmbedArg,
+
+    /// How deep template instances may nest (bounds recursive templates)
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_TEMPLATE_DEPTH)]
+    max_template_depth: usize,
+
+    /// Directory of SVG icons for `icon "name"` (reads DIR/name.svg; repeatable)
+    #[arg(long, value_name = "DIR")]
+    icon_path: Vec<PathBuf>,
+
+    /// Allow the raw_svg modifier to emit unescaped markup (trusted input only)
+    #[arg(long)]
+    allow_raw_svg: bool,
+
+    /// Hide `detail: high` elements when the SVG is rendered narrower than this many pixels
+    #[arg(long, value_name = "PX")]
+    detail_breakpoint: Option<f64>,
+
+    /// Export groups and layouts as role="group" with aria-label for screen readers
+    #[arg(long)]
+    semantic_groups: bool,
+
+    /// Emit element labels as <title> tooltips (explicit `tooltip:` always wins)
+    #[arg(long)]
+    label_tooltips: bool,
+
+    /// Tag elements with data-ai-span="start..end", the source bytes that produced them
+    #[arg(long)]
+    source_spans: bool,
+
+    /// Tag elements with data-ai-id/x/y/width/height, their name and layout bounds
+    #[arg(long)]
+    geometry_attrs: bool,
+
+    /// Reference palette colors as --ai-* CSS custom properties with fallbacks,
+    /// so host pages can re-theme the SVG
+    #[arg(long)]
+    css_variables: bool,
+
+    /// Shrink the SVG: round coordinates, merge identical defs, drop empty groups
+    #[arg(long)]
+    optimize: bool,
+
+    /// Output width in pixels (overrides the document's `canvas`)
+    #[arg(long, value_name = "PX")]
+    width: Option<f64>,
+
+    /// Output height in pixels (overrides the document's `canvas`)
+    #[arg(long, value_name = "PX")]
+    height: Option<f64>,
+
+    /// Output size as a multiple of the diagram size
+    #[arg(long, value_name = "FACTOR", conflicts_with_all = ["width", "height"])]
+    scale: Option<f64>,
+
+    /// With --width and --height: letterbox the whole diagram, or fill and crop
+    #[arg(long, value_enum)]
+    fit: Option<FitArg>,
+
+    /// Write the output size in a physical unit for print (1 layout pixel = 1/96 in)
+    #[arg(long, value_enum)]
+    unit: Option<UnitArg>,
+
+    /// Paint the canvas behind the diagram (overrides the document's `canvas`)
+    #[arg(long, value_name = "COLOR")]
+    background: Option<String>,
+
+    /// Space around the diagram on every side (overrides the document's `canvas`)
+    #[arg(long, value_name = "PX")]
+    margin: Option<f64>,
+
+    /// Write paint as inline attributes or as generated CSS classes (for restyling)
+    #[arg(long, value_enum, default_value_t = StyleModeArg::Inline)]
+    style_mode: StyleModeArg,
+
+    /// Order in which keyboard and assistive technology navigation walks the elements
+    #[arg(long, value_enum, default_value_t = TraversalArg::Document)]
+    traversal_order: TraversalArg,
+
+    /// Jitter element positions and curves by up to this many units for a hand-drawn look
+    /// (overrides the document's canvas [organic: N]; 0 turns it off)
+    #[arg(long, value_name = "AMOUNT")]
+    organic: Option<f64>,
+
+    /// Seed for organic jitter (defaults to a hash of the input, so each document is stable)
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum FitArg {
+    /// Show the whole diagram, centered with empty margins
+    Contain,
+    /// Fill the whole output, cropping the diagram's edges
+    Cover,
+}
+
+impl From<FitArg> for Fit {
+    fn from(arg: FitArg) -> Self {
+        match arg {
+            FitArg::Contain => Fit::Contain,
+            FitArg::Cover => Fit::Cover,
+        }
+    }
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum UnitArg {
+    Px,
+    Mm,
+    Cm,
+    In,
+    Pt,
+}
+
+impl From<UnitArg> for Unit {
+    fn from(arg: UnitArg) -> Self {
+        match arg {
+            UnitArg::Px => Unit::Px,
+            UnitArg::Mm => Unit::Mm,
+            UnitArg::Cm => Unit::Cm,
+            UnitArg::In => Unit::In,
+            UnitArg::Pt => Unit::Pt,
+        }
+    }
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum StyleModeArg {
+    /// Presentation attributes on each element
+    Inline,
+    /// Shared classes in the <style> block; elements reference classes only
+    Classes,
+}
+
+impl From<StyleModeArg> for StyleMode {
+    fn from(arg: StyleModeArg) -> Self {
+        match arg {
+            StyleModeArg::Inline => StyleMode::Inline,
+            StyleModeArg::Classes => StyleMode::Classes,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum EmitArg {
+    /// The rendered SVG
+    Svg,
+    /// Markdown table of elements (id, type, label, group, tags, links)
+    Inventory,
+    /// The element table as CSV
+    InventoryCsv,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum DenyArg {
+    /// Fail on warnings (visible defects)
+    Warnings,
+    /// Fail on any finding, including informational suggestions
+    Info,
+}
+
+impl From<DenyArg> for LintSeverity {
+    fn from(arg: DenyArg) -> Self {
+        match arg {
+            DenyArg::Warnings => LintSeverity::Warning,
+            DenyArg::Info => LintSeverity::Info,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum LintFormatArg {
+    /// One `lint:` line per finding on stderr
+    Text,
+    /// A JSON array of findings on stdout, for tools and agents
+    Json,
 }
 
 #[derive(Clone, Copy, clap::ValueEnum)]
@@ -113,9 +418,81 @@ impl From<ImageHrefArg> for ImageHrefMode {
     }
 }
 
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum SvgEmbedArg {
+    /// Remove scripts, event handlers, and references to external resources
+    Strict,
+    /// Remove scripts and event handlers but keep external references
+    AllowExternal,
+    /// Embed SVG files unchanged (only for template libraries you control)
+    Trusted,
+}
+
+impl From<SvgEmbedArg> for SvgEmbedPolicy {
+    fn from(arg: SvgEmbedArg) -> Self {
+        match arg {
+            SvgEmbedArg::Strict => SvgEmbedPolicy::Strict,
+            SvgEmbedArg::AllowExternal => SvgEmbedPolicy::AllowExternal,
+            SvgEmbedArg::Trusted => SvgEmbedPolicy::Trusted,
+        }
+    }
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum TraversalArg {
+    /// Keep the order of the AIL source
+    Document,
+    /// Top-to-bottom, then left-to-right by position
+    Reading,
+}
+
+impl From<TraversalArg> for TraversalOrder {
+    fn from(arg: TraversalArg) -> Self {
+        match arg {
+            TraversalArg::Document => TraversalOrder::Document,
+            TraversalArg::Reading => TraversalOrder::Reading,
+        }
+    }
+}
+
 fn main() {
     let cli = Cli::parse();
 
+    match cli.command {
+        Some(Command::Lint {
+            input,
+            deny,
+            format,
+            render,
+        }) => {
+            let config = render_config(render, input.as_deref()).with_lint(true);
+            let source = read_input(input.as_deref());
+            lint(&source, config, deny, format);
+            return;
+        }
+        Some(Command::Fmt {
+            input,
+            write,
+            check,
+        }) => {
+            let source = read_input(input.as_deref());
+            fmt(&source, input.as_deref().filter(|_| write), check);
+            return;
+        }
+        Some(Command::Build {
+            dir,
+            out_dir,
+            render,
+        }) => {
+            let config = render_config(render, Some(&dir));
+            if !build(&dir, &out_dir, &config) {
+                std::process::exit(1);
+            }
+            return;
+        }
+        None => {}
+    }
+
     // Handle documentation flags first
     if cli.grammar {
         print_grammar();
@@ -153,29 +530,240 @@ fn main() {
         return;
     }
 
+    // Live reload reads the stylesheets again on every render
+    let stylesheets = cli.render.stylesheet.clone();
+    let theme = cli.render.theme.clone();
+    let css = cli.render.stylesheet_css.clone();
+    let config = render_config(cli.render, cli.input.as_deref()).with_lint(cli.lint);
+
+    if let (Some(addr), Some(input)) = (&cli.serve, &cli.input) {
+        // Templates and icons usually sit next to the document, so the whole
+        // directory is watched, together with the stylesheets
+        let dir = input.parent().filter(|dir| !dir.as_os_str().is_empty());
+        let mut watched = vec![dir.unwrap_or(Path::new(".")).to_path_buf()];
+        watched.extend(stylesheets.iter().cloned());
+        watched.extend(css.iter().cloned());
+        let render = || {
+            let source = match fs::read_to_string(input) {
+                Ok(source) => source,
+                Err(e) => {
+                    eprintln!("Error reading file '{}': {}", input.display(), e);
+                    return None;
+                }
+            };
+            let mut config = config.clone();
+            match load_styles(&stylesheets, theme.as_deref(), css.as_deref()) {
+                Ok((stylesheet, custom_css)) => {
+                    config.stylesheet = stylesheet;
+                    config.custom_css = custom_css;
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return None;
+                }
+            }
+            let (svg, errors) = render_graceful(&source, config);
+            for e in &errors {
+                eprintln!("Error: {}", e);
+            }
+            Some(svg)
+        };
+        if let Err(e) = serve::serve(addr, watched, render) {
+            eprintln!("Error serving on {}: {}", addr, e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let source = read_input(cli.input.as_deref());
+
+    if cli.emit != EmitArg::Svg {
+        match inventory(&source, &config) {
+            Ok(entries) => {
+                let table = if cli.emit == EmitArg::InventoryCsv {
+                    inventory::to_csv(&entries)
+                } else {
+                    inventory::to_markdown(&entries)
+                };
+                print!("{}", table);
+                return;
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(prefix) = &cli.steps {
+        let slides = match render_steps(&source, config) {
+            Ok(slides) => slides,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        for (i, svg) in slides.iter().enumerate() {
+            let path = PathBuf::from(format!("{}-{}.svg", prefix.display(), i + 1));
+            if let Err(e) = fs::write(&path, format!("{}\n", svg)) {
+                eprintln!("Error writing file '{}': {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if cli.graceful {
+        let (svg, errors) = render_graceful(&source, config);
+        println!("{}", svg);
+        for e in &errors {
+            eprintln!("Error: {}", e);
+        }
+        if !errors.is_empty() {
+            std::process::exit(1);
+        }
+    } else if cli.lint {
+        lint(&source, config, None, LintFormatArg::Text);
+    } else {
+        match render_with_config(&source, config) {
+            Ok(svg) if cli.svgz => {
+                let svgz = agent_illustrator::renderer::gzip(&format!("{}\n", svg));
+                if let Err(e) = io::stdout().write_all(&svgz) {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            Ok(svg) => {
+                println!("{}", svg);
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// The render configuration for the given options; exits on unreadable stylesheets.
+/// Templates are resolved relative to the directory of `input`.
+fn render_config(args: RenderArgs, input: Option<&Path>) -> RenderConfig {
     // Load stylesheet
     // When --stylesheet-css is provided without --stylesheet, use an empty TOML
     // stylesheet so the CSS file is the sole source of styling variables.
-    if cli.stylesheet.is_some() {
+    if !args.stylesheet.is_empty() {
         eprintln!("warning: --stylesheet is deprecated, use --stylesheet-css instead");
     }
-    let stylesheet = match &cli.stylesheet {
-        Some(path) => match Stylesheet::from_file(path) {
-            Ok(s) => s,
-            Err(e) => {
-                eprintln!("Error loading stylesheet '{}': {}", path.display(), e);
-                std::process::exit(1);
-            }
-        },
-        None => {
-            // Always use default palette for CSS variable definitions.
-            // --stylesheet-css adds custom CSS rules on top, not replacements.
-            Stylesheet::default()
+    let styles = load_styles(
+        &args.stylesheet,
+        args.theme.as_deref(),
+        args.stylesheet_css.as_deref(),
+    );
+    let (stylesheet, custom_css) = match styles {
+        Ok(styles) => styles,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Render with stylesheet, debug mode, and trace mode
+    let mut config = RenderConfig::new()
+        .with_stylesheet(stylesheet)
+        .with_debug(args.debug)
+        .with_trace(args.trace)
+        .with_profile(args.profile)
+        .with_image_href_mode(args.image_href.into())
+        .with_svg_embed_policy(args.svg_embed_policy.into())
+        .with_max_template_depth(args.max_template_depth)
+        .with_allow_raw_svg(args.allow_raw_svg)
+        .with_icon_paths(args.icon_path);
+    config.frame = args.frame;
+    config.animate = args.animate;
+    config.animate_css = args.animate_css;
+    if let Some(width) = args.detail_breakpoint {
+        config.svg = config.svg.with_detail_breakpoint(width);
+    }
+    config.svg.width = args.width;
+    config.svg.height = args.height;
+    config.svg.scale = args.scale;
+    config.svg.fit = args.fit.map(Fit::from);
+    config.svg.unit = args.unit.map(Unit::from);
+    config.svg.background = args.background;
+    if let Some(margin) = args.margin {
+        config.svg = config.svg.with_margin(Padding {
+            top: margin,
+            right: margin,
+            bottom: margin,
+            left: margin,
+        });
+    }
+    config.svg = config
+        .svg
+        .with_semantic_groups(args.semantic_groups)
+        .with_label_tooltips(args.label_tooltips)
+        .with_source_spans(args.source_spans)
+        .with_geometry_attrs(args.geometry_attrs)
+        .with_css_variables(args.css_variables)
+        .with_optimize(args.optimize)
+        .with_style_mode(args.style_mode.into())
+        .with_traversal_order(args.traversal_order.into());
+    if let Some(amount) = args.organic {
+        config.layout = config.layout.with_organic(amount);
+    }
+    if let Some(seed) = args.seed {
+        config.layout = config.layout.with_organic_seed(seed);
+    }
+    if let Some(css) = custom_css {
+        config = config.with_custom_css(css);
+    }
+    // Set template base path to input file's directory for relative imports
+    if let Some(path) = input {
+        if let Some(parent) = path.parent() {
+            config = config.with_template_base_path(parent.to_path_buf());
         }
+    }
+    config
+}
+
+/// The stylesheet layered from `paths` with `theme` applied, and the custom
+/// CSS read from `css`
+fn load_styles(
+    paths: &[PathBuf],
+    theme: Option<&str>,
+    css: Option<&Path>,
+) -> Result<(Stylesheet, Option<String>), String> {
+    let mut layers = Vec::with_capacity(paths.len());
+    for path in paths {
+        let stylesheet = Stylesheet::from_file(path)
+            .map_err(|e| format!("Error loading stylesheet '{}': {}", path.display(), e))?;
+        layers.push(stylesheet);
+    }
+    // Later stylesheets are layered over earlier ones (-s base.toml -s team.toml).
+    // Always use default palette for CSS variable definitions when none is given;
+    // --stylesheet-css adds custom CSS rules on top, not replacements.
+    let mut stylesheet = layers
+        .into_iter()
+        .reduce(Stylesheet::merge)
+        .unwrap_or_default();
+    if let Some(theme) = theme {
+        stylesheet = stylesheet
+            .with_theme(theme)
+            .map_err(|e| format!("Error: {}", e))?;
+    }
+
+    let custom_css = match css {
+        Some(path) => Some(
+            fs::read_to_string(path)
+                .map_err(|e| format!("Error loading CSS '{}': {}", path.display(), e))?,
+        ),
+        None => None,
     };
+    Ok((stylesheet, custom_css))
+}
 
-    // Read input
-    let source = match &cli.input {
+/// The contents of `input`, or of stdin without one; exits when unreadable
+fn read_input(input: Option<&Path>) -> String {
+    match input {
         Some(path) => match fs::read_to_string(path) {
             Ok(content) => content,
             Err(e) => {
@@ -193,70 +781,188 @@ fn main() {
                 }
             }
         }
-    };
+    }
+}
 
-    // Load custom CSS
-    let custom_css = match &cli.stylesheet_css {
-        Some(path) => match fs::read_to_string(path) {
-            Ok(css) => Some(css),
-            Err(e) => {
-                eprintln!("Error loading CSS '{}': {}", path.display(), e);
+/// Print `source` in canonical formatting, or rewrite `write_to` with it;
+/// with `check`, only exit with status 1 if it is not formatted
+fn fmt(source: &str, write_to: Option<&Path>, check: bool) {
+    // Unparseable source is left alone by format(); report why
+    if let Err(e) = parse(source) {
+        eprintln!("Error: {}", RenderError::Parse(e));
+        std::process::exit(1);
+    }
+    let formatted = format(source);
+    if check {
+        std::process::exit(i32::from(formatted != source));
+    }
+    match write_to {
+        Some(path) => {
+            if let Err(e) = fs::write(path, formatted) {
+                eprintln!("Error writing file '{}': {}", path.display(), e);
                 std::process::exit(1);
             }
-        },
-        None => None,
-    };
-
-    // Render with stylesheet, debug mode, and trace mode
-    let mut config = RenderConfig::new()
-        .with_stylesheet(stylesheet)
-        .with_debug(cli.debug)
-        .with_trace(cli.trace)
-        .with_lint(cli.lint)
-        .with_image_href_mode(cli.image_href.into());
-    config.frame = cli.frame;
-    config.animate = cli.animate;
-    config.animate_css = cli.animate_css;
-    if let Some(css) = custom_css {
-        config = config.with_custom_css(css);
-    }
-    // Set template base path to input file's directory for relative imports
-    if let Some(path) = &cli.input {
-        if let Some(parent) = path.parent() {
-            config = config.with_template_base_path(parent.to_path_buf());
         }
+        None => print!("{}", formatted),
     }
+}
 
-    if cli.lint {
-        match render_with_lint(&source, config) {
-            Ok((svg, lint_warnings)) => {
+/// Render `source` and report its lint findings; exits with status 1 on
+/// errors, or on findings at or above `deny`
+fn lint(source: &str, config: RenderConfig, deny: Option<DenyArg>, format: LintFormatArg) {
+    match render_with_lint(source, config) {
+        Ok((svg, lint_warnings)) => {
+            if format == LintFormatArg::Json {
+                println!("{}", lint_json(source, &lint_warnings));
+            } else {
                 println!("{}", svg);
+                for w in &lint_warnings {
+                    eprintln!("{}", lint_line(source, w));
+                }
                 if lint_warnings.is_empty() {
                     eprintln!("lint: clean");
                 } else {
-                    for w in &lint_warnings {
-                        eprintln!("lint: {}: {}", w.category, w.message);
-                    }
                     eprintln!("lint: {} warning(s)", lint_warnings.len());
-                    std::process::exit(1);
                 }
             }
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+            if let Some(deny) = deny {
+                let level = LintSeverity::from(deny);
+                if lint_warnings.iter().any(|w| w.severity() >= level) {
+                    std::process::exit(1);
+                }
             }
         }
-    } else {
-        match render_with_config(&source, config) {
-            Ok(svg) => {
-                println!("{}", svg);
-            }
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Render every `.ail` file under `dir` into `out_dir`, mirroring the tree.
+/// Problems are reported per file on stderr; returns whether all rendered.
+fn build(dir: &Path, out_dir: &Path, config: &RenderConfig) -> bool {
+    let mut files = vec![];
+    if let Err(e) = collect_ail_files(dir, &mut files) {
+        eprintln!("Error reading directory '{}': {}", dir.display(), e);
+        return false;
+    }
+    files.sort();
+
+    let render = |path: &PathBuf| build_file(path, dir, out_dir, config);
+    #[cfg(feature = "parallel")]
+    let results: Vec<_> = {
+        use rayon::prelude::*;
+        files.par_iter().map(render).collect()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let results: Vec<_> = files.iter().map(render).collect();
+
+    // Report in file order, whatever order the files rendered in
+    let mut failed = 0;
+    for (path, problems) in files.iter().zip(&results) {
+        if !problems.is_empty() {
+            failed += 1;
+        }
+        for problem in problems {
+            eprintln!("{}:{}", path.display(), problem);
+        }
+    }
+    eprintln!(
+        "build: {} rendered, {} failed",
+        files.len() - failed,
+        failed
+    );
+    failed == 0
+}
+
+/// `.ail` files under `dir`, recursively
+fn collect_ail_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_ail_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "ail") {
+            files.push(path);
         }
     }
+    Ok(())
+}
+
+/// Render one file of a build; returns its problems as
+/// `line:column: message` (or `: message` when there is no position)
+fn build_file(path: &Path, dir: &Path, out_dir: &Path, config: &RenderConfig) -> Vec<String> {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => return vec![format!(" error: {}", e)],
+    };
+    let mut config = config.clone();
+    if let Some(parent) = path.parent() {
+        config = config.with_template_base_path(parent.to_path_buf());
+    }
+    let svg = match render_with_config(&source, config) {
+        Ok(svg) => svg,
+        Err(e) => {
+            return Diagnostic::from_render_error(&e, &source)
+                .into_iter()
+                .map(|d| format!("{}:{}: error: {}", d.line, d.column, d.message))
+                .collect()
+        }
+    };
+    let relative = path.strip_prefix(dir).unwrap_or(path);
+    let out = out_dir.join(relative).with_extension("svg");
+    let written = out
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&out, svg + "\n"));
+    match written {
+        Ok(()) => vec![],
+        Err(e) => vec![format!(" error: writing '{}': {}", out.display(), e)],
+    }
+}
+
+/// `lint: warning[overlap] 3:1: elements "a" and "b" overlap by 10x10px`
+fn lint_line(source: &str, w: &LintWarning) -> String {
+    let location = match &w.span {
+        Some(span) => {
+            let (line, column) = line_col(source, span.start);
+            format!(" {}:{}", line, column)
+        }
+        None => String::new(),
+    };
+    format!(
+        "lint: {}[{}]{}: {}",
+        w.severity(),
+        w.category,
+        location,
+        w.message
+    )
+}
+
+fn lint_json(source: &str, warnings: &[LintWarning]) -> String {
+    let findings: Vec<_> = warnings
+        .iter()
+        .map(|w| {
+            let location = w.span.as_ref().map(|span| {
+                let (line, column) = line_col(source, span.start);
+                serde_json::json!({
+                    "line": line,
+                    "column": column,
+                    "start": span.start,
+                    "end": span.end,
+                })
+            });
+            serde_json::json!({
+                "severity": w.severity().to_string(),
+                "category": w.category.to_string(),
+                "element": w.element,
+                "related": w.related,
+                "message": w.message,
+                "span": location,
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&findings).unwrap_or_default()
 }
 
 fn print_intro() {
@@ -272,7 +978,7 @@ OPTIONS:
     -e, --examples     Show annotated examples
     --skill            Output LLM skill document (for embedding in agent context)
     --stylesheet-css   CSS stylesheet for colors and visual styling
-    -s, --stylesheet   [Deprecated] TOML color palette
+    -s, --stylesheet   [Deprecated] TOML color palette (repeat to layer)
     -d, --debug        Show element bounds and IDs
     -h, --help         Print help
 
@@ -0,0 +1,305 @@
+//! Canonical minimal source for a document
+//!
+//! [`Document::to_minimal_source`] re-emits a document in the shortest form
+//! that lays out the same way, so a diagram file that an agent rewrites over
+//! many edit cycles doesn't accumulate no-op modifiers and repeated constraints.
+
+use std::collections::HashSet;
+
+use crate::parser::ast::{
+    ConstrainDecl, ConstraintExpr, ConstraintProperty, Document, PropertyRef, Spanned, Statement,
+    StyleKey, StyleModifier,
+};
+use crate::parser::{constrain_text, unparse};
+
+impl Document {
+    /// Shortest equivalent DSL source
+    ///
+    /// - repeats of the same modifier are dropped. Modifiers set to their
+    ///   built-in default (`opacity: 1`) are kept: a style rule, connection
+    ///   style or type default in the stylesheet may set something else,
+    ///   which the explicit value overrides
+    /// - duplicate constraints are dropped, including mirrored equalities and
+    ///   the `x`/`left` and `y`/`top` aliases, as are constraints that relate
+    ///   a property to itself
+    /// - sibling `contains` and `equal_width`/`equal_height` constraints that
+    ///   can be expressed as one are merged
+    ///
+    /// ```rust
+    /// use agent_illustrator::parse;
+    ///
+    /// let doc = parse(
+    ///     "rect a [opacity: 1, opacity: 1] rect b\nconstrain a.x = b.x\nconstrain b.left = a.left",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(
+    ///     doc.to_minimal_source(),
+    ///     "rect a [opacity: 1]\nrect b\nconstrain a.x = b.x\n"
+    /// );
+    /// ```
+    pub fn to_minimal_source(&self) -> String {
+        let mut doc = self.clone();
+        simplify(&mut doc.statements, &mut HashSet::new());
+        unparse(&doc)
+    }
+}
+
+/// Simplify a statement list in place; `seen` holds the canonical form of
+/// every constraint kept so far in the same scope
+fn simplify(stmts: &mut Vec<Spanned<Statement>>, seen: &mut HashSet<String>) {
+    let mut kept: Vec<Spanned<Statement>> = Vec::with_capacity(stmts.len());
+    for mut stmt in stmts.drain(..) {
+        match &mut stmt.node {
+            Statement::Constrain(c) => {
+                if is_trivial(&c.expr) || !seen.insert(canonical(c)) {
+                    continue;
+                }
+                if merge_into_sibling(&mut kept, c) {
+                    continue;
+                }
+            }
+            Statement::Constraint(_) => {
+                if !seen.insert(unparse(&Document {
+                    statements: vec![stmt.clone()],
+                    comments: vec![],
+                })) {
+                    continue;
+                }
+            }
+            Statement::TemplateDecl(t) => {
+                // Template bodies have their own names
+                if let Some(body) = &mut t.body {
+                    simplify(body, &mut HashSet::new());
+                }
+            }
+            other => simplify_statement(other, seen),
+        }
+        kept.push(stmt);
+    }
+    *stmts = kept;
+}
+
+fn simplify_statement(stmt: &mut Statement, seen: &mut HashSet<String>) {
+    match stmt {
+        Statement::Shape(s) => strip_modifiers(&mut s.modifiers),
+        Statement::Connection(conns) => {
+            for conn in conns {
+                strip_modifiers(&mut conn.modifiers);
+            }
+        }
+        Statement::Layout(l) => {
+            strip_modifiers(&mut l.modifiers);
+            simplify(&mut l.children, seen);
+        }
+        Statement::Group(g) => {
+            strip_modifiers(&mut g.modifiers);
+            simplify(&mut g.children, seen);
+        }
+        Statement::Note(n) => {
+            strip_modifiers(&mut n.modifiers);
+            simplify(&mut n.children, seen);
+        }
+        Statement::Embed(e) => strip_modifiers(&mut e.modifiers),
+        Statement::Label(inner) => simplify_statement(inner, seen),
+        _ => {}
+    }
+}
+
+/// Collapse repeats of a modifier
+///
+/// Keys that appear with different values are left alone: some properties
+/// take the first value and some the last.
+fn strip_modifiers(modifiers: &mut Vec<Spanned<StyleModifier>>) {
+    let same_value = |key: &StyleKey| {
+        let mut values = modifiers
+            .iter()
+            .filter(|m| m.node.key.node == *key)
+            .map(|m| &m.node.value.node);
+        let first = values.next();
+        values.all(|v| Some(v) == first)
+    };
+    let collapsible: Vec<bool> = modifiers
+        .iter()
+        .map(|m| same_value(&m.node.key.node))
+        .collect();
+
+    let mut seen_keys: Vec<StyleKey> = vec![];
+    let mut index = 0;
+    modifiers.retain(|m| {
+        let collapse = collapsible[index];
+        index += 1;
+        let key = &m.node.key.node;
+        if !collapse {
+            return true;
+        }
+        if seen_keys.contains(key) {
+            return false;
+        }
+        seen_keys.push(key.clone());
+        true
+    });
+}
+
+/// `x`/`left` and `y`/`top` are the same solver variable
+fn normalized(p: &PropertyRef) -> PropertyRef {
+    let mut p = p.clone();
+    p.property.node = match p.property.node {
+        ConstraintProperty::X => ConstraintProperty::Left,
+        ConstraintProperty::Y => ConstraintProperty::Top,
+        other => other,
+    };
+    p
+}
+
+fn same_property(a: &PropertyRef, b: &PropertyRef) -> bool {
+    let (a, b) = (normalized(a), normalized(b));
+    a.element.node.to_string() == b.element.node.to_string() && a.property.node == b.property.node
+}
+
+/// `a.left = a.x` and the like
+fn is_trivial(expr: &ConstraintExpr) -> bool {
+    matches!(expr, ConstraintExpr::Equal { left, right } if same_property(left, right))
+}
+
+/// Text form that is equal for constraints with the same effect
+fn canonical(c: &ConstrainDecl) -> String {
+    let mut c = c.clone();
+    match &mut c.expr {
+        ConstraintExpr::Equal { left, right } => {
+            let (mut a, mut b) = (normalized(left), normalized(right));
+            let text = |p: &PropertyRef| format!("{}.{:?}", p.element.node, p.property.node);
+            if text(&a) > text(&b) {
+                std::mem::swap(&mut a, &mut b);
+            }
+            *left = a;
+            *right = b;
+        }
+        ConstraintExpr::EqualWithOffset { left, right, .. } => {
+            *left = normalized(left);
+            *right = normalized(right);
+        }
+        ConstraintExpr::Constant { left, .. }
+        | ConstraintExpr::GreaterOrEqual { left, .. }
+        | ConstraintExpr::LessOrEqual { left, .. } => *left = normalized(left),
+        _ => {}
+    }
+    constrain_text(&c)
+}
+
+/// Fold `contains` and equal-size constraints into an earlier sibling that
+/// can absorb them; returns true if `c` was merged
+fn merge_into_sibling(kept: &mut [Spanned<Statement>], c: &ConstrainDecl) -> bool {
+    for stmt in kept.iter_mut() {
+        let Statement::Constrain(prev) = &mut stmt.node else {
+            continue;
+        };
+        if prev.strength != c.strength {
+            continue;
+        }
+        let (prev_elements, new_elements) = match (&mut prev.expr, &c.expr) {
+            (
+                ConstraintExpr::Contains {
+                    container: a,
+                    elements: prev_elements,
+                    padding: pa,
+                },
+                ConstraintExpr::Contains {
+                    container: b,
+                    elements,
+                    padding: pb,
+                },
+            ) if a.node == b.node && pa == pb => (prev_elements, elements),
+            // Equal sizes are transitive, so lists sharing an element combine
+            (
+                ConstraintExpr::EqualSize {
+                    dimension: da,
+                    elements: prev_elements,
+                },
+                ConstraintExpr::EqualSize {
+                    dimension: db,
+                    elements,
+                },
+            ) if da == db
+                && elements
+                    .iter()
+                    .any(|e| prev_elements.iter().any(|p| p.node == e.node)) =>
+            {
+                (prev_elements, elements)
+            }
+            _ => continue,
+        };
+        for element in new_elements {
+            if !prev_elements.iter().any(|p| p.node == element.node) {
+                prev_elements.push(element.clone());
+            }
+        }
+        return true;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse;
+
+    #[test]
+    fn test_to_minimal_source() {
+        let doc = parse(
+            r#"col main [gap: 20] {
+                rect a [opacity: 1, fill: red, fill: red, rotation: 0]
+                rect b [z: 2, z: 3]
+                rect c
+            }
+            a -> b [label_at: 0.5, label: "x"]
+            constrain a.left = b.left
+            constrain b.x = a.x
+            constrain a.top = a.y
+            constrain main contains a [padding: 4]
+            constrain main contains b, a [padding: 4]
+            constrain equal_width a, b
+            constrain equal_width c, b
+            place c below a
+            place c below a"#,
+        )
+        .unwrap();
+        let minimal = doc.to_minimal_source();
+        assert_eq!(
+            minimal,
+            r#"col main [gap: 20] {
+    rect a [opacity: 1, fill: red, rotation: 0]
+    rect b [z: 2, z: 3]
+    rect c
+}
+a -> b [label_at: 0.5, label: "x"]
+constrain a.left = b.left
+constrain main contains a, b [padding: 4]
+constrain equal_width a, b, c
+place c below a
+"#
+        );
+        // The minimal form is stable and parses to the same minimal form
+        assert_eq!(parse(&minimal).unwrap().to_minimal_source(), minimal);
+    }
+
+    #[test]
+    fn test_minimal_source_keeps_explicit_defaults() {
+        use crate::{render_with_config, RenderConfig, Stylesheet};
+
+        // The explicit opacity overrides the style rule, so it must stay
+        let source = r#"rect a [class: "faded", opacity: 1]"#;
+        let minimal = parse(source).unwrap().to_minimal_source();
+        assert_eq!(minimal, "rect a [class: \"faded\", opacity: 1]\n");
+
+        let stylesheet =
+            Stylesheet::from_str("[colors]\n[styles.faded]\nopacity = \"0.5\"").unwrap();
+        let config = RenderConfig::new()
+            .with_stylesheet(Stylesheet::merge(Stylesheet::default(), stylesheet));
+        let rendered = render_with_config(source, config.clone()).unwrap();
+        assert_eq!(
+            render_with_config(&minimal, config.clone()).unwrap(),
+            rendered
+        );
+        let without = render_with_config(r#"rect a [class: "faded"]"#, config).unwrap();
+        assert_ne!(without, rendered);
+    }
+}
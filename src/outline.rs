@@ -0,0 +1,236 @@
+//! Structural outline of a document
+//!
+//! [`Document::outline`] summarizes a diagram as indented containers and
+//! elements followed by the connection list, without any styling, so an agent
+//! editing a large diagram can be shown its structure instead of the source.
+
+use crate::inventory::{direction_arrow, label_text, layout_kind, shape_kind};
+use crate::parser::ast::{
+    AnchorReference, ConnectionDecl, Document, ShapeType, Spanned, Statement,
+};
+use crate::parser::expr_text;
+
+impl Document {
+    /// Compact hierarchical summary: one line per element, indented by
+    /// nesting, with labels in quotes, then the connections
+    ///
+    /// ```rust
+    /// use agent_illustrator::parse;
+    ///
+    /// let doc = parse(r#"row { rect a [label: "A", fill: red] rect b } a -> b"#).unwrap();
+    /// assert_eq!(doc.outline(), "row\n  rect a \"A\"\n  rect b\nconnections:\n  a -> b\n");
+    /// ```
+    pub fn outline(&self) -> String {
+        let mut outline = Outline::default();
+        outline.statements(&self.statements, 0);
+
+        let mut out = outline.lines.join("\n");
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        if !outline.connections.is_empty() {
+            out.push_str("connections:\n");
+            for conn in &outline.connections {
+                out.push_str(&format!("  {}\n", conn));
+            }
+        }
+        if outline.constraints > 0 {
+            out.push_str(&format!("constraints: {}\n", outline.constraints));
+        }
+        out
+    }
+}
+
+#[derive(Default)]
+struct Outline {
+    lines: Vec<String>,
+    connections: Vec<String>,
+    constraints: usize,
+}
+
+impl Outline {
+    fn statements(&mut self, stmts: &[Spanned<Statement>], depth: usize) {
+        for stmt in stmts {
+            self.statement(&stmt.node, depth);
+        }
+    }
+
+    fn line(&mut self, depth: usize, text: String, label: Option<String>) {
+        let label = label.map(|l| format!(" {:?}", l)).unwrap_or_default();
+        self.lines
+            .push(format!("{}{}{}", "  ".repeat(depth), text, label));
+    }
+
+    fn statement(&mut self, stmt: &Statement, depth: usize) {
+        let named = |kind: &str, name: Option<&str>| match name {
+            Some(name) => format!("{} {}", kind, name),
+            None => kind.to_string(),
+        };
+        match stmt {
+            Statement::Shape(s) => {
+                let name = s.name.as_ref().map(|n| n.node.as_str());
+                let label = match &s.shape_type.node {
+                    ShapeType::Text { content } => Some(content.clone()),
+                    _ => label_text(&s.modifiers),
+                };
+                self.line(depth, named(&shape_kind(&s.shape_type.node), name), label);
+            }
+            Statement::Layout(l) => {
+                let name = l.name.as_ref().map(|n| n.node.as_str());
+                self.line(
+                    depth,
+                    named(layout_kind(l.layout_type.node), name),
+                    label_text(&l.modifiers),
+                );
+                self.statements(&l.children, depth + 1);
+            }
+            Statement::Group(g) => {
+                let text = match &g.layer {
+                    Some(layer) => format!("layer {}", layer.node),
+                    None => named("group", g.name.as_ref().map(|n| n.node.as_str())),
+                };
+                self.line(depth, text, label_text(&g.modifiers));
+                self.statements(&g.children, depth + 1);
+            }
+            Statement::Label(inner) => self.statement(inner, depth),
+            Statement::Connection(conns) => {
+                self.connections.extend(conns.iter().map(connection_line));
+            }
+            Statement::Constraint(_) | Statement::Constrain(_) => self.constraints += 1,
+            Statement::TemplateDecl(t) => {
+                let text = match &t.source_path {
+                    Some(path) => format!("template {:?} from {:?}", t.name.node.0, path.node),
+                    None => format!("template {:?}", t.name.node.0),
+                };
+                self.line(depth, text, None);
+                if let Some(body) = &t.body {
+                    self.statements(body, depth + 1);
+                }
+            }
+            Statement::TemplateInstance(inst) => {
+                self.line(
+                    depth,
+                    format!("{} {}", inst.template_name.node, inst.instance_name.node),
+                    None,
+                );
+                self.statements(&inst.children, depth + 1);
+            }
+            Statement::Slot => self.line(depth, "slot".to_string(), None),
+            Statement::Embed(e) => self.line(
+                depth,
+                format!("embed {:?} as {}", e.path.node, e.name.node),
+                None,
+            ),
+            Statement::Import(i) => self.line(
+                depth,
+                format!("import {:?} as {}", i.path.node, i.alias.node),
+                None,
+            ),
+            Statement::Repeat(r) => {
+                let text = format!("repeat {} as {}", expr_text(&r.count.node), r.variable.node);
+                self.line(depth, text, None);
+                self.statements(&r.body, depth + 1);
+            }
+            Statement::If(i) => {
+                self.line(depth, format!("if {}", i.condition.node), None);
+                self.statements(&i.then_body, depth + 1);
+                if !i.else_body.is_empty() {
+                    self.line(depth, "else".to_string(), None);
+                    self.statements(&i.else_body, depth + 1);
+                }
+            }
+            Statement::Step(s) => {
+                self.line(depth, format!("step {}", s.number.node), None);
+                self.statements(&s.children, depth + 1);
+            }
+            Statement::Note(note) => {
+                self.line(depth, format!("note at {}", anchor_ref(&note.target)), None);
+                self.statements(&note.children, depth + 1);
+            }
+            Statement::Export(_)
+            | Statement::AnchorDecl(_)
+            | Statement::Keyframe(_)
+            | Statement::Let(_)
+            | Statement::Canvas(_)
+            | Statement::Animate(_) => {}
+            Statement::Title(t) => self.line(
+                depth,
+                format!("{} {:?}", t.kind.as_str(), t.text.node),
+                None,
+            ),
+            Statement::Legend(l) => {
+                self.line(depth, "legend".to_string(), None);
+                for entry in &l.entries {
+                    self.line(
+                        depth + 1,
+                        format!("entry {:?}", entry.node.label.node),
+                        None,
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn connection_line(conn: &ConnectionDecl) -> String {
+    let mut line = format!(
+        "{} {} {}",
+        anchor_ref(&conn.from),
+        direction_arrow(conn.direction, true),
+        anchor_ref(&conn.to)
+    );
+    if let Some(name) = &conn.name {
+        line.push_str(&format!(" as {}", name.node));
+    }
+    if let Some(label) = label_text(&conn.modifiers) {
+        line.push_str(&format!(" {:?}", label));
+    }
+    line
+}
+
+fn anchor_ref(r: &AnchorReference) -> String {
+    match &r.anchor {
+        Some(anchor) => format!("{}.{}", r.element.node, anchor.node),
+        None => r.element.node.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse;
+
+    #[test]
+    fn test_outline() {
+        let doc = parse(
+            r#"template "card" { rect body [label: "Card"] }
+            col main {
+                group backend [label: "Backend", fill: #eef] {
+                    rect api [stroke: red]
+                    circle db [label: "Orders DB"]
+                }
+                card c1
+            }
+            text "Read only" note_text
+            api.right -> db [label: "reads", stroke_width: 2]
+            c1 <- api as fetch
+            constrain note_text.left = main.right + 10"#,
+        )
+        .unwrap();
+        assert_eq!(
+            doc.outline(),
+            r#"template "card"
+  rect body "Card"
+col main
+  group backend "Backend"
+    rect api
+    circle db "Orders DB"
+  card c1
+text note_text "Read only"
+connections:
+  api.right -> db "reads"
+  c1 <- api as fetch
+constraints: 1
+"#
+        );
+    }
+}
@@ -115,6 +115,17 @@ impl std::fmt::Display for Identifier {
 #[derive(Debug, Clone, PartialEq)]
 pub struct Document {
     pub statements: Vec<Spanned<Statement>>,
+    /// Comments in source order; layout ignores them, printing keeps them
+    pub comments: Vec<Spanned<Comment>>,
+}
+
+/// A `// line` or `/* block */` comment, kept as trivia on the document
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    /// The comment including its delimiters
+    pub text: String,
+    /// End of the token this comment follows on the same line, if any
+    pub after: Option<usize>,
 }
 
 /// Top-level statement in a document
@@ -140,12 +151,37 @@ pub enum Statement {
     TemplateDecl(TemplateDecl),
     /// Template instance: `template_name "instance_name" [params]`
     TemplateInstance(TemplateInstance),
+    /// Embedded document: `embed "other.ail" as name [scale: 0.5]`
+    Embed(EmbedDecl),
+    /// Template library import: `import "lib/electronics.ail" as elec`
+    Import(ImportDecl),
     /// Export declaration: `export port1, port2`
     Export(ExportDecl),
     /// Anchor declaration: `anchor name [position: element.property]` (Feature 009)
     AnchorDecl(AnchorDecl),
     /// Keyframe declaration: `keyframe "name" { show/hide/transform ... }` (Feature 011)
     Keyframe(KeyframeDecl),
+    /// Annotation attached to an anchor: `note at api.top_right [dx: 10] { ... }`
+    Note(NoteDecl),
+    /// Loop expanded during template resolution: `repeat 5 as i { rect item_$i }`
+    Repeat(RepeatDecl),
+    /// Conditional in a template body: `if with_cache { rect cache } else { ... }`
+    If(IfDecl),
+    /// Numeric variable: `let base = 40`
+    Let(LetDecl),
+    /// Placeholder in a template body for the children of an instance:
+    /// `template "card" { rect title; slot }` used as `card c { rect content }`
+    Slot,
+    /// Output size of the whole diagram: `canvas [width: 800, height: 600, fit: cover]`
+    Canvas(CanvasDecl),
+    /// Diagram heading or footnote: `title "Request flow"`, `caption "Figure 1"`
+    Title(TitleDecl),
+    /// Color key: `legend { entry accent "API calls" }`
+    Legend(LegendDecl),
+    /// Property animation: `animate box [property: opacity, from: 0, to: 1, duration: 2s]`
+    Animate(AnimateDecl),
+    /// Presentation step revealed from slide N onwards: `step 2 { rect cache }`
+    Step(StepDecl),
 }
 
 /// Shape declaration
@@ -154,6 +190,23 @@ pub struct ShapeDecl {
     pub shape_type: Spanned<ShapeType>,
     pub name: Option<Spanned<Identifier>>,
     pub modifiers: Vec<Spanned<StyleModifier>>,
+    /// Ports declared in the shape's `{ ... }` body
+    pub ports: Vec<Spanned<PortDecl>>,
+}
+
+/// Named connection point on a side of a shape: `port in1 [side: left, at: 0.25]`
+///
+/// Ports become anchors of the shape, so `chip.in1` works in connections and
+/// `chip.in1_x` / `chip.in1_y` in constraints.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortDecl {
+    pub name: Spanned<Identifier>,
+    /// Side the port sits on: left, right, top or bottom
+    pub side: Edge,
+    /// Position along the side, from 0 (top or left end) to 1 (bottom or right end)
+    pub at: f64,
+    /// Draw a small dot at the port
+    pub marker: bool,
 }
 
 /// Built-in shape types
@@ -166,6 +219,8 @@ pub enum ShapeType {
     Polygon,
     Icon {
         icon_name: String,
+        /// Markup found by an icon resolver; `None` renders a placeholder
+        svg: Option<IconSvg>,
     },
     Text {
         content: String,
@@ -180,9 +235,39 @@ pub enum ShapeType {
     RasterImage {
         /// Path to the image file (relative to template base path)
         path: String,
+        /// Pixel dimensions read from the file during template resolution
+        intrinsic_width: Option<f64>,
+        intrinsic_height: Option<f64>,
     },
     /// Custom path shape (Feature 007)
     Path(PathDecl),
+    /// Shared rail that connections tap into with short stubs:
+    /// `bus rail [orientation: vertical]`
+    Bus,
+    /// State of a state machine, a rounded rect: `state idle [initial: true]`
+    State,
+    /// UML class box with name, attribute and operation compartments:
+    /// `class User { attr "name: String" op "login()" }`
+    Class {
+        attributes: Vec<Spanned<String>>,
+        operations: Vec<Spanned<String>>,
+    },
+    /// Application-defined shape drawn by a registered renderer:
+    /// `kubernetes_pod web`
+    Custom {
+        kind: String,
+        /// Size reported by the renderer during template resolution
+        intrinsic_width: f64,
+        intrinsic_height: f64,
+    },
+}
+
+/// Sanitized SVG for a resolved icon, with its intrinsic size
+#[derive(Debug, Clone, PartialEq)]
+pub struct IconSvg {
+    pub content: String,
+    pub width: f64,
+    pub height: f64,
 }
 
 /// Connection between shapes
@@ -229,6 +314,14 @@ pub enum LayoutType {
     Column,
     Grid,
     Stack,
+    /// Left-to-right, wrapping to a new line past `max_width`
+    Flow,
+    /// Evenly spaced around a circle (`radius: N`)
+    Ring,
+    /// Bars on a horizontal time axis, one row per child (`start: N, end: N`)
+    Timeline,
+    /// Children at their `x`/`y` canvas coordinates, without stacking
+    Absolute,
 }
 
 /// Semantic group (no layout implication)
@@ -241,6 +334,108 @@ pub struct GroupDecl {
     pub anchors: Vec<AnchorDecl>,
     /// Whether this group was created by template expansion (vs user-authored)
     pub is_template_instance: bool,
+    /// Render layer name when declared as `layer name [order: N, opacity: N] { ... }`
+    pub layer: Option<Spanned<Identifier>>,
+}
+
+/// Free-floating annotation: `note at api.top_right [dx: 10, dy: -10] { text "deprecated" }`
+///
+/// The content is laid out like a group and centered on the referenced anchor
+/// (plus the `dx`/`dy` offset) after constraints are solved, so it follows the
+/// target wherever it ends up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoteDecl {
+    pub target: AnchorReference,
+    pub modifiers: Vec<Spanned<StyleModifier>>,
+    pub children: Vec<Spanned<Statement>>,
+}
+
+/// Repetition: `repeat 5 as i { rect item_$i [x: $i * 40] }`
+///
+/// The body is instantiated `count` times with `$i` bound to 0, 1, ... and
+/// substituted into names, strings and numeric expressions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepeatDecl {
+    pub count: Spanned<NumExpr>,
+    /// Loop variable name, without the `$`
+    pub variable: Spanned<Identifier>,
+    pub body: Vec<Spanned<Statement>>,
+}
+
+/// Conditional: `if with_cache { rect cache } else { rect placeholder }`
+///
+/// Evaluated when the enclosing template is instantiated: the condition names
+/// a template parameter and the branch is chosen from its value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfDecl {
+    pub condition: Spanned<Identifier>,
+    pub then_body: Vec<Spanned<Statement>>,
+    /// Empty without an `else`; `else if` is a nested `If`
+    pub else_body: Vec<Spanned<Statement>>,
+}
+
+/// Variable binding: `let base = 40`, used as `[width: base * 2]`
+///
+/// The variable is visible to the statements after it in the same block and
+/// in nested blocks. Values can refer to earlier variables and, inside a
+/// template body, to the template's numeric parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LetDecl {
+    pub name: Spanned<Identifier>,
+    pub value: Spanned<NumExpr>,
+}
+
+/// Diagram heading or footnote: `title "Request flow" [font_size: 24]`
+///
+/// Titles are centered above everything else and captions below it, after
+/// constraints are solved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TitleDecl {
+    pub kind: TitleKind,
+    pub text: Spanned<String>,
+    pub modifiers: Vec<Spanned<StyleModifier>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TitleKind {
+    Title,
+    Caption,
+}
+
+impl TitleKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TitleKind::Title => "title",
+            TitleKind::Caption => "caption",
+        }
+    }
+}
+
+/// Color key: `legend [position: top_right] { entry accent "API calls" }`
+///
+/// The legend is placed beside the diagram at one of its corners
+/// (`bottom_right` by default), with a swatch per entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegendDecl {
+    pub modifiers: Vec<Spanned<StyleModifier>>,
+    pub entries: Vec<Spanned<LegendEntry>>,
+}
+
+/// One legend row: `entry <color> "label" [swatch modifiers]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LegendEntry {
+    pub color: Spanned<StyleValue>,
+    pub label: Spanned<String>,
+    pub modifiers: Vec<Spanned<StyleModifier>>,
+}
+
+/// Document-level output size: `canvas [width: 800, height: 600]`
+///
+/// Keys are `width`, `height`, `scale` and `fit` (`contain` or `cover`);
+/// they fill in whatever the SVG configuration leaves unset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanvasDecl {
+    pub modifiers: Vec<Spanned<StyleModifier>>,
 }
 
 /// Keyframe declaration (Feature 011)
@@ -268,7 +463,30 @@ pub enum KeyframeOp {
     },
 }
 
-/// Position constraint
+/// Animation of one element property, rendered as an SVG `<animate>`:
+/// `animate box [property: opacity, from: 0, to: 1, duration: 2s]`
+///
+/// Keys are `property`, `from`, `to` (required), `duration`, `delay` and
+/// `repeat` (a count or `indefinite`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimateDecl {
+    pub target: Spanned<Identifier>,
+    pub modifiers: Vec<Spanned<StyleModifier>>,
+}
+
+/// Statements revealed from one presentation step onwards: `step 2 { ... }`
+///
+/// The body is laid out together with the rest of the document, so every
+/// step keeps the same geometry; rendering step N shows everything outside
+/// steps plus the bodies of steps 1 to N.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepDecl {
+    pub number: Spanned<u32>,
+    pub children: Vec<Spanned<Statement>>,
+}
+
+/// Position constraint (legacy; `constrain a right_of b` is the solver-based
+/// replacement and new features build on that)
 /// Supports both relational positioning and direct offsets:
 /// - `place a right-of b` - relative positioning
 /// - `place a [x: 10, y: 20]` - absolute or offset positioning
@@ -342,10 +560,56 @@ pub enum StyleKey {
     Custom(String),
 }
 
+/// Length unit written after a number: `width: 20mm`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Px,
+    Mm,
+    Cm,
+    In,
+    Pt,
+}
+
+impl Unit {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "px" => Some(Unit::Px),
+            "mm" => Some(Unit::Mm),
+            "cm" => Some(Unit::Cm),
+            "in" => Some(Unit::In),
+            "pt" => Some(Unit::Pt),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Unit::Px => "px",
+            Unit::Mm => "mm",
+            Unit::Cm => "cm",
+            Unit::In => "in",
+            Unit::Pt => "pt",
+        }
+    }
+
+    /// Layout pixels per unit, at 96 pixels per inch as in CSS
+    pub fn px(self) -> f64 {
+        match self {
+            Unit::Px => 1.0,
+            Unit::Mm => 96.0 / 25.4,
+            Unit::Cm => 96.0 / 2.54,
+            Unit::In => 96.0,
+            Unit::Pt => 96.0 / 72.0,
+        }
+    }
+}
+
 /// Style values
 #[derive(Debug, Clone, PartialEq)]
 pub enum StyleValue {
     Color(ColorValue),
+    /// A number with an optional [`Unit`] name; layout works in pixels, so
+    /// units are converted before layout
     Number {
         value: f64,
         unit: Option<String>,
@@ -356,6 +620,83 @@ pub enum StyleValue {
     Identifier(Identifier),
     /// List of identifiers (for `[via: c1, c2, c3]` syntax - Feature 008)
     IdentifierList(Vec<Identifier>),
+    /// Function call such as `fade(accent, transparent)`
+    Function {
+        name: String,
+        args: Vec<Spanned<StyleValue>>,
+    },
+    /// Arithmetic over loop variables (`$i * 40 + 10`), evaluated when the
+    /// enclosing `repeat` is expanded
+    Expr(NumExpr),
+}
+
+/// Numeric expression over `let` variables, template parameters and
+/// `repeat` loop variables
+#[derive(Debug, Clone, PartialEq)]
+pub enum NumExpr {
+    Number(f64),
+    /// Variable reference; loop variables keep their leading `$`
+    Var(String),
+    Binary {
+        op: BinaryOp,
+        lhs: Box<NumExpr>,
+        rhs: Box<NumExpr>,
+    },
+}
+
+/// Arithmetic operator in a [`NumExpr`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl NumExpr {
+    /// Evaluate with `lookup` resolving variable names (as written, with any `$`)
+    pub fn eval(&self, lookup: &dyn Fn(&str) -> Option<f64>) -> Result<f64, String> {
+        match self {
+            NumExpr::Number(n) => Ok(*n),
+            NumExpr::Var(name) => lookup(name).ok_or_else(|| {
+                if name.starts_with('$') {
+                    format!("undefined loop variable '{}'", name)
+                } else {
+                    format!("undefined variable '{}'", name)
+                }
+            }),
+            NumExpr::Binary { op, lhs, rhs } => {
+                let (l, r) = (lhs.eval(lookup)?, rhs.eval(lookup)?);
+                match op {
+                    BinaryOp::Add => Ok(l + r),
+                    BinaryOp::Sub => Ok(l - r),
+                    BinaryOp::Mul => Ok(l * r),
+                    BinaryOp::Div if r == 0.0 => Err("division by zero".to_string()),
+                    BinaryOp::Div => Ok(l / r),
+                }
+            }
+        }
+    }
+
+    /// Replace the variables `lookup` knows by their expressions and fold
+    /// constant subexpressions; other variables are left in place
+    pub fn substitute(&self, lookup: &dyn Fn(&str) -> Option<NumExpr>) -> NumExpr {
+        match self {
+            NumExpr::Number(_) => self.clone(),
+            NumExpr::Var(name) => lookup(name).unwrap_or_else(|| self.clone()),
+            NumExpr::Binary { op, lhs, rhs } => {
+                let expr = NumExpr::Binary {
+                    op: *op,
+                    lhs: Box::new(lhs.substitute(lookup)),
+                    rhs: Box::new(rhs.substitute(lookup)),
+                };
+                match expr.eval(&|_| None) {
+                    Ok(n) => NumExpr::Number(n),
+                    Err(_) => expr,
+                }
+            }
+        }
+    }
 }
 
 // ============================================
@@ -375,13 +716,65 @@ pub enum TemplateSourceType {
     Raster,
 }
 
-/// Parameter definition with default value
+/// Parameter definition with default value: `size: 50` or `pins: number 8`
 #[derive(Debug, Clone, PartialEq)]
 pub struct ParameterDef {
     pub name: Spanned<Identifier>,
+    /// Declared type; arguments are checked against it on instantiation
+    pub param_type: Option<Spanned<ParamType>>,
     pub default_value: Spanned<StyleValue>,
 }
 
+/// Declared type of a template parameter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamType {
+    Number,
+    String,
+    Color,
+    Bool,
+}
+
+impl ParamType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "number" => Some(Self::Number),
+            "string" => Some(Self::String),
+            "color" => Some(Self::Color),
+            "bool" => Some(Self::Bool),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Number => "number",
+            Self::String => "string",
+            Self::Color => "color",
+            Self::Bool => "bool",
+        }
+    }
+
+    /// Whether `value` is acceptable for a parameter of this type
+    pub fn accepts(&self, value: &StyleValue) -> bool {
+        match (self, value) {
+            (Self::Number, StyleValue::Number { .. } | StyleValue::Expr(_)) => true,
+            (Self::String, StyleValue::String(_)) => true,
+            (
+                Self::Color,
+                StyleValue::Color(_)
+                | StyleValue::Function { .. }
+                | StyleValue::Keyword(_)
+                | StyleValue::Identifier(_),
+            ) => true,
+            (Self::Bool, StyleValue::Number { value, .. }) => *value == 0.0 || *value == 1.0,
+            (Self::Bool, StyleValue::Identifier(Identifier(v)) | StyleValue::Keyword(v)) => {
+                matches!(v.as_str(), "true" | "false" | "yes" | "no")
+            }
+            _ => false,
+        }
+    }
+}
+
 /// Template declaration
 #[derive(Debug, Clone, PartialEq)]
 pub struct TemplateDecl {
@@ -392,12 +785,33 @@ pub struct TemplateDecl {
     pub body: Option<Vec<Spanned<Statement>>>,
 }
 
-/// Template instance: template_name "instance_name" [params]
+/// Template instance: template_name "instance_name" [params] { children }
 #[derive(Debug, Clone, PartialEq)]
 pub struct TemplateInstance {
     pub template_name: Spanned<Identifier>,
     pub instance_name: Spanned<Identifier>,
     pub arguments: Vec<(Spanned<Identifier>, Spanned<StyleValue>)>,
+    /// Statements that replace the template's `slot`; empty without a block
+    pub children: Vec<Spanned<Statement>>,
+}
+
+/// Embedded document: embed "other.ail" as name [modifiers]
+///
+/// The other document is rendered on its own and placed as a single element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbedDecl {
+    pub path: Spanned<String>,
+    pub name: Spanned<Identifier>,
+    pub modifiers: Vec<Spanned<StyleModifier>>,
+}
+
+/// Template library import: import "lib/electronics.ail" as elec
+///
+/// The library's templates become available as `elec.name`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportDecl {
+    pub path: Spanned<String>,
+    pub alias: Spanned<Identifier>,
 }
 
 /// Export declaration: export port1, port2
@@ -590,12 +1004,73 @@ pub enum ConstraintExpr {
         elements: Vec<Spanned<Identifier>>,
         padding: Option<f64>,
     },
+    /// a.prop = 0.5 * b.prop + 0.5 * c.prop + constant
+    ///
+    /// Arithmetic right-hand sides are normalized to a linear combination.
+    Linear {
+        left: PropertyRef,
+        terms: Vec<LinearTerm>,
+        constant: f64,
+    },
+    /// equal_width a, b, c or equal_height a, b, c
+    EqualSize {
+        dimension: SizeDimension,
+        elements: Vec<Spanned<Identifier>>,
+    },
+    /// subject right_of anchor [gap: N] (also left_of, above, below)
+    ///
+    /// Places the subject beside the anchor with `gap` clearance between the
+    /// facing edges and aligns the other axis, like `place` but in the solver.
+    Relative {
+        subject: Spanned<Identifier>,
+        relation: PositionRelation,
+        anchor: Spanned<Identifier>,
+        gap: Option<f64>,
+    },
+}
+
+/// One `coefficient * element.property` term of a linear constraint
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearTerm {
+    pub coefficient: f64,
+    pub property: PropertyRef,
+}
+
+/// Dimension shared by an equal-size constraint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeDimension {
+    Width,
+    Height,
+}
+
+/// Solver strength of a user constraint (`[strength: weak]`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstraintStrength {
+    Weak,
+    Medium,
+    Strong,
+    Required,
+}
+
+impl ConstraintStrength {
+    /// Parse a strength keyword
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "weak" => Some(Self::Weak),
+            "medium" => Some(Self::Medium),
+            "strong" => Some(Self::Strong),
+            "required" => Some(Self::Required),
+            _ => None,
+        }
+    }
 }
 
 /// Constrain statement declaration
 #[derive(Debug, Clone, PartialEq)]
 pub struct ConstrainDecl {
     pub expr: ConstraintExpr,
+    /// Explicit solver strength; `None` uses the constraint's default
+    pub strength: Option<ConstraintStrength>,
 }
 
 // ============================================
@@ -719,31 +1194,48 @@ pub struct PathDecl {
 // ============================================
 
 /// Reference to an element with optional anchor name (T003)
-/// Used in connections: `element.anchor` or just `element`
+/// Used in connections: `element.anchor`, `element.anchor+10` or just `element`
 #[derive(Debug, Clone, PartialEq)]
 pub struct AnchorReference {
+    /// Named containers written before the element: `outer.inner` in
+    /// `outer.inner.x.left`. Scope resolution folds them into `element`
+    /// (template resolution, for paths into template instances).
+    pub scope: Vec<Spanned<Identifier>>,
     /// The element being referenced
     pub element: Spanned<Identifier>,
     /// Optional anchor name (e.g., "top", "left", "input")
     pub anchor: Option<Spanned<String>>,
+    /// Shift along the anchor's edge: right on top/bottom edges, down on
+    /// left/right edges (`a.right+10`)
+    pub offset: Option<Spanned<f64>>,
 }
 
 impl AnchorReference {
     /// Create a reference to just an element (anchor auto-detect)
     pub fn element_only(element: Spanned<Identifier>) -> Self {
         Self {
+            scope: vec![],
             element,
             anchor: None,
+            offset: None,
         }
     }
 
     /// Create a reference to an element with a specific anchor
     pub fn with_anchor(element: Spanned<Identifier>, anchor: Spanned<String>) -> Self {
         Self {
+            scope: vec![],
             element,
             anchor: Some(anchor),
+            offset: None,
         }
     }
+
+    /// Shift the attachment point along the anchor's edge
+    pub fn with_offset(mut self, offset: Spanned<f64>) -> Self {
+        self.offset = Some(offset);
+        self
+    }
 }
 
 /// Cardinal direction for anchor direction specification (T004)
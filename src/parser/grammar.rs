@@ -1,5 +1,6 @@
 //! Parser implementation using chumsky
 
+use chumsky::error::RichReason;
 use chumsky::input::{Stream, ValueInput};
 use chumsky::prelude::*;
 
@@ -13,12 +14,115 @@ use crate::parser::lexer::Token;
 // - ArcParams, SweepDirection
 // All are available via the ast::* glob import above
 
-/// Helper enum for parsing constraint equality expressions
+/// Right-hand side of a constraint equality, normalized to `sum(coef * prop) + constant`
 #[derive(Debug, Clone)]
-enum ConstraintExprKind {
-    Property(PropertyRef),
-    PropertyWithOffset(PropertyRef, f64),
-    Constant(f64),
+struct LinearForm {
+    terms: Vec<LinearTerm>,
+    constant: f64,
+}
+
+/// Binary operator in a constraint arithmetic expression
+#[derive(Debug, Clone, Copy)]
+enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl LinearForm {
+    fn constant(value: f64) -> Self {
+        Self {
+            terms: vec![],
+            constant: value,
+        }
+    }
+
+    fn property(property: PropertyRef) -> Self {
+        Self {
+            terms: vec![LinearTerm {
+                coefficient: 1.0,
+                property,
+            }],
+            constant: 0.0,
+        }
+    }
+
+    fn scale(mut self, factor: f64) -> Self {
+        for term in &mut self.terms {
+            term.coefficient *= factor;
+        }
+        self.constant *= factor;
+        self
+    }
+
+    /// Apply a binary operator, rejecting anything that is not linear
+    fn combine(
+        lhs: Result<Self, String>,
+        op: ArithOp,
+        rhs: Result<Self, String>,
+    ) -> Result<Self, String> {
+        let (lhs, rhs) = (lhs?, rhs?);
+        match op {
+            ArithOp::Add => Ok(Self {
+                terms: lhs.terms.into_iter().chain(rhs.terms).collect(),
+                constant: lhs.constant + rhs.constant,
+            }),
+            ArithOp::Sub => Self::combine(Ok(lhs), ArithOp::Add, Ok(rhs.scale(-1.0))),
+            ArithOp::Mul if rhs.terms.is_empty() => Ok(lhs.scale(rhs.constant)),
+            ArithOp::Mul if lhs.terms.is_empty() => Ok(rhs.scale(lhs.constant)),
+            ArithOp::Mul => Err(
+                "constraints must be linear: cannot multiply two element properties".to_string(),
+            ),
+            ArithOp::Div if !rhs.terms.is_empty() => {
+                Err("constraints must be linear: cannot divide by an element property".to_string())
+            }
+            ArithOp::Div if rhs.constant == 0.0 => {
+                Err("division by zero in constraint".to_string())
+            }
+            ArithOp::Div => Ok(lhs.scale(1.0 / rhs.constant)),
+        }
+    }
+
+    /// Build the simplest constraint expression for `left = self`
+    fn into_constraint(self, left: PropertyRef) -> Result<ConstraintExpr, String> {
+        let Self {
+            mut terms,
+            constant,
+        } = self;
+        if terms.is_empty() {
+            return Ok(ConstraintExpr::Constant {
+                left,
+                value: constant,
+            });
+        }
+        if terms.len() == 1 && terms[0].coefficient == 1.0 {
+            let right = terms.remove(0).property;
+            return Ok(if constant == 0.0 {
+                ConstraintExpr::Equal { left, right }
+            } else {
+                ConstraintExpr::EqualWithOffset {
+                    left,
+                    right,
+                    offset: constant,
+                }
+            });
+        }
+        let is_anchor = |p: &PropertyRef| {
+            matches!(
+                p.property.node,
+                ConstraintProperty::AnchorX(_) | ConstraintProperty::AnchorY(_)
+            )
+        };
+        if is_anchor(&left) || terms.iter().any(|t| is_anchor(&t.property)) {
+            return Err("anchor coordinates cannot be used in arithmetic constraints; use `a.prop = b.anchor_x + offset`".to_string());
+        }
+        Ok(ConstraintExpr::Linear {
+            left,
+            terms,
+            constant,
+        })
+    }
 }
 
 /// Check if an identifier is a symbolic color category
@@ -42,22 +146,85 @@ fn is_lightness_modifier(ident: &str) -> Option<Lightness> {
     }
 }
 
+/// Check if an identifier is a time unit (`2s`, `500ms` in animations)
+fn is_time_unit(ident: &str) -> bool {
+    matches!(ident, "s" | "ms")
+}
+
 /// Parse DSL source code into an AST
 pub fn parse(input: &str) -> Result<Document, Vec<crate::ParseError>> {
-    let len = input.len();
+    match parse_recovering(input) {
+        (Some(doc), errors) if errors.is_empty() => Ok(doc),
+        (_, errors) => Err(errors),
+    }
+}
+
+/// Parse as much of the source as possible
+///
+/// A statement that fails to parse is skipped up to the next statement
+/// boundary and reported, and parsing carries on, so the document holds
+/// every statement that did parse. The document is `None` only when the
+/// source is too broken to recover from (e.g. an unmatched `}`).
+pub fn parse_recovering(input: &str) -> (Option<Document>, Vec<crate::ParseError>) {
+    let (doc, errors, _) = parse_skipping(input);
+    (doc, errors)
+}
+
+/// Message of the marker the recovery parsers emit for the source they skip
+const RECOVERED: &str = "\0recovered";
 
-    // Create a logos lexer and convert to token stream
-    let token_iter = crate::parser::lexer::lex(input).map(|(tok, span)| (tok, span.into()));
+/// Like [`parse_recovering`], also returning the source span of every
+/// statement that was skipped
+pub(crate) fn parse_skipping(input: &str) -> (Option<Document>, Vec<crate::ParseError>, Vec<Span>) {
+    let len = input.len();
+    let line_starts = statement_line_starts(input);
+
+    // Lex once; comments are set aside as trivia and the rest is parsed
+    let mut comments = vec![];
+    let mut tokens = vec![];
+    let mut previous_end = None;
+    for (tok, span) in crate::parser::lexer::lex_with_comments(input) {
+        match tok {
+            Token::LineComment(text) | Token::BlockComment(text) => {
+                // Trailing when nothing but spaces separates it from the previous token
+                let after = previous_end.filter(|&end| !input[end..span.start].contains('\n'));
+                comments.push(Spanned::new(Comment { text, after }, span.clone()));
+            }
+            tok => tokens.push((tok, SimpleSpan::from(span.clone()))),
+        }
+        previous_end = Some(span.end);
+    }
+    let token_iter = tokens.into_iter();
 
     // Turn the token iterator into a stream that chumsky can use
     let token_stream = Stream::from_iter(token_iter)
         // Split (Token, SimpleSpan) into token and span parts
         .map((len..len).into(), |(t, s): (_, _)| (t, s));
 
-    document_parser()
+    let (doc, errors) = document_parser(&line_starts)
         .parse(token_stream)
-        .into_result()
-        .map_err(|errs| errs.into_iter().map(|e| e.into()).collect())
+        .into_output_errors();
+    let doc = doc.map(|doc| Document { comments, ..doc });
+    let (skipped, errors): (Vec<_>, Vec<_>) = errors
+        .into_iter()
+        .partition(|e| matches!(e.reason(), RichReason::Custom(m) if m == RECOVERED));
+    let mut skipped: Vec<Span> = skipped.iter().map(|e| span_range(e.span())).collect();
+    skipped.sort_by_key(|s| s.start);
+    (doc, errors.into_iter().map(|e| e.into()).collect(), skipped)
+}
+
+/// Offset of the first non-blank character of every line; a token there
+/// starts a new statement for error recovery
+fn statement_line_starts(input: &str) -> Vec<usize> {
+    let mut starts = vec![];
+    let mut offset = 0;
+    for line in input.split_inclusive('\n') {
+        if let Some(indent) = line.find(|c: char| !c.is_whitespace()) {
+            starts.push(offset + indent);
+        }
+        offset += line.len();
+    }
+    starts
 }
 
 /// Helper to extract span range from chumsky's MapExtra
@@ -65,6 +232,14 @@ fn span_range(e: &impl chumsky::span::Span<Offset = usize>) -> std::ops::Range<u
     e.start()..e.end()
 }
 
+/// One line of a `class` body
+#[derive(Debug, Clone)]
+enum ClassMember {
+    Attribute(Spanned<String>),
+    Operation(Spanned<String>),
+    Port(Spanned<PortDecl>),
+}
+
 // ==================== Path Shape Parsers (Feature 007) ====================
 
 /// Parsed modifier value - can be a number, sweep direction, or identifier (Feature 008)
@@ -114,11 +289,106 @@ impl ParsedArcModifiers {
     }
 }
 
-fn document_parser<'a, I>() -> impl Parser<'a, I, Document, extra::Err<Rich<'a, Token>>> + Clone
+/// The modifier key written as `name`
+pub(crate) fn style_key(name: &str) -> StyleKey {
+    match name {
+        "label" => StyleKey::Label,
+        "role" => StyleKey::Role,
+        "fill" => StyleKey::Fill,
+        "stroke" => StyleKey::Stroke,
+        "stroke_width" => StyleKey::StrokeWidth,
+        "opacity" => StyleKey::Opacity,
+        "font_size" => StyleKey::FontSize,
+        "class" => StyleKey::Class,
+        "gap" => StyleKey::Gap,
+        "size" => StyleKey::Size,
+        "width" => StyleKey::Width,
+        "height" => StyleKey::Height,
+        "routing" => StyleKey::Routing,
+        "label_position" => StyleKey::LabelPosition,
+        "x" => StyleKey::X,
+        "y" => StyleKey::Y,
+        "stroke_dasharray" => StyleKey::StrokeDasharray,
+        "rotation" => StyleKey::Rotation,
+        "label_at" => StyleKey::LabelAt,
+        "label_offset" => StyleKey::LabelOffset,
+        "z_order" | "z" => StyleKey::ZOrder,
+        other => StyleKey::Custom(other.to_string()),
+    }
+}
+
+/// Parse a single modifier value such as `accent-dark`, `20mm` or
+/// `darken(accent, 20%)`; `None` unless the whole input is one value
+pub(crate) fn parse_style_value(input: &str) -> Option<StyleValue> {
+    let len = input.len();
+    let tokens: Vec<_> = crate::parser::lexer::lex(input)
+        .map(|(tok, span)| (tok, SimpleSpan::from(span)))
+        .collect();
+    let token_stream = Stream::from_iter(tokens).map((len..len).into(), |(t, s): (_, _)| (t, s));
+    style_value_parser()
+        .then_ignore(end())
+        .parse(token_stream)
+        .into_output()
+        .map(|value| value.node)
+}
+
+/// Arithmetic over numbers and `$var` loop variables: `$i * 40 + 10`
+fn num_expr_parser<'a, I>() -> impl Parser<'a, I, NumExpr, extra::Err<Rich<'a, Token>>> + Clone
+where
+    I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
+{
+    recursive(|num_expr| {
+        let atom = choice((
+            select! { Token::Number(n) => NumExpr::Number(n) },
+            select! { Token::Ident(s) => NumExpr::Var(s) },
+            num_expr.delimited_by(just(Token::ParenOpen), just(Token::ParenClose)),
+        ));
+        let unary = just(Token::Minus)
+            .or_not()
+            .then(atom)
+            .map(|(neg, expr)| match (neg, expr) {
+                (None, expr) => expr,
+                (Some(_), NumExpr::Number(n)) => NumExpr::Number(-n),
+                (Some(_), expr) => NumExpr::Binary {
+                    op: BinaryOp::Mul,
+                    lhs: Box::new(NumExpr::Number(-1.0)),
+                    rhs: Box::new(expr),
+                },
+            });
+        let binary = |lhs, (op, rhs)| NumExpr::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        };
+        let product = unary.clone().foldl(
+            choice((
+                just(Token::Star).to(BinaryOp::Mul),
+                just(Token::Slash).to(BinaryOp::Div),
+            ))
+            .then(unary)
+            .repeated(),
+            binary,
+        );
+        product.clone().foldl(
+            choice((
+                just(Token::Plus).to(BinaryOp::Add),
+                just(Token::Minus).to(BinaryOp::Sub),
+            ))
+            .then(product)
+            .repeated(),
+            binary,
+        )
+    })
+    .boxed()
+}
+
+/// A modifier value: a color, number, length, expression, string, keyword,
+/// identifier or function call
+fn style_value_parser<'a, I>(
+) -> impl Parser<'a, I, Spanned<StyleValue>, extra::Err<Rich<'a, Token>>> + Clone
 where
     I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
 {
-    // Basic token parsers
     let identifier = select! {
         Token::Ident(s) => Identifier::new(s),
     }
@@ -134,41 +404,6 @@ where
     }
     .map_with(|n, e| Spanned::new(n, span_range(&e.span())));
 
-    // Style key/value parsers
-    // Note: We need to handle keyword tokens explicitly since they're not identifiers
-    let style_key = choice((
-        // Handle the "label" keyword token explicitly
-        just(Token::Label).map_with(|_, e| Spanned::new(StyleKey::Label, span_range(&e.span()))),
-        // Handle the "role" keyword token explicitly
-        just(Token::Role).map_with(|_, e| Spanned::new(StyleKey::Role, span_range(&e.span()))),
-        // Handle all other style keys as identifiers
-        identifier.map(|id| {
-            let key = match id.node.as_str() {
-                "fill" => StyleKey::Fill,
-                "stroke" => StyleKey::Stroke,
-                "stroke_width" => StyleKey::StrokeWidth,
-                "opacity" => StyleKey::Opacity,
-                "font_size" => StyleKey::FontSize,
-                "class" => StyleKey::Class,
-                "gap" => StyleKey::Gap,
-                "size" => StyleKey::Size,
-                "width" => StyleKey::Width,
-                "height" => StyleKey::Height,
-                "routing" => StyleKey::Routing,
-                "label_position" => StyleKey::LabelPosition,
-                "x" => StyleKey::X,
-                "y" => StyleKey::Y,
-                "stroke_dasharray" => StyleKey::StrokeDasharray,
-                "rotation" => StyleKey::Rotation,
-                "label_at" => StyleKey::LabelAt,
-                "label_offset" => StyleKey::LabelOffset,
-                "z_order" => StyleKey::ZOrder,
-                other => StyleKey::Custom(other.to_string()),
-            };
-            Spanned::new(key, id.span)
-        }),
-    ));
-
     // Parse a color category - identifier or "text" keyword (since text is reserved)
     let color_category = choice((
         // "text" keyword token maps to Text category
@@ -201,110 +436,201 @@ where
             }
         });
 
-    let style_value = choice((
-        // Hex colors like #ff0000 or #f00
-        select! { Token::HexColor(c) => StyleValue::Color(ColorValue::Hex(c)) }
-            .map_with(|v, e| Spanned::new(v, span_range(&e.span()))),
-        // Symbolic colors (must come before plain identifiers)
-        symbolic_color.map_with(|v, e| Spanned::new(v, span_range(&e.span()))),
-        // Numbers (including negative via Minus token)
-        just(Token::Minus)
-            .or_not()
-            .then(number)
-            .map_with(|(neg, n), e| {
-                let value = if neg.is_some() { -n.node } else { n.node };
+    let num_expr = num_expr_parser();
+
+    recursive(|style_value| {
+        // Function calls like fade(accent, transparent)
+        let function = identifier
+            .then(
+                style_value
+                    .separated_by(just(Token::Comma))
+                    .collect::<Vec<_>>()
+                    .delimited_by(just(Token::ParenOpen), just(Token::ParenClose)),
+            )
+            .map_with(|(name, args), e| {
                 Spanned::new(
-                    StyleValue::Number { value, unit: None },
+                    StyleValue::Function {
+                        name: name.node.0,
+                        args,
+                    },
                     span_range(&e.span()),
                 )
+            });
+
+        choice((
+            // Hex colors like #ff0000 or #f00
+            select! { Token::HexColor(c) => StyleValue::Color(ColorValue::Hex(c)) }
+                .map_with(|v, e| Spanned::new(v, span_range(&e.span()))),
+            function,
+            // Symbolic colors (must come before plain identifiers)
+            symbolic_color.map_with(|v, e| Spanned::new(v, span_range(&e.span()))),
+            // Lengths with a unit: `20mm`, `0.5in`, `-2pt`; percentages: `20%`
+            just(Token::Minus)
+                .or_not()
+                .then(select! { Token::Number(n) => n })
+                .then(choice((
+                    select! { Token::Ident(u) if Unit::parse(&u).is_some() || is_time_unit(&u) => u },
+                    just(Token::Percent).to("%".to_string()),
+                )))
+                .map_with(|((minus, value), unit), e| {
+                    let value = if minus.is_some() { -value } else { value };
+                    Spanned::new(
+                        StyleValue::Number {
+                            value,
+                            unit: Some(unit),
+                        },
+                        span_range(&e.span()),
+                    )
+                }),
+            // Numbers (including negative via Minus token) and arithmetic on
+            // variables; expressions without variables fold to a number. A lone
+            // name is left to the identifier alternatives below.
+            num_expr.clone().try_map(|expr, span| {
+                let value = match expr.eval(&|_| None) {
+                    Ok(value) => StyleValue::Number { value, unit: None },
+                    Err(_) if matches!(&expr, NumExpr::Var(name) if !name.starts_with('$')) => {
+                        return Err(Rich::custom(span, "not an expression"));
+                    }
+                    Err(_) => StyleValue::Expr(expr),
+                };
+                Ok(Spanned::new(value, span_range(&span)))
             }),
-        // Quoted strings
-        string_literal.map(|s| Spanned::new(StyleValue::String(s.node), s.span)),
-        // Handle "label" keyword as a keyword value (for [role: label])
-        just(Token::Label).map_with(|_, e| {
-            Spanned::new(
-                StyleValue::Keyword("label".to_string()),
-                span_range(&e.span()),
-            )
-        }),
-        // Handle edge keywords as keyword values (for [label_position: left], etc.)
-        just(Token::Left).map_with(|_, e| {
-            Spanned::new(
-                StyleValue::Keyword("left".to_string()),
-                span_range(&e.span()),
-            )
-        }),
-        just(Token::Right).map_with(|_, e| {
-            Spanned::new(
-                StyleValue::Keyword("right".to_string()),
-                span_range(&e.span()),
-            )
-        }),
-        just(Token::Top).map_with(|_, e| {
-            Spanned::new(
-                StyleValue::Keyword("top".to_string()),
-                span_range(&e.span()),
-            )
-        }),
-        just(Token::Bottom).map_with(|_, e| {
-            Spanned::new(
-                StyleValue::Keyword("bottom".to_string()),
-                span_range(&e.span()),
-            )
-        }),
-        just(Token::HorizontalCenter).map_with(|_, e| {
-            Spanned::new(
-                StyleValue::Keyword("horizontal_center".to_string()),
-                span_range(&e.span()),
-            )
-        }),
-        just(Token::VerticalCenter).map_with(|_, e| {
-            Spanned::new(
-                StyleValue::Keyword("vertical_center".to_string()),
-                span_range(&e.span()),
-            )
-        }),
-        // Center token (can be used in style values like [label_position: center])
-        just(Token::Center).map_with(|_, e| {
-            Spanned::new(
-                StyleValue::Keyword("center".to_string()),
-                span_range(&e.span()),
-            )
-        }),
-        // center_x and center_y tokens
-        just(Token::CenterXProp).map_with(|_, e| {
-            Spanned::new(
-                StyleValue::Keyword("center_x".to_string()),
-                span_range(&e.span()),
-            )
-        }),
-        just(Token::CenterYProp).map_with(|_, e| {
+            // Quoted strings
+            string_literal.map(|s| Spanned::new(StyleValue::String(s.node), s.span)),
+            // Handle "label" keyword as a keyword value (for [role: label])
+            just(Token::Label).map_with(|_, e| {
+                Spanned::new(
+                    StyleValue::Keyword("label".to_string()),
+                    span_range(&e.span()),
+                )
+            }),
+            // Handle edge keywords as keyword values (for [label_position: left], etc.)
+            just(Token::Left).map_with(|_, e| {
+                Spanned::new(
+                    StyleValue::Keyword("left".to_string()),
+                    span_range(&e.span()),
+                )
+            }),
+            just(Token::Right).map_with(|_, e| {
+                Spanned::new(
+                    StyleValue::Keyword("right".to_string()),
+                    span_range(&e.span()),
+                )
+            }),
+            just(Token::Top).map_with(|_, e| {
+                Spanned::new(
+                    StyleValue::Keyword("top".to_string()),
+                    span_range(&e.span()),
+                )
+            }),
+            just(Token::Bottom).map_with(|_, e| {
+                Spanned::new(
+                    StyleValue::Keyword("bottom".to_string()),
+                    span_range(&e.span()),
+                )
+            }),
+            just(Token::HorizontalCenter).map_with(|_, e| {
+                Spanned::new(
+                    StyleValue::Keyword("horizontal_center".to_string()),
+                    span_range(&e.span()),
+                )
+            }),
+            just(Token::VerticalCenter).map_with(|_, e| {
+                Spanned::new(
+                    StyleValue::Keyword("vertical_center".to_string()),
+                    span_range(&e.span()),
+                )
+            }),
+            // Center token (can be used in style values like [label_position: center])
+            just(Token::Center).map_with(|_, e| {
+                Spanned::new(
+                    StyleValue::Keyword("center".to_string()),
+                    span_range(&e.span()),
+                )
+            }),
+            // center_x and center_y tokens
+            just(Token::CenterXProp).map_with(|_, e| {
+                Spanned::new(
+                    StyleValue::Keyword("center_x".to_string()),
+                    span_range(&e.span()),
+                )
+            }),
+            just(Token::CenterYProp).map_with(|_, e| {
+                Spanned::new(
+                    StyleValue::Keyword("center_y".to_string()),
+                    span_range(&e.span()),
+                )
+            }),
+            // Identifiers can be either keyword values OR identifier references
+            // Certain common keywords are recognized and stored as Keywords for backward compatibility
+            identifier.map(|id| {
+                let value = match id.node.as_str() {
+                    // Common style value keywords (not alignment edges)
+                    // Feature 008: added "curved" for curved routing
+                    "center" | "direct" | "orthogonal" | "curved" | "bundled" | "none" | "auto"
+                    | "solid" | "dashed" | "dotted" | "hidden" | "bold" | "italic" | "normal"
+                    | "start" | "middle" | "end" => StyleValue::Keyword(id.node.0.clone()),
+                    // Color keywords
+                    "red" | "green" | "blue" | "black" | "white" | "gray" | "grey" | "yellow"
+                    | "orange" | "purple" | "pink" | "cyan" | "magenta" | "transparent" => {
+                        StyleValue::Keyword(id.node.0.clone())
+                    }
+                    // Everything else is an identifier reference (for [label: my_shape] syntax)
+                    _ => StyleValue::Identifier(id.node),
+                };
+                Spanned::new(value, id.span)
+            }),
+        ))
+        .boxed() // Feature 008: boxed() for faster compilation (chumsky trait solving)
+    })
+}
+
+fn document_parser<'a, I>(
+    line_starts: &'a [usize],
+) -> impl Parser<'a, I, Document, extra::Err<Rich<'a, Token>>> + Clone
+where
+    I: ValueInput<'a, Token = Token, Span = SimpleSpan>,
+{
+    // Basic token parsers
+    let identifier = select! {
+        Token::Ident(s) => Identifier::new(s),
+    }
+    .map_with(|id, e| Spanned::new(id, span_range(&e.span())));
+
+    let string_literal = select! {
+        Token::String(s) => s,
+    }
+    .map_with(|s, e| Spanned::new(s, span_range(&e.span())));
+
+    let number = select! {
+        Token::Number(n) => n,
+    }
+    .map_with(|n, e| Spanned::new(n, span_range(&e.span())));
+
+    // Style key/value parsers
+    // Note: We need to handle keyword tokens explicitly since they're not identifiers
+    let style_key = choice((
+        // Handle the "label" keyword token explicitly
+        just(Token::Label).map_with(|_, e| Spanned::new(StyleKey::Label, span_range(&e.span()))),
+        // Handle the "role" keyword token explicitly
+        just(Token::Role).map_with(|_, e| Spanned::new(StyleKey::Role, span_range(&e.span()))),
+        // `position` is a keyword for anchors; as a modifier key it places a legend
+        just(Token::Position).map_with(|_, e| {
             Spanned::new(
-                StyleValue::Keyword("center_y".to_string()),
+                StyleKey::Custom("position".to_string()),
                 span_range(&e.span()),
             )
         }),
-        // Identifiers can be either keyword values OR identifier references
-        // Certain common keywords are recognized and stored as Keywords for backward compatibility
-        identifier.map(|id| {
-            let value = match id.node.as_str() {
-                // Common style value keywords (not alignment edges)
-                // Feature 008: added "curved" for curved routing
-                "center" | "direct" | "orthogonal" | "curved" | "none" | "auto" | "solid"
-                | "dashed" | "dotted" | "hidden" | "bold" | "italic" | "normal" | "start"
-                | "middle" | "end" => StyleValue::Keyword(id.node.0.clone()),
-                // Color keywords
-                "red" | "green" | "blue" | "black" | "white" | "gray" | "grey" | "yellow"
-                | "orange" | "purple" | "pink" | "cyan" | "magenta" | "transparent" => {
-                    StyleValue::Keyword(id.node.0.clone())
-                }
-                // Everything else is an identifier reference (for [label: my_shape] syntax)
-                _ => StyleValue::Identifier(id.node),
-            };
-            Spanned::new(value, id.span)
+        // `from` is a keyword for imports; as a modifier key it starts an animation
+        just(Token::From).map_with(|_, e| {
+            Spanned::new(StyleKey::Custom("from".to_string()), span_range(&e.span()))
         }),
-    ))
-    .boxed(); // Feature 008: boxed() for faster compilation (chumsky trait solving)
+        // Handle all other style keys as identifiers
+        identifier.map(|id| Spanned::new(style_key(id.node.as_str()), id.span)),
+    ));
+
+    let num_expr = num_expr_parser();
+    let style_value = style_value_parser();
 
     let modifier = style_key
         .then_ignore(just(Token::Colon))
@@ -313,6 +639,21 @@ where
             Spanned::new(StyleModifier { key, value }, span_range(&e.span()))
         });
 
+    // Template instance arguments may be prefixed with the path of a child
+    // of the template to override its modifiers: `motor m1 [body.fill: red]`
+    let instance_argument = identifier
+        .then_ignore(just(Token::Dot))
+        .repeated()
+        .collect::<Vec<_>>()
+        .then(modifier.clone());
+
+    let instance_arguments = instance_argument
+        .separated_by(just(Token::Comma))
+        .allow_trailing()
+        .collect::<Vec<_>>()
+        .delimited_by(just(Token::BracketOpen), just(Token::BracketClose))
+        .boxed();
+
     let modifier_block = modifier
         .separated_by(just(Token::Comma))
         .allow_trailing()
@@ -329,24 +670,155 @@ where
         just(Token::Line).to(ShapeType::Line),
         just(Token::Icon)
             .ignore_then(string_literal)
-            .map(|s| ShapeType::Icon { icon_name: s.node }),
+            .map(|s| ShapeType::Icon {
+                icon_name: s.node,
+                svg: None,
+            }),
+        // `image` is contextual, so it stays usable as an element name
+        just(Token::Ident("image".into()))
+            .ignore_then(string_literal)
+            .map(|s| ShapeType::RasterImage {
+                path: s.node,
+                intrinsic_width: None,
+                intrinsic_height: None,
+            }),
         just(Token::Text)
             .ignore_then(string_literal)
             .map(|s| ShapeType::Text { content: s.node }),
+        // `bus` and `state` are contextual
+        just(Token::Ident("bus".into())).to(ShapeType::Bus),
+        just(Token::Ident("state".into())).to(ShapeType::State),
     ))
     .map_with(|st, e| Spanned::new(st, span_range(&e.span())));
 
-    // Shape declaration
+    // Port settings: side: left|right|top|bottom, at: <fraction>, marker: true|false
+    let port_setting = choice((
+        just(Token::Ident("side".into()))
+            .ignore_then(just(Token::Colon))
+            .ignore_then(choice((
+                just(Token::Left).to(Edge::Left),
+                just(Token::Right).to(Edge::Right),
+                just(Token::Top).to(Edge::Top),
+                just(Token::Bottom).to(Edge::Bottom),
+            )))
+            .map(|side| (Some(side), None, None)),
+        just(Token::Ident("at".into()))
+            .ignore_then(just(Token::Colon))
+            .ignore_then(number)
+            .map(|at| (None, Some(at.node), None)),
+        just(Token::Ident("marker".into()))
+            .ignore_then(just(Token::Colon))
+            .ignore_then(choice((
+                just(Token::Ident("true".into())).to(true),
+                just(Token::Ident("false".into())).to(false),
+            )))
+            .map(|marker| (None, None, Some(marker))),
+    ));
+
+    // Port declaration inside a shape body: port name [side: left, at: 0.25]
+    let port_decl = just(Token::Ident("port".into()))
+        .ignore_then(identifier)
+        .then(
+            port_setting
+                .separated_by(just(Token::Comma))
+                .allow_trailing()
+                .collect::<Vec<_>>()
+                .delimited_by(just(Token::BracketOpen), just(Token::BracketClose)),
+        )
+        .try_map(|(name, settings), span| {
+            let mut port = PortDecl {
+                name,
+                side: Edge::Right,
+                at: 0.5,
+                marker: false,
+            };
+            let mut has_side = false;
+            for (side, at, marker) in settings {
+                if let Some(side) = side {
+                    port.side = side;
+                    has_side = true;
+                }
+                port.at = at.unwrap_or(port.at);
+                port.marker = marker.unwrap_or(port.marker);
+            }
+            if !has_side {
+                return Err(Rich::custom(
+                    span,
+                    "port declaration requires 'side' modifier",
+                ));
+            }
+            if !(0.0..=1.0).contains(&port.at) {
+                return Err(Rich::custom(span, "port 'at' must be between 0 and 1"));
+            }
+            Ok(Spanned::new(port, span_range(&span)))
+        });
+
+    // Shape declaration, optionally with a body of ports
     let shape_decl = shape_type
         .then(identifier.or_not())
         .then(modifier_block.clone().or_not())
-        .map(|((shape_type, name), modifiers)| ShapeDecl {
+        .then(
+            port_decl
+                .clone()
+                .repeated()
+                .collect::<Vec<_>>()
+                .delimited_by(just(Token::BraceOpen), just(Token::BraceClose))
+                .or_not(),
+        )
+        .map(|(((shape_type, name), modifiers), ports)| ShapeDecl {
             shape_type,
             name,
             modifiers: modifiers.unwrap_or_default(),
+            ports: ports.unwrap_or_default(),
         })
         .boxed(); // boxed() for faster compilation
 
+    // UML class box: class User [mods] { attr "name: String" op "login()" }
+    // `class`, `attr` and `op` are contextual, like `state`
+    let class_member = choice((
+        just(Token::Ident("attr".into()))
+            .ignore_then(string_literal)
+            .map(ClassMember::Attribute),
+        just(Token::Ident("op".into()))
+            .ignore_then(string_literal)
+            .map(ClassMember::Operation),
+        port_decl.clone().map(ClassMember::Port),
+    ));
+    let class_decl = just(Token::Ident("class".into()))
+        .map_with(|_, e| span_range(&e.span()))
+        .then(identifier)
+        .then(modifier_block.clone().or_not())
+        .then(
+            class_member
+                .repeated()
+                .collect::<Vec<_>>()
+                .delimited_by(just(Token::BraceOpen), just(Token::BraceClose))
+                .or_not(),
+        )
+        .map(|(((keyword, name), modifiers), members)| {
+            let (mut attributes, mut operations, mut ports) = (vec![], vec![], vec![]);
+            for member in members.unwrap_or_default() {
+                match member {
+                    ClassMember::Attribute(text) => attributes.push(text),
+                    ClassMember::Operation(text) => operations.push(text),
+                    ClassMember::Port(port) => ports.push(port),
+                }
+            }
+            ShapeDecl {
+                shape_type: Spanned::new(
+                    ShapeType::Class {
+                        attributes,
+                        operations,
+                    },
+                    keyword,
+                ),
+                name: Some(name),
+                modifiers: modifiers.unwrap_or_default(),
+                ports,
+            }
+        })
+        .boxed();
+
     // Connection operators
     let connection_op = choice((
         just(Token::ArrowBoth).to(ConnectionDirection::Bidirectional),
@@ -368,21 +840,56 @@ where
     ))
     .map_with(|name, e| Spanned::new(name, span_range(&e.span())));
 
-    // Anchor reference parser: identifier { "." anchor_name }?
+    // Port offset along the anchor's edge: `+10` or `-5`
+    let anchor_offset = choice((
+        just(Token::Plus).ignore_then(number).map(|n| n.node),
+        just(Token::Minus).ignore_then(number).map(|n| -n.node),
+    ))
+    .map_with(|offset, e| Spanned::new(offset, span_range(&e.span())));
+
+    // Anchor reference parser: identifier { "." anchor_name } { offset }?
     // Parses either:
     //   - `element` -> AnchorReference with anchor=None
     //   - `element.anchor_name` -> AnchorReference with anchor=Some
+    //   - `element.anchor_name+10` -> ... and offset=Some
+    //   - `scope.element.anchor_name` -> ... and scope=[scope]
+    // Whether the last segment is an anchor or a child element is decided
+    // by scope resolution, which knows the declared names.
     let anchor_reference = identifier
-        .then(just(Token::Dot).ignore_then(anchor_name).or_not())
-        .map(|(element, anchor_opt)| match anchor_opt {
-            Some(anchor_name) => AnchorReference::with_anchor(element, anchor_name),
-            None => AnchorReference::element_only(element),
+        .then(
+            just(Token::Dot)
+                .ignore_then(anchor_name)
+                .repeated()
+                .at_least(1)
+                .collect::<Vec<_>>()
+                .then(anchor_offset.or_not())
+                .or_not(),
+        )
+        .map(|(first, rest)| match rest {
+            Some((mut segments, offset)) => {
+                let anchor_name = segments.pop().expect("at least one segment");
+                let mut path: Vec<_> = std::iter::once(first)
+                    .chain(
+                        segments
+                            .into_iter()
+                            .map(|s| Spanned::new(Identifier::new(s.node), s.span)),
+                    )
+                    .collect();
+                let element = path.pop().expect("path starts with an identifier");
+                let mut reference = AnchorReference::with_anchor(element, anchor_name);
+                reference.scope = path;
+                match offset {
+                    Some(offset) => reference.with_offset(offset),
+                    None => reference,
+                }
+            }
+            None => AnchorReference::element_only(first),
         });
 
     // Connection declaration (supports chained: a -> b -> c [modifiers])
     // Feature 009: Now supports anchor syntax (a.right -> b.left)
     // Feature 011: Now supports named connections (a -> b as name [modifiers])
-    let connection_name = just(Token::As).ignore_then(identifier.clone());
+    let connection_name = just(Token::As).ignore_then(identifier);
 
     let connection_decl = anchor_reference
         .clone()
@@ -422,6 +929,11 @@ where
         just(Token::Col).to(LayoutType::Column),
         just(Token::Grid).to(LayoutType::Grid),
         just(Token::Stack).to(LayoutType::Stack),
+        // Contextual keywords, so `flow` and `ring` stay usable as element names
+        just(Token::Ident("flow".into())).to(LayoutType::Flow),
+        just(Token::Ident("ring".into())).to(LayoutType::Ring),
+        just(Token::Ident("timeline".into())).to(LayoutType::Timeline),
+        just(Token::Ident("absolute".into())).to(LayoutType::Absolute),
     ))
     .map_with(|lt, e| Spanned::new(lt, span_range(&e.span())));
 
@@ -548,6 +1060,7 @@ where
         });
 
     // Contains: container contains a, b, c [padding: N]
+    // The modifier block is parsed by constrain_decl, which fills in padding.
     let contains_expr = identifier
         .then_ignore(just(Token::Contains))
         .then(
@@ -556,26 +1069,42 @@ where
                 .at_least(1)
                 .collect::<Vec<_>>(),
         )
-        .then(modifier_block.clone().or_not())
-        .map(|((container, elements), modifiers)| {
-            // Extract padding from modifiers if present
-            let padding = modifiers.as_ref().and_then(|mods| {
-                mods.iter().find_map(|m| {
-                    if let StyleKey::Custom(k) = &m.node.key.node {
-                        if k == "padding" {
-                            if let StyleValue::Number { value, .. } = &m.node.value.node {
-                                return Some(*value);
-                            }
-                        }
-                    }
-                    None
-                })
-            });
-            ConstraintExpr::Contains {
-                container,
-                elements,
-                padding,
-            }
+        .map(|(container, elements)| ConstraintExpr::Contains {
+            container,
+            elements,
+            padding: None,
+        });
+
+    // Equal size: equal_width a, b, c or equal_height a, b, c
+    let equal_size_expr = choice((
+        just(Token::EqualWidth).to(SizeDimension::Width),
+        just(Token::EqualHeight).to(SizeDimension::Height),
+    ))
+    .then(
+        identifier
+            .separated_by(just(Token::Comma))
+            .at_least(2)
+            .collect::<Vec<_>>(),
+    )
+    .map(|(dimension, elements)| ConstraintExpr::EqualSize {
+        dimension,
+        elements,
+    });
+
+    // Relational sugar: a right_of b [gap: N] (the gap is filled in by constrain_decl)
+    let relative_expr = identifier
+        .then(choice((
+            just(Token::RightOf).to(PositionRelation::RightOf),
+            just(Token::LeftOf).to(PositionRelation::LeftOf),
+            just(Token::Above).to(PositionRelation::Above),
+            just(Token::Below).to(PositionRelation::Below),
+        )))
+        .then(identifier)
+        .map(|((subject, relation), anchor)| ConstraintExpr::Relative {
+            subject,
+            relation,
+            anchor,
+            gap: None,
         });
 
     // Inequality: a.prop >= value or a.prop <= value
@@ -597,56 +1126,128 @@ where
             ConstraintExpr::LessOrEqual { left, value }
         });
 
-    // Equality with property: a.prop = b.prop [+ offset]
-    // Or constant: a.prop = value
-    let equality_expr = property_ref.clone().then_ignore(just(Token::Equals)).then(
-        // Try property ref with optional offset first
-        property_ref
-            .clone()
-            .then(offset.clone().or_not())
-            .map(|(right, off)| {
-                if let Some(offset) = off {
-                    ConstraintExprKind::PropertyWithOffset(right, offset)
+    // Equality with an arithmetic right-hand side:
+    //   a.prop = b.prop [+ offset], a.prop = value,
+    //   a.width = b.width * 0.5 + 10, a.center_x = (b.left + c.right) / 2
+    let linear_rhs = recursive(|linear_rhs| {
+        let atom = choice((
+            number.map(|n| Ok(LinearForm::constant(n.node))),
+            property_ref.clone().map(|p| Ok(LinearForm::property(p))),
+            linear_rhs.delimited_by(just(Token::ParenOpen), just(Token::ParenClose)),
+        ));
+        let unary = just(Token::Minus).or_not().then(atom).map(
+            |(neg, form): (_, Result<LinearForm, String>)| {
+                if neg.is_some() {
+                    form.map(|f| f.scale(-1.0))
                 } else {
-                    ConstraintExprKind::Property(right)
+                    form
                 }
-            })
-            // Or just a constant number
-            .or(just(Token::Minus).or_not().then(number).map(|(neg, n)| {
-                let value = if neg.is_some() { -n.node } else { n.node };
-                ConstraintExprKind::Constant(value)
-            })),
-    );
-
-    // Build the final constraint expression from equality
-    let equality_constraint = equality_expr.map(|(left, kind)| match kind {
-        ConstraintExprKind::Property(right) => ConstraintExpr::Equal { left, right },
-        ConstraintExprKind::PropertyWithOffset(right, offset) => ConstraintExpr::EqualWithOffset {
-            left,
-            right,
-            offset,
-        },
-        ConstraintExprKind::Constant(value) => ConstraintExpr::Constant { left, value },
-    });
+            },
+        );
+        let product = unary.clone().foldl(
+            choice((
+                just(Token::Star).to(ArithOp::Mul),
+                just(Token::Slash).to(ArithOp::Div),
+            ))
+            .then(unary)
+            .repeated(),
+            |lhs, (op, rhs)| LinearForm::combine(lhs, op, rhs),
+        );
+        product.clone().foldl(
+            choice((
+                just(Token::Plus).to(ArithOp::Add),
+                just(Token::Minus).to(ArithOp::Sub),
+            ))
+            .then(product)
+            .repeated(),
+            |lhs, (op, rhs)| LinearForm::combine(lhs, op, rhs),
+        )
+    })
+    .boxed();
+
+    let equality_constraint = property_ref
+        .clone()
+        .then_ignore(just(Token::Equals))
+        .then(linear_rhs)
+        .try_map(|(left, rhs), span| {
+            rhs.and_then(|form| form.into_constraint(left))
+                .map_err(|msg| Rich::custom(span, msg))
+        });
 
     // All constraint expressions (order matters - try more specific first)
     let constraint_expr = choice((
         midpoint_expr,
         contains_expr,
+        equal_size_expr,
+        relative_expr,
         ge_expr,
         le_expr,
         equality_constraint,
     ))
     .boxed(); // boxed() for faster compilation (chumsky trait solving)
 
-    // Constrain declaration: constrain <expr>
+    // Constrain declaration: constrain <expr> [strength: weak, padding: N, gap: N]
     let constrain_decl = just(Token::Constrain)
         .ignore_then(constraint_expr)
-        .map(|expr| ConstrainDecl { expr });
+        .then(modifier_block.clone().or_not())
+        .try_map(|(mut expr, modifiers), span| {
+            let mut strength = None;
+            for m in modifiers.iter().flatten() {
+                let key = match &m.node.key.node {
+                    StyleKey::Gap => "gap",
+                    StyleKey::Custom(key) => key.as_str(),
+                    _ => continue,
+                };
+                match (key, &m.node.value.node) {
+                    ("strength", value) => {
+                        let name = match value {
+                            StyleValue::Keyword(v) | StyleValue::String(v) => v.as_str(),
+                            StyleValue::Identifier(id) => id.0.as_str(),
+                            _ => "",
+                        };
+                        strength = Some(ConstraintStrength::parse(name).ok_or_else(|| {
+                            Rich::custom(span, format!("'{}' is not a valid constraint strength. Expected one of: weak, medium, strong, required", name))
+                        })?);
+                    }
+                    ("padding", StyleValue::Number { value, .. }) => {
+                        if let ConstraintExpr::Contains { padding, .. } = &mut expr {
+                            *padding = Some(*value);
+                        }
+                    }
+                    ("gap", StyleValue::Number { value, .. }) => {
+                        if let ConstraintExpr::Relative { gap, .. } = &mut expr {
+                            *gap = Some(*value);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Ok(ConstrainDecl { expr, strength })
+        });
 
     // ==================== Template Parsing (Feature 005) ====================
 
     // Export declaration: export name1, name2
+    // Embedded document: embed "other.ail" as name [scale: 0.5]
+    // `embed` and `import` are contextual, like the other newer keywords
+    let embed_decl = just(Token::Ident("embed".into()))
+        .ignore_then(string_literal)
+        .then_ignore(just(Token::As))
+        .then(identifier)
+        .then(modifier_block.clone().or_not())
+        .map(|((path, name), modifiers)| EmbedDecl {
+            path,
+            name,
+            modifiers: modifiers.unwrap_or_default(),
+        });
+
+    // Template library import: import "lib/electronics.ail" as elec
+    let import_decl = just(Token::Ident("import".into()))
+        .ignore_then(string_literal)
+        .then_ignore(just(Token::As))
+        .then(identifier)
+        .map(|(path, alias)| ImportDecl { path, alias });
+
     let export_decl = just(Token::Export)
         .ignore_then(
             identifier
@@ -656,12 +1257,22 @@ where
         )
         .map(|exports| ExportDecl { exports });
 
-    // Parameter definition: name: default_value
+    // Parameter definition: name: default_value, or name: type default_value
+    let param_type = identifier.try_map(|id, span| match ParamType::from_name(id.node.as_str()) {
+        Some(t) => Ok(Spanned::new(t, id.span)),
+        None => Err(Rich::custom(span, "not a parameter type")),
+    });
     let param_def = identifier
         .then_ignore(just(Token::Colon))
-        .then(style_value.clone())
-        .map(|(name, default_value)| ParameterDef {
+        .then(choice((
+            param_type
+                .then(style_value.clone())
+                .map(|(t, value)| (Some(t), value)),
+            style_value.clone().map(|value| (None, value)),
+        )))
+        .map(|(name, (param_type, default_value))| ParameterDef {
             name,
+            param_type,
             default_value,
         });
 
@@ -927,20 +1538,106 @@ where
                 shape_type: Spanned::new(ShapeType::Path(path), 0..0), // Span will be updated
                 name: None,                                            // Name is inside PathDecl
                 modifiers: mods.unwrap_or_default(),
+                ports: vec![],
             }
         });
 
+    // Error recovery: a statement that fails to parse is skipped together
+    // with what follows it, up to the next line that starts a statement, a
+    // statement keyword, or the end of the enclosing block. Bracketed and
+    // braced groups are skipped whole.
+    let balanced = recursive(|balanced| {
+        let inner = choice((
+            balanced,
+            none_of([
+                Token::BraceOpen,
+                Token::BraceClose,
+                Token::BracketOpen,
+                Token::BracketClose,
+            ])
+            .ignored(),
+        ))
+        .repeated();
+        choice((
+            inner
+                .clone()
+                .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
+            inner.delimited_by(just(Token::BracketOpen), just(Token::BracketClose)),
+        ))
+    });
+    let line_start = any().try_map(move |_, span: SimpleSpan| {
+        if line_starts.binary_search(&span.start).is_ok() {
+            Ok(())
+        } else {
+            Err(Rich::custom(span, "not at the start of a line"))
+        }
+    });
+    let statement_keyword = select! {
+        Token::Rect => (),
+        Token::Circle => (),
+        Token::Ellipse => (),
+        Token::Polygon => (),
+        Token::Line => (),
+        Token::Icon => (),
+        Token::Text => (),
+        Token::Path => (),
+        Token::Row => (),
+        Token::Col => (),
+        Token::Grid => (),
+        Token::Stack => (),
+        Token::Group => (),
+        Token::Label => (),
+        Token::Template => (),
+        Token::Export => (),
+        Token::Anchor => (),
+        Token::Place => (),
+        Token::Keyframe => (),
+        Token::Constrain => (),
+        Token::Ident(s) if matches!(s.as_str(), "image" | "flow" | "embed" | "import") => (),
+    };
+    let boundary = choice((line_start, statement_keyword));
+    let junk = choice((balanced.clone(), none_of([Token::BraceClose]).ignored()));
+    // The skipped source is reported (see `RECOVERED`), so callers can tell
+    // which statement failed even when the error points past it
+    let skip_statement = junk
+        .clone()
+        .then(junk.and_is(boundary.not()).repeated())
+        .validate(|_, e, emitter| {
+            emitter.emit(Rich::custom(e.span(), RECOVERED));
+            None
+        });
+    // At the top level there is no enclosing block, so a stray `}` is junk too
+    let top_junk = choice((balanced, any().ignored()));
+    let skip_top_statement = top_junk
+        .clone()
+        .then(top_junk.and_is(boundary.not()).repeated())
+        .validate(|_, e, emitter| {
+            emitter.emit(Rich::custom(e.span(), RECOVERED));
+            None
+        });
+
     // Recursive statement parser
     let statement = recursive(|stmt| {
+        // Statements of a block (or the document), skipping broken ones
+        let stmts = stmt
+            .clone()
+            .map(Some)
+            .recover_with(via_parser(skip_statement.clone()))
+            .repeated()
+            .collect::<Vec<_>>()
+            .map(|stmts: Vec<Option<Spanned<Statement>>>| {
+                stmts.into_iter().flatten().collect::<Vec<_>>()
+            })
+            .boxed();
+
         // Layout declaration with children
         let layout_decl = layout_type
             .clone()
             .then(identifier.or_not())
             .then(modifier_block.clone().or_not())
             .then(
-                stmt.clone()
-                    .repeated()
-                    .collect::<Vec<_>>()
+                stmts
+                    .clone()
                     .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
             )
             .map(|(((layout_type, name), modifiers), children)| LayoutDecl {
@@ -955,9 +1652,8 @@ where
             .ignore_then(identifier.or_not())
             .then(modifier_block.clone().or_not())
             .then(
-                stmt.clone()
-                    .repeated()
-                    .collect::<Vec<_>>()
+                stmts
+                    .clone()
                     .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
             )
             .map(|((name, modifiers), children)| GroupDecl {
@@ -966,8 +1662,165 @@ where
                 modifiers: modifiers.unwrap_or_default(),
                 anchors: vec![], // Parsed groups don't have custom anchors
                 is_template_instance: false,
+                layer: None,
+            });
+
+        // Note: `note at element.anchor [dx: N, dy: N] { ... }`
+        // `note` and `at` are contextual so they stay usable as identifiers
+        let note_decl = just(Token::Ident("note".into()))
+            .ignore_then(just(Token::Ident("at".into())))
+            .ignore_then(anchor_reference.clone())
+            .then(modifier_block.clone().or_not())
+            .then(
+                stmts
+                    .clone()
+                    .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
+            )
+            .map(|((target, modifiers), children)| NoteDecl {
+                target,
+                modifiers: modifiers.unwrap_or_default(),
+                children,
+            });
+
+        // Layer: `layer name [order: N, opacity: N] { ... }` (contextual keyword)
+        let layer_decl = just(Token::Ident("layer".into()))
+            .ignore_then(identifier)
+            .then(modifier_block.clone().or_not())
+            .then(
+                stmts
+                    .clone()
+                    .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
+            )
+            .map(|((name, modifiers), children)| GroupDecl {
+                name: None,
+                children,
+                modifiers: modifiers.unwrap_or_default(),
+                anchors: vec![],
+                is_template_instance: false,
+                layer: Some(name),
+            });
+
+        // Repeat: `repeat 5 as i { rect item_$i }` (`repeat` is contextual)
+        let repeat_decl = just(Token::Ident("repeat".into()))
+            .ignore_then(
+                num_expr
+                    .clone()
+                    .map_with(|expr, e| Spanned::new(expr, span_range(&e.span()))),
+            )
+            .then_ignore(just(Token::As))
+            .then(identifier)
+            .then(
+                stmts
+                    .clone()
+                    .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
+            )
+            .map(|((count, variable), body)| RepeatDecl {
+                count,
+                variable,
+                body,
+            });
+
+        // Presentation step: `step 2 { ... }` (`step` is contextual)
+        let step_decl = just(Token::Ident("step".into()))
+            .ignore_then(
+                select! { Token::Number(n) if n >= 1.0 && n.fract() == 0.0 => n as u32 }
+                    .map_with(|n, e| Spanned::new(n, span_range(&e.span()))),
+            )
+            .then(
+                stmts
+                    .clone()
+                    .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
+            )
+            .map(|(number, children)| StepDecl { number, children });
+
+        // Variable: `let base = 40` (`let` is contextual)
+        let let_decl = just(Token::Ident("let".into()))
+            .ignore_then(identifier)
+            .then_ignore(just(Token::Equals))
+            .then(
+                num_expr
+                    .clone()
+                    .map_with(|expr, e| Spanned::new(expr, span_range(&e.span()))),
+            )
+            .map(|(name, value)| LetDecl { name, value });
+
+        // Title and caption: `title "Request flow" [font_size: 24]` (contextual)
+        let title_decl = choice((
+            just(Token::Ident("title".into())).to(TitleKind::Title),
+            just(Token::Ident("caption".into())).to(TitleKind::Caption),
+        ))
+        .then(string_literal)
+        .then(modifier_block.clone().or_not())
+        .map(|((kind, text), modifiers)| TitleDecl {
+            kind,
+            text,
+            modifiers: modifiers.unwrap_or_default(),
+        });
+
+        // Legend: `legend [position: top_right] { entry accent "API calls" }`
+        // (`legend` and `entry` are contextual)
+        let legend_entry = just(Token::Ident("entry".into()))
+            .ignore_then(style_value.clone())
+            .then(string_literal)
+            .then(modifier_block.clone().or_not())
+            .map_with(|((color, label), modifiers), e| {
+                Spanned::new(
+                    LegendEntry {
+                        color,
+                        label,
+                        modifiers: modifiers.unwrap_or_default(),
+                    },
+                    span_range(&e.span()),
+                )
+            });
+        let legend_decl = just(Token::Ident("legend".into()))
+            .ignore_then(modifier_block.clone().or_not())
+            .then(
+                legend_entry
+                    .repeated()
+                    .collect::<Vec<_>>()
+                    .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
+            )
+            .map(|(modifiers, entries)| LegendDecl {
+                modifiers: modifiers.unwrap_or_default(),
+                entries,
             });
 
+        // Animation: `animate box [property: opacity, from: 0, to: 1]`
+        // (`animate` is contextual)
+        let animate_decl = just(Token::Ident("animate".into()))
+            .ignore_then(identifier)
+            .then(modifier_block.clone())
+            .map(|(target, modifiers)| AnimateDecl { target, modifiers });
+
+        // Canvas: `canvas [width: 800, height: 600]` (`canvas` is contextual)
+        let canvas_decl = just(Token::Ident("canvas".into()))
+            .ignore_then(modifier_block.clone())
+            .map(|modifiers| CanvasDecl { modifiers });
+
+        // Conditional: `if flag { ... } else if other { ... } else { ... }`
+        // (`if` and `else` are contextual)
+        let if_decl = recursive(|if_decl| {
+            let block = stmts
+                .clone()
+                .delimited_by(just(Token::BraceOpen), just(Token::BraceClose));
+            let else_branch = just(Token::Ident("else".into())).ignore_then(choice((
+                block.clone(),
+                if_decl.map_with(|decl, e| {
+                    vec![Spanned::new(Statement::If(decl), span_range(&e.span()))]
+                }),
+            )));
+            just(Token::Ident("if".into()))
+                .ignore_then(identifier)
+                .then(block)
+                .then(else_branch.or_not())
+                .map(|((condition, then_body), else_body)| IfDecl {
+                    condition,
+                    then_body,
+                    else_body: else_body.unwrap_or_default(),
+                })
+        });
+
         // Label declaration: `label { ... }` or `label: <element>`
         // The inner element can be any statement (shape, group, layout, etc.)
         let label_decl = just(Token::Label)
@@ -988,9 +1841,8 @@ where
             .ignore_then(string_literal)
             .then(param_list.clone())
             .then(
-                stmt.clone()
-                    .repeated()
-                    .collect::<Vec<_>>()
+                stmts
+                    .clone()
                     .delimited_by(just(Token::BraceOpen), just(Token::BraceClose)),
             )
             .map(|((name, parameters), body)| {
@@ -1010,41 +1862,69 @@ where
         // For now, we support the syntax: identifier identifier [params]
         // where the first identifier is the template name and second is instance name.
         // Template instances will be distinguished from connections by not having ->/<- operators.
-        let template_instance = identifier
-            .then(identifier)
-            .then(modifier_block.clone().or_not())
-            .try_map(|((template_name, instance_name), mods), _span| {
-                // Convert modifiers to argument list
-                let arguments: Vec<(Spanned<Identifier>, Spanned<StyleValue>)> = mods
-                    .unwrap_or_default()
-                    .into_iter()
-                    .filter_map(|m| {
-                        // Convert StyleKey to Identifier
-                        let key_str = match &m.node.key.node {
-                            StyleKey::Fill => "fill".to_string(),
-                            StyleKey::Stroke => "stroke".to_string(),
-                            StyleKey::StrokeWidth => "stroke_width".to_string(),
-                            StyleKey::Size => "size".to_string(),
-                            StyleKey::Width => "width".to_string(),
-                            StyleKey::Height => "height".to_string(),
-                            StyleKey::Label => "label".to_string(),
-                            StyleKey::Rotation => "rotation".to_string(),
-                            StyleKey::Custom(s) => s.clone(),
-                            _ => return None,
-                        };
-                        Some((
-                            Spanned::new(Identifier::new(key_str), m.node.key.span),
-                            m.node.value,
-                        ))
-                    })
-                    .collect();
-
-                Ok(Statement::TemplateInstance(TemplateInstance {
-                    template_name,
-                    instance_name,
-                    arguments,
-                }))
+        // Imported templates are qualified with the import alias: `elec.resistor r1`.
+        let template_name = identifier
+            .separated_by(just(Token::Dot))
+            .at_least(1)
+            .collect::<Vec<_>>()
+            .map_with(|parts, e| {
+                let name: Vec<&str> = parts.iter().map(|p| p.node.as_str()).collect();
+                Spanned::new(Identifier::new(name.join(".")), span_range(&e.span()))
             });
+        let template_instance = template_name
+            .then(identifier)
+            .then(instance_arguments.or_not())
+            .then(
+                stmts
+                    .clone()
+                    .delimited_by(just(Token::BraceOpen), just(Token::BraceClose))
+                    .or_not(),
+            )
+            .try_map(
+                |(((template_name, instance_name), mods), children), _span| {
+                    // Convert modifiers to argument list
+                    let arguments: Vec<(Spanned<Identifier>, Spanned<StyleValue>)> = mods
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter_map(|(path, m)| {
+                            // Convert StyleKey to Identifier
+                            let key_str = match &m.node.key.node {
+                                StyleKey::Fill => "fill".to_string(),
+                                StyleKey::Stroke => "stroke".to_string(),
+                                StyleKey::StrokeWidth => "stroke_width".to_string(),
+                                StyleKey::Size => "size".to_string(),
+                                StyleKey::Width => "width".to_string(),
+                                StyleKey::Height => "height".to_string(),
+                                StyleKey::Label => "label".to_string(),
+                                StyleKey::Rotation => "rotation".to_string(),
+                                StyleKey::Custom(s) => s.clone(),
+                                _ => return None,
+                            };
+                            // An override keeps its path: `body.fill`
+                            let (key_str, key_span) = match path.first() {
+                                Some(first) => {
+                                    let mut segments: Vec<_> =
+                                        path.iter().map(|p| p.node.as_str()).collect();
+                                    segments.push(&key_str);
+                                    (segments.join("."), first.span.start..m.node.key.span.end)
+                                }
+                                None => (key_str, m.node.key.span),
+                            };
+                            Some((
+                                Spanned::new(Identifier::new(key_str), key_span),
+                                m.node.value,
+                            ))
+                        })
+                        .collect();
+
+                    Ok(Statement::TemplateInstance(TemplateInstance {
+                        template_name,
+                        instance_name,
+                        arguments,
+                        children: children.unwrap_or_default(),
+                    }))
+                },
+            );
 
         // Anchor declaration: anchor name [position: element.property, direction: up/down/left/right]
         // (Feature 009 - T010)
@@ -1131,7 +2011,6 @@ where
         let show_op = just(Token::Show)
             .ignore_then(
                 identifier
-                    .clone()
                     .separated_by(just(Token::Comma))
                     .at_least(1)
                     .collect::<Vec<_>>(),
@@ -1141,7 +2020,6 @@ where
         let hide_op = just(Token::Hide)
             .ignore_then(
                 identifier
-                    .clone()
                     .separated_by(just(Token::Comma))
                     .at_least(1)
                     .collect::<Vec<_>>(),
@@ -1149,7 +2027,7 @@ where
             .map_with(|targets, e| Spanned::new(KeyframeOp::Hide(targets), span_range(&e.span())));
 
         let transform_op = just(Token::Transform)
-            .ignore_then(identifier.clone())
+            .ignore_then(identifier)
             .then(modifier_block.clone())
             .map_with(|(target, modifiers), e| {
                 Spanned::new(
@@ -1165,7 +2043,7 @@ where
 
         // Parse optional [no_resolve] modifier on keyframes
         let no_resolve_flag = just(Token::BracketOpen)
-            .ignore_then(identifier.clone().try_map(|id, span| {
+            .ignore_then(identifier.try_map(|id, span| {
                 if id.node.0 == "no_resolve" {
                     Ok(true)
                 } else {
@@ -1175,7 +2053,7 @@ where
             .then_ignore(just(Token::BracketClose));
 
         let keyframe_decl = just(Token::Keyframe)
-            .ignore_then(string_literal.clone())
+            .ignore_then(string_literal)
             .then(no_resolve_flag.or_not())
             .then(
                 keyframe_op
@@ -1207,13 +2085,37 @@ where
             file_template.clone(),
             inline_template,
             export_decl.clone().map(Statement::Export),
+            embed_decl.clone().map(Statement::Embed),
+            import_decl.map(Statement::Import),
             anchor_decl, // Feature 009: anchor declarations
+            note_decl.map(Statement::Note),
+            choice((
+                repeat_decl.map(Statement::Repeat),
+                step_decl.map(Statement::Step),
+                if_decl.map(Statement::If),
+                let_decl.map(Statement::Let),
+            )),
+            // `slot` is contextual: only a `slot` that ends its statement marks
+            // where instance children go, so `slot s1` and `slot -> a` keep
+            // their meaning
+            just(Token::Ident("slot".into()))
+                .then_ignore(
+                    choice((boundary, just(Token::BraceClose).ignored(), end())).rewind(),
+                )
+                .to(Statement::Slot),
+            canvas_decl.map(Statement::Canvas),
+            animate_decl.map(Statement::Animate),
+            title_decl.map(Statement::Title),
+            legend_decl.map(Statement::Legend),
+            layer_decl.map(Statement::Group),
             layout_decl.map(Statement::Layout),
             group_decl.map(Statement::Group),
             label_decl,
             connection_decl.clone().map(Statement::Connection),
             // path_decl before shape_decl since 'path' is a keyword (Feature 007)
             path_decl.clone().map(Statement::Shape),
+            // Before template_instance, which would take `class User { ... }`
+            class_decl.map(Statement::Shape),
             shape_decl.clone().map(Statement::Shape),
             // Template instance must be last since it matches "identifier identifier"
             // which could conflict with other patterns
@@ -1225,10 +2127,15 @@ where
 
     // Document is a list of statements
     statement
+        .map(Some)
+        .recover_with(via_parser(skip_top_statement))
         .repeated()
-        .collect()
+        .collect::<Vec<_>>()
         .then_ignore(end())
-        .map(|statements| Document { statements })
+        .map(|statements| Document {
+            statements: statements.into_iter().flatten().collect(),
+            comments: vec![],
+        })
 }
 
 #[cfg(test)]
@@ -1300,6 +2207,128 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_shape_ports() {
+        let doc = parse(
+            "rect chip [width: 60] {\n  port in1 [side: left, at: 0.25]\n  port out [side: right, marker: true]\n}",
+        )
+        .expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Shape(s) => {
+                assert_eq!(s.ports.len(), 2);
+                assert_eq!(s.ports[0].node.name.node.as_str(), "in1");
+                assert_eq!(s.ports[0].node.side, Edge::Left);
+                assert_eq!(s.ports[0].node.at, 0.25);
+                assert_eq!(s.ports[1].node.at, 0.5);
+                assert!(s.ports[1].node.marker);
+            }
+            _ => panic!("Expected shape"),
+        }
+
+        assert!(parse("rect chip { port p [at: 0.5] }").is_err());
+        assert!(parse("rect chip { port p [side: top, at: 2] }").is_err());
+    }
+
+    #[test]
+    fn test_parse_step() {
+        let doc = parse("step 2 {\n    rect db\n    a -> db\n}").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Step(s) => {
+                assert_eq!(s.number.node, 2);
+                assert_eq!(s.children.len(), 2);
+            }
+            _ => panic!("Expected step"),
+        }
+        assert!(parse("step 1.5 { rect a }").is_err());
+        assert!(parse("step 0 { rect a }").is_err());
+    }
+
+    #[test]
+    fn test_parse_animate() {
+        let doc = parse("animate box [property: opacity, from: 0, to: 1, duration: 2s]")
+            .expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Animate(a) => {
+                assert_eq!(a.target.node.as_str(), "box");
+                assert_eq!(a.modifiers.len(), 4);
+            }
+            _ => panic!("Expected animate"),
+        }
+    }
+
+    #[test]
+    fn test_parse_state() {
+        let doc =
+            parse("state idle [initial: true]\nidle -> idle [on: \"tick\"]").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Shape(s) => {
+                assert_eq!(s.shape_type.node, ShapeType::State);
+                assert_eq!(s.name.as_ref().unwrap().node.as_str(), "idle");
+            }
+            _ => panic!("Expected shape"),
+        }
+        assert!(matches!(doc.statements[1].node, Statement::Connection(_)));
+    }
+
+    #[test]
+    fn test_parse_class() {
+        let doc = parse(
+            "class User [fill: white] {\n    attr \"name: String\"\n    op \"login()\"\n    attr \"id: u64\"\n}",
+        )
+        .expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Shape(s) => {
+                assert_eq!(s.name.as_ref().unwrap().node.as_str(), "User");
+                assert_eq!(s.modifiers.len(), 1);
+                let ShapeType::Class {
+                    attributes,
+                    operations,
+                } = &s.shape_type.node
+                else {
+                    panic!("Expected class, got {:?}", s.shape_type.node);
+                };
+                let attributes: Vec<_> = attributes.iter().map(|a| a.node.as_str()).collect();
+                assert_eq!(attributes, ["name: String", "id: u64"]);
+                assert_eq!(operations.len(), 1);
+                assert_eq!(operations[0].node, "login()");
+            }
+            _ => panic!("Expected shape"),
+        }
+
+        // `class` stays usable as a modifier key
+        assert!(parse("rect a [class: highlight]").is_ok());
+    }
+
+    #[test]
+    fn test_parse_bus() {
+        let doc = parse("bus rail [orientation: vertical]\na -> rail").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Shape(s) => {
+                assert_eq!(s.shape_type.node, ShapeType::Bus);
+                assert_eq!(s.name.as_ref().unwrap().node.as_str(), "rail");
+                assert_eq!(s.modifiers.len(), 1);
+            }
+            _ => panic!("Expected shape"),
+        }
+        assert!(matches!(doc.statements[1].node, Statement::Connection(_)));
+    }
+
+    #[test]
+    fn test_parse_connection_anchor_offsets() {
+        let doc = parse("a.right+10 -> b.left-5").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Connection(conns) => {
+                assert_eq!(conns[0].from.offset.as_ref().map(|o| o.node), Some(10.0));
+                assert_eq!(conns[0].to.offset.as_ref().map(|o| o.node), Some(-5.0));
+                assert_eq!(
+                    conns[0].to.anchor.as_ref().map(|s| s.node.as_str()),
+                    Some("left")
+                );
+            }
+            _ => panic!("Expected connection"),
+        }
+    }
+
     #[test]
     fn test_parse_connection_mixed_anchors() {
         // One with anchor, one without
@@ -1316,6 +2345,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_connection_scoped_endpoints() {
+        let doc = parse("outer.inner.x.right+5 -> b.x").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Connection(conns) => {
+                let from = &conns[0].from;
+                let scope: Vec<_> = from.scope.iter().map(|s| s.node.as_str()).collect();
+                assert_eq!(scope, ["outer", "inner"]);
+                assert_eq!(from.element.node.as_str(), "x");
+                assert_eq!(from.anchor.as_ref().map(|s| s.node.as_str()), Some("right"));
+                assert_eq!(from.offset.as_ref().map(|o| o.node), Some(5.0));
+                // Two segments stay element and anchor until scope resolution
+                assert!(conns[0].to.scope.is_empty());
+                assert_eq!(conns[0].to.element.node.as_str(), "b");
+            }
+            _ => panic!("Expected connection"),
+        }
+    }
+
     #[test]
     fn test_parse_layout() {
         let doc = parse("row { rect a rect b }").expect("Should parse");
@@ -1325,7 +2373,53 @@ mod tests {
                 assert!(matches!(l.layout_type.node, LayoutType::Row));
                 assert_eq!(l.children.len(), 2);
             }
-            _ => panic!("Expected layout"),
+            _ => panic!("Expected layout"),
+        }
+    }
+
+    #[test]
+    fn test_parse_ring_layout() {
+        let doc = parse("ring hub [radius: 120] { rect a rect b }").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Layout(l) => {
+                assert!(matches!(l.layout_type.node, LayoutType::Ring));
+                assert_eq!(l.name.as_ref().unwrap().node.as_str(), "hub");
+                assert_eq!(l.children.len(), 2);
+            }
+            other => panic!("Expected layout, got {:?}", other),
+        }
+
+        // `ring` is only a keyword in layout position
+        let doc =
+            parse("circle ring [size: 14]\nconstrain a.left = ring.right").expect("Should parse");
+        assert!(matches!(doc.statements[0].node, Statement::Shape(_)));
+    }
+
+    #[test]
+    fn test_parse_timeline_layout() {
+        let doc = parse("timeline plan [unit_width: 20] { rect a [start: 0, end: 3] }")
+            .expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Layout(l) => {
+                assert!(matches!(l.layout_type.node, LayoutType::Timeline));
+                assert_eq!(l.name.as_ref().unwrap().node.as_str(), "plan");
+                assert_eq!(l.children.len(), 1);
+            }
+            other => panic!("Expected layout, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_absolute_layout() {
+        let doc =
+            parse("absolute canvas [width: 400] { rect a [x: 10, y: 20] }").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Layout(l) => {
+                assert!(matches!(l.layout_type.node, LayoutType::Absolute));
+                assert_eq!(l.name.as_ref().unwrap().node.as_str(), "canvas");
+                assert_eq!(l.children.len(), 1);
+            }
+            other => panic!("Expected layout, got {:?}", other),
         }
     }
 
@@ -1400,7 +2494,7 @@ mod tests {
         match &doc.statements[0].node {
             Statement::Shape(s) => {
                 match &s.shape_type.node {
-                    ShapeType::Icon { icon_name } => assert_eq!(icon_name, "server"),
+                    ShapeType::Icon { icon_name, .. } => assert_eq!(icon_name, "server"),
                     _ => panic!("Expected icon"),
                 }
                 assert_eq!(s.name.as_ref().unwrap().node.as_str(), "myserver");
@@ -1409,6 +2503,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_image() {
+        let doc = parse(r#"image "logo.png" mylogo [width: 64]"#).expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Shape(s) => {
+                match &s.shape_type.node {
+                    ShapeType::RasterImage { path, .. } => assert_eq!(path, "logo.png"),
+                    _ => panic!("Expected image"),
+                }
+                assert_eq!(s.name.as_ref().unwrap().node.as_str(), "mylogo");
+            }
+            _ => panic!("Expected shape"),
+        }
+    }
+
     #[test]
     fn test_parse_nested() {
         let input = r#"
@@ -1950,6 +3059,185 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_constrain_equal_width() {
+        let doc = parse("constrain equal_width a, b, c").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Constrain(c) => match &c.expr {
+                ConstraintExpr::EqualSize {
+                    dimension,
+                    elements,
+                } => {
+                    assert_eq!(*dimension, SizeDimension::Width);
+                    assert_eq!(elements.len(), 3);
+                    assert_eq!(elements[2].node.as_str(), "c");
+                }
+                other => panic!("Expected EqualSize, got {:?}", other),
+            },
+            other => panic!("Expected Constrain, got {:?}", other),
+        }
+
+        let doc = parse("constrain equal_height a, b").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Constrain(c) => assert!(matches!(
+                c.expr,
+                ConstraintExpr::EqualSize {
+                    dimension: SizeDimension::Height,
+                    ..
+                }
+            )),
+            other => panic!("Expected Constrain, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_constrain_equal_width_needs_two_elements() {
+        assert!(parse("constrain equal_width a").is_err());
+    }
+
+    #[test]
+    fn test_parse_constrain_strength() {
+        let doc = parse("constrain a.left = b.left [strength: weak]").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Constrain(c) => {
+                assert!(matches!(c.expr, ConstraintExpr::Equal { .. }));
+                assert_eq!(c.strength, Some(ConstraintStrength::Weak));
+            }
+            other => panic!("Expected Constrain, got {:?}", other),
+        }
+
+        let doc = parse("constrain a.x = 10").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Constrain(c) => assert_eq!(c.strength, None),
+            other => panic!("Expected Constrain, got {:?}", other),
+        }
+
+        let doc = parse("constrain bg contains a [padding: 5, strength: required]")
+            .expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Constrain(c) => {
+                assert_eq!(c.strength, Some(ConstraintStrength::Required));
+                assert!(matches!(
+                    c.expr,
+                    ConstraintExpr::Contains {
+                        padding: Some(p),
+                        ..
+                    } if (p - 5.0).abs() < 0.001
+                ));
+            }
+            other => panic!("Expected Constrain, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_constrain_relative() {
+        let doc = parse("constrain a right_of b [gap: 40]").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Constrain(c) => match &c.expr {
+                ConstraintExpr::Relative {
+                    subject,
+                    relation,
+                    anchor,
+                    gap,
+                } => {
+                    assert_eq!(subject.node.as_str(), "a");
+                    assert_eq!(*relation, PositionRelation::RightOf);
+                    assert_eq!(anchor.node.as_str(), "b");
+                    assert_eq!(*gap, Some(40.0));
+                }
+                other => panic!("Expected Relative, got {:?}", other),
+            },
+            other => panic!("Expected Constrain, got {:?}", other),
+        }
+
+        let doc = parse("constrain a below b").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Constrain(c) => assert!(matches!(
+                c.expr,
+                ConstraintExpr::Relative {
+                    relation: PositionRelation::Below,
+                    gap: None,
+                    ..
+                }
+            )),
+            other => panic!("Expected Constrain, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_constrain_invalid_strength() {
+        assert!(parse("constrain a.left = b.left [strength: mighty]").is_err());
+    }
+
+    #[test]
+    fn test_parse_constrain_arithmetic() {
+        let doc = parse("constrain a.width = b.width * 0.5 + 10").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Constrain(c) => match &c.expr {
+                ConstraintExpr::Linear {
+                    left,
+                    terms,
+                    constant,
+                } => {
+                    assert_eq!(left.element.node.to_string(), "a");
+                    assert_eq!(terms.len(), 1);
+                    assert!((terms[0].coefficient - 0.5).abs() < 0.001);
+                    assert!(matches!(
+                        terms[0].property.property.node,
+                        ConstraintProperty::Width
+                    ));
+                    assert!((constant - 10.0).abs() < 0.001);
+                }
+                other => panic!("Expected Linear, got {:?}", other),
+            },
+            other => panic!("Expected Constrain, got {:?}", other),
+        }
+
+        let doc = parse("constrain a.center_x = (b.left + c.right) / 2").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Constrain(c) => match &c.expr {
+                ConstraintExpr::Linear {
+                    terms, constant, ..
+                } => {
+                    assert_eq!(terms.len(), 2);
+                    assert!(terms.iter().all(|t| (t.coefficient - 0.5).abs() < 0.001));
+                    assert_eq!(*constant, 0.0);
+                }
+                other => panic!("Expected Linear, got {:?}", other),
+            },
+            other => panic!("Expected Constrain, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_constrain_arithmetic_simplifies() {
+        // Single unit-coefficient references keep their simple forms
+        let doc = parse("constrain a.x = b.x + 5 * 2").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Constrain(c) => assert!(matches!(
+                c.expr,
+                ConstraintExpr::EqualWithOffset { offset, .. } if (offset - 10.0).abs() < 0.001
+            )),
+            other => panic!("Expected Constrain, got {:?}", other),
+        }
+
+        let doc = parse("constrain a.width = (100 - 20) / 2").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Constrain(c) => assert!(matches!(
+                c.expr,
+                ConstraintExpr::Constant { value, .. } if (value - 40.0).abs() < 0.001
+            )),
+            other => panic!("Expected Constrain, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_constrain_nonlinear_rejected() {
+        assert!(parse("constrain a.width = b.width * c.width").is_err());
+        assert!(parse("constrain a.width = 100 / b.width").is_err());
+        assert!(parse("constrain a.width = b.width / 0").is_err());
+    }
+
     #[test]
     fn test_parse_constrain_center_properties() {
         // Test all center property keywords
@@ -2127,6 +3415,92 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_embed_declaration() {
+        let doc = parse(r#"embed "sub.ail" as subsystem [scale: 0.5]"#).expect("Should parse");
+        assert_eq!(doc.statements.len(), 1);
+        match &doc.statements[0].node {
+            Statement::Embed(e) => {
+                assert_eq!(e.path.node, "sub.ail");
+                assert_eq!(e.name.node.as_str(), "subsystem");
+                assert_eq!(e.modifiers.len(), 1);
+            }
+            other => panic!("Expected Embed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_note() {
+        let doc = parse(r#"note at api.top_right [dx: 10, dy: -10] { text "deprecated" }"#)
+            .expect("Should parse");
+        assert_eq!(doc.statements.len(), 1);
+        match &doc.statements[0].node {
+            Statement::Note(n) => {
+                assert_eq!(n.target.element.node.as_str(), "api");
+                assert_eq!(n.target.anchor.as_ref().unwrap().node, "top_right");
+                assert_eq!(n.modifiers.len(), 2);
+                assert_eq!(n.children.len(), 1);
+            }
+            other => panic!("Expected Note, got {:?}", other),
+        }
+
+        // `note` remains usable as an identifier
+        let doc = parse("rect note\nnote -> note").expect("Should parse");
+        assert_eq!(doc.statements.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_function_style_value() {
+        let doc = parse("a -> b [stroke: fade(accent-1, transparent)]").expect("Should parse");
+        let Statement::Connection(conns) = &doc.statements[0].node else {
+            panic!("Expected connection");
+        };
+        match &conns[0].modifiers[0].node.value.node {
+            StyleValue::Function { name, args } => {
+                assert_eq!(name, "fade");
+                assert_eq!(args.len(), 2);
+                assert!(matches!(
+                    args[0].node,
+                    StyleValue::Color(ColorValue::Symbolic { .. })
+                ));
+                assert_eq!(args[1].node, StyleValue::Keyword("transparent".to_string()));
+            }
+            other => panic!("Expected Function, got {:?}", other),
+        }
+
+        let doc = parse("rect a [fill: darken(accent, 20%)]").expect("Should parse");
+        let Statement::Shape(shape) = &doc.statements[0].node else {
+            panic!("Expected shape");
+        };
+        match &shape.modifiers[0].node.value.node {
+            StyleValue::Function { name, args } => {
+                assert_eq!(name, "darken");
+                assert_eq!(
+                    args[1].node,
+                    StyleValue::Number {
+                        value: 20.0,
+                        unit: Some("%".to_string())
+                    }
+                );
+            }
+            other => panic!("Expected Function, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_layer() {
+        let doc = parse("layer background [opacity: 0.5] { rect a rect b }").expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Group(g) => {
+                assert_eq!(g.layer.as_ref().unwrap().node.as_str(), "background");
+                assert!(g.name.is_none());
+                assert_eq!(g.children.len(), 2);
+                assert_eq!(g.modifiers.len(), 1);
+            }
+            other => panic!("Expected layer Group, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_parse_template_instance() {
         let doc = parse("server myserver [fill: red, size: 100]").expect("Should parse");
@@ -2141,6 +3515,284 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_template_instance_overrides() {
+        let doc = parse("motor m1 [body.fill: red, rotor.shaft.width: 12, label: \"M\"]")
+            .expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::TemplateInstance(inst) => {
+                let keys: Vec<_> = inst
+                    .arguments
+                    .iter()
+                    .map(|(k, _)| k.node.as_str())
+                    .collect();
+                assert_eq!(keys, ["body.fill", "rotor.shaft.width", "label"]);
+            }
+            other => panic!("Expected TemplateInstance, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_import() {
+        let doc = parse(
+            r#"import "lib/electronics.ail" as elec
+            elec.resistor r1 [value: "10k"]"#,
+        )
+        .expect("Should parse");
+        match &doc.statements[0].node {
+            Statement::Import(import) => {
+                assert_eq!(import.path.node, "lib/electronics.ail");
+                assert_eq!(import.alias.node.as_str(), "elec");
+            }
+            other => panic!("Expected Import, got {:?}", other),
+        }
+        match &doc.statements[1].node {
+            Statement::TemplateInstance(inst) => {
+                assert_eq!(inst.template_name.node.as_str(), "elec.resistor");
+                assert_eq!(inst.instance_name.node.as_str(), "r1");
+            }
+            other => panic!("Expected TemplateInstance, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_repeat() {
+        let doc = parse(
+            r#"repeat 5 as i {
+                rect item_$i [label: "Item $i", x: ($i + 1) * 40, y: 2 * 3]
+            }"#,
+        )
+        .expect("Should parse");
+        let Statement::Repeat(repeat) = &doc.statements[0].node else {
+            panic!("Expected Repeat, got {:?}", doc.statements[0].node);
+        };
+        assert_eq!(repeat.count.node, NumExpr::Number(5.0));
+        assert_eq!(repeat.variable.node.as_str(), "i");
+        let Statement::Shape(shape) = &repeat.body[0].node else {
+            panic!("Expected Shape, got {:?}", repeat.body[0].node);
+        };
+        assert_eq!(shape.name.as_ref().unwrap().node.as_str(), "item_$i");
+        assert!(matches!(
+            &shape.modifiers[1].node.value.node,
+            StyleValue::Expr(NumExpr::Binary {
+                op: BinaryOp::Mul,
+                ..
+            })
+        ));
+        // Constant expressions fold to plain numbers
+        assert_eq!(
+            shape.modifiers[2].node.value.node,
+            StyleValue::Number {
+                value: 6.0,
+                unit: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_if_else() {
+        let doc = parse(
+            r#"template "server" (with_cache: false) {
+                if with_cache { rect cache } else if fast { rect fast_path } else { rect slow }
+            }"#,
+        )
+        .expect("Should parse");
+        let Statement::TemplateDecl(decl) = &doc.statements[0].node else {
+            panic!("Expected TemplateDecl, got {:?}", doc.statements[0].node);
+        };
+        let body = decl.body.as_ref().unwrap();
+        let Statement::If(cond) = &body[0].node else {
+            panic!("Expected If, got {:?}", body[0].node);
+        };
+        assert_eq!(cond.condition.node.as_str(), "with_cache");
+        assert_eq!(cond.then_body.len(), 1);
+        let Statement::If(nested) = &cond.else_body[0].node else {
+            panic!("Expected else if, got {:?}", cond.else_body[0].node);
+        };
+        assert_eq!(nested.condition.node.as_str(), "fast");
+        assert_eq!(nested.else_body.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_let() {
+        let doc = parse("let base = 40\nrect a [width: base * 2, height: base, fill: red]")
+            .expect("Should parse");
+        let Statement::Let(decl) = &doc.statements[0].node else {
+            panic!("Expected Let, got {:?}", doc.statements[0].node);
+        };
+        assert_eq!(decl.name.node.as_str(), "base");
+        assert_eq!(decl.value.node, NumExpr::Number(40.0));
+        let Statement::Shape(shape) = &doc.statements[1].node else {
+            panic!("Expected Shape, got {:?}", doc.statements[1].node);
+        };
+        assert!(matches!(
+            &shape.modifiers[0].node.value.node,
+            StyleValue::Expr(NumExpr::Binary { lhs, .. }) if **lhs == NumExpr::Var("base".into())
+        ));
+        // A lone name stays an identifier (template parameter, color, ...)
+        assert_eq!(
+            shape.modifiers[1].node.value.node,
+            StyleValue::Identifier(Identifier::new("base"))
+        );
+    }
+
+    #[test]
+    fn test_parse_slot_and_instance_children() {
+        let doc = parse(
+            r#"template "card" { rect frame slot }
+            card mycard [title: "Hi"] { rect content }"#,
+        )
+        .expect("Should parse");
+        let Statement::TemplateDecl(decl) = &doc.statements[0].node else {
+            panic!("Expected TemplateDecl, got {:?}", doc.statements[0].node);
+        };
+        assert_eq!(decl.body.as_ref().unwrap()[1].node, Statement::Slot);
+        let Statement::TemplateInstance(inst) = &doc.statements[1].node else {
+            panic!(
+                "Expected TemplateInstance, got {:?}",
+                doc.statements[1].node
+            );
+        };
+        assert_eq!(inst.arguments.len(), 1);
+        assert_eq!(inst.children.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_contextual_keywords_as_names() {
+        // Newer keywords stay usable as element and instance names
+        let doc = parse(
+            "rect slot\nrect flow\nrect image\nrect embed\nrect import\n\
+             slot -> flow\nimage -> embed\nslot s1\nflow f1\nimport i1",
+        )
+        .expect("Should parse");
+        assert_eq!(doc.statements.len(), 10);
+        assert!(matches!(doc.statements[5].node, Statement::Connection(_)));
+        assert!(matches!(doc.statements[6].node, Statement::Connection(_)));
+        for stmt in &doc.statements[7..] {
+            let Statement::TemplateInstance(inst) = &stmt.node else {
+                panic!("Expected TemplateInstance, got {:?}", stmt.node);
+            };
+            assert!(inst.children.is_empty());
+        }
+
+        // As keywords they still work where they start a statement
+        let doc = parse(
+            r#"template "card" { rect frame slot
+                flow f { rect a }
+                image "logo.png" logo
+                slot
+                frame -> f
+            }"#,
+        )
+        .expect("Should parse");
+        let Statement::TemplateDecl(decl) = &doc.statements[0].node else {
+            panic!("Expected TemplateDecl, got {:?}", doc.statements[0].node);
+        };
+        let body = decl.body.as_ref().unwrap();
+        assert_eq!(body[1].node, Statement::Slot);
+        assert!(matches!(body[2].node, Statement::Layout(_)));
+        assert!(matches!(body[3].node, Statement::Shape(_)));
+        assert_eq!(body[4].node, Statement::Slot);
+        assert!(matches!(body[5].node, Statement::Connection(_)));
+    }
+
+    #[test]
+    fn test_parse_units() {
+        let doc =
+            parse("rect a [width: 20mm, height: 0.5in, x: -2pt, y: 3 px]").expect("Should parse");
+        let Statement::Shape(shape) = &doc.statements[0].node else {
+            panic!("Expected Shape, got {:?}", doc.statements[0].node);
+        };
+        let values: Vec<_> = shape
+            .modifiers
+            .iter()
+            .map(|m| match &m.node.value.node {
+                StyleValue::Number { value, unit } => (*value, unit.as_deref()),
+                other => panic!("Expected Number, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            values,
+            [
+                (20.0, Some("mm")),
+                (0.5, Some("in")),
+                (-2.0, Some("pt")),
+                (3.0, Some("px"))
+            ]
+        );
+        assert!(parse("rect a [width: 20 furlongs]").is_err());
+    }
+
+    #[test]
+    fn test_parse_title_and_legend() {
+        let doc = parse(
+            r#"title "Overview" [font_size: 24]
+            caption "Figure 1"
+            legend [position: top_left] {
+                entry accent-1 "API calls"
+                entry #f00 "Errors" [stroke: none]
+            }
+            rect title"#,
+        )
+        .expect("Should parse");
+        let Statement::Title(title) = &doc.statements[0].node else {
+            panic!("Expected Title, got {:?}", doc.statements[0].node);
+        };
+        assert_eq!(
+            (title.kind, title.text.node.as_str()),
+            (TitleKind::Title, "Overview")
+        );
+        assert!(
+            matches!(&doc.statements[1].node, Statement::Title(t) if t.kind == TitleKind::Caption)
+        );
+        let Statement::Legend(legend) = &doc.statements[2].node else {
+            panic!("Expected Legend, got {:?}", doc.statements[2].node);
+        };
+        assert_eq!(legend.entries.len(), 2);
+        assert_eq!(legend.entries[1].node.label.node, "Errors");
+        assert_eq!(legend.entries[1].node.modifiers.len(), 1);
+        // `title` stays usable as a name
+        assert!(matches!(&doc.statements[3].node, Statement::Shape(_)));
+    }
+
+    #[test]
+    fn test_parse_canvas() {
+        let doc = parse("canvas [width: 800, height: 600, fit: cover]\nrect canvas")
+            .expect("Should parse");
+        let Statement::Canvas(decl) = &doc.statements[0].node else {
+            panic!("Expected Canvas, got {:?}", doc.statements[0].node);
+        };
+        assert_eq!(decl.modifiers.len(), 3);
+        assert_eq!(decl.modifiers[0].node.key.node, StyleKey::Width);
+        // `canvas` stays usable as a name
+        assert!(matches!(&doc.statements[1].node, Statement::Shape(_)));
+    }
+
+    #[test]
+    fn test_parse_typed_parameters() {
+        let doc = parse(
+            r#"template "chip" (pins: number 8, title: string "IC", kind: string) { rect body }"#,
+        )
+        .expect("Should parse");
+        let Statement::TemplateDecl(decl) = &doc.statements[0].node else {
+            panic!("Expected TemplateDecl, got {:?}", doc.statements[0].node);
+        };
+        let types: Vec<_> = decl
+            .parameters
+            .iter()
+            .map(|p| p.param_type.as_ref().map(|t| t.node))
+            .collect();
+        assert_eq!(
+            types,
+            [Some(ParamType::Number), Some(ParamType::String), None]
+        );
+        // Without a value, a type name is the default itself
+        assert_eq!(
+            decl.parameters[2].default_value.node,
+            StyleValue::Identifier(Identifier::new("string"))
+        );
+    }
+
     #[test]
     fn test_parse_template_with_export() {
         let doc = parse(
@@ -2562,4 +4214,75 @@ mod tests {
             other => panic!("Expected TemplateDecl, got {:?}", other),
         }
     }
+
+    #[test]
+    fn test_parse_recovers_at_statement_boundaries() {
+        let input = "rect a [fill: ]\nrect b\ncircle c [stroke ]\nrect d";
+        let (doc, errors) = parse_recovering(input);
+        assert_eq!(errors.len(), 2);
+        let names: Vec<_> = doc
+            .unwrap()
+            .statements
+            .iter()
+            .filter_map(|s| match &s.node {
+                Statement::Shape(shape) => shape.name.as_ref().map(|n| n.node.0.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, ["a", "b", "c", "d"]);
+
+        // A stray closing brace only costs itself
+        let (doc, errors) = parse_recovering("rect a\n}\nrect b");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(doc.unwrap().statements.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_recovers_inside_blocks() {
+        let input = "row r {\n  rect a\n  rect b [width: ]\n  rect c\n}\nrect d";
+        let (doc, errors) = parse_recovering(input);
+        assert_eq!(errors.len(), 1);
+        let doc = doc.unwrap();
+        assert_eq!(doc.statements.len(), 2);
+        match &doc.statements[0].node {
+            Statement::Layout(l) => assert_eq!(l.children.len(), 3),
+            other => panic!("Expected Layout, got {:?}", other),
+        }
+        // parse() still reports failure
+        assert!(parse(input).is_err());
+    }
+
+    #[test]
+    fn test_parse_reports_skipped_statements() {
+        // The errors are reported at `rect b` and `rect d`, but what is
+        // skipped are the unclosed brackets on the lines before
+        let input = "rect a [fill: red\nrect b\nrow {\n  rect c [\n  rect d\n}";
+        let (doc, errors, skipped) = parse_skipping(input);
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|e| !e.to_string().contains(RECOVERED)));
+        let skipped: Vec<_> = skipped.iter().map(|s| &input[s.clone()]).collect();
+        assert_eq!(skipped, ["[fill: red", "["]);
+        assert_eq!(doc.unwrap().statements.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_keeps_comments_as_trivia() {
+        let input = "// intro\nrect a [fill: red, /* inline */ stroke: blue] // after a\n";
+        let doc = parse(input).expect("Should parse");
+        assert_eq!(doc.statements.len(), 1);
+        let comments: Vec<_> = doc
+            .comments
+            .iter()
+            .map(|c| (c.node.text.as_str(), c.node.after))
+            .collect();
+        assert_eq!(
+            comments,
+            [
+                ("// intro", None),
+                ("/* inline */", Some(27)),
+                ("// after a", Some(54)),
+            ]
+        );
+        assert_eq!(&input[doc.comments[2].span.clone()], "// after a");
+    }
 }
@@ -85,8 +85,10 @@ pub enum Token {
     #[token("place")]
     Place,
     #[token("right-of")]
+    #[token("right_of")]
     RightOf,
     #[token("left-of")]
+    #[token("left_of")]
     LeftOf,
     #[token("above")]
     Above,
@@ -134,6 +136,10 @@ pub enum Token {
     Midpoint,
     #[token("contains")]
     Contains,
+    #[token("equal_width")]
+    EqualWidth,
+    #[token("equal_height")]
+    EqualHeight,
 
     // Constraint property keywords
     #[token("center_x")]
@@ -161,6 +167,14 @@ pub enum Token {
     #[token("+")]
     Plus,
 
+    // Arithmetic in constraint expressions (a.width = b.width * 0.5)
+    #[token("*")]
+    Star,
+    #[token("/")]
+    Slash,
+    #[token("%")]
+    Percent,
+
     // Delimiters
     #[token("{")]
     BraceOpen,
@@ -189,7 +203,8 @@ pub enum Token {
     Equals,
 
     // Literals - identifiers must come after keywords
-    #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*", |lex| lex.slice().to_string(), priority = 1)]
+    // `$` marks repeat loop variables: `$i`, `item_$i`
+    #[regex(r"[a-zA-Z_$][a-zA-Z0-9_$]*", |lex| lex.slice().to_string(), priority = 1)]
     Ident(String),
 
     #[regex(r#""([^"\\]|\\.)*""#, |lex| {
@@ -204,16 +219,28 @@ pub enum Token {
     #[regex(r"#[0-9a-fA-F]{3,6}", |lex| lex.slice().to_string())]
     HexColor(String),
 
-    // Comments (skip)
-    #[regex(r"//[^\n]*", logos::skip)]
-    LineComment,
+    // Comments, kept as trivia
+    #[regex(r"//[^\n]*", |lex| lex.slice().trim_end().to_string())]
+    LineComment(String),
+
+    #[regex(r"/\*([^*]|\*[^/])*\*/", |lex| lex.slice().to_string())]
+    BlockComment(String),
+}
 
-    #[regex(r"/\*([^*]|\*[^/])*\*/", logos::skip)]
-    BlockComment,
+impl Token {
+    /// Whether this is a `// line` or `/* block */` comment
+    pub fn is_comment(&self) -> bool {
+        matches!(self, Token::LineComment(_) | Token::BlockComment(_))
+    }
 }
 
-/// Lex input string into tokens with spans
+/// Lex input string into tokens with spans, skipping comments
 pub fn lex(input: &str) -> impl Iterator<Item = (Token, Span)> + '_ {
+    lex_with_comments(input).filter(|(tok, _)| !tok.is_comment())
+}
+
+/// Lex input string into tokens with spans, comments included
+pub fn lex_with_comments(input: &str) -> impl Iterator<Item = (Token, Span)> + '_ {
     Token::lexer(input)
         .spanned()
         .filter_map(|(tok, span)| tok.ok().map(|t| (t, span)))
@@ -273,6 +300,21 @@ mod tests {
     }
 
     #[test]
+    fn test_lex_with_comments() {
+        let tokens: Vec<_> = lex_with_comments("rect // line \n/* block */ circle").collect();
+        assert_eq!(
+            tokens,
+            vec![
+                (Token::Rect, 0..4),
+                (Token::LineComment("// line".to_string()), 5..13),
+                (Token::BlockComment("/* block */".to_string()), 14..25),
+                (Token::Circle, 26..32),
+            ]
+        );
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
     fn test_numbers() {
         let tokens: Vec<_> = lex("42 3.14 -10").map(|(t, _)| t).collect();
         assert_eq!(
@@ -428,10 +470,18 @@ mod tests {
 
     #[test]
     fn test_constrain_keywords() {
-        let tokens: Vec<_> = lex("constrain midpoint contains").map(|(t, _)| t).collect();
+        let tokens: Vec<_> = lex("constrain midpoint contains equal_width equal_height")
+            .map(|(t, _)| t)
+            .collect();
         assert_eq!(
             tokens,
-            vec![Token::Constrain, Token::Midpoint, Token::Contains]
+            vec![
+                Token::Constrain,
+                Token::Midpoint,
+                Token::Contains,
+                Token::EqualWidth,
+                Token::EqualHeight
+            ]
         );
     }
 
@@ -466,6 +516,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_arithmetic_operators() {
+        let tokens: Vec<_> = lex("* / // comment\n/* block */ *")
+            .map(|(t, _)| t)
+            .collect();
+        assert_eq!(tokens, vec![Token::Star, Token::Slash, Token::Star]);
+    }
+
     #[test]
     fn test_plus_for_offset() {
         let tokens: Vec<_> = lex("a.left = b.right + 20").map(|(t, _)| t).collect();
@@ -3,6 +3,12 @@
 pub mod ast;
 mod grammar;
 pub mod lexer;
+mod unparse;
+pub mod visit;
 
 pub use ast::*;
-pub use grammar::parse;
+pub use grammar::{parse, parse_recovering};
+pub use unparse::unparse;
+pub use visit::{Visitor, VisitorMut};
+pub(crate) use grammar::{parse_skipping, parse_style_value, style_key};
+pub(crate) use unparse::{constrain_text, expr_text, key_text, unparse_with_source, value_text};
@@ -0,0 +1,1142 @@
+//! Regenerate DSL source from an AST
+//!
+//! The output uses one statement per line with four-space indentation inside
+//! blocks. Chained connections are re-joined (`a -> b -> c`) when only the
+//! last segment carries a name or modifiers, which is how the parser splits them.
+
+use super::ast::*;
+
+/// Emit DSL source that parses back to an equivalent document
+///
+/// The document's comments are printed next to the statements they were
+/// written beside. Importers and tools that build or rewrite a [`Document`]
+/// use this to write it out as `.ail` source.
+pub fn unparse(doc: &Document) -> String {
+    unparse_with_source(doc, "")
+}
+
+/// Like [`unparse`], but also keeps single blank lines between statements
+/// from `source`, which `doc` was parsed from
+pub(crate) fn unparse_with_source(doc: &Document, source: &str) -> String {
+    let mut printer = Printer {
+        source,
+        comments: doc.comments.iter().cloned().collect(),
+        ..Default::default()
+    };
+    printer.statements(&doc.statements, 0);
+    printer.comments_before(usize::MAX, 0);
+    printer.out
+}
+
+#[derive(Default)]
+struct Printer<'a> {
+    out: String,
+    /// Source the document was parsed from, when blank lines are kept
+    source: &'a str,
+    /// Comments not yet printed, in source order
+    comments: std::collections::VecDeque<Spanned<Comment>>,
+    /// End offsets of the statements being printed, innermost last
+    ends: Vec<usize>,
+    /// End of the last statement or comment printed
+    last_end: usize,
+}
+
+impl Printer<'_> {
+    fn line(&mut self, depth: usize, text: &str) {
+        self.out.push_str(&"    ".repeat(depth));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn statements(&mut self, stmts: &[Spanned<Statement>], depth: usize) {
+        for stmt in stmts {
+            self.comments_before(stmt.span.start, depth);
+            self.blank_line_before(stmt.span.start);
+            self.last_end = stmt.span.start;
+            self.ends.push(stmt.span.end);
+            self.statement(&stmt.node, depth);
+            self.ends.pop();
+            let printed = self.out.len();
+            // Comments inside the statement (e.g. between modifiers) follow it
+            self.comments_before(stmt.span.end, depth);
+            self.last_end = self.last_end.max(stmt.span.end);
+            if self.out.len() == printed {
+                self.trailing_comment();
+            }
+        }
+    }
+
+    /// Print the pending comments that start before `offset`, one per line
+    fn comments_before(&mut self, offset: usize, depth: usize) {
+        while let Some(comment) = self.comments.front() {
+            if comment.span.start >= offset {
+                break;
+            }
+            let comment = self.comments.pop_front().unwrap_or_else(|| unreachable!());
+            self.blank_line_before(comment.span.start);
+            self.line(depth, &comment.node.text);
+            self.last_end = comment.span.end;
+        }
+    }
+
+    /// Append a comment that follows the last statement on the same line
+    fn trailing_comment(&mut self) {
+        let Some(comment) = self.comments.front() else {
+            return;
+        };
+        if comment.node.after != Some(self.last_end) {
+            return;
+        }
+        let comment = self.comments.pop_front().unwrap_or_else(|| unreachable!());
+        self.out.pop();
+        self.out.push(' ');
+        self.out.push_str(&comment.node.text);
+        self.out.push('\n');
+        self.last_end = comment.span.end;
+    }
+
+    /// Keep one blank line where the source had at least one before `offset`
+    fn blank_line_before(&mut self, offset: usize) {
+        let gap = self.source.get(self.last_end..offset).unwrap_or_default();
+        // Whole lines are the ones between the first and last line break
+        let lines: Vec<&str> = gap.split('\n').collect();
+        let blank = lines.len() > 2
+            && lines[1..lines.len() - 1]
+                .iter()
+                .any(|l| l.trim().is_empty());
+        if blank
+            && !self.out.is_empty()
+            && !self.out.ends_with("{\n")
+            && !self.out.ends_with("\n\n")
+        {
+            self.out.push('\n');
+        }
+    }
+
+    /// `head { children }`, or `head {}` when there are no children
+    fn block(&mut self, depth: usize, head: String, children: &[Spanned<Statement>]) {
+        if children.is_empty() {
+            self.line(depth, &format!("{} {{}}", head));
+        } else {
+            self.line(depth, &format!("{} {{", head));
+            self.statements(children, depth + 1);
+            // Comments after the last child stay inside the block
+            if let Some(&end) = self.ends.last() {
+                self.comments_before(end, depth + 1);
+            }
+            self.line(depth, "}");
+        }
+    }
+
+    /// A line inside a path or keyframe body, with the comments around it
+    fn body_line(&mut self, depth: usize, text: &str, span: &Span) {
+        self.comments_before(span.start, depth);
+        self.line(depth, text);
+        self.last_end = span.end;
+        self.trailing_comment();
+    }
+
+    /// `}` after [`Self::body_line`]s, keeping the comments before it inside
+    fn close_body(&mut self, depth: usize) {
+        if let Some(&end) = self.ends.last() {
+            self.comments_before(end, depth + 1);
+        }
+        self.line(depth, "}");
+    }
+
+    /// `if a { ... } else if b { ... } else { ... }`
+    fn if_decl(&mut self, decl: &IfDecl, depth: usize) {
+        let mut head = format!("if {} {{", decl.condition.node);
+        let mut decl = decl;
+        loop {
+            self.line(depth, &head);
+            self.statements(&decl.then_body, depth + 1);
+            match decl.else_body.as_slice() {
+                [] => break,
+                [Spanned {
+                    node: Statement::If(nested),
+                    ..
+                }] => {
+                    head = format!("}} else if {} {{", nested.condition.node);
+                    decl = nested;
+                }
+                body => {
+                    self.line(depth, "} else {");
+                    self.statements(body, depth + 1);
+                    break;
+                }
+            }
+        }
+        self.line(depth, "}");
+    }
+
+    fn statement(&mut self, stmt: &Statement, depth: usize) {
+        match stmt {
+            Statement::Shape(s) => self.shape(s, depth),
+            Statement::Connection(conns) => {
+                for chain in connection_chains(conns) {
+                    self.line(depth, &chain);
+                }
+            }
+            Statement::Layout(l) => {
+                let head = words([
+                    Some(layout_keyword(l.layout_type.node).to_string()),
+                    l.name.as_ref().map(|n| n.node.to_string()),
+                    modifier_block(&l.modifiers),
+                ]);
+                self.block(depth, head, &l.children);
+            }
+            Statement::Group(g) => {
+                let head = match &g.layer {
+                    Some(layer) => format!("layer {}", layer.node),
+                    None => words([
+                        Some("group".to_string()),
+                        g.name.as_ref().map(|n| n.node.to_string()),
+                    ]),
+                };
+                let head = words([Some(head), modifier_block(&g.modifiers)]);
+                self.block(depth, head, &g.children);
+            }
+            Statement::Constraint(c) => {
+                let relation = c
+                    .relation
+                    .as_ref()
+                    .zip(c.anchor.as_ref())
+                    .map(|(r, a)| format!("{} {}", relation_keyword(r.node), a.node));
+                let text = words([
+                    Some(format!("place {}", c.subject.node)),
+                    relation,
+                    modifier_block(&c.modifiers),
+                ]);
+                self.line(depth, &text);
+            }
+            Statement::Label(inner) => {
+                let start = self.out.len() + 4 * depth;
+                self.statement(inner, depth);
+                self.out.insert_str(start, "label: ");
+            }
+            Statement::Constrain(c) => self.line(depth, &constrain_text(c)),
+            Statement::TemplateDecl(t) => match (&t.source_path, &t.body) {
+                (Some(path), _) => self.line(
+                    depth,
+                    &format!(
+                        "template {} from {}",
+                        quoted(&t.name.node.0),
+                        quoted(&path.node)
+                    ),
+                ),
+                (None, body) => {
+                    let mut head = format!("template {}", quoted(&t.name.node.0));
+                    if !t.parameters.is_empty() {
+                        let params: Vec<String> = t
+                            .parameters
+                            .iter()
+                            .map(|p| {
+                                let type_prefix = p
+                                    .param_type
+                                    .as_ref()
+                                    .map_or(String::new(), |t| format!("{} ", t.node.name()));
+                                format!(
+                                    "{}: {}{}",
+                                    p.name.node,
+                                    type_prefix,
+                                    value_text(&p.default_value.node)
+                                )
+                            })
+                            .collect();
+                        head.push_str(&format!(" ({})", params.join(", ")));
+                    }
+                    self.block(depth, head, body.as_deref().unwrap_or_default());
+                }
+            },
+            Statement::TemplateInstance(inst) => {
+                let args: Vec<String> = inst
+                    .arguments
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k.node, value_text(&v.node)))
+                    .collect();
+                let args = (!args.is_empty()).then(|| format!("[{}]", args.join(", ")));
+                let text = words([
+                    Some(format!(
+                        "{} {}",
+                        inst.template_name.node, inst.instance_name.node
+                    )),
+                    args,
+                ]);
+                if inst.children.is_empty() {
+                    self.line(depth, &text);
+                } else {
+                    self.block(depth, text, &inst.children);
+                }
+            }
+            Statement::Embed(e) => {
+                let text = words([
+                    Some(format!("embed {} as {}", quoted(&e.path.node), e.name.node)),
+                    modifier_block(&e.modifiers),
+                ]);
+                self.line(depth, &text);
+            }
+            Statement::Import(i) => self.line(
+                depth,
+                &format!("import {} as {}", quoted(&i.path.node), i.alias.node),
+            ),
+            Statement::Export(e) => {
+                let names: Vec<&str> = e.exports.iter().map(|n| n.node.as_str()).collect();
+                self.line(depth, &format!("export {}", names.join(", ")));
+            }
+            Statement::AnchorDecl(a) => self.line(depth, &anchor_decl_text(a)),
+            Statement::Keyframe(kf) => {
+                let mut head = format!("keyframe {}", quoted(&kf.name.node));
+                if kf.no_resolve {
+                    head.push_str(" [no_resolve]");
+                }
+                if kf.operations.is_empty() {
+                    self.line(depth, &format!("{} {{}}", head));
+                    return;
+                }
+                self.line(depth, &format!("{} {{", head));
+                for op in &kf.operations {
+                    let text = match &op.node {
+                        KeyframeOp::Show(ids) => format!("show {}", identifier_list(ids)),
+                        KeyframeOp::Hide(ids) => format!("hide {}", identifier_list(ids)),
+                        KeyframeOp::Transform { target, modifiers } => format!(
+                            "transform {} {}",
+                            target.node,
+                            modifier_block(modifiers).unwrap_or_else(|| "[]".to_string())
+                        ),
+                    };
+                    self.body_line(depth + 1, &text, &op.span);
+                }
+                self.close_body(depth);
+            }
+            Statement::Repeat(r) => {
+                let head = format!("repeat {} as {}", expr_text(&r.count.node), r.variable.node);
+                self.block(depth, head, &r.body);
+            }
+            Statement::If(i) => self.if_decl(i, depth),
+            Statement::Step(s) => self.block(depth, format!("step {}", s.number.node), &s.children),
+            Statement::Slot => self.line(depth, "slot"),
+            Statement::Title(t) => {
+                let head = format!("{} {}", t.kind.as_str(), quoted(&t.text.node));
+                self.line(depth, &words([Some(head), modifier_block(&t.modifiers)]));
+            }
+            Statement::Legend(l) => {
+                let head = words([Some("legend".to_string()), modifier_block(&l.modifiers)]);
+                if l.entries.is_empty() {
+                    self.line(depth, &format!("{} {{}}", head));
+                    return;
+                }
+                self.line(depth, &format!("{} {{", head));
+                for entry in &l.entries {
+                    let text = words([
+                        Some("entry".to_string()),
+                        Some(value_text(&entry.node.color.node)),
+                        Some(quoted(&entry.node.label.node)),
+                        modifier_block(&entry.node.modifiers),
+                    ]);
+                    self.body_line(depth + 1, &text, &entry.span);
+                }
+                self.close_body(depth);
+            }
+            Statement::Canvas(c) => {
+                let text = format!(
+                    "canvas {}",
+                    modifier_block(&c.modifiers).unwrap_or_else(|| "[]".to_string())
+                );
+                self.line(depth, &text);
+            }
+            Statement::Animate(a) => {
+                let text = format!(
+                    "animate {} {}",
+                    a.target.node,
+                    modifier_block(&a.modifiers).unwrap_or_else(|| "[]".to_string())
+                );
+                self.line(depth, &text);
+            }
+            Statement::Let(l) => {
+                let text = format!("let {} = {}", l.name.node, expr_text(&l.value.node));
+                self.line(depth, &text);
+            }
+            Statement::Note(n) => {
+                let head = words([
+                    Some(format!("note at {}", anchor_ref_text(&n.target))),
+                    modifier_block(&n.modifiers),
+                ]);
+                self.block(depth, head, &n.children);
+            }
+        }
+    }
+
+    fn shape(&mut self, s: &ShapeDecl, depth: usize) {
+        let kind = match &s.shape_type.node {
+            ShapeType::Rectangle | ShapeType::SvgEmbed { .. } => "rect".to_string(),
+            ShapeType::Circle => "circle".to_string(),
+            ShapeType::Ellipse => "ellipse".to_string(),
+            ShapeType::Line => "line".to_string(),
+            ShapeType::Bus => "bus".to_string(),
+            ShapeType::State => "state".to_string(),
+            ShapeType::Custom { kind, .. } => kind.clone(),
+            ShapeType::Class {
+                attributes,
+                operations,
+            } => {
+                let head = words([
+                    Some("class".to_string()),
+                    s.name.as_ref().map(|n| n.node.to_string()),
+                    modifier_block(&s.modifiers),
+                ]);
+                if attributes.is_empty() && operations.is_empty() && s.ports.is_empty() {
+                    self.line(depth, &head);
+                    return;
+                }
+                self.line(depth, &format!("{} {{", head));
+                for attr in attributes {
+                    let text = format!("attr {}", quoted(&attr.node));
+                    self.body_line(depth + 1, &text, &attr.span);
+                }
+                for op in operations {
+                    self.body_line(depth + 1, &format!("op {}", quoted(&op.node)), &op.span);
+                }
+                for port in &s.ports {
+                    self.body_line(depth + 1, &port_text(&port.node), &port.span);
+                }
+                self.close_body(depth);
+                return;
+            }
+            ShapeType::Polygon => "polygon".to_string(),
+            ShapeType::Icon { icon_name, .. } => format!("icon {}", quoted(icon_name)),
+            ShapeType::Text { content } => format!("text {}", quoted(content)),
+            ShapeType::RasterImage { path, .. } => format!("image {}", quoted(path)),
+            ShapeType::Path(path) => {
+                let name = path.name.as_ref().map(|n| {
+                    if is_identifier(n.node.as_str()) {
+                        n.node.to_string()
+                    } else {
+                        quoted(n.node.as_str())
+                    }
+                });
+                let head = words([Some("path".to_string()), name, modifier_block(&s.modifiers)]);
+                self.line(depth, &format!("{} {{", head));
+                for cmd in &path.body.commands {
+                    self.body_line(depth + 1, &path_command_text(&cmd.node), &cmd.span);
+                }
+                self.close_body(depth);
+                return;
+            }
+        };
+        let text = words([
+            Some(kind),
+            s.name.as_ref().map(|n| n.node.to_string()),
+            modifier_block(&s.modifiers),
+        ]);
+        if s.ports.is_empty() {
+            self.line(depth, &text);
+            return;
+        }
+        self.line(depth, &format!("{} {{", text));
+        for port in &s.ports {
+            self.body_line(depth + 1, &port_text(&port.node), &port.span);
+        }
+        self.close_body(depth);
+    }
+}
+
+fn port_text(p: &PortDecl) -> String {
+    let side = match p.side {
+        Edge::Left => "left",
+        Edge::Top => "top",
+        Edge::Bottom => "bottom",
+        _ => "right",
+    };
+    let mut settings = vec![format!("side: {}", side)];
+    if p.at != 0.5 {
+        settings.push(format!("at: {}", number_text(p.at)));
+    }
+    if p.marker {
+        settings.push("marker: true".to_string());
+    }
+    format!("port {} [{}]", p.name.node, settings.join(", "))
+}
+
+/// Join the present parts with single spaces
+fn words<const N: usize>(parts: [Option<String>; N]) -> String {
+    parts.into_iter().flatten().collect::<Vec<_>>().join(" ")
+}
+
+fn quoted(s: &str) -> String {
+    format!("\"{}\"", s)
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn identifier_list(ids: &[Spanned<Identifier>]) -> String {
+    let names: Vec<&str> = ids.iter().map(|n| n.node.as_str()).collect();
+    names.join(", ")
+}
+
+/// Shortest decimal form: `10`, `0.5`, `-3`
+fn number_text(value: f64) -> String {
+    // Avoid printing `-0`
+    let value = if value == 0.0 { 0.0 } else { value };
+    format!("{}", value)
+}
+
+pub(crate) fn key_text(key: &StyleKey) -> &str {
+    match key {
+        StyleKey::Fill => "fill",
+        StyleKey::Stroke => "stroke",
+        StyleKey::StrokeWidth => "stroke_width",
+        StyleKey::Opacity => "opacity",
+        StyleKey::Label => "label",
+        StyleKey::LabelPosition => "label_position",
+        StyleKey::FontSize => "font_size",
+        StyleKey::Class => "class",
+        StyleKey::Gap => "gap",
+        StyleKey::Size => "size",
+        StyleKey::Width => "width",
+        StyleKey::Height => "height",
+        StyleKey::Routing => "routing",
+        StyleKey::Role => "role",
+        StyleKey::X => "x",
+        StyleKey::Y => "y",
+        StyleKey::StrokeDasharray => "stroke_dasharray",
+        StyleKey::Rotation => "rotation",
+        StyleKey::LabelAt => "label_at",
+        StyleKey::LabelOffset => "label_offset",
+        StyleKey::ZOrder => "z",
+        StyleKey::Custom(key) => key,
+    }
+}
+
+pub(crate) fn value_text(value: &StyleValue) -> String {
+    match value {
+        StyleValue::Color(ColorValue::Hex(c)) | StyleValue::Color(ColorValue::Named(c)) => {
+            c.clone()
+        }
+        StyleValue::Color(color) => color.token_string().unwrap_or_default(),
+        StyleValue::Number { value, unit } => {
+            format!("{}{}", number_text(*value), unit.as_deref().unwrap_or(""))
+        }
+        StyleValue::String(s) => quoted(s),
+        StyleValue::Keyword(k) => k.clone(),
+        StyleValue::Identifier(id) => id.to_string(),
+        StyleValue::IdentifierList(ids) => {
+            let names: Vec<&str> = ids.iter().map(|id| id.as_str()).collect();
+            names.join(", ")
+        }
+        StyleValue::Function { name, args } => {
+            let args: Vec<String> = args.iter().map(|a| value_text(&a.node)).collect();
+            format!("{}({})", name, args.join(", "))
+        }
+        StyleValue::Expr(expr) => expr_text(expr),
+    }
+}
+
+/// Arithmetic with only the parentheses precedence requires
+pub(crate) fn expr_text(expr: &NumExpr) -> String {
+    let precedence = |e: &NumExpr| match e {
+        NumExpr::Binary {
+            op: BinaryOp::Add | BinaryOp::Sub,
+            ..
+        } => 1,
+        NumExpr::Binary { .. } => 2,
+        _ => 3,
+    };
+    match expr {
+        NumExpr::Number(n) => number_text(*n),
+        NumExpr::Var(name) => name.clone(),
+        NumExpr::Binary { op, lhs, rhs } => {
+            let symbol = match op {
+                BinaryOp::Add => "+",
+                BinaryOp::Sub => "-",
+                BinaryOp::Mul => "*",
+                BinaryOp::Div => "/",
+            };
+            let own = precedence(expr);
+            let wrap = |e: &NumExpr, needs: bool| {
+                if needs {
+                    format!("({})", expr_text(e))
+                } else {
+                    expr_text(e)
+                }
+            };
+            // Operators are left-associative, so an equal-precedence right
+            // operand needs parentheses
+            format!(
+                "{} {} {}",
+                wrap(lhs, precedence(lhs) < own),
+                symbol,
+                wrap(rhs, precedence(rhs) <= own)
+            )
+        }
+    }
+}
+
+/// `[key: value, ...]`, or `None` when there are no modifiers
+fn modifier_block(modifiers: &[Spanned<StyleModifier>]) -> Option<String> {
+    if modifiers.is_empty() {
+        return None;
+    }
+    let items: Vec<String> = modifiers
+        .iter()
+        .map(|m| {
+            format!(
+                "{}: {}",
+                key_text(&m.node.key.node),
+                value_text(&m.node.value.node)
+            )
+        })
+        .collect();
+    Some(format!("[{}]", items.join(", ")))
+}
+
+fn layout_keyword(layout: LayoutType) -> &'static str {
+    match layout {
+        LayoutType::Row => "row",
+        LayoutType::Column => "col",
+        LayoutType::Grid => "grid",
+        LayoutType::Stack => "stack",
+        LayoutType::Flow => "flow",
+        LayoutType::Ring => "ring",
+        LayoutType::Timeline => "timeline",
+        LayoutType::Absolute => "absolute",
+    }
+}
+
+fn relation_keyword(relation: PositionRelation) -> &'static str {
+    match relation {
+        PositionRelation::RightOf => "right_of",
+        PositionRelation::LeftOf => "left_of",
+        PositionRelation::Above => "above",
+        PositionRelation::Below => "below",
+        PositionRelation::Inside => "inside",
+    }
+}
+
+fn arrow(direction: ConnectionDirection) -> &'static str {
+    match direction {
+        ConnectionDirection::Forward => "->",
+        ConnectionDirection::Backward => "<-",
+        ConnectionDirection::Bidirectional => "<->",
+        ConnectionDirection::Undirected => "--",
+    }
+}
+
+fn anchor_ref_text(r: &AnchorReference) -> String {
+    let mut text: String = r.scope.iter().map(|s| format!("{}.", s.node)).collect();
+    text += &match &r.anchor {
+        Some(anchor) => format!("{}.{}", r.element.node, anchor.node),
+        None => r.element.node.to_string(),
+    };
+    if let Some(offset) = &r.offset {
+        let sign = if offset.node < 0.0 { "" } else { "+" };
+        text.push_str(&format!("{}{}", sign, number_text(offset.node)));
+    }
+    text
+}
+
+/// Re-join consecutive connections into `a -> b -> c` chains
+fn connection_chains(conns: &[ConnectionDecl]) -> Vec<String> {
+    let mut chains = vec![];
+    let mut current: Option<String> = None;
+    let mut last_to: Option<String> = None;
+    for conn in conns {
+        let from = anchor_ref_text(&conn.from);
+        let segment = format!("{} {}", arrow(conn.direction), anchor_ref_text(&conn.to));
+        let text = match current.take() {
+            Some(chain) if last_to.as_deref() == Some(from.as_str()) => {
+                format!("{} {}", chain, segment)
+            }
+            previous => {
+                chains.extend(previous);
+                format!("{} {}", from, segment)
+            }
+        };
+        if conn.name.is_some() || !conn.modifiers.is_empty() {
+            // Only the last segment of a chain carries a name and modifiers
+            chains.push(words([
+                Some(text),
+                conn.name.as_ref().map(|n| format!("as {}", n.node)),
+                modifier_block(&conn.modifiers),
+            ]));
+            last_to = None;
+        } else {
+            current = Some(text);
+            last_to = Some(anchor_ref_text(&conn.to));
+        }
+    }
+    chains.extend(current);
+    chains
+}
+
+fn property_text(p: &PropertyRef) -> String {
+    let property = match &p.property.node {
+        ConstraintProperty::X => "x".to_string(),
+        ConstraintProperty::Y => "y".to_string(),
+        ConstraintProperty::Width => "width".to_string(),
+        ConstraintProperty::Height => "height".to_string(),
+        ConstraintProperty::Left => "left".to_string(),
+        ConstraintProperty::Right => "right".to_string(),
+        ConstraintProperty::Top => "top".to_string(),
+        ConstraintProperty::Bottom => "bottom".to_string(),
+        ConstraintProperty::CenterX => "center_x".to_string(),
+        ConstraintProperty::CenterY => "center_y".to_string(),
+        ConstraintProperty::Center => "center".to_string(),
+        ConstraintProperty::AnchorX(name) => format!("{}_x", name),
+        ConstraintProperty::AnchorY(name) => format!("{}_y", name),
+    };
+    format!("{}.{}", p.element.node, property)
+}
+
+/// ` + 10`, ` - 10`, or nothing for zero
+fn offset_text(offset: f64) -> String {
+    if offset > 0.0 {
+        format!(" + {}", number_text(offset))
+    } else if offset < 0.0 {
+        format!(" - {}", number_text(-offset))
+    } else {
+        String::new()
+    }
+}
+
+pub(crate) fn constrain_text(c: &ConstrainDecl) -> String {
+    let mut options = vec![];
+    let expr = match &c.expr {
+        ConstraintExpr::Equal { left, right } => {
+            format!("{} = {}", property_text(left), property_text(right))
+        }
+        ConstraintExpr::EqualWithOffset {
+            left,
+            right,
+            offset,
+        } => format!(
+            "{} = {}{}",
+            property_text(left),
+            property_text(right),
+            offset_text(*offset)
+        ),
+        ConstraintExpr::Constant { left, value } => {
+            format!("{} = {}", property_text(left), number_text(*value))
+        }
+        ConstraintExpr::GreaterOrEqual { left, value } => {
+            format!("{} >= {}", property_text(left), number_text(*value))
+        }
+        ConstraintExpr::LessOrEqual { left, value } => {
+            format!("{} <= {}", property_text(left), number_text(*value))
+        }
+        ConstraintExpr::Midpoint {
+            target,
+            a,
+            b,
+            offset,
+        } => format!(
+            "{} = midpoint({}, {}){}",
+            property_text(target),
+            a.node,
+            b.node,
+            offset_text(*offset)
+        ),
+        ConstraintExpr::Contains {
+            container,
+            elements,
+            padding,
+        } => {
+            if let Some(padding) = padding {
+                options.push(format!("padding: {}", number_text(*padding)));
+            }
+            format!("{} contains {}", container.node, identifier_list(elements))
+        }
+        ConstraintExpr::Linear {
+            left,
+            terms,
+            constant,
+        } => {
+            let mut rhs = String::new();
+            for (i, term) in terms.iter().enumerate() {
+                let magnitude = term.coefficient.abs();
+                let product = if magnitude == 1.0 {
+                    property_text(&term.property)
+                } else {
+                    format!(
+                        "{} * {}",
+                        number_text(magnitude),
+                        property_text(&term.property)
+                    )
+                };
+                match (i, term.coefficient < 0.0) {
+                    (0, false) => rhs.push_str(&product),
+                    (0, true) => rhs.push_str(&format!("-{}", product)),
+                    (_, false) => rhs.push_str(&format!(" + {}", product)),
+                    (_, true) => rhs.push_str(&format!(" - {}", product)),
+                }
+            }
+            format!(
+                "{} = {}{}",
+                property_text(left),
+                rhs,
+                offset_text(*constant)
+            )
+        }
+        ConstraintExpr::EqualSize {
+            dimension,
+            elements,
+        } => {
+            let keyword = match dimension {
+                SizeDimension::Width => "equal_width",
+                SizeDimension::Height => "equal_height",
+            };
+            format!("{} {}", keyword, identifier_list(elements))
+        }
+        ConstraintExpr::Relative {
+            subject,
+            relation,
+            anchor,
+            gap,
+        } => {
+            if let Some(gap) = gap {
+                options.push(format!("gap: {}", number_text(*gap)));
+            }
+            format!(
+                "{} {} {}",
+                subject.node,
+                relation_keyword(*relation),
+                anchor.node
+            )
+        }
+    };
+    if let Some(strength) = c.strength {
+        let name = match strength {
+            ConstraintStrength::Weak => "weak",
+            ConstraintStrength::Medium => "medium",
+            ConstraintStrength::Strong => "strong",
+            ConstraintStrength::Required => "required",
+        };
+        options.push(format!("strength: {}", name));
+    }
+    if options.is_empty() {
+        format!("constrain {}", expr)
+    } else {
+        format!("constrain {} [{}]", expr, options.join(", "))
+    }
+}
+
+fn anchor_decl_text(a: &AnchorDecl) -> String {
+    let position = match &a.position {
+        AnchorPosition::PropertyRef(p) => property_text(p),
+        AnchorPosition::PropertyRefWithOffset { prop_ref, offset } => {
+            format!("{}{}", property_text(prop_ref), offset_text(*offset))
+        }
+    };
+    let mut options = vec![format!("position: {}", position)];
+    if let Some(direction) = &a.direction {
+        let direction = match direction {
+            AnchorDirectionSpec::Cardinal(CardinalDirection::Up) => "up".to_string(),
+            AnchorDirectionSpec::Cardinal(CardinalDirection::Down) => "down".to_string(),
+            AnchorDirectionSpec::Cardinal(CardinalDirection::Left) => "left".to_string(),
+            AnchorDirectionSpec::Cardinal(CardinalDirection::Right) => "right".to_string(),
+            AnchorDirectionSpec::Angle(angle) => number_text(*angle),
+        };
+        options.push(format!("direction: {}", direction));
+    }
+    format!("anchor {} [{}]", a.name.node, options.join(", "))
+}
+
+fn position_options(position: &Option<VertexPosition>) -> Vec<String> {
+    let mut options = vec![];
+    if let Some(position) = position {
+        if let Some(x) = position.x {
+            options.push(format!("x: {}", number_text(x)));
+        }
+        if let Some(y) = position.y {
+            options.push(format!("y: {}", number_text(y)));
+        }
+    }
+    options
+}
+
+fn arc_options(params: &ArcParams) -> Vec<String> {
+    match params {
+        ArcParams::Bulge(_) if *params == ArcParams::default() => vec![],
+        ArcParams::Bulge(bulge) => vec![format!("bulge: {}", number_text(*bulge))],
+        ArcParams::Radius {
+            radius,
+            sweep,
+            large_arc,
+        } => {
+            let mut options = vec![format!("radius: {}", number_text(*radius))];
+            if *sweep == SweepDirection::Counterclockwise {
+                options.push("sweep: ccw".to_string());
+            }
+            if *large_arc {
+                options.push("large_arc: true".to_string());
+            }
+            options
+        }
+    }
+}
+
+fn path_command_text(cmd: &PathCommand) -> String {
+    let with_options = |head: String, options: Vec<String>| {
+        if options.is_empty() {
+            head
+        } else {
+            format!("{} [{}]", head, options.join(", "))
+        }
+    };
+    match cmd {
+        PathCommand::Vertex(v) => with_options(
+            format!("vertex {}", v.name.node),
+            position_options(&v.position),
+        ),
+        PathCommand::LineTo(l) => with_options(
+            format!("line_to {}", l.target.node),
+            position_options(&l.position),
+        ),
+        PathCommand::ArcTo(a) => {
+            let mut options = position_options(&a.position);
+            options.extend(arc_options(&a.params));
+            with_options(format!("arc_to {}", a.target.node), options)
+        }
+        PathCommand::CurveTo(c) => {
+            let mut options = position_options(&c.position);
+            if let Some(via) = &c.via {
+                options.push(format!("via: {}", via.node));
+            }
+            with_options(format!("curve_to {}", c.target.node), options)
+        }
+        // `close_arc` has no surface syntax; a straight close is the closest form
+        PathCommand::Close | PathCommand::CloseArc(_) => "close".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_unparse_round_trip() {
+        let source = r#"canvas [width: 210mm, height: 297mm, fit: cover]
+title "Overview" [font_size: 24]
+caption "Figure 1"
+legend [position: top_right] {
+    entry accent "API calls"
+    entry #ff0000 "Errors" [stroke: black]
+}
+template "svc" (name: string "Service", detailed: false, compact: bool true) {
+    rect body [width: 140, fill: accent-light, label: name]
+    anchor top_conn [position: body.top - 4, direction: up]
+    slot
+    if detailed {
+        repeat 3 as i {
+            rect row_$i [y: $i * 20]
+        }
+    } else if compact {
+    } else {
+        text "..." more
+    }
+}
+import "lib/electronics.ail" as elec
+col main [gap: 20] {
+    group backend [label: "Backend"] {
+        rect api [fill: #ff0000, stroke_width: 2]
+        text "Read only" note_text
+    }
+    svc web [name: "Web"] {
+        text "v2" version
+    }
+    elec.resistor r1
+}
+path arrow [fill: none] {
+    vertex a
+    line_to b [x: 10]
+    arc_to c [x: 10, y: 10, radius: 5, sweep: ccw]
+    curve_to d [x: -5, y: 0, via: b]
+    close
+}
+a -> b -> c [label: "flow"]
+api.right <-> web as link
+api.bottom+10 -> web.top-5
+rect chip [width: 60] {
+    port in1 [side: left, at: 0.25]
+    port out [side: right, marker: true]
+}
+place note_text below main [y: 10]
+constrain api.left = web.right + 10
+constrain web.width = 0.5 * api.width - 2 [strength: weak]
+constrain main contains api, web [padding: 8]
+keyframe "intro" [no_resolve] {
+    show api, web
+    transform api [opacity: 0.5]
+}
+"#;
+        let doc = parse(source).unwrap();
+        assert_eq!(unparse(&doc), source);
+    }
+
+    #[test]
+    fn test_unparse_keeps_comments() {
+        let source = r#"// Header
+col main [gap: 20] {
+    rect a [fill: red] // trailing
+    /* before b */
+    rect b
+    // last in block
+}
+path arrow [fill: none] {
+    // start
+    vertex a
+    line_to b [x: 10] // tip
+    /* closing */
+}
+keyframe "intro" {
+    show a // first
+}
+"#;
+        let doc = parse(source).unwrap();
+        assert_eq!(doc.comments.len(), 8);
+        assert_eq!(unparse(&doc), source);
+
+        // Comments inside a modifier list follow the statement
+        let doc = parse("rect a [fill: red, /* why */ stroke: blue]").unwrap();
+        assert_eq!(
+            unparse(&doc),
+            "rect a [fill: red, stroke: blue]\n/* why */\n"
+        );
+    }
+
+    /// Parse, unparse and parse again; the two printed forms must agree
+    fn assert_stable(source: &str) {
+        let doc = parse(source).unwrap_or_else(|e| panic!("{:?}\n{}", e, source));
+        let printed = unparse(&doc);
+        let reparsed = parse(&printed).unwrap_or_else(|e| panic!("{:?}\n{}", e, printed));
+        assert_eq!(unparse(&reparsed), printed, "from:\n{}", source);
+        assert_eq!(reparsed.statements.len(), doc.statements.len());
+    }
+
+    #[test]
+    fn test_unparse_examples_are_stable() {
+        for dir in ["examples", "tests/lint-fixtures"] {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.extension().is_some_and(|e| e == "ail") {
+                    assert_stable(&std::fs::read_to_string(&path).unwrap());
+                }
+            }
+        }
+    }
+
+    /// Small deterministic generator of random documents (splitmix64)
+    struct Generator {
+        state: u64,
+        names: Vec<String>,
+    }
+
+    impl Generator {
+        fn below(&mut self, n: usize) -> usize {
+            self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = self.state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            ((z ^ (z >> 31)) % n as u64) as usize
+        }
+
+        fn pick<'a>(&mut self, options: &[&'a str]) -> &'a str {
+            options[self.below(options.len())]
+        }
+
+        fn name(&mut self) -> String {
+            let name = format!("n{}", self.names.len());
+            self.names.push(name.clone());
+            name
+        }
+
+        fn existing(&mut self) -> String {
+            let index = self.below(self.names.len());
+            self.names[index].clone()
+        }
+
+        fn modifiers(&mut self) -> String {
+            const MODIFIERS: &[&str] = &[
+                "fill: red",
+                "fill: accent-1",
+                "fill: #ff0000",
+                "stroke: foreground-dark",
+                "stroke_width: 2",
+                "opacity: 0.5",
+                "width: 120",
+                "height: 20mm",
+                "label: \"A \\\"quoted\\\" label\"",
+                "rotation: -45",
+                "class: \"db\"",
+                "fill: darken(accent, 20%)",
+                "stroke: fade(accent-1, transparent)",
+            ];
+            let count = self.below(4);
+            if count == 0 {
+                return String::new();
+            }
+            let modifiers: Vec<&str> = (0..count).map(|_| self.pick(MODIFIERS)).collect();
+            format!(" [{}]", modifiers.join(", "))
+        }
+
+        fn statement(&mut self, depth: usize, out: &mut String) {
+            let indent = "  ".repeat(depth);
+            match self.below(if depth < 2 { 8 } else { 5 }) {
+                0..=2 => {
+                    let shape = match self.pick(&["rect", "circle", "ellipse", "text"]) {
+                        "text" => "text \"Hello\"",
+                        shape => shape,
+                    };
+                    let name = self.name();
+                    let modifiers = self.modifiers();
+                    out.push_str(&format!("{}{} {}{}\n", indent, shape, name, modifiers));
+                }
+                3 | 4 if self.names.len() >= 2 => {
+                    let from = self.existing();
+                    let to = self.existing();
+                    let arrow = self.pick(&["->", "<-", "<->", "--"]);
+                    let label = self.pick(&["", " [label: \"calls\"]", " [routing: direct]"]);
+                    out.push_str(&format!("{}{} {} {}{}\n", indent, from, arrow, to, label));
+                }
+                3 | 4 => {
+                    let name = self.name();
+                    out.push_str(&format!("{}rect {}\n", indent, name));
+                }
+                _ => {
+                    let keyword = self.pick(&["row", "col", "stack", "group"]);
+                    let name = self.name();
+                    let modifiers = self.modifiers();
+                    out.push_str(&format!("{}{} {}{} {{\n", indent, keyword, name, modifiers));
+                    for _ in 0..1 + self.below(3) {
+                        self.statement(depth + 1, out);
+                    }
+                    out.push_str(&format!("{}}}\n", indent));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_unparse_random_documents_are_stable() {
+        for seed in 0..300 {
+            let mut generator = Generator {
+                state: seed,
+                names: vec![],
+            };
+            let mut source = String::new();
+            for _ in 0..1 + generator.below(8) {
+                generator.statement(0, &mut source);
+            }
+            if generator.names.len() >= 2 {
+                let (a, b) = (&generator.names[0], &generator.names[1]);
+                source.push_str(&format!("constrain {}.left = {}.right + 10\n", a, b));
+                source.push_str(&format!("place {} below {} [x: 5]\n", a, b));
+            }
+            assert_stable(&source);
+        }
+    }
+}
@@ -0,0 +1,563 @@
+//! Document traversal: [`Visitor`] walks an AST, [`VisitorMut`] rewrites one
+//!
+//! Both traits have a method per kind of node with a default that walks into
+//! the node's children through the matching `walk_*` function. Override the
+//! methods for the nodes of interest and call the `walk_*` function from the
+//! override to keep descending:
+//!
+//! ```rust
+//! use agent_illustrator::parser::{parse, Identifier, Spanned, VisitorMut};
+//!
+//! struct Rename;
+//!
+//! impl VisitorMut for Rename {
+//!     fn visit_identifier(&mut self, id: &mut Spanned<Identifier>) {
+//!         if id.node.as_str() == "db" {
+//!             id.node = Identifier::new("database");
+//!         }
+//!     }
+//! }
+//!
+//! let mut doc = parse("rect db\nrect api\napi -> db").unwrap();
+//! Rename.visit_document(&mut doc);
+//! assert_eq!(agent_illustrator::parser::unparse(&doc), "rect database\nrect api\napi -> database\n");
+//! ```
+//!
+//! Template bodies are walked like any other block; override
+//! `visit_statement` to skip `Statement::TemplateDecl` where their names
+//! should be left alone.
+
+use super::ast::*;
+
+/// Read-only traversal of a [`Document`]
+pub trait Visitor {
+    fn visit_document(&mut self, doc: &Document) {
+        self.visit_statements(&doc.statements);
+    }
+
+    /// A block of statements: the document, or the children of a container
+    fn visit_statements(&mut self, stmts: &[Spanned<Statement>]) {
+        walk_statements(self, stmts);
+    }
+
+    fn visit_statement(&mut self, stmt: &Statement) {
+        walk_statement(self, stmt);
+    }
+
+    fn visit_modifier(&mut self, modifier: &Spanned<StyleModifier>) {
+        walk_modifier(self, modifier);
+    }
+
+    /// A style value; function arguments are values of their own
+    fn visit_value(&mut self, value: &Spanned<StyleValue>) {
+        walk_value(self, value);
+    }
+
+    /// An element name, where it is declared and everywhere it is referenced
+    fn visit_identifier(&mut self, _id: &Spanned<Identifier>) {}
+}
+
+/// Mutable traversal of a [`Document`], for rewriting it in place
+pub trait VisitorMut {
+    fn visit_document(&mut self, doc: &mut Document) {
+        self.visit_statements(&mut doc.statements);
+    }
+
+    /// A block of statements: the document, or the children of a container.
+    /// Override to add or remove statements.
+    fn visit_statements(&mut self, stmts: &mut Vec<Spanned<Statement>>) {
+        walk_statements_mut(self, stmts);
+    }
+
+    fn visit_statement(&mut self, stmt: &mut Statement) {
+        walk_statement_mut(self, stmt);
+    }
+
+    fn visit_modifier(&mut self, modifier: &mut Spanned<StyleModifier>) {
+        walk_modifier_mut(self, modifier);
+    }
+
+    /// A style value; function arguments are values of their own
+    fn visit_value(&mut self, value: &mut Spanned<StyleValue>) {
+        walk_value_mut(self, value);
+    }
+
+    /// An element name, where it is declared and everywhere it is referenced
+    fn visit_identifier(&mut self, _id: &mut Spanned<Identifier>) {}
+}
+
+pub fn walk_statements<V: Visitor + ?Sized>(v: &mut V, stmts: &[Spanned<Statement>]) {
+    for stmt in stmts {
+        v.visit_statement(&stmt.node);
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(v: &mut V, stmt: &Statement) {
+    let modifiers = |v: &mut V, modifiers: &[Spanned<StyleModifier>]| {
+        for modifier in modifiers {
+            v.visit_modifier(modifier);
+        }
+    };
+    match stmt {
+        Statement::Shape(s) => {
+            if let Some(name) = &s.name {
+                v.visit_identifier(name);
+            }
+            if let ShapeType::Path(PathDecl {
+                name: Some(name), ..
+            }) = &s.shape_type.node
+            {
+                v.visit_identifier(name);
+            }
+            modifiers(v, &s.modifiers);
+        }
+        Statement::Connection(conns) => {
+            for conn in conns {
+                for end in [&conn.from, &conn.to] {
+                    for scope in &end.scope {
+                        v.visit_identifier(scope);
+                    }
+                    v.visit_identifier(&end.element);
+                }
+                if let Some(name) = &conn.name {
+                    v.visit_identifier(name);
+                }
+                modifiers(v, &conn.modifiers);
+            }
+        }
+        Statement::Layout(l) => {
+            if let Some(name) = &l.name {
+                v.visit_identifier(name);
+            }
+            modifiers(v, &l.modifiers);
+            v.visit_statements(&l.children);
+        }
+        Statement::Group(g) => {
+            if let Some(name) = &g.name {
+                v.visit_identifier(name);
+            }
+            modifiers(v, &g.modifiers);
+            v.visit_statements(&g.children);
+        }
+        Statement::Constraint(c) => {
+            v.visit_identifier(&c.subject);
+            if let Some(anchor) = &c.anchor {
+                v.visit_identifier(anchor);
+            }
+            modifiers(v, &c.modifiers);
+        }
+        Statement::Label(inner) => v.visit_statement(inner),
+        Statement::Constrain(c) => walk_constraint(v, &c.expr),
+        Statement::TemplateDecl(t) => {
+            for param in &t.parameters {
+                v.visit_value(&param.default_value);
+            }
+            if let Some(body) = &t.body {
+                v.visit_statements(body);
+            }
+        }
+        Statement::TemplateInstance(inst) => {
+            v.visit_identifier(&inst.instance_name);
+            for (_, value) in &inst.arguments {
+                v.visit_value(value);
+            }
+            v.visit_statements(&inst.children);
+        }
+        Statement::Embed(e) => {
+            v.visit_identifier(&e.name);
+            modifiers(v, &e.modifiers);
+        }
+        Statement::Export(e) => {
+            for name in &e.exports {
+                v.visit_identifier(name);
+            }
+        }
+        Statement::AnchorDecl(a) => {
+            let (AnchorPosition::PropertyRef(prop_ref)
+            | AnchorPosition::PropertyRefWithOffset { prop_ref, .. }) = &a.position;
+            for segment in &prop_ref.element.node.segments {
+                v.visit_identifier(segment);
+            }
+        }
+        Statement::Keyframe(kf) => {
+            for op in &kf.operations {
+                match &op.node {
+                    KeyframeOp::Show(ids) | KeyframeOp::Hide(ids) => {
+                        for id in ids {
+                            v.visit_identifier(id);
+                        }
+                    }
+                    KeyframeOp::Transform {
+                        target,
+                        modifiers: m,
+                    } => {
+                        v.visit_identifier(target);
+                        modifiers(v, m);
+                    }
+                }
+            }
+        }
+        Statement::Note(n) => {
+            for scope in &n.target.scope {
+                v.visit_identifier(scope);
+            }
+            v.visit_identifier(&n.target.element);
+            modifiers(v, &n.modifiers);
+            v.visit_statements(&n.children);
+        }
+        Statement::Repeat(r) => v.visit_statements(&r.body),
+        Statement::If(i) => {
+            v.visit_statements(&i.then_body);
+            v.visit_statements(&i.else_body);
+        }
+        Statement::Canvas(c) => modifiers(v, &c.modifiers),
+        Statement::Title(t) => modifiers(v, &t.modifiers),
+        Statement::Legend(l) => {
+            modifiers(v, &l.modifiers);
+            for entry in &l.entries {
+                v.visit_value(&entry.node.color);
+                modifiers(v, &entry.node.modifiers);
+            }
+        }
+        Statement::Animate(a) => {
+            v.visit_identifier(&a.target);
+            modifiers(v, &a.modifiers);
+        }
+        Statement::Step(s) => v.visit_statements(&s.children),
+        Statement::Import(_) | Statement::Let(_) | Statement::Slot => {}
+    }
+}
+
+fn walk_constraint<V: Visitor + ?Sized>(v: &mut V, expr: &ConstraintExpr) {
+    let property = |v: &mut V, prop: &PropertyRef| {
+        for segment in &prop.element.node.segments {
+            v.visit_identifier(segment);
+        }
+    };
+    match expr {
+        ConstraintExpr::Equal { left, right }
+        | ConstraintExpr::EqualWithOffset { left, right, .. } => {
+            property(v, left);
+            property(v, right);
+        }
+        ConstraintExpr::Constant { left, .. }
+        | ConstraintExpr::GreaterOrEqual { left, .. }
+        | ConstraintExpr::LessOrEqual { left, .. } => property(v, left),
+        ConstraintExpr::Midpoint { target, a, b, .. } => {
+            property(v, target);
+            v.visit_identifier(a);
+            v.visit_identifier(b);
+        }
+        ConstraintExpr::Contains {
+            container,
+            elements,
+            ..
+        } => {
+            v.visit_identifier(container);
+            for element in elements {
+                v.visit_identifier(element);
+            }
+        }
+        ConstraintExpr::Linear { left, terms, .. } => {
+            property(v, left);
+            for term in terms {
+                property(v, &term.property);
+            }
+        }
+        ConstraintExpr::EqualSize { elements, .. } => {
+            for element in elements {
+                v.visit_identifier(element);
+            }
+        }
+        ConstraintExpr::Relative {
+            subject, anchor, ..
+        } => {
+            v.visit_identifier(subject);
+            v.visit_identifier(anchor);
+        }
+    }
+}
+
+pub fn walk_modifier<V: Visitor + ?Sized>(v: &mut V, modifier: &Spanned<StyleModifier>) {
+    v.visit_value(&modifier.node.value);
+}
+
+pub fn walk_value<V: Visitor + ?Sized>(v: &mut V, value: &Spanned<StyleValue>) {
+    if let StyleValue::Function { args, .. } = &value.node {
+        for arg in args {
+            v.visit_value(arg);
+        }
+    }
+}
+
+pub fn walk_statements_mut<V: VisitorMut + ?Sized>(v: &mut V, stmts: &mut [Spanned<Statement>]) {
+    for stmt in stmts {
+        v.visit_statement(&mut stmt.node);
+    }
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut Statement) {
+    let modifiers = |v: &mut V, modifiers: &mut [Spanned<StyleModifier>]| {
+        for modifier in modifiers {
+            v.visit_modifier(modifier);
+        }
+    };
+    match stmt {
+        Statement::Shape(s) => {
+            if let Some(name) = &mut s.name {
+                v.visit_identifier(name);
+            }
+            if let ShapeType::Path(PathDecl {
+                name: Some(name), ..
+            }) = &mut s.shape_type.node
+            {
+                v.visit_identifier(name);
+            }
+            modifiers(v, &mut s.modifiers);
+        }
+        Statement::Connection(conns) => {
+            for conn in conns {
+                for end in [&mut conn.from, &mut conn.to] {
+                    for scope in &mut end.scope {
+                        v.visit_identifier(scope);
+                    }
+                    v.visit_identifier(&mut end.element);
+                }
+                if let Some(name) = &mut conn.name {
+                    v.visit_identifier(name);
+                }
+                modifiers(v, &mut conn.modifiers);
+            }
+        }
+        Statement::Layout(l) => {
+            if let Some(name) = &mut l.name {
+                v.visit_identifier(name);
+            }
+            modifiers(v, &mut l.modifiers);
+            v.visit_statements(&mut l.children);
+        }
+        Statement::Group(g) => {
+            if let Some(name) = &mut g.name {
+                v.visit_identifier(name);
+            }
+            modifiers(v, &mut g.modifiers);
+            v.visit_statements(&mut g.children);
+        }
+        Statement::Constraint(c) => {
+            v.visit_identifier(&mut c.subject);
+            if let Some(anchor) = &mut c.anchor {
+                v.visit_identifier(anchor);
+            }
+            modifiers(v, &mut c.modifiers);
+        }
+        Statement::Label(inner) => v.visit_statement(inner),
+        Statement::Constrain(c) => walk_constraint_mut(v, &mut c.expr),
+        Statement::TemplateDecl(t) => {
+            for param in &mut t.parameters {
+                v.visit_value(&mut param.default_value);
+            }
+            if let Some(body) = &mut t.body {
+                v.visit_statements(body);
+            }
+        }
+        Statement::TemplateInstance(inst) => {
+            v.visit_identifier(&mut inst.instance_name);
+            for (_, value) in &mut inst.arguments {
+                v.visit_value(value);
+            }
+            v.visit_statements(&mut inst.children);
+        }
+        Statement::Embed(e) => {
+            v.visit_identifier(&mut e.name);
+            modifiers(v, &mut e.modifiers);
+        }
+        Statement::Export(e) => {
+            for name in &mut e.exports {
+                v.visit_identifier(name);
+            }
+        }
+        Statement::AnchorDecl(a) => {
+            let (AnchorPosition::PropertyRef(prop_ref)
+            | AnchorPosition::PropertyRefWithOffset { prop_ref, .. }) = &mut a.position;
+            for segment in &mut prop_ref.element.node.segments {
+                v.visit_identifier(segment);
+            }
+        }
+        Statement::Keyframe(kf) => {
+            for op in &mut kf.operations {
+                match &mut op.node {
+                    KeyframeOp::Show(ids) | KeyframeOp::Hide(ids) => {
+                        for id in ids {
+                            v.visit_identifier(id);
+                        }
+                    }
+                    KeyframeOp::Transform {
+                        target,
+                        modifiers: m,
+                    } => {
+                        v.visit_identifier(target);
+                        modifiers(v, m);
+                    }
+                }
+            }
+        }
+        Statement::Note(n) => {
+            for scope in &mut n.target.scope {
+                v.visit_identifier(scope);
+            }
+            v.visit_identifier(&mut n.target.element);
+            modifiers(v, &mut n.modifiers);
+            v.visit_statements(&mut n.children);
+        }
+        Statement::Repeat(r) => v.visit_statements(&mut r.body),
+        Statement::If(i) => {
+            v.visit_statements(&mut i.then_body);
+            v.visit_statements(&mut i.else_body);
+        }
+        Statement::Canvas(c) => modifiers(v, &mut c.modifiers),
+        Statement::Title(t) => modifiers(v, &mut t.modifiers),
+        Statement::Legend(l) => {
+            modifiers(v, &mut l.modifiers);
+            for entry in &mut l.entries {
+                v.visit_value(&mut entry.node.color);
+                modifiers(v, &mut entry.node.modifiers);
+            }
+        }
+        Statement::Animate(a) => {
+            v.visit_identifier(&mut a.target);
+            modifiers(v, &mut a.modifiers);
+        }
+        Statement::Step(s) => v.visit_statements(&mut s.children),
+        Statement::Import(_) | Statement::Let(_) | Statement::Slot => {}
+    }
+}
+
+fn walk_constraint_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut ConstraintExpr) {
+    let property = |v: &mut V, prop: &mut PropertyRef| {
+        for segment in &mut prop.element.node.segments {
+            v.visit_identifier(segment);
+        }
+    };
+    match expr {
+        ConstraintExpr::Equal { left, right }
+        | ConstraintExpr::EqualWithOffset { left, right, .. } => {
+            property(v, left);
+            property(v, right);
+        }
+        ConstraintExpr::Constant { left, .. }
+        | ConstraintExpr::GreaterOrEqual { left, .. }
+        | ConstraintExpr::LessOrEqual { left, .. } => property(v, left),
+        ConstraintExpr::Midpoint { target, a, b, .. } => {
+            property(v, target);
+            v.visit_identifier(a);
+            v.visit_identifier(b);
+        }
+        ConstraintExpr::Contains {
+            container,
+            elements,
+            ..
+        } => {
+            v.visit_identifier(container);
+            for element in elements {
+                v.visit_identifier(element);
+            }
+        }
+        ConstraintExpr::Linear { left, terms, .. } => {
+            property(v, left);
+            for term in terms {
+                property(v, &mut term.property);
+            }
+        }
+        ConstraintExpr::EqualSize { elements, .. } => {
+            for element in elements {
+                v.visit_identifier(element);
+            }
+        }
+        ConstraintExpr::Relative {
+            subject, anchor, ..
+        } => {
+            v.visit_identifier(subject);
+            v.visit_identifier(anchor);
+        }
+    }
+}
+
+pub fn walk_modifier_mut<V: VisitorMut + ?Sized>(v: &mut V, modifier: &mut Spanned<StyleModifier>) {
+    v.visit_value(&mut modifier.node.value);
+}
+
+pub fn walk_value_mut<V: VisitorMut + ?Sized>(v: &mut V, value: &mut Spanned<StyleValue>) {
+    if let StyleValue::Function { args, .. } = &mut value.node {
+        for arg in args {
+            v.visit_value(arg);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, unparse};
+
+    #[test]
+    fn test_visitor_sees_every_reference() {
+        struct Names(Vec<String>);
+        impl Visitor for Names {
+            fn visit_identifier(&mut self, id: &Spanned<Identifier>) {
+                self.0.push(id.node.0.clone());
+            }
+        }
+
+        let doc = parse(
+            "row r { rect a\n group g { circle b } }\na -> b as link\nplace a right-of b\nconstrain a.left = b.right + 10\nkeyframe \"k\" { hide link }\nnote at b.top { text \"hi\" }",
+        )
+        .unwrap();
+        let mut names = Names(vec![]);
+        names.visit_document(&doc);
+        assert_eq!(
+            names.0,
+            ["r", "a", "g", "b", "a", "b", "link", "a", "b", "a", "b", "link", "b"]
+        );
+    }
+
+    #[test]
+    fn test_visitor_mut_removes_statements() {
+        struct StripDebug;
+        impl VisitorMut for StripDebug {
+            fn visit_statements(&mut self, stmts: &mut Vec<Spanned<Statement>>) {
+                stmts.retain(|s| {
+                    !matches!(&s.node, Statement::Shape(shape) if shape.modifiers.iter().any(|m| {
+                        m.node.key.node == StyleKey::Class
+                            && matches!(&m.node.value.node, StyleValue::Identifier(id) if id.0 == "debug")
+                    }))
+                });
+                walk_statements_mut(self, stmts);
+            }
+        }
+
+        let mut doc =
+            parse("rect a [class: debug]\ncol { rect b\n rect c [class: debug] }").unwrap();
+        StripDebug.visit_document(&mut doc);
+        assert_eq!(unparse(&doc), "col {\n    rect b\n}\n");
+    }
+
+    #[test]
+    fn test_visitor_mut_rewrites_nested_values() {
+        struct Double;
+        impl VisitorMut for Double {
+            fn visit_value(&mut self, value: &mut Spanned<StyleValue>) {
+                walk_value_mut(self, value);
+                if let StyleValue::Number { value, .. } = &mut value.node {
+                    *value *= 2.0;
+                }
+            }
+        }
+
+        let mut doc = parse("rect a [width: 10, fill: alpha(accent, 0.25)]").unwrap();
+        Double.visit_document(&mut doc);
+        assert_eq!(
+            unparse(&doc),
+            "rect a [width: 20, fill: alpha(accent, 0.5)]\n"
+        );
+    }
+}
@@ -0,0 +1,439 @@
+//! Targeted edits to a parsed document
+//!
+//! Agents editing an existing diagram can describe the change as a few
+//! [`PatchOp`]s instead of regenerating the whole source. [`apply_patch`]
+//! checks every operation against the document (unknown elements, duplicate
+//! names, existing connections) and reports all problems at once.
+//!
+//! The text form has one operation per line:
+//!
+//! ```text
+//! add rect cache [label: "Cache"] under backend
+//! connect api -> cache [label: "reads"]
+//! set api fill: #ff0000
+//! remove legacy
+//! ```
+//!
+//! Statements added by a patch carry spans into their own snippet, not into
+//! the original source.
+
+use thiserror::Error;
+
+use crate::parse;
+use crate::parser::ast::{
+    AnchorReference, ConnectionDecl, Document, Spanned, Statement, StyleModifier,
+};
+
+/// One edit operation
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchOp {
+    /// Append the statement `source` (e.g. `rect cache [label: "Cache"]`) to the
+    /// container named `parent`, or to the top level
+    Add {
+        parent: Option<String>,
+        source: String,
+    },
+    /// Add a connection statement, e.g. `api -> cache [label: "reads"]`
+    Connect { connection: String },
+    /// Set or replace one modifier; `value` is in DSL syntax (`#ff0000`, `"Cache"`, `2`)
+    Set {
+        element: String,
+        key: String,
+        value: String,
+    },
+    /// Remove an element and every connection touching it
+    Remove { element: String },
+}
+
+/// A patch operation that could not be applied
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("patch line {line}: {message}")]
+pub struct PatchError {
+    /// 1-based index of the operation (the line in the text form)
+    pub line: usize,
+    pub message: String,
+}
+
+impl PatchOp {
+    /// Parse the line-based text form (blank lines and `//` comments are skipped)
+    pub fn parse_all(text: &str) -> Result<Vec<(usize, PatchOp)>, Vec<PatchError>> {
+        let mut ops = vec![];
+        let mut errors = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            match Self::parse_line(line) {
+                Ok(op) => ops.push((line_no, op)),
+                Err(message) => errors.push(PatchError {
+                    line: line_no,
+                    message,
+                }),
+            }
+        }
+        if errors.is_empty() {
+            Ok(ops)
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn parse_line(line: &str) -> Result<PatchOp, String> {
+        let (verb, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        match verb {
+            "add" => {
+                let (source, parent) = match rest.rsplit_once(" under ") {
+                    Some((source, parent)) if is_identifier(parent.trim()) => {
+                        (source.trim(), Some(parent.trim().to_string()))
+                    }
+                    _ => (rest, None),
+                };
+                Ok(PatchOp::Add {
+                    parent,
+                    source: source.to_string(),
+                })
+            }
+            "connect" => Ok(PatchOp::Connect {
+                connection: rest.to_string(),
+            }),
+            "set" => {
+                let (element, assignment) = rest
+                    .split_once(char::is_whitespace)
+                    .ok_or("expected `set ELEMENT key: value`")?;
+                let (key, value) = assignment
+                    .split_once(':')
+                    .ok_or("expected `set ELEMENT key: value`")?;
+                Ok(PatchOp::Set {
+                    element: element.to_string(),
+                    key: key.trim().to_string(),
+                    value: value.trim().to_string(),
+                })
+            }
+            "remove" if is_identifier(rest) => Ok(PatchOp::Remove {
+                element: rest.to_string(),
+            }),
+            "remove" => Err("expected `remove ELEMENT`".to_string()),
+            other => Err(format!(
+                "unknown operation '{}'. Expected add, connect, set or remove",
+                other
+            )),
+        }
+    }
+}
+
+/// Apply a list of operations (as returned by [`PatchOp::parse_all`]) to a document
+///
+/// Operations run in order, so later ones can refer to elements added by
+/// earlier ones. The document is returned only if every operation applies.
+///
+/// ```rust
+/// use agent_illustrator::{apply_patch, parse, PatchOp};
+///
+/// let doc = parse("group backend { rect api }").unwrap();
+/// let ops = PatchOp::parse_all("add rect cache under backend\nconnect api -> cache").unwrap();
+/// let doc = apply_patch(&doc, &ops).unwrap();
+/// assert!(doc.outline().contains("\n  rect cache"));
+/// ```
+pub fn apply_patch(doc: &Document, ops: &[(usize, PatchOp)]) -> Result<Document, Vec<PatchError>> {
+    let mut doc = doc.clone();
+    let mut errors = vec![];
+    for (line, op) in ops {
+        if let Err(message) = apply_op(&mut doc, op) {
+            errors.push(PatchError {
+                line: *line,
+                message,
+            });
+        }
+    }
+    if errors.is_empty() {
+        Ok(doc)
+    } else {
+        Err(errors)
+    }
+}
+
+fn apply_op(doc: &mut Document, op: &PatchOp) -> Result<(), String> {
+    match op {
+        PatchOp::Add { parent, source } => {
+            let stmt = parse_single(source)?;
+            let Some(name) = declared_name(&stmt.node) else {
+                return Err(format!("'{}' does not declare a named element", source));
+            };
+            if contains_element(&doc.statements, &name) {
+                return Err(format!("element '{}' already exists", name));
+            }
+            let children = match parent {
+                None => &mut doc.statements,
+                Some(parent) => container_children(&mut doc.statements, parent)
+                    .ok_or_else(|| format!("no group or layout named '{}'", parent))?,
+            };
+            children.push(stmt);
+            Ok(())
+        }
+        PatchOp::Connect { connection } => {
+            let stmt = parse_single(connection)?;
+            let Statement::Connection(conns) = &stmt.node else {
+                return Err(format!("'{}' is not a connection", connection));
+            };
+            for conn in conns {
+                for end in [&conn.from, &conn.to] {
+                    let name = end.element.node.as_str();
+                    if !contains_element(&doc.statements, name) {
+                        return Err(format!("unknown element '{}'", name));
+                    }
+                }
+                if has_connection(&doc.statements, conn) {
+                    return Err(format!(
+                        "{} and {} are already connected",
+                        conn.from.element.node, conn.to.element.node
+                    ));
+                }
+            }
+            doc.statements.push(stmt);
+            Ok(())
+        }
+        PatchOp::Set {
+            element,
+            key,
+            value,
+        } => {
+            let source = format!("rect patch_value [{}: {}]", key, value);
+            let modifier = match parse(&source).map(|d| d.statements.into_iter().next()) {
+                Ok(Some(Spanned {
+                    node: Statement::Shape(mut shape),
+                    ..
+                })) if shape.modifiers.len() == 1 => shape.modifiers.remove(0),
+                _ => return Err(format!("invalid modifier '{}: {}'", key, value)),
+            };
+            let modifiers = element_modifiers(&mut doc.statements, element)
+                .ok_or_else(|| format!("unknown element '{}'", element))?;
+            set_modifier(modifiers, modifier);
+            Ok(())
+        }
+        PatchOp::Remove { element } => {
+            if !remove_element(&mut doc.statements, element) {
+                return Err(format!("unknown element '{}'", element));
+            }
+            remove_connections(&mut doc.statements, element);
+            Ok(())
+        }
+    }
+}
+
+fn parse_single(source: &str) -> Result<Spanned<Statement>, String> {
+    let doc = parse(source).map_err(|errors| {
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        format!("cannot parse '{}': {}", source, messages.join("; "))
+    })?;
+    let mut statements = doc.statements;
+    if statements.len() != 1 {
+        return Err(format!("expected one statement in '{}'", source));
+    }
+    Ok(statements.remove(0))
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn declared_name(stmt: &Statement) -> Option<String> {
+    let name = match stmt {
+        Statement::Shape(s) => s.name.as_ref()?,
+        Statement::Layout(l) => l.name.as_ref()?,
+        Statement::Group(g) => g.name.as_ref()?,
+        Statement::TemplateInstance(t) => &t.instance_name,
+        Statement::Embed(e) => &e.name,
+        Statement::Label(inner) => return declared_name(inner),
+        _ => return None,
+    };
+    Some(name.node.0.clone())
+}
+
+fn contains_element(stmts: &[Spanned<Statement>], name: &str) -> bool {
+    stmts.iter().any(|stmt| {
+        declared_name(&stmt.node).as_deref() == Some(name)
+            || match &stmt.node {
+                Statement::Layout(l) => contains_element(&l.children, name),
+                Statement::Group(g) => contains_element(&g.children, name),
+                Statement::Note(n) => contains_element(&n.children, name),
+                _ => false,
+            }
+    })
+}
+
+fn container_children<'a>(
+    stmts: &'a mut [Spanned<Statement>],
+    name: &str,
+) -> Option<&'a mut Vec<Spanned<Statement>>> {
+    for stmt in stmts {
+        let is_match = declared_name(&stmt.node).as_deref() == Some(name);
+        let children = match &mut stmt.node {
+            Statement::Layout(l) => &mut l.children,
+            Statement::Group(g) => &mut g.children,
+            _ => continue,
+        };
+        if is_match {
+            return Some(children);
+        }
+        if let Some(found) = container_children(children, name) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+fn element_modifiers<'a>(
+    stmts: &'a mut [Spanned<Statement>],
+    name: &str,
+) -> Option<&'a mut Vec<Spanned<StyleModifier>>> {
+    for stmt in stmts {
+        let is_match = declared_name(&stmt.node).as_deref() == Some(name);
+        match &mut stmt.node {
+            Statement::Shape(s) if is_match => return Some(&mut s.modifiers),
+            Statement::Embed(e) if is_match => return Some(&mut e.modifiers),
+            Statement::Layout(l) => {
+                if is_match {
+                    return Some(&mut l.modifiers);
+                }
+                if let Some(found) = element_modifiers(&mut l.children, name) {
+                    return Some(found);
+                }
+            }
+            Statement::Group(g) => {
+                if is_match {
+                    return Some(&mut g.modifiers);
+                }
+                if let Some(found) = element_modifiers(&mut g.children, name) {
+                    return Some(found);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn set_modifier(modifiers: &mut Vec<Spanned<StyleModifier>>, modifier: Spanned<StyleModifier>) {
+    match modifiers
+        .iter_mut()
+        .find(|m| m.node.key.node == modifier.node.key.node)
+    {
+        Some(existing) => *existing = modifier,
+        None => modifiers.push(modifier),
+    }
+}
+
+fn has_connection(stmts: &[Spanned<Statement>], conn: &ConnectionDecl) -> bool {
+    stmts.iter().any(|stmt| match &stmt.node {
+        Statement::Connection(existing) => existing.iter().any(|c| {
+            same_endpoint(&c.from, &conn.from)
+                && same_endpoint(&c.to, &conn.to)
+                && c.direction == conn.direction
+        }),
+        Statement::Layout(l) => has_connection(&l.children, conn),
+        Statement::Group(g) => has_connection(&g.children, conn),
+        _ => false,
+    })
+}
+
+/// Endpoint equality ignoring spans
+fn same_endpoint(a: &AnchorReference, b: &AnchorReference) -> bool {
+    a.element.node == b.element.node
+        && a.anchor.as_ref().map(|s| &s.node) == b.anchor.as_ref().map(|s| &s.node)
+}
+
+fn remove_element(stmts: &mut Vec<Spanned<Statement>>, name: &str) -> bool {
+    let before = stmts.len();
+    stmts.retain(|stmt| declared_name(&stmt.node).as_deref() != Some(name));
+    if stmts.len() != before {
+        return true;
+    }
+    stmts.iter_mut().any(|stmt| match &mut stmt.node {
+        Statement::Layout(l) => remove_element(&mut l.children, name),
+        Statement::Group(g) => remove_element(&mut g.children, name),
+        Statement::Note(n) => remove_element(&mut n.children, name),
+        _ => false,
+    })
+}
+
+fn remove_connections(stmts: &mut Vec<Spanned<Statement>>, name: &str) {
+    for stmt in stmts.iter_mut() {
+        match &mut stmt.node {
+            Statement::Connection(conns) => conns.retain(|c| {
+                c.from.element.node.as_str() != name && c.to.element.node.as_str() != name
+            }),
+            Statement::Layout(l) => remove_connections(&mut l.children, name),
+            Statement::Group(g) => remove_connections(&mut g.children, name),
+            _ => {}
+        }
+    }
+    stmts.retain(|stmt| !matches!(&stmt.node, Statement::Connection(c) if c.is_empty()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(source: &str, text: &str) -> Result<Document, Vec<PatchError>> {
+        let doc = parse(source).unwrap();
+        apply_patch(&doc, &PatchOp::parse_all(text)?)
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let doc = patch(
+            r#"group backend { rect api [fill: blue] rect legacy }
+            rect client
+            client -> legacy"#,
+            r#"add rect cache [label: "Cache"] under backend
+            connect api -> cache [label: "reads"]
+            set api fill: #ff0000
+            set backend label: "Backend"
+            remove legacy"#,
+        )
+        .unwrap();
+        assert_eq!(
+            doc.outline(),
+            r#"group backend "Backend"
+  rect api
+  rect cache "Cache"
+rect client
+connections:
+  api -> cache "reads"
+"#
+        );
+        assert_eq!(
+            doc.statements.len(),
+            3,
+            "the emptied connection statement is dropped"
+        );
+    }
+
+    #[test]
+    fn test_patch_conflicts() {
+        let errors = patch(
+            "rect a\nrect b\na -> b",
+            "add rect a\nconnect a -> b\nconnect a -> nowhere\nset ghost fill: red\nadd rect c under b\nremove ghost",
+        )
+        .unwrap_err();
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "patch line 1: element 'a' already exists",
+                "patch line 2: a and b are already connected",
+                "patch line 3: unknown element 'nowhere'",
+                "patch line 4: unknown element 'ghost'",
+                "patch line 5: no group or layout named 'b'",
+                "patch line 6: unknown element 'ghost'",
+            ]
+        );
+
+        let errors = PatchOp::parse_all("rename a b").unwrap_err();
+        assert!(errors[0].message.contains("unknown operation 'rename'"));
+    }
+}
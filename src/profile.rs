@@ -0,0 +1,75 @@
+//! Per-phase timings for `--profile`
+
+use std::time::{Duration, Instant};
+
+/// Wall-clock time spent in each pipeline phase
+///
+/// A disabled timer never reads the clock, so the pipeline also runs where
+/// `Instant` is unavailable (wasm32).
+pub(crate) struct PhaseTimer {
+    last: Option<Instant>,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl PhaseTimer {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Self {
+            last: enabled.then(Instant::now),
+            phases: Vec::new(),
+        }
+    }
+
+    /// Record the time since the previous lap as `phase`
+    ///
+    /// Laps with the same name add up, so a phase split by others is
+    /// reported once.
+    pub(crate) fn lap(&mut self, phase: &'static str) {
+        let Some(last) = self.last else {
+            return;
+        };
+        let now = Instant::now();
+        let elapsed = now - last;
+        self.last = Some(now);
+        match self.phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += elapsed,
+            None => self.phases.push((phase, elapsed)),
+        }
+    }
+
+    /// The timings as a table, or nothing when disabled
+    pub(crate) fn report(&self) -> Option<String> {
+        self.last?;
+        let total: Duration = self.phases.iter().map(|(_, d)| *d).sum();
+        let mut out = String::from("=== Profile ===\n");
+        for (name, duration) in self.phases.iter().chain([&("total", total)]) {
+            out.push_str(&format!(
+                "{:<12} {:>9.3} ms\n",
+                name,
+                duration.as_secs_f64() * 1000.0
+            ));
+        }
+        out.push_str("===============");
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_timer() {
+        let mut timer = PhaseTimer::new(true);
+        timer.lap("parse");
+        timer.lap("layout");
+        timer.lap("parse");
+        let report = timer.report().unwrap();
+        assert_eq!(report.matches("parse").count(), 1);
+        assert!(report.contains("layout"));
+        assert!(report.contains("total"));
+
+        let mut timer = PhaseTimer::new(false);
+        timer.lap("parse");
+        assert!(timer.report().is_none());
+    }
+}
@@ -1,11 +1,77 @@
 //! Configuration for SVG rendering
 
+use crate::layout::{CrossingStyle, Padding};
+use crate::parser::ast::Unit;
+
+use super::custom::ShapeRenderers;
+
+/// Order in which keyboard and assistive technology navigation walks the
+/// elements; paint order always follows the document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TraversalOrder {
+    /// Source order of the AIL document
+    #[default]
+    Document,
+    /// Top-to-bottom, then left-to-right by position, numbered with
+    /// `tabindex` on named elements
+    Reading,
+}
+
+/// How element paint (fill, stroke, opacity, font size) is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StyleMode {
+    /// Presentation attributes on every element (`fill="#f0f0f0"`)
+    #[default]
+    Inline,
+    /// Shared generated classes defined in the `<style>` block, so the SVG
+    /// can be restyled afterwards by overriding CSS rules
+    Classes,
+}
+
+/// How output of a fixed width and height is filled when its aspect ratio
+/// differs from the diagram's
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fit {
+    /// Show the whole diagram, centered with empty margins
+    #[default]
+    Contain,
+    /// Fill the whole output, cropping the diagram's edges
+    Cover,
+}
+
+impl Fit {
+    /// Parse `contain` or `cover`
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "contain" => Some(Fit::Contain),
+            "cover" => Some(Fit::Cover),
+            _ => None,
+        }
+    }
+
+    /// Value of the root `preserveAspectRatio` attribute
+    pub fn preserve_aspect_ratio(self) -> &'static str {
+        match self {
+            Fit::Contain => "xMidYMid meet",
+            Fit::Cover => "xMidYMid slice",
+        }
+    }
+}
+
 /// Configuration options for SVG output
 #[derive(Debug, Clone)]
 pub struct SvgConfig {
     /// Padding around the viewBox
     pub viewbox_padding: f64,
 
+    /// Per-side space between the diagram and the viewBox edge; replaces
+    /// `viewbox_padding` when set
+    pub margin: Option<Padding>,
+
+    /// Fill painted over the whole viewBox behind the diagram (transparent
+    /// when unset)
+    pub background: Option<String>,
+
     /// Whether to include XML declaration and standalone attributes
     pub standalone: bool,
 
@@ -14,15 +80,105 @@ pub struct SvgConfig {
 
     /// Prefix for CSS class names (e.g., "ai-" for "ai-shape")
     pub class_prefix: Option<String>,
+
+    /// Default crossing style for connections without a `crossings` modifier
+    pub crossings: CrossingStyle,
+
+    /// Radius of the hop arc drawn at jumped crossings
+    pub jump_radius: f64,
+
+    /// Rendered width (px) below which `detail: high` elements are hidden
+    pub detail_breakpoint: Option<f64>,
+
+    /// Mark containers as `role="group"` with an `aria-label` for screen readers
+    pub semantic_groups: bool,
+
+    /// Use element labels as `<title>` tooltips when no `tooltip` is given
+    pub label_tooltips: bool,
+
+    /// Order in which elements are navigated
+    pub traversal_order: TraversalOrder,
+
+    /// Arrowhead size per unit of connection stroke width
+    pub arrow_scale: f64,
+
+    /// Smallest arrowhead size (px), so thin lines keep a visible arrow
+    pub arrow_min_size: f64,
+
+    /// Largest arrowhead size (px), so thick lines don't end in huge arrows
+    pub arrow_max_size: f64,
+
+    /// Inline presentation attributes or generated CSS classes
+    pub style_mode: StyleMode,
+
+    /// Tag elements and connections with `data-ai-span="start..end"`, the
+    /// byte range of the statement that produced them
+    pub source_spans: bool,
+
+    /// Tag elements with `data-ai-id`, `data-ai-x`, `data-ai-y`,
+    /// `data-ai-width` and `data-ai-height`, their name and layout bounds
+    pub geometry_attrs: bool,
+
+    /// Reference palette colors as `var(--{prefix}token, value)` custom
+    /// properties, declared with zero specificity so a host page can re-theme
+    /// the SVG by setting them
+    pub css_variables: bool,
+
+    /// Shrink the output: round coordinates to two decimals, merge identical
+    /// defs and drop empty groups
+    pub optimize: bool,
+
+    /// Renderers of application-defined shapes, by shape name
+    pub shape_renderers: ShapeRenderers,
+
+    /// Output width (px) of the root element; with only one of width and
+    /// height set, the other follows the diagram's aspect ratio
+    pub width: Option<f64>,
+
+    /// Output height (px) of the root element
+    pub height: Option<f64>,
+
+    /// Output size as a multiple of the diagram size, used when neither
+    /// width nor height is set
+    pub scale: Option<f64>,
+
+    /// How a fixed width and height are filled (`contain` when unset)
+    pub fit: Option<Fit>,
+
+    /// Unit of the root width and height, for print; without a size set,
+    /// one layout pixel maps to 1/96 in, so `20mm` prints as 20 mm
+    pub unit: Option<Unit>,
 }
 
 impl Default for SvgConfig {
     fn default() -> Self {
         Self {
             viewbox_padding: 60.0,
+            margin: None,
+            background: None,
             standalone: true,
             pretty_print: true,
             class_prefix: Some("ai-".to_string()),
+            crossings: CrossingStyle::Plain,
+            jump_radius: 5.0,
+            detail_breakpoint: None,
+            semantic_groups: false,
+            label_tooltips: false,
+            traversal_order: TraversalOrder::Document,
+            arrow_scale: 4.0,
+            arrow_min_size: 6.0,
+            arrow_max_size: 24.0,
+            style_mode: StyleMode::Inline,
+            source_spans: false,
+            geometry_attrs: false,
+            css_variables: false,
+            optimize: false,
+            shape_renderers: ShapeRenderers::new(),
+            width: None,
+            height: None,
+            scale: None,
+            fit: None,
+            unit: None,
         }
     }
 }
@@ -39,6 +195,28 @@ impl SvgConfig {
         self
     }
 
+    /// Set the space around the diagram per side
+    pub fn with_margin(mut self, margin: Padding) -> Self {
+        self.margin = Some(margin);
+        self
+    }
+
+    /// Paint a background behind the diagram
+    pub fn with_background(mut self, color: impl Into<String>) -> Self {
+        self.background = Some(color.into());
+        self
+    }
+
+    /// Space between the diagram and each viewBox edge
+    pub fn margins(&self) -> Padding {
+        self.margin.unwrap_or(Padding {
+            top: self.viewbox_padding,
+            right: self.viewbox_padding,
+            bottom: self.viewbox_padding,
+            left: self.viewbox_padding,
+        })
+    }
+
     /// Set whether output is standalone
     pub fn with_standalone(mut self, standalone: bool) -> Self {
         self.standalone = standalone;
@@ -62,6 +240,137 @@ impl SvgConfig {
         self.class_prefix = None;
         self
     }
+
+    /// Set the default crossing style for connections
+    pub fn with_crossings(mut self, crossings: CrossingStyle) -> Self {
+        self.crossings = crossings;
+        self
+    }
+
+    /// Set the radius of hop arcs at jumped crossings
+    pub fn with_jump_radius(mut self, radius: f64) -> Self {
+        self.jump_radius = radius;
+        self
+    }
+
+    /// Hide `detail: high` elements when the SVG is rendered narrower than `width` px
+    pub fn with_detail_breakpoint(mut self, width: f64) -> Self {
+        self.detail_breakpoint = Some(width);
+        self
+    }
+
+    /// Set whether containers are exported as labelled ARIA groups
+    pub fn with_semantic_groups(mut self, enabled: bool) -> Self {
+        self.semantic_groups = enabled;
+        self
+    }
+
+    /// Set whether labels double as `<title>` tooltips
+    pub fn with_label_tooltips(mut self, enabled: bool) -> Self {
+        self.label_tooltips = enabled;
+        self
+    }
+
+    /// Set the order in which elements are navigated
+    pub fn with_traversal_order(mut self, order: TraversalOrder) -> Self {
+        self.traversal_order = order;
+        self
+    }
+
+    /// Set the arrowhead size per unit of stroke width
+    pub fn with_arrow_scale(mut self, scale: f64) -> Self {
+        self.arrow_scale = scale;
+        self
+    }
+
+    /// Set whether paint is written inline or as generated classes
+    pub fn with_style_mode(mut self, mode: StyleMode) -> Self {
+        self.style_mode = mode;
+        self
+    }
+
+    /// Set whether elements carry a `data-ai-span` source range
+    pub fn with_source_spans(mut self, enabled: bool) -> Self {
+        self.source_spans = enabled;
+        self
+    }
+
+    /// Set whether elements carry their name and bounds as `data-ai-*` attributes
+    pub fn with_geometry_attrs(mut self, enabled: bool) -> Self {
+        self.geometry_attrs = enabled;
+        self
+    }
+
+    /// Set whether palette colors are themable `--{prefix}token` custom properties
+    pub fn with_css_variables(mut self, enabled: bool) -> Self {
+        self.css_variables = enabled;
+        self
+    }
+
+    /// Set whether the output is size-optimized
+    pub fn with_optimize(mut self, enabled: bool) -> Self {
+        self.optimize = enabled;
+        self
+    }
+
+    /// Render at exactly `width` x `height` px
+    pub fn with_size(mut self, width: f64, height: f64) -> Self {
+        self.width = Some(width);
+        self.height = Some(height);
+        self
+    }
+
+    /// Render `width` px wide, keeping the diagram's aspect ratio
+    pub fn with_width(mut self, width: f64) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Render `height` px high, keeping the diagram's aspect ratio
+    pub fn with_height(mut self, height: f64) -> Self {
+        self.height = Some(height);
+        self
+    }
+
+    /// Render at `scale` times the diagram size
+    pub fn with_scale(mut self, scale: f64) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    /// Set how a fixed width and height are filled
+    pub fn with_fit(mut self, fit: Fit) -> Self {
+        self.fit = Some(fit);
+        self
+    }
+
+    /// Write the root width and height in `unit` (e.g. millimetres for print)
+    pub fn with_unit(mut self, unit: Unit) -> Self {
+        self.unit = Some(unit);
+        self
+    }
+
+    /// Output size in px for a viewBox of `view_width` x `view_height`, or
+    /// nothing when the SVG should take the viewBox size
+    pub fn output_size(&self, view_width: f64, view_height: f64) -> Option<(f64, f64)> {
+        match (self.width, self.height) {
+            (Some(w), Some(h)) => Some((w, h)),
+            (Some(w), None) => Some((w, view_height * w / view_width)),
+            (None, Some(h)) => Some((view_width * h / view_height, h)),
+            (None, None) => match (self.scale, self.unit) {
+                (Some(s), _) => Some((view_width * s, view_height * s)),
+                (None, Some(_)) => Some((view_width, view_height)),
+                (None, None) => None,
+            },
+        }
+    }
+
+    /// Clamp arrowhead sizes to `min..=max` px
+    pub fn with_arrow_size_limits(mut self, min: f64, max: f64) -> Self {
+        self.arrow_min_size = min;
+        self.arrow_max_size = max;
+        self
+    }
 }
 
 #[cfg(test)]
@@ -75,6 +384,8 @@ mod tests {
         assert!(config.standalone);
         assert!(config.pretty_print);
         assert_eq!(config.class_prefix, Some("ai-".to_string()));
+        assert_eq!(config.crossings, CrossingStyle::Plain);
+        assert_eq!(config.style_mode, StyleMode::Inline);
     }
 
     #[test]
@@ -90,4 +401,93 @@ mod tests {
         assert!(!config.pretty_print);
         assert_eq!(config.class_prefix, Some("my-".to_string()));
     }
+
+    #[test]
+    fn test_margins() {
+        let config = SvgConfig::new().with_viewbox_padding(10.0);
+        assert_eq!(config.margins().left, 10.0);
+
+        let margin = Padding {
+            top: 1.0,
+            right: 2.0,
+            bottom: 3.0,
+            left: 4.0,
+        };
+        assert_eq!(config.with_margin(margin).margins(), margin);
+    }
+
+    #[test]
+    fn test_crossings_builder() {
+        let config = SvgConfig::new()
+            .with_crossings(CrossingStyle::Jump)
+            .with_jump_radius(8.0);
+
+        assert_eq!(config.crossings, CrossingStyle::Jump);
+        assert_eq!(config.jump_radius, 8.0);
+    }
+
+    #[test]
+    fn test_detail_breakpoint_builder() {
+        assert_eq!(SvgConfig::default().detail_breakpoint, None);
+        let config = SvgConfig::new().with_detail_breakpoint(400.0);
+        assert_eq!(config.detail_breakpoint, Some(400.0));
+    }
+
+    #[test]
+    fn test_arrow_scale_builder() {
+        let config = SvgConfig::new()
+            .with_arrow_scale(3.0)
+            .with_arrow_size_limits(4.0, 12.0);
+        assert_eq!(config.arrow_scale, 3.0);
+        assert_eq!(config.arrow_min_size, 4.0);
+        assert_eq!(config.arrow_max_size, 12.0);
+    }
+
+    #[test]
+    fn test_semantic_groups_builder() {
+        let config = SvgConfig::default();
+        assert!(!config.semantic_groups);
+        assert_eq!(config.traversal_order, TraversalOrder::Document);
+
+        assert!(!config.label_tooltips);
+
+        let config = SvgConfig::new()
+            .with_semantic_groups(true)
+            .with_label_tooltips(true)
+            .with_traversal_order(TraversalOrder::Reading);
+        assert!(config.semantic_groups);
+        assert!(config.label_tooltips);
+        assert_eq!(config.traversal_order, TraversalOrder::Reading);
+    }
+
+    #[test]
+    fn test_output_size() {
+        assert_eq!(SvgConfig::default().output_size(400.0, 200.0), None);
+
+        let config = SvgConfig::new().with_size(800.0, 600.0);
+        assert_eq!(config.output_size(400.0, 200.0), Some((800.0, 600.0)));
+
+        let config = SvgConfig::new().with_width(800.0);
+        assert_eq!(config.output_size(400.0, 200.0), Some((800.0, 400.0)));
+        let config = SvgConfig::new().with_height(100.0);
+        assert_eq!(config.output_size(400.0, 200.0), Some((200.0, 100.0)));
+
+        // An explicit size wins over scale
+        let config = SvgConfig::new().with_scale(2.0);
+        assert_eq!(config.output_size(400.0, 200.0), Some((800.0, 400.0)));
+        let config = config.with_width(100.0);
+        assert_eq!(config.output_size(400.0, 200.0), Some((100.0, 50.0)));
+
+        // A physical unit alone gives the natural size
+        let config = SvgConfig::new().with_unit(Unit::Mm);
+        assert_eq!(config.output_size(400.0, 200.0), Some((400.0, 200.0)));
+    }
+
+    #[test]
+    fn test_fit() {
+        assert_eq!(Fit::parse("cover"), Some(Fit::Cover));
+        assert_eq!(Fit::parse("stretch"), None);
+        assert_eq!(Fit::default().preserve_aspect_ratio(), "xMidYMid meet");
+        assert_eq!(Fit::Cover.preserve_aspect_ratio(), "xMidYMid slice");
+    }
 }
@@ -0,0 +1,233 @@
+//! Crossing detection and "jump over" path generation for connections
+//!
+//! When two orthogonal connections cross, the one drawn later can hop over
+//! the earlier one with a small semicircular arc. This keeps dense wiring
+//! diagrams readable because a crossing is visually distinct from a junction.
+
+use crate::layout::{ConnectionLayout, CrossingStyle, Point, RoutingMode};
+
+const EPSILON: f64 = 0.001;
+
+/// A point where a connection segment crosses an earlier connection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crossing {
+    /// Index of the segment (from `path[segment]` to `path[segment + 1]`)
+    pub segment: usize,
+    /// Intersection point on that segment
+    pub point: Point,
+}
+
+/// Find the jump points for every connection.
+///
+/// Returns one list per connection (same order as `connections`). A connection
+/// only receives jumps when its effective crossing style is `Jump` and it is not
+/// curved. Each crossing is attributed to the later connection in document order,
+/// so it hops over connections that are already drawn underneath it.
+pub fn find_crossings(
+    connections: &[ConnectionLayout],
+    default_style: CrossingStyle,
+    radius: f64,
+) -> Vec<Vec<Crossing>> {
+    connections
+        .iter()
+        .enumerate()
+        .map(|(i, conn)| {
+            let style = conn.crossings.unwrap_or(default_style);
+            if style != CrossingStyle::Jump || conn.routing_mode == RoutingMode::Curved {
+                return vec![];
+            }
+            let mut found = vec![];
+            for (seg_idx, seg) in conn.path.windows(2).enumerate() {
+                for other in &connections[..i] {
+                    if other.routing_mode == RoutingMode::Curved {
+                        continue;
+                    }
+                    for other_seg in other.path.windows(2) {
+                        if let Some(point) =
+                            perpendicular_crossing(seg[0], seg[1], other_seg[0], other_seg[1])
+                        {
+                            // Skip crossings too close to a bend or endpoint to fit an arc
+                            if distance(point, seg[0]) > 2.0 * radius
+                                && distance(point, seg[1]) > 2.0 * radius
+                            {
+                                found.push(Crossing {
+                                    segment: seg_idx,
+                                    point,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+            found
+        })
+        .collect()
+}
+
+/// Intersection of an axis-aligned segment with a perpendicular axis-aligned segment.
+///
+/// Only proper crossings count: touching at an endpoint, overlapping collinear
+/// segments, and diagonal segments are ignored.
+fn perpendicular_crossing(a1: Point, a2: Point, b1: Point, b2: Point) -> Option<Point> {
+    let a_horizontal = (a1.y - a2.y).abs() < EPSILON && (a1.x - a2.x).abs() > EPSILON;
+    let a_vertical = (a1.x - a2.x).abs() < EPSILON && (a1.y - a2.y).abs() > EPSILON;
+    let b_horizontal = (b1.y - b2.y).abs() < EPSILON && (b1.x - b2.x).abs() > EPSILON;
+    let b_vertical = (b1.x - b2.x).abs() < EPSILON && (b1.y - b2.y).abs() > EPSILON;
+
+    let (h1, h2, v1, v2) = if a_horizontal && b_vertical {
+        (a1, a2, b1, b2)
+    } else if a_vertical && b_horizontal {
+        (b1, b2, a1, a2)
+    } else {
+        return None;
+    };
+
+    let x = v1.x;
+    let y = h1.y;
+    let strictly_between =
+        |v: f64, e1: f64, e2: f64| v > e1.min(e2) + EPSILON && v < e1.max(e2) - EPSILON;
+
+    if strictly_between(x, h1.x, h2.x) && strictly_between(y, v1.y, v2.y) {
+        Some(Point::new(x, y))
+    } else {
+        None
+    }
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2)).sqrt()
+}
+
+/// Build an SVG path `d` string for a polyline, inserting hop arcs at crossings.
+///
+/// Hops bulge upward on horizontal segments and to the right on vertical ones,
+/// regardless of travel direction, so parallel wires jump consistently.
+pub fn path_to_d_with_jumps(path: &[Point], crossings: &[Crossing], radius: f64) -> String {
+    if path.is_empty() {
+        return String::new();
+    }
+
+    let mut d = format!("M{} {}", path[0].x, path[0].y);
+    for (seg_idx, seg) in path.windows(2).enumerate() {
+        let (start, end) = (seg[0], seg[1]);
+        let len = distance(start, end);
+
+        let mut points: Vec<Point> = crossings
+            .iter()
+            .filter(|c| c.segment == seg_idx)
+            .map(|c| c.point)
+            .collect();
+
+        if len > EPSILON && !points.is_empty() {
+            let (ux, uy) = ((end.x - start.x) / len, (end.y - start.y) / len);
+            points.sort_by(|a, b| distance(start, *a).total_cmp(&distance(start, *b)));
+
+            // Clockwise (sweep=1) bulges up when heading right and right when heading down
+            let sweep = if ux > 0.5 || uy > 0.5 { 1 } else { 0 };
+
+            let mut last_exit = 0.0;
+            for p in points {
+                let along = distance(start, p);
+                // Merge hops that would overlap the previous one
+                if along - radius < last_exit {
+                    continue;
+                }
+                let (ex, ey) = (p.x - ux * radius, p.y - uy * radius);
+                let (lx, ly) = (p.x + ux * radius, p.y + uy * radius);
+                d.push_str(&format!(
+                    " L{} {} A{} {} 0 0 {} {} {}",
+                    ex, ey, radius, radius, sweep, lx, ly
+                ));
+                last_exit = along + radius;
+            }
+        }
+
+        d.push_str(&format!(" L{} {}", end.x, end.y));
+    }
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::ResolvedStyles;
+    use crate::parser::ast::{ConnectionDirection, Identifier};
+
+    fn conn(path: Vec<Point>, crossings: Option<CrossingStyle>) -> ConnectionLayout {
+        ConnectionLayout {
+            from_id: Identifier::new("a"),
+            to_id: Identifier::new("b"),
+            direction: ConnectionDirection::Forward,
+            path,
+            styles: ResolvedStyles::default(),
+            label: None,
+            routing_mode: RoutingMode::Orthogonal,
+            crossings,
+            name: None,
+            seq: None,
+            span: None,
+        }
+    }
+
+    #[test]
+    fn test_perpendicular_crossing() {
+        let p = perpendicular_crossing(
+            Point::new(0.0, 50.0),
+            Point::new(100.0, 50.0),
+            Point::new(40.0, 0.0),
+            Point::new(40.0, 100.0),
+        );
+        assert_eq!(p, Some(Point::new(40.0, 50.0)));
+    }
+
+    #[test]
+    fn test_touching_endpoints_are_not_crossings() {
+        let p = perpendicular_crossing(
+            Point::new(0.0, 50.0),
+            Point::new(100.0, 50.0),
+            Point::new(100.0, 0.0),
+            Point::new(100.0, 100.0),
+        );
+        assert_eq!(p, None);
+    }
+
+    #[test]
+    fn test_later_connection_jumps_earlier() {
+        let vertical = conn(vec![Point::new(50.0, 0.0), Point::new(50.0, 100.0)], None);
+        let horizontal = conn(vec![Point::new(0.0, 50.0), Point::new(100.0, 50.0)], None);
+        let crossings = find_crossings(&[vertical, horizontal], CrossingStyle::Jump, 5.0);
+        assert!(crossings[0].is_empty());
+        assert_eq!(crossings[1].len(), 1);
+        assert_eq!(crossings[1][0].point, Point::new(50.0, 50.0));
+    }
+
+    #[test]
+    fn test_per_connection_style_overrides_default() {
+        let vertical = conn(vec![Point::new(50.0, 0.0), Point::new(50.0, 100.0)], None);
+        let horizontal = conn(
+            vec![Point::new(0.0, 50.0), Point::new(100.0, 50.0)],
+            Some(CrossingStyle::Jump),
+        );
+        let crossings = find_crossings(&[vertical, horizontal], CrossingStyle::Plain, 5.0);
+        assert_eq!(crossings[1].len(), 1);
+
+        let plain = conn(
+            vec![Point::new(0.0, 50.0), Point::new(100.0, 50.0)],
+            Some(CrossingStyle::Plain),
+        );
+        let vertical = conn(vec![Point::new(50.0, 0.0), Point::new(50.0, 100.0)], None);
+        let crossings = find_crossings(&[vertical, plain], CrossingStyle::Jump, 5.0);
+        assert!(crossings[1].is_empty());
+    }
+
+    #[test]
+    fn test_path_to_d_with_jumps() {
+        let path = vec![Point::new(0.0, 50.0), Point::new(100.0, 50.0)];
+        let crossings = vec![Crossing {
+            segment: 0,
+            point: Point::new(40.0, 50.0),
+        }];
+        let d = path_to_d_with_jumps(&path, &crossings, 5.0);
+        assert_eq!(d, "M0 50 L35 50 A5 5 0 0 1 45 50 L100 50");
+    }
+}
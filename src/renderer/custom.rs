@@ -0,0 +1,25 @@
+//! Application-defined shapes
+//!
+//! A [`ShapeRenderer`] registered under a name with
+//! [`RenderConfig::register_shape`](crate::RenderConfig::register_shape)
+//! makes `name instance [modifiers]` a shape of its own: it is sized by the
+//! renderer when no width or height is given, laid out and connected like any
+//! other shape, and drawn by the renderer's SVG.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::layout::{BoundingBox, ResolvedStyles};
+
+/// Sizes and draws one kind of custom shape
+pub trait ShapeRenderer: std::fmt::Debug + Send + Sync {
+    /// Size of the shape when the document gives neither width nor height
+    fn intrinsic_size(&self, styles: &ResolvedStyles) -> (f64, f64);
+
+    /// SVG markup drawing the shape inside `bounds`, in absolute coordinates.
+    /// Labels are drawn separately, as for built-in shapes.
+    fn render(&self, bounds: &BoundingBox, styles: &ResolvedStyles) -> String;
+}
+
+/// Custom shape renderers by shape name
+pub type ShapeRenderers = HashMap<String, Arc<dyn ShapeRenderer>>;
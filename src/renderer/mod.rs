@@ -4,9 +4,17 @@
 //! with appropriate CSS classes for styling.
 
 pub mod config;
+pub mod crossings;
+pub mod custom;
+pub mod optimize;
 pub mod path;
 pub mod svg;
 
-pub use config::SvgConfig;
+pub use config::{Fit, StyleMode, SvgConfig, TraversalOrder};
+pub use custom::{ShapeRenderer, ShapeRenderers};
+pub use optimize::{gzip, optimize_svg};
 pub use path::{resolve_path, ResolvedPath};
-pub use svg::{render_svg, render_svg_with_keyframes, render_svg_with_stylesheet};
+pub use svg::{
+    render_animations, render_svg, render_svg_page, render_svg_with_keyframes,
+    render_svg_with_stylesheet,
+};
@@ -0,0 +1,335 @@
+//! Size optimization of rendered SVG (`SvgConfig::optimize`) and SVGZ output
+//!
+//! The passes work on the renderer's own output: attribute values never
+//! contain `<` or `>` (they are escaped), which keeps the tag scanning simple.
+
+use std::io::Write;
+
+/// Decimal places kept in numeric attribute values
+const PRECISION: usize = 2;
+
+/// Attributes whose values are names or URLs, never coordinates
+const NON_NUMERIC_ATTRIBUTES: &[&str] = &[
+    "id",
+    "class",
+    "href",
+    "xlink:href",
+    "data-ai-id",
+    "font-family",
+];
+
+/// Shrink an SVG document: round numbers in attributes to two decimals, merge
+/// identical `<defs>` entries and drop empty unnamed groups
+pub fn optimize_svg(svg: &str) -> String {
+    let svg = merge_identical_defs(svg);
+    let svg = collapse_empty_groups(&svg);
+    round_attribute_numbers(&svg)
+}
+
+/// Gzip an SVG document for `.svgz` output
+pub fn gzip(svg: &str) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    // Writing to a Vec cannot fail
+    encoder.write_all(svg.as_bytes()).expect("in-memory write");
+    encoder.finish().expect("in-memory write")
+}
+
+/// Byte ranges of the top-level elements in `content`
+fn top_level_elements(content: &str) -> Vec<std::ops::Range<usize>> {
+    let mut elements = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    let mut pos = 0;
+    while let Some(open) = content[pos..].find('<') {
+        let tag_start = pos + open;
+        let Some(close) = content[tag_start..].find('>') else {
+            break;
+        };
+        let tag_end = tag_start + close + 1;
+        let tag = &content[tag_start..tag_end];
+        if tag.starts_with("</") {
+            depth -= 1;
+            if depth == 0 {
+                elements.push(start..tag_end);
+            }
+        } else if !tag.starts_with("<!") && !tag.starts_with("<?") {
+            if depth == 0 {
+                start = tag_start;
+            }
+            if tag.ends_with("/>") {
+                if depth == 0 {
+                    elements.push(start..tag_end);
+                }
+            } else {
+                depth += 1;
+            }
+        }
+        pos = tag_end;
+    }
+    elements
+}
+
+/// The value of `name="..."` in a start tag
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let needle = format!(" {}=\"", name);
+    let start = tag.find(&needle)? + needle.len();
+    let end = start + tag[start..].find('"')?;
+    Some(&tag[start..end])
+}
+
+/// Keep one of each set of `<defs>` entries that differ only in their id,
+/// pointing references to the dropped ones at the one kept
+fn merge_identical_defs(svg: &str) -> String {
+    let (Some(open), Some(close)) = (svg.find("<defs>"), svg.find("</defs>")) else {
+        return svg.to_string();
+    };
+    let inner_start = open + "<defs>".len();
+    let inner = &svg[inner_start..close];
+
+    let mut kept: Vec<(String, String)> = vec![];
+    let mut renamed: Vec<(String, String)> = vec![];
+    let mut dropped = vec![];
+    for range in top_level_elements(inner) {
+        let element = &inner[range.clone()];
+        let Some(id) = attribute(element, "id") else {
+            continue;
+        };
+        let shape = element.replacen(&format!(" id=\"{}\"", id), "", 1);
+        match kept.iter().find(|(_, s)| *s == shape) {
+            Some((original, _)) => {
+                renamed.push((id.to_string(), original.clone()));
+                dropped.push(range);
+            }
+            None => kept.push((id.to_string(), shape)),
+        }
+    }
+    if dropped.is_empty() {
+        return svg.to_string();
+    }
+
+    let mut defs = String::new();
+    let mut pos = 0;
+    for range in &dropped {
+        defs.push_str(&inner[pos..range.start]);
+        pos = range.end;
+        // Drop the line the entry was on, too
+        let rest = &inner[pos..];
+        let blank = rest.len() - rest.trim_start_matches([' ', '\n']).len();
+        if rest[..blank].contains('\n') {
+            pos += rest[..blank].rfind('\n').map_or(0, |i| i + 1);
+        }
+    }
+    defs.push_str(&inner[pos..]);
+
+    let mut result = format!("{}{}{}", &svg[..inner_start], defs, &svg[close..]);
+    for (from, to) in &renamed {
+        result = result
+            .replace(&format!("url(#{})", from), &format!("url(#{})", to))
+            .replace(&format!("href=\"#{}\"", from), &format!("href=\"#{}\"", to));
+    }
+    result
+}
+
+/// Remove `<g>` elements without children or an id, innermost first
+fn collapse_empty_groups(svg: &str) -> String {
+    let mut svg = svg.to_string();
+    loop {
+        let mut changed = false;
+        let mut pos = 0;
+        while let Some(found) = svg[pos..].find("<g") {
+            let start = pos + found;
+            let Some(close) = svg[start..].find('>') else {
+                break;
+            };
+            let tag_end = start + close + 1;
+            let tag = &svg[start..tag_end];
+            let is_group = tag.starts_with("<g ") || tag.starts_with("<g>") || tag == "<g/>";
+            if !is_group || attribute(tag, "id").is_some() {
+                pos = tag_end;
+                continue;
+            }
+            let end = if tag.ends_with("/>") {
+                Some(tag_end)
+            } else {
+                let rest = &svg[tag_end..];
+                let blank = rest.len() - rest.trim_start().len();
+                rest[blank..]
+                    .starts_with("</g>")
+                    .then_some(tag_end + blank + "</g>".len())
+            };
+            match end {
+                Some(end) => {
+                    // Take the line's indentation and newline along
+                    let line_start = svg[..start].rfind('\n').map_or(start, |i| i + 1);
+                    let start = if svg[line_start..start].trim().is_empty() {
+                        line_start
+                    } else {
+                        start
+                    };
+                    let end = if svg[end..].starts_with('\n') && start == line_start {
+                        end + 1
+                    } else {
+                        end
+                    };
+                    svg.replace_range(start..end, "");
+                    changed = true;
+                    pos = start;
+                }
+                None => pos = tag_end,
+            }
+        }
+        if !changed {
+            return svg;
+        }
+    }
+}
+
+/// Round numbers with more than two decimals in attribute values
+fn round_attribute_numbers(svg: &str) -> String {
+    let mut result = String::with_capacity(svg.len());
+    let mut pos = 0;
+    while let Some(open) = svg[pos..].find('<') {
+        let tag_start = pos + open;
+        result.push_str(&svg[pos..tag_start]);
+        let Some(close) = svg[tag_start..].find('>') else {
+            pos = tag_start;
+            break;
+        };
+        let tag_end = tag_start + close + 1;
+        result.push_str(&round_tag(&svg[tag_start..tag_end]));
+        pos = tag_end;
+    }
+    result.push_str(&svg[pos..]);
+    result
+}
+
+fn round_tag(tag: &str) -> String {
+    let mut result = String::with_capacity(tag.len());
+    let mut rest = tag;
+    while let Some(eq) = rest.find("=\"") {
+        let name_start = rest[..eq].rfind(' ').map_or(0, |i| i + 1);
+        let name = &rest[name_start..eq];
+        let value_start = eq + 2;
+        let Some(value_len) = rest[value_start..].find('"') else {
+            break;
+        };
+        let value = &rest[value_start..value_start + value_len];
+        result.push_str(&rest[..value_start]);
+        if NON_NUMERIC_ATTRIBUTES.contains(&name) {
+            result.push_str(value);
+        } else {
+            result.push_str(&round_numbers(value));
+        }
+        rest = &rest[value_start + value_len..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Round every decimal number in `value` to [`PRECISION`] places
+fn round_numbers(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let bytes = value.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let starts_number = bytes[i].is_ascii_digit()
+            && (i == 0 || !(bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'#'));
+        if !starts_number {
+            result.push(bytes[i] as char);
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+        if i + 1 < bytes.len() && bytes[i] == b'.' && bytes[i + 1].is_ascii_digit() {
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+        }
+        let number = &value[start..i];
+        let decimals = number.find('.').map_or(0, |dot| number.len() - dot - 1);
+        if decimals > PRECISION {
+            let factor = 10f64.powi(PRECISION as i32);
+            let rounded = number
+                .parse::<f64>()
+                .map_or(0.0, |n| (n * factor).round() / factor);
+            result.push_str(&rounded.to_string());
+        } else {
+            result.push_str(number);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_numbers() {
+        assert_eq!(
+            round_numbers("M 10.123456 -3.456789 L 2.5 100"),
+            "M 10.12 -3.46 L 2.5 100"
+        );
+        assert_eq!(
+            round_numbers("translate(0.333333, 1.999)"),
+            "translate(0.33, 2)"
+        );
+        assert_eq!(round_numbers("-0.0001"), "-0");
+        // Colors and names are not numbers
+        assert_eq!(round_numbers("#123456"), "#123456");
+        assert_eq!(round_numbers("ai-style-12"), "ai-style-12");
+    }
+
+    #[test]
+    fn test_round_attribute_numbers_skips_text_and_names() {
+        let svg = r#"<rect id="a.123456" x="1.23456"/><text x="0.55555">3.14159</text>"#;
+        assert_eq!(
+            round_attribute_numbers(svg),
+            r#"<rect id="a.123456" x="1.23"/><text x="0.56">3.14159</text>"#
+        );
+    }
+
+    #[test]
+    fn test_merge_identical_defs() {
+        let svg = concat!(
+            "<svg>\n  <defs>\n",
+            "    <marker id=\"m1\"><path d=\"M0 0\"/></marker>\n",
+            "    <marker id=\"m2\"><path d=\"M0 0\"/></marker>\n",
+            "    <marker id=\"m3\"><path d=\"M1 1\"/></marker>\n",
+            "  </defs>\n",
+            "  <path marker-end=\"url(#m2)\"/>\n</svg>"
+        );
+        let merged = merge_identical_defs(svg);
+        assert!(!merged.contains(r#"id="m2""#), "{}", merged);
+        assert!(merged.contains(r#"id="m3""#));
+        assert!(merged.contains(r#"marker-end="url(#m1)""#));
+        assert!(!merged.contains("\n\n"));
+    }
+
+    #[test]
+    fn test_collapse_empty_groups() {
+        let svg = "<svg>\n  <g class=\"a\">\n    <g class=\"b\">\n    </g>\n  </g>\n  <g id=\"keep\"></g>\n  <g><rect/></g>\n</svg>";
+        assert_eq!(
+            collapse_empty_groups(svg),
+            "<svg>\n  <g id=\"keep\"></g>\n  <g><rect/></g>\n</svg>"
+        );
+    }
+
+    #[test]
+    fn test_gzip() {
+        use std::io::Read;
+
+        let svg = "<svg>".to_string() + &"<rect/>".repeat(100) + "</svg>";
+        let compressed = gzip(&svg);
+        assert!(compressed.len() < svg.len());
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, svg);
+    }
+}
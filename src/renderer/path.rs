@@ -96,6 +96,24 @@ impl ResolvedPath {
 /// (0, 6), they are shifted to start at (0, 0) relative to the element's bounds,
 /// ensuring constraints like `element.top` refer to the actual visual top of the content.
 pub fn resolve_path_with_options(decl: &PathDecl, origin: Point, normalize: bool) -> ResolvedPath {
+    let (segments, _) = resolve_segments(decl, origin, normalize);
+    ResolvedPath { segments }
+}
+
+/// Absolute positions of the named vertices of a (normalized) path
+///
+/// These become anchors on the path element, so connections can end on a
+/// vertex (`wire -> plug.pin3`).
+pub fn vertex_positions(decl: &PathDecl, origin: Point) -> HashMap<String, Point> {
+    let (_, vertices) = resolve_segments(decl, origin, true);
+    vertices
+}
+
+fn resolve_segments(
+    decl: &PathDecl,
+    origin: Point,
+    normalize: bool,
+) -> (Vec<PathSegment>, HashMap<String, Point>) {
     // First pass: compute the min x and y from all path coordinates
     // This allows us to normalize the path so content starts at (0, 0)
     let (min_x, min_y) = if normalize {
@@ -218,7 +236,7 @@ pub fn resolve_path_with_options(decl: &PathDecl, origin: Point, normalize: bool
         }
     }
 
-    ResolvedPath { segments }
+    (segments, vertices)
 }
 
 /// Resolve a PathDecl into concrete coordinates
@@ -1,13 +1,27 @@
 //! SVG generation from layout results
-
+//!
+//! # Escaping
+//!
+//! Everything that originates from the source document (labels, text content,
+//! class names, dash patterns, colors, image paths, element ids) is XML-escaped
+//! before it is written, both in text content and in attribute values. Untrusted
+//! input therefore cannot inject markup or event-handler attributes.
+//!
+//! The single exception is the `raw_svg` modifier, which emits its string
+//! verbatim. The render pipeline rejects it unless `RenderConfig::allow_raw_svg`
+//! is set, so it is only available to callers that explicitly trust their input.
+
+use crate::layout::keyframe::Animation;
 use crate::layout::{
-    BoundingBox, ConnectionLayout, ElementLayout, ElementType, LayoutResult, Point, ResolvedStyles,
-    RoutingMode, TextAnchor,
+    BoundingBox, ClassCompartments, ConnectionLayout, ElementLayout, ElementTransform, ElementType,
+    FillGradient, LayoutResult, Page, Point, ResolvedStyles, RoutingMode, TextAnchor,
+    VerticalAlign,
 };
-use crate::parser::ast::{ConnectionDirection, ShapeType};
+use crate::parser::ast::{ConnectionDirection, ShapeType, Span, Unit};
 use crate::stylesheet::Stylesheet;
 
-use super::SvgConfig;
+use super::crossings::{find_crossings, path_to_d_with_jumps, Crossing};
+use super::{StyleMode, SvgConfig, TraversalOrder};
 
 /// Build SVG elements incrementally
 pub struct SvgBuilder {
@@ -19,6 +33,32 @@ pub struct SvgBuilder {
     indent: usize,
     /// Frame names for data-frames attribute (Feature 011)
     data_frames: Option<String>,
+    /// Arrowhead sizes that already have a marker definition
+    arrow_sizes: Vec<f64>,
+    /// Ids of the `marker_mid` markers defined so far
+    mid_markers: Vec<String>,
+    /// Number of stroke gradients defined so far (for unique ids)
+    gradient_count: usize,
+    /// Stylesheet gradients by name, with colors already converted to CSS
+    named_gradients: std::collections::HashMap<String, FillGradient>,
+    /// Fill gradients that already have a definition, with their ids
+    fill_gradients: Vec<(FillGradient, String)>,
+    /// CSS declarations of the generated style classes (`StyleMode::Classes`),
+    /// indexed by class number
+    style_classes: Vec<String>,
+    /// Source range of the element or connection being rendered, written as
+    /// `data-ai-span` when `SvgConfig::source_spans` is set
+    source_span: Option<Span>,
+    /// Name and bounds of the element being rendered, written as `data-ai-*`
+    /// attributes when `SvgConfig::geometry_attrs` is set
+    geometry: Option<(Option<String>, BoundingBox)>,
+    /// Navigation numbers of named elements, written as `tabindex`
+    /// (`TraversalOrder::Reading`)
+    tab_order: std::collections::HashMap<String, usize>,
+    /// Default font family from the stylesheet, set on the root element
+    font_family: Option<String>,
+    /// Palette tokens and values, for `SvgConfig::css_variables`
+    palette: Vec<(String, String)>,
 }
 
 impl SvgBuilder {
@@ -32,6 +72,17 @@ impl SvgBuilder {
             connections: vec![],
             indent: 1,
             data_frames: None,
+            arrow_sizes: vec![],
+            mid_markers: vec![],
+            gradient_count: 0,
+            named_gradients: std::collections::HashMap::new(),
+            fill_gradients: vec![],
+            style_classes: vec![],
+            source_span: None,
+            geometry: None,
+            tab_order: std::collections::HashMap::new(),
+            font_family: None,
+            palette: vec![],
         }
     }
 
@@ -42,12 +93,39 @@ impl SvgBuilder {
 
     /// Add CSS custom properties from a stylesheet
     pub fn add_stylesheet(&mut self, stylesheet: &Stylesheet) {
+        for (name, def) in &stylesheet.gradients {
+            let gradient = FillGradient::Linear {
+                from: stylesheet.color_css(&def.from),
+                to: stylesheet.color_css(&def.to),
+                direction: def
+                    .direction
+                    .clone()
+                    .unwrap_or_else(|| "horizontal".to_string()),
+            };
+            self.named_gradients.insert(name.clone(), gradient);
+        }
+        for face in &stylesheet.fonts.faces {
+            self.styles.push(face.to_css());
+        }
+        self.font_family = stylesheet.fonts.family.clone();
         if stylesheet.colors.is_empty() {
             return;
         }
-        let mut css = String::from(":root {\n");
+        // Themable properties are prefixed and declared with zero specificity,
+        // so any host page rule setting them wins
+        let (selector, property_prefix) = if self.config.css_variables {
+            self.palette = stylesheet
+                .colors
+                .iter()
+                .map(|(token, value)| (token.clone(), value.clone()))
+                .collect();
+            (":where(:root)", self.prefix())
+        } else {
+            (":root", String::new())
+        };
+        let mut css = format!("{} {{\n", selector);
         for (token, value) in &stylesheet.colors {
-            css.push_str(&format!("    --{}: {};\n", token, value));
+            css.push_str(&format!("    --{}{}: {};\n", property_prefix, token, value));
         }
         css.push_str("  }\n");
         // Apply font-family to text elements if defined
@@ -65,6 +143,112 @@ impl SvgBuilder {
         self.config.class_prefix.clone().unwrap_or_default()
     }
 
+    /// Format the `id` attribute plus, when enabled, the `data-ai-span` and
+    /// geometry attributes of the element being rendered (with leading space)
+    fn id_attr(&self, id: Option<&str>) -> String {
+        let tab_index = id
+            .and_then(|id| self.tab_order.get(id))
+            .map(|n| format!(r#" tabindex="{}""#, n))
+            .unwrap_or_default();
+        format!(
+            "{}{}{}{}",
+            id_attr(id),
+            tab_index,
+            self.span_attr(),
+            self.geometry_attr()
+        )
+    }
+
+    /// Number the named elements for navigation when the configured
+    /// traversal order differs from the document's
+    fn number_elements(&mut self, elements: &[ElementLayout]) {
+        fn walk(elements: &[ElementLayout], order: &mut std::collections::HashMap<String, usize>) {
+            for element in reading_order(elements) {
+                if let Some(id) = element.id_str() {
+                    let next = order.len() + 1;
+                    order.insert(id.to_string(), next);
+                }
+                walk(&element.children, order);
+            }
+        }
+        if self.config.traversal_order == TraversalOrder::Reading {
+            walk(elements, &mut self.tab_order);
+        }
+    }
+
+    fn geometry_attr(&self) -> String {
+        match &self.geometry {
+            Some((name, bounds)) if self.config.geometry_attrs => {
+                let name = name
+                    .as_deref()
+                    .map(|n| format!(r#" data-ai-id="{}""#, escape_xml(n)))
+                    .unwrap_or_default();
+                format!(
+                    r#"{} data-ai-x="{}" data-ai-y="{}" data-ai-width="{}" data-ai-height="{}""#,
+                    name, bounds.x, bounds.y, bounds.width, bounds.height
+                )
+            }
+            _ => String::new(),
+        }
+    }
+
+    fn span_attr(&self) -> String {
+        match &self.source_span {
+            Some(span) if self.config.source_spans => {
+                format!(r#" data-ai-span="{}..{}""#, span.start, span.end)
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// Move CSS-capable presentation attributes into a generated class
+    ///
+    /// In `StyleMode::Classes`, paint attributes (`fill`, `stroke`, ...) are
+    /// replaced by a shared `{prefix}style-N` class defined in the `<style>`
+    /// block; everything else (`rx`, `marker-end`, ...) stays inline. Returns
+    /// the extended class list and the remaining attributes.
+    fn styled(&mut self, class_list: String, styles: &str) -> (String, String) {
+        if self.config.style_mode == StyleMode::Inline {
+            return (class_list, styles.to_string());
+        }
+        let mut declarations = vec![];
+        let mut inline = String::new();
+        for (name, value) in split_attributes(styles) {
+            if CSS_PAINT_PROPERTIES.contains(&name) {
+                // CSS lengths need a unit where attributes allow bare numbers
+                let unit = if matches!(name, "stroke-width" | "font-size")
+                    && value.parse::<f64>().is_ok()
+                {
+                    "px"
+                } else {
+                    ""
+                };
+                declarations.push(format!("{}: {}{};", name, value, unit));
+            } else {
+                inline.push_str(&format!(r#" {}="{}""#, name, value));
+            }
+        }
+        if declarations.is_empty() {
+            return (class_list, inline);
+        }
+
+        let rule = declarations.join(" ");
+        let index = match self.style_classes.iter().position(|r| *r == rule) {
+            Some(index) => index,
+            None => {
+                self.style_classes.push(rule);
+                self.style_classes.len() - 1
+            }
+        };
+        let class = format!("{}style-{}", self.prefix(), index);
+        let class_list = if class_list.is_empty() {
+            class
+        } else {
+            format!("{} {}", class_list, class)
+        };
+        (class_list, inline)
+    }
+
     fn indent_str(&self) -> String {
         if self.config.pretty_print {
             "  ".repeat(self.indent)
@@ -81,19 +265,145 @@ impl SvgBuilder {
         }
     }
 
-    /// Add the arrow marker definition for directed connections
-    pub fn add_arrow_marker(&mut self) {
+    /// Arrowhead size for a connection: `arrow_scale` times the stroke width,
+    /// clamped to `arrow_min_size..=arrow_max_size`
+    fn arrow_size(&self, stroke_width: f64) -> f64 {
+        let size = (self.config.arrow_scale * stroke_width)
+            .max(self.config.arrow_min_size)
+            .min(self.config.arrow_max_size);
+        (size * 100.0).round() / 100.0
+    }
+
+    /// Return the id of the arrow marker for `size`, defining it on first use
+    pub fn add_arrow_marker(&mut self, size: f64) -> String {
         let prefix = self.prefix();
+        let id = format!("{prefix}arrow-{}", size.to_string().replace('.', "_"));
+        if self.arrow_sizes.contains(&size) {
+            return id;
+        }
+        self.arrow_sizes.push(size);
         // Use orient="auto" to automatically rotate the marker to match path direction
         // at the marker position. The arrow shape points right (+X), so it will
         // rotate to match the final segment direction (e.g., down for vertical paths).
         // Use fill="context-stroke" so the arrow inherits the line's stroke color.
-        // Use markerUnits="strokeWidth" so arrow size scales with line thickness.
+        // Sizes are in user units so they can be clamped independently of the stroke.
         self.defs.push(format!(
-            r#"<marker id="{prefix}arrow" viewBox="0 0 10 10" refX="1" refY="5" markerWidth="4" markerHeight="4" markerUnits="strokeWidth" orient="auto">
+            r#"<marker id="{id}" viewBox="0 0 10 10" refX="1" refY="5" markerWidth="{size}" markerHeight="{size}" markerUnits="userSpaceOnUse" orient="auto">
       <path d="M0,0 L10,5 L0,10 Z" fill="context-stroke"/>
     </marker>"#
         ));
+        id
+    }
+
+    /// Return the id of the marker drawn at a connection's interior vertices
+    /// (`marker_mid: dot|arrow`), defining it on first use; `None` for `none`
+    ///
+    /// Unlike the end arrow, a mid arrow is centered on its vertex.
+    pub fn add_mid_marker(&mut self, kind: &str, stroke_width: f64) -> Option<String> {
+        let size = self.arrow_size(stroke_width);
+        let shape = match kind {
+            "dot" => r#"<circle cx="5" cy="5" r="4" fill="context-stroke"/>"#,
+            "arrow" => r#"<path d="M0,0 L10,5 L0,10 Z" fill="context-stroke"/>"#,
+            _ => return None,
+        };
+        let prefix = self.prefix();
+        let id = format!("{prefix}mid-{kind}-{}", size.to_string().replace('.', "_"));
+        if !self.mid_markers.contains(&id) {
+            self.mid_markers.push(id.clone());
+            self.defs.push(format!(
+                r#"<marker id="{id}" viewBox="0 0 10 10" refX="5" refY="5" markerWidth="{size}" markerHeight="{size}" markerUnits="userSpaceOnUse" orient="auto">
+      {shape}
+    </marker>"#
+            ));
+        }
+        Some(id)
+    }
+
+    /// Define a gradient running from the first to the last point of `path` for
+    /// a faded stroke (`stroke: fade(a, b)`, `opacity_start`, `opacity_end`).
+    ///
+    /// Returns the gradient id, or `None` when the styles ask for a flat stroke.
+    pub fn add_stroke_gradient(
+        &mut self,
+        path: &[Point],
+        styles: &ResolvedStyles,
+    ) -> Option<String> {
+        if styles.stroke_fade.is_none()
+            && styles.opacity_start.is_none()
+            && styles.opacity_end.is_none()
+        {
+            return None;
+        }
+        let (start, end) = (path.first()?, path.last()?);
+        if (start.x - end.x).abs() < 0.001 && (start.y - end.y).abs() < 0.001 {
+            return None;
+        }
+
+        let stroke = styles.stroke.clone().unwrap_or_else(|| "#333".to_string());
+        let (from, to) = styles
+            .stroke_fade
+            .clone()
+            .unwrap_or_else(|| (stroke.clone(), stroke));
+        let id = format!("{}fade-{}", self.prefix(), self.gradient_count);
+        self.gradient_count += 1;
+
+        // userSpaceOnUse keeps straight horizontal/vertical paths (zero-size bbox) working
+        self.defs.push(format!(
+            r#"<linearGradient id="{}" gradientUnits="userSpaceOnUse" x1="{}" y1="{}" x2="{}" y2="{}">
+      <stop offset="0" stop-color="{}" stop-opacity="{}"/>
+      <stop offset="1" stop-color="{}" stop-opacity="{}"/>
+    </linearGradient>"#,
+            id,
+            start.x,
+            start.y,
+            end.x,
+            end.y,
+            escape_xml(&from),
+            styles.opacity_start.unwrap_or(1.0),
+            escape_xml(&to),
+            styles.opacity_end.unwrap_or(1.0),
+        ));
+        Some(id)
+    }
+
+    /// Define a fill gradient (once per distinct gradient) and return its id
+    ///
+    /// Returns None for a stylesheet gradient name that is not defined.
+    pub fn add_fill_gradient(&mut self, gradient: &FillGradient) -> Option<String> {
+        let gradient = match gradient {
+            FillGradient::Named(name) => self.named_gradients.get(name)?.clone(),
+            linear => linear.clone(),
+        };
+        if let Some((_, id)) = self.fill_gradients.iter().find(|(g, _)| *g == gradient) {
+            return Some(id.clone());
+        }
+        let FillGradient::Linear {
+            from,
+            to,
+            direction,
+        } = &gradient
+        else {
+            return None;
+        };
+        let (x2, y2) = match direction.as_str() {
+            "vertical" => (0, 1),
+            "diagonal" => (1, 1),
+            _ => (1, 0),
+        };
+        let id = format!("{}gradient-{}", self.prefix(), self.fill_gradients.len());
+        self.defs.push(format!(
+            r#"<linearGradient id="{}" x1="0" y1="0" x2="{}" y2="{}">
+      <stop offset="0" stop-color="{}"/>
+      <stop offset="1" stop-color="{}"/>
+    </linearGradient>"#,
+            id,
+            x2,
+            y2,
+            escape_xml(from),
+            escape_xml(to),
+        ));
+        self.fill_gradients.push((gradient, id.clone()));
+        Some(id)
     }
 
     /// Add a rectangle element
@@ -109,18 +419,20 @@ impl SvgBuilder {
         styles: &str,
     ) {
         let prefix = self.prefix();
-        let id_attr = id.map(|i| format!(r#" id="{}""#, i)).unwrap_or_default();
+        let id_attr = self.id_attr(id);
         let class_list = std::iter::once(format!("{}shape", prefix))
             .chain(std::iter::once(format!("{}rect", prefix)))
             .chain(classes.iter().cloned())
             .collect::<Vec<_>>()
             .join(" ");
 
+        let (class_list, styles) = self.styled(class_list, styles);
+
         self.elements.push(format!(
             r#"{}<rect{} class="{}" x="{}" y="{}" width="{}" height="{}"{}/>"#,
             self.indent_str(),
             id_attr,
-            class_list,
+            escape_xml(&class_list),
             x,
             y,
             w,
@@ -129,6 +441,22 @@ impl SvgBuilder {
         ));
     }
 
+    /// Add the background rect of a styled container (drawn behind its children)
+    pub fn add_container_background(&mut self, bounds: &BoundingBox, styles: &str) {
+        let class_list = format!("{}container-bg", self.prefix());
+        let (class_list, styles) = self.styled(class_list, styles);
+        self.elements.push(format!(
+            r#"{}<rect class="{}" x="{}" y="{}" width="{}" height="{}"{}/>"#,
+            self.indent_str(),
+            escape_xml(&class_list),
+            bounds.x,
+            bounds.y,
+            bounds.width,
+            bounds.height,
+            styles
+        ));
+    }
+
     /// Add a debug rectangle with dashed border and tiny label
     pub fn add_debug_rect(&mut self, x: f64, y: f64, w: f64, h: f64, label: &str) {
         // Dashed magenta rectangle
@@ -144,7 +472,7 @@ impl SvgBuilder {
                 self.indent_str(),
                 x + 1.0,
                 y + 6.0,
-                label
+                escape_xml(label)
             ));
         }
     }
@@ -160,18 +488,20 @@ impl SvgBuilder {
         styles: &str,
     ) {
         let prefix = self.prefix();
-        let id_attr = id.map(|i| format!(r#" id="{}""#, i)).unwrap_or_default();
+        let id_attr = self.id_attr(id);
         let class_list = std::iter::once(format!("{}shape", prefix))
             .chain(std::iter::once(format!("{}circle", prefix)))
             .chain(classes.iter().cloned())
             .collect::<Vec<_>>()
             .join(" ");
 
+        let (class_list, styles) = self.styled(class_list, styles);
+
         self.elements.push(format!(
             r#"{}<circle{} class="{}" cx="{}" cy="{}" r="{}"{}/>"#,
             self.indent_str(),
             id_attr,
-            class_list,
+            escape_xml(&class_list),
             cx,
             cy,
             r,
@@ -192,18 +522,20 @@ impl SvgBuilder {
         styles: &str,
     ) {
         let prefix = self.prefix();
-        let id_attr = id.map(|i| format!(r#" id="{}""#, i)).unwrap_or_default();
+        let id_attr = self.id_attr(id);
         let class_list = std::iter::once(format!("{}shape", prefix))
             .chain(std::iter::once(format!("{}ellipse", prefix)))
             .chain(classes.iter().cloned())
             .collect::<Vec<_>>()
             .join(" ");
 
+        let (class_list, styles) = self.styled(class_list, styles);
+
         self.elements.push(format!(
             r#"{}<ellipse{} class="{}" cx="{}" cy="{}" rx="{}" ry="{}"{}/>"#,
             self.indent_str(),
             id_attr,
-            class_list,
+            escape_xml(&class_list),
             cx,
             cy,
             rx,
@@ -221,7 +553,7 @@ impl SvgBuilder {
         styles: &str,
     ) {
         let prefix = self.prefix();
-        let id_attr = id.map(|i| format!(r#" id="{}""#, i)).unwrap_or_default();
+        let id_attr = self.id_attr(id);
         let class_list = std::iter::once(format!("{}shape", prefix))
             .chain(std::iter::once(format!("{}polygon", prefix)))
             .chain(classes.iter().cloned())
@@ -234,31 +566,88 @@ impl SvgBuilder {
             .collect::<Vec<_>>()
             .join(" ");
 
+        let (class_list, styles) = self.styled(class_list, styles);
+
         self.elements.push(format!(
             r#"{}<polygon{} class="{}" points="{}"{}/>"#,
             self.indent_str(),
             id_attr,
-            class_list,
+            escape_xml(&class_list),
             points_str,
             styles
         ));
     }
 
+    /// Add the extruded top and right faces behind a rect (`depth` modifier)
+    ///
+    /// The faces are shaded darker than the fill. Hex fills are shaded
+    /// directly; other colors (named, `var(--...)`) get a translucent black
+    /// overlay on top of the original fill instead.
+    pub fn add_extrusion(&mut self, bounds: &BoundingBox, depth: f64, styles: &ResolvedStyles) {
+        let (x, y, r, b) = (bounds.x, bounds.y, bounds.right(), bounds.bottom());
+        let faces = [
+            (
+                0.85,
+                [
+                    Point::new(x, y),
+                    Point::new(x + depth, y - depth),
+                    Point::new(r + depth, y - depth),
+                    Point::new(r, y),
+                ],
+            ),
+            (
+                0.7,
+                [
+                    Point::new(r, y),
+                    Point::new(r + depth, y - depth),
+                    Point::new(r + depth, b - depth),
+                    Point::new(r, b),
+                ],
+            ),
+        ];
+        let class = vec![format!("{}extrusion", self.prefix())];
+        let fill = styles.fill.as_deref().unwrap_or("#f0f0f0");
+
+        for (factor, points) in faces {
+            let mut face = styles.clone();
+            match shade_hex(fill, factor) {
+                Some(shaded) => {
+                    face.fill = Some(shaded);
+                    self.add_polygon(None, &points, &class, &format_styles(&face));
+                }
+                None => {
+                    self.add_polygon(None, &points, &class, &format_styles(&face));
+                    self.add_polygon(
+                        None,
+                        &points,
+                        &class,
+                        &format!(
+                            r##" fill="#000000" fill-opacity="{:.2}" stroke="none""##,
+                            1.0 - factor
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
     /// Add a path element with custom d attribute (Feature 007)
     pub fn add_path(&mut self, id: Option<&str>, d: &str, classes: &[String], styles: &str) {
         let prefix = self.prefix();
-        let id_attr = id.map(|i| format!(r#" id="{}""#, i)).unwrap_or_default();
+        let id_attr = self.id_attr(id);
         let class_list = std::iter::once(format!("{}shape", prefix))
             .chain(std::iter::once(format!("{}path", prefix)))
             .chain(classes.iter().cloned())
             .collect::<Vec<_>>()
             .join(" ");
 
+        let (class_list, styles) = self.styled(class_list, styles);
+
         self.elements.push(format!(
             r#"{}<path{} class="{}" d="{}"{}/>"#,
             self.indent_str(),
             id_attr,
-            class_list,
+            escape_xml(&class_list),
             d,
             if styles.is_empty() {
                 String::new()
@@ -281,18 +670,20 @@ impl SvgBuilder {
         styles: &str,
     ) {
         let prefix = self.prefix();
-        let id_attr = id.map(|i| format!(r#" id="{}""#, i)).unwrap_or_default();
+        let id_attr = self.id_attr(id);
         let class_list = std::iter::once(format!("{}shape", prefix))
             .chain(std::iter::once(format!("{}line", prefix)))
             .chain(classes.iter().cloned())
             .collect::<Vec<_>>()
             .join(" ");
 
+        let (class_list, styles) = self.styled(class_list, styles);
+
         self.elements.push(format!(
             r#"{}<line{} class="{}" x1="{}" y1="{}" x2="{}" y2="{}"{}/>"#,
             self.indent_str(),
             id_attr,
-            class_list,
+            escape_xml(&class_list),
             x1,
             y1,
             x2,
@@ -314,7 +705,7 @@ impl SvgBuilder {
         classes: &[String],
         transform: Option<&str>,
     ) {
-        let id_attr = id.map(|i| format!(r#" id="{}""#, i)).unwrap_or_default();
+        let id_attr = self.id_attr(id);
         let class_list = classes.join(" ");
         let transform_attr = transform
             .map(|t| format!(r#" transform="{}""#, t))
@@ -324,8 +715,8 @@ impl SvgBuilder {
             r#"{}<image{} class="{}" href="{}" x="{}" y="{}" width="{}" height="{}"{}/>"#,
             self.indent_str(),
             id_attr,
-            class_list,
-            href,
+            escape_xml(&class_list),
+            escape_xml(href),
             x,
             y,
             width,
@@ -335,21 +726,31 @@ impl SvgBuilder {
     }
 
     /// Add a text element
-    pub fn add_text(&mut self, text: &str, x: f64, y: f64, anchor: &TextAnchor, styles: &str) {
-        let prefix = self.prefix();
+    pub fn add_text(
+        &mut self,
+        text: &str,
+        x: f64,
+        y: f64,
+        anchor: &TextAnchor,
+        baseline: VerticalAlign,
+        styles: &str,
+    ) {
         let anchor_str = match anchor {
             TextAnchor::Start => "start",
             TextAnchor::Middle => "middle",
             TextAnchor::End => "end",
         };
+        let class_list = format!("{}label", self.prefix());
+        let (class_list, styles) = self.styled(class_list, styles);
 
         self.elements.push(format!(
-            r#"{}<text class="{}label" x="{}" y="{}" text-anchor="{}" dominant-baseline="middle"{}>{}</text>"#,
+            r#"{}<text class="{}" x="{}" y="{}" text-anchor="{}" dominant-baseline="{}"{}>{}</text>"#,
             self.indent_str(),
-            prefix,
+            escape_xml(&class_list),
             x,
             y,
             anchor_str,
+            baseline.dominant_baseline(),
             styles,
             escape_xml(text)
         ));
@@ -364,11 +765,12 @@ impl SvgBuilder {
         x: f64,
         y: f64,
         anchor: &TextAnchor,
+        baseline: VerticalAlign,
         classes: &[String],
         styles: &str,
     ) {
         let prefix = self.prefix();
-        let id_attr = id.map(|i| format!(r#" id="{}""#, i)).unwrap_or_default();
+        let id_attr = self.id_attr(id);
         let anchor_str = match anchor {
             TextAnchor::Start => "start",
             TextAnchor::Middle => "middle",
@@ -380,20 +782,24 @@ impl SvgBuilder {
             .collect::<Vec<_>>()
             .join(" ");
 
+        let (class_list, styles) = self.styled(class_list, styles);
+
         self.elements.push(format!(
-            r#"{}<text{} class="{}" x="{}" y="{}" text-anchor="{}" dominant-baseline="middle"{}>{}</text>"#,
+            r#"{}<text{} class="{}" x="{}" y="{}" text-anchor="{}" dominant-baseline="{}"{}>{}</text>"#,
             self.indent_str(),
             id_attr,
-            class_list,
+            escape_xml(&class_list),
             x,
             y,
             anchor_str,
+            baseline.dominant_baseline(),
             styles,
             escape_xml(text)
         ));
     }
 
     /// Add a path for a connection
+    #[allow(clippy::too_many_arguments)]
     pub fn add_connection_path(
         &mut self,
         path: &[Point],
@@ -402,6 +808,7 @@ impl SvgBuilder {
         styles: &str,
         marker_end: bool,
         stroke_width: f64,
+        jumps: &[Crossing],
     ) {
         let prefix = self.prefix();
         let class_list = std::iter::once(format!("{}connection", prefix))
@@ -409,10 +816,11 @@ impl SvgBuilder {
             .collect::<Vec<_>>()
             .join(" ");
 
+        let arrow_size = self.arrow_size(stroke_width);
+
         // Shorten endpoint when marker is present to place arrow tip at anchor position
-        // The arrow marker has refX=1, so the arrow extends ~9 marker units past the endpoint.
-        // With markerWidth=4 and markerUnits="strokeWidth", each marker unit = (4 * strokeWidth) / 10.
-        // So pullback = 9 * (4/10) * strokeWidth = 3.6 * strokeWidth.
+        // The arrow marker has refX=1, so the arrow extends 9 of its 10 viewBox units
+        // (0.9 * arrow_size) past the endpoint.
         let path = if marker_end && path.len() >= 2 {
             let mut shortened = path.to_vec();
             let last_idx = shortened.len() - 1;
@@ -424,8 +832,8 @@ impl SvgBuilder {
             let len = (dx * dx + dy * dy).sqrt();
 
             if len > 0.001 {
-                // Pull back to compensate for arrow length (scales with stroke width)
-                let pullback = 3.6 * stroke_width;
+                // Pull back to compensate for arrow length
+                let pullback = 0.9 * arrow_size;
                 shortened[last_idx].x -= dx / len * pullback;
                 shortened[last_idx].y -= dy / len * pullback;
             }
@@ -471,19 +879,23 @@ impl SvgBuilder {
                 }
                 d
             }
+            _ if !jumps.is_empty() => path_to_d_with_jumps(&path, jumps, self.config.jump_radius),
             _ => path_to_d(&path), // Default polyline for orthogonal/direct
         };
 
         let marker = if marker_end {
-            format!(r#" marker-end="url(#{prefix}arrow)""#)
+            let id = self.add_arrow_marker(arrow_size);
+            format!(r#" marker-end="url(#{id})""#)
         } else {
             String::new()
         };
+        let (class_list, styles) = self.styled(class_list, &format!(r#" fill="none"{}"#, styles));
 
         self.connections.push(format!(
-            r#"{}<path class="{}" d="{}" fill="none"{}{}/>"#,
+            r#"{}<path{} class="{}" d="{}"{}{}/>"#,
             self.indent_str(),
-            class_list,
+            self.span_attr(),
+            escape_xml(&class_list),
             d,
             styles,
             marker
@@ -492,11 +904,11 @@ impl SvgBuilder {
 
     /// Add a group element with optional ID and classes
     pub fn start_group(&mut self, id: Option<&str>, classes: &[String]) {
-        let id_attr = id.map(|i| format!(r#" id="{}""#, i)).unwrap_or_default();
+        let id_attr = self.id_attr(id);
         let class_attr = if classes.is_empty() {
             String::new()
         } else {
-            format!(r#" class="{}""#, classes.join(" "))
+            format!(r#" class="{}""#, escape_xml(&classes.join(" ")))
         };
 
         self.elements
@@ -504,6 +916,32 @@ impl SvgBuilder {
         self.indent += 1;
     }
 
+    /// Start a `<g role="img">` announced by its `<title>`/`<desc>` children
+    pub fn start_titled_group(&mut self, title: Option<&str>, description: Option<&str>) {
+        self.elements
+            .push(format!(r#"{}<g role="img">"#, self.indent_str()));
+        self.indent += 1;
+        self.add_title(title, description);
+    }
+
+    /// Add `<title>`/`<desc>` children to the group that was just opened
+    pub fn add_title(&mut self, title: Option<&str>, description: Option<&str>) {
+        if let Some(title) = title {
+            self.elements.push(format!(
+                "{}<title>{}</title>",
+                self.indent_str(),
+                escape_xml(title)
+            ));
+        }
+        if let Some(description) = description {
+            self.elements.push(format!(
+                "{}<desc>{}</desc>",
+                self.indent_str(),
+                escape_xml(description)
+            ));
+        }
+    }
+
     /// Close a group element
     pub fn end_group(&mut self) {
         self.indent = self.indent.saturating_sub(1);
@@ -526,7 +964,7 @@ impl SvgBuilder {
         self.elements.push(format!(
             r#"{}<g class="kf-hidden kf-{}">"#,
             self.indent_str(),
-            element_id
+            escape_xml(element_id)
         ));
         self.indent += 1;
     }
@@ -538,11 +976,11 @@ impl SvgBuilder {
         classes: &[String],
         transform: &str,
     ) {
-        let id_attr = id.map(|i| format!(r#" id="{}""#, i)).unwrap_or_default();
+        let id_attr = self.id_attr(id);
         let class_attr = if classes.is_empty() {
             String::new()
         } else {
-            format!(r#" class="{}""#, classes.join(" "))
+            format!(r#" class="{}""#, escape_xml(&classes.join(" ")))
         };
         let transform_attr = if transform.is_empty() {
             String::new()
@@ -560,6 +998,111 @@ impl SvgBuilder {
         self.indent += 1;
     }
 
+    /// Start a render layer group (`<g class="ai-layer-name" opacity="...">`)
+    pub fn start_layer_group(&mut self, class: &str, opacity: Option<f64>) {
+        let opacity_attr = opacity
+            .filter(|o| *o < 1.0)
+            .map(|o| format!(r#" opacity="{}""#, o))
+            .unwrap_or_default();
+        self.elements.push(format!(
+            r#"{}<g class="{}"{}>"#,
+            self.indent_str(),
+            escape_xml(class),
+            opacity_attr
+        ));
+        self.indent += 1;
+    }
+
+    /// Start a container group, exported as a labelled ARIA group when
+    /// `semantic_groups` is enabled
+    pub fn start_container_group(
+        &mut self,
+        id: Option<&str>,
+        classes: &[String],
+        transform: &str,
+        aria_label: Option<&str>,
+    ) {
+        if !self.config.semantic_groups {
+            self.start_group_with_transform(id, classes, transform);
+            return;
+        }
+        let class_attr = if classes.is_empty() {
+            String::new()
+        } else {
+            format!(r#" class="{}""#, escape_xml(&classes.join(" ")))
+        };
+        let transform_attr = if transform.is_empty() {
+            String::new()
+        } else {
+            format!(r#" transform="{}""#, transform)
+        };
+        let label_attr = aria_label
+            .map(|l| format!(r#" aria-label="{}""#, escape_xml(l)))
+            .unwrap_or_default();
+
+        self.elements.push(format!(
+            r#"{}<g{}{}{} role="group"{}>"#,
+            self.indent_str(),
+            self.id_attr(id),
+            class_attr,
+            transform_attr,
+            label_attr
+        ));
+        self.indent += 1;
+    }
+
+    /// Add a numbered step marker (filled circle) on top of the connections
+    pub fn add_seq_marker(&mut self, position: Point, number: u32, color: &str) {
+        let prefix = self.prefix();
+        let (circle_class, circle_styles) =
+            self.styled(String::new(), &format!(r#" fill="{}""#, escape_xml(color)));
+        let (text_class, text_styles) =
+            self.styled(String::new(), r##" font-size="11" fill="#ffffff""##);
+        let class_attr = |class: &str| {
+            if class.is_empty() {
+                String::new()
+            } else {
+                format!(r#" class="{}""#, class)
+            }
+        };
+        self.connections.push(format!(
+            r#"{}<g class="{}seq"><circle{} cx="{}" cy="{}" r="9"{}/><text{} x="{}" y="{}" text-anchor="middle" dominant-baseline="central"{}>{}</text></g>"#,
+            self.indent_str(),
+            prefix,
+            class_attr(&circle_class),
+            position.x,
+            position.y,
+            circle_styles,
+            class_attr(&text_class),
+            position.x,
+            position.y,
+            text_styles,
+            number
+        ));
+    }
+
+    /// Add a page continuation marker where a connection leaves the page
+    pub fn add_continuation_marker(&mut self, x: f64, y: f64, label: &str) {
+        let prefix = self.prefix();
+        self.connections.push(format!(
+            r#"{}<circle class="{}continuation" cx="{}" cy="{}" r="4"/>"#,
+            self.indent_str(),
+            prefix,
+            x,
+            y
+        ));
+        if !label.is_empty() {
+            self.connections.push(format!(
+                r#"{}<text class="{}continuation-label" x="{}" y="{}" font-size="8">{}</text>"#,
+                self.indent_str(),
+                prefix,
+                x + 6.0,
+                y - 6.0,
+                escape_xml(label)
+            ));
+        }
+    }
+
     /// Add raw SVG content (for embedded SVG templates)
     pub fn add_raw(&mut self, content: &str) {
         // Split content into lines and add with proper indentation
@@ -574,11 +1117,11 @@ impl SvgBuilder {
 
     /// Build the final SVG string
     pub fn build(self, viewbox: BoundingBox) -> String {
-        let padding = self.config.viewbox_padding;
-        let vb_x = viewbox.x - padding;
-        let vb_y = viewbox.y - padding;
-        let vb_w = viewbox.width + 2.0 * padding;
-        let vb_h = viewbox.height + 2.0 * padding;
+        let margin = self.config.margins();
+        let vb_x = viewbox.x - margin.left;
+        let vb_y = viewbox.y - margin.top;
+        let vb_w = viewbox.width + margin.left + margin.right;
+        let vb_h = viewbox.height + margin.top + margin.bottom;
 
         let nl = self.newline();
 
@@ -594,18 +1137,53 @@ impl SvgBuilder {
         let data_frames_attr = self
             .data_frames
             .as_ref()
-            .map(|f| format!(r#" data-frames="{}""#, f))
+            .map(|f| format!(r#" data-frames="{}""#, escape_xml(f)))
+            .unwrap_or_default();
+        // Output size; the viewBox keeps diagram units, so the SVG scales to it
+        let size_attr = self
+            .config
+            .output_size(vb_w, vb_h)
+            .map(|(w, h)| {
+                // Sizes are in layout pixels; px is written without a suffix
+                let unit = self.config.unit.filter(|u| *u != Unit::Px);
+                let length = |v: f64| {
+                    let v = unit.map_or(v, |u| v / u.px());
+                    let v = (v * 100.0).round() / 100.0;
+                    format!("{}{}", v, unit.map_or("", Unit::as_str))
+                };
+                let mut attr = format!(r#" width="{}" height="{}""#, length(w), length(h));
+                if self.config.width.is_some() && self.config.height.is_some() {
+                    let fit = self.config.fit.unwrap_or_default();
+                    attr.push_str(&format!(
+                        r#" preserveAspectRatio="{}""#,
+                        fit.preserve_aspect_ratio()
+                    ));
+                }
+                attr
+            })
+            .unwrap_or_default();
+        // Inherited by all text; CSS and per-element font_family override it
+        let font_attr = self
+            .font_family
+            .as_ref()
+            .map(|f| format!(r#" font-family="{}""#, escape_xml(f)))
             .unwrap_or_default();
         svg.push_str(&format!(
-            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}"{}>"#,
-            vb_x, vb_y, vb_w, vb_h, data_frames_attr
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}"{}{}{}>"#,
+            vb_x, vb_y, vb_w, vb_h, size_attr, font_attr, data_frames_attr
         ));
         svg.push_str(nl);
 
-        // Style section for CSS custom properties
-        if !self.styles.is_empty() {
+        // Style section for generated style classes and CSS custom properties.
+        // Generated classes come first so custom CSS can override them.
+        if !self.styles.is_empty() || !self.style_classes.is_empty() {
             svg.push_str("  <style>");
             svg.push_str(nl);
+            let prefix = self.prefix();
+            for (index, rule) in self.style_classes.iter().enumerate() {
+                svg.push_str(&format!("    .{}style-{} {{ {} }}", prefix, index, rule));
+                svg.push_str(nl);
+            }
             for style in &self.styles {
                 svg.push_str("    ");
                 svg.push_str(style);
@@ -628,6 +1206,20 @@ impl SvgBuilder {
             svg.push_str(nl);
         }
 
+        // Background covering the whole canvas, behind everything else
+        if let Some(background) = &self.config.background {
+            svg.push_str(&format!(
+                r#"  <rect class="{}background" x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+                self.prefix(),
+                vb_x,
+                vb_y,
+                vb_w,
+                vb_h,
+                escape_xml(background)
+            ));
+            svg.push_str(nl);
+        }
+
         // Elements
         for elem in &self.elements {
             svg.push_str(elem);
@@ -642,6 +1234,24 @@ impl SvgBuilder {
 
         svg.push_str("</svg>");
 
+        let svg = self.theme_variables(svg);
+        if self.config.optimize {
+            super::optimize_svg(&svg)
+        } else {
+            svg
+        }
+    }
+
+    /// With `SvgConfig::css_variables`, point palette references at the
+    /// prefixed custom properties, with the palette value as fallback
+    fn theme_variables(&self, mut svg: String) -> String {
+        let prefix = self.prefix();
+        for (token, value) in &self.palette {
+            svg = svg.replace(
+                &format!("var(--{})", token),
+                &format!("var(--{}{}, {})", prefix, token, escape_xml(value)),
+            );
+        }
         svg
     }
 }
@@ -651,6 +1261,57 @@ pub fn render_svg(result: &LayoutResult, config: &SvgConfig) -> String {
     render_svg_with_stylesheet(result, config, &Stylesheet::default(), None, false)
 }
 
+/// SMIL `<animate>` elements for the document's `animate` statements, to be
+/// placed just before `</svg>`
+///
+/// Each one points at its element by id, so the elements themselves stay
+/// untouched. SMIL does not read CSS variables, so palette colors are
+/// resolved through the stylesheet.
+pub fn render_animations(animations: &[Animation], stylesheet: &Stylesheet) -> String {
+    let color = |value: &str| match value
+        .strip_prefix("var(--")
+        .and_then(|v| v.strip_suffix(')'))
+    {
+        Some(token) => stylesheet.resolve_or_default(token),
+        None => value.to_string(),
+    };
+    let mut out = String::new();
+    for animation in animations {
+        let timing = format!(
+            r#" dur="{}s"{}{} fill="freeze""#,
+            animation.duration,
+            if animation.delay > 0.0 {
+                format!(r#" begin="{}s""#, animation.delay)
+            } else {
+                String::new()
+            },
+            animation
+                .repeat
+                .as_ref()
+                .map(|r| format!(r#" repeatCount="{}""#, escape_xml(r)))
+                .unwrap_or_default()
+        );
+        let href = escape_xml(&animation.target);
+        if animation.attribute == "transform" {
+            // Rotate about the element's center, on top of any existing transform
+            let (cx, cy) = (animation.center.x, animation.center.y);
+            out.push_str(&format!(
+                r##"  <animateTransform href="#{href}" attributeName="transform" type="rotate" from="{} {cx} {cy}" to="{} {cx} {cy}" additive="sum"{timing}/>"##,
+                animation.from, animation.to
+            ));
+        } else {
+            out.push_str(&format!(
+                r##"  <animate href="#{href}" attributeName="{}" from="{}" to="{}"{timing}/>"##,
+                animation.attribute,
+                escape_xml(&color(&animation.from)),
+                escape_xml(&color(&animation.to))
+            ));
+        }
+        out.push('\n');
+    }
+    out
+}
+
 /// Render a LayoutResult with keyframe animation CSS (Feature 011)
 pub fn render_svg_with_keyframes(
     result: &LayoutResult,
@@ -674,22 +1335,15 @@ pub fn render_svg_with_keyframes(
     let keyframe_css = generate_keyframe_css(frame_states, frame_diffs);
     builder.add_custom_css(&keyframe_css);
 
+    if let Some(width) = config.detail_breakpoint {
+        builder.add_custom_css(&detail_breakpoint_css(&builder.prefix(), width));
+    }
+
     // Add custom CSS after keyframe CSS
     if let Some(css) = custom_css {
         builder.add_custom_css(css);
     }
 
-    // Add arrow marker if there are any directed connections
-    let has_directed = result.connections.iter().any(|c| {
-        matches!(
-            c.direction,
-            ConnectionDirection::Forward | ConnectionDirection::Backward
-        )
-    });
-    if has_directed {
-        builder.add_arrow_marker();
-    }
-
     // Render elements at frame-0 positions, with hidden elements getting opacity: 0
     let empty_set = std::collections::HashSet::new();
     let frame0_hidden = if !frame_states.is_empty() {
@@ -698,9 +1352,8 @@ pub fn render_svg_with_keyframes(
         &empty_set
     };
 
-    let mut sorted_elements: Vec<&ElementLayout> = result.root_elements.iter().collect();
-    sorted_elements.sort_by_key(|e| e.z_order);
-    for element in &sorted_elements {
+    builder.number_elements(&result.root_elements);
+    for element in ordered_elements(&result.root_elements) {
         render_element_with_visibility(element, &mut builder, frame0_hidden);
     }
 
@@ -711,17 +1364,18 @@ pub fn render_svg_with_keyframes(
         &empty_set
     };
 
-    for conn in &result.connections {
+    let jumps = find_crossings(&result.connections, config.crossings, config.jump_radius);
+    for (conn, conn_jumps) in result.connections.iter().zip(&jumps) {
         if let Some(name) = &conn.name {
             if frame0_hidden_conns.contains(&name.0) {
                 // Render with opacity 0 for hidden connections
                 let mut hidden_conn = conn.clone();
                 hidden_conn.styles.opacity = Some(0.0);
-                render_connection(&hidden_conn, &mut builder);
+                render_connection(&hidden_conn, &mut builder, conn_jumps);
                 continue;
             }
         }
-        render_connection(conn, &mut builder);
+        render_connection(conn, &mut builder, conn_jumps);
     }
 
     // Render debug overlays
@@ -823,6 +1477,17 @@ fn generate_keyframe_css(
     css
 }
 
+/// Media query hiding `detail: high` elements when the SVG renders narrower than `width`.
+///
+/// Inside a standalone SVG (e.g. loaded via `<img>`), the media query matches
+/// the rendered size of the image, so thumbnails drop fine detail.
+fn detail_breakpoint_css(prefix: &str, width: f64) -> String {
+    format!(
+        "@media (max-width: {}px) {{\n  .{}detail-high {{ display: none; }}\n}}\n",
+        width, prefix
+    )
+}
+
 /// Render a LayoutResult to an SVG string with a custom stylesheet
 pub fn render_svg_with_stylesheet(
     result: &LayoutResult,
@@ -831,37 +1496,68 @@ pub fn render_svg_with_stylesheet(
     custom_css: Option<&str>,
     debug: bool,
 ) -> String {
+    render_layout(result, config, stylesheet, custom_css, debug).build(result.bounds)
+}
+
+/// Render a single page of a paginated layout.
+///
+/// The viewBox is the page viewport (without padding) and every connection
+/// crossing the page edge gets a marker naming the page it continues on.
+pub fn render_svg_page(
+    result: &LayoutResult,
+    page: &Page,
+    config: &SvgConfig,
+    stylesheet: &Stylesheet,
+) -> String {
+    let mut config = config.clone();
+    config.viewbox_padding = 0.0;
+    config.margin = None;
+    let mut builder = render_layout(result, &config, stylesheet, None, false);
+
+    for cont in &page.continuations {
+        let label = cont
+            .remote_page
+            .filter(|&p| p != page.index)
+            .map(|p| format!("\u{2192} p{}", p + 1))
+            .unwrap_or_default();
+        builder.add_continuation_marker(cont.point.x, cont.point.y, &label);
+    }
+
+    builder.build(page.viewport)
+}
+
+/// Render elements and connections into a builder, leaving the viewBox to the caller
+fn render_layout(
+    result: &LayoutResult,
+    config: &SvgConfig,
+    stylesheet: &Stylesheet,
+    custom_css: Option<&str>,
+    debug: bool,
+) -> SvgBuilder {
     let mut builder = SvgBuilder::new(config.clone());
 
     // Add CSS custom properties from the stylesheet
     builder.add_stylesheet(stylesheet);
 
+    if let Some(width) = config.detail_breakpoint {
+        builder.add_custom_css(&detail_breakpoint_css(&builder.prefix(), width));
+    }
+
     // Add custom CSS after stylesheet variables (so it can reference/override them)
     if let Some(css) = custom_css {
         builder.add_custom_css(css);
     }
 
-    // Add arrow marker if there are any directed connections
-    let has_directed = result.connections.iter().any(|c| {
-        matches!(
-            c.direction,
-            ConnectionDirection::Forward | ConnectionDirection::Backward
-        )
-    });
-    if has_directed {
-        builder.add_arrow_marker();
-    }
-
-    // Render all root elements, sorted by z_order (stable sort preserves document order)
-    let mut sorted_elements: Vec<&ElementLayout> = result.root_elements.iter().collect();
-    sorted_elements.sort_by_key(|e| e.z_order);
-    for element in &sorted_elements {
+    // Render all root elements (by z_order, then declaration order)
+    builder.number_elements(&result.root_elements);
+    for element in ordered_elements(&result.root_elements) {
         render_element(element, &mut builder);
     }
 
-    // Render all connections
-    for conn in &result.connections {
-        render_connection(conn, &mut builder);
+    // Render all connections, hopping over earlier ones where requested
+    let jumps = find_crossings(&result.connections, config.crossings, config.jump_radius);
+    for (conn, conn_jumps) in result.connections.iter().zip(&jumps) {
+        render_connection(conn, &mut builder, conn_jumps);
     }
 
     // Render debug overlays
@@ -871,7 +1567,44 @@ pub fn render_svg_with_stylesheet(
         }
     }
 
-    builder.build(result.bounds)
+    builder
+}
+
+/// Siblings in emission order: ascending `z_order`, ties in declaration
+/// order, so later siblings draw on top
+fn ordered_elements(elements: &[ElementLayout]) -> Vec<&ElementLayout> {
+    let mut ordered: Vec<&ElementLayout> = elements.iter().collect();
+    // Stable sort, so equal z keeps the declaration order
+    ordered.sort_by_key(|e| e.z_order);
+    ordered
+}
+
+/// Siblings in reading order: elements that overlap vertically share a
+/// line, lines are read top-to-bottom and each line left-to-right
+fn reading_order(elements: &[ElementLayout]) -> Vec<&ElementLayout> {
+    let mut ordered: Vec<&ElementLayout> = elements.iter().collect();
+    ordered.sort_by(|a, b| a.bounds.y.total_cmp(&b.bounds.y));
+    let mut lines: Vec<Vec<&ElementLayout>> = vec![];
+    let mut line_bottom = f64::NEG_INFINITY;
+    for elem in ordered {
+        match lines.last_mut() {
+            Some(line) if elem.bounds.y < line_bottom => {
+                line_bottom = line_bottom.max(elem.bounds.bottom());
+                line.push(elem);
+            }
+            _ => {
+                line_bottom = elem.bounds.bottom();
+                lines.push(vec![elem]);
+            }
+        }
+    }
+    lines
+        .into_iter()
+        .flat_map(|mut line| {
+            line.sort_by(|a, b| a.bounds.x.total_cmp(&b.bounds.x));
+            line
+        })
+        .collect()
 }
 
 /// Render debug bounds for an element and its children
@@ -888,21 +1621,37 @@ fn render_debug_bounds(element: &ElementLayout, builder: &mut SvgBuilder) {
     }
 }
 
-/// Wrap shape rendering with rotation transform if needed
+/// SVG `transform` value for an element's rotation, scale and flip around `center`
+fn element_transform(styles: &ResolvedStyles, center: Point) -> Option<String> {
+    let transform = ElementTransform::from_styles(styles);
+    if transform.is_identity() {
+        return None;
+    }
+    let (scale_x, scale_y) = transform.factors();
+    if (scale_x - 1.0).abs() < f64::EPSILON && (scale_y - 1.0).abs() < f64::EPSILON {
+        return Some(format!(
+            "rotate({} {} {})",
+            transform.rotation, center.x, center.y
+        ));
+    }
+    let mut parts = vec![format!("translate({} {})", center.x, center.y)];
+    if transform.rotation.abs() > f64::EPSILON {
+        parts.push(format!("rotate({})", transform.rotation));
+    }
+    parts.push(format!("scale({} {})", scale_x, scale_y));
+    parts.push(format!("translate({} {})", -center.x, -center.y));
+    Some(parts.join(" "))
+}
+
+/// Wrap shape rendering with rotation/scale/flip transform if needed
 fn render_shape_with_rotation<F>(element: &ElementLayout, builder: &mut SvgBuilder, render_fn: F)
 where
     F: FnOnce(&mut SvgBuilder),
 {
-    if let Some(rotation) = element.styles.rotation {
-        if rotation.abs() > f64::EPSILON {
-            let center = element.bounds.center();
-            let transform = format!("rotate({} {} {})", rotation, center.x, center.y);
-            builder.start_group_with_transform(None, &[], &transform);
-            render_fn(builder);
-            builder.end_group();
-        } else {
-            render_fn(builder);
-        }
+    if let Some(transform) = element_transform(&element.styles, element.bounds.center()) {
+        builder.start_group_with_transform(None, &[], &transform);
+        render_fn(builder);
+        builder.end_group();
     } else {
         render_fn(builder);
     }
@@ -914,14 +1663,167 @@ fn render_element(element: &ElementLayout, builder: &mut SvgBuilder) {
 }
 
 /// Render a single element to the builder with visibility checks for children
+///
+/// Elements with a `detail` level are wrapped in a `detail-<level>` group so
+/// breakpoint CSS can hide the shape together with its label.
 fn render_element_inner(element: &ElementLayout, builder: &mut SvgBuilder, hidden: &std::collections::HashSet<String>) {
+    let outer_span = std::mem::replace(&mut builder.source_span, element.span.clone());
+    let outer_geometry = builder
+        .geometry
+        .replace((element.id_str().map(str::to_string), element.bounds));
+    if let Some(level) = &element.styles.detail {
+        let class = format!("{}detail-{}", builder.prefix(), level);
+        builder.start_group(None, &[class]);
+        render_element_body(element, builder, hidden);
+        builder.end_group();
+    } else {
+        render_element_body(element, builder, hidden);
+    }
+    builder.source_span = outer_span;
+    builder.geometry = outer_geometry;
+}
+
+/// Accessible title and description of an element, if it has any
+///
+/// The title is the `tooltip` modifier, falling back to the label text when
+/// `label_tooltips` is enabled.
+fn element_title<'a>(
+    element: &'a ElementLayout,
+    config: &SvgConfig,
+) -> (Option<&'a str>, Option<&'a str>) {
+    let title = element.styles.tooltip.as_deref().or_else(|| {
+        config
+            .label_tooltips
+            .then(|| element.label.as_ref().map(|l| l.text.as_str()))
+            .flatten()
+    });
+    (title, element.styles.description.as_deref())
+}
+
+/// Render an element, wrapping titled shapes in a `<g role="img">` that
+/// carries their `<title>`/`<desc>` (and covers the label text too)
+fn render_element_body(
+    element: &ElementLayout,
+    builder: &mut SvgBuilder,
+    hidden: &std::collections::HashSet<String>,
+) {
+    let (title, description) = element_title(element, &builder.config);
+    let wrap = matches!(element.element_type, ElementType::Shape(_))
+        && (title.is_some() || description.is_some());
+    if wrap {
+        builder.start_titled_group(title, description);
+        render_element_content(element, builder, hidden);
+        builder.end_group();
+    } else {
+        render_element_content(element, builder, hidden);
+    }
+    render_port_markers(element, builder);
+    if element.styles.initial {
+        render_initial_marker(element, builder);
+    }
+}
+
+/// Radius of the dot drawn for `port ... [marker: true]`
+const PORT_MARKER_RADIUS: f64 = 3.0;
+
+/// Dots on the ports declared with `marker: true`, in the element's stroke color
+fn render_port_markers(element: &ElementLayout, builder: &mut SvgBuilder) {
+    let fill = element.styles.stroke.as_deref().unwrap_or("#333333");
+    let classes = [format!("{}port", builder.prefix())];
+    for port in element.anchors.ports().iter().filter(|p| p.marker) {
+        if let Some(anchor) = element.anchors.get(&port.name) {
+            builder.add_circle(
+                None,
+                anchor.position.x,
+                anchor.position.y,
+                PORT_MARKER_RADIUS,
+                &classes,
+                &format!(r#" fill="{}""#, escape_xml(fill)),
+            );
+        }
+    }
+}
+
+/// Gap between a `state [initial: true]` and the dot marking it
+const INITIAL_MARKER_LENGTH: f64 = 28.0;
+
+/// Radius of the initial-state dot
+const INITIAL_MARKER_RADIUS: f64 = 5.0;
+
+/// The dot and arrow marking a state machine's initial state, left of it
+fn render_initial_marker(element: &ElementLayout, builder: &mut SvgBuilder) {
+    let color = escape_xml(element.styles.stroke.as_deref().unwrap_or("#333333"));
+    let classes = [format!("{}initial", builder.prefix())];
+    let (x, y) = (element.bounds.x, element.bounds.center().y);
+    let dot_x = x - INITIAL_MARKER_LENGTH;
+    builder.add_circle(
+        None,
+        dot_x,
+        y,
+        INITIAL_MARKER_RADIUS,
+        &classes,
+        &format!(r#" fill="{}""#, color),
+    );
+    let stroke_width = 1.5;
+    let size = builder.arrow_size(stroke_width);
+    let marker = builder.add_arrow_marker(size);
+    // Pulled back like a connection end so the arrow tip meets the edge
+    let d = format!(
+        "M{} {} L{} {}",
+        dot_x + INITIAL_MARKER_RADIUS,
+        y,
+        x - 0.9 * size,
+        y
+    );
+    builder.add_path(
+        None,
+        &d,
+        &classes,
+        &format!(
+            r#" fill="none" stroke="{}" stroke-width="{}" marker-end="url(#{})""#,
+            color, stroke_width, marker
+        ),
+    );
+}
+
+fn render_element_content(
+    element: &ElementLayout,
+    builder: &mut SvgBuilder,
+    hidden: &std::collections::HashSet<String>,
+) {
+    // Rotated elements keep their rotated bounding box for layout; draw them
+    // at their own size and let the rotate transform do the rest
+    if let Some(bounds) = element.pre_transform_bounds() {
+        let unrotated = ElementLayout {
+            bounds,
+            pre_transform_size: None,
+            ..element.clone()
+        };
+        return render_element_content(&unrotated, builder, hidden);
+    }
     let id = element.id.as_ref().map(|i| i.0.as_str());
-    let styles = format_styles(&element.styles);
+    // Gradient fills reference a shared <linearGradient> definition
+    let gradient = element
+        .styles
+        .fill_gradient
+        .as_ref()
+        .and_then(|g| builder.add_fill_gradient(g));
+    let painted = match gradient {
+        Some(gradient) => ResolvedStyles {
+            fill: Some(format!("url(#{})", gradient)),
+            ..element.styles.clone()
+        },
+        None => element.styles.clone(),
+    };
+    let styles = format_styles(&painted);
     let classes = element.styles.css_classes.clone();
 
     match &element.element_type {
         ElementType::Shape(ShapeType::Rectangle) => {
             render_shape_with_rotation(element, builder, |b| {
+                if let Some(depth) = element.styles.depth.filter(|d| *d > 0.0) {
+                    b.add_extrusion(&element.bounds, depth, &element.styles);
+                }
                 b.add_rect(
                     id,
                     element.bounds.x,
@@ -929,10 +1831,91 @@ fn render_element_inner(element: &ElementLayout, builder: &mut SvgBuilder, hidde
                     element.bounds.width,
                     element.bounds.height,
                     &classes,
-                    &styles,
+                    &format!("{}{}", styles, format_corner_radius(&element.styles)),
                 );
             });
         }
+        ElementType::Shape(ShapeType::State) => {
+            let classes = std::iter::once(format!("{}state", builder.prefix()))
+                .chain(classes.iter().cloned())
+                .collect::<Vec<_>>();
+            render_shape_with_rotation(element, builder, |b| {
+                b.add_rect(
+                    id,
+                    element.bounds.x,
+                    element.bounds.y,
+                    element.bounds.width,
+                    element.bounds.height,
+                    &classes,
+                    &format!("{}{}", styles, format_corner_radius(&element.styles)),
+                );
+            });
+        }
+        ElementType::Shape(ShapeType::Class {
+            attributes,
+            operations,
+        }) => {
+            // The name goes in the element label; members are listed left
+            // aligned below dividers
+            let prefix = builder.prefix();
+            let box_classes = std::iter::once(format!("{}class", prefix))
+                .chain(classes.iter().cloned())
+                .collect::<Vec<_>>();
+            let divider_classes = [format!("{}class-divider", prefix)];
+            let member_classes = [format!("{}class-member", prefix)];
+            let divider_styles = format!(
+                r#" stroke="{}" stroke-width="{}""#,
+                escape_xml(element.styles.stroke.as_deref().unwrap_or("#333333")),
+                element.styles.stroke_width.unwrap_or(1.5)
+            );
+            let member_styles = format_font(&element.styles);
+            let rows = ClassCompartments::new(
+                attributes.len(),
+                operations.len(),
+                element.styles.font_size.unwrap_or(14.0),
+            );
+            let bounds = &element.bounds;
+            render_shape_with_rotation(element, builder, |b| {
+                b.add_rect(
+                    id,
+                    bounds.x,
+                    bounds.y,
+                    bounds.width,
+                    bounds.height,
+                    &box_classes,
+                    &format!("{}{}", styles, format_corner_radius(&element.styles)),
+                );
+                let mut top = bounds.y + rows.name;
+                for (members, height) in
+                    [(attributes, rows.attributes), (operations, rows.operations)]
+                {
+                    b.add_line(
+                        None,
+                        bounds.x,
+                        top,
+                        bounds.right(),
+                        top,
+                        &divider_classes,
+                        &divider_styles,
+                    );
+                    for (i, member) in members.iter().enumerate() {
+                        let y =
+                            top + ClassCompartments::PADDING + (i as f64 + 0.5) * rows.line_height;
+                        b.add_text_element(
+                            None,
+                            &member.node,
+                            bounds.x + ClassCompartments::INSET,
+                            y,
+                            &TextAnchor::Start,
+                            VerticalAlign::Middle,
+                            &member_classes,
+                            &member_styles,
+                        );
+                    }
+                    top += height;
+                }
+            });
+        }
         ElementType::Shape(ShapeType::Circle) => {
             let r = element.bounds.width.min(element.bounds.height) / 2.0;
             render_shape_with_rotation(element, builder, |b| {
@@ -985,7 +1968,50 @@ fn render_element_inner(element: &ElementLayout, builder: &mut SvgBuilder, hidde
                 );
             });
         }
-        ElementType::Shape(ShapeType::Icon { icon_name }) => {
+        ElementType::Shape(ShapeType::Bus) => {
+            // A solid rail, filled in its stroke color unless given a fill
+            let rail = ResolvedStyles {
+                fill: painted
+                    .fill
+                    .clone()
+                    .or_else(|| painted.stroke.clone())
+                    .or_else(|| Some("#333333".to_string())),
+                ..painted.clone()
+            };
+            let bus_classes = std::iter::once(format!("{}bus", builder.prefix()))
+                .chain(classes.iter().cloned())
+                .collect::<Vec<_>>();
+            render_shape_with_rotation(element, builder, |b| {
+                b.add_rect(
+                    id,
+                    element.bounds.x,
+                    element.bounds.y,
+                    element.bounds.width,
+                    element.bounds.height,
+                    &bus_classes,
+                    &format_styles(&rail),
+                );
+            });
+        }
+        ElementType::Shape(ShapeType::Icon {
+            svg: Some(icon), ..
+        }) => {
+            // Resolved icon: scale uniformly into the bounds, centered
+            let bounds = &element.bounds;
+            let scale = (bounds.width / icon.width).min(bounds.height / icon.height);
+            let x = bounds.x + (bounds.width - icon.width * scale) / 2.0;
+            let y = bounds.y + (bounds.height - icon.height * scale) / 2.0;
+            let transform = format!("translate({}, {}) scale({})", x, y, scale);
+            let icon_classes = std::iter::once(format!("{}icon", builder.prefix()))
+                .chain(classes.iter().cloned())
+                .collect::<Vec<_>>();
+            render_shape_with_rotation(element, builder, |b| {
+                b.start_group_with_transform(id, &icon_classes, &transform);
+                b.add_raw(&strip_svg_wrapper(&icon.content));
+                b.end_group();
+            });
+        }
+        ElementType::Shape(ShapeType::Icon { icon_name, .. }) => {
             // For icons, render a placeholder rect with the icon name as text
             render_shape_with_rotation(element, builder, |b| {
                 b.add_rect(
@@ -1003,32 +2029,77 @@ fn render_element_inner(element: &ElementLayout, builder: &mut SvgBuilder, hidde
                     element.bounds.x + element.bounds.width / 2.0,
                     element.bounds.y + element.bounds.height / 2.0,
                     &TextAnchor::Middle,
+                    VerticalAlign::Middle,
                     "",
                 );
             });
         }
+        ElementType::Shape(ShapeType::Custom { kind, .. }) => {
+            let prefix = builder.prefix();
+            let custom_classes = [format!("{}custom", prefix), format!("{}{}", prefix, kind)]
+                .into_iter()
+                .chain(classes.iter().cloned())
+                .collect::<Vec<_>>();
+            match builder.config.shape_renderers.get(kind).cloned() {
+                Some(renderer) => render_shape_with_rotation(element, builder, |b| {
+                    b.start_group(id, &custom_classes);
+                    b.add_raw(&renderer.render(&element.bounds, &element.styles));
+                    b.end_group();
+                }),
+                // Rendered without its renderer: a placeholder naming the shape
+                None => render_shape_with_rotation(element, builder, |b| {
+                    b.add_rect(
+                        id,
+                        element.bounds.x,
+                        element.bounds.y,
+                        element.bounds.width,
+                        element.bounds.height,
+                        &custom_classes,
+                        &styles,
+                    );
+                    b.add_text(
+                        kind,
+                        element.bounds.x + element.bounds.width / 2.0,
+                        element.bounds.y + element.bounds.height / 2.0,
+                        &TextAnchor::Middle,
+                        VerticalAlign::Middle,
+                        "",
+                    );
+                }),
+            }
+        }
         ElementType::Shape(ShapeType::Text { content }) => {
-            // Render text element as SVG text
-            // Position text at the center of bounds, vertically centered using dominant-baseline
-            let font_styles = element
-                .styles
-                .font_size
-                .map(|fs| format!(r#" font-size="{}""#, fs))
-                .unwrap_or_default();
+            // Render text element as SVG text, at the left edge and vertical
+            // center of its bounds unless `text_align`/`vertical_align` say otherwise
+            let font_styles = format_font(&element.styles);
             let fill_style = element
                 .styles
                 .fill
                 .as_ref()
-                .map(|f| format!(r#" fill="{}""#, f))
+                .map(|f| format!(r#" fill="{}""#, escape_xml(f)))
                 .unwrap_or_default();
             let combined_styles = format!("{}{}", font_styles, fill_style);
+            let bounds = &element.bounds;
+            let anchor = element.styles.text_align.unwrap_or(TextAnchor::Start);
+            let x = match anchor {
+                TextAnchor::Start => bounds.x,
+                TextAnchor::Middle => bounds.center().x,
+                TextAnchor::End => bounds.right(),
+            };
+            let baseline = element.styles.vertical_align.unwrap_or_default();
+            let y = match baseline {
+                VerticalAlign::Top => bounds.y,
+                VerticalAlign::Middle => bounds.center().y,
+                VerticalAlign::Bottom => bounds.bottom(),
+            };
             render_shape_with_rotation(element, builder, |b| {
                 b.add_text_element(
                     id,
                     content,
-                    element.bounds.x,
-                    element.bounds.y + element.bounds.height / 2.0,
-                    &TextAnchor::Start,
+                    x,
+                    y,
+                    &anchor,
+                    baseline,
                     &classes,
                     &combined_styles,
                 );
@@ -1055,26 +2126,18 @@ fn render_element_inner(element: &ElementLayout, builder: &mut SvgBuilder, hidde
 
             // Create group with transform for positioning, scaling, and optional rotation
             // SVG transforms apply right-to-left, so: rotate around center, then scale, then translate
-            let transform = if let Some(rotation) = element.styles.rotation {
-                if rotation.abs() > f64::EPSILON {
-                    let cx = intrinsic_width.unwrap_or(element.bounds.width) / 2.0;
-                    let cy = intrinsic_height.unwrap_or(element.bounds.height) / 2.0;
-                    format!(
-                        "translate({}, {}) scale({}, {}) rotate({} {} {})",
-                        element.bounds.x, element.bounds.y, scale_x, scale_y, rotation, cx, cy
-                    )
-                } else {
-                    format!(
-                        "translate({}, {}) scale({}, {})",
-                        element.bounds.x, element.bounds.y, scale_x, scale_y
-                    )
-                }
-            } else {
-                format!(
-                    "translate({}, {}) scale({}, {})",
-                    element.bounds.x, element.bounds.y, scale_x, scale_y
-                )
-            };
+            let mut transform = format!(
+                "translate({}, {}) scale({}, {})",
+                element.bounds.x, element.bounds.y, scale_x, scale_y
+            );
+            let center = Point::new(
+                intrinsic_width.unwrap_or(element.bounds.width) / 2.0,
+                intrinsic_height.unwrap_or(element.bounds.height) / 2.0,
+            );
+            if let Some(own) = element_transform(&element.styles, center) {
+                transform.push(' ');
+                transform.push_str(&own);
+            }
 
             builder.start_group_with_transform(id, &embed_classes, &transform);
 
@@ -1084,24 +2147,15 @@ fn render_element_inner(element: &ElementLayout, builder: &mut SvgBuilder, hidde
 
             builder.end_group();
         }
-        ElementType::Shape(ShapeType::RasterImage { path }) => {
+        ElementType::Shape(ShapeType::RasterImage { path, .. }) => {
             // Render raster image as SVG <image> element
             let prefix = builder.prefix();
             let image_classes = std::iter::once(format!("{}raster-image", prefix))
                 .chain(classes.iter().cloned())
                 .collect::<Vec<_>>();
 
-            // Apply rotation transform if specified
-            let transform = if let Some(rotation) = element.styles.rotation {
-                if rotation.abs() > f64::EPSILON {
-                    let center = element.bounds.center();
-                    Some(format!("rotate({} {} {})", rotation, center.x, center.y))
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
+            // Apply rotation/scale/flip transform if specified
+            let transform = element_transform(&element.styles, element.bounds.center());
 
             builder.add_image(
                 id,
@@ -1130,26 +2184,33 @@ fn render_element_inner(element: &ElementLayout, builder: &mut SvgBuilder, hidde
                 b.add_path(id, &d, &classes, &styles);
             });
         }
+        ElementType::Layer(name) => {
+            let prefix = builder.prefix();
+            builder.start_layer_group(&format!("{}layer-{}", prefix, name), element.styles.opacity);
+            for child in ordered_elements(&element.children) {
+                render_element_with_visibility(child, builder, hidden);
+            }
+            builder.end_group();
+        }
         ElementType::Layout(_) | ElementType::Group => {
             // Start a group for containers (with optional rotation)
             let prefix = builder.prefix();
             let container_classes = std::iter::once(format!("{}container", prefix))
                 .chain(classes.iter().cloned())
                 .collect::<Vec<_>>();
-            if let Some(rotation) = element.styles.rotation {
-                if rotation.abs() > f64::EPSILON {
-                    let center = element.bounds.center();
-                    let transform = format!("rotate({} {} {})", rotation, center.x, center.y);
-                    builder.start_group_with_transform(id, &container_classes, &transform);
-                } else {
-                    builder.start_group(id, &container_classes);
-                }
-            } else {
-                builder.start_group(id, &container_classes);
+            let transform =
+                element_transform(&element.styles, element.bounds.center()).unwrap_or_default();
+            // Screen readers announce the group by its label, falling back to its name
+            let aria_label = element.label.as_ref().map(|l| l.text.as_str()).or(id);
+            builder.start_container_group(id, &container_classes, &transform, aria_label);
+            let (title, description) = element_title(element, &builder.config);
+            builder.add_title(title, description);
+            if let Some(background) = format_container_background(&painted) {
+                builder.add_container_background(&element.bounds, &background);
             }
 
             // Render children (with visibility checks for keyframe animations)
-            for child in &element.children {
+            for child in ordered_elements(&element.children) {
                 render_element_with_visibility(child, builder, hidden);
             }
 
@@ -1157,35 +2218,58 @@ fn render_element_inner(element: &ElementLayout, builder: &mut SvgBuilder, hidde
         }
     }
 
+    // Trusted raw SVG snippet, positioned relative to the element's top-left corner
+    if let Some(raw) = &element.styles.raw_svg {
+        let prefix = builder.prefix();
+        let transform = format!("translate({}, {})", element.bounds.x, element.bounds.y);
+        builder.start_group_with_transform(None, &[format!("{}raw-svg", prefix)], &transform);
+        builder.add_raw(raw);
+        builder.end_group();
+    }
+
     // Render label if present
     if let Some(label) = &element.label {
-        let font_styles = element
-            .styles
-            .font_size
-            .map(|fs| format!(r#" font-size="{}""#, fs))
-            .unwrap_or_default();
+        let font_styles = format_font(&element.styles);
         builder.add_text(
             &label.text,
             label.position.x,
             label.position.y,
             &label.anchor,
+            label.vertical_align,
             &font_styles,
         );
     }
 }
 
 /// Render a connection to the builder
-fn render_connection(conn: &ConnectionLayout, builder: &mut SvgBuilder) {
+fn render_connection(conn: &ConnectionLayout, builder: &mut SvgBuilder, jumps: &[Crossing]) {
+    let outer_span = std::mem::replace(&mut builder.source_span, conn.span.clone());
     let mut classes = conn.styles.css_classes.clone();
     // Add connection name as CSS class for keyframe targeting (Feature 011)
     if let Some(name) = &conn.name {
         classes.push(format!("conn-{}", name.0));
     }
-    let styles = format_connection_styles(&conn.styles);
+    let mut styles = match builder.add_stroke_gradient(&conn.path, &conn.styles) {
+        Some(gradient) => format_connection_styles(&ResolvedStyles {
+            stroke: Some(format!("url(#{})", gradient)),
+            ..conn.styles.clone()
+        }),
+        None => format_connection_styles(&conn.styles),
+    };
 
     // Get stroke width for arrow pullback calculation (default: 2.0)
     let stroke_width = conn.styles.stroke_width.unwrap_or(2.0);
 
+    // Curves only have vertices at their segment joins, so mid markers are
+    // left to polylines
+    if conn.routing_mode != RoutingMode::Curved {
+        if let Some(kind) = conn.styles.marker_mid.as_deref() {
+            if let Some(id) = builder.add_mid_marker(kind, stroke_width) {
+                styles.push_str(&format!(r#" marker-mid="url(#{id})""#));
+            }
+        }
+    }
+
     let marker_end = matches!(
         conn.direction,
         ConnectionDirection::Forward | ConnectionDirection::Bidirectional
@@ -1198,8 +2282,14 @@ fn render_connection(conn: &ConnectionLayout, builder: &mut SvgBuilder) {
         &styles,
         marker_end,
         stroke_width,
+        jumps,
     );
 
+    if let (Some(seq), Some(position)) = (&conn.seq, conn.seq_position()) {
+        let color = conn.styles.stroke.as_deref().unwrap_or("#333");
+        builder.add_seq_marker(position, seq.number, color);
+    }
+
     // Render connection label if present
     if let Some(label) = &conn.label {
         // Use label's own styles if available (from referenced element),
@@ -1214,16 +2304,18 @@ fn render_connection(conn: &ConnectionLayout, builder: &mut SvgBuilder) {
             label.position.x,
             label.position.y,
             &label.anchor,
+            label.vertical_align,
             &label_styles,
         );
     }
+    builder.source_span = outer_span;
 }
 
 /// Format connection styles (stroke-focused, no fill)
 fn format_connection_styles(styles: &ResolvedStyles) -> String {
     let mut parts = vec![];
     if let Some(stroke) = &styles.stroke {
-        parts.push(format!(r#" stroke="{}""#, stroke));
+        parts.push(format!(r#" stroke="{}""#, escape_xml(stroke)));
     } else {
         parts.push(r##" stroke="#333""##.to_string());
     }
@@ -1233,8 +2325,9 @@ fn format_connection_styles(styles: &ResolvedStyles) -> String {
         parts.push(r#" stroke-width="2""#.to_string());
     }
     if let Some(dash) = &styles.stroke_dasharray {
-        parts.push(format!(r#" stroke-dasharray="{}""#, dash));
+        parts.push(format!(r#" stroke-dasharray="{}""#, escape_xml(dash)));
     }
+    parts.push(format_line_ends(styles));
     if let Some(opacity) = styles.opacity {
         if (opacity - 1.0).abs() > f64::EPSILON {
             parts.push(format!(r#" opacity="{}""#, opacity));
@@ -1243,15 +2336,30 @@ fn format_connection_styles(styles: &ResolvedStyles) -> String {
     parts.join("")
 }
 
-/// Format text styles (fill and font_size for labels)
+/// Format font attributes (font_size and font_family) with leading spaces
+fn format_font(styles: &ResolvedStyles) -> String {
+    let mut attrs = String::new();
+    if let Some(font_size) = styles.font_size {
+        attrs.push_str(&format!(r#" font-size="{}""#, font_size));
+    }
+    if let Some(family) = &styles.font_family {
+        attrs.push_str(&format!(r#" font-family="{}""#, escape_xml(family)));
+    }
+    attrs
+}
+
+/// Format text styles (fill, font_size and font_family for labels)
 fn format_text_styles(styles: &ResolvedStyles) -> String {
     let mut parts = vec![];
     if let Some(fill) = &styles.fill {
-        parts.push(format!(r#"fill="{}""#, fill));
+        parts.push(format!(r#"fill="{}""#, escape_xml(fill)));
     }
     if let Some(font_size) = styles.font_size {
         parts.push(format!(r#"font-size="{}""#, font_size));
     }
+    if let Some(family) = &styles.font_family {
+        parts.push(format!(r#"font-family="{}""#, escape_xml(family)));
+    }
     if !parts.is_empty() {
         // Add leading space so it can be appended to existing attributes
         format!(" {}", parts.join(" "))
@@ -1267,18 +2375,19 @@ fn format_styles(styles: &ResolvedStyles) -> String {
 
     // Default fill: light gray for visibility
     let fill = styles.fill.as_deref().unwrap_or("#f0f0f0");
-    parts.push(format!(r#" fill="{}""#, fill));
+    parts.push(format!(r#" fill="{}""#, escape_xml(fill)));
 
     // Default stroke: dark gray
     let stroke = styles.stroke.as_deref().unwrap_or("#333333");
-    parts.push(format!(r#" stroke="{}""#, stroke));
+    parts.push(format!(r#" stroke="{}""#, escape_xml(stroke)));
 
     // Default stroke-width: 1.5
     let sw = styles.stroke_width.unwrap_or(1.5);
     parts.push(format!(r#" stroke-width="{}""#, sw));
     if let Some(dash) = &styles.stroke_dasharray {
-        parts.push(format!(r#" stroke-dasharray="{}""#, dash));
+        parts.push(format!(r#" stroke-dasharray="{}""#, escape_xml(dash)));
     }
+    parts.push(format_line_ends(styles));
     if let Some(op) = styles.opacity {
         if op < 1.0 {
             parts.push(format!(r#" opacity="{}""#, op));
@@ -1287,6 +2396,95 @@ fn format_styles(styles: &ResolvedStyles) -> String {
     parts.join("")
 }
 
+/// Format `stroke-linecap`/`stroke-linejoin` (empty without `line_cap`/`line_join`)
+fn format_line_ends(styles: &ResolvedStyles) -> String {
+    let mut attrs = String::new();
+    if let Some(cap) = &styles.line_cap {
+        attrs.push_str(&format!(r#" stroke-linecap="{}""#, escape_xml(cap)));
+    }
+    if let Some(join) = &styles.line_join {
+        attrs.push_str(&format!(r#" stroke-linejoin="{}""#, escape_xml(join)));
+    }
+    attrs
+}
+
+/// Format `rx`/`ry` attributes for a rounded rect (empty without `corner_radius`)
+fn format_corner_radius(styles: &ResolvedStyles) -> String {
+    styles
+        .corner_radius
+        .map(|r| format!(r#" rx="{}" ry="{}""#, r, r))
+        .unwrap_or_default()
+}
+
+/// Format the background rect styles of a container.
+///
+/// Containers are invisible unless they have a `fill`, `stroke` or
+/// `corner_radius`. Only the given paint is drawn (a stroke-only container
+/// gets no fill); a container with just a corner radius uses the shape defaults.
+fn format_container_background(styles: &ResolvedStyles) -> Option<String> {
+    if styles.fill.is_none() && styles.stroke.is_none() && styles.corner_radius.is_none() {
+        return None;
+    }
+    let mut background = styles.clone();
+    if styles.fill.is_some() || styles.stroke.is_some() {
+        background.fill.get_or_insert_with(|| "none".to_string());
+        background.stroke.get_or_insert_with(|| "none".to_string());
+    }
+    Some(format!(
+        "{}{}",
+        format_styles(&background),
+        format_corner_radius(styles)
+    ))
+}
+
+/// Scale the channels of a `#rgb`/`#rrggbb` color by `factor` (None for other colors)
+fn shade_hex(color: &str, factor: f64) -> Option<String> {
+    let hex = color.strip_prefix('#')?;
+    let expanded: String = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 => hex.to_string(),
+        _ => return None,
+    };
+    let mut shaded = String::from("#");
+    for i in 0..3 {
+        let channel = u8::from_str_radix(expanded.get(i * 2..i * 2 + 2)?, 16).ok()?;
+        let scaled = (channel as f64 * factor).round().clamp(0.0, 255.0) as u8;
+        shaded.push_str(&format!("{:02x}", scaled));
+    }
+    Some(shaded)
+}
+
+/// Presentation attributes that `StyleMode::Classes` moves into CSS rules
+const CSS_PAINT_PROPERTIES: &[&str] = &[
+    "fill",
+    "fill-opacity",
+    "stroke",
+    "stroke-width",
+    "stroke-dasharray",
+    "stroke-linecap",
+    "stroke-linejoin",
+    "stroke-opacity",
+    "opacity",
+    "font-size",
+    "font-family",
+];
+
+/// Split a ` name="value"` attribute string into pairs
+fn split_attributes(attrs: &str) -> Vec<(&str, &str)> {
+    let mut pairs = vec![];
+    let mut rest = attrs;
+    while let Some(eq) = rest.find("=\"") {
+        let name = rest[..eq].trim();
+        let value_start = eq + 2;
+        let Some(len) = rest[value_start..].find('"') else {
+            break;
+        };
+        pairs.push((name, &rest[value_start..value_start + len]));
+        rest = &rest[value_start + len + 1..];
+    }
+    pairs
+}
+
 /// Convert a path of points to an SVG path d attribute
 fn path_to_d(path: &[Point]) -> String {
     if path.is_empty() {
@@ -1300,7 +2498,15 @@ fn path_to_d(path: &[Point]) -> String {
     d
 }
 
+/// Format an optional `id` attribute (with leading space)
+fn id_attr(id: Option<&str>) -> String {
+    id.map(|i| format!(r#" id="{}""#, escape_xml(i)))
+        .unwrap_or_default()
+}
+
 /// Escape special XML characters
+///
+/// Safe for both text content and double- or single-quoted attribute values.
 fn escape_xml(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
@@ -1348,8 +2554,9 @@ fn strip_svg_wrapper(svg: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::layout::{AnchorSet, ElementType, LayoutResult, ResolvedStyles};
+    use crate::layout::{AnchorSet, ElementType, LayoutResult, Padding, ResolvedStyles};
     use crate::parser::ast::{Identifier, LayoutType};
+    use crate::renderer::Fit;
 
     #[test]
     fn test_path_to_d() {
@@ -1362,6 +2569,14 @@ mod tests {
         assert_eq!(d, "M0 0 L100 0 L100 100");
     }
 
+    #[test]
+    fn test_shade_hex() {
+        assert_eq!(shade_hex("#4080c0", 0.85).as_deref(), Some("#366da3"));
+        assert_eq!(shade_hex("#fff", 0.5).as_deref(), Some("#808080"));
+        assert_eq!(shade_hex("steelblue", 0.5), None);
+        assert_eq!(shade_hex("var(--accent-1)", 0.5), None);
+    }
+
     #[test]
     fn test_escape_xml() {
         assert_eq!(escape_xml("a < b"), "a &lt; b");
@@ -1369,6 +2584,67 @@ mod tests {
         assert_eq!(escape_xml("<tag>"), "&lt;tag&gt;");
     }
 
+    #[test]
+    fn test_escape_xml_attribute_quotes() {
+        assert_eq!(
+            escape_xml(r#"x" onload="alert(1)"#),
+            "x&quot; onload=&quot;alert(1)"
+        );
+        assert_eq!(escape_xml("it's"), "it&apos;s");
+    }
+
+    #[test]
+    fn test_user_strings_escaped_in_attributes() {
+        let mut builder = SvgBuilder::new(SvgConfig::default());
+        builder.add_rect(
+            Some("a"),
+            0.0,
+            0.0,
+            10.0,
+            10.0,
+            &[r#"x" onclick="evil()"#.to_string()],
+            &format_styles(&ResolvedStyles {
+                stroke_dasharray: Some(r#"1" onmouseover="evil()"#.to_string()),
+                ..Default::default()
+            }),
+        );
+        builder.add_image(
+            None,
+            r#"a.png" onerror="evil()"#,
+            0.0,
+            0.0,
+            10.0,
+            10.0,
+            &[],
+            None,
+        );
+        let svg = builder.build(BoundingBox::new(0.0, 0.0, 10.0, 10.0));
+        assert!(!svg.contains(r#"" onclick=""#));
+        assert!(!svg.contains(r#"" onmouseover=""#));
+        assert!(!svg.contains(r#"" onerror=""#));
+        assert!(svg.contains("&quot; onclick=&quot;evil()"));
+    }
+
+    #[test]
+    fn test_arrowhead_scales_with_stroke_width() {
+        let mut builder = SvgBuilder::new(SvgConfig::default());
+        let path = [Point::new(0.0, 0.0), Point::new(100.0, 0.0)];
+        for width in [1.0, 2.0, 2.0, 10.0] {
+            builder.add_connection_path(&path, RoutingMode::Direct, &[], "", true, width, &[]);
+        }
+        let svg = builder.build(BoundingBox::new(0.0, 0.0, 100.0, 10.0));
+
+        // One marker per distinct size, clamped to the configured limits
+        assert_eq!(svg.matches("<marker ").count(), 3);
+        assert!(svg
+            .contains(r#"id="ai-arrow-6" viewBox="0 0 10 10" refX="1" refY="5" markerWidth="6""#));
+        assert!(svg.contains(r#"id="ai-arrow-8""#));
+        assert!(svg.contains(r#"id="ai-arrow-24""#));
+        // The path is pulled back by the visible arrow length
+        assert!(svg.contains(r#"d="M0 0 L92.8 0""#));
+        assert!(svg.contains(r#"marker-end="url(#ai-arrow-24)""#));
+    }
+
     #[test]
     fn test_format_styles() {
         let styles = ResolvedStyles {
@@ -1376,10 +2652,30 @@ mod tests {
             stroke: Some("#000000".to_string()),
             stroke_width: Some(2.0),
             stroke_dasharray: Some("4,2".to_string()),
+            line_cap: None,
+            line_join: None,
+            marker_mid: None,
             opacity: Some(0.5),
             font_size: None,
+            font_family: None,
             css_classes: vec![],
             rotation: None,
+            scale: None,
+            flip: None,
+            raw_svg: None,
+            detail: None,
+            corner_radius: None,
+            stroke_fade: None,
+            opacity_start: None,
+            opacity_end: None,
+            depth: None,
+            fill_gradient: None,
+            tooltip: None,
+            description: None,
+            initial: false,
+            pinned: false,
+            text_align: None,
+            vertical_align: None,
         };
         let result = format_styles(&styles);
         assert!(result.contains(r##"fill="#ff0000""##));
@@ -1402,6 +2698,9 @@ mod tests {
             anchors: AnchorSet::default(),
             path_normalize: true,
             z_order: 0,
+            padding: Padding::default(),
+            span: None,
+            pre_transform_size: None,
         });
         result.compute_bounds();
 
@@ -1414,6 +2713,46 @@ mod tests {
         assert!(svg.contains("ai-rect"));
     }
 
+    #[test]
+    fn test_render_output_size() {
+        let mut result = LayoutResult::new();
+        result.add_element(ElementLayout {
+            id: Some(Identifier::new("box")),
+            element_type: ElementType::Shape(ShapeType::Rectangle),
+            bounds: BoundingBox::new(0.0, 0.0, 100.0, 50.0),
+            styles: ResolvedStyles::default(),
+            children: vec![],
+            label: None,
+            anchors: AnchorSet::default(),
+            path_normalize: true,
+            z_order: 0,
+            padding: Padding::default(),
+            span: None,
+            pre_transform_size: None,
+        });
+        result.compute_bounds();
+        let config = SvgConfig::default().with_viewbox_padding(0.0);
+
+        let svg = render_svg(&result, &config);
+        assert!(svg.contains(r#"viewBox="0 0 100 50">"#), "{}", svg);
+
+        let svg = render_svg(&result, &config.clone().with_scale(2.0));
+        assert!(svg.contains(r#"viewBox="0 0 100 50" width="200" height="100">"#));
+
+        let svg = render_svg(&result, &config.clone().with_unit(Unit::In));
+        assert!(
+            svg.contains(r#"width="1.04in" height="0.52in">"#),
+            "{}",
+            svg
+        );
+
+        let svg = render_svg(
+            &result,
+            &config.with_size(800.0, 600.0).with_fit(Fit::Cover),
+        );
+        assert!(svg.contains(r#"width="800" height="600" preserveAspectRatio="xMidYMid slice""#));
+    }
+
     #[test]
     fn test_render_with_connection() {
         let mut result = LayoutResult::new();
@@ -1427,6 +2766,9 @@ mod tests {
             anchors: AnchorSet::default(),
             path_normalize: true,
             z_order: 0,
+            padding: Padding::default(),
+            span: None,
+            pre_transform_size: None,
         });
         result.add_element(ElementLayout {
             id: Some(Identifier::new("b")),
@@ -1438,6 +2780,9 @@ mod tests {
             anchors: AnchorSet::default(),
             path_normalize: true,
             z_order: 0,
+            padding: Padding::default(),
+            span: None,
+            pre_transform_size: None,
         });
         result.connections.push(ConnectionLayout {
             from_id: Identifier::new("a"),
@@ -1447,7 +2792,10 @@ mod tests {
             styles: ResolvedStyles::default(),
             label: None,
             routing_mode: RoutingMode::default(),
+            crossings: None,
             name: None,
+            seq: None,
+            span: None,
         });
         result.compute_bounds();
 
@@ -1460,6 +2808,38 @@ mod tests {
         assert!(svg.contains("marker-end"));
     }
 
+    #[test]
+    fn test_render_svg_page_continuation() {
+        let mut result = LayoutResult::new();
+        result.connections.push(ConnectionLayout {
+            from_id: Identifier::new("a"),
+            to_id: Identifier::new("b"),
+            direction: ConnectionDirection::Forward,
+            path: vec![Point::new(25.0, 25.0), Point::new(175.0, 25.0)],
+            styles: ResolvedStyles::default(),
+            label: None,
+            routing_mode: RoutingMode::default(),
+            crossings: None,
+            name: None,
+            seq: None,
+            span: None,
+        });
+        result.bounds = BoundingBox::new(0.0, 0.0, 200.0, 50.0);
+
+        let pages = crate::layout::paginate(&result, crate::layout::PageSpec::new(100.0, 50.0));
+        assert_eq!(pages.len(), 2);
+
+        let svg = render_svg_page(
+            &result,
+            &pages[0],
+            &SvgConfig::default(),
+            &Stylesheet::default(),
+        );
+        assert!(svg.contains(r#"viewBox="0 0 100 50""#));
+        assert!(svg.contains(r#"class="ai-continuation" cx="100" cy="25""#));
+        assert!(svg.contains("\u{2192} p2"));
+    }
+
     #[test]
     fn test_render_nested_layout() {
         let mut result = LayoutResult::new();
@@ -1479,6 +2859,9 @@ mod tests {
                     anchors: AnchorSet::default(),
                     path_normalize: true,
                     z_order: 0,
+                    padding: Padding::default(),
+                    span: None,
+                    pre_transform_size: None,
                 },
                 ElementLayout {
                     id: Some(Identifier::new("b")),
@@ -1490,12 +2873,18 @@ mod tests {
                     anchors: AnchorSet::default(),
                     path_normalize: true,
                     z_order: 0,
+                    padding: Padding::default(),
+                    span: None,
+                    pre_transform_size: None,
                 },
             ],
             label: None,
             anchors: AnchorSet::default(),
             path_normalize: true,
             z_order: 0,
+            padding: Padding::default(),
+            span: None,
+            pre_transform_size: None,
         });
         result.compute_bounds();
 
@@ -0,0 +1,567 @@
+//! Scoped element names: `group a { rect x }` next to `group b { rect x }`
+//!
+//! Named groups, layouts and template instances are scopes. A name declared
+//! in more than one scope gets a unique id made of its scopes' names, the way
+//! template instances prefix their children (`a_x`, `b_x`); a name declared
+//! once keeps its id. References resolve through the scope chain of the
+//! statement they are written in: `x` is the nearest `x` visible from there,
+//! `a.x` the `x` inside `a`.
+//!
+//! Template bodies are left alone: their names are prefixed per instance.
+//! A path into a template instance (`motor1.terminal_a`) only has its
+//! instance resolved here; the rest is resolved once the template is
+//! expanded.
+
+use std::collections::HashMap;
+
+use crate::layout::LayoutError;
+use crate::parser::ast::*;
+use crate::parser::visit::{walk_statement, walk_statement_mut, Visitor, VisitorMut};
+
+/// Give every element a unique id and point references at the ids
+pub(crate) fn resolve_scopes(doc: &mut Document) -> Result<(), LayoutError> {
+    let mut collector = Collector::default();
+    collector.visit_document(doc);
+    let scopes = Scopes::new(collector.declarations)?;
+    let mut resolver = Resolver {
+        scopes: &scopes,
+        scope: vec![],
+        error: None,
+    };
+    resolver.visit_document(doc);
+    match resolver.error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// A declared element name with the scopes around it, outermost first
+#[derive(Debug)]
+struct Declaration {
+    scope: Vec<String>,
+    name: String,
+    /// Declared by a template instance, whose children are not known yet
+    instance: bool,
+}
+
+impl Declaration {
+    fn path(&self) -> Vec<&str> {
+        self.scope
+            .iter()
+            .map(String::as_str)
+            .chain(std::iter::once(self.name.as_str()))
+            .collect()
+    }
+
+    /// Whether `path` names this declaration: it ends the declaration's path
+    fn matches(&self, path: &[&str]) -> bool {
+        self.path().ends_with(path)
+    }
+}
+
+/// The element name a statement declares
+pub(crate) fn declared_name(stmt: &Statement) -> Option<&Spanned<Identifier>> {
+    match stmt {
+        Statement::Shape(s) => match &s.shape_type.node {
+            ShapeType::Path(path) => path.name.as_ref(),
+            _ => s.name.as_ref(),
+        },
+        Statement::Layout(l) => l.name.as_ref(),
+        Statement::Group(g) => g.name.as_ref(),
+        Statement::TemplateInstance(i) => Some(&i.instance_name),
+        Statement::Embed(e) => Some(&e.name),
+        _ => None,
+    }
+}
+
+fn declared_name_mut(stmt: &mut Statement) -> Option<&mut Spanned<Identifier>> {
+    match stmt {
+        Statement::Shape(s) => match &mut s.shape_type.node {
+            ShapeType::Path(path) => path.name.as_mut(),
+            _ => s.name.as_mut(),
+        },
+        Statement::Layout(l) => l.name.as_mut(),
+        Statement::Group(g) => g.name.as_mut(),
+        Statement::TemplateInstance(i) => Some(&mut i.instance_name),
+        Statement::Embed(e) => Some(&mut e.name),
+        _ => None,
+    }
+}
+
+/// The scope a statement opens for its children
+fn scope_name(stmt: &Statement) -> Option<String> {
+    match stmt {
+        Statement::Layout(_) | Statement::Group(_) | Statement::TemplateInstance(_) => {
+            declared_name(stmt).map(|name| name.node.0.clone())
+        }
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+struct Collector {
+    scope: Vec<String>,
+    declarations: Vec<Declaration>,
+}
+
+impl Visitor for Collector {
+    fn visit_statement(&mut self, stmt: &Statement) {
+        if matches!(stmt, Statement::TemplateDecl(_)) {
+            return;
+        }
+        if let Some(name) = declared_name(stmt) {
+            self.declarations.push(Declaration {
+                scope: self.scope.clone(),
+                name: name.node.0.clone(),
+                instance: matches!(stmt, Statement::TemplateInstance(_)),
+            });
+        }
+        let opened = scope_name(stmt);
+        if let Some(scope) = &opened {
+            self.scope.push(scope.clone());
+        }
+        walk_statement(self, stmt);
+        if opened.is_some() {
+            self.scope.pop();
+        }
+    }
+}
+
+/// Declarations and the ids they get
+struct Scopes {
+    declarations: Vec<Declaration>,
+    ids: Vec<String>,
+}
+
+impl Scopes {
+    fn new(declarations: Vec<Declaration>) -> Result<Self, LayoutError> {
+        let mut paths: HashMap<&str, Vec<Vec<&str>>> = HashMap::new();
+        for decl in &declarations {
+            let entry = paths.entry(decl.name.as_str()).or_default();
+            let path = decl.path();
+            if !entry.contains(&path) {
+                entry.push(path);
+            }
+        }
+        let ids: Vec<String> = declarations
+            .iter()
+            .map(|decl| {
+                if paths[decl.name.as_str()].len() > 1 {
+                    decl.path().join("_")
+                } else {
+                    decl.name.clone()
+                }
+            })
+            .collect();
+
+        let mut owners: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (decl, id) in declarations.iter().zip(&ids) {
+            let path = decl.path();
+            match owners.get(id.as_str()) {
+                Some(owner) if *owner != path => {
+                    return Err(LayoutError::validation_error(format!(
+                        "'{}' and '{}' would both get the id '{}'; rename one of them",
+                        owner.join("."),
+                        path.join("."),
+                        id
+                    )));
+                }
+                _ => {
+                    owners.insert(id, path);
+                }
+            }
+        }
+        Ok(Self { declarations, ids })
+    }
+
+    /// Id of the declaration of `name` directly in `scope`
+    fn id(&self, scope: &[String], name: &str) -> Option<&str> {
+        self.declarations
+            .iter()
+            .position(|d| d.scope == scope && d.name == name)
+            .map(|i| self.ids[i].as_str())
+    }
+
+    /// Id of the element `path` names from inside `scope`, searching the
+    /// scope chain outwards; the least nested match in the innermost scope
+    /// that has one wins
+    fn resolve(
+        &self,
+        path: &[&str],
+        scope: &[String],
+        span: &Span,
+    ) -> Result<Option<&str>, LayoutError> {
+        Ok(self.find(path, scope, span)?.map(|i| self.ids[i].as_str()))
+    }
+
+    /// The longest proper prefix of `path` that names a template instance,
+    /// with the instance's id
+    fn instance_prefix(
+        &self,
+        path: &[&str],
+        scope: &[String],
+        span: &Span,
+    ) -> Result<Option<(usize, &str)>, LayoutError> {
+        for len in (1..path.len()).rev() {
+            if let Some(i) = self.find(&path[..len], scope, span)? {
+                let found = self.declarations[i].instance;
+                return Ok(found.then(|| (len, self.ids[i].as_str())));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Index of the declaration `path` names from inside `scope`
+    fn find(
+        &self,
+        path: &[&str],
+        scope: &[String],
+        span: &Span,
+    ) -> Result<Option<usize>, LayoutError> {
+        for depth in (0..=scope.len()).rev() {
+            let within = &scope[..depth];
+            let candidates: Vec<usize> = (0..self.declarations.len())
+                .filter(|&i| {
+                    let decl = &self.declarations[i];
+                    decl.scope.starts_with(within) && decl.matches(path)
+                })
+                .collect();
+            let Some(nearest) = candidates
+                .iter()
+                .map(|&i| self.declarations[i].scope.len())
+                .min()
+            else {
+                continue;
+            };
+            let mut found: Vec<usize> = candidates
+                .into_iter()
+                .filter(|&i| self.declarations[i].scope.len() == nearest)
+                .collect();
+            // Redeclarations in the same scope share an id
+            found.dedup_by(|a, b| self.ids[*a] == self.ids[*b]);
+            return match found.as_slice() {
+                [i] => Ok(Some(*i)),
+                _ => Err(LayoutError::ambiguous(
+                    path.join("."),
+                    span.clone(),
+                    found
+                        .iter()
+                        .map(|&i| self.declarations[i].path().join("."))
+                        .collect(),
+                )),
+            };
+        }
+        Ok(None)
+    }
+}
+
+struct Resolver<'a> {
+    scopes: &'a Scopes,
+    /// Scopes around the statement being resolved, outermost first
+    scope: Vec<String>,
+    error: Option<LayoutError>,
+}
+
+impl Resolver<'_> {
+    /// A plain element name
+    fn name(&self, id: &mut Spanned<Identifier>) -> Result<(), LayoutError> {
+        if let Some(resolved) = self
+            .scopes
+            .resolve(&[id.node.as_str()], &self.scope, &id.span)?
+        {
+            id.node = Identifier::new(resolved);
+        }
+        Ok(())
+    }
+
+    /// A constraint's element path (`a.x` in `a.x.left`)
+    fn element_path(&self, path: &mut Spanned<ElementPath>) -> Result<(), LayoutError> {
+        let segments: Vec<&str> = path.node.segments.iter().map(|s| s.node.as_str()).collect();
+        match self.scopes.resolve(&segments, &self.scope, &path.span)? {
+            Some(resolved) => {
+                let span = path.span.clone();
+                path.node = ElementPath::simple(Identifier::new(resolved), span);
+            }
+            None if segments.len() > 1 => {
+                let Some((len, instance)) =
+                    self.scopes
+                        .instance_prefix(&segments, &self.scope, &path.span)?
+                else {
+                    return Err(LayoutError::path_not_found(
+                        segments.join("."),
+                        path.span.clone(),
+                        vec![],
+                    ));
+                };
+                let segments = &mut path.node.segments;
+                let span = segments[0].span.start..segments[len - 1].span.end;
+                segments.splice(..len, [Spanned::new(Identifier::new(instance), span)]);
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// A connection or note endpoint; in `a.x` the `x` is a child of `a`
+    /// when `a` declares one, an anchor otherwise
+    fn endpoint(&self, reference: &mut AnchorReference) -> Result<(), LayoutError> {
+        let start = reference
+            .scope
+            .first()
+            .unwrap_or(&reference.element)
+            .span
+            .start;
+        let mut path: Vec<String> = reference
+            .scope
+            .iter()
+            .chain(std::iter::once(&reference.element))
+            .map(|s| s.node.0.clone())
+            .collect();
+        if let Some(anchor) = &reference.anchor {
+            path.push(anchor.node.clone());
+            let segments: Vec<&str> = path.iter().map(String::as_str).collect();
+            let span = start..anchor.span.end;
+            if let Some(child) = self.scopes.resolve(&segments, &self.scope, &span)? {
+                reference.element = Spanned::new(Identifier::new(child), span);
+                reference.scope.clear();
+                reference.anchor = None;
+                return Ok(());
+            }
+            path.pop();
+        }
+
+        let segments: Vec<&str> = path.iter().map(String::as_str).collect();
+        let span = start..reference.element.span.end;
+        match self.scopes.resolve(&segments, &self.scope, &span)? {
+            Some(resolved) => {
+                reference.element = Spanned::new(Identifier::new(resolved), span);
+                reference.scope.clear();
+            }
+            None if !reference.scope.is_empty() => {
+                if let Some((len, instance)) =
+                    self.scopes.instance_prefix(&segments, &self.scope, &span)?
+                {
+                    let span = start..reference.scope[len - 1].span.end;
+                    reference
+                        .scope
+                        .splice(..len, [Spanned::new(Identifier::new(instance), span)]);
+                    return Ok(());
+                }
+                let written = match &reference.anchor {
+                    Some(anchor) => format!("{}.{}", segments.join("."), anchor.node),
+                    None => segments.join("."),
+                };
+                return Err(LayoutError::path_not_found(written, span, vec![]));
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn property(&self, prop: &mut PropertyRef) -> Result<(), LayoutError> {
+        self.element_path(&mut prop.element)
+    }
+
+    fn references(&self, stmt: &mut Statement) -> Result<(), LayoutError> {
+        match stmt {
+            Statement::Connection(conns) => {
+                for conn in conns {
+                    self.endpoint(&mut conn.from)?;
+                    self.endpoint(&mut conn.to)?;
+                }
+            }
+            Statement::Note(n) => self.endpoint(&mut n.target)?,
+            Statement::Constraint(c) => {
+                self.name(&mut c.subject)?;
+                if let Some(anchor) = &mut c.anchor {
+                    self.name(anchor)?;
+                }
+            }
+            Statement::Constrain(c) => match &mut c.expr {
+                ConstraintExpr::Equal { left, right }
+                | ConstraintExpr::EqualWithOffset { left, right, .. } => {
+                    self.property(left)?;
+                    self.property(right)?;
+                }
+                ConstraintExpr::Constant { left, .. }
+                | ConstraintExpr::GreaterOrEqual { left, .. }
+                | ConstraintExpr::LessOrEqual { left, .. } => self.property(left)?,
+                ConstraintExpr::Midpoint { target, a, b, .. } => {
+                    self.property(target)?;
+                    self.name(a)?;
+                    self.name(b)?;
+                }
+                ConstraintExpr::Contains {
+                    container,
+                    elements,
+                    ..
+                } => {
+                    self.name(container)?;
+                    for element in elements {
+                        self.name(element)?;
+                    }
+                }
+                ConstraintExpr::Linear { left, terms, .. } => {
+                    self.property(left)?;
+                    for term in terms {
+                        self.property(&mut term.property)?;
+                    }
+                }
+                ConstraintExpr::EqualSize { elements, .. } => {
+                    for element in elements {
+                        self.name(element)?;
+                    }
+                }
+                ConstraintExpr::Relative {
+                    subject, anchor, ..
+                } => {
+                    self.name(subject)?;
+                    self.name(anchor)?;
+                }
+            },
+            Statement::Keyframe(kf) => {
+                for op in &mut kf.operations {
+                    match &mut op.node {
+                        KeyframeOp::Show(ids) | KeyframeOp::Hide(ids) => {
+                            for id in ids {
+                                self.name(id)?;
+                            }
+                        }
+                        KeyframeOp::Transform { target, .. } => self.name(target)?,
+                    }
+                }
+            }
+            Statement::Animate(a) => self.name(&mut a.target)?,
+            Statement::AnchorDecl(a) => {
+                let (AnchorPosition::PropertyRef(prop_ref)
+                | AnchorPosition::PropertyRefWithOffset { prop_ref, .. }) = &mut a.position;
+                self.property(prop_ref)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl VisitorMut for Resolver<'_> {
+    fn visit_statement(&mut self, stmt: &mut Statement) {
+        if self.error.is_some() || matches!(stmt, Statement::TemplateDecl(_)) {
+            return;
+        }
+        if let Err(e) = self.references(stmt) {
+            self.error = Some(e);
+            return;
+        }
+        let opened = scope_name(stmt);
+        if let Some(name) = declared_name_mut(stmt) {
+            if let Some(id) = self.scopes.id(&self.scope, name.node.as_str()) {
+                name.node = Identifier::new(id);
+            }
+        }
+        if let Some(scope) = &opened {
+            self.scope.push(scope.clone());
+        }
+        walk_statement_mut(self, stmt);
+        if opened.is_some() {
+            self.scope.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, unparse};
+
+    fn resolved(source: &str) -> Result<String, LayoutError> {
+        let mut doc = parse(source).unwrap();
+        resolve_scopes(&mut doc)?;
+        Ok(unparse(&doc))
+    }
+
+    #[test]
+    fn test_unique_names_are_kept() {
+        let source = "group a {\n    rect x\n}\nrect y\nx -> y\n";
+        assert_eq!(resolved(source).unwrap(), source);
+    }
+
+    #[test]
+    fn test_duplicate_names_get_scoped_ids() {
+        let out = resolved(
+            "group a { rect x\n rect y\n x -> y }\ngroup b { rect x }\na.x -> b.x\nconstrain a.x.left = b.x.right",
+        )
+        .unwrap();
+        assert!(out.contains("rect a_x"), "{}", out);
+        assert!(out.contains("rect b_x"), "{}", out);
+        // Inside `a`, `x` is a's own
+        assert!(out.contains("a_x -> y"), "{}", out);
+        assert!(out.contains("a_x -> b_x"), "{}", out);
+        assert!(out.contains("constrain a_x.left = b_x.right"), "{}", out);
+    }
+
+    #[test]
+    fn test_nested_scopes_and_anchors() {
+        let out = resolved(
+            "group outer { group inner { rect x } }\ngroup other { rect x }\nouter.inner.x.right -> other.x.left\nother.top -> outer",
+        )
+        .unwrap();
+        assert!(
+            out.contains("outer_inner_x.right -> other_x.left"),
+            "{}",
+            out
+        );
+        assert!(out.contains("other.top -> outer"), "{}", out);
+    }
+
+    #[test]
+    fn test_ambiguous_and_unknown_references() {
+        let err = resolved("group a { rect x }\ngroup b { rect x }\nrect y\nx -> y").unwrap_err();
+        assert!(
+            matches!(&err, LayoutError::AmbiguousReference { name, candidates, .. }
+                if name == "x" && candidates == &["a.x", "b.x"]),
+            "{:?}",
+            err
+        );
+
+        let err = resolved("group a { rect x }\ngroup b { rect x }\na.b.x -> b.x").unwrap_err();
+        assert!(
+            matches!(&err, LayoutError::PathNotFound { path, .. } if path == "a.b.x"),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_nearest_declaration_wins() {
+        let out =
+            resolved("rect x\ngroup a { rect x\n group b { rect x }\n x -> b.x }\nx -> a").unwrap();
+        assert!(out.contains("a_x -> a_b_x"), "{}", out);
+        assert!(out.contains("\nx -> a\n"), "{}", out);
+    }
+
+    #[test]
+    fn test_paths_into_instances_are_deferred() {
+        let out = resolved(
+            "group a { motor m }
+group b { motor m }
+a.m.terminal.left -> b.m.shaft
+constrain a.m.shaft.left = b.m.left",
+        )
+        .unwrap();
+        assert!(out.contains("a_m.terminal.left -> b_m.shaft"), "{}", out);
+        assert!(
+            out.contains("constrain a_m.shaft.left = b_m.left"),
+            "{}",
+            out
+        );
+
+        // Paths through plain groups still have to resolve
+        let err = resolved(
+            "group a { rect x }
+rect y
+a.x.z.left -> y",
+        )
+        .unwrap_err();
+        assert!(matches!(err, LayoutError::PathNotFound { .. }), "{:?}", err);
+    }
+}
@@ -0,0 +1,318 @@
+//! Live preview server for `--serve` (part of the CLI, not the library)
+//!
+//! Serves three routes over plain HTTP:
+//! - `/` a page showing the diagram, kept up to date by the client script
+//! - `/diagram.svg` the current rendering
+//! - `/live-reload.js` the client script, which any page can embed: it
+//!   connects to the `/live` WebSocket and replaces the contents of every
+//!   element with a `data-ai-live` attribute with each SVG the server pushes
+//!
+//! The watched files are polled for changes; on a change the document is
+//! rendered again and the SVG is sent to every connected client.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use base64::Engine;
+
+/// How often the watched files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How long a client may stall a push before it is dropped
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Appended to the client's key to form the handshake accept value (RFC 6455)
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const CLIENT_SCRIPT: &str = r#"// Agent Illustrator live reload: replaces the contents of every element
+// with a data-ai-live attribute with each SVG the server pushes.
+(function () {
+  var script = document.currentScript;
+  var url = new URL("/live", script ? script.src : location.href);
+  url.protocol = url.protocol === "https:" ? "wss:" : "ws:";
+  function connect() {
+    var socket = new WebSocket(url);
+    socket.onmessage = function (event) {
+      document.querySelectorAll("[data-ai-live]").forEach(function (el) {
+        el.innerHTML = event.data;
+      });
+    };
+    socket.onclose = function () {
+      setTimeout(connect, 1000);
+    };
+  }
+  connect();
+})();
+"#;
+
+/// A connected WebSocket client; its lock keeps frames from interleaving
+type Client = Arc<Mutex<TcpStream>>;
+
+struct Shared {
+    svg: String,
+    clients: Vec<Client>,
+}
+
+/// Serve the output of `render` on `addr` until the process is stopped,
+/// rendering again whenever one of `watched` (files or directories of
+/// files) changes. When `render` returns `None` the last SVG stays.
+pub fn serve(
+    addr: &str,
+    watched: Vec<PathBuf>,
+    render: impl Fn() -> Option<String>,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("serving on http://{}", listener.local_addr()?);
+
+    let shared = Arc::new(Mutex::new(Shared {
+        svg: render().unwrap_or_default(),
+        clients: vec![],
+    }));
+    let accepting = Arc::clone(&shared);
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let shared = Arc::clone(&accepting);
+            thread::spawn(move || {
+                if let Err(e) = handle_connection(stream, &shared) {
+                    eprintln!("serve: {}", e);
+                }
+            });
+        }
+    });
+
+    let mut last = fingerprint(&watched);
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let current = fingerprint(&watched);
+        if current == last {
+            continue;
+        }
+        last = current;
+        let Some(svg) = render() else {
+            continue;
+        };
+        let frame = text_frame(&svg);
+        // Write without holding the shared lock, so a slow client does not
+        // hold up new connections; clients that connect meanwhile get the
+        // new SVG when they register
+        let clients = {
+            let mut shared = lock(&shared);
+            shared.svg = svg;
+            shared.clients.clone()
+        };
+        // Clients that went away or stall fail the write and are dropped
+        let failed: Vec<Client> = clients
+            .into_iter()
+            .filter(|client| lock(client).write_all(&frame).is_err())
+            .collect();
+        if !failed.is_empty() {
+            lock(&shared)
+                .clients
+                .retain(|client| !failed.iter().any(|f| Arc::ptr_eq(f, client)));
+        }
+    }
+}
+
+/// Lock `mutex`, ignoring poisoning: a failed connection thread leaves the
+/// SVG and client list usable
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Modification times of the watched files, to compare between polls
+fn fingerprint(watched: &[PathBuf]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let modified = |path: &PathBuf| {
+        let time = path.metadata().and_then(|m| m.modified()).ok();
+        (path.clone(), time)
+    };
+    let mut files = vec![];
+    for path in watched {
+        match path.read_dir() {
+            Ok(entries) => files.extend(entries.flatten().map(|e| modified(&e.path()))),
+            Err(_) => files.push(modified(path)),
+        }
+    }
+    files.sort();
+    files
+}
+
+fn handle_connection(stream: TcpStream, shared: &Mutex<Shared>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let mut websocket_key = None;
+    let (mut upgrade, mut connection_upgrade) = (false, false);
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let (name, value) = (name.trim(), value.trim());
+            if name.eq_ignore_ascii_case("sec-websocket-key") {
+                websocket_key = Some(value.to_string());
+            } else if name.eq_ignore_ascii_case("upgrade") {
+                upgrade = value.eq_ignore_ascii_case("websocket");
+            } else if name.eq_ignore_ascii_case("connection") {
+                connection_upgrade = value
+                    .split(',')
+                    .any(|token| token.trim().eq_ignore_ascii_case("upgrade"));
+            }
+        }
+    }
+
+    let mut stream = stream;
+    let svg = || lock(shared).svg.clone();
+    match path {
+        "/live" => {
+            let Some(key) = websocket_key.filter(|_| upgrade && connection_upgrade) else {
+                return respond(
+                    &mut stream,
+                    "400 Bad Request",
+                    "text/plain",
+                    "expected a WebSocket upgrade request\n",
+                );
+            };
+            write!(
+                stream,
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                 Connection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept_key(&key)
+            )?;
+            stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+            let client = Arc::new(Mutex::new(stream.try_clone()?));
+            {
+                // Pushes wait for the client lock, so the current SVG goes
+                // out first and no later one is missed
+                let mut writer = lock(&client);
+                let current = {
+                    let mut shared = lock(shared);
+                    shared.clients.push(Arc::clone(&client));
+                    shared.svg.clone()
+                };
+                writer.write_all(&text_frame(&current))?;
+            }
+            let closed = read_until_close(&mut reader, &client);
+            lock(shared).clients.retain(|c| !Arc::ptr_eq(c, &client));
+            closed
+        }
+        "/" => {
+            let page = format!(
+                "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\">\
+                 <title>Agent Illustrator</title></head>\n<body>\n\
+                 <div data-ai-live>{}</div>\n\
+                 <script src=\"/live-reload.js\"></script>\n</body>\n</html>\n",
+                svg()
+            );
+            respond(&mut stream, "200 OK", "text/html; charset=utf-8", &page)
+        }
+        "/diagram.svg" => respond(&mut stream, "200 OK", "image/svg+xml", &svg()),
+        "/live-reload.js" => respond(
+            &mut stream,
+            "200 OK",
+            "text/javascript; charset=utf-8",
+            CLIENT_SCRIPT,
+        ),
+        _ => respond(&mut stream, "404 Not Found", "text/plain", "not found\n"),
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+         Cache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// Discard the client's frames until it closes the connection, answering
+/// its close frame
+fn read_until_close(reader: &mut impl Read, client: &Mutex<TcpStream>) -> io::Result<()> {
+    loop {
+        let mut header = [0; 2];
+        if reader.read_exact(&mut header).is_err() {
+            return Ok(());
+        }
+        let opcode = header[0] & 0x0f;
+        let len = match header[1] & 0x7f {
+            126 => {
+                let mut len = [0; 2];
+                reader.read_exact(&mut len)?;
+                u64::from(u16::from_be_bytes(len))
+            }
+            127 => {
+                let mut len = [0; 8];
+                reader.read_exact(&mut len)?;
+                u64::from_be_bytes(len)
+            }
+            len => u64::from(len),
+        };
+        // Client frames are masked: a 4-byte key precedes the payload
+        let masked = header[1] & 0x80 != 0;
+        io::copy(
+            &mut reader.by_ref().take(len + if masked { 4 } else { 0 }),
+            &mut io::sink(),
+        )?;
+        if opcode == 0x8 {
+            return lock(client).write_all(&[0x88, 0x00]);
+        }
+    }
+}
+
+/// The `Sec-WebSocket-Accept` value for a client's `Sec-WebSocket-Key`
+fn accept_key(key: &str) -> String {
+    let digest = sha1_smol::Sha1::from(format!("{}{}", key, WEBSOCKET_GUID)).digest();
+    base64::engine::general_purpose::STANDARD.encode(digest.bytes())
+}
+
+/// A single unmasked WebSocket text frame carrying `text`
+fn text_frame(text: &str) -> Vec<u8> {
+    let len = text.len();
+    let mut frame = vec![0x81];
+    if len < 126 {
+        frame.push(len as u8);
+    } else if let Ok(len) = u16::try_from(len) {
+        frame.push(126);
+        frame.extend(len.to_be_bytes());
+    } else {
+        frame.push(127);
+        frame.extend((len as u64).to_be_bytes());
+    }
+    frame.extend(text.as_bytes());
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_key() {
+        // The example handshake from RFC 6455, section 1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_text_frame_lengths() {
+        assert_eq!(text_frame("hi"), [0x81, 2, b'h', b'i']);
+        let medium = "x".repeat(300);
+        assert_eq!(text_frame(&medium)[..4], [0x81, 126, 0x01, 0x2c]);
+        let large = "x".repeat(70_000);
+        let frame = text_frame(&large);
+        assert_eq!(frame[..2], [0x81, 127]);
+        assert_eq!(frame[2..10], 70_000u64.to_be_bytes());
+        assert_eq!(frame.len(), 10 + 70_000);
+    }
+}
@@ -3,13 +3,89 @@
 //! This module provides symbolic color tokens that can be resolved to concrete
 //! color values via stylesheets. This enables brand-agnostic illustrations that
 //! can be rendered with different color schemes.
+//!
+//! Stylesheets can also define named connection styles, so notation conventions
+//! (sync vs async calls, encrypted links, ...) are written down once:
+//!
+//! ```toml
+//! [connection_styles.async-call]
+//! stroke = "accent-1"
+//! stroke_dasharray = "6,3"
+//! routing = "curved"
+//! ```
+//!
+//! and referenced with `a -> b [style: "async-call"]`.
+//!
+//! Style rules apply to every element with a matching `class` modifier, so a
+//! diagram can be restyled without touching the source:
+//!
+//! ```toml
+//! [styles.database]
+//! fill = "accent-2"
+//! stroke_width = 2
+//! ```
+//!
+//! Defaults apply to every element of a type (`rect`, `circle`, `row`,
+//! `group`, ...), under its own modifiers and its class rules:
+//!
+//! ```toml
+//! [defaults.circle]
+//! fill = "accent-1"
+//!
+//! [defaults.group]
+//! stroke_dasharray = "4,2"
+//! ```
+//!
+//! Named fill gradients work the same way:
+//!
+//! ```toml
+//! [gradients.hero]
+//! from = "accent-1"
+//! to = "accent-dark"
+//! direction = "vertical"
+//! ```
+//!
+//! and are referenced with `rect a [fill: gradient(hero)]`.
+//!
+//! Fonts set the default font family, how wide its glyphs are when sizing
+//! text (relative to the default sans-serif), and web fonts to load:
+//!
+//! ```toml
+//! [fonts]
+//! family = "Inter, sans-serif"
+//! width_scale = 1.05
+//!
+//! [[fonts.face]]
+//! family = "Inter"
+//! file = "fonts/Inter.woff2"
+//! weight = 400
+//! ```
+//!
+//! Faces with a `file` are embedded as data URIs when the stylesheet is loaded
+//! with [`Stylesheet::from_file`] (paths are relative to the stylesheet);
+//! faces with a `src` URL are referenced.
+//!
+//! Themes are named sets of color overrides, selected with
+//! [`Stylesheet::with_theme`] (`--theme dark` on the command line):
+//!
+//! ```toml
+//! [themes.dark]
+//! background-1 = "#1e1e1e"
+//! text-1 = "#eeeeee"
+//! ```
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::path::Path;
 
 use serde::Deserialize;
 use thiserror::Error;
 
+use crate::parser::ast::{
+    ConnectionDecl, ShapeType, Span, Statement, StyleKey, StyleModifier, StyleValue,
+};
+use crate::parser::visit::{walk_statement_mut, walk_statements_mut};
+use crate::parser::{parse_style_value, style_key, Document, Spanned, VisitorMut};
+
 /// Errors that can occur when loading or parsing stylesheets
 #[derive(Error, Debug)]
 pub enum StylesheetError {
@@ -17,9 +93,13 @@ pub enum StylesheetError {
     IoError(#[from] std::io::Error),
     #[error("Failed to parse stylesheet TOML: {0}")]
     ParseError(#[from] toml::de::Error),
+    #[error("Unknown theme '{name}'. Available themes: {available}")]
+    UnknownTheme { name: String, available: String },
 }
 
 /// A stylesheet mapping symbolic colors to concrete values
+///
+/// Maps are ordered so the CSS generated from them is the same on every run.
 #[derive(Debug, Clone)]
 pub struct Stylesheet {
     /// Optional name for the stylesheet
@@ -27,14 +107,105 @@ pub struct Stylesheet {
     /// Optional description
     pub description: Option<String>,
     /// Color mappings: token name -> hex color
-    pub colors: HashMap<String, String>,
+    pub colors: BTreeMap<String, String>,
+    /// Named connection styles: style name -> modifiers
+    pub connection_styles: BTreeMap<String, ConnectionStyle>,
+    /// Style rules: class name -> modifiers
+    pub styles: BTreeMap<String, StyleRule>,
+    /// Default modifiers: element type -> modifiers
+    pub defaults: BTreeMap<String, StyleRule>,
+    /// Named fill gradients: gradient name -> definition
+    pub gradients: BTreeMap<String, GradientDef>,
+    /// Font family, text measurement and web fonts
+    pub fonts: FontConfig,
+    /// Named themes: theme name -> color overrides
+    pub themes: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// Modifiers applied by a named connection style, as `(key, value)` pairs
+/// sorted by key. Values use modifier syntax (`accent-1`, `curved`, `2`).
+pub type ConnectionStyle = Vec<(String, String)>;
+
+/// Modifiers a style rule adds to elements of its class, in the same form
+pub type StyleRule = ConnectionStyle;
+
+/// A named linear gradient. Colors use modifier syntax (`accent-1`, `#fff`, `red`);
+/// `direction` is `horizontal` (default), `vertical` or `diagonal`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct GradientDef {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub direction: Option<String>,
+}
+
+/// The `[fonts]` table
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct FontConfig {
+    /// Default font family for all text, as a CSS `font-family` value
+    #[serde(default)]
+    pub family: Option<String>,
+    /// Glyph width relative to the default sans-serif, used to size shapes
+    /// to their text
+    #[serde(default)]
+    pub width_scale: Option<f64>,
+    /// Web fonts, emitted as `@font-face` rules
+    #[serde(default, rename = "face")]
+    pub faces: Vec<FontFace>,
+}
+
+/// A web font: `src` is a URL, `file` a font file to embed
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FontFace {
+    pub family: String,
+    #[serde(default)]
+    pub src: Option<String>,
+    #[serde(default)]
+    pub file: Option<String>,
+    #[serde(default)]
+    pub weight: Option<u16>,
+    #[serde(default)]
+    pub style: Option<String>,
+}
+
+impl FontFace {
+    /// The `@font-face` rule for this face
+    ///
+    /// A `file` that was not embedded is referenced by its path.
+    pub fn to_css(&self) -> String {
+        let src = self.src.as_deref().or(self.file.as_deref()).unwrap_or("");
+        let mut css = format!(
+            "@font-face {{ font-family: \"{}\"; src: url(\"{}\");",
+            self.family, src
+        );
+        if let Some(weight) = self.weight {
+            css.push_str(&format!(" font-weight: {};", weight));
+        }
+        if let Some(style) = &self.style {
+            css.push_str(&format!(" font-style: {};", style));
+        }
+        css.push_str(" }");
+        css
+    }
 }
 
 /// TOML structure for deserializing stylesheets
 #[derive(Deserialize)]
 struct TomlStylesheet {
     metadata: Option<TomlMetadata>,
-    colors: HashMap<String, String>,
+    colors: BTreeMap<String, String>,
+    #[serde(default)]
+    connection_styles: BTreeMap<String, toml::Table>,
+    #[serde(default)]
+    styles: BTreeMap<String, toml::Table>,
+    #[serde(default)]
+    defaults: BTreeMap<String, toml::Table>,
+    #[serde(default)]
+    gradients: BTreeMap<String, GradientDef>,
+    #[serde(default)]
+    fonts: FontConfig,
+    #[serde(default)]
+    themes: BTreeMap<String, BTreeMap<String, String>>,
 }
 
 #[derive(Deserialize)]
@@ -85,6 +256,36 @@ secondary-dark = "#e65100"
 status-success = "#4caf50"
 status-warning = "#ff9800"
 status-error = "#f44336"
+
+# The base palette is light
+[themes.light]
+
+[themes.dark]
+foreground-1 = "#e0e0e0"
+foreground-2 = "#b0b0b0"
+foreground-3 = "#808080"
+foreground-light = "#3a3a3a"
+foreground-dark = "#f5f5f5"
+background-1 = "#1e1e1e"
+background-2 = "#252526"
+background-3 = "#2d2d2d"
+background-light = "#333333"
+background-dark = "#121212"
+text-1 = "#e0e0e0"
+text-2 = "#b0b0b0"
+text-3 = "#808080"
+text-light = "#1a1a1a"
+text-dark = "#ffffff"
+accent-1 = "#64b5f6"
+accent-2 = "#0d2a40"
+accent-3 = "#1565c0"
+accent-light = "#0d2a40"
+accent-dark = "#90caf9"
+secondary-1 = "#ffb74d"
+secondary-2 = "#3e2a10"
+secondary-3 = "#e65100"
+secondary-light = "#3e2a10"
+secondary-dark = "#ffcc80"
 "##;
 
 impl Stylesheet {
@@ -93,14 +294,36 @@ impl Stylesheet {
         Stylesheet {
             name: None,
             description: None,
-            colors: HashMap::new(),
+            colors: BTreeMap::new(),
+            connection_styles: BTreeMap::new(),
+            styles: BTreeMap::new(),
+            defaults: BTreeMap::new(),
+            gradients: BTreeMap::new(),
+            fonts: FontConfig::default(),
+            themes: BTreeMap::new(),
         }
     }
 
-    /// Load stylesheet from TOML file
+    /// Load stylesheet from TOML file, embedding the font files of its faces
     pub fn from_file(path: &Path) -> Result<Self, StylesheetError> {
         let content = std::fs::read_to_string(path)?;
-        Self::from_str(&content)
+        let mut stylesheet = Self::from_str(&content)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        for face in &mut stylesheet.fonts.faces {
+            let Some(file) = &face.file else {
+                continue;
+            };
+            let bytes = std::fs::read(base.join(file))?;
+            let mime = match Path::new(file).extension().and_then(|e| e.to_str()) {
+                Some("woff") => "font/woff",
+                Some("ttf") => "font/ttf",
+                Some("otf") => "font/otf",
+                _ => "font/woff2",
+            };
+            let encoded = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, bytes);
+            face.src = Some(format!("data:{};base64,{}", mime, encoded));
+        }
+        Ok(stylesheet)
     }
 
     /// Load stylesheet from TOML string
@@ -108,13 +331,147 @@ impl Stylesheet {
     pub fn from_str(content: &str) -> Result<Self, StylesheetError> {
         let parsed: TomlStylesheet = toml::from_str(content)?;
 
+        let modifier_tables = |tables: BTreeMap<String, toml::Table>| {
+            tables
+                .into_iter()
+                .map(|(name, table)| {
+                    let modifiers = table
+                        .into_iter()
+                        .map(|(key, value)| {
+                            let value = match value {
+                                toml::Value::String(s) => s,
+                                other => other.to_string(),
+                            };
+                            (key, value)
+                        })
+                        .collect();
+                    (name, modifiers)
+                })
+                .collect()
+        };
+        let connection_styles = modifier_tables(parsed.connection_styles);
+        let styles = modifier_tables(parsed.styles);
+        let defaults = modifier_tables(parsed.defaults);
+
         Ok(Stylesheet {
             name: parsed.metadata.as_ref().and_then(|m| m.name.clone()),
             description: parsed.metadata.as_ref().and_then(|m| m.description.clone()),
             colors: parsed.colors,
+            connection_styles,
+            styles,
+            defaults,
+            gradients: parsed.gradients,
+            fonts: parsed.fonts,
+            themes: parsed.themes,
         })
     }
 
+    /// Layer one stylesheet on top of another
+    ///
+    /// Colors and font settings defined in `overrides` replace those in
+    /// `base`; everything else is kept from `base`. Connection styles, style
+    /// rules and defaults are merged modifier by modifier, gradients field by
+    /// field and themes color by color, so an override only changes the keys it
+    /// sets. Font faces are added. Metadata is taken from `overrides` when
+    /// present.
+    pub fn merge(base: Stylesheet, overrides: Stylesheet) -> Self {
+        let mut colors = base.colors;
+        colors.extend(overrides.colors);
+        let connection_styles = merge_rules(base.connection_styles, overrides.connection_styles);
+        let styles = merge_rules(base.styles, overrides.styles);
+        let defaults = merge_rules(base.defaults, overrides.defaults);
+        let mut gradients = base.gradients;
+        for (name, gradient) in overrides.gradients {
+            let direction = match gradients.remove(&name) {
+                Some(base) => gradient.direction.or(base.direction),
+                None => gradient.direction,
+            };
+            gradients.insert(
+                name,
+                GradientDef {
+                    direction,
+                    ..gradient
+                },
+            );
+        }
+        let mut faces = base.fonts.faces;
+        faces.extend(overrides.fonts.faces);
+        let fonts = FontConfig {
+            family: overrides.fonts.family.or(base.fonts.family),
+            width_scale: overrides.fonts.width_scale.or(base.fonts.width_scale),
+            faces,
+        };
+        let mut themes = base.themes;
+        for (name, colors) in overrides.themes {
+            themes.entry(name).or_default().extend(colors);
+        }
+        Stylesheet {
+            name: overrides.name.or(base.name),
+            description: overrides.description.or(base.description),
+            colors,
+            connection_styles,
+            styles,
+            defaults,
+            gradients,
+            fonts,
+            themes,
+        }
+    }
+
+    /// Apply a named theme: its colors replace the base colors
+    ///
+    /// Fails with [`StylesheetError::UnknownTheme`] if the stylesheet does not
+    /// define the theme.
+    pub fn with_theme(mut self, name: &str) -> Result<Self, StylesheetError> {
+        let Some(colors) = self.themes.get(name) else {
+            let mut available: Vec<_> = self.themes.keys().cloned().collect();
+            available.sort();
+            return Err(StylesheetError::UnknownTheme {
+                name: name.to_string(),
+                available: if available.is_empty() {
+                    "(none)".to_string()
+                } else {
+                    available.join(", ")
+                },
+            });
+        };
+        let colors = colors.clone();
+        self.colors.extend(colors);
+        Ok(self)
+    }
+
+    /// Look up a named connection style
+    pub fn connection_style(&self, name: &str) -> Option<&ConnectionStyle> {
+        self.connection_styles.get(name)
+    }
+
+    /// Look up the style rule for a `class`
+    pub fn class_style(&self, class: &str) -> Option<&StyleRule> {
+        self.styles.get(class)
+    }
+
+    /// Look up the default modifiers for an element type (`rect`, `row`, ...)
+    pub fn type_defaults(&self, kind: &str) -> Option<&StyleRule> {
+        self.defaults.get(kind)
+    }
+
+    /// Look up a named gradient
+    pub fn gradient(&self, name: &str) -> Option<&GradientDef> {
+        self.gradients.get(name)
+    }
+
+    /// Convert a color written in modifier syntax to a CSS value
+    ///
+    /// Palette tokens become CSS variable references (`var(--accent-1)`), like
+    /// symbolic colors in the DSL; anything else passes through unchanged.
+    pub fn color_css(&self, value: &str) -> String {
+        if self.resolve(value).is_some() || Self::default().resolve(value).is_some() {
+            format!("var(--{})", value)
+        } else {
+            value.to_string()
+        }
+    }
+
     /// Resolve a symbolic color token to a concrete value
     ///
     /// Returns None if the token is not defined in this stylesheet.
@@ -165,6 +522,25 @@ impl Stylesheet {
     }
 }
 
+/// Layer the named rules of `overrides` over those of `base`, modifier by
+/// modifier; each rule stays sorted by key
+fn merge_rules(
+    mut base: BTreeMap<String, StyleRule>,
+    overrides: BTreeMap<String, StyleRule>,
+) -> BTreeMap<String, StyleRule> {
+    for (name, rule) in overrides {
+        let merged = base.entry(name).or_default();
+        for (key, value) in rule {
+            match merged.iter_mut().find(|(k, _)| *k == key) {
+                Some(existing) => existing.1 = value,
+                None => merged.push((key, value)),
+            }
+        }
+        merged.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+    base
+}
+
 impl Default for Stylesheet {
     fn default() -> Self {
         Self::from_str(DEFAULT_PALETTE).expect("Default palette should be valid TOML")
@@ -220,6 +596,231 @@ pub fn validate_color_token(token: &str, stylesheet: &Stylesheet) -> Result<(),
     ))
 }
 
+/// Replace `style: "name"` on connections with the modifiers of the named
+/// connection style from the stylesheet.
+///
+/// Modifiers written on the connection itself take precedence over the style.
+pub(crate) fn apply_connection_styles(
+    doc: &mut Document,
+    stylesheet: &Stylesheet,
+) -> Result<(), String> {
+    struct ConnectionStyles<'a> {
+        stylesheet: &'a Stylesheet,
+        error: Option<String>,
+    }
+
+    impl VisitorMut for ConnectionStyles<'_> {
+        fn visit_statement(&mut self, stmt: &mut Statement) {
+            if self.error.is_some() {
+                return;
+            }
+            match stmt {
+                Statement::Connection(conns) => {
+                    for conn in conns {
+                        if let Err(e) = apply_connection_style(conn, self.stylesheet) {
+                            self.error = Some(e);
+                            return;
+                        }
+                    }
+                }
+                // Template bodies are styled where they are instantiated
+                Statement::TemplateDecl(_) => {}
+                _ => walk_statement_mut(self, stmt),
+            }
+        }
+    }
+
+    let mut styles = ConnectionStyles {
+        stylesheet,
+        error: None,
+    };
+    styles.visit_document(doc);
+    styles.error.map_or(Ok(()), Err)
+}
+
+/// Expand the `style:` reference of one connection, if it has one
+fn apply_connection_style(
+    conn: &mut ConnectionDecl,
+    stylesheet: &Stylesheet,
+) -> Result<(), String> {
+    let Some(pos) = conn
+        .modifiers
+        .iter()
+        .position(|m| matches!(&m.node.key.node, StyleKey::Custom(k) if k == "style"))
+    else {
+        return Ok(());
+    };
+    let style_ref = conn.modifiers.remove(pos);
+    let name = match &style_ref.node.value.node {
+        StyleValue::String(s) | StyleValue::Keyword(s) => s.clone(),
+        StyleValue::Identifier(id) => id.0.clone(),
+        _ => return Err("connection style must be a name".to_string()),
+    };
+    let style = stylesheet.connection_style(&name).ok_or_else(|| {
+        let mut known: Vec<_> = stylesheet.connection_styles.keys().cloned().collect();
+        known.sort();
+        format!(
+            "Unknown connection style '{}'. Defined styles: {}",
+            name,
+            if known.is_empty() {
+                "(none)".to_string()
+            } else {
+                known.join(", ")
+            }
+        )
+    })?;
+
+    // Point diagnostics at the `style:` reference
+    let span = style_ref.span;
+    for (key, value) in style {
+        let modifier = rule_modifier(key, value, &span)
+            .ok_or_else(|| format!("Invalid modifier '{}' in connection style '{}'", key, name))?;
+        if conn
+            .modifiers
+            .iter()
+            .any(|m| m.node.key.node == modifier.key.node)
+        {
+            continue;
+        }
+        conn.modifiers.push(Spanned::new(modifier, span.clone()));
+    }
+    Ok(())
+}
+
+/// Add the stylesheet's style rules to the document: the modifiers of
+/// `[styles.<class>]` to every element and connection with that `class`, then
+/// those of `[defaults.<type>]` to every element of that type
+///
+/// Modifiers written on the element win, then class rules (the class listed
+/// last first), then type defaults.
+pub(crate) fn apply_style_rules(doc: &mut Document, stylesheet: &Stylesheet) -> Result<(), String> {
+    fn add_rule(
+        modifiers: &mut Vec<Spanned<StyleModifier>>,
+        rule: &StyleRule,
+        what: &str,
+        span: &Span,
+    ) -> Result<(), String> {
+        for (key, value) in rule {
+            let modifier = rule_modifier(key, value, span)
+                .ok_or_else(|| format!("Invalid modifier '{}' in {}", key, what))?;
+            if modifiers
+                .iter()
+                .any(|m| m.node.key.node == modifier.key.node)
+            {
+                continue;
+            }
+            modifiers.push(Spanned::new(modifier, span.clone()));
+        }
+        Ok(())
+    }
+
+    fn add_rules(
+        modifiers: &mut Vec<Spanned<StyleModifier>>,
+        kind: &str,
+        span: &Span,
+        stylesheet: &Stylesheet,
+    ) -> Result<(), String> {
+        let classes: Vec<(String, Span)> = modifiers
+            .iter()
+            .filter(|m| m.node.key.node == StyleKey::Class)
+            .filter_map(|m| match &m.node.value.node {
+                StyleValue::String(s) | StyleValue::Keyword(s) => Some((s.clone(), m.span.clone())),
+                StyleValue::Identifier(id) => Some((id.0.clone(), m.span.clone())),
+                _ => None,
+            })
+            .collect();
+        for (class, class_span) in classes.into_iter().rev() {
+            if let Some(rule) = stylesheet.class_style(&class) {
+                // Point diagnostics at the `class:` modifier
+                let what = format!("style rule '{}'", class);
+                add_rule(modifiers, rule, &what, &class_span)?;
+            }
+        }
+        if let Some(rule) = stylesheet.type_defaults(kind) {
+            let what = format!("defaults for '{}'", kind);
+            add_rule(modifiers, rule, &what, span)?;
+        }
+        Ok(())
+    }
+
+    struct StyleRules<'a> {
+        stylesheet: &'a Stylesheet,
+        error: Option<String>,
+    }
+
+    impl VisitorMut for StyleRules<'_> {
+        fn visit_statements(&mut self, stmts: &mut Vec<Spanned<Statement>>) {
+            // Type defaults point diagnostics at the statement
+            for stmt in stmts.iter_mut() {
+                if self.error.is_some() {
+                    return;
+                }
+                let span = &stmt.span;
+                let stylesheet = self.stylesheet;
+                let added = match &mut stmt.node {
+                    Statement::Shape(s) => {
+                        let kind = match &s.shape_type.node {
+                            ShapeType::Icon { .. } => "icon".to_string(),
+                            shape => crate::inventory::shape_kind(shape),
+                        };
+                        add_rules(&mut s.modifiers, &kind, span, stylesheet)
+                    }
+                    Statement::Connection(conns) => conns.iter_mut().try_for_each(|conn| {
+                        add_rules(&mut conn.modifiers, "connection", span, stylesheet)
+                    }),
+                    Statement::Layout(l) => {
+                        let kind = crate::inventory::layout_kind(l.layout_type.node);
+                        add_rules(&mut l.modifiers, kind, span, stylesheet)
+                    }
+                    Statement::Group(g) => add_rules(&mut g.modifiers, "group", span, stylesheet),
+                    _ => Ok(()),
+                };
+                if let Err(e) = added {
+                    self.error = Some(e);
+                }
+            }
+            walk_statements_mut(self, stmts);
+        }
+
+        fn visit_statement(&mut self, stmt: &mut Statement) {
+            // Template bodies are styled where they are instantiated
+            if self.error.is_none() && !matches!(stmt, Statement::TemplateDecl(_)) {
+                walk_statement_mut(self, stmt);
+            }
+        }
+    }
+
+    if stylesheet.styles.is_empty() && stylesheet.defaults.is_empty() {
+        return Ok(());
+    }
+    let mut rules = StyleRules {
+        stylesheet,
+        error: None,
+    };
+    rules.visit_document(doc);
+    rules.error.map_or(Ok(()), Err)
+}
+
+/// A stylesheet `key = value` pair as the modifier it stands for, spanned at
+/// `span`. The value is read with the modifier value grammar and kept as a
+/// string when it is not a single value (e.g. `6,3`); `None` when the key is
+/// not a modifier name.
+fn rule_modifier(key: &str, value: &str, span: &Span) -> Option<StyleModifier> {
+    let mut chars = key.chars();
+    let is_name = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_name {
+        return None;
+    }
+    let value = parse_style_value(value).unwrap_or_else(|| StyleValue::String(value.to_string()));
+    Some(StyleModifier {
+        key: Spanned::new(style_key(key), span.clone()),
+        value: Spanned::new(value, span.clone()),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -252,7 +853,13 @@ mod tests {
         let empty = Stylesheet {
             name: None,
             description: None,
-            colors: HashMap::new(),
+            colors: BTreeMap::new(),
+            connection_styles: BTreeMap::new(),
+            styles: BTreeMap::new(),
+            defaults: BTreeMap::new(),
+            gradients: BTreeMap::new(),
+            fonts: FontConfig::default(),
+            themes: BTreeMap::new(),
         };
         assert_eq!(empty.resolve_or_default("foreground-1"), "#333333");
     }
@@ -263,7 +870,13 @@ mod tests {
         let empty = Stylesheet {
             name: None,
             description: None,
-            colors: HashMap::new(),
+            colors: BTreeMap::new(),
+            connection_styles: BTreeMap::new(),
+            styles: BTreeMap::new(),
+            defaults: BTreeMap::new(),
+            gradients: BTreeMap::new(),
+            fonts: FontConfig::default(),
+            themes: BTreeMap::new(),
         };
         // Unknown specific token but known category
         assert_eq!(empty.resolve_or_default("foreground-99"), "#333333");
@@ -286,6 +899,55 @@ foreground-1 = "#000000"
         assert_eq!(stylesheet.resolve("foreground-1"), Some("#000000"));
     }
 
+    #[test]
+    fn test_with_theme() {
+        let toml_str = r##"
+[colors]
+background-1 = "#ffffff"
+text-1 = "#000000"
+
+[themes.dark]
+background-1 = "#000000"
+"##;
+        let stylesheet = Stylesheet::from_str(toml_str).expect("Should parse");
+        let dark = stylesheet.clone().with_theme("dark").unwrap();
+        assert_eq!(dark.resolve("background-1"), Some("#000000"));
+        assert_eq!(dark.resolve("text-1"), Some("#000000"));
+
+        let err = stylesheet.with_theme("solar").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown theme 'solar'. Available themes: dark"
+        );
+
+        // The default palette ships light and dark themes
+        let dark = Stylesheet::default().with_theme("dark").unwrap();
+        assert_eq!(dark.resolve("background-1"), Some("#1e1e1e"));
+        assert!(Stylesheet::default().with_theme("light").is_ok());
+    }
+
+    #[test]
+    fn test_parse_gradients() {
+        let toml_str = r##"
+[colors]
+brand = "#123456"
+
+[gradients.hero]
+from = "brand"
+to = "#ffffff"
+direction = "vertical"
+"##;
+        let stylesheet = Stylesheet::from_str(toml_str).expect("Should parse");
+        let hero = stylesheet.gradient("hero").unwrap();
+        assert_eq!(hero.direction.as_deref(), Some("vertical"));
+        assert_eq!(stylesheet.color_css(&hero.from), "var(--brand)");
+        assert_eq!(stylesheet.color_css(&hero.to), "#ffffff");
+        assert_eq!(stylesheet.color_css("accent-1"), "var(--accent-1)");
+
+        let merged = Stylesheet::merge(Stylesheet::default(), stylesheet);
+        assert!(merged.gradient("hero").is_some());
+    }
+
     #[test]
     fn test_parse_toml_without_metadata() {
         let toml_str = r##"
@@ -303,4 +965,184 @@ foreground-1 = "#111111"
         let result = Stylesheet::from_str(invalid);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_merge_overrides_win() {
+        let base = Stylesheet::from_str(
+            r##"
+[metadata]
+name = "Base"
+description = "Shared palette"
+
+[colors]
+foreground-1 = "#111111"
+accent-1 = "#222222"
+"##,
+        )
+        .unwrap();
+        let team = Stylesheet::from_str(
+            r##"
+[metadata]
+name = "Team"
+
+[colors]
+accent-1 = "#ff0000"
+brand = "#00ff00"
+"##,
+        )
+        .unwrap();
+
+        let merged = Stylesheet::merge(base, team);
+        assert_eq!(merged.resolve("foreground-1"), Some("#111111"));
+        assert_eq!(merged.resolve("accent-1"), Some("#ff0000"));
+        assert_eq!(merged.resolve("brand"), Some("#00ff00"));
+        assert_eq!(merged.name, Some("Team".to_string()));
+        assert_eq!(merged.description, Some("Shared palette".to_string()));
+    }
+
+    #[test]
+    fn test_merge_rules_key_by_key() {
+        let base = Stylesheet::from_str(
+            r##"
+[colors]
+
+[styles.database]
+fill = "accent-2"
+stroke = "accent-dark"
+stroke_width = 2
+
+[connection_styles.async-call]
+stroke = "accent-1"
+stroke_dasharray = "6,3"
+
+[gradients.hero]
+from = "accent-1"
+to = "accent-dark"
+direction = "vertical"
+"##,
+        )
+        .unwrap();
+        let team = Stylesheet::from_str(
+            r##"
+[colors]
+
+[styles.database]
+stroke_width = 4
+
+[connection_styles.async-call]
+routing = "curved"
+
+[gradients.hero]
+from = "#ff0000"
+to = "#0000ff"
+"##,
+        )
+        .unwrap();
+
+        let merged = Stylesheet::merge(base, team);
+        assert_eq!(
+            merged.class_style("database").unwrap(),
+            &vec![
+                ("fill".to_string(), "accent-2".to_string()),
+                ("stroke".to_string(), "accent-dark".to_string()),
+                ("stroke_width".to_string(), "4".to_string()),
+            ]
+        );
+        assert_eq!(
+            merged.connection_style("async-call").unwrap(),
+            &vec![
+                ("routing".to_string(), "curved".to_string()),
+                ("stroke".to_string(), "accent-1".to_string()),
+                ("stroke_dasharray".to_string(), "6,3".to_string()),
+            ]
+        );
+        let hero = merged.gradient("hero").unwrap();
+        assert_eq!(hero.from, "#ff0000");
+        assert_eq!(hero.direction.as_deref(), Some("vertical"));
+    }
+
+    #[test]
+    fn test_parse_connection_styles() {
+        let stylesheet = Stylesheet::from_str(
+            r##"
+[colors]
+
+[connection_styles.async-call]
+stroke = "accent-1"
+stroke_width = 2
+routing = "curved"
+"##,
+        )
+        .unwrap();
+        let style = stylesheet.connection_style("async-call").unwrap();
+        assert_eq!(
+            style,
+            &vec![
+                ("routing".to_string(), "curved".to_string()),
+                ("stroke".to_string(), "accent-1".to_string()),
+                ("stroke_width".to_string(), "2".to_string()),
+            ]
+        );
+        assert!(stylesheet.connection_style("sync-call").is_none());
+    }
+
+    #[test]
+    fn test_parse_style_rules() {
+        let stylesheet = Stylesheet::from_str(
+            r##"
+[colors]
+
+[styles.database]
+fill = "accent-2"
+stroke_width = 2
+
+[defaults.circle]
+fill = "accent-1"
+"##,
+        )
+        .unwrap();
+        assert_eq!(
+            stylesheet.type_defaults("circle").unwrap(),
+            &vec![("fill".to_string(), "accent-1".to_string())]
+        );
+        assert_eq!(
+            stylesheet.class_style("database").unwrap(),
+            &vec![
+                ("fill".to_string(), "accent-2".to_string()),
+                ("stroke_width".to_string(), "2".to_string()),
+            ]
+        );
+        assert!(stylesheet.class_style("queue").is_none());
+    }
+
+    #[test]
+    fn test_rule_modifier_values() {
+        let value =
+            |key: &str, value: &str| rule_modifier(key, value, &(0..0)).map(|m| m.value.node);
+        assert_eq!(
+            value("stroke_width", "2"),
+            Some(StyleValue::Number {
+                value: 2.0,
+                unit: None
+            })
+        );
+        assert!(matches!(
+            value("fill", "darken(accent-1, 20%)"),
+            Some(StyleValue::Function { .. })
+        ));
+        assert_eq!(
+            value("stroke_dasharray", "6,3"),
+            Some(StyleValue::String("6,3".to_string()))
+        );
+
+        // Quotes and brackets stay inside the one value
+        let modifier = rule_modifier("fill", r#"red, label: "x"] [onclick: y"#, &(0..0)).unwrap();
+        assert_eq!(modifier.key.node, StyleKey::Fill);
+        assert_eq!(
+            modifier.value.node,
+            StyleValue::String(r#"red, label: "x"] [onclick: y"#.to_string())
+        );
+
+        assert!(rule_modifier("fill: red, x", "1", &(0..0)).is_none());
+    }
 }
@@ -0,0 +1,291 @@
+//! Nested document embedding (`embed "other.ail" as name`)
+//!
+//! The embedded document is rendered through the full pipeline with its own
+//! templates and constraints, then placed in the outer document as a single
+//! SVG element. Layout treats it like any other shape, so it gets the usual
+//! border-midpoint anchors and can be connected, constrained and placed.
+//!
+//! The embedded document shares the outer stylesheet, and its own `<style>`
+//! block is dropped before inlining: that block is global, so it would
+//! override the outer palette for the whole drawing.
+
+use std::path::PathBuf;
+
+use crate::parser::ast::{
+    EmbedDecl, ShapeDecl, ShapeType, Spanned, Statement, StyleKey, StyleModifier, StyleValue,
+};
+
+use super::registry::{TemplateError, TemplateRegistry};
+
+/// Render the embedded document and turn it into an `SvgEmbed` shape.
+///
+/// A `scale: N` modifier sizes the element relative to the embedded drawing;
+/// explicit `width`/`height` modifiers still take precedence.
+pub(super) fn resolve_embed(
+    decl: &EmbedDecl,
+    span: &std::ops::Range<usize>,
+    registry: &TemplateRegistry,
+) -> Result<Spanned<Statement>, TemplateError> {
+    let full_path = registry.resolve_path(&decl.path.node);
+    let identity = full_path
+        .canonicalize()
+        .unwrap_or_else(|_| full_path.clone());
+
+    if registry.embed_chain().contains(&identity) {
+        let chain = registry
+            .embed_chain()
+            .iter()
+            .chain(std::iter::once(&identity))
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        return Err(TemplateError::CircularReference { chain });
+    }
+
+    let source = std::fs::read_to_string(&full_path).map_err(|e| TemplateError::FileReadError {
+        path: full_path.clone(),
+        message: e.to_string(),
+    })?;
+
+    let svg = render_embedded(&source, &full_path, identity, registry).map_err(|e| match e {
+        crate::RenderError::Template(inner @ TemplateError::CircularReference { .. }) => inner,
+        other => TemplateError::Embedded {
+            path: full_path.clone(),
+            source: Box::new(other),
+            span: span.clone(),
+        },
+    })?;
+    let (origin_x, origin_y, width, height) =
+        parse_viewbox(&svg).ok_or_else(|| TemplateError::InvalidSvg {
+            message: format!("embedded document {} has no viewBox", full_path.display()),
+        })?;
+
+    // Shift the drawing so its top-left corner sits at the element origin, and
+    // namespace ids so they cannot collide with the outer document.
+    let content = format!(
+        r#"<svg><g transform="translate({}, {})">{}</g></svg>"#,
+        -origin_x,
+        -origin_y,
+        namespace_ids(strip_style(strip_wrapper(&svg)), &decl.name.node.0)
+    );
+
+    let scale = decl
+        .modifiers
+        .iter()
+        .find_map(|m| match (&m.node.key.node, &m.node.value.node) {
+            (StyleKey::Custom(k), StyleValue::Number { value, .. }) if k == "scale" => Some(*value),
+            _ => None,
+        })
+        .unwrap_or(1.0);
+
+    let mut modifiers: Vec<_> = decl
+        .modifiers
+        .iter()
+        .filter(|m| !matches!(&m.node.key.node, StyleKey::Custom(k) if k == "scale"))
+        .cloned()
+        .collect();
+    for (key, value) in [(StyleKey::Width, width), (StyleKey::Height, height)] {
+        if !modifiers.iter().any(|m| m.node.key.node == key) {
+            modifiers.push(Spanned::new(
+                StyleModifier {
+                    key: Spanned::new(key, span.clone()),
+                    value: Spanned::new(
+                        StyleValue::Number {
+                            value: value * scale,
+                            unit: None,
+                        },
+                        span.clone(),
+                    ),
+                },
+                span.clone(),
+            ));
+        }
+    }
+
+    let shape = ShapeDecl {
+        shape_type: Spanned::new(
+            ShapeType::SvgEmbed {
+                content,
+                intrinsic_width: Some(width),
+                intrinsic_height: Some(height),
+            },
+            span.clone(),
+        ),
+        name: Some(decl.name.clone()),
+        modifiers,
+        ports: vec![],
+    };
+
+    Ok(Spanned::new(Statement::Shape(shape), span.clone()))
+}
+
+/// Render an embedded document with paths relative to its own location and
+/// the outer document's stylesheet
+fn render_embedded(
+    source: &str,
+    path: &std::path::Path,
+    identity: PathBuf,
+    registry: &TemplateRegistry,
+) -> Result<String, crate::RenderError> {
+    let mut config = crate::RenderConfig::new()
+        .with_stylesheet(registry.stylesheet().clone())
+        .with_image_href_mode(registry.image_href_mode())
+        .with_svg_embed_policy(registry.svg_embed_policy())
+        .with_svg(
+            crate::SvgConfig::default()
+                .with_viewbox_padding(0.0)
+                .with_standalone(false),
+        );
+    if let Some(parent) = path.parent() {
+        config = config.with_template_base_path(parent.to_path_buf());
+    }
+    config.icon_resolvers = registry.icon_resolvers().to_vec();
+    config.embed_chain = registry.embed_chain().to_vec();
+    config.embed_chain.push(identity);
+
+    crate::render_with_config(source, config)
+}
+
+/// Read `x y width height` from the root `viewBox` attribute
+fn parse_viewbox(svg: &str) -> Option<(f64, f64, f64, f64)> {
+    let start = svg.find(r#"viewBox=""#)? + r#"viewBox=""#.len();
+    let end = start + svg[start..].find('"')?;
+    let values: Vec<f64> = svg[start..end]
+        .split_whitespace()
+        .map(|v| v.parse().ok())
+        .collect::<Option<_>>()?;
+    match values[..] {
+        [x, y, w, h] => Some((x, y, w, h)),
+        _ => None,
+    }
+}
+
+/// Content between the root `<svg ...>` and `</svg>` tags
+fn strip_wrapper(svg: &str) -> &str {
+    let body_start = svg
+        .find("<svg")
+        .and_then(|start| svg[start..].find('>').map(|end| start + end + 1))
+        .unwrap_or(0);
+    let body_end = svg.rfind("</svg>").unwrap_or(svg.len());
+    &svg[body_start..body_end.max(body_start)]
+}
+
+/// Drop the leading `<style>` block the renderer emits
+fn strip_style(body: &str) -> &str {
+    let trimmed = body.trim_start();
+    if !trimmed.starts_with("<style") {
+        return body;
+    }
+    match trimmed.find("</style>") {
+        Some(end) => &trimmed[end + "</style>".len()..],
+        None => body,
+    }
+}
+
+/// Prefix every `id` attribute and local reference (`url(#..)`, `href="#.."`)
+/// with `prefix_`
+///
+/// Only attribute values inside tags are rewritten; text content and
+/// comments stay as they are.
+fn namespace_ids(svg: &str, prefix: &str) -> String {
+    let mut out = String::with_capacity(svg.len());
+    let mut rest = svg;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |i| i + "-->".len());
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+        } else {
+            let end = tag_end(rest);
+            namespace_tag(&rest[..end], prefix, &mut out);
+            rest = &rest[end..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Length of the tag at the start of `tag`, up to and including its `>`
+fn tag_end(tag: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return i + 1,
+            _ => {}
+        }
+    }
+    tag.len()
+}
+
+/// Append `tag` to `out` with its attribute values namespaced
+fn namespace_tag(tag: &str, prefix: &str, out: &mut String) {
+    let mut rest = tag;
+    while let Some(open) = rest.find(['"', '\'']) {
+        let quote = &rest[open..open + 1];
+        let Some(len) = rest[open + 1..].find(quote) else {
+            break;
+        };
+        let name = rest[..open]
+            .trim_end()
+            .trim_end_matches('=')
+            .split_whitespace()
+            .last()
+            .unwrap_or("");
+        let value = &rest[open + 1..open + 1 + len];
+        out.push_str(&rest[..open + 1]);
+        match name {
+            "id" => out.push_str(&format!("{}_{}", prefix, value)),
+            "href" | "xlink:href" if value.starts_with('#') => {
+                out.push_str(&format!("#{}_{}", prefix, &value[1..]))
+            }
+            _ => out.push_str(&value.replace("url(#", &format!("url(#{}_", prefix))),
+        }
+        out.push_str(quote);
+        rest = &rest[open + len + 2..];
+    }
+    out.push_str(rest);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_viewbox() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-5 10 200 80.5">"#;
+        assert_eq!(parse_viewbox(svg), Some((-5.0, 10.0, 200.0, 80.5)));
+        assert_eq!(parse_viewbox("<svg>"), None);
+    }
+
+    #[test]
+    fn test_namespace_ids() {
+        let svg = r#"<rect id="a"/><path marker-end="url(#ai-arrow)"/>"#;
+        assert_eq!(
+            namespace_ids(svg, "sub"),
+            r#"<rect id="sub_a"/><path marker-end="url(#sub_ai-arrow)"/>"#
+        );
+        // Text content, comments and other attributes are left alone
+        let svg =
+            r##"<text data-ai-id="t">see url(#a) id="b"</text><!-- id="c" --><use href="#d"/>"##;
+        assert_eq!(
+            namespace_ids(svg, "sub"),
+            r##"<text data-ai-id="t">see url(#a) id="b"</text><!-- id="c" --><use href="#sub_d"/>"##
+        );
+    }
+
+    #[test]
+    fn test_strip_style() {
+        assert_eq!(
+            strip_style("\n  <style>:root { --accent-1: blue; }</style>\n  <rect/>"),
+            "\n  <rect/>"
+        );
+        assert_eq!(
+            strip_style("<rect/><style></style>"),
+            "<rect/><style></style>"
+        );
+    }
+}
@@ -0,0 +1,470 @@
+//! Expansion of `repeat` blocks and `let` variables
+//!
+//! Runs before templates are collected, so both can appear anywhere,
+//! including inside template bodies. Each copy of a repeat body has `$i`
+//! replaced in identifiers and strings; `let` variables are inlined into the
+//! modifier values that use them and numeric expressions such as
+//! `[x: $i * 40]` are evaluated. Inside a template body, expressions that
+//! depend on the template's parameters are kept until instantiation.
+
+use super::registry::TemplateError;
+use crate::parser::ast::{
+    AnchorPosition, AnchorReference, ConstraintExpr, ConstraintProperty, Identifier, KeyframeOp,
+    NumExpr, PathCommand, PropertyRef, ShapeType, Spanned, Statement, StyleModifier, StyleValue,
+};
+
+/// Upper bound on the iterations of a single repeat
+const MAX_REPEAT_COUNT: f64 = 1000.0;
+
+/// Unroll every repeat in `stmts` and inline every `let`
+pub fn expand_statements(
+    stmts: Vec<Spanned<Statement>>,
+) -> Result<Vec<Spanned<Statement>>, TemplateError> {
+    expand(stmts, &Scope::default())
+}
+
+/// Expand the statements of a template file, which like a template body
+/// may use the template's parameters in expressions
+pub(crate) fn expand_template_body(
+    stmts: Vec<Spanned<Statement>>,
+) -> Result<Vec<Spanned<Statement>>, TemplateError> {
+    let scope = Scope {
+        in_template: true,
+        ..Scope::default()
+    };
+    expand(stmts, &scope)
+}
+
+/// Variables in scope, innermost last
+#[derive(Debug, Clone, Default)]
+struct Scope {
+    /// `repeat` loop variables, without the `$`
+    loops: Vec<(String, f64)>,
+    /// `let` variables; a non-constant expression only inside template bodies
+    lets: Vec<(String, NumExpr)>,
+    /// Inside a template body, where parameters are bound on instantiation
+    in_template: bool,
+}
+
+impl Scope {
+    fn lookup(&self, name: &str) -> Option<NumExpr> {
+        match name.strip_prefix('$') {
+            Some(var) => self
+                .loops
+                .iter()
+                .rev()
+                .find(|(n, _)| n == var)
+                .map(|(_, v)| NumExpr::Number(*v)),
+            None => self
+                .lets
+                .iter()
+                .rev()
+                .find(|(n, _)| n == name)
+                .map(|(_, e)| e.clone()),
+        }
+    }
+
+    /// Substitute the variables in scope; an expression that is still not a
+    /// number is an error outside template bodies
+    fn resolve(&self, expr: &NumExpr) -> Result<NumExpr, TemplateError> {
+        let expr = expr.substitute(&|name| self.lookup(name));
+        if !self.in_template {
+            expr.eval(&|_| None)
+                .map_err(|message| TemplateError::Expansion { message })?;
+        }
+        Ok(expr)
+    }
+
+    fn number(&self, expr: &NumExpr) -> Result<f64, TemplateError> {
+        expr.substitute(&|name| self.lookup(name))
+            .eval(&|_| None)
+            .map_err(|message| TemplateError::Expansion { message })
+    }
+
+    /// Replace `$name` by the variable's value; the longest bound name wins,
+    /// so `$index` is not read as `$i` followed by `ndex`
+    fn substitute(&self, text: &str) -> String {
+        if self.loops.is_empty() || !text.contains('$') {
+            return text.to_string();
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(pos) = rest.find('$') {
+            out.push_str(&rest[..pos]);
+            let after = &rest[pos + 1..];
+            let bound = self
+                .loops
+                .iter()
+                .rev()
+                .filter(|(name, _)| after.starts_with(name.as_str()))
+                .max_by_key(|(name, _)| name.len());
+            match bound {
+                Some((name, value)) => {
+                    out.push_str(&value.to_string());
+                    rest = &after[name.len()..];
+                }
+                None => {
+                    out.push('$');
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+
+    fn identifier(&self, id: &mut Identifier) {
+        id.0 = self.substitute(&id.0);
+    }
+
+    fn value(&self, value: &mut StyleValue) -> Result<(), TemplateError> {
+        match value {
+            StyleValue::String(s) => *s = self.substitute(s),
+            StyleValue::Identifier(id) => match self.lookup(id.as_str()) {
+                Some(expr) => *value = expr_value(expr),
+                None => self.identifier(id),
+            },
+            StyleValue::IdentifierList(ids) => ids.iter_mut().for_each(|id| self.identifier(id)),
+            StyleValue::Function { args, .. } => {
+                for arg in args {
+                    self.value(&mut arg.node)?;
+                }
+            }
+            StyleValue::Expr(expr) => *value = expr_value(self.resolve(expr)?),
+            StyleValue::Color(_) | StyleValue::Number { .. } | StyleValue::Keyword(_) => {}
+        }
+        Ok(())
+    }
+
+    fn modifiers(&self, modifiers: &mut [Spanned<StyleModifier>]) -> Result<(), TemplateError> {
+        for m in modifiers {
+            self.value(&mut m.node.value.node)?;
+        }
+        Ok(())
+    }
+
+    fn anchor_ref(&self, r: &mut AnchorReference) {
+        self.identifier(&mut r.element.node);
+        if let Some(anchor) = &mut r.anchor {
+            anchor.node = self.substitute(&anchor.node);
+        }
+    }
+
+    fn property(&self, p: &mut PropertyRef) {
+        for segment in &mut p.element.node.segments {
+            self.identifier(&mut segment.node);
+        }
+        if let ConstraintProperty::AnchorX(name) | ConstraintProperty::AnchorY(name) =
+            &mut p.property.node
+        {
+            *name = self.substitute(name);
+        }
+    }
+
+    fn constraint(&self, expr: &mut ConstraintExpr) {
+        match expr {
+            ConstraintExpr::Equal { left, right }
+            | ConstraintExpr::EqualWithOffset { left, right, .. } => {
+                self.property(left);
+                self.property(right);
+            }
+            ConstraintExpr::Constant { left, .. }
+            | ConstraintExpr::GreaterOrEqual { left, .. }
+            | ConstraintExpr::LessOrEqual { left, .. } => self.property(left),
+            ConstraintExpr::Midpoint { target, a, b, .. } => {
+                self.property(target);
+                self.identifier(&mut a.node);
+                self.identifier(&mut b.node);
+            }
+            ConstraintExpr::Contains {
+                container,
+                elements,
+                ..
+            } => {
+                self.identifier(&mut container.node);
+                elements
+                    .iter_mut()
+                    .for_each(|e| self.identifier(&mut e.node));
+            }
+            ConstraintExpr::Linear { left, terms, .. } => {
+                self.property(left);
+                terms
+                    .iter_mut()
+                    .for_each(|t| self.property(&mut t.property));
+            }
+            ConstraintExpr::EqualSize { elements, .. } => {
+                elements
+                    .iter_mut()
+                    .for_each(|e| self.identifier(&mut e.node));
+            }
+            ConstraintExpr::Relative {
+                subject, anchor, ..
+            } => {
+                self.identifier(&mut subject.node);
+                self.identifier(&mut anchor.node);
+            }
+        }
+    }
+
+    fn statement(&self, stmt: &mut Statement) -> Result<(), TemplateError> {
+        let name = |name: &mut Option<Spanned<Identifier>>| {
+            if let Some(name) = name {
+                self.identifier(&mut name.node);
+            }
+        };
+        match stmt {
+            Statement::Shape(s) => {
+                name(&mut s.name);
+                match &mut s.shape_type.node {
+                    ShapeType::Text { content } => *content = self.substitute(content),
+                    ShapeType::Icon { icon_name, .. } => *icon_name = self.substitute(icon_name),
+                    ShapeType::RasterImage { path, .. } => *path = self.substitute(path),
+                    ShapeType::Path(path) => {
+                        name(&mut path.name);
+                        self.modifiers(&mut path.modifiers)?;
+                        for cmd in &mut path.body.commands {
+                            match &mut cmd.node {
+                                PathCommand::Vertex(v) => self.identifier(&mut v.name.node),
+                                PathCommand::LineTo(l) => self.identifier(&mut l.target.node),
+                                PathCommand::ArcTo(a) => self.identifier(&mut a.target.node),
+                                PathCommand::CurveTo(c) => {
+                                    self.identifier(&mut c.target.node);
+                                    name(&mut c.via);
+                                }
+                                PathCommand::Close | PathCommand::CloseArc(_) => {}
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                self.modifiers(&mut s.modifiers)?;
+            }
+            Statement::Connection(conns) => {
+                for conn in conns {
+                    self.anchor_ref(&mut conn.from);
+                    self.anchor_ref(&mut conn.to);
+                    name(&mut conn.name);
+                    self.modifiers(&mut conn.modifiers)?;
+                }
+            }
+            Statement::Layout(l) => {
+                name(&mut l.name);
+                self.modifiers(&mut l.modifiers)?;
+                l.children = expand(std::mem::take(&mut l.children), self)?;
+            }
+            Statement::Group(g) => {
+                name(&mut g.name);
+                name(&mut g.layer);
+                self.modifiers(&mut g.modifiers)?;
+                g.children = expand(std::mem::take(&mut g.children), self)?;
+            }
+            Statement::Constraint(c) => {
+                self.identifier(&mut c.subject.node);
+                name(&mut c.anchor);
+                self.modifiers(&mut c.modifiers)?;
+            }
+            Statement::Label(inner) => self.statement(inner)?,
+            Statement::Constrain(c) => self.constraint(&mut c.expr),
+            Statement::TemplateDecl(t) => {
+                for param in &mut t.parameters {
+                    self.value(&mut param.default_value.node)?;
+                }
+                if let Some(body) = &mut t.body {
+                    let scope = Scope {
+                        in_template: true,
+                        ..self.clone()
+                    };
+                    *body = expand(std::mem::take(body), &scope)?;
+                }
+            }
+            Statement::TemplateInstance(inst) => {
+                self.identifier(&mut inst.instance_name.node);
+                for (_, value) in &mut inst.arguments {
+                    self.value(&mut value.node)?;
+                }
+                inst.children = expand(std::mem::take(&mut inst.children), self)?;
+            }
+            Statement::Embed(e) => {
+                self.identifier(&mut e.name.node);
+                self.modifiers(&mut e.modifiers)?;
+            }
+            Statement::Export(e) => e
+                .exports
+                .iter_mut()
+                .for_each(|id| self.identifier(&mut id.node)),
+            Statement::AnchorDecl(a) => {
+                self.identifier(&mut a.name.node);
+                match &mut a.position {
+                    AnchorPosition::PropertyRef(p)
+                    | AnchorPosition::PropertyRefWithOffset { prop_ref: p, .. } => self.property(p),
+                }
+            }
+            Statement::Keyframe(kf) => {
+                for op in &mut kf.operations {
+                    match &mut op.node {
+                        KeyframeOp::Show(ids) | KeyframeOp::Hide(ids) => {
+                            ids.iter_mut().for_each(|id| self.identifier(&mut id.node));
+                        }
+                        KeyframeOp::Transform { target, modifiers } => {
+                            self.identifier(&mut target.node);
+                            self.modifiers(modifiers)?;
+                        }
+                    }
+                }
+            }
+            Statement::Note(n) => {
+                self.anchor_ref(&mut n.target);
+                self.modifiers(&mut n.modifiers)?;
+                n.children = expand(std::mem::take(&mut n.children), self)?;
+            }
+            Statement::If(i) => {
+                i.then_body = expand(std::mem::take(&mut i.then_body), self)?;
+                i.else_body = expand(std::mem::take(&mut i.else_body), self)?;
+            }
+            Statement::Step(s) => s.children = expand(std::mem::take(&mut s.children), self)?,
+            Statement::Title(t) => self.modifiers(&mut t.modifiers)?,
+            Statement::Legend(l) => {
+                self.modifiers(&mut l.modifiers)?;
+                for entry in &mut l.entries {
+                    self.value(&mut entry.node.color.node)?;
+                    self.modifiers(&mut entry.node.modifiers)?;
+                }
+            }
+            Statement::Animate(a) => {
+                self.identifier(&mut a.target.node);
+                self.modifiers(&mut a.modifiers)?;
+            }
+            Statement::Import(_) | Statement::Slot | Statement::Canvas(_) => {}
+            // Repeats and lets are handled by `expand`
+            Statement::Repeat(_) | Statement::Let(_) => {}
+        }
+        Ok(())
+    }
+}
+
+/// Modifier value for an expression: a number once it is constant
+fn expr_value(expr: NumExpr) -> StyleValue {
+    match expr {
+        NumExpr::Number(value) => StyleValue::Number { value, unit: None },
+        expr => StyleValue::Expr(expr),
+    }
+}
+
+fn expand(
+    stmts: Vec<Spanned<Statement>>,
+    scope: &Scope,
+) -> Result<Vec<Spanned<Statement>>, TemplateError> {
+    let mut scope = scope.clone();
+    let mut out = Vec::with_capacity(stmts.len());
+    for mut stmt in stmts {
+        if let Statement::Let(decl) = stmt.node {
+            let value = scope.resolve(&decl.value.node)?;
+            scope.lets.push((decl.name.node.0, value));
+            continue;
+        }
+        if let Statement::Repeat(repeat) = stmt.node {
+            let count = scope.number(&repeat.count.node)?;
+            if count.fract() != 0.0 || !(0.0..=MAX_REPEAT_COUNT).contains(&count) {
+                return Err(TemplateError::Expansion {
+                    message: format!(
+                        "repeat count must be a whole number from 0 to {}, got {}",
+                        MAX_REPEAT_COUNT, count
+                    ),
+                });
+            }
+            for index in 0..count as usize {
+                let mut inner = scope.clone();
+                inner
+                    .loops
+                    .push((repeat.variable.node.0.clone(), index as f64));
+                out.extend(expand(repeat.body.clone(), &inner)?);
+            }
+            continue;
+        }
+        scope.statement(&mut stmt.node)?;
+        out.push(stmt);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_expand_statements() {
+        let doc = parse(
+            r#"repeat 2 as r {
+                repeat 2 as c {
+                    rect cell_$r_$c [label: "R$r C$c", x: $c * 40 + 10]
+                }
+            }
+            constrain cell_1_1.left = cell_0_0.right"#,
+        )
+        .unwrap();
+        let expanded = expand_statements(doc.statements).unwrap();
+        let outline = crate::Document {
+            statements: expanded.clone(),
+            comments: vec![],
+        }
+        .outline();
+        assert_eq!(
+            outline,
+            "rect cell_0_0 \"R0 C0\"\nrect cell_0_1 \"R0 C1\"\nrect cell_1_0 \"R1 C0\"\nrect cell_1_1 \"R1 C1\"\nconstraints: 1\n"
+        );
+        match &expanded[3].node {
+            Statement::Shape(s) => assert_eq!(
+                s.modifiers[1].node.value.node,
+                StyleValue::Number {
+                    value: 50.0,
+                    unit: None
+                }
+            ),
+            other => panic!("expected shape, got {:?}", other),
+        }
+
+        let unbound = parse("rect a [x: $n * 2]").unwrap();
+        let err = expand_statements(unbound.statements).unwrap_err();
+        assert!(
+            err.to_string().contains("undefined loop variable '$n'"),
+            "{}",
+            err
+        );
+
+        let too_many = parse("repeat 1.5 as i { rect a_$i }").unwrap();
+        assert!(expand_statements(too_many.statements).is_err());
+    }
+
+    #[test]
+    fn test_let_variables() {
+        let doc = parse(
+            r#"let base = 40
+            let wide = base * 2 + 4
+            rect a [width: wide, height: base]
+            row r {
+                let base = 10
+                repeat 2 as i { rect b_$i [width: base * ($i + 1)] }
+            }
+            rect c [width: base / 2]"#,
+        )
+        .unwrap();
+        let expanded = expand_statements(doc.statements).unwrap();
+        let source = crate::parser::unparse(&crate::Document {
+            statements: expanded,
+            comments: vec![],
+        });
+        assert_eq!(
+            source,
+            "rect a [width: 84, height: 40]\nrow r {\n    rect b_0 [width: 10]\n    rect b_1 [width: 20]\n}\nrect c [width: 20]\n"
+        );
+
+        let undefined = parse("rect a [width: gap * 2]").unwrap();
+        let err = expand_statements(undefined.statements).unwrap_err();
+        assert!(
+            err.to_string().contains("undefined variable 'gap'"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! Icon lookup for `icon "name"` shapes
+//!
+//! Icons are resolved during template resolution by asking each configured
+//! [`IconResolver`] in turn. Icons that no resolver knows keep rendering as
+//! the labelled placeholder box.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Source of SVG markup for icon names such as `"aws/ec2"`
+pub trait IconResolver: std::fmt::Debug + Send + Sync {
+    /// SVG document for the icon, or `None` if this resolver doesn't have it
+    fn resolve(&self, name: &str) -> Option<String>;
+}
+
+/// Icon pack on disk: `icon "aws/ec2"` reads `<root>/aws/ec2.svg`
+#[derive(Debug, Clone)]
+pub struct IconDirectory {
+    root: PathBuf,
+}
+
+impl IconDirectory {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl IconResolver for IconDirectory {
+    fn resolve(&self, name: &str) -> Option<String> {
+        // Names are slash-separated segments; never leave the pack directory
+        let segments: Vec<&str> = name.split('/').collect();
+        if segments
+            .iter()
+            .any(|s| s.is_empty() || *s == "." || *s == ".." || s.contains('\\'))
+        {
+            return None;
+        }
+        let mut path = self.root.clone();
+        path.extend(&segments);
+        path.set_extension("svg");
+        std::fs::read_to_string(path).ok()
+    }
+}
+
+/// Icons supplied programmatically, keyed by name
+impl IconResolver for HashMap<String, String> {
+    fn resolve(&self, name: &str) -> Option<String> {
+        self.get(name).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_icon_directory() {
+        let dir = std::env::temp_dir().join(format!("ail-icons-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("aws")).unwrap();
+        std::fs::write(dir.join("aws/ec2.svg"), "<svg/>").unwrap();
+
+        let icons = IconDirectory::new(&dir);
+        assert_eq!(icons.resolve("aws/ec2").as_deref(), Some("<svg/>"));
+        assert_eq!(icons.resolve("aws/s3"), None);
+        assert_eq!(icons.resolve("../ail-icons/aws/ec2"), None);
+        assert_eq!(icons.resolve("/etc/passwd"), None);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,274 @@
+//! Dotted references into expanded template instances
+//!
+//! Expansion renames the children of instance `motor1` to `motor1_*`, so
+//! `motor1.terminal_a` is rewritten to `motor1_terminal_a`, and
+//! `rack.m1.terminal_a` reaches through the nested instance `rack_m1`. A
+//! template with an `export` list only exposes the children it lists, and the
+//! anchors it declares take precedence over children of the same name.
+//!
+//! Any other anchor on an instance that expanded to a group is an error that
+//! lists what the template does export, rather than a silent fallback to an
+//! edge.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::parser::ast::{
+    AnchorPosition, AnchorReference, ConstraintExpr, Document, ElementPath, Identifier,
+    PropertyRef, Span, Spanned, Statement,
+};
+use crate::parser::visit::{walk_statement, walk_statement_mut, Visitor, VisitorMut};
+use crate::scope::declared_name;
+
+use super::registry::{TemplateDefinition, TemplateError, TemplateRegistry};
+
+/// Anchors every element has, whatever its template exports
+const EDGE_ANCHORS: &[&str] = &[
+    "top",
+    "bottom",
+    "left",
+    "right",
+    "top_left",
+    "top_right",
+    "bottom_left",
+    "bottom_right",
+    "center",
+];
+
+/// Point connection endpoints and constraint paths that go through template
+/// instances at the expanded elements
+pub(super) fn resolve_member_paths(
+    doc: &mut Document,
+    registry: &TemplateRegistry,
+) -> Result<(), TemplateError> {
+    let mut elements = Elements::default();
+    elements.visit_document(doc);
+    let mut members = Members {
+        registry,
+        elements: &elements,
+        error: None,
+    };
+    members.visit_document(doc);
+    match members.error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+#[derive(Default)]
+struct Elements {
+    /// Ids of all declared elements
+    ids: HashSet<String>,
+    /// Ids of the direct children of each instance expanded to a group
+    groups: HashMap<String, Vec<String>>,
+}
+
+impl Visitor for Elements {
+    fn visit_statement(&mut self, stmt: &Statement) {
+        if let Some(name) = declared_name(stmt) {
+            self.ids.insert(name.node.0.clone());
+        }
+        if let Statement::Group(g) = stmt {
+            if let (true, Some(name)) = (g.is_template_instance, &g.name) {
+                let children = g
+                    .children
+                    .iter()
+                    .filter_map(|c| declared_name(&c.node))
+                    .map(|id| id.node.0.clone())
+                    .collect();
+                self.groups.insert(name.node.0.clone(), children);
+            }
+        }
+        walk_statement(self, stmt);
+    }
+}
+
+struct Members<'a> {
+    registry: &'a TemplateRegistry,
+    elements: &'a Elements,
+    error: Option<TemplateError>,
+}
+
+impl Members<'_> {
+    /// Id of child `member` of `owner`, when `owner` is an instance with one
+    fn member(
+        &self,
+        owner: &str,
+        member: &str,
+        span: &Span,
+    ) -> Result<Option<String>, TemplateError> {
+        let Some(def) = self.registry.instance_template(owner) else {
+            return Ok(None);
+        };
+        let id = format!("{}_{}", owner, member);
+        if !self.elements.ids.contains(&id) || def.anchors.iter().any(|a| a.name.node.0 == member) {
+            return Ok(None);
+        }
+        if !def.exports.is_empty() && !def.exports.iter().any(|e| e == member) {
+            return Err(self.not_exported(owner, def, member, span));
+        }
+        Ok(Some(id))
+    }
+
+    /// Error for `owner.export`, listing what the template does export:
+    /// its anchors, then its `export` list or, without one, all its children
+    fn not_exported(
+        &self,
+        owner: &str,
+        def: &TemplateDefinition,
+        export: &str,
+        span: &Span,
+    ) -> TemplateError {
+        let mut exports: Vec<String> = def.anchors.iter().map(|a| a.name.node.0.clone()).collect();
+        if def.exports.is_empty() {
+            let prefix = format!("{}_", owner);
+            let children = self.elements.groups.get(owner).into_iter().flatten();
+            exports.extend(children.filter_map(|c| c.strip_prefix(&prefix).map(String::from)));
+        } else {
+            exports.extend(def.exports.iter().cloned());
+        }
+        TemplateError::ExportNotFound {
+            template: def.name.clone(),
+            export: export.to_string(),
+            exports,
+            span: span.clone(),
+            instance_span: self
+                .registry
+                .instance_span(owner)
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The element `segments` names, each a child of the instance before it
+    fn path(
+        &self,
+        segments: &[&Spanned<Identifier>],
+    ) -> Result<Spanned<Identifier>, TemplateError> {
+        let span = segments[0].span.start..segments[segments.len() - 1].span.end;
+        let mut id = segments[0].node.0.clone();
+        for segment in &segments[1..] {
+            let name = segment.node.as_str();
+            id = match self.member(&id, name, &span)? {
+                Some(member) => member,
+                None => {
+                    return Err(match self.registry.instance_template(&id) {
+                        Some(def) => self.not_exported(&id, def, name, &span),
+                        None => TemplateError::MemberNotFound {
+                            path: segments
+                                .iter()
+                                .map(|s| s.node.as_str())
+                                .collect::<Vec<_>>()
+                                .join("."),
+                            span,
+                        },
+                    })
+                }
+            };
+        }
+        Ok(Spanned::new(Identifier::new(id), span))
+    }
+
+    /// A connection or note endpoint; in `motor1.terminal_a` the
+    /// `terminal_a` is a child of the instance when it has one
+    fn endpoint(&self, reference: &mut AnchorReference) -> Result<(), TemplateError> {
+        if !reference.scope.is_empty() {
+            let segments: Vec<_> = reference
+                .scope
+                .iter()
+                .chain(std::iter::once(&reference.element))
+                .collect();
+            reference.element = self.path(&segments)?;
+            reference.scope.clear();
+        }
+        let Some(anchor) = &reference.anchor else {
+            return Ok(());
+        };
+        let owner = reference.element.node.as_str();
+        let span = reference.element.span.start..anchor.span.end;
+        if let Some(id) = self.member(owner, &anchor.node, &span)? {
+            reference.element = Spanned::new(Identifier::new(id), span);
+            reference.anchor = None;
+            return Ok(());
+        }
+        // A group only has its edges and the anchors its template declares
+        if let (Some(def), true) = (
+            self.registry.instance_template(owner),
+            self.elements.groups.contains_key(owner),
+        ) {
+            let declared = def.anchors.iter().any(|a| a.name.node.0 == anchor.node);
+            if !declared && !EDGE_ANCHORS.contains(&anchor.node.as_str()) {
+                return Err(self.not_exported(owner, def, &anchor.node, &span));
+            }
+        }
+        Ok(())
+    }
+
+    fn element_path(&self, path: &mut Spanned<ElementPath>) -> Result<(), TemplateError> {
+        if !path.node.is_simple() {
+            let segments: Vec<_> = path.node.segments.iter().collect();
+            let id = self.path(&segments)?;
+            path.node = ElementPath::simple(id.node, id.span);
+        }
+        Ok(())
+    }
+
+    fn property(&self, prop: &mut PropertyRef) -> Result<(), TemplateError> {
+        self.element_path(&mut prop.element)
+    }
+
+    fn references(&self, stmt: &mut Statement) -> Result<(), TemplateError> {
+        match stmt {
+            Statement::Connection(conns) => {
+                for conn in conns {
+                    self.endpoint(&mut conn.from)?;
+                    self.endpoint(&mut conn.to)?;
+                }
+            }
+            Statement::Note(n) => self.endpoint(&mut n.target)?,
+            Statement::Constrain(c) => match &mut c.expr {
+                ConstraintExpr::Equal { left, right }
+                | ConstraintExpr::EqualWithOffset { left, right, .. } => {
+                    self.property(left)?;
+                    self.property(right)?;
+                }
+                ConstraintExpr::Constant { left, .. }
+                | ConstraintExpr::GreaterOrEqual { left, .. }
+                | ConstraintExpr::LessOrEqual { left, .. } => self.property(left)?,
+                ConstraintExpr::Midpoint { target, .. } => self.property(target)?,
+                ConstraintExpr::Linear { left, terms, .. } => {
+                    self.property(left)?;
+                    for term in terms {
+                        self.property(&mut term.property)?;
+                    }
+                }
+                ConstraintExpr::Contains { .. }
+                | ConstraintExpr::EqualSize { .. }
+                | ConstraintExpr::Relative { .. } => {}
+            },
+            // Template anchors can be placed on a nested instance's children
+            Statement::Group(g) => {
+                for anchor in &mut g.anchors {
+                    let (AnchorPosition::PropertyRef(prop_ref)
+                    | AnchorPosition::PropertyRefWithOffset { prop_ref, .. }) =
+                        &mut anchor.position;
+                    self.property(prop_ref)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl VisitorMut for Members<'_> {
+    fn visit_statement(&mut self, stmt: &mut Statement) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(e) = self.references(stmt) {
+            self.error = Some(e);
+            return;
+        }
+        walk_statement_mut(self, stmt);
+    }
+}
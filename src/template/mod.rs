@@ -17,8 +17,18 @@
 //! server myserver [fill: red, size: 100]
 //! ```
 
+mod embed;
+mod expand;
+mod icons;
+mod members;
+mod recolor;
 mod registry;
 mod resolver;
+pub mod sanitize;
 
-pub use registry::{TemplateDefinition, TemplateError, TemplateRegistry};
-pub use resolver::{resolve_templates, ResolutionContext};
+pub use expand::expand_statements;
+pub use icons::{IconDirectory, IconResolver};
+pub use registry::{
+    TemplateDefinition, TemplateError, TemplateRegistry, DEFAULT_MAX_TEMPLATE_DEPTH,
+};
+pub use resolver::{expand_document, resolve_templates, ResolutionContext};
@@ -0,0 +1,250 @@
+//! Fill and stroke substitution in SVG template content
+//!
+//! `icon logo [fill: red]` on an SVG template repaints the file's painted
+//! areas: every `fill` attribute, `style` declaration and `<style>` rule that
+//! sets a color (not `none` or a gradient `url(...)`) gets the new one, and
+//! elements that inherit the default black fill get it from a group wrapped
+//! around the content. `stroke` repaints the outlines the file already has.
+
+use super::sanitize::{find_tag_end, tag_name};
+
+/// Repaint `svg` with the given fill and stroke colors
+pub(super) fn recolor_svg(svg: &str, fill: Option<&str>, stroke: Option<&str>) -> String {
+    if fill.is_none() && stroke.is_none() {
+        return svg.to_string();
+    }
+    let paint = Paint { fill, stroke };
+
+    let mut out = String::with_capacity(svg.len());
+    let mut in_style = false;
+    let mut wrapped = false;
+    let mut unwrapped = false;
+    let mut rest = svg;
+    while let Some(lt) = rest.find('<') {
+        let text = &rest[..lt];
+        if in_style {
+            out.push_str(&paint.css(text));
+        } else {
+            out.push_str(text);
+        }
+        rest = &rest[lt..];
+
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            let end = if rest.starts_with("<!--") {
+                rest.find("-->").map_or(rest.len(), |i| i + 3)
+            } else if rest.starts_with("<![CDATA[") {
+                rest.find("]]>").map_or(rest.len(), |i| i + 3)
+            } else {
+                rest.find('>').map_or(rest.len(), |i| i + 1)
+            };
+            let (markup, after) = rest.split_at(end);
+            match markup.strip_prefix("<![CDATA[") {
+                Some(inner) if in_style => {
+                    let inner = inner.strip_suffix("]]>").unwrap_or(inner);
+                    out.push_str("<![CDATA[");
+                    out.push_str(&paint.css(inner));
+                    out.push_str("]]>");
+                }
+                _ => out.push_str(markup),
+            }
+            rest = after;
+            continue;
+        }
+
+        let end = find_tag_end(rest);
+        let (tag, after) = rest.split_at(end);
+        rest = after;
+        let name = tag_name(tag);
+        if name == "style" {
+            in_style = !tag.starts_with("</") && !tag.ends_with("/>");
+        }
+        if tag.starts_with("</") {
+            if name == "svg" && wrapped && !unwrapped && !rest.contains("</svg") {
+                // The group opened after the root tag
+                out.push_str("</g>");
+                unwrapped = true;
+            }
+            out.push_str(tag);
+            continue;
+        }
+        out.push_str(&paint.tag(tag));
+        if name == "svg" && !wrapped && !tag.ends_with("/>") {
+            // The root's own paint is dropped when the content is inlined,
+            // so it moves to a group around the content
+            out.push_str(&paint.root_group(tag));
+            wrapped = true;
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+struct Paint<'a> {
+    fill: Option<&'a str>,
+    stroke: Option<&'a str>,
+}
+
+impl Paint<'_> {
+    /// The color that replaces `value` of property `name`, if it does
+    fn replacement(&self, name: &str, value: &str) -> Option<&str> {
+        let color = match name {
+            "fill" => self.fill?,
+            "stroke" => self.stroke?,
+            _ => return None,
+        };
+        is_color(value).then_some(color)
+    }
+
+    /// A start tag with its paint attributes and `style` repainted
+    fn tag(&self, tag: &str) -> String {
+        let mut out = String::with_capacity(tag.len());
+        let mut rest = tag;
+        while let Some(eq) = rest.find('=') {
+            let name_start = rest[..eq]
+                .trim_end()
+                .rfind(char::is_whitespace)
+                .map_or(0, |i| i + 1);
+            let name = rest[name_start..eq].trim().to_ascii_lowercase();
+            let after_eq = &rest[eq + 1..];
+            let Some(quote) = after_eq
+                .trim_start()
+                .chars()
+                .next()
+                .filter(|c| *c == '"' || *c == '\'')
+            else {
+                break;
+            };
+            let value_start = eq + 1 + (after_eq.len() - after_eq.trim_start().len()) + 1;
+            let Some(value_len) = rest[value_start..].find(quote) else {
+                break;
+            };
+            let value = &rest[value_start..value_start + value_len];
+            out.push_str(&rest[..value_start]);
+            match (name.as_str(), self.replacement(&name, value)) {
+                (_, Some(color)) => out.push_str(&attribute_text(color)),
+                ("style", None) => out.push_str(&self.css(value)),
+                _ => out.push_str(value),
+            }
+            rest = &rest[value_start + value_len..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// CSS declarations (a `style` attribute or `<style>` rules) repainted
+    fn css(&self, css: &str) -> String {
+        let mut out = String::with_capacity(css.len());
+        let mut rest = css;
+        while let Some(colon) = rest.find(':') {
+            let name_start = rest[..colon]
+                .rfind(|c: char| c == ';' || c == '{' || c.is_whitespace())
+                .map_or(0, |i| i + 1);
+            let name = rest[name_start..colon].trim().to_ascii_lowercase();
+            let value_end = rest[colon + 1..]
+                .find([';', '}'])
+                .map_or(rest.len(), |i| colon + 1 + i);
+            let value = &rest[colon + 1..value_end];
+            out.push_str(&rest[..=colon]);
+            match self.replacement(&name, value.trim()) {
+                Some(color) => {
+                    let lead = value.len() - value.trim_start().len();
+                    out.push_str(&value[..lead]);
+                    out.push_str(color);
+                    out.push_str(&value[value.trim_end().len()..]);
+                }
+                None => out.push_str(value),
+            }
+            rest = &rest[value_end..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// The group that takes over the root element's fill and stroke
+    fn root_group(&self, root: &str) -> String {
+        let mut attrs = String::new();
+        let root_fill = attribute(root, "fill");
+        match (self.fill, root_fill) {
+            // An unfilled icon stays unfilled
+            (_, Some(value)) if !is_color(value) => {
+                attrs.push_str(&format!(r#" fill="{}""#, attribute_text(value)))
+            }
+            (Some(fill), _) => attrs.push_str(&format!(r#" fill="{}""#, attribute_text(fill))),
+            (None, Some(value)) => attrs.push_str(&format!(r#" fill="{}""#, attribute_text(value))),
+            (None, None) => {}
+        }
+        if let Some(value) = attribute(root, "stroke") {
+            let stroke = self.replacement("stroke", value).unwrap_or(value);
+            attrs.push_str(&format!(r#" stroke="{}""#, attribute_text(stroke)));
+        }
+        format!("<g{}>", attrs)
+    }
+}
+
+/// Whether a paint value is a color that can be replaced
+fn is_color(value: &str) -> bool {
+    let value = value.trim().to_ascii_lowercase();
+    !(value.is_empty() || value == "none" || value == "transparent" || value.starts_with("url("))
+}
+
+/// The value of attribute `name` in a start tag
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    ['"', '\''].into_iter().find_map(|quote| {
+        let needle = format!(" {}={}", name, quote);
+        let start = tag.find(&needle)? + needle.len();
+        let end = start + tag[start..].find(quote)?;
+        Some(&tag[start..end])
+    })
+}
+
+/// A color written as an attribute value
+fn attribute_text(color: &str) -> String {
+    color.replace('&', "&amp;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recolor_attributes_and_styles() {
+        let svg = concat!(
+            r#"<svg viewBox="0 0 10 10">"#,
+            r##"<path fill="#000" stroke='black' d="M0 0"/>"##,
+            r##"<rect fill="none" style="fill: #123; stroke-width: 2"/>"##,
+            r##"<circle fill="url(#g)"/>"##,
+            "</svg>"
+        );
+        let out = recolor_svg(svg, Some("red"), Some("blue"));
+        assert_eq!(
+            out,
+            concat!(
+                r#"<svg viewBox="0 0 10 10"><g fill="red">"#,
+                r#"<path fill="red" stroke='blue' d="M0 0"/>"#,
+                r#"<rect fill="none" style="fill: red; stroke-width: 2"/>"#,
+                r##"<circle fill="url(#g)"/>"##,
+                "</g></svg>"
+            )
+        );
+    }
+
+    #[test]
+    fn test_recolor_keeps_unfilled_roots_and_style_rules() {
+        let svg = concat!(
+            r#"<svg fill="none" stroke="currentColor">"#,
+            "<style>.a { fill: #fff; stroke: #000 }</style>",
+            r#"<path d="M0 0"/></svg>"#
+        );
+        let out = recolor_svg(svg, Some("red"), Some("blue"));
+        assert!(out.contains(r#"<g fill="none" stroke="blue">"#), "{}", out);
+        assert!(out.contains(".a { fill: red; stroke: blue }"), "{}", out);
+        assert!(
+            out.contains(r#"<svg fill="none" stroke="blue">"#),
+            "{}",
+            out
+        );
+
+        // Nothing to substitute
+        assert_eq!(recolor_svg(svg, None, None), svg);
+    }
+}
@@ -1,14 +1,22 @@
 //! Template registry for storing and retrieving template definitions
 
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use thiserror::Error;
 
+use super::icons::IconResolver;
 use crate::parser::ast::{
-    AnchorDecl, ExportDecl, ParameterDef, Spanned, Statement, StyleValue, TemplateDecl,
-    TemplateSourceType,
+    AnchorDecl, ExportDecl, IconSvg, ParameterDef, ShapeType, Span, Spanned, Statement, StyleValue,
+    TemplateDecl, TemplateSourceType,
 };
-use crate::ImageHrefMode;
+use crate::renderer::{ShapeRenderer, ShapeRenderers};
+use crate::stylesheet::Stylesheet;
+use crate::{ImageHrefMode, SvgEmbedPolicy};
+
+/// How deep template instances nest by default, counting each instance
+/// inside another template's body as one level
+pub const DEFAULT_MAX_TEMPLATE_DEPTH: usize = 16;
 
 /// Errors that can occur during template operations
 #[derive(Debug, Error)]
@@ -25,18 +33,37 @@ pub enum TemplateError {
     #[error("missing required parameter: {param} for template {template}")]
     MissingParameter { template: String, param: String },
 
-    /// Invalid parameter type
-    #[error("invalid parameter type for {param}: expected {expected}")]
-    InvalidParameterType { param: String, expected: String },
+    /// Argument or default that does not match the parameter's declared type
+    #[error("invalid value {found} for parameter {param}: expected {expected}")]
+    InvalidParameterType {
+        param: String,
+        expected: String,
+        found: String,
+        span: Span,
+    },
 
     /// File not found for file-based template
     #[error("template file not found: {path}")]
     FileNotFound { path: PathBuf },
 
+    /// Missing file for an `image` shape
+    #[error("image file not found: {}", path.display())]
+    ImageNotFound { path: PathBuf, span: Span },
+
     /// Error reading template file
     #[error("error reading template file {path}: {message}")]
     FileReadError { path: PathBuf, message: String },
 
+    /// Error rendering an `embed` target, kept whole with its own spans
+    /// (which point into the embedded file)
+    #[error("in embedded document {}: {source}", path.display())]
+    Embedded {
+        path: PathBuf,
+        source: Box<crate::RenderError>,
+        /// The `embed` statement
+        span: Span,
+    },
+
     /// Invalid SVG content
     #[error("invalid SVG content: {message}")]
     InvalidSvg { message: String },
@@ -45,9 +72,65 @@ pub enum TemplateError {
     #[error("circular template reference detected: {chain}")]
     CircularReference { chain: String },
 
-    /// Export not found in template
-    #[error("exported identifier not found in template {template}: {export}")]
-    ExportNotFound { template: String, export: String },
+    /// Template instances nested deeper than the configured limit
+    #[error(
+        "template instances nest more than {limit} deep ({chain}); give a recursive template a base case with 'if' or raise the limit"
+    )]
+    RecursionLimit { chain: String, limit: usize },
+
+    /// Reference to an anchor or child that a template instance does not export
+    #[error(
+        "template {template} has no anchor or exported element {export}; it exports {}",
+        export_list(exports)
+    )]
+    ExportNotFound {
+        template: String,
+        export: String,
+        /// Anchors and elements the template does export
+        exports: Vec<String>,
+        /// The reference
+        span: Span,
+        /// Where the instance is created
+        instance_span: Span,
+    },
+
+    /// Dotted path into a template instance that names none of its children
+    #[error("{path} does not name an element of a template instance")]
+    MemberNotFound { path: String, span: Span },
+
+    /// Repeat, variable or numeric expression that cannot be expanded
+    #[error("cannot expand: {message}")]
+    Expansion { message: String },
+
+    /// `if` that is outside a template body or names an unknown parameter
+    #[error("invalid condition: {message}")]
+    InvalidCondition { message: String },
+
+    /// Slot misuse, or instance children with nowhere to go
+    #[error("invalid slot: {message}")]
+    InvalidSlot { message: String },
+}
+
+impl TemplateError {
+    /// Get the source span if available
+    pub fn span(&self) -> Option<&Span> {
+        match self {
+            Self::InvalidParameterType { span, .. }
+            | Self::ExportNotFound { span, .. }
+            | Self::MemberNotFound { span, .. }
+            | Self::ImageNotFound { span, .. }
+            | Self::Embedded { span, .. } => Some(span),
+            _ => None,
+        }
+    }
+}
+
+fn export_list(exports: &[String]) -> String {
+    if exports.is_empty() {
+        "only its edges".to_string()
+    } else {
+        exports.join(", ")
+    }
 }
 
 /// A stored template definition
@@ -147,6 +230,24 @@ pub struct TemplateRegistry {
     base_path: Option<PathBuf>,
     /// How image href paths are emitted in SVG output
     image_href_mode: ImageHrefMode,
+    /// How embedded SVG content is sanitized when loaded
+    svg_embed_policy: SvgEmbedPolicy,
+    /// Documents currently being embedded, outermost first (cycle detection)
+    embed_chain: Vec<PathBuf>,
+    /// Stylesheet of the outermost document, which embedded documents share
+    stylesheet: Stylesheet,
+    /// Template libraries currently being imported, outermost first (cycle detection)
+    import_chain: Vec<PathBuf>,
+    /// Where `icon "name"` markup is looked up, in order
+    icon_resolvers: Vec<Arc<dyn IconResolver>>,
+    /// Icons already looked up (including misses)
+    icon_cache: HashMap<String, Option<IconSvg>>,
+    /// Application-defined shapes, instantiated like templates
+    shape_renderers: ShapeRenderers,
+    /// Template each expanded instance was made from and where, by instance id
+    instances: HashMap<String, (String, Span)>,
+    /// How deep instances may nest; [`DEFAULT_MAX_TEMPLATE_DEPTH`] when unset
+    max_depth: Option<usize>,
 }
 
 impl TemplateRegistry {
@@ -161,6 +262,15 @@ impl TemplateRegistry {
             templates: HashMap::new(),
             base_path: Some(base_path),
             image_href_mode: ImageHrefMode::default(),
+            svg_embed_policy: SvgEmbedPolicy::default(),
+            embed_chain: vec![],
+            stylesheet: Stylesheet::default(),
+            import_chain: vec![],
+            icon_resolvers: vec![],
+            icon_cache: HashMap::new(),
+            shape_renderers: ShapeRenderers::new(),
+            instances: HashMap::new(),
+            max_depth: None,
         }
     }
 
@@ -197,6 +307,34 @@ impl TemplateRegistry {
         self.templates.get_mut(name)
     }
 
+    /// Set how deep template instances may nest
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = Some(depth);
+    }
+
+    /// How deep template instances may nest
+    pub fn max_depth(&self) -> usize {
+        self.max_depth.unwrap_or(DEFAULT_MAX_TEMPLATE_DEPTH)
+    }
+
+    /// Record that instance `instance` was expanded from template `template`
+    pub fn record_instance(&mut self, instance: &str, template: &str, span: Span) {
+        self.instances
+            .insert(instance.to_string(), (template.to_string(), span));
+    }
+
+    /// The template an expanded instance was made from
+    pub fn instance_template(&self, instance: &str) -> Option<&TemplateDefinition> {
+        self.instances
+            .get(instance)
+            .and_then(|(template, _)| self.templates.get(template))
+    }
+
+    /// Where an expanded instance was created
+    pub fn instance_span(&self, instance: &str) -> Option<&Span> {
+        self.instances.get(instance).map(|(_, span)| span)
+    }
+
     /// Check if a template exists
     pub fn contains(&self, name: &str) -> bool {
         self.templates.contains_key(name)
@@ -227,6 +365,82 @@ impl TemplateRegistry {
         self.image_href_mode
     }
 
+    /// Set the sanitization policy for embedded SVG content
+    pub fn set_svg_embed_policy(&mut self, policy: SvgEmbedPolicy) {
+        self.svg_embed_policy = policy;
+    }
+
+    /// Get the sanitization policy for embedded SVG content
+    pub fn svg_embed_policy(&self) -> SvgEmbedPolicy {
+        self.svg_embed_policy
+    }
+
+    /// Set the chain of documents being embedded around this one
+    pub fn set_embed_chain(&mut self, chain: Vec<PathBuf>) {
+        self.embed_chain = chain;
+    }
+
+    /// Set the stylesheet embedded documents are rendered with
+    pub fn set_stylesheet(&mut self, stylesheet: Stylesheet) {
+        self.stylesheet = stylesheet;
+    }
+
+    /// Get the stylesheet embedded documents are rendered with
+    pub fn stylesheet(&self) -> &Stylesheet {
+        &self.stylesheet
+    }
+
+    /// Set where icon markup is looked up
+    pub fn set_icon_resolvers(&mut self, resolvers: Vec<Arc<dyn IconResolver>>) {
+        self.icon_resolvers = resolvers;
+        self.icon_cache.clear();
+    }
+
+    /// Set the renderers of application-defined shapes
+    pub fn set_shape_renderers(&mut self, renderers: ShapeRenderers) {
+        self.shape_renderers = renderers;
+    }
+
+    /// Renderer of the application-defined shape `name`
+    pub fn shape_renderer(&self, name: &str) -> Option<&Arc<dyn ShapeRenderer>> {
+        self.shape_renderers.get(name)
+    }
+
+    /// Get the configured icon resolvers
+    pub fn icon_resolvers(&self) -> &[Arc<dyn IconResolver>] {
+        &self.icon_resolvers
+    }
+
+    /// Look up an icon in the configured resolvers (first match wins)
+    ///
+    /// The markup is sanitized with the embed policy. Results are cached, so
+    /// an icon used many times is read once.
+    pub fn resolve_icon(&mut self, name: &str) -> Option<IconSvg> {
+        if let Some(cached) = self.icon_cache.get(name) {
+            return cached.clone();
+        }
+        let icon = self
+            .icon_resolvers
+            .iter()
+            .find_map(|r| r.resolve(name))
+            .map(|svg| {
+                let content = super::sanitize::sanitize_svg(&svg, self.svg_embed_policy);
+                let (width, height) = parse_svg_dimensions(&content).unwrap_or((24.0, 24.0));
+                IconSvg {
+                    content,
+                    width,
+                    height,
+                }
+            });
+        self.icon_cache.insert(name.to_string(), icon.clone());
+        icon
+    }
+
+    /// Get the chain of documents being embedded around this one
+    pub fn embed_chain(&self) -> &[PathBuf] {
+        &self.embed_chain
+    }
+
     /// Resolve a relative path to an absolute path
     pub fn resolve_path(&self, relative: &str) -> PathBuf {
         if let Some(base) = &self.base_path {
@@ -277,6 +491,19 @@ impl TemplateRegistry {
         }
     }
 
+    /// Build a raster image shape: the href per the configured mode, plus the
+    /// pixel size from the file header if the file can be read
+    pub fn load_raster_image(&self, relative: &str) -> ShapeType {
+        let size = std::fs::read(self.resolve_path(relative))
+            .ok()
+            .and_then(|bytes| raster_dimensions(&bytes));
+        ShapeType::RasterImage {
+            path: self.resolve_image_href(relative),
+            intrinsic_width: size.map(|(w, _)| w),
+            intrinsic_height: size.map(|(_, h)| h),
+        }
+    }
+
     /// Load SVG content for a file-based template
     pub fn load_svg_template(&mut self, name: &str) -> Result<(), TemplateError> {
         let def = self
@@ -309,6 +536,9 @@ impl TemplateRegistry {
                 message: e.to_string(),
             })?;
 
+        // Strip scripts, event handlers, and external references before inlining
+        let content = super::sanitize::sanitize_svg(&content, self.svg_embed_policy);
+
         // Parse SVG dimensions from viewBox or width/height attributes
         let dimensions = parse_svg_dimensions(&content);
 
@@ -326,12 +556,101 @@ impl TemplateRegistry {
         statements: &[Spanned<Statement>],
     ) -> Result<(), TemplateError> {
         for stmt in statements {
-            if let Statement::TemplateDecl(decl) = &stmt.node {
-                self.register(decl)?;
+            match &stmt.node {
+                Statement::TemplateDecl(decl) => self.register(decl)?,
+                Statement::Import(import) => {
+                    self.import(&import.path.node, import.alias.node.as_str(), Path::new(""))?
+                }
+                _ => {}
             }
         }
         Ok(())
     }
+
+    /// Register the templates of a library file as `alias.name`
+    ///
+    /// `dir` is the importing file's directory relative to the base path; the
+    /// library's own imports and template files resolve relative to the library.
+    fn import(&mut self, path: &str, alias: &str, dir: &Path) -> Result<(), TemplateError> {
+        let relative = dir.join(path);
+        let full_path = normalize_path(&self.resolve_path(relative.to_str().unwrap_or("")));
+        if self.import_chain.contains(&full_path) {
+            let chain: Vec<String> = self
+                .import_chain
+                .iter()
+                .chain([&full_path])
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(TemplateError::CircularReference {
+                chain: chain.join(" -> "),
+            });
+        }
+
+        let content =
+            std::fs::read_to_string(&full_path).map_err(|e| TemplateError::FileReadError {
+                path: full_path.clone(),
+                message: e.to_string(),
+            })?;
+        let mut library =
+            crate::parser::parse(&content).map_err(|errors| TemplateError::FileReadError {
+                path: full_path.clone(),
+                message: format!("Parse errors: {:?}", errors),
+            })?;
+        library.statements = super::expand::expand_statements(library.statements)?;
+        let library_dir = relative.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        // Names the library can use unqualified: its own templates and import aliases
+        let local: HashSet<String> = library
+            .statements
+            .iter()
+            .filter_map(|stmt| match &stmt.node {
+                Statement::TemplateDecl(decl) => Some(decl.name.node.0.clone()),
+                Statement::Import(import) => Some(import.alias.node.0.clone()),
+                _ => None,
+            })
+            .collect();
+
+        self.import_chain.push(full_path);
+        let result = library.statements.iter().try_for_each(|stmt| {
+            match &stmt.node {
+                Statement::TemplateDecl(decl) => {
+                    let mut def = TemplateDefinition::from_decl(decl);
+                    def.name = format!("{}.{}", alias, def.name);
+                    def.source_path = def.source_path.map(|p| library_dir.join(p));
+                    if let Some(body) = &mut def.body {
+                        qualify_instances(body, alias, &local);
+                    }
+                    self.register_definition(def)?;
+                }
+                Statement::Import(import) => {
+                    let nested = format!("{}.{}", alias, import.alias.node);
+                    self.import(&import.path.node, &nested, &library_dir)?;
+                }
+                _ => {}
+            }
+            Ok(())
+        });
+        self.import_chain.pop();
+        result
+    }
+}
+
+/// Prefix instances of library-local templates with the import alias
+fn qualify_instances(stmts: &mut [Spanned<Statement>], alias: &str, local: &HashSet<String>) {
+    for stmt in stmts {
+        match &mut stmt.node {
+            Statement::TemplateInstance(inst) => {
+                let name = &mut inst.template_name.node.0;
+                let head = name.split('.').next().unwrap_or_default();
+                if local.contains(head) {
+                    *name = format!("{}.{}", alias, name);
+                }
+            }
+            Statement::Layout(l) => qualify_instances(&mut l.children, alias, local),
+            Statement::Group(g) => qualify_instances(&mut g.children, alias, local),
+            _ => {}
+        }
+    }
 }
 
 /// Determine MIME type from a file extension
@@ -347,6 +666,39 @@ fn mime_from_extension(path: &str) -> &'static str {
     }
 }
 
+/// Read the pixel size from a PNG, GIF, JPEG or BMP header
+fn raster_dimensions(bytes: &[u8]) -> Option<(f64, f64)> {
+    let be16 = |i: usize| Some(u16::from_be_bytes([*bytes.get(i)?, *bytes.get(i + 1)?]) as f64);
+    let le16 = |i: usize| Some(u16::from_le_bytes([*bytes.get(i)?, *bytes.get(i + 1)?]) as f64);
+    let be32 = |i: usize| Some(u32::from_be_bytes(bytes.get(i..i + 4)?.try_into().ok()?) as f64);
+    let le32 = |i: usize| Some(i32::from_le_bytes(bytes.get(i..i + 4)?.try_into().ok()?) as f64);
+
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        // IHDR is always the first chunk
+        return Some((be32(16)?, be32(20)?));
+    }
+    if bytes.starts_with(b"GIF8") {
+        return Some((le16(6)?, le16(8)?));
+    }
+    if bytes.starts_with(b"BM") {
+        // Height is negative for top-down bitmaps
+        return Some((le32(18)?, le32(22)?.abs()));
+    }
+    if bytes.starts_with(&[0xFF, 0xD8]) {
+        // Walk the JPEG segments up to the first start-of-frame marker
+        let mut i = 2;
+        while *bytes.get(i)? == 0xFF {
+            let marker = *bytes.get(i + 1)?;
+            let is_sof = matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+            if is_sof {
+                return Some((be16(i + 7)?, be16(i + 5)?));
+            }
+            i += 2 + be16(i + 2)? as usize;
+        }
+    }
+    None
+}
+
 /// Normalize a path by resolving `.` and `..` components without touching the filesystem.
 fn normalize_path(path: &std::path::Path) -> PathBuf {
     use std::path::Component;
@@ -426,6 +778,23 @@ mod tests {
         Spanned::new(node, make_span())
     }
 
+    #[test]
+    fn test_raster_dimensions() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend_from_slice(&[0, 0, 1, 0, 0, 0, 0, 64]);
+        assert_eq!(raster_dimensions(&png), Some((256.0, 64.0)));
+
+        let gif = b"GIF89a\x20\x00\x10\x00";
+        assert_eq!(raster_dimensions(gif), Some((32.0, 16.0)));
+
+        // APP0 segment, then a baseline SOF0 for 640x480
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00];
+        jpeg.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x11, 0x08, 0x01, 0xE0, 0x02, 0x80]);
+        assert_eq!(raster_dimensions(&jpeg), Some((640.0, 480.0)));
+
+        assert_eq!(raster_dimensions(b"not an image"), None);
+    }
+
     #[test]
     fn test_registry_register_and_get() {
         let mut registry = TemplateRegistry::new();
@@ -1,13 +1,18 @@
 //! Template resolution - expands template instances into concrete elements
 
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
 use crate::parser::ast::{
     AnchorDecl, AnchorPosition, ConstrainDecl, ConstraintExpr, Document, ElementPath, GroupDecl,
-    Identifier, PropertyRef, ShapeDecl, ShapeType, Spanned, Statement, StyleKey, StyleModifier,
-    StyleValue, TemplateInstance,
+    Identifier, LinearTerm, NumExpr, ParameterDef, PropertyRef, ShapeDecl, ShapeType, Spanned,
+    Statement, StyleKey, StyleModifier, StyleValue, TemplateInstance, Unit,
 };
+use crate::parser::value_text;
+use crate::scope::declared_name;
 
+use super::expand::{expand_statements, expand_template_body};
+use super::members::resolve_member_paths;
+use super::recolor::recolor_svg;
 use super::registry::{TemplateError, TemplateRegistry};
 
 /// Context for template resolution
@@ -17,8 +22,8 @@ pub struct ResolutionContext {
     pub parameters: HashMap<String, StyleValue>,
     /// Instance name prefix for nested templates
     pub name_prefix: String,
-    /// Set of templates currently being resolved (for cycle detection)
-    pub resolving: HashSet<String>,
+    /// Templates currently being resolved, outermost first (for the depth limit)
+    pub resolving: Vec<String>,
 }
 
 impl Default for ResolutionContext {
@@ -33,7 +38,7 @@ impl ResolutionContext {
         Self {
             parameters: HashMap::new(),
             name_prefix: String::new(),
-            resolving: HashSet::new(),
+            resolving: vec![],
         }
     }
 
@@ -42,7 +47,7 @@ impl ResolutionContext {
         Self {
             parameters,
             name_prefix: String::new(),
-            resolving: HashSet::new(),
+            resolving: vec![],
         }
     }
 
@@ -75,32 +80,55 @@ impl ResolutionContext {
         }
     }
 
-    /// Check if a template is currently being resolved (cycle detection)
+    /// Check if a template is currently being resolved
     pub fn is_resolving(&self, name: &str) -> bool {
-        self.resolving.contains(name)
+        self.resolving.iter().any(|n| n == name)
+    }
+
+    /// How many template instances enclose the one being resolved
+    pub fn depth(&self) -> usize {
+        self.resolving.len()
     }
 
     /// Mark a template as being resolved
     pub fn start_resolving(&mut self, name: &str) {
-        self.resolving.insert(name.to_string());
+        self.resolving.push(name.to_string());
     }
 
     /// Mark a template as done resolving
     pub fn done_resolving(&mut self, name: &str) {
-        self.resolving.remove(name);
+        if let Some(i) = self.resolving.iter().rposition(|n| n == name) {
+            self.resolving.remove(i);
+        }
     }
 }
 
+/// Unroll `repeat` blocks and inline `let` variables without expanding
+/// template instances, rejecting `if` and `slot` outside template bodies
+pub fn expand_document(doc: Document) -> Result<Document, TemplateError> {
+    Ok(Document {
+        statements: select_branches(expand_statements(doc.statements)?, None)?,
+        comments: doc.comments,
+    })
+}
+
 /// Resolve all template instances in a document
 ///
 /// This function:
-/// 1. Collects all template declarations into a registry
-/// 2. Expands template instances into their concrete shapes
-/// 3. Returns a new document with all templates resolved
+/// 1. Unrolls `repeat` blocks and inlines `let` variables (and rejects `if`
+///    outside template bodies)
+/// 2. Collects all template declarations into a registry
+/// 3. Expands template instances into their concrete shapes
+/// 4. Evaluates expressions that depended on template parameters
+/// 5. Points dotted references into instances (`motor1.terminal_a`) at the
+///    expanded elements
+/// 6. Returns a new document with all templates resolved
 pub fn resolve_templates(
     doc: Document,
     registry: &mut TemplateRegistry,
 ) -> Result<Document, TemplateError> {
+    let doc = expand_document(doc)?;
+
     // First pass: collect template declarations
     registry.collect_from_statements(&doc.statements)?;
 
@@ -110,8 +138,8 @@ pub fn resolve_templates(
 
     for stmt in doc.statements {
         match &stmt.node {
-            Statement::TemplateDecl(_) => {
-                // Template declarations are consumed by the registry, not rendered
+            Statement::TemplateDecl(_) | Statement::Import(_) => {
+                // Template declarations and imports are consumed by the registry, not rendered
                 continue;
             }
             Statement::TemplateInstance(inst) => {
@@ -127,9 +155,13 @@ pub fn resolve_templates(
         }
     }
 
-    Ok(Document {
-        statements: resolved_statements,
-    })
+    // Anything still unevaluated refers to a variable that is not defined
+    let mut doc = Document {
+        statements: expand_statements(resolved_statements)?,
+        comments: doc.comments,
+    };
+    resolve_member_paths(&mut doc, registry)?;
+    Ok(doc)
 }
 
 /// Resolve a single template instance into statements
@@ -142,21 +174,46 @@ fn resolve_instance(
     let template_name = inst.template_name.node.as_str();
     let instance_name = inst.instance_name.node.as_str();
 
-    // Check for circular references
-    if ctx.is_resolving(template_name) {
-        return Err(TemplateError::CircularReference {
-            chain: format!(
-                "{} -> {}",
-                ctx.resolving
-                    .iter()
-                    .cloned()
-                    .collect::<Vec<_>>()
-                    .join(" -> "),
-                template_name
-            ),
+    // Templates may instantiate themselves, as long as a base case (an `if`
+    // branch without the instance) ends the recursion in time
+    if ctx.depth() >= registry.max_depth() {
+        return Err(TemplateError::RecursionLimit {
+            chain: ctx
+                .resolving
+                .iter()
+                .map(String::as_str)
+                .chain(std::iter::once(template_name))
+                .collect::<Vec<_>>()
+                .join(" -> "),
+            limit: registry.max_depth(),
         });
     }
 
+    // Not a template: an application-defined shape, if one is registered
+    if registry.get(template_name).is_none() {
+        if let Some(renderer) = registry.shape_renderer(template_name).cloned() {
+            let modifiers = arguments_to_modifiers(&inst.arguments, &[], span);
+            let (width, height) =
+                renderer.intrinsic_size(&crate::layout::ResolvedStyles::from_modifiers(&modifiers));
+            let shape = ShapeDecl {
+                shape_type: Spanned::new(
+                    ShapeType::Custom {
+                        kind: template_name.to_string(),
+                        intrinsic_width: width,
+                        intrinsic_height: height,
+                    },
+                    inst.template_name.span.clone(),
+                ),
+                name: Some(inst.instance_name.clone()),
+                modifiers,
+                ports: vec![],
+            };
+            let mut expanded = vec![Spanned::new(Statement::Shape(shape), span.clone())];
+            fill_slot(&mut expanded, inst.children.clone(), template_name)?;
+            return Ok(expanded);
+        }
+    }
+
     // Get the template definition
     let def = registry
         .get(template_name)
@@ -164,24 +221,38 @@ fn resolve_instance(
             name: template_name.to_string(),
         })?
         .clone(); // Clone to avoid borrow issues
+    registry.record_instance(instance_name, template_name, span.clone());
 
     // Build parameter values from arguments and defaults
     let mut param_values: HashMap<String, StyleValue> = HashMap::new();
 
     // Start with defaults
     for param in &def.parameters {
+        check_parameter_type(param, &param.default_value)?;
         param_values.insert(param.name.node.0.clone(), param.default_value.node.clone());
     }
 
     // Override with provided arguments
     for (name, value) in &inst.arguments {
         let param_name = name.node.0.clone();
-        if def.has_parameter(&param_name) {
+        if let Some(param) = def.parameters.iter().find(|p| p.name.node.0 == param_name) {
+            check_parameter_type(param, value)?;
             param_values.insert(param_name, value.node.clone());
         }
         // Note: Extra arguments are silently ignored (could warn in future)
     }
 
+    // Children belong to the caller, so they are resolved (and named) in its context
+    let mut children = Vec::new();
+    for child in &inst.children {
+        match &child.node {
+            Statement::TemplateInstance(nested) => {
+                children.extend(resolve_instance(nested, &child.span, registry, ctx)?);
+            }
+            _ => children.push(resolve_statement(child.clone(), registry, ctx)?),
+        }
+    }
+
     ctx.start_resolving(template_name);
 
     // Convert instance arguments to modifiers (excluding template parameters)
@@ -212,7 +283,129 @@ fn resolve_instance(
     };
 
     ctx.done_resolving(template_name);
-    result
+    let mut expanded = result?;
+    apply_overrides(&mut expanded, instance_name, &inst.arguments, span)?;
+    fill_slot(&mut expanded, children, template_name)?;
+    Ok(expanded)
+}
+
+/// Reject a value that does not match the parameter's declared type
+fn check_parameter_type(
+    param: &ParameterDef,
+    value: &Spanned<StyleValue>,
+) -> Result<(), TemplateError> {
+    match &param.param_type {
+        Some(t) if !t.node.accepts(&value.node) => Err(TemplateError::InvalidParameterType {
+            param: param.name.node.0.clone(),
+            expected: t.node.name().to_string(),
+            found: value_text(&value.node),
+            span: value.span.clone(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Apply the dotted arguments of an instance (`body.fill: red`) to the
+/// children of the expanded template they name
+fn apply_overrides(
+    stmts: &mut [Spanned<Statement>],
+    instance_name: &str,
+    arguments: &[(Spanned<Identifier>, Spanned<StyleValue>)],
+    span: &std::ops::Range<usize>,
+) -> Result<(), TemplateError> {
+    fn modifiers_of<'a>(
+        stmts: &'a mut [Spanned<Statement>],
+        id: &str,
+    ) -> Option<&'a mut Vec<Spanned<StyleModifier>>> {
+        for stmt in stmts {
+            if declared_name(&stmt.node).is_some_and(|name| name.node.0 == id) {
+                return match &mut stmt.node {
+                    Statement::Shape(s) => Some(&mut s.modifiers),
+                    Statement::Group(g) => Some(&mut g.modifiers),
+                    Statement::Layout(l) => Some(&mut l.modifiers),
+                    Statement::Embed(e) => Some(&mut e.modifiers),
+                    _ => None,
+                };
+            }
+            let found = match &mut stmt.node {
+                Statement::Group(g) => modifiers_of(&mut g.children, id),
+                Statement::Layout(l) => modifiers_of(&mut l.children, id),
+                _ => None,
+            };
+            if found.is_some() {
+                return found;
+            }
+        }
+        None
+    }
+
+    for (name, value) in arguments {
+        let Some((path, key)) = name.node.0.rsplit_once('.') else {
+            continue;
+        };
+        let id = format!("{}_{}", instance_name, path.replace('.', "_"));
+        let modifiers = modifiers_of(stmts, &id).ok_or_else(|| TemplateError::MemberNotFound {
+            path: format!("{}.{}", instance_name, path),
+            span: name.span.clone(),
+        })?;
+        let key = style_key(key);
+        modifiers.retain(|m| m.node.key.node != key);
+        modifiers.push(Spanned::new(
+            StyleModifier {
+                key: Spanned::new(key, name.span.clone()),
+                value: value.clone(),
+            },
+            span.clone(),
+        ));
+    }
+    Ok(())
+}
+
+/// Replace the `slot` in an expanded template with the instance's children
+fn fill_slot(
+    stmts: &mut Vec<Spanned<Statement>>,
+    children: Vec<Spanned<Statement>>,
+    template: &str,
+) -> Result<(), TemplateError> {
+    fn replace(
+        stmts: &mut Vec<Spanned<Statement>>,
+        children: &mut Option<Vec<Spanned<Statement>>>,
+        template: &str,
+    ) -> Result<(), TemplateError> {
+        let mut filled = Vec::with_capacity(stmts.len());
+        for mut stmt in stmts.drain(..) {
+            match &mut stmt.node {
+                Statement::Slot => {
+                    let Some(children) = children.take() else {
+                        return Err(TemplateError::InvalidSlot {
+                            message: format!("template {} has more than one slot", template),
+                        });
+                    };
+                    filled.extend(children);
+                    continue;
+                }
+                Statement::Layout(layout) => replace(&mut layout.children, children, template)?,
+                Statement::Group(group) => replace(&mut group.children, children, template)?,
+                Statement::Note(note) => replace(&mut note.children, children, template)?,
+                _ => {}
+            }
+            filled.push(stmt);
+        }
+        *stmts = filled;
+        Ok(())
+    }
+
+    let mut children = Some(children);
+    replace(stmts, &mut children, template)?;
+    match children {
+        Some(children) if !children.is_empty() => Err(TemplateError::InvalidSlot {
+            message: format!(
+                "template {} has no slot for the instance's children",
+                template
+            ),
+        }),
+        _ => Ok(()),
+    }
 }
 
 /// Resolve an SVG file template into an SvgEmbed shape
@@ -235,9 +428,15 @@ fn resolve_svg_template(
             name: def.name.clone(),
         })?;
 
-    let content = def.svg_content.clone().unwrap_or_default();
+    let mut content = def.svg_content.clone().unwrap_or_default();
     let (width, height) = def.svg_dimensions.unwrap_or((100.0, 100.0));
 
+    // `fill` and `stroke` arguments repaint the file's own colors
+    let styles = crate::layout::ResolvedStyles::from_modifiers(instance_modifiers);
+    if styles.fill.is_some() || styles.stroke.is_some() {
+        content = recolor_svg(&content, styles.fill.as_deref(), styles.stroke.as_deref());
+    }
+
     let shape = ShapeDecl {
         shape_type: Spanned::new(
             ShapeType::SvgEmbed {
@@ -249,6 +448,7 @@ fn resolve_svg_template(
         ),
         name: Some(Spanned::new(Identifier::new(instance_name), span.clone())),
         modifiers: instance_modifiers.to_vec(),
+        ports: vec![],
     };
 
     Ok(vec![Spanned::new(Statement::Shape(shape), span.clone())])
@@ -270,13 +470,13 @@ fn resolve_raster_template(
             path: std::path::PathBuf::from(&def.name),
         })?;
 
-    // Resolve image href according to the configured mode
-    let href = registry.resolve_image_href(source_path.to_str().unwrap_or(""));
+    let shape_type = registry.load_raster_image(source_path.to_str().unwrap_or(""));
 
     let shape = ShapeDecl {
-        shape_type: Spanned::new(ShapeType::RasterImage { path: href }, span.clone()),
+        shape_type: Spanned::new(shape_type, span.clone()),
         name: Some(Spanned::new(Identifier::new(instance_name), span.clone())),
         modifiers: instance_modifiers.to_vec(),
+        ports: vec![],
     };
 
     Ok(vec![Spanned::new(Statement::Shape(shape), span.clone())])
@@ -310,11 +510,15 @@ fn resolve_ail_template(
         })?;
 
     // Parse the AIL content
-    let parsed_doc =
+    let mut parsed_doc =
         crate::parser::parse(&content).map_err(|errors| TemplateError::FileReadError {
             path: full_path.clone(),
             message: format!("Parse errors: {:?}", errors),
         })?;
+    parsed_doc.statements = select_branches(
+        expand_template_body(parsed_doc.statements)?,
+        Some((&def.name, param_values)),
+    )?;
 
     // Collect any nested template declarations from the AIL file
     registry.collect_from_statements(&parsed_doc.statements)?;
@@ -326,8 +530,8 @@ fn resolve_ail_template(
 
     for stmt in parsed_doc.statements {
         match &stmt.node {
-            Statement::TemplateDecl(_) => {
-                // Template declarations are consumed by the registry, not expanded
+            Statement::TemplateDecl(_) | Statement::Import(_) => {
+                // Template declarations and imports are consumed by the registry, not expanded
                 continue;
             }
             Statement::Export(_) => {
@@ -336,8 +540,10 @@ fn resolve_ail_template(
             }
             Statement::TemplateInstance(nested_inst) => {
                 // Recursively expand nested template instances
+                let nested_inst =
+                    substitute_instance(nested_inst.clone(), param_values, instance_name);
                 let nested_expanded =
-                    resolve_instance(nested_inst, &stmt.span, registry, &mut nested_ctx)?;
+                    resolve_instance(&nested_inst, &stmt.span, registry, &mut nested_ctx)?;
                 expanded.extend(nested_expanded);
             }
             _ => {
@@ -370,7 +576,7 @@ fn resolve_inline_template(
     param_values: &HashMap<String, StyleValue>,
 ) -> Result<Vec<Spanned<Statement>>, TemplateError> {
     let body = match &def.body {
-        Some(b) => b.clone(),
+        Some(b) => select_branches(b.clone(), Some((&def.name, param_values)))?,
         None => return Ok(vec![]),
     };
 
@@ -388,8 +594,10 @@ fn resolve_inline_template(
             }
             Statement::TemplateInstance(nested_inst) => {
                 // Recursively expand nested template instances
+                let nested_inst =
+                    substitute_instance(nested_inst.clone(), param_values, instance_name);
                 let nested_expanded =
-                    resolve_instance(nested_inst, &stmt.span, registry, &mut nested_ctx)?;
+                    resolve_instance(&nested_inst, &stmt.span, registry, &mut nested_ctx)?;
                 expanded.extend(nested_expanded);
             }
             _ => {
@@ -427,6 +635,7 @@ fn resolve_inline_template(
         modifiers: vec![],
         anchors: prefixed_anchors,
         is_template_instance: true,
+        layer: None,
     };
     Ok(vec![Spanned::new(Statement::Group(group), span.clone())])
 }
@@ -488,11 +697,120 @@ fn resolve_statement(
                 stmt.span,
             ))
         }
+        Statement::Note(mut note) => {
+            let mut resolved_children = Vec::new();
+            for child in note.children {
+                match &child.node {
+                    Statement::TemplateInstance(inst) => {
+                        let expanded = resolve_instance(inst, &child.span, registry, ctx)?;
+                        resolved_children.extend(expanded);
+                    }
+                    _ => {
+                        let resolved = resolve_statement(child, registry, ctx)?;
+                        resolved_children.push(resolved);
+                    }
+                }
+            }
+            note.children = resolved_children;
+            Ok(Spanned::new(Statement::Note(note), stmt.span))
+        }
+        Statement::Embed(embed) => super::embed::resolve_embed(&embed, &stmt.span, registry),
+        Statement::Shape(mut shape) => {
+            match &mut shape.shape_type.node {
+                // `image "logo.png"`: resolve the href and read the image size
+                ShapeType::RasterImage { path, .. } => {
+                    let file = registry.resolve_path(path);
+                    if !file.is_file() {
+                        return Err(TemplateError::ImageNotFound {
+                            path: file,
+                            span: shape.shape_type.span,
+                        });
+                    }
+                    shape.shape_type.node = registry.load_raster_image(path);
+                }
+                ShapeType::Icon { icon_name, svg } => *svg = registry.resolve_icon(icon_name),
+                _ => {}
+            }
+            Ok(Spanned::new(Statement::Shape(shape), stmt.span))
+        }
         // Other statements pass through unchanged
         _ => Ok(stmt),
     }
 }
 
+/// Replace each `if` by the branch its condition selects
+///
+/// `template` is the template being instantiated and its parameter values;
+/// `None` outside template bodies, where conditionals and slots are an error.
+fn select_branches(
+    stmts: Vec<Spanned<Statement>>,
+    template: Option<(&str, &HashMap<String, StyleValue>)>,
+) -> Result<Vec<Spanned<Statement>>, TemplateError> {
+    let mut selected = Vec::with_capacity(stmts.len());
+    for mut stmt in stmts {
+        match &mut stmt.node {
+            Statement::If(decl) => {
+                let Some((name, params)) = template else {
+                    return Err(TemplateError::InvalidCondition {
+                        message: format!(
+                            "'if {}' is only allowed inside template bodies",
+                            decl.condition.node
+                        ),
+                    });
+                };
+                let condition = decl.condition.node.as_str();
+                let value =
+                    params
+                        .get(condition)
+                        .ok_or_else(|| TemplateError::InvalidCondition {
+                            message: format!(
+                                "'{}' is not a parameter of template {}",
+                                condition, name
+                            ),
+                        })?;
+                let branch = if is_truthy(value) {
+                    std::mem::take(&mut decl.then_body)
+                } else {
+                    std::mem::take(&mut decl.else_body)
+                };
+                selected.extend(select_branches(branch, template)?);
+                continue;
+            }
+            Statement::Slot if template.is_none() => {
+                return Err(TemplateError::InvalidSlot {
+                    message: "'slot' is only allowed inside template bodies".to_string(),
+                });
+            }
+            Statement::TemplateInstance(inst) => {
+                inst.children = select_branches(std::mem::take(&mut inst.children), template)?;
+            }
+            Statement::Layout(layout) => {
+                layout.children = select_branches(std::mem::take(&mut layout.children), template)?;
+            }
+            Statement::Group(group) => {
+                group.children = select_branches(std::mem::take(&mut group.children), template)?;
+            }
+            Statement::Note(note) => {
+                note.children = select_branches(std::mem::take(&mut note.children), template)?;
+            }
+            _ => {}
+        }
+        selected.push(stmt);
+    }
+    Ok(selected)
+}
+
+/// `false`, `no`, `none`, `0` and the empty string are false; anything else is true
+fn is_truthy(value: &StyleValue) -> bool {
+    match value {
+        StyleValue::Number { value, .. } => *value != 0.0,
+        StyleValue::String(s) => !matches!(s.as_str(), "" | "false" | "no"),
+        StyleValue::Identifier(id) => !matches!(id.as_str(), "false" | "no" | "none"),
+        StyleValue::Keyword(k) => !matches!(k.as_str(), "false" | "no" | "none"),
+        _ => true,
+    }
+}
+
 /// Substitute parameter references in a statement
 fn substitute_parameters(
     stmt: Spanned<Statement>,
@@ -559,15 +877,44 @@ fn substitute_parameters(
             // Prefix all element references in the constraint expression
             let new_expr = prefix_constraint_expr(&decl.expr, prefix);
             Spanned::new(
-                Statement::Constrain(ConstrainDecl { expr: new_expr }),
+                Statement::Constrain(ConstrainDecl {
+                    expr: new_expr,
+                    strength: decl.strength,
+                }),
                 stmt.span,
             )
         }
+        Statement::TemplateInstance(inst) => Spanned::new(
+            Statement::TemplateInstance(substitute_instance(inst, params, prefix)),
+            stmt.span,
+        ),
         // Other statements pass through
         _ => stmt,
     }
 }
 
+/// A template instance in another template's body: named like the other
+/// children (so `outer.inner.x` can reach into it), with the enclosing
+/// template's parameters substituted into its arguments and children
+fn substitute_instance(
+    mut inst: TemplateInstance,
+    params: &HashMap<String, StyleValue>,
+    prefix: &str,
+) -> TemplateInstance {
+    inst.instance_name = prefix_identifier(&inst.instance_name, prefix);
+    inst.arguments = inst
+        .arguments
+        .into_iter()
+        .map(|(name, value)| (name, substitute_value(&value, params)))
+        .collect();
+    inst.children = inst
+        .children
+        .into_iter()
+        .map(|c| substitute_parameters(c, params, prefix))
+        .collect();
+    inst
+}
+
 /// Prefix all element references in a constraint expression
 fn prefix_constraint_expr(expr: &ConstraintExpr, prefix: &str) -> ConstraintExpr {
     match expr {
@@ -596,6 +943,21 @@ fn prefix_constraint_expr(expr: &ConstraintExpr, prefix: &str) -> ConstraintExpr
             left: prefix_property_ref(left, prefix),
             value: *value,
         },
+        ConstraintExpr::Linear {
+            left,
+            terms,
+            constant,
+        } => ConstraintExpr::Linear {
+            left: prefix_property_ref(left, prefix),
+            terms: terms
+                .iter()
+                .map(|t| LinearTerm {
+                    coefficient: t.coefficient,
+                    property: prefix_property_ref(&t.property, prefix),
+                })
+                .collect(),
+            constant: *constant,
+        },
         ConstraintExpr::Midpoint {
             target,
             a,
@@ -619,6 +981,27 @@ fn prefix_constraint_expr(expr: &ConstraintExpr, prefix: &str) -> ConstraintExpr
                 .collect(),
             padding: *padding,
         },
+        ConstraintExpr::EqualSize {
+            dimension,
+            elements,
+        } => ConstraintExpr::EqualSize {
+            dimension: *dimension,
+            elements: elements
+                .iter()
+                .map(|e| prefix_identifier(e, prefix))
+                .collect(),
+        },
+        ConstraintExpr::Relative {
+            subject,
+            relation,
+            anchor,
+            gap,
+        } => ConstraintExpr::Relative {
+            subject: prefix_identifier(subject, prefix),
+            relation: *relation,
+            anchor: prefix_identifier(anchor, prefix),
+            gap: *gap,
+        },
     }
 }
 
@@ -664,22 +1047,10 @@ fn substitute_modifiers(
     modifiers
         .iter()
         .map(|m| {
-            let new_value = match &m.node.value.node {
-                StyleValue::Identifier(id) => {
-                    // Check if this identifier is a parameter reference
-                    if let Some(param_value) = params.get(id.as_str()) {
-                        Spanned::new(param_value.clone(), m.node.value.span.clone())
-                    } else {
-                        m.node.value.clone()
-                    }
-                }
-                _ => m.node.value.clone(),
-            };
-
             Spanned::new(
                 StyleModifier {
                     key: m.node.key.clone(),
-                    value: new_value,
+                    value: substitute_value(&m.node.value, params),
                 },
                 m.span.clone(),
             )
@@ -687,6 +1058,40 @@ fn substitute_modifiers(
         .collect()
 }
 
+/// Replace parameter references in a modifier or argument value
+fn substitute_value(
+    value: &Spanned<StyleValue>,
+    params: &HashMap<String, StyleValue>,
+) -> Spanned<StyleValue> {
+    match &value.node {
+        StyleValue::Identifier(id) => {
+            // Check if this identifier is a parameter reference
+            if let Some(param_value) = params.get(id.as_str()) {
+                Spanned::new(param_value.clone(), value.span.clone())
+            } else {
+                value.clone()
+            }
+        }
+        StyleValue::Expr(expr) => {
+            // Numeric parameters can be used in arithmetic: [width: size * 2];
+            // lengths with a unit take part in pixels
+            let expr = expr.substitute(&|name| match params.get(name) {
+                Some(StyleValue::Number { value, unit }) => {
+                    let px = unit.as_deref().and_then(Unit::parse).map_or(1.0, Unit::px);
+                    Some(NumExpr::Number(*value * px))
+                }
+                _ => None,
+            });
+            let result = match expr {
+                NumExpr::Number(value) => StyleValue::Number { value, unit: None },
+                expr => StyleValue::Expr(expr),
+            };
+            Spanned::new(result, value.span.clone())
+        }
+        _ => value.clone(),
+    }
+}
+
 /// Convert template instance arguments to style modifiers
 /// Arguments that match template parameters are filtered out (they're used for substitution)
 /// Remaining arguments are converted to modifiers for the resulting shape
@@ -698,31 +1103,12 @@ fn arguments_to_modifiers(
     arguments
         .iter()
         .filter(|(name, _)| !template_params.contains(&name.node.0))
+        // Dotted arguments override the template's children instead
+        .filter(|(name, _)| !name.node.0.contains('.'))
         .map(|(name, value)| {
-            let key = match name.node.0.as_str() {
-                "fill" => StyleKey::Fill,
-                "stroke" => StyleKey::Stroke,
-                "stroke_width" => StyleKey::StrokeWidth,
-                "opacity" => StyleKey::Opacity,
-                "label" => StyleKey::Label,
-                "font_size" => StyleKey::FontSize,
-                "class" => StyleKey::Class,
-                "gap" => StyleKey::Gap,
-                "size" => StyleKey::Size,
-                "width" => StyleKey::Width,
-                "height" => StyleKey::Height,
-                "routing" => StyleKey::Routing,
-                "role" => StyleKey::Role,
-                "x" => StyleKey::X,
-                "y" => StyleKey::Y,
-                "stroke_dasharray" => StyleKey::StrokeDasharray,
-                "rotation" | "rotate" => StyleKey::Rotation,
-                "z_order" => StyleKey::ZOrder,
-                other => StyleKey::Custom(other.to_string()),
-            };
             Spanned::new(
                 StyleModifier {
-                    key: Spanned::new(key, name.span.clone()),
+                    key: Spanned::new(style_key(&name.node.0), name.span.clone()),
                     value: value.clone(),
                 },
                 span.clone(),
@@ -731,6 +1117,31 @@ fn arguments_to_modifiers(
         .collect()
 }
 
+/// The modifier key an instance argument sets
+fn style_key(name: &str) -> StyleKey {
+    match name {
+        "fill" => StyleKey::Fill,
+        "stroke" => StyleKey::Stroke,
+        "stroke_width" => StyleKey::StrokeWidth,
+        "opacity" => StyleKey::Opacity,
+        "label" => StyleKey::Label,
+        "font_size" => StyleKey::FontSize,
+        "class" => StyleKey::Class,
+        "gap" => StyleKey::Gap,
+        "size" => StyleKey::Size,
+        "width" => StyleKey::Width,
+        "height" => StyleKey::Height,
+        "routing" => StyleKey::Routing,
+        "role" => StyleKey::Role,
+        "x" => StyleKey::X,
+        "y" => StyleKey::Y,
+        "stroke_dasharray" => StyleKey::StrokeDasharray,
+        "rotation" | "rotate" => StyleKey::Rotation,
+        "z_order" | "z" => StyleKey::ZOrder,
+        other => StyleKey::Custom(other.to_string()),
+    }
+}
+
 /// Prefix element references in an anchor declaration (Feature 009)
 fn prefix_anchor_decl(anchor: &AnchorDecl, prefix: &str) -> AnchorDecl {
     let prefixed_position = match &anchor.position {
@@ -923,4 +1334,334 @@ mod tests {
             other => panic!("Expected Group, got {:?}", other),
         }
     }
+
+    #[test]
+    fn test_resolve_conditionals() {
+        let source = r#"
+            template "server" (with_cache: false, tier: 1) {
+                rect host
+                if with_cache {
+                    rect cache
+                } else if tier {
+                    col tiers { if tier { rect tier_box } }
+                } else {
+                    rect placeholder
+                }
+            }
+            server plain [tier: 0]
+            server tiered
+            server cached [with_cache: true]
+        "#;
+
+        let doc = parse(source).expect("Should parse");
+        let mut registry = TemplateRegistry::new();
+        let resolved = resolve_templates(doc, &mut registry).expect("Should resolve");
+        let outline = Document {
+            statements: resolved.statements,
+            comments: vec![],
+        }
+        .outline();
+        assert!(outline.contains("rect plain_placeholder"), "{}", outline);
+        assert!(outline.contains("rect tiered_tier_box"), "{}", outline);
+        assert!(outline.contains("rect cached_cache"), "{}", outline);
+        assert!(!outline.contains("plain_cache"), "{}", outline);
+        assert!(!outline.contains("cached_placeholder"), "{}", outline);
+
+        let unknown = parse(r#"template "t" { if missing { rect a } rect b } t x"#).unwrap();
+        let err = resolve_templates(unknown, &mut TemplateRegistry::new()).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("'missing' is not a parameter of template t"));
+
+        let top_level = parse("if flag { rect a }").unwrap();
+        assert!(resolve_templates(top_level, &mut TemplateRegistry::new()).is_err());
+    }
+
+    #[test]
+    fn test_resolve_parameter_arithmetic() {
+        let source = r#"
+            let gap = 4
+            template "box" (size: 50) {
+                let half = size / 2
+                rect body [width: size * 2 + gap, height: half]
+            }
+            box small [size: 10]
+        "#;
+
+        let doc = parse(source).expect("Should parse");
+        let mut registry = TemplateRegistry::new();
+        let resolved = resolve_templates(doc, &mut registry).expect("Should resolve");
+        let Statement::Shape(s) = &resolved.statements[0].node else {
+            panic!("Expected Shape, got {:?}", resolved.statements[0].node);
+        };
+        let values: Vec<_> = s.modifiers.iter().map(|m| &m.node.value.node).collect();
+        assert_eq!(
+            values,
+            [
+                &StyleValue::Number {
+                    value: 24.0,
+                    unit: None
+                },
+                &StyleValue::Number {
+                    value: 5.0,
+                    unit: None
+                }
+            ]
+        );
+
+        let unknown = parse(r#"template "t" { rect a [width: depth * 2] } t x"#).unwrap();
+        let err = resolve_templates(unknown, &mut TemplateRegistry::new()).unwrap_err();
+        assert!(
+            err.to_string().contains("undefined variable 'depth'"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_resolve_slot_children() {
+        let source = r#"
+            template "card" (title: "Card") {
+                col body {
+                    rect heading [label: title]
+                    slot
+                }
+            }
+            card outer [title: "Outer"] {
+                rect content
+                card inner { circle dot }
+            }
+            card empty
+        "#;
+
+        let doc = parse(source).expect("Should parse");
+        let mut registry = TemplateRegistry::new();
+        let resolved = resolve_templates(doc, &mut registry).expect("Should resolve");
+        let outline = Document {
+            statements: resolved.statements,
+            comments: vec![],
+        }
+        .outline();
+        assert_eq!(
+            outline,
+            "group outer\n  col outer_body\n    rect outer_heading \"Outer\"\n    rect content\n    group inner\n      col inner_body\n        rect inner_heading \"Card\"\n        circle dot\ngroup empty\n  col empty_body\n    rect empty_heading \"Card\"\n"
+        );
+
+        let no_slot = parse(r#"template "t" { rect a rect b } t x { rect c }"#).unwrap();
+        let err = resolve_templates(no_slot, &mut TemplateRegistry::new()).unwrap_err();
+        assert!(
+            err.to_string().contains("template t has no slot"),
+            "{}",
+            err
+        );
+
+        let top_level = parse("slot").unwrap();
+        assert!(resolve_templates(top_level, &mut TemplateRegistry::new()).is_err());
+    }
+
+    #[test]
+    fn test_parameter_type_validation() {
+        let template = r#"template "chip" (pins: number 8, title: string "IC", on: bool true) {
+                rect body [label: title]
+            }
+            "#;
+        let ok = parse(&format!("{}chip u1 [pins: 14, on: false]", template)).unwrap();
+        assert!(resolve_templates(ok, &mut TemplateRegistry::new()).is_ok());
+
+        let source = format!("{}chip u1 [pins: \"eight\"]", template);
+        let doc = parse(&source).unwrap();
+        let err = resolve_templates(doc, &mut TemplateRegistry::new()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value \"eight\" for parameter pins: expected number"
+        );
+        let span = err.span().expect("type errors carry a span").clone();
+        assert_eq!(&source[span], "\"eight\"");
+
+        let bad_default = parse(r#"template "t" (n: number "x") { rect a } t x"#).unwrap();
+        assert!(resolve_templates(bad_default, &mut TemplateRegistry::new()).is_err());
+    }
+    #[test]
+    fn test_resolve_paths_into_instances() {
+        let source = r#"
+            template "motor" {
+                rect body
+                circle terminal
+                anchor body [position: terminal.right]
+            }
+            template "rack" { motor m1  rect base }
+            rack r
+            rect battery
+            r.m1.terminal.left -> battery
+            r.m1.body -> r.base
+            constrain r.m1.terminal.left = battery.right
+        "#;
+        let doc = parse(source).expect("Should parse");
+        let resolved =
+            resolve_templates(doc, &mut TemplateRegistry::new()).expect("Should resolve");
+        let out = crate::parser::unparse(&resolved);
+        assert!(out.contains("group r_m1"), "{}", out);
+        assert!(out.contains("r_m1_terminal.left -> battery"), "{}", out);
+        // The template's anchor wins over its child of the same name
+        assert!(out.contains("r_m1.body -> r_base"), "{}", out);
+        assert!(
+            out.contains("constrain r_m1_terminal.left = battery.right"),
+            "{}",
+            out
+        );
+    }
+
+    #[test]
+    fn test_paths_into_instances_respect_exports() {
+        let resolve =
+            |source: &str| resolve_templates(parse(source).unwrap(), &mut TemplateRegistry::new());
+        let template =
+            r#"template "motor" { rect body  rect shaft  export shaft } motor m rect b "#;
+
+        assert!(resolve(&format!("{}m.shaft -> b", template)).is_ok());
+        assert!(resolve(&format!("{}m.left -> b", template)).is_ok());
+        let err = resolve(&format!("{}m.body -> b", template)).unwrap_err();
+        assert!(
+            matches!(&err, TemplateError::ExportNotFound { export, exports, .. }
+                if export == "body" && exports == &["shaft"]),
+            "{:?}",
+            err
+        );
+
+        let source = format!("{}m.shaft.x.left -> b", template);
+        let err = resolve(&source).unwrap_err();
+        assert!(
+            matches!(err, TemplateError::MemberNotFound { .. }),
+            "{:?}",
+            err
+        );
+        let span = err.span().expect("member errors carry a span").clone();
+        assert_eq!(&source[span], "m.shaft.x");
+    }
+
+    #[test]
+    fn test_unknown_anchor_on_instance_lists_exports() {
+        let source = r#"
+            template "motor" {
+                rect body
+                rect shaft
+                anchor power [position: body.left]
+            }
+            motor m
+            rect b
+            m.rotor -> b
+        "#;
+        let err =
+            resolve_templates(parse(source).unwrap(), &mut TemplateRegistry::new()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "template motor has no anchor or exported element rotor; it exports power, body, shaft"
+        );
+        let TemplateError::ExportNotFound {
+            span,
+            instance_span,
+            ..
+        } = &err
+        else {
+            panic!("{:?}", err);
+        };
+        assert_eq!(&source[span.clone()], "m.rotor");
+        assert_eq!(&source[instance_span.clone()], "motor m");
+
+        // The same goes for paths through the instance
+        let source = source.replace("m.rotor -> b", "constrain m.rotor.left = b.right");
+        let err =
+            resolve_templates(parse(&source).unwrap(), &mut TemplateRegistry::new()).unwrap_err();
+        assert!(
+            matches!(&err, TemplateError::ExportNotFound { export, .. } if export == "rotor"),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_recursive_template_with_base_case() {
+        let source = r#"
+            template "tree" (depth: number 2) {
+                col {
+                    circle node
+                    if depth {
+                        row { tree l [depth: depth - 1]  tree r [depth: depth - 1] }
+                    }
+                }
+            }
+            tree t
+        "#;
+        let resolved = resolve_templates(parse(source).unwrap(), &mut TemplateRegistry::new())
+            .expect("Should resolve");
+        let out = crate::parser::unparse(&resolved);
+        for id in ["t_node", "t_l_node", "t_r_l_node", "t_r_r_node"] {
+            assert!(out.contains(&format!("circle {}", id)), "{}", out);
+        }
+        assert!(!out.contains("t_l_l_l"), "{}", out);
+
+        // Two levels deep needs three nested instances
+        let mut registry = TemplateRegistry::new();
+        registry.set_max_depth(2);
+        let err = resolve_templates(parse(source).unwrap(), &mut registry).unwrap_err();
+        assert!(
+            matches!(&err, TemplateError::RecursionLimit { chain, limit: 2 }
+                if chain == "tree -> tree -> tree"),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_unbounded_recursion_hits_depth_limit() {
+        let source = r#"template "t" { rect a  t inner } t x"#;
+        let err =
+            resolve_templates(parse(source).unwrap(), &mut TemplateRegistry::new()).unwrap_err();
+        assert!(
+            matches!(&err, TemplateError::RecursionLimit { limit, .. }
+                if *limit == crate::template::DEFAULT_MAX_TEMPLATE_DEPTH),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_instance_overrides_children() {
+        let source = r#"
+            template "motor" (color: color gray) {
+                rect body [fill: color, width: 40]
+                rect shaft [width: 8]
+            }
+            template "rack" { motor m1  rect base }
+            motor m [body.fill: red, shaft.width: 12, fill: blue]
+            rack r [m1.body.fill: green]
+        "#;
+        let doc = parse(source).expect("Should parse");
+        let resolved =
+            resolve_templates(doc, &mut TemplateRegistry::new()).expect("Should resolve");
+        let out = crate::parser::unparse(&resolved);
+        assert!(
+            out.contains("rect m_body [width: 40, fill: red]"),
+            "{}",
+            out
+        );
+        assert!(out.contains("rect m_shaft [width: 12]"), "{}", out);
+        assert!(
+            out.contains("rect r_m1_body [width: 40, fill: green]"),
+            "{}",
+            out
+        );
+
+        let err = resolve_templates(
+            parse(r#"template "motor" { rect body  rect shaft } motor m [axle.fill: red]"#)
+                .unwrap(),
+            &mut TemplateRegistry::new(),
+        )
+        .unwrap_err();
+        assert!(
+            matches!(&err, TemplateError::MemberNotFound { path, .. } if path == "m.axle"),
+            "{:?}",
+            err
+        );
+    }
 }
@@ -0,0 +1,495 @@
+//! Sanitization of embedded SVG content
+//!
+//! SVG templates may come from untrusted template libraries. Before their markup
+//! is inlined into the output, scripts, event handlers, and (depending on the
+//! policy) references to external resources are removed.
+//!
+//! This is a tag-level filter, not a full XML parser: it walks the markup once,
+//! drops blocked elements together with their content, and rewrites the
+//! attribute list of every remaining tag.
+
+use crate::SvgEmbedPolicy;
+
+/// Elements removed together with everything inside them
+const BLOCKED_ELEMENTS: &[&str] = &[
+    "script",
+    "foreignobject",
+    "iframe",
+    "object",
+    "embed",
+    "handler",
+    "listener",
+];
+
+/// Attributes that reference another resource
+const REFERENCE_ATTRIBUTES: &[&str] = &["href", "xlink:href", "src"];
+
+/// Sanitize SVG markup according to the given policy
+pub fn sanitize_svg(svg: &str, policy: SvgEmbedPolicy) -> String {
+    if policy == SvgEmbedPolicy::Trusted {
+        return svg.to_string();
+    }
+
+    let mut out = String::with_capacity(svg.len());
+    // Name and nesting depth of the blocked element currently being skipped
+    let mut skipping: Option<(String, usize)> = None;
+    let mut in_style = false;
+    let mut rest = svg;
+
+    while let Some(lt) = rest.find('<') {
+        let text = &rest[..lt];
+        if skipping.is_none() {
+            if in_style {
+                out.push_str(&sanitize_css(text, policy));
+            } else {
+                out.push_str(text);
+            }
+        }
+        rest = &rest[lt..];
+
+        // Comments are dropped entirely
+        if rest.starts_with("<!--") {
+            rest = rest.find("-->").map(|i| &rest[i + 3..]).unwrap_or("");
+            continue;
+        }
+
+        // CDATA is only meaningful inside <style>; keep it there, drop it elsewhere
+        if rest.starts_with("<![CDATA[") {
+            let end = rest.find("]]>").map(|i| i + 3).unwrap_or(rest.len());
+            if skipping.is_none() && in_style {
+                let inner = &rest[9..end.saturating_sub(3).max(9)];
+                out.push_str("<![CDATA[");
+                out.push_str(&sanitize_css(inner, policy));
+                out.push_str("]]>");
+            }
+            rest = &rest[end..];
+            continue;
+        }
+
+        // DOCTYPE (may declare entities) is dropped; an internal subset ends with "]>"
+        if rest.starts_with("<!") {
+            let end = match (rest.find('['), rest.find('>')) {
+                (Some(b), Some(g)) if b < g => rest.find("]>").map(|i| i + 2),
+                (_, Some(g)) => Some(g + 1),
+                _ => None,
+            };
+            rest = end.map(|e| &rest[e..]).unwrap_or("");
+            continue;
+        }
+
+        // Processing instructions (e.g. the XML declaration) are kept as-is
+        if rest.starts_with("<?") {
+            let end = rest.find("?>").map(|i| i + 2).unwrap_or(rest.len());
+            if skipping.is_none() {
+                out.push_str(&rest[..end]);
+            }
+            rest = &rest[end..];
+            continue;
+        }
+
+        let end = find_tag_end(rest);
+        let tag = &rest[..end];
+        rest = &rest[end..];
+
+        let closing = tag.starts_with("</");
+        let self_closing = tag.ends_with("/>");
+        let name = tag_name(tag);
+
+        if let Some((skip_name, depth)) = &mut skipping {
+            if name == *skip_name {
+                if closing {
+                    *depth -= 1;
+                } else if !self_closing {
+                    *depth += 1;
+                }
+                if *depth == 0 {
+                    skipping = None;
+                }
+            }
+            continue;
+        }
+
+        // `<s:script xmlns:s="http://www.w3.org/2000/svg">` is still a script
+        let local_name = name.rsplit(':').next().unwrap_or(&name);
+        if BLOCKED_ELEMENTS.contains(&local_name) {
+            if !closing && !self_closing {
+                skipping = Some((name, 1));
+            }
+            continue;
+        }
+
+        if local_name == "style" {
+            in_style = !closing && !self_closing;
+        }
+
+        if closing {
+            out.push_str(tag);
+        } else {
+            out.push_str(&sanitize_tag(tag, &name, self_closing, policy));
+        }
+    }
+
+    if skipping.is_none() {
+        out.push_str(rest);
+    }
+    out
+}
+
+/// Find the end of a tag starting at `<`, honoring quoted attribute values
+pub(super) fn find_tag_end(s: &str) -> usize {
+    let mut quote: Option<char> = None;
+    for (i, c) in s.char_indices().skip(1) {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '>' => return i + 1,
+            None => {}
+        }
+    }
+    s.len()
+}
+
+/// Lowercase element name of an opening or closing tag
+pub(super) fn tag_name(tag: &str) -> String {
+    tag.trim_start_matches('<')
+        .trim_start_matches('/')
+        .chars()
+        .take_while(|c| !c.is_whitespace() && *c != '>' && *c != '/')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// Rebuild an opening tag keeping only safe attributes
+fn sanitize_tag(tag: &str, name: &str, self_closing: bool, policy: SvgEmbedPolicy) -> String {
+    let body = tag
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .trim_end_matches('/');
+    let attrs = &body[body.find(|c: char| c.is_whitespace()).unwrap_or(body.len())..];
+
+    let original_name: String = body.chars().take(name.len()).collect();
+    let mut out = format!("<{}", original_name);
+    for (attr, value, quote) in parse_attributes(attrs) {
+        if is_allowed_attribute(&attr, &value, policy) {
+            match value {
+                Some(v) => {
+                    let v =
+                        if policy == SvgEmbedPolicy::Strict && attr.eq_ignore_ascii_case("style") {
+                            sanitize_css(&v, policy)
+                        } else {
+                            v
+                        };
+                    out.push_str(&format!(
+                        " {}={}{}{}",
+                        attr,
+                        quote,
+                        escape_attribute(&v),
+                        quote
+                    ));
+                }
+                None => out.push_str(&format!(" {}", attr)),
+            }
+        }
+    }
+    out.push_str(if self_closing { "/>" } else { ">" });
+    out
+}
+
+/// Escape an attribute value so it cannot end its quotes early; entity
+/// references already in the value are kept as they are
+fn escape_attribute(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for (i, c) in value.char_indices() {
+        match c {
+            '&' if is_entity_reference(&value[i..]) => out.push('&'),
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Whether `s` starts with `&name;`, `&#123;` or `&#x7b;`
+fn is_entity_reference(s: &str) -> bool {
+    let Some(semi) = s.find(';') else {
+        return false;
+    };
+    let entity = &s[1..semi];
+    let (digits, radix) = match entity.strip_prefix('#') {
+        Some(num) => match num.strip_prefix(['x', 'X']) {
+            Some(hex) => (hex, 16),
+            None => (num, 10),
+        },
+        None => {
+            return !entity.is_empty() && entity.chars().all(|c| c.is_ascii_alphanumeric());
+        }
+    };
+    !digits.is_empty() && digits.chars().all(|c| c.is_digit(radix))
+}
+
+/// Parse `name="value"` pairs; returns (name, value, quote char)
+fn parse_attributes(s: &str) -> Vec<(String, Option<String>, char)> {
+    let mut attrs = vec![];
+    let mut chars = s.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let mut name = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || c == '=' {
+                break;
+            }
+            name.push(c);
+            chars.next();
+        }
+        if name.is_empty() {
+            break;
+        }
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.peek() != Some(&'=') {
+            attrs.push((name, None, '"'));
+            continue;
+        }
+        chars.next();
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let quote = match chars.peek() {
+            Some(&q) if q == '"' || q == '\'' => {
+                chars.next();
+                Some(q)
+            }
+            _ => None,
+        };
+        let mut value = String::new();
+        for c in chars.by_ref() {
+            match quote {
+                Some(q) if c == q => break,
+                None if c.is_whitespace() => break,
+                _ => value.push(c),
+            }
+        }
+        attrs.push((name, Some(value), quote.unwrap_or('"')));
+    }
+    attrs
+}
+
+fn is_allowed_attribute(name: &str, value: &Option<String>, policy: SvgEmbedPolicy) -> bool {
+    let lower = name.to_ascii_lowercase();
+
+    // Event handlers (onclick, onload, ...)
+    if lower.starts_with("on") {
+        return false;
+    }
+
+    let Some(value) = value else {
+        return true;
+    };
+    let normalized: String = decode_entities(value)
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase();
+
+    // Script URLs are never allowed, including via animation values (to/from/values)
+    if normalized.contains("javascript:") || normalized.contains("vbscript:") {
+        return false;
+    }
+
+    if REFERENCE_ATTRIBUTES.contains(&lower.as_str()) {
+        if normalized.starts_with("data:text/html") || normalized.starts_with("data:image/svg") {
+            return false;
+        }
+        if policy == SvgEmbedPolicy::Strict {
+            return is_local_reference(&normalized);
+        }
+    }
+
+    true
+}
+
+/// Decode numeric character references and the few named entities that can be
+/// used to disguise a URL scheme (e.g. `java&#x09;script:`)
+fn decode_entities(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..semi];
+        let decoded = if let Some(hex) = entity.strip_prefix("#x").or(entity.strip_prefix("#X")) {
+            u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+        } else if let Some(dec) = entity.strip_prefix('#') {
+            dec.parse::<u32>().ok().and_then(char::from_u32)
+        } else {
+            match entity.to_ascii_lowercase().as_str() {
+                "colon" => Some(':'),
+                "tab" => Some('\t'),
+                "newline" => Some('\n'),
+                _ => None,
+            }
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Fragment references and inline raster data stay inside the document
+fn is_local_reference(normalized: &str) -> bool {
+    normalized.starts_with('#') || normalized.starts_with("data:image/")
+}
+
+/// Remove `@import` rules and external `url(...)` references from CSS
+fn sanitize_css(css: &str, policy: SvgEmbedPolicy) -> String {
+    if policy != SvgEmbedPolicy::Strict {
+        return css.to_string();
+    }
+
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        let import = lower.find("@import");
+        let url = lower.find("url(");
+        match (import, url) {
+            (Some(i), u) if u.is_none_or(|u| i < u) => {
+                out.push_str(&rest[..i]);
+                let end = rest[i..].find(';').map(|e| i + e + 1).unwrap_or(rest.len());
+                rest = &rest[end..];
+            }
+            (_, Some(u)) => {
+                let end = rest[u..].find(')').map(|e| u + e + 1).unwrap_or(rest.len());
+                let target: String = rest[u + 4..end.saturating_sub(1).max(u + 4)]
+                    .chars()
+                    .filter(|c| !c.is_whitespace() && *c != '"' && *c != '\'')
+                    .collect();
+                out.push_str(&rest[..u]);
+                if target.starts_with('#') {
+                    out.push_str(&rest[u..end]);
+                } else {
+                    out.push_str("none");
+                }
+                rest = &rest[end..];
+            }
+            _ => {
+                out.push_str(rest);
+                break;
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strict(svg: &str) -> String {
+        sanitize_svg(svg, SvgEmbedPolicy::Strict)
+    }
+
+    #[test]
+    fn test_removes_script_elements() {
+        let out = strict(r#"<svg><script>alert(1)</script><rect width="5"/></svg>"#);
+        assert_eq!(out, r#"<svg><rect width="5"/></svg>"#);
+    }
+
+    #[test]
+    fn test_removes_nested_foreign_object() {
+        let out = strict(
+            "<svg><foreignObject><foreignObject><div/></foreignObject></foreignObject><circle r=\"1\"/></svg>",
+        );
+        assert_eq!(out, r#"<svg><circle r="1"/></svg>"#);
+    }
+
+    #[test]
+    fn test_removes_event_handlers() {
+        let out = strict(r#"<svg onload="evil()"><rect ONCLICK='evil()' fill="red"/></svg>"#);
+        assert_eq!(out, r#"<svg><rect fill="red"/></svg>"#);
+    }
+
+    #[test]
+    fn test_removes_javascript_urls() {
+        let out = strict(
+            r#"<a href=" java&#x09;script:x"><set attributeName="href" to="javascript:alert(1)"/></a>"#,
+        );
+        assert!(!out.contains("javascript:"));
+        assert!(out.contains("<set attributeName=\"href\"/>"));
+    }
+
+    #[test]
+    fn test_external_references_depend_on_policy() {
+        let svg = r##"<use href="https://evil.example/x.svg#a"/><use href="#local"/>"##;
+        assert_eq!(strict(svg), r##"<use/><use href="#local"/>"##);
+        assert_eq!(
+            sanitize_svg(svg, SvgEmbedPolicy::AllowExternal),
+            svg.to_string()
+        );
+    }
+
+    #[test]
+    fn test_css_imports_and_urls() {
+        let out = strict(
+            r#"<style>@import "http://x/y.css"; .a { fill: url(http://x/p); } .b { fill: url(#grad); }</style>"#,
+        );
+        assert!(!out.contains("@import"));
+        assert!(!out.contains("http://"));
+        assert!(out.contains("url(#grad)"));
+    }
+
+    #[test]
+    fn test_doctype_and_comments_dropped() {
+        let out = strict(r#"<!DOCTYPE svg [<!ENTITY x "y">]><!-- c --><svg/>"#);
+        assert_eq!(out, "<svg/>");
+    }
+
+    #[test]
+    fn test_removes_prefixed_blocked_elements() {
+        let out = strict(concat!(
+            r#"<svg xmlns:s="http://www.w3.org/2000/svg">"#,
+            "<s:script>alert(1)</s:script>",
+            "<x:foreignObject><div/></x:foreignObject>",
+            r#"<rect width="5"/></svg>"#
+        ));
+        assert_eq!(
+            out,
+            r#"<svg xmlns:s="http://www.w3.org/2000/svg"><rect width="5"/></svg>"#
+        );
+    }
+
+    #[test]
+    fn test_unquoted_values_cannot_add_attributes() {
+        let out = strict(r#"<rect x=a"onclick=alert(2) y=1/>"#);
+        assert_eq!(out, r#"<rect x="a&quot;onclick=alert(2)" y="1"/>"#);
+
+        // Entity references that are already there are kept
+        let out = strict(r#"<text title="a &amp; b &#38; c < d"/>"#);
+        assert_eq!(out, r#"<text title="a &amp; b &#38; c &lt; d"/>"#);
+    }
+
+    #[test]
+    fn test_trusted_policy_is_passthrough() {
+        let svg = r#"<svg><script>ok()</script></svg>"#;
+        assert_eq!(sanitize_svg(svg, SvgEmbedPolicy::Trusted), svg);
+    }
+}
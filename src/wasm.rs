@@ -0,0 +1,165 @@
+//! JavaScript bindings (feature `wasm`)
+//!
+//! Build with `wasm-pack build --target web --features wasm` to run the
+//! renderer in a browser or VS Code webview without the native binary:
+//!
+//! ```js
+//! import init, { render } from "./pkg/agent_illustrator.js";
+//!
+//! await init();
+//! try {
+//!     const svg = render("rect a -> rect b", JSON.stringify({ theme: "dark" }));
+//! } catch (e) {
+//!     // e is an Error with `kind` and `diagnostics` (line, column, span, ...)
+//!     console.log(e.kind, e.message, e.diagnostics);
+//! }
+//! ```
+//!
+//! There is no file system, so `template ... from` and `embed` cannot load
+//! files and icons fall back to labelled boxes.
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::diagnostic::Diagnostic;
+use crate::stylesheet::Stylesheet;
+use crate::{render_with_config, RenderConfig, RenderError};
+
+/// Render options accepted as JSON; every field is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct JsConfig {
+    /// Stylesheet TOML, replacing the default palette
+    pub stylesheet: Option<String>,
+    /// Theme from the stylesheet's `[themes.NAME]`
+    pub theme: Option<String>,
+    /// Extra CSS for the SVG `<style>` block
+    pub css: Option<String>,
+    /// Show container bounds and element IDs
+    pub debug: bool,
+    /// Render a single keyframe (by index or name)
+    pub frame: Option<String>,
+    /// Embed minimal JS for animated playback
+    pub animate: bool,
+    /// Pure CSS animation
+    pub animate_css: bool,
+    /// Emit `data-ai-span` source ranges on elements
+    pub source_spans: bool,
+}
+
+/// What `render` throws: an `Error` with these fields
+#[derive(Debug, Serialize)]
+pub struct JsRenderError {
+    /// `config`, `parse`, `layout` or `template`
+    pub kind: &'static str,
+    pub message: String,
+    /// Located problems; empty for `config` errors
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Render `source` to SVG; `config_json` is a [`JsConfig`] object (or empty)
+#[wasm_bindgen]
+pub fn render(source: &str, config_json: &str) -> Result<String, JsValue> {
+    render_with_json_config(source, config_json).map_err(|e| e.into_js())
+}
+
+/// [`render`] without the JavaScript conversion of the error
+pub fn render_with_json_config(source: &str, config_json: &str) -> Result<String, JsRenderError> {
+    let config = parse_config(config_json)?;
+    render_with_config(source, config).map_err(|e| JsRenderError::from_render_error(&e, source))
+}
+
+fn parse_config(config_json: &str) -> Result<RenderConfig, JsRenderError> {
+    let js: JsConfig = if config_json.trim().is_empty() {
+        JsConfig::default()
+    } else {
+        serde_json::from_str(config_json).map_err(|e| JsRenderError::config(e.to_string()))?
+    };
+    let mut stylesheet = match &js.stylesheet {
+        Some(toml) => {
+            Stylesheet::from_str(toml).map_err(|e| JsRenderError::config(e.to_string()))?
+        }
+        None => Stylesheet::default(),
+    };
+    if let Some(theme) = &js.theme {
+        stylesheet = stylesheet
+            .with_theme(theme)
+            .map_err(|e| JsRenderError::config(e.to_string()))?;
+    }
+    let mut config = RenderConfig::new()
+        .with_stylesheet(stylesheet)
+        .with_debug(js.debug);
+    config.svg = config.svg.with_source_spans(js.source_spans);
+    config.custom_css = js.css;
+    config.frame = js.frame;
+    config.animate = js.animate;
+    config.animate_css = js.animate_css;
+    Ok(config)
+}
+
+impl JsRenderError {
+    fn config(message: String) -> Self {
+        JsRenderError {
+            kind: "config",
+            message,
+            diagnostics: vec![],
+        }
+    }
+
+    fn from_render_error(err: &RenderError, source: &str) -> Self {
+        let kind = match err {
+            RenderError::Parse(_) => "parse",
+            RenderError::Layout(_) => "layout",
+            RenderError::Template(_) => "template",
+        };
+        JsRenderError {
+            kind,
+            message: err.to_string(),
+            diagnostics: Diagnostic::from_render_error(err, source),
+        }
+    }
+
+    /// A JavaScript `Error` carrying `kind` and `diagnostics`
+    fn into_js(self) -> JsValue {
+        let error = js_sys::Error::new(&self.message);
+        let diagnostics = serde_json::to_string(&self.diagnostics)
+            .ok()
+            .and_then(|json| js_sys::JSON::parse(&json).ok())
+            .unwrap_or_else(|| js_sys::Array::new().into());
+        // Setting properties on a fresh Error object cannot fail
+        let _ = js_sys::Reflect::set(&error, &"kind".into(), &self.kind.into());
+        let _ = js_sys::Reflect::set(&error, &"diagnostics".into(), &diagnostics);
+        error.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_with_json_config() {
+        let svg = render_with_json_config("rect a", "").unwrap();
+        assert!(svg.contains("<svg"));
+
+        let svg = render_with_json_config("rect a", r#"{"sourceSpans": true}"#).unwrap();
+        assert!(svg.contains("data-ai-span=\"0..6\""), "{}", svg);
+    }
+
+    #[test]
+    fn test_render_error_shape() {
+        let err = render_with_json_config("rect a\na -> missing", "{}").unwrap_err();
+        assert_eq!(err.kind, "layout");
+        assert_eq!(err.diagnostics.len(), 1);
+        assert_eq!((err.diagnostics[0].line, err.diagnostics[0].column), (2, 6));
+
+        let err = render_with_json_config("rect a [fill red]", "{}").unwrap_err();
+        assert_eq!(err.kind, "parse");
+        let json = serde_json::to_value(&err).unwrap();
+        assert_eq!(json["diagnostics"][0]["code"], "unexpected-token");
+
+        let err = render_with_json_config("rect a", r#"{"colour": "red"}"#).unwrap_err();
+        assert_eq!(err.kind, "config");
+        assert!(err.message.contains("colour"), "{}", err.message);
+    }
+}
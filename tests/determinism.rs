@@ -0,0 +1,72 @@
+//! Determinism tests
+//!
+//! Identical input must give byte-identical SVG: agents diff rendered output
+//! to see what a change did, so any run-to-run variation reads as a change.
+//! Every render below builds fresh hash maps (with fresh random seeds), so
+//! iteration-order dependence shows up as differing renders.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use agent_illustrator::{render_with_config, RenderConfig};
+
+const RUNS: usize = 5;
+
+fn fixtures() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = ["examples", "tests/out"]
+        .iter()
+        .flat_map(|dir| fs::read_dir(dir).expect("fixture directory exists"))
+        .map(|entry| entry.expect("readable entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ail"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn render(path: &Path, config: RenderConfig) -> Option<String> {
+    let source = fs::read_to_string(path).expect("readable fixture");
+    let base = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+    render_with_config(&source, config.with_template_base_path(base)).ok()
+}
+
+fn assert_deterministic(config: impl Fn() -> RenderConfig) {
+    let mut rendered = 0;
+    for path in fixtures() {
+        // Fixtures that do not render are covered by the regression tests
+        let Some(first) = render(&path, config()) else {
+            continue;
+        };
+        for run in 1..RUNS {
+            let again = render(&path, config()).expect("rendered before");
+            if again != first {
+                let line = first
+                    .lines()
+                    .zip(again.lines())
+                    .position(|(a, b)| a != b)
+                    .map_or(0, |i| i + 1);
+                panic!(
+                    "{} rendered differently on run {} (first difference on line {})",
+                    path.display(),
+                    run + 1,
+                    line
+                );
+            }
+        }
+        rendered += 1;
+    }
+    assert!(rendered > 0, "no fixtures rendered");
+}
+
+#[test]
+fn test_render_is_byte_identical_across_runs() {
+    assert_deterministic(RenderConfig::new);
+}
+
+#[test]
+fn test_animated_render_is_byte_identical_across_runs() {
+    assert_deterministic(|| {
+        let mut config = RenderConfig::new();
+        config.animate_css = true;
+        config
+    });
+}
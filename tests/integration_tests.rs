@@ -1609,6 +1609,88 @@ fn test_svg_template_aspect_ratio_preserved() {
     assert!(svg.contains("rect"), "Should contain the embedded rect");
 }
 
+#[test]
+fn test_svg_template_width_keeps_aspect_ratio() {
+    use agent_illustrator::{render_with_config, RenderConfig};
+    use std::io::Write;
+
+    let temp_dir = std::env::temp_dir().join("agent_illustrator_test_svg_width");
+    std::fs::create_dir_all(&temp_dir).expect("Should create temp dir");
+
+    let svg_content = r#"<svg viewBox="0 0 200 100" xmlns="http://www.w3.org/2000/svg">
+        <rect x="0" y="0" width="200" height="100"/>
+    </svg>"#;
+
+    let svg_path = temp_dir.join("banner.svg");
+    let mut file = std::fs::File::create(&svg_path).expect("Should create SVG file");
+    file.write_all(svg_content.as_bytes())
+        .expect("Should write SVG content");
+
+    let input = r#"
+        template "banner" from "banner.svg"
+        banner b1 [width: 100]
+        banner b2 [size: 50]
+    "#;
+
+    let config = RenderConfig::new().with_template_base_path(temp_dir.clone());
+
+    let result = render_with_config(input, config);
+
+    // Cleanup
+    let _ = std::fs::remove_file(&svg_path);
+    let _ = std::fs::remove_dir(&temp_dir);
+
+    let svg = result.expect("SVG template with a width should render");
+    // Both sides scale by the same factor
+    assert!(svg.contains("scale(0.5, 0.5)"), "{}", svg);
+    assert!(svg.contains("scale(0.25, 0.25)"), "{}", svg);
+}
+
+#[test]
+fn test_svg_template_fill_and_stroke_substitution() {
+    use agent_illustrator::{render_with_config, RenderConfig};
+    use std::io::Write;
+
+    let temp_dir = std::env::temp_dir().join("agent_illustrator_test_svg_recolor");
+    std::fs::create_dir_all(&temp_dir).expect("Should create temp dir");
+
+    let svg_content = r##"<svg viewBox="0 0 100 100" xmlns="http://www.w3.org/2000/svg">
+        <circle cx="50" cy="50" r="45" fill="#000000" stroke="black"/>
+        <path d="M10 10 L90 90"/>
+        <rect width="10" height="10" fill="none"/>
+    </svg>"##;
+
+    let svg_path = temp_dir.join("badge.svg");
+    let mut file = std::fs::File::create(&svg_path).expect("Should create SVG file");
+    file.write_all(svg_content.as_bytes())
+        .expect("Should write SVG content");
+
+    let input = r#"
+        template "badge" from "badge.svg"
+        badge plain
+        badge red_badge [fill: red, stroke: blue]
+    "#;
+
+    let config = RenderConfig::new().with_template_base_path(temp_dir.clone());
+
+    let result = render_with_config(input, config);
+
+    // Cleanup
+    let _ = std::fs::remove_file(&svg_path);
+    let _ = std::fs::remove_dir(&temp_dir);
+
+    let svg = result.expect("Recolored SVG template should render");
+    assert!(
+        svg.contains(r##"fill="#000000" stroke="black""##),
+        "{}",
+        svg
+    );
+    assert!(svg.contains(r#"fill="red" stroke="blue""#), "{}", svg);
+    // The unfilled path inherits the fill from the wrapping group
+    assert!(svg.contains(r#"fill="red">"#), "{}", svg);
+    assert!(svg.contains(r#"fill="none""#), "{}", svg);
+}
+
 #[test]
 fn test_svg_template_file_not_found_error() {
     use agent_illustrator::{render_with_config, RenderConfig};
@@ -2089,8 +2171,8 @@ fn test_ail_template_circular_dependency_error() {
     let temp_dir = std::env::temp_dir().join("agent_illustrator_test_ail_circular");
     std::fs::create_dir_all(&temp_dir).expect("Should create temp dir");
 
-    // Create circular dependency via inline template self-reference
-    // Template "recursive" uses itself - this should be caught during resolution
+    // Template "recursive" uses itself without a base case, so it only stops
+    // at the nesting limit
     let input = r#"
         template "recursive" {
             rect box
@@ -2106,15 +2188,71 @@ fn test_ail_template_circular_dependency_error() {
     // Cleanup
     let _ = std::fs::remove_dir(&temp_dir);
 
-    assert!(result.is_err(), "Circular dependency should cause an error");
+    assert!(result.is_err(), "Unbounded recursion should cause an error");
     let err_msg = result.unwrap_err().to_string();
     assert!(
-        err_msg.to_lowercase().contains("circular") || err_msg.contains("Circular"),
-        "Error should mention circular dependency: {}",
+        err_msg.contains("nest more than 16 deep (recursive -> recursive"),
+        "Error should mention the nesting limit: {}",
         err_msg
     );
 }
 
+#[test]
+fn test_template_library_import() {
+    use agent_illustrator::{render_with_config, RenderConfig};
+
+    let temp_dir = std::env::temp_dir().join(format!("ail-import-{}", std::process::id()));
+    std::fs::create_dir_all(temp_dir.join("lib")).expect("Should create temp dir");
+
+    // The library uses its own templates and a nested import unqualified
+    std::fs::write(
+        temp_dir.join("lib/passive.ail"),
+        r#"template "resistor" { rect body [width: 40, height: 10] }"#,
+    )
+    .unwrap();
+    std::fs::write(
+        temp_dir.join("lib/electronics.ail"),
+        r#"
+        import "passive.ail" as passive
+        template "pin" { circle dot [size: 6] }
+        template "divider" {
+            col { passive.resistor top_r pin mid passive.resistor bottom_r }
+        }
+        "#,
+    )
+    .unwrap();
+
+    let input = r#"
+        import "lib/electronics.ail" as elec
+        row {
+            elec.passive.resistor r1
+            elec.divider d1
+        }
+    "#;
+    let config = RenderConfig::new().with_template_base_path(temp_dir.clone());
+    let result = render_with_config(input, config);
+
+    // A library that imports itself is rejected
+    std::fs::write(
+        temp_dir.join("lib/loop.ail"),
+        r#"import "loop.ail" as again"#,
+    )
+    .unwrap();
+    let config = RenderConfig::new().with_template_base_path(temp_dir.clone());
+    let cycle = render_with_config(r#"import "lib/loop.ail" as l"#, config);
+
+    let _ = std::fs::remove_dir_all(&temp_dir);
+
+    let svg = result.expect("Imported templates should render");
+    assert!(svg.contains(r#"id="r1""#), "{}", svg);
+    assert!(svg.contains(r#"id="d1""#), "{}", svg);
+    assert!(svg.contains(r#"id="d1_top_r""#), "{}", svg);
+    assert!(svg.contains(r#"id="d1_mid""#), "{}", svg);
+
+    let err_msg = cycle.unwrap_err().to_string();
+    assert!(err_msg.contains("circular"), "{}", err_msg);
+}
+
 #[test]
 fn test_ail_template_file_not_found_error() {
     use agent_illustrator::{render_with_config, RenderConfig};
@@ -2572,3 +2710,50 @@ fn test_unanchored_connections_in_feedback_loop_pattern() {
         }
     }
 }
+
+#[test]
+fn test_repeat_renders_unrolled_shapes() {
+    let input = r#"
+        template "slot" (n: 0) {
+            repeat 2 as k { rect cell_$k [width: 10, height: 10] }
+        }
+        row items {
+            repeat 5 as i {
+                rect item_$i [label: "Item $i", width: 20 + $i * 10]
+            }
+        }
+        repeat 4 as i { item_$i -> item_4 }
+        slot s1
+    "#;
+    let svg = agent_illustrator::render(input).expect("should render");
+    for i in 0..5 {
+        assert!(
+            svg.contains(&format!("id=\"item_{}\"", i)),
+            "missing item_{}",
+            i
+        );
+        assert!(svg.contains(&format!("Item {}", i)));
+    }
+    assert!(svg.contains("width=\"60\""), "item_4 width should be 60");
+    assert!(svg.contains("s1_cell_1"));
+}
+
+#[test]
+fn test_variables_and_repeats_without_template_resolution() {
+    use agent_illustrator::{render_with_config, RenderConfig};
+
+    let input = r#"
+        let w = 40
+        row {
+            repeat 3 as i { rect item_$i [width: w + $i * 10] }
+        }
+    "#;
+    let config = RenderConfig::new().with_resolve_templates(false);
+    let svg = render_with_config(input, config).expect("should render");
+    assert!(svg.contains(r#"id="item_2""#), "{}", svg);
+    assert!(svg.contains(r#"width="60""#), "{}", svg);
+
+    // Conditionals outside template bodies are still an error, not a panic
+    let config = RenderConfig::new().with_resolve_templates(false);
+    assert!(render_with_config("if x { rect a }", config).is_err());
+}
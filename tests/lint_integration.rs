@@ -1,5 +1,6 @@
 //! Integration tests for the --lint feature
 
+use agent_illustrator::layout::lint::LintSeverity;
 use agent_illustrator::{render_with_config, render_with_lint, RenderConfig};
 
 #[test]
@@ -239,3 +240,19 @@ fn test_shacl_overview_reducible_bends() {
         "Expected reducible-bend warnings for SHACL overview"
     );
 }
+
+#[test]
+fn test_warnings_carry_severity_and_source_span() {
+    let source = "rect a\nrect b [label: \"a very long label indeed\", width: 20, height: 10]\n";
+    let config = RenderConfig::new().with_lint(true);
+    let (_, warnings) = render_with_lint(source, config).expect("Should render");
+
+    let overflow = warnings
+        .iter()
+        .find(|w| w.category.to_string() == "label-overflow")
+        .expect("Expected a label-overflow warning");
+    assert_eq!(overflow.severity(), LintSeverity::Warning);
+    assert_eq!(overflow.element.as_deref(), Some("b"));
+    let span = overflow.span.clone().expect("Expected a source span");
+    assert!(source[span].starts_with("rect b"));
+}
@@ -3,9 +3,8 @@
 //! These tests ensure that existing AIL files without rotation produce
 //! consistent SVG output after the constraint solver refactor.
 //!
-//! Note: Due to HashMap iteration order being non-deterministic for CSS
-//! variables and floating point precision in viewBox calculations, we
-//! compare the structural content excluding the style block.
+//! They compare structural content excluding the style block; run-to-run
+//! stability of the full output is covered by `determinism.rs`.
 
 use std::fs;
 use std::path::Path;
@@ -13,7 +12,7 @@ use std::path::Path;
 use agent_illustrator::{render_with_config, RenderConfig};
 
 /// Normalize an SVG string for comparison by:
-/// 1. Removing the style block (CSS variable order is non-deterministic)
+/// 1. Removing the style block
 /// 2. Normalizing whitespace
 /// 3. Rounding viewBox numbers to 1 decimal place
 #[allow(dead_code)]
@@ -82,9 +81,10 @@ fn normalize_viewbox(line: &str) -> String {
 /// 2. The output is valid SVG (starts with proper SVG tag)
 /// 3. The output contains expected structural elements
 ///
-/// Note: Due to HashMap iteration order being non-deterministic for CSS
-/// variables, we don't compare byte-for-byte but instead verify structure.
+/// Byte-for-byte stability across runs is checked in `determinism.rs`; this
+/// test only verifies structure, so intended output changes don't break it.
 #[test]
+#[allow(clippy::unnecessary_map_or, clippy::expect_fun_call)]
 fn test_svg_regression_all_examples() {
     let examples_dir = Path::new("examples");
 
@@ -159,6 +159,7 @@ fn test_svg_regression_all_examples() {
 /// in regression tests.
 #[test]
 #[ignore]
+#[allow(clippy::unnecessary_map_or, clippy::expect_fun_call)]
 fn generate_baselines() {
     let examples_dir = Path::new("examples");
     let baseline_dir = Path::new("tests/baseline");